@@ -0,0 +1,219 @@
+//! Compares the auto-vectorized leaf-scanning kernels in `float_leaf_slice::fallback`
+//! against a hand-written AVX2 implementation of the same operation, and, with the
+//! `simd_stable` feature, against the explicit-lane kernels in `float_leaf_slice::lanes`.
+//!
+//! The fallback kernels only vectorize for the target features enabled at compile time,
+//! so for a like-for-like comparison on an AVX2 machine run with:
+//!
+//! `RUSTFLAGS="-C target-cpu=native" cargo bench --bench leaf_scan --features test_utils`
+use codspeed_criterion_compat::{
+    criterion_group, criterion_main, BenchmarkId, Criterion, Throughput,
+};
+use kiddo::float_leaf_slice::fallback::{
+    update_nearest_dist_autovec, update_nearest_dists_within_autovec,
+};
+#[cfg(feature = "simd_stable")]
+use kiddo::float_leaf_slice::lanes::{for_each_dist_within_lanes, update_nearest_dist_lanes};
+use kiddo::NearestNeighbour;
+use rand::{Rng, SeedableRng};
+
+const CHUNK_SIZE: usize = 32;
+const CHUNKS_PER_LOOP: usize = 1000;
+
+fn random_chunks<A>(rng: &mut rand_chacha::ChaCha8Rng) -> Vec<[A; CHUNK_SIZE]>
+where
+    rand::distributions::Standard: rand::distributions::Distribution<A>,
+{
+    (0..CHUNKS_PER_LOOP)
+        .map(|_| std::array::from_fn(|_| rng.gen::<A>()))
+        .collect()
+}
+
+fn leaf_scan_nearest_one(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Leaf Scan Nearest 1");
+    group.throughput(Throughput::Elements((CHUNKS_PER_LOOP * CHUNK_SIZE) as u64));
+
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(1);
+    let items: [u32; CHUNK_SIZE] = std::array::from_fn(|i| i as u32);
+
+    let chunks_f64 = random_chunks::<f64>(&mut rng);
+    group.bench_function(BenchmarkId::new("autovec", "f64"), |b| {
+        b.iter(|| {
+            let mut best_dist = f64::INFINITY;
+            let mut best_item = u32::MAX;
+            for chunk in &chunks_f64 {
+                update_nearest_dist_autovec(chunk, &items, &mut best_dist, &mut best_item);
+            }
+            (best_dist, best_item)
+        })
+    });
+
+    #[cfg(feature = "simd_stable")]
+    group.bench_function(BenchmarkId::new("lanes", "f64"), |b| {
+        b.iter(|| {
+            let mut best_dist = f64::INFINITY;
+            let mut best_item = u32::MAX;
+            for chunk in &chunks_f64 {
+                update_nearest_dist_lanes(chunk, &items, &mut best_dist, &mut best_item);
+            }
+            (best_dist, best_item)
+        })
+    });
+
+    #[cfg(target_arch = "x86_64")]
+    if is_x86_feature_detected!("avx2") {
+        group.bench_function(BenchmarkId::new("avx2", "f64"), |b| {
+            b.iter(|| {
+                let mut best_dist = f64::INFINITY;
+                let mut best_item = u32::MAX;
+                for chunk in &chunks_f64 {
+                    unsafe {
+                        avx2::update_nearest_dist_f64(chunk, &items, &mut best_dist, &mut best_item)
+                    };
+                }
+                (best_dist, best_item)
+            })
+        });
+    }
+
+    let chunks_f32 = random_chunks::<f32>(&mut rng);
+    group.bench_function(BenchmarkId::new("autovec", "f32"), |b| {
+        b.iter(|| {
+            let mut best_dist = f32::INFINITY;
+            let mut best_item = u32::MAX;
+            for chunk in &chunks_f32 {
+                update_nearest_dist_autovec(chunk, &items, &mut best_dist, &mut best_item);
+            }
+            (best_dist, best_item)
+        })
+    });
+
+    #[cfg(feature = "simd_stable")]
+    group.bench_function(BenchmarkId::new("lanes", "f32"), |b| {
+        b.iter(|| {
+            let mut best_dist = f32::INFINITY;
+            let mut best_item = u32::MAX;
+            for chunk in &chunks_f32 {
+                update_nearest_dist_lanes(chunk, &items, &mut best_dist, &mut best_item);
+            }
+            (best_dist, best_item)
+        })
+    });
+
+    group.finish();
+}
+
+fn leaf_scan_within(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Leaf Scan Within");
+    group.throughput(Throughput::Elements((CHUNKS_PER_LOOP * CHUNK_SIZE) as u64));
+
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(2);
+    let items: [u32; CHUNK_SIZE] = std::array::from_fn(|i| i as u32);
+    let chunks_f64 = random_chunks::<f64>(&mut rng);
+
+    // a radius that matches roughly 1 in 1000 distances, so most chunks contain no results
+    let radius = 0.001f64;
+
+    group.bench_function(BenchmarkId::new("autovec", "f64"), |b| {
+        b.iter(|| {
+            let mut results: Vec<NearestNeighbour<f64, u32>> = Vec::new();
+            for chunk in &chunks_f64 {
+                update_nearest_dists_within_autovec(chunk, &items, radius, &mut results);
+            }
+            results
+        })
+    });
+
+    #[cfg(feature = "simd_stable")]
+    group.bench_function(BenchmarkId::new("lanes", "f64"), |b| {
+        b.iter(|| {
+            let mut results: Vec<NearestNeighbour<f64, u32>> = Vec::new();
+            for chunk in &chunks_f64 {
+                for_each_dist_within_lanes(chunk, radius, |idx| {
+                    results.push(NearestNeighbour {
+                        distance: chunk[idx],
+                        item: items[idx],
+                    })
+                });
+            }
+            results
+        })
+    });
+
+    group.finish();
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use std::arch::x86_64::{
+        __m128, __m128d, __m128i, __m256d, __m256i, _mm256_castpd256_pd128, _mm256_cmp_pd,
+        _mm256_loadu_pd, _mm256_min_pd, _mm256_permutevar8x32_epi32, _mm256_set_epi32,
+        _mm256_storeu_pd, _mm256_testz_si256, _mm_add_epi32, _mm_blendv_ps, _mm_set1_epi32,
+        _mm_set_epi32, _mm_storeu_si128, _CMP_LT_OQ,
+    };
+
+    /// Reference AVX2 implementation of `update_nearest_dist_autovec` for `f64`,
+    /// adapted from the crate's (currently disabled) `simd` feature kernel.
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn update_nearest_dist_f64<const C: usize>(
+        dists: &[f64; C],
+        items: &[u32; C],
+        best_dist: &mut f64,
+        best_item: &mut u32,
+    ) {
+        let is_better_shuffle_pattern: __m256i = _mm256_set_epi32(6, 4, 2, 0, 7, 5, 3, 1);
+
+        let mut index_v = _mm_set_epi32(3, 2, 1, 0);
+        let mut min_dist_indexes_v = _mm_set1_epi32(-1);
+        let all_fours = _mm_set1_epi32(4);
+
+        let mut min_dists = [*best_dist; 4];
+        let mut min_dists_v = _mm256_loadu_pd(min_dists.as_ptr());
+
+        let mut any_is_better = false;
+        for chunk in dists.chunks_exact(4) {
+            let chunk_v = _mm256_loadu_pd(chunk.as_ptr());
+
+            let is_better = _mm256_cmp_pd::<_CMP_LT_OQ>(chunk_v, min_dists_v);
+            let is_better_i = std::mem::transmute::<__m256d, __m256i>(is_better);
+
+            any_is_better |= _mm256_testz_si256(is_better_i, is_better_i) == 0;
+
+            min_dists_v = _mm256_min_pd(min_dists_v, chunk_v);
+
+            let is_better_shuffled =
+                _mm256_permutevar8x32_epi32(is_better_i, is_better_shuffle_pattern);
+            let is_better_mask =
+                _mm256_castpd256_pd128(std::mem::transmute::<__m256i, __m256d>(is_better_shuffled));
+
+            min_dist_indexes_v = std::mem::transmute::<__m128, __m128i>(_mm_blendv_ps(
+                std::mem::transmute::<__m128i, __m128>(min_dist_indexes_v),
+                std::mem::transmute::<__m128i, __m128>(index_v),
+                std::mem::transmute::<__m128d, __m128>(is_better_mask),
+            ));
+
+            index_v = _mm_add_epi32(index_v, all_fours);
+        }
+
+        if !any_is_better {
+            return;
+        }
+
+        let mut min_dist_indexes = [0i32; 4];
+        _mm_storeu_si128(
+            min_dist_indexes.as_mut_ptr() as *mut __m128i,
+            min_dist_indexes_v,
+        );
+        _mm256_storeu_pd(min_dists.as_mut_ptr(), min_dists_v);
+
+        for (i, dist) in min_dists.iter().enumerate() {
+            if *dist < *best_dist {
+                *best_dist = *dist;
+                *best_item = items[min_dist_indexes[i] as usize];
+            }
+        }
+    }
+}
+
+criterion_group!(benches, leaf_scan_nearest_one, leaf_scan_within);
+criterion_main!(benches);
@@ -0,0 +1,357 @@
+//! Structured benchmark suite, covering construction and each of the main query types for
+//! both `KdTree` and `ImmutableKdTree`, across a grid of dimensions (K), bucket sizes (B),
+//! tree sizes and dataset shapes.
+//!
+//! Benchmark IDs take the form `<activity>/<tree type>/<shape>/K<K>/B<B>/<size>`, so any
+//! subset can be run by passing a criterion filter:
+//!
+//! ```text
+//! cargo bench --bench suite --features test_utils
+//! cargo bench --bench suite --features test_utils -- "nearest_one/immutable/clustered"
+//! cargo bench --bench suite --features test_utils -- --save-baseline master
+//! cargo bench --bench suite --features test_utils -- --baseline master
+//! ```
+//!
+//! Setting `KIDDO_BENCH_JSON=1` bypasses criterion. Each benchmark is instead timed over a
+//! fixed period and reported as a single line of JSON on stdout, which is convenient for
+//! collating results across machines or feeding them into other tooling. The filter is
+//! still honoured in this mode.
+use std::env;
+use std::hint::black_box;
+use std::num::NonZero;
+use std::time::{Duration, Instant};
+
+use codspeed_criterion_compat::{criterion_group, criterion_main, Criterion, Throughput};
+use kiddo::float::kdtree::KdTree;
+use kiddo::immutable::float::kdtree::ImmutableKdTree;
+use kiddo::SquaredEuclidean;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, Normal};
+use rayon::prelude::*;
+
+const TREE_SIZES: [usize; 2] = [10_000, 1_000_000];
+const QUERY_POINTS_PER_LOOP: usize = 1_000;
+const NEAREST_N_QTY: usize = 10;
+
+/// Median number of results returned by each `within` query
+const WITHIN_EXPECTED_QTY: usize = 10;
+
+const CLUSTER_QTY: usize = 20;
+const CLUSTER_STD_DEV: f64 = 0.01;
+
+const JSON_ENV_VAR: &str = "KIDDO_BENCH_JSON";
+const JSON_MIN_DURATION: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug)]
+enum Shape {
+    /// Points distributed uniformly throughout the unit hypercube
+    Uniform,
+
+    /// Points normally distributed around a small number of uniformly distributed centres
+    Clustered,
+
+    /// Points on a regular grid within the unit hypercube. Many points share the same
+    /// value along each axis, so only `ImmutableKdTree` is benchmarked with this shape.
+    Lattice,
+}
+
+impl Shape {
+    const ALL: [Shape; 3] = [Shape::Uniform, Shape::Clustered, Shape::Lattice];
+
+    fn name(self) -> &'static str {
+        match self {
+            Shape::Uniform => "uniform",
+            Shape::Clustered => "clustered",
+            Shape::Lattice => "lattice",
+        }
+    }
+
+    /// Generates `qty` points of this shape, along with `query_qty` query points drawn from
+    /// the same distribution (or, for `Lattice`, uniformly from the unit hypercube)
+    fn generate<const K: usize>(
+        self,
+        qty: usize,
+        query_qty: usize,
+    ) -> (Vec<[f64; K]>, Vec<[f64; K]>) {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+
+        let mut uniform = |qty: usize| -> Vec<[f64; K]> {
+            (0..qty)
+                .map(|_| std::array::from_fn(|_| rng.gen()))
+                .collect()
+        };
+
+        match self {
+            Shape::Uniform => (uniform(qty), uniform(query_qty)),
+            Shape::Clustered => {
+                let centres = uniform(CLUSTER_QTY);
+                let spread = Normal::new(0.0, CLUSTER_STD_DEV).unwrap();
+
+                let mut clustered = |qty: usize| -> Vec<[f64; K]> {
+                    (0..qty)
+                        .map(|_| {
+                            let centre = centres[rng.gen_range(0..CLUSTER_QTY)];
+                            centre.map(|c| c + spread.sample(&mut rng))
+                        })
+                        .collect()
+                };
+
+                (clustered(qty), clustered(query_qty))
+            }
+            Shape::Lattice => {
+                let side = (qty as f64).powf(1.0 / K as f64).ceil() as usize;
+
+                let points = (0..qty)
+                    .map(|idx| {
+                        let mut remaining = idx;
+                        std::array::from_fn(|_| {
+                            let coord = remaining % side;
+                            remaining /= side;
+                            coord as f64 / side as f64
+                        })
+                    })
+                    .collect();
+
+                (points, uniform(query_qty))
+            }
+        }
+    }
+}
+
+/// Either hands each benchmark to criterion, or times it directly and prints it as JSON
+enum Runner<'a> {
+    Criterion(&'a mut Criterion),
+    Json { filter: Option<String> },
+}
+
+impl Runner<'_> {
+    fn bench<R>(
+        &mut self,
+        activity: &str,
+        id: &str,
+        elements: usize,
+        sample_size: usize,
+        mut routine: impl FnMut() -> R,
+    ) {
+        match self {
+            Runner::Criterion(c) => {
+                let mut group = c.benchmark_group(activity);
+                group.throughput(Throughput::Elements(elements as u64));
+                group.sample_size(sample_size);
+                group.bench_function(id, |b| b.iter(&mut routine));
+                group.finish();
+            }
+            Runner::Json { filter } => {
+                let full_id = format!("{activity}/{id}");
+                if filter
+                    .as_ref()
+                    .is_some_and(|f| !full_id.contains(f.as_str()))
+                {
+                    return;
+                }
+
+                // warm up, and make sure that we run for long enough to be meaningful
+                black_box(routine());
+
+                let start = Instant::now();
+                let mut iterations = 0u64;
+                while iterations == 0 || start.elapsed() < JSON_MIN_DURATION {
+                    black_box(routine());
+                    iterations += 1;
+                }
+                let ns_per_iter = start.elapsed().as_nanos() as f64 / iterations as f64;
+
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "id": full_id,
+                        "elements": elements,
+                        "iterations": iterations,
+                        "ns_per_iter": ns_per_iter,
+                        "ns_per_element": ns_per_iter / elements as f64,
+                    })
+                );
+            }
+        }
+    }
+}
+
+/// The dataset and query parameters shared by every benchmark of one configuration
+struct Config<const K: usize> {
+    id_suffix: String,
+    points: Vec<[f64; K]>,
+    query_points: Vec<[f64; K]>,
+    radius_sq: f64,
+    construction_sample_size: usize,
+}
+
+impl<const K: usize> Config<K> {
+    fn new<const B: usize>(shape: Shape, size: usize) -> Self {
+        let (points, query_points) = shape.generate(size, QUERY_POINTS_PER_LOOP);
+
+        // the median distance from a query point to its WITHIN_EXPECTED_QTY-th nearest
+        // neighbour, so that within queries return a comparable amount of results
+        // regardless of the density of the dataset
+        let tree = ImmutableKdTree::<f64, u64, K, 32>::new_from_slice(&points);
+        let mut kth_dists: Vec<f64> = query_points
+            .iter()
+            .map(|query| {
+                tree.nearest_n::<SquaredEuclidean>(
+                    query,
+                    NonZero::new(WITHIN_EXPECTED_QTY).unwrap(),
+                )
+                .last()
+                .unwrap()
+                .distance
+            })
+            .collect();
+        kth_dists.sort_by(f64::total_cmp);
+
+        Config {
+            id_suffix: format!("{}/K{K}/B{B}/{size}", shape.name()),
+            points,
+            query_points,
+            radius_sq: kth_dists[kth_dists.len() / 2],
+            construction_sample_size: if size >= 1_000_000 { 10 } else { 50 },
+        }
+    }
+
+    fn bench_queries<R>(
+        &self,
+        runner: &mut Runner,
+        activity: &str,
+        tree_type: &str,
+        query: impl Fn(&[f64; K]) -> R,
+    ) {
+        runner.bench(
+            activity,
+            &format!("{tree_type}/{}", self.id_suffix),
+            QUERY_POINTS_PER_LOOP,
+            100,
+            || {
+                self.query_points.iter().for_each(|point| {
+                    black_box(query(point));
+                })
+            },
+        );
+    }
+
+    fn bench_batch<R: Send>(
+        &self,
+        runner: &mut Runner,
+        tree_type: &str,
+        query: impl Fn(&[f64; K]) -> R + Sync,
+    ) {
+        runner.bench(
+            "batch_nearest_one",
+            &format!("{tree_type}/{}", self.id_suffix),
+            QUERY_POINTS_PER_LOOP,
+            100,
+            || self.query_points.par_iter().map(&query).collect::<Vec<_>>(),
+        );
+    }
+}
+
+fn bench_mutable<const K: usize, const B: usize>(runner: &mut Runner, config: &Config<K>) {
+    let build = || {
+        let mut tree: KdTree<f64, u64, K, B, u32> = KdTree::with_capacity(config.points.len());
+        config
+            .points
+            .iter()
+            .enumerate()
+            .for_each(|(idx, point)| tree.add(point, idx as u64));
+        tree
+    };
+
+    runner.bench(
+        "construction",
+        &format!("mutable/{}", config.id_suffix),
+        config.points.len(),
+        config.construction_sample_size,
+        build,
+    );
+
+    let tree = build();
+
+    config.bench_queries(runner, "nearest_one", "mutable", |query| {
+        tree.nearest_one::<SquaredEuclidean>(query)
+    });
+    config.bench_queries(runner, "nearest_n", "mutable", |query| {
+        tree.nearest_n::<SquaredEuclidean>(query, NEAREST_N_QTY)
+    });
+    config.bench_queries(runner, "within", "mutable", |query| {
+        tree.within::<SquaredEuclidean>(query, config.radius_sq)
+    });
+    config.bench_queries(runner, "within_unsorted", "mutable", |query| {
+        tree.within_unsorted::<SquaredEuclidean>(query, config.radius_sq)
+    });
+    config.bench_batch(runner, "mutable", |query| {
+        tree.nearest_one::<SquaredEuclidean>(query)
+    });
+}
+
+fn bench_immutable<const K: usize, const B: usize>(runner: &mut Runner, config: &Config<K>) {
+    let build = || ImmutableKdTree::<f64, u64, K, B>::new_from_slice(&config.points);
+
+    runner.bench(
+        "construction",
+        &format!("immutable/{}", config.id_suffix),
+        config.points.len(),
+        config.construction_sample_size,
+        build,
+    );
+
+    let tree = build();
+    let nearest_n_qty = NonZero::new(NEAREST_N_QTY).unwrap();
+
+    config.bench_queries(runner, "nearest_one", "immutable", |query| {
+        tree.nearest_one::<SquaredEuclidean>(query)
+    });
+    config.bench_queries(runner, "nearest_n", "immutable", |query| {
+        tree.nearest_n::<SquaredEuclidean>(query, nearest_n_qty)
+    });
+    config.bench_queries(runner, "within", "immutable", |query| {
+        tree.within::<SquaredEuclidean>(query, config.radius_sq)
+    });
+    config.bench_queries(runner, "within_unsorted", "immutable", |query| {
+        tree.within_unsorted::<SquaredEuclidean>(query, config.radius_sq)
+    });
+    config.bench_batch(runner, "immutable", |query| {
+        tree.nearest_one::<SquaredEuclidean>(query)
+    });
+}
+
+fn bench_config<const K: usize, const B: usize>(runner: &mut Runner, shape: Shape, size: usize) {
+    let config = Config::<K>::new::<B>(shape, size);
+
+    // KdTree panics if more than B points share the same value on an axis
+    if !matches!(shape, Shape::Lattice) {
+        bench_mutable::<K, B>(runner, &config);
+    }
+    bench_immutable::<K, B>(runner, &config);
+}
+
+fn run_suite(runner: &mut Runner) {
+    for shape in Shape::ALL {
+        for size in TREE_SIZES {
+            bench_config::<2, 32>(runner, shape, size);
+            bench_config::<3, 32>(runner, shape, size);
+            bench_config::<4, 32>(runner, shape, size);
+            bench_config::<3, 128>(runner, shape, size);
+        }
+    }
+}
+
+pub fn suite(c: &mut Criterion) {
+    if env::var_os(JSON_ENV_VAR).is_some() {
+        // the first free argument that isn't a flag is the filter, as with criterion
+        let filter = env::args().skip(1).find(|arg| !arg.starts_with('-'));
+        run_suite(&mut Runner::Json { filter });
+    } else {
+        run_suite(&mut Runner::Criterion(c));
+    }
+}
+
+criterion_group!(benches, suite);
+criterion_main!(benches);
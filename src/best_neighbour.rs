@@ -1,6 +1,8 @@
 //! A result item returned by a query
 use crate::traits::Content;
-use std::cmp::Ordering;
+use core::cmp::Ordering;
+#[cfg(feature = "std")]
+use std::collections::BinaryHeap;
 
 /// Represents an entry in the results of a "best" query, with `distance` being the distance of this
 /// particular item from the query point, and `item` being the stored item index that was found
@@ -43,6 +45,80 @@ impl<A, T: Content> From<BestNeighbour<A, T>> for (A, T) {
     }
 }
 
+/// Keeps the `max_qty` entries with the lowest scores, for queries where the "best"
+/// entries are chosen by a user-supplied score rather than by item.
+#[cfg(feature = "std")]
+pub(crate) struct BestByScore<S, A, T> {
+    heap: BinaryHeap<Scored<S, A, T>>,
+    max_qty: usize,
+}
+
+#[cfg(feature = "std")]
+struct Scored<S, A, T> {
+    score: S,
+    neighbour: BestNeighbour<A, T>,
+}
+
+#[cfg(feature = "std")]
+impl<S: PartialOrd, A, T> Ord for Scored<S, A, T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .partial_cmp(&other.score)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S: PartialOrd, A, T> PartialOrd for Scored<S, A, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S: PartialOrd, A, T> Eq for Scored<S, A, T> {}
+
+#[cfg(feature = "std")]
+impl<S: PartialOrd, A, T> PartialEq for Scored<S, A, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S: PartialOrd, A, T> BestByScore<S, A, T> {
+    pub(crate) fn new(max_qty: usize) -> Self {
+        BestByScore {
+            heap: BinaryHeap::with_capacity(max_qty.min(1024)),
+            max_qty,
+        }
+    }
+
+    pub(crate) fn add(&mut self, distance: A, item: T, score: S) {
+        let scored = Scored {
+            score,
+            neighbour: BestNeighbour { distance, item },
+        };
+
+        if self.heap.len() < self.max_qty {
+            self.heap.push(scored);
+        } else if let Some(mut worst) = self.heap.peek_mut() {
+            if scored < *worst {
+                *worst = scored;
+            }
+        }
+    }
+
+    /// Returns the kept entries, lowest score first
+    pub(crate) fn into_sorted_vec(self) -> Vec<BestNeighbour<A, T>> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|scored| scored.neighbour)
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::best_neighbour::BestNeighbour;
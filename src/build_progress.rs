@@ -0,0 +1,69 @@
+//! Progress reporting and cancellation for long-running tree construction
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Allows the construction of a tree, started with a constructor such as
+/// [`ImmutableKdTree::new_from_slice_with_progress`](`crate::immutable::float::kdtree::ImmutableKdTree::new_from_slice_with_progress`),
+/// to be abandoned from another thread.
+///
+/// Clones of a token share the same state, so one clone can be handed to the thread that
+/// builds the tree and another kept by the thread that may want to cancel it. Cancellation
+/// is cooperative: construction checks the token between steps, and stops at the next check
+/// after [`cancel`](CancellationToken::cancel) is called.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Creates a token that has not been cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests that any construction using this token, or a clone of it, stops
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` if [`cancel`](CancellationToken::cancel) has been called on this
+    /// token or a clone of it
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Reports the progress of a tree's construction to an optional callback, and tells the
+/// construction whether it has been cancelled through an optional token
+#[derive(Default)]
+pub(crate) struct BuildMonitor<'a> {
+    progress: Option<&'a mut dyn FnMut(usize, usize)>,
+    cancel: Option<&'a CancellationToken>,
+    total: usize,
+}
+
+impl<'a> BuildMonitor<'a> {
+    pub(crate) fn new(
+        total: usize,
+        progress: &'a mut dyn FnMut(usize, usize),
+        cancel: &'a CancellationToken,
+    ) -> Self {
+        BuildMonitor {
+            progress: Some(progress),
+            cancel: Some(cancel),
+            total,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancel.is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Reports that the first `done` items have been placed in their leaves
+    #[inline]
+    pub(crate) fn items_placed(&mut self, done: usize) {
+        if let Some(progress) = &mut self.progress {
+            progress(done, self.total);
+        }
+    }
+}
@@ -0,0 +1,315 @@
+//! A C API over [`DynKdTree`], for using Kiddo from other languages.
+//!
+//! The trees in the rest of the crate are generic over their number of dimensions, which
+//! can't be expressed across a C ABI. The functions here instead work on opaque trees whose
+//! number of dimensions is chosen at runtime, with points passed as flat arrays of `f32`
+//! or `f64` of `count * dims` elements. The item of each point is its index within the
+//! array that the tree was built from, and distances are squared Euclidean.
+//!
+//! There is one set of functions for each of `f32` and `f64`, prefixed `kiddo_f32_` and
+//! `kiddo_f64_`:
+//!
+//! ```c
+//! KiddoTreeF64 *kiddo_f64_new(const double *points, size_t count, size_t dims);
+//! void kiddo_f64_free(KiddoTreeF64 *tree);
+//! size_t kiddo_f64_size(const KiddoTreeF64 *tree);
+//! size_t kiddo_f64_dims(const KiddoTreeF64 *tree);
+//! size_t kiddo_f64_nearest_n(const KiddoTreeF64 *tree, const double *query, size_t qty,
+//!                            uint64_t *out_items, double *out_distances);
+//! void kiddo_f64_nearest_n_batch(const KiddoTreeF64 *tree, const double *queries,
+//!                                size_t query_count, size_t qty,
+//!                                uint64_t *out_items, double *out_distances);
+//! ```
+//!
+//! A shared library can be built with
+//! `cargo rustc --release --features capi --crate-type cdylib`.
+//!
+//! Requires the `capi` feature.
+use std::slice;
+
+use crate::float::dyn_kdtree::DynKdTree;
+use crate::float::kdtree::Axis;
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::SquaredEuclidean;
+
+/// A tree of `f32` points, created by [`kiddo_f32_new`]
+pub struct KiddoTreeF32(DynKdTree<f32, u64>);
+
+/// A tree of `f64` points, created by [`kiddo_f64_new`]
+pub struct KiddoTreeF64(DynKdTree<f64, u64>);
+
+/// Builds the tree for the `*_new` functions, or returns `None` if `points` can't be
+/// made into one
+fn build<A: Axis>(points: &[A], dims: usize) -> Option<DynKdTree<A, u64>> {
+    if dims == 0 {
+        return None;
+    }
+    let points: Vec<&[A]> = points.chunks_exact(dims).collect();
+
+    DynKdTree::try_from_points(dims, &points).ok()
+}
+
+/// Writes `results` to the start of `out_items` and `out_distances`, filling any that
+/// remain with `u64::MAX` and infinity
+fn write_results<A: Axis>(
+    results: &[NearestNeighbour<A, u64>],
+    out_items: &mut [u64],
+    out_distances: &mut [A],
+) {
+    for (idx, (item, distance)) in out_items.iter_mut().zip(out_distances).enumerate() {
+        (*item, *distance) = results
+            .get(idx)
+            .map_or((u64::MAX, A::infinity()), |neighbour| {
+                (neighbour.item, neighbour.distance)
+            });
+    }
+}
+
+macro_rules! generate_capi {
+    ($float:ty, $tree:ident, $new:ident, $free:ident, $size:ident, $dims:ident, $nearest_n:ident, $nearest_n_batch:ident) => {
+        /// Creates a tree from `count` points of `dims` coordinates each, stored one after
+        /// another in `points`, using the index of each point as its item.
+        ///
+        /// Returns null if `dims` is zero, if `count * dims` overflows a `usize`, if any
+        /// coordinate is NaN, or if more points share a position on one axis than fit in a
+        /// bucket. The tree must be freed with the matching `_free` function.
+        ///
+        /// # Safety
+        ///
+        /// `points` must be valid for reads of `count * dims` elements. A `count * dims`
+        /// that overflows a `usize` is rejected with null before `points` is read.
+        #[no_mangle]
+        pub unsafe extern "C" fn $new(
+            points: *const $float,
+            count: usize,
+            dims: usize,
+        ) -> *mut $tree {
+            let points = if count == 0 || dims == 0 {
+                &[]
+            } else {
+                let Some(len) = count.checked_mul(dims) else {
+                    return std::ptr::null_mut();
+                };
+                slice::from_raw_parts(points, len)
+            };
+
+            match build(points, dims) {
+                Some(tree) => Box::into_raw(Box::new($tree(tree))),
+                None => std::ptr::null_mut(),
+            }
+        }
+
+        /// Frees a tree created by the matching `_new` function. Does nothing if `tree` is null.
+        ///
+        /// # Safety
+        ///
+        /// `tree` must be null or have been returned by the matching `_new` function, and
+        /// must not be used again afterwards.
+        #[no_mangle]
+        pub unsafe extern "C" fn $free(tree: *mut $tree) {
+            if !tree.is_null() {
+                drop(Box::from_raw(tree));
+            }
+        }
+
+        /// Returns the number of points in `tree`.
+        ///
+        /// # Safety
+        ///
+        /// `tree` must be a live tree returned by the matching `_new` function.
+        #[no_mangle]
+        pub unsafe extern "C" fn $size(tree: *const $tree) -> usize {
+            (*tree).0.size()
+        }
+
+        /// Returns the number of dimensions of the points in `tree`.
+        ///
+        /// # Safety
+        ///
+        /// `tree` must be a live tree returned by the matching `_new` function.
+        #[no_mangle]
+        pub unsafe extern "C" fn $dims(tree: *const $tree) -> usize {
+            (*tree).0.dims()
+        }
+
+        /// Finds the nearest `qty` points to `query`, writing their items and squared
+        /// Euclidean distances, nearest first, to `out_items` and `out_distances`.
+        ///
+        /// Returns the number of points found, which is less than `qty` if the tree holds
+        /// fewer than `qty` points. The entries after those are filled with `UINT64_MAX`
+        /// and infinity.
+        ///
+        /// # Safety
+        ///
+        /// `tree` must be a live tree returned by the matching `_new` function, `query`
+        /// must be valid for reads of as many elements as the tree has dimensions, and
+        /// `out_items` and `out_distances` must each be valid for writes of `qty` elements.
+        #[no_mangle]
+        pub unsafe extern "C" fn $nearest_n(
+            tree: *const $tree,
+            query: *const $float,
+            qty: usize,
+            out_items: *mut u64,
+            out_distances: *mut $float,
+        ) -> usize {
+            if qty == 0 {
+                return 0;
+            }
+            let tree = &(*tree).0;
+            let query = slice::from_raw_parts(query, tree.dims());
+            let results = tree.nearest_n::<SquaredEuclidean>(query, qty);
+
+            write_results(
+                &results,
+                slice::from_raw_parts_mut(out_items, qty),
+                slice::from_raw_parts_mut(out_distances, qty),
+            );
+
+            results.len()
+        }
+
+        /// Finds the nearest `qty` points to each of `query_count` queries, stored one
+        /// after another in `queries`, as the matching `_nearest_n` function does for a
+        /// single query.
+        ///
+        /// The results for each query take up `qty` consecutive entries of `out_items` and
+        /// `out_distances`, in the same order as the queries.
+        ///
+        /// # Safety
+        ///
+        /// `tree` must be a live tree returned by the matching `_new` function, `queries`
+        /// must be valid for reads of `query_count` times as many elements as the tree has
+        /// dimensions, and `out_items` and `out_distances` must each be valid for writes
+        /// of `query_count * qty` elements. If either of those products overflows a
+        /// `usize`, nothing is read or written.
+        #[no_mangle]
+        pub unsafe extern "C" fn $nearest_n_batch(
+            tree: *const $tree,
+            queries: *const $float,
+            query_count: usize,
+            qty: usize,
+            out_items: *mut u64,
+            out_distances: *mut $float,
+        ) {
+            if query_count == 0 || qty == 0 {
+                return;
+            }
+            let tree = &(*tree).0;
+            let (Some(queries_len), Some(out_len)) = (
+                query_count.checked_mul(tree.dims()),
+                query_count.checked_mul(qty),
+            ) else {
+                return;
+            };
+            let queries = slice::from_raw_parts(queries, queries_len);
+            let out_items = slice::from_raw_parts_mut(out_items, out_len);
+            let out_distances = slice::from_raw_parts_mut(out_distances, out_len);
+
+            for ((query, items), distances) in queries
+                .chunks_exact(tree.dims())
+                .zip(out_items.chunks_exact_mut(qty))
+                .zip(out_distances.chunks_exact_mut(qty))
+            {
+                let results = tree.nearest_n::<SquaredEuclidean>(query, qty);
+                write_results(&results, items, distances);
+            }
+        }
+    };
+}
+
+generate_capi!(
+    f32,
+    KiddoTreeF32,
+    kiddo_f32_new,
+    kiddo_f32_free,
+    kiddo_f32_size,
+    kiddo_f32_dims,
+    kiddo_f32_nearest_n,
+    kiddo_f32_nearest_n_batch
+);
+
+generate_capi!(
+    f64,
+    KiddoTreeF64,
+    kiddo_f64_new,
+    kiddo_f64_free,
+    kiddo_f64_size,
+    kiddo_f64_dims,
+    kiddo_f64_nearest_n,
+    kiddo_f64_nearest_n_batch
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_build_and_query_through_the_c_api() {
+        let points: Vec<f64> = (0..300)
+            .flat_map(|i| [i as f64, (i * 37 % 300) as f64])
+            .collect();
+
+        unsafe {
+            let tree = kiddo_f64_new(points.as_ptr(), 300, 2);
+            assert!(!tree.is_null());
+            assert_eq!(kiddo_f64_size(tree), 300);
+            assert_eq!(kiddo_f64_dims(tree), 2);
+
+            let mut items = [0u64; 3];
+            let mut distances = [0f64; 3];
+            let found = kiddo_f64_nearest_n(
+                tree,
+                [10.0, 70.2].as_ptr(),
+                3,
+                items.as_mut_ptr(),
+                distances.as_mut_ptr(),
+            );
+            assert_eq!(found, 3);
+            assert_eq!(items[0], 10);
+            assert!((distances[0] - 0.04).abs() < 1e-9);
+
+            let queries = [10.0, 70.2, 299.0, 263.0];
+            let mut batch_items = [0u64; 6];
+            let mut batch_distances = [0f64; 6];
+            kiddo_f64_nearest_n_batch(
+                tree,
+                queries.as_ptr(),
+                2,
+                3,
+                batch_items.as_mut_ptr(),
+                batch_distances.as_mut_ptr(),
+            );
+            assert_eq!(batch_items[..3], items);
+            assert_eq!(batch_items[3], 299);
+
+            kiddo_f64_free(tree);
+        }
+    }
+
+    #[test]
+    fn pads_results_and_rejects_unusable_input() {
+        unsafe {
+            assert!(kiddo_f32_new([1.0, f32::NAN].as_ptr(), 1, 2).is_null());
+            assert!(kiddo_f32_new([1.0, 2.0].as_ptr(), 1, 0).is_null());
+            assert!(kiddo_f64_new([1.0, 2.0].as_ptr(), usize::MAX, 2).is_null());
+
+            let tree = kiddo_f32_new([1.0, 2.0, 3.0, 4.0].as_ptr(), 2, 2);
+            let mut items = [0u64; 4];
+            let mut distances = [0f32; 4];
+            let found = kiddo_f32_nearest_n(
+                tree,
+                [3.0, 4.0].as_ptr(),
+                4,
+                items.as_mut_ptr(),
+                distances.as_mut_ptr(),
+            );
+
+            assert_eq!(found, 2);
+            assert_eq!(items, [1, 0, u64::MAX, u64::MAX]);
+            assert_eq!(distances, [0.0, 8.0, f32::INFINITY, f32::INFINITY]);
+
+            kiddo_f32_free(tree);
+            kiddo_f32_free(std::ptr::null_mut());
+        }
+    }
+}
@@ -0,0 +1,374 @@
+//! Density-based clustering of the points in an [`ImmutableKdTree`].
+//!
+//! Requires the `cluster` feature.
+use std::num::NonZero;
+
+use az::Cast;
+
+use crate::float::kdtree::Axis;
+use crate::float_leaf_slice::leaf_slice::{LeafSliceFloat, LeafSliceFloatChunk};
+use crate::immutable::float::kdtree::ImmutableKdTree;
+use crate::traits::{Content, DistanceMetric};
+
+/// Clusters the points in `tree` using [DBSCAN](https://en.wikipedia.org/wiki/DBSCAN).
+///
+/// A point is a core point if at least `min_points` points (including itself) are within
+/// distance `eps` of it, as found by `within_unsorted`. Core points that are within `eps` of each other are in the same
+/// cluster, and any other point within `eps` of a core point joins that core point's cluster.
+/// Points that are not within `eps` of any core point are noise.
+///
+/// Returns a label for each item, indexed by item: `Some(cluster)` for points that are part of a
+/// cluster, with clusters numbered from zero, or `None` for noise. Points that are within `eps` of
+/// core points of more than one cluster are assigned to whichever cluster reaches them first.
+///
+/// `eps` is expressed in the same units as the distances returned by `D`, so for
+/// [`SquaredEuclidean`](`crate::SquaredEuclidean`) it is the square of the radius.
+///
+/// # Panics
+///
+/// The items in `tree` must be the integers `0..tree.size()`, as is the case for trees
+/// constructed with [`ImmutableKdTree::new_from_slice`]. Panics if any item is out of range,
+/// or if any items have been deleted from `tree` without it being compacted since.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::cluster::dbscan;
+/// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+/// use kiddo::SquaredEuclidean;
+///
+/// let points: Vec<[f64; 2]> = vec![
+///     [0.0, 0.0], [0.0, 1.0], [1.0, 0.0],
+///     [10.0, 10.0], [10.0, 11.0], [11.0, 10.0],
+///     [50.0, 50.0],
+/// ];
+/// let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+///
+/// let labels = dbscan::<SquaredEuclidean, _, _, 2, 32>(&tree, 1.5, 3);
+///
+/// assert_eq!(labels[0], labels[1]);
+/// assert_eq!(labels[0], labels[2]);
+/// assert_eq!(labels[3], labels[4]);
+/// assert_eq!(labels[3], labels[5]);
+/// assert_ne!(labels[0], labels[3]);
+/// assert_eq!(labels[6], None);
+/// ```
+pub fn dbscan<D, A, T, const K: usize, const B: usize>(
+    tree: &ImmutableKdTree<A, T, K, B>,
+    eps: A,
+    min_points: usize,
+) -> Vec<Option<usize>>
+where
+    D: DistanceMetric<A, K>,
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content + Cast<usize>,
+    usize: Cast<T>,
+{
+    assert_eq!(
+        tree.tombstone_count(),
+        0,
+        "dbscan requires a tree without deleted items. Call compact() first"
+    );
+    let size = tree.size();
+
+    // Where each item's point is stored within the tree's leaves, so that the points
+    // of neighbours can be read directly rather than being copied out up-front
+    let mut positions = vec![0u32; size];
+    for (position, &item) in tree.leaf_items.iter().enumerate() {
+        let item: usize = item.cast();
+        assert!(item < size, "dbscan requires items to be in 0..tree.size()");
+        positions[item] = position as u32;
+    }
+
+    let point_at =
+        |position: usize| -> [A; K] { core::array::from_fn(|dim| tree.leaf_points[dim][position]) };
+
+    let mut labels: Vec<Option<usize>> = vec![None; size];
+    let mut visited = vec![0u64; size.div_ceil(64)];
+    let mut next_cluster = 0usize;
+    let mut frontier: Vec<usize> = Vec::new();
+
+    // Seeding in storage order, rather than item order, means that consecutive
+    // queries are spatially close to one another and so touch the same leaves
+    for (seed_position, &seed) in tree.leaf_items.iter().enumerate() {
+        let seed: usize = seed.cast();
+        if !mark_visited(&mut visited, seed) {
+            continue;
+        }
+
+        let neighbours = tree.within_unsorted::<D>(&point_at(seed_position), eps);
+        if neighbours.len() < min_points {
+            // noise, unless a cluster reaches it later on
+            continue;
+        }
+
+        let cluster = next_cluster;
+        next_cluster += 1;
+
+        labels[seed] = Some(cluster);
+        frontier.extend(neighbours.iter().map(|nn| nn.item.cast()));
+
+        while let Some(item) = frontier.pop() {
+            if labels[item].is_none() {
+                labels[item] = Some(cluster);
+            }
+
+            if !mark_visited(&mut visited, item) {
+                continue;
+            }
+
+            let neighbours = tree.within_unsorted::<D>(&point_at(positions[item] as usize), eps);
+            if neighbours.len() >= min_points {
+                frontier.extend(neighbours.iter().map(|nn| nn.item.cast()));
+            }
+        }
+    }
+
+    labels
+}
+
+/// Counts the distances from each of `queries` to its `k`th nearest neighbour in `tree`
+/// into the buckets between `bucket_edges`.
+///
+/// Returns `bucket_edges.len() + 1` counts. The first is of the distances below
+/// `bucket_edges[0]`, the `i`th of those from `bucket_edges[i - 1]` up to but excluding
+/// `bucket_edges[i]`, and the last of those at or above the last edge. Queries with fewer
+/// than `k` neighbours in the tree are not counted. The neighbours are found with a single
+/// reused buffer, so the distances are never all held in memory at once.
+///
+/// This is the usual way to choose `eps` for [`dbscan`]. Using the points in the tree as
+/// the queries, with `k` equal to `min_points`, the points whose `k`th distance is below
+/// `eps` are exactly the core points. Each point counts as its own nearest neighbour, as it
+/// does in `dbscan`. A value of `eps` just past the "knee" of the distribution, where the
+/// counts start to tail off, separates points in clusters from noise.
+///
+/// # Panics
+///
+/// Panics if `bucket_edges` is not sorted in ascending order.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::num::NonZero;
+/// use kiddo::cluster::distance_histogram;
+/// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+/// use kiddo::SquaredEuclidean;
+///
+/// let points: Vec<[f64; 2]> = vec![
+///     [0.0, 0.0], [0.0, 1.0], [1.0, 0.0],
+///     [10.0, 10.0], [10.0, 11.0], [11.0, 10.0],
+///     [50.0, 50.0],
+/// ];
+/// let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+///
+/// let histogram = distance_histogram::<SquaredEuclidean, _, _, 2, 32>(
+///     &tree,
+///     &points,
+///     NonZero::new(3).unwrap(),
+///     &[2.5, 100.0],
+/// );
+///
+/// // six points have two neighbours close by, and one is far from everything
+/// assert_eq!(histogram, vec![6, 0, 1]);
+/// ```
+pub fn distance_histogram<D, A, T, const K: usize, const B: usize>(
+    tree: &ImmutableKdTree<A, T, K, B>,
+    queries: &[[A; K]],
+    k: NonZero<usize>,
+    bucket_edges: &[A],
+) -> Vec<usize>
+where
+    D: DistanceMetric<A, K>,
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    usize: Cast<T>,
+{
+    assert!(
+        bucket_edges.windows(2).all(|pair| pair[0] <= pair[1]),
+        "bucket_edges must be sorted in ascending order"
+    );
+
+    let mut counts = vec![0; bucket_edges.len() + 1];
+    let mut neighbours = Vec::with_capacity(k.get());
+
+    for query in queries {
+        tree.nearest_n_into::<D>(query, k, &mut neighbours);
+
+        if neighbours.len() == k.get() {
+            let distance = neighbours[k.get() - 1].distance;
+            counts[bucket_edges.partition_point(|&edge| edge <= distance)] += 1;
+        }
+    }
+
+    counts
+}
+
+/// Marks `idx` as visited, returning false if it already was
+#[inline]
+fn mark_visited(visited: &mut [u64], idx: usize) -> bool {
+    let word = &mut visited[idx / 64];
+    let bit = 1u64 << (idx % 64);
+    let unvisited = *word & bit == 0;
+    *word |= bit;
+
+    unvisited
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dbscan, distance_histogram};
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use crate::traits::DistanceMetric;
+    use crate::{Manhattan, SquaredEuclidean};
+    use rand::{Rng, SeedableRng};
+    use std::num::NonZero;
+
+    #[test]
+    fn separates_clusters_and_noise() {
+        let points: Vec<[f64; 2]> = vec![
+            [0.0, 0.0],
+            [0.0, 1.0],
+            [1.0, 0.0],
+            [1.0, 1.0],
+            [10.0, 10.0],
+            [10.0, 11.0],
+            [11.0, 10.0],
+            [5.0, 5.0],
+        ];
+        let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+
+        let labels = dbscan::<Manhattan, _, _, 2, 32>(&tree, 1.5, 3);
+
+        assert!(labels[0].is_some());
+        assert!(labels[0..4].iter().all(|&label| label == labels[0]));
+        assert!(labels[4].is_some());
+        assert!(labels[4..7].iter().all(|&label| label == labels[4]));
+        assert_ne!(labels[0], labels[4]);
+        assert_eq!(labels[7], None);
+    }
+
+    #[test]
+    fn matches_brute_force_definition() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(3);
+
+        let centres: Vec<[f32; 3]> = (0..5).map(|_| rng.gen()).collect();
+        let mut points: Vec<[f32; 3]> = (0..2000)
+            .map(|_| {
+                let centre = centres[rng.gen_range(0..centres.len())];
+                centre.map(|c| c + rng.gen_range(-0.05..0.05))
+            })
+            .collect();
+        points.extend((0..200).map(|_| rng.gen::<[f32; 3]>()));
+
+        let tree: ImmutableKdTree<f32, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+
+        let eps = 0.0004f32;
+        let min_points = 5;
+        let labels = dbscan::<SquaredEuclidean, _, _, 3, 32>(&tree, eps, min_points);
+
+        let neighbours: Vec<Vec<usize>> = points
+            .iter()
+            .map(|p| {
+                (0..points.len())
+                    .filter(|&j| SquaredEuclidean::dist(p, &points[j]) < eps)
+                    .collect()
+            })
+            .collect();
+        let is_core: Vec<bool> = neighbours.iter().map(|n| n.len() >= min_points).collect();
+
+        for idx in 0..points.len() {
+            if is_core[idx] {
+                // core points are clustered along with every point within reach
+                assert!(labels[idx].is_some());
+                for &j in &neighbours[idx] {
+                    if is_core[j] {
+                        assert_eq!(labels[j], labels[idx]);
+                    }
+                }
+            } else {
+                // border points belong to the cluster of one of their core neighbours,
+                // and everything else is noise
+                let core_labels: Vec<_> = neighbours[idx]
+                    .iter()
+                    .filter(|&&j| is_core[j])
+                    .map(|&j| labels[j])
+                    .collect();
+
+                match labels[idx] {
+                    Some(_) => assert!(core_labels.contains(&labels[idx])),
+                    None => assert!(core_labels.is_empty()),
+                }
+            }
+        }
+
+        // one cluster per connected component of core points
+        let mut component: Vec<usize> = (0..points.len()).collect();
+        fn root(component: &mut [usize], mut idx: usize) -> usize {
+            while component[idx] != idx {
+                idx = component[idx];
+            }
+            idx
+        }
+        for idx in (0..points.len()).filter(|&idx| is_core[idx]) {
+            for &j in neighbours[idx].iter().filter(|&&j| is_core[j]) {
+                let (a, b) = (root(&mut component, idx), root(&mut component, j));
+                component[a] = b;
+            }
+        }
+        let mut roots: Vec<usize> = (0..points.len())
+            .filter(|&idx| is_core[idx])
+            .map(|idx| root(&mut component, idx))
+            .collect();
+        roots.sort();
+        roots.dedup();
+
+        let cluster_count = labels.iter().flatten().max().map_or(0, |&max| max + 1);
+        assert!(cluster_count > 1);
+        assert_eq!(cluster_count, roots.len());
+        assert!((0..cluster_count).all(|cluster| labels.contains(&Some(cluster))));
+    }
+
+    #[test]
+    fn distance_histogram_matches_brute_force() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(69);
+        let points: Vec<[f64; 3]> = (0..2000).map(|_| rng.gen()).collect();
+        let queries: Vec<[f64; 3]> = (0..200).map(|_| rng.gen()).collect();
+        let tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+
+        let k = 5;
+        let bucket_edges = [0.001, 0.002, 0.004, 0.008];
+        let histogram = distance_histogram::<SquaredEuclidean, _, _, 3, 32>(
+            &tree,
+            &queries,
+            NonZero::new(k).unwrap(),
+            &bucket_edges,
+        );
+
+        let mut expected = vec![0; bucket_edges.len() + 1];
+        for query in &queries {
+            let mut distances: Vec<f64> = points
+                .iter()
+                .map(|point| SquaredEuclidean::dist(query, point))
+                .collect();
+            distances.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let bucket = bucket_edges
+                .iter()
+                .filter(|&&edge| edge <= distances[k - 1])
+                .count();
+            expected[bucket] += 1;
+        }
+
+        assert_eq!(histogram, expected);
+        assert_eq!(histogram.iter().sum::<usize>(), queries.len());
+
+        // queries with fewer than k neighbours aren't counted
+        let small: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points[..3]);
+        let histogram = distance_histogram::<SquaredEuclidean, _, _, 3, 32>(
+            &small,
+            &queries,
+            NonZero::new(k).unwrap(),
+            &bucket_edges,
+        );
+        assert_eq!(histogram, vec![0; bucket_edges.len() + 1]);
+    }
+}
@@ -13,12 +13,33 @@ macro_rules! generate_best_n_within {
     ) -> impl Iterator<Item = BestNeighbour<A, T>>
     where
         D: DistanceMetric<A, K>,
+    {
+        self.best_n_within_with_metric(&$crate::traits::StaticMetric::<D>::new(), query, dist, max_qty)
+    }
+    }
+
+    /// Finds the "best" `n` elements within `dist` of `query`, using a distance metric
+    /// that is passed in as a value rather than as a type parameter.
+    ///
+    /// Behaves the same as `best_n_within`, but allows metrics with runtime
+    /// parameters, such as per-axis weights, to be used.
+    #[inline]
+    pub fn best_n_within_with_metric<M>(
+        &self,
+        metric: &M,
+        query: &[A; K],
+        dist: A,
+        max_qty: usize,
+    ) -> impl Iterator<Item = BestNeighbour<A, T>>
+    where
+        M: $crate::traits::StatefulDistanceMetric<A, K>,
     {
         let mut off = [A::zero(); K];
         let mut best_items: BinaryHeap<BestNeighbour<A, T>> = BinaryHeap::new();
 
         unsafe {
-            self.best_n_within_recurse::<D>(
+            self.best_n_within_recurse(
+                metric,
                 query,
                 dist,
                 max_qty,
@@ -30,12 +51,20 @@ macro_rules! generate_best_n_within {
             );
         }
 
+        #[cfg(feature = "verify_results")]
+        let best_items = {
+            let best_items = best_items.into_vec();
+            $crate::verify::verify_best_n_within(self, metric, query, dist, max_qty, &best_items);
+            best_items
+        };
+
         best_items.into_iter()
     }
 
     #[allow(clippy::too_many_arguments)]
-    unsafe fn best_n_within_recurse<D>(
+    unsafe fn best_n_within_recurse<M>(
         &self,
+        metric: &M,
         query: &[A; K],
         radius: A,
         max_qty: usize,
@@ -45,7 +74,7 @@ macro_rules! generate_best_n_within {
         off: &mut [A; K],
         rd: A,
     ) where
-        D: DistanceMetric<A, K>,
+        M: $crate::traits::StatefulDistanceMetric<A, K>,
     {
         if is_stem_index(curr_node_idx) {
             let node = self.stems.get_unchecked(curr_node_idx.az::<usize>());
@@ -62,7 +91,8 @@ macro_rules! generate_best_n_within {
                 };
             let next_split_dim = (split_dim + 1).rem(K);
 
-            self.best_n_within_recurse::<D>(
+            self.best_n_within_recurse(
+                metric,
                 query,
                 radius,
                 max_qty,
@@ -73,11 +103,17 @@ macro_rules! generate_best_n_within {
                 rd,
             );
 
-            rd = Axis::rd_update(rd, D::dist1(new_off, old_off));
+            rd = $crate::traits::accumulate_dist(
+                    rd,
+                    metric.dist1(new_off, old_off, split_dim),
+                    M::MAX_OF_AXES,
+                    Axis::rd_update,
+                );
 
             if rd <= radius {
                 off[split_dim] = new_off;
-                self.best_n_within_recurse::<D>(
+                self.best_n_within_recurse(
+                    metric,
                     query,
                     radius,
                     max_qty,
@@ -94,25 +130,26 @@ macro_rules! generate_best_n_within {
                 .leaves
                 .get_unchecked((curr_node_idx - IDX::leaf_offset()).az::<usize>());
 
-            Self::process_leaf_node::<D>(query, radius, max_qty, best_items, leaf_node);
+            Self::process_leaf_node(metric, query, radius, max_qty, best_items, leaf_node);
         }
     }
 
     #[inline]
-    unsafe fn process_leaf_node<D>(
+    unsafe fn process_leaf_node<M>(
+        metric: &M,
         query: &[A; K],
         radius: A,
         max_qty: usize,
         best_items: &mut BinaryHeap<BestNeighbour<A, T>>,
         leaf_node: &$leafnode<A, T, K, B, IDX>,
     ) where
-        D: DistanceMetric<A, K>,
+        M: $crate::traits::StatefulDistanceMetric<A, K>,
     {
         leaf_node
             .content_points
             .iter()
             .take(leaf_node.size.az::<usize>())
-            .map(|entry| D::dist(query, entry))
+            .map(|entry| metric.dist(query, entry))
             .enumerate()
             .filter(|(_, distance)| *distance <= radius)
             .for_each(|(idx, distance)| {
@@ -139,4 +176,4 @@ macro_rules! generate_best_n_within {
             }
         }
     }
-}}}
+}}
@@ -2,110 +2,298 @@
 #[macro_export]
 macro_rules! generate_nearest_n {
     ($comments:tt) => {
-    doc_comment! {
-    concat!$comments,
-    #[inline]
-    pub fn nearest_n<D>(&self, query: &[A; K], qty: usize) -> Vec<NearestNeighbour<A, T>>
-    where
-        D: DistanceMetric<A, K>,
-    {
-        let mut off = [A::zero(); K];
-        let mut result: BinaryHeap<NearestNeighbour<A, T>> = BinaryHeap::with_capacity(qty);
-
-        unsafe {
-            self.nearest_n_recurse::<D>(
+        doc_comment! {
+        concat!$comments,
+        #[inline]
+        pub fn nearest_n<D>(&self, query: &[A; K], qty: usize) -> Vec<NearestNeighbour<A, T>>
+        where
+            D: DistanceMetric<A, K>,
+        {
+            self.nearest_n_with_metric(&$crate::traits::StaticMetric::<D>::new(), query, qty)
+        }
+        }
+
+        /// Finds the nearest `qty` elements to `query`, using a distance metric that is
+        /// passed in as a value rather than as a type parameter.
+        ///
+        /// Behaves the same as `nearest_n`, but allows metrics with runtime
+        /// parameters, such as per-axis weights, to be used.
+        #[inline]
+        pub fn nearest_n_with_metric<M>(
+            &self,
+            metric: &M,
+            query: &[A; K],
+            qty: usize,
+        ) -> Vec<NearestNeighbour<A, T>>
+        where
+            M: $crate::traits::StatefulDistanceMetric<A, K>,
+        {
+            let result = self.nearest_n_filtered_with_metric(metric, query, qty, &|_| true);
+
+            #[cfg(feature = "verify_results")]
+            $crate::verify::verify_nearest_n_within(self, metric, query, None, qty, &result);
+
+            result
+        }
+
+        /// Finds the nearest `qty` elements to `query` whose item satisfies `filter`,
+        /// sorted by distance.
+        ///
+        /// `filter` is applied to each item as the tree is searched, rather than to the
+        /// results afterwards, so items that it rejects never take up space in the results.
+        /// For example, the neighbours of a point that is itself stored in the tree can be
+        /// found without the point itself being returned, even if other items share its
+        /// coordinates, by passing `|item| item != own_item`.
+        #[inline]
+        pub fn nearest_n_filtered<D, F>(
+            &self,
+            query: &[A; K],
+            qty: usize,
+            filter: F,
+        ) -> Vec<NearestNeighbour<A, T>>
+        where
+            D: DistanceMetric<A, K>,
+            F: Fn(T) -> bool,
+        {
+            self.nearest_n_filtered_with_metric(
+                &$crate::traits::StaticMetric::<D>::new(),
                 query,
-                self.root_index,
-                0,
-                &mut result,
-                &mut off,
-                A::zero(),
+                qty,
+                &filter,
             )
         }
 
-        result.into_sorted_vec()
-    }
-
-    #[allow(clippy::too_many_arguments)]
-    unsafe fn nearest_n_recurse<D>(
-        &self,
-        query: &[A; K],
-        curr_node_idx: IDX,
-        split_dim: usize,
-        results: &mut BinaryHeap<NearestNeighbour<A, T>>,
-        off: &mut [A; K],
-        rd: A,
-    ) where
-        D: DistanceMetric<A, K>,
-    {
-        if is_stem_index(curr_node_idx) {
-            let node = &self.stems.get_unchecked(curr_node_idx.az::<usize>());
-
-            let mut rd = rd;
-            let old_off = off[split_dim];
-            let new_off = query[split_dim].saturating_dist(node.split_val);
-
-            let [closer_node_idx, further_node_idx] =
-                if *query.get_unchecked(split_dim) < node.split_val {
-                    [node.left, node.right]
-                } else {
-                    [node.right, node.left]
-                };
-            let next_split_dim = (split_dim + 1).rem(K);
-
-            self.nearest_n_recurse::<D>(
+        /// Finds the nearest `N` elements to `query`, sorted by distance, without allocating.
+        ///
+        /// Behaves the same as `nearest_n`, but returns an array rather than a `Vec`, making
+        /// it suitable for small `N` in loops where allocation must be avoided. If the tree
+        /// holds fewer than `N` items, the remaining entries have a distance of
+        /// `A::max_value()` and an item of zero.
+        #[inline]
+        pub fn nearest_n_const<D, const N: usize>(
+            &self,
+            query: &[A; K],
+        ) -> [NearestNeighbour<A, T>; N]
+        where
+            D: DistanceMetric<A, K>,
+        {
+            let metric = $crate::traits::StaticMetric::<D>::new();
+            let mut off = [A::zero(); K];
+            let mut result =
+                $crate::common::nearest_n_results::SortedArray::<A, T, N>::new(A::max_value());
+
+            unsafe {
+                self.nearest_n_recurse(
+                    &metric,
+                    query,
+                    self.root_index,
+                    0,
+                    &mut result,
+                    &mut off,
+                    A::zero(),
+                    &|_| true,
+                )
+            }
+
+            #[cfg(feature = "verify_results")]
+            $crate::verify::verify_nearest_n_within(
+                self,
+                &metric,
                 query,
-                closer_node_idx,
-                next_split_dim,
-                results,
-                off,
-                rd,
+                None,
+                N,
+                result.as_slice(),
             );
 
-            rd = Axis::rd_update(rd, D::dist1(new_off, old_off));
+            result.into_array()
+        }
+
+        /// Finds the nearest `qty` elements to `query`, sorted by distance, writing them
+        /// into `out`.
+        ///
+        /// Behaves the same as `nearest_n`, but clears and reuses `out` rather than returning
+        /// a new `Vec`, so a loop that makes many queries with the same buffer only allocates
+        /// when `out` needs to grow.
+        #[inline]
+        pub fn nearest_n_into<D>(
+            &self,
+            query: &[A; K],
+            qty: usize,
+            out: &mut Vec<NearestNeighbour<A, T>>,
+        ) where
+            D: DistanceMetric<A, K>,
+        {
+            let metric = $crate::traits::StaticMetric::<D>::new();
+            let mut off = [A::zero(); K];
+            let mut result =
+                $crate::common::nearest_n_results::ResultBuffer::new(core::mem::take(out), qty);
+
+            unsafe {
+                self.nearest_n_recurse(
+                    &metric,
+                    query,
+                    self.root_index,
+                    0,
+                    &mut result,
+                    &mut off,
+                    A::zero(),
+                    &|_| true,
+                )
+            }
+
+            *out = result.into_vec();
+
+            #[cfg(feature = "verify_results")]
+            $crate::verify::verify_nearest_n_within(self, &metric, query, None, qty, out);
+        }
+
+        /// Finds the distance from `query` to its `k`-th nearest neighbour, where a `k` of
+        /// one gives the distance to the nearest.
+        ///
+        /// Only the distances to the nearest `k` neighbours found so far are kept while
+        /// searching, in a bounded max-heap, rather than the neighbours themselves, making this
+        /// suitable for estimators of local density that call it in tight loops.
+        /// Returns `A::max_value()` if the tree holds fewer than `k` items, or if `k` is zero.
+        #[inline]
+        pub fn kth_nearest_distance<D>(&self, query: &[A; K], k: usize) -> A
+        where
+            D: DistanceMetric<A, K>,
+        {
+            let metric = $crate::traits::StaticMetric::<D>::new();
+            let mut off = [A::zero(); K];
+            let mut result = $crate::common::nearest_n_results::KthDistance::new(k);
+
+            unsafe {
+                self.nearest_n_recurse(
+                    &metric,
+                    query,
+                    self.root_index,
+                    0,
+                    &mut result,
+                    &mut off,
+                    A::zero(),
+                    &|_| true,
+                )
+            }
+
+            result.kth_dist().unwrap_or(A::max_value())
+        }
+
+        #[inline]
+        fn nearest_n_filtered_with_metric<M, F>(
+            &self,
+            metric: &M,
+            query: &[A; K],
+            qty: usize,
+            filter: &F,
+        ) -> Vec<NearestNeighbour<A, T>>
+        where
+            M: $crate::traits::StatefulDistanceMetric<A, K>,
+            F: Fn(T) -> bool,
+        {
+            let mut off = [A::zero(); K];
+            let mut result: BinaryHeap<NearestNeighbour<A, T>> = BinaryHeap::with_capacity(qty);
 
-            if Self::dist_belongs_in_heap(rd, results) {
-                off[split_dim] = new_off;
-                self.nearest_n_recurse::<D>(
+            unsafe {
+                self.nearest_n_recurse(
+                    metric,
                     query,
-                    further_node_idx,
+                    self.root_index,
+                    0,
+                    &mut result,
+                    &mut off,
+                    A::zero(),
+                    filter,
+                )
+            }
+
+            result.into_sorted_vec()
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        unsafe fn nearest_n_recurse<M, F, R>(
+            &self,
+            metric: &M,
+            query: &[A; K],
+            curr_node_idx: IDX,
+            split_dim: usize,
+            results: &mut R,
+            off: &mut [A; K],
+            rd: A,
+            filter: &F,
+        ) where
+            M: $crate::traits::StatefulDistanceMetric<A, K>,
+            F: Fn(T) -> bool,
+            R: $crate::common::nearest_n_results::NearestNResults<A, T>,
+        {
+            if is_stem_index(curr_node_idx) {
+                let node = &self.stems.get_unchecked(curr_node_idx.az::<usize>());
+
+                let mut rd = rd;
+                let old_off = off[split_dim];
+                let new_off = query[split_dim].saturating_dist(node.split_val);
+
+                let [closer_node_idx, further_node_idx] =
+                    if *query.get_unchecked(split_dim) < node.split_val {
+                        [node.left, node.right]
+                    } else {
+                        [node.right, node.left]
+                    };
+                let next_split_dim = (split_dim + 1).rem(K);
+
+                self.nearest_n_recurse(
+                    metric,
+                    query,
+                    closer_node_idx,
                     next_split_dim,
                     results,
                     off,
                     rd,
+                    filter,
                 );
-                off[split_dim] = old_off;
-            }
-        } else {
-            let leaf_node = self
-                .leaves
-                .get_unchecked((curr_node_idx - IDX::leaf_offset()).az::<usize>());
-
-            leaf_node
-                .content_points
-                .iter()
-                .take(leaf_node.size.az::<usize>())
-                .enumerate()
-                .for_each(|(idx, entry)| {
-                    let distance: A = D::dist(query, entry);
-                    if Self::dist_belongs_in_heap(distance, results) {
-                        let item = unsafe { *leaf_node.content_items.get_unchecked(idx) };
-                        let element = NearestNeighbour { distance, item };
-                        if results.len() < results.capacity() {
-                            results.push(element)
-                        } else {
-                            let mut top = results.peek_mut().unwrap();
-                            if element.distance < top.distance {
-                                *top = element;
+
+                rd = $crate::traits::accumulate_dist(
+                    rd,
+                    metric.dist1(new_off, old_off, split_dim),
+                    M::MAX_OF_AXES,
+                    Axis::rd_update,
+                );
+
+                if results.dist_belongs(rd) {
+                    off[split_dim] = new_off;
+                    self.nearest_n_recurse(
+                        metric,
+                        query,
+                        further_node_idx,
+                        next_split_dim,
+                        results,
+                        off,
+                        rd,
+                        filter,
+                    );
+                    off[split_dim] = old_off;
+                }
+            } else {
+                let leaf_node = self
+                    .leaves
+                    .get_unchecked((curr_node_idx - IDX::leaf_offset()).az::<usize>());
+
+                leaf_node
+                    .content_points
+                    .iter()
+                    .take(leaf_node.size.az::<usize>())
+                    .enumerate()
+                    .for_each(|(idx, entry)| {
+                        let distance: A = metric.dist(query, entry);
+                        if results.dist_belongs(distance) {
+                            let item = unsafe { *leaf_node.content_items.get_unchecked(idx) };
+                            if !filter(item) {
+                                return;
                             }
+                            results.add_nearest(NearestNeighbour { distance, item });
                         }
-                    }
-                });
+                    });
+            }
         }
-    }
-
-    #[inline]
-    fn dist_belongs_in_heap(dist: A, heap: &BinaryHeap<NearestNeighbour<A, T>>) -> bool {
-        heap.is_empty() || dist < heap.peek().unwrap().distance || heap.len() < heap.capacity()
-    }
-}}}
+    };
+}
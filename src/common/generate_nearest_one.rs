@@ -9,115 +9,455 @@ macro_rules! generate_nearest_one {
                 where
                     D: DistanceMetric<A, K>,
             {
-                let mut off = [A::zero(); K];
+                self.nearest_one_with_metric(&$crate::traits::StaticMetric::<D>::new(), query)
+            }
+        }
 
-                unsafe {
-                    self.nearest_one_recurse::<D>(
-                        query,
-                        self.root_index,
-                        0,
-                        NearestNeighbour { distance: A::max_value(), item: T::zero() },
-                        &mut off,
-                        A::zero(),
-                    )
-                }
+        /// Finds the nearest element to `query`, using a distance metric that is
+        /// passed in as a value rather than as a type parameter.
+        ///
+        /// Behaves the same as `nearest_one`, but allows metrics with runtime
+        /// parameters, such as per-axis weights, to be used.
+        #[inline]
+        pub fn nearest_one_with_metric<M>(
+            &self,
+            metric: &M,
+            query: &[A; K],
+        ) -> NearestNeighbour<A, T>
+        where
+            M: $crate::traits::StatefulDistanceMetric<A, K>,
+        {
+            self.nearest_one_point_with_metric(metric, query).into()
+        }
+
+        /// Finds the nearest element to `query`, returning the coordinates that it was
+        /// stored at alongside its distance and item.
+        ///
+        /// Behaves the same as `nearest_one`, but saves having to maintain a separate
+        /// lookup from item to point.
+        #[inline]
+        pub fn nearest_one_point<D>(
+            &self,
+            query: &[A; K],
+        ) -> $crate::nearest_neighbour::NearestNeighbourWithPoint<A, T, K>
+        where
+            D: DistanceMetric<A, K>,
+        {
+            self.nearest_one_point_with_metric(&$crate::traits::StaticMetric::<D>::new(), query)
+        }
+
+        /// Finds the nearest element to `query`, returning the coordinates that it was
+        /// stored at alongside its distance and item, using a distance metric that is
+        /// passed in as a value rather than as a type parameter.
+        #[inline]
+        pub fn nearest_one_point_with_metric<M>(
+            &self,
+            metric: &M,
+            query: &[A; K],
+        ) -> $crate::nearest_neighbour::NearestNeighbourWithPoint<A, T, K>
+        where
+            M: $crate::traits::StatefulDistanceMetric<A, K>,
+        {
+            let mut point = [A::zero(); K];
+            let result =
+                self.nearest_one_bounded(metric, query, A::max_value(), &mut point, &mut ());
+
+            #[cfg(feature = "verify_results")]
+            $crate::verify::verify_nearest_one(self, metric, query, &result);
+
+            $crate::nearest_neighbour::NearestNeighbourWithPoint {
+                distance: result.distance,
+                item: result.item,
+                point,
             }
+        }
 
-            #[allow(clippy::too_many_arguments)]
-            unsafe fn nearest_one_recurse<D>(
-                &self,
-                query: &[A; K],
-                curr_node_idx: IDX,
-                split_dim: usize,
-                mut nearest: NearestNeighbour<A, T>,
-                off: &mut [A; K],
-                rd: A,
-            ) -> NearestNeighbour<A, T>
-                where
-                    D: DistanceMetric<A, K>,
-            {
-                if is_stem_index(curr_node_idx) {
-                    let node = &self.stems.get_unchecked(curr_node_idx.az::<usize>());
-
-                    let mut rd = rd;
-                    let old_off = off[split_dim];
-                    let new_off = query[split_dim].saturating_dist(node.split_val);
-
-                    let [closer_node_idx, further_node_idx] =
-                        if *query.get_unchecked(split_dim) < node.split_val {
-                            [node.left, node.right]
-                        } else {
-                            [node.right, node.left]
-                        };
-                    let next_split_dim = (split_dim + 1).rem(K);
-
-                    let nearest_neighbour = self.nearest_one_recurse::<D>(
+        /// Finds the nearest element to `query` that is less than `max_dist` from it,
+        /// or `None` if there are no elements that close.
+        ///
+        /// Faster than calling `nearest_one` and checking the distance of the result
+        /// afterwards, as `max_dist` is used to prune the search from the outset.
+        #[inline]
+        pub fn nearest_one_within<D>(
+            &self,
+            query: &[A; K],
+            max_dist: A,
+        ) -> Option<NearestNeighbour<A, T>>
+        where
+            D: DistanceMetric<A, K>,
+        {
+            self.nearest_one_within_with_metric(
+                &$crate::traits::StaticMetric::<D>::new(),
+                query,
+                max_dist,
+            )
+        }
+
+        /// Finds the nearest element to `query` that is less than `max_dist` from it,
+        /// or `None` if there are no elements that close, using a distance metric that
+        /// is passed in as a value rather than as a type parameter.
+        #[inline]
+        pub fn nearest_one_within_with_metric<M>(
+            &self,
+            metric: &M,
+            query: &[A; K],
+            max_dist: A,
+        ) -> Option<NearestNeighbour<A, T>>
+        where
+            M: $crate::traits::StatefulDistanceMetric<A, K>,
+        {
+            let mut point = [A::zero(); K];
+            let result = self.nearest_one_bounded(metric, query, max_dist, &mut point, &mut ());
+            let result = (result.distance < max_dist).then_some(result);
+
+            #[cfg(feature = "verify_results")]
+            $crate::verify::verify_nearest_n_within(
+                self,
+                metric,
+                query,
+                Some(max_dist),
+                1,
+                result.as_slice(),
+            );
+
+            result
+        }
+
+        /// Finds the nearest element to `query`, along with counts of the stems visited,
+        /// leaves scanned and points compared whilst finding it.
+        ///
+        /// Returns the same result as `nearest_one`. Requires the `stats` feature.
+        #[cfg(feature = "stats")]
+        #[inline]
+        pub fn nearest_one_with_stats<D>(
+            &self,
+            query: &[A; K],
+        ) -> (NearestNeighbour<A, T>, $crate::stats::QueryStats)
+        where
+            D: DistanceMetric<A, K>,
+        {
+            let metric = $crate::traits::StaticMetric::<D>::new();
+            let mut stats = $crate::stats::QueryStats::default();
+            let mut point = [A::zero(); K];
+            let result =
+                self.nearest_one_bounded(&metric, query, A::max_value(), &mut point, &mut stats);
+
+            #[cfg(feature = "verify_results")]
+            $crate::verify::verify_nearest_one(self, &metric, query, &result);
+
+            (result, stats)
+        }
+
+        /// Finds the nearest element to `query` whose item satisfies `filter`, or `None`
+        /// if no items do.
+        ///
+        /// `filter` is applied to each item as the tree is searched. For example, the nearest
+        /// neighbour of a point that is itself stored in the tree can be found, even if other
+        /// items share its coordinates, by passing `|item| item != own_item`.
+        #[inline]
+        pub fn nearest_one_filtered<D, F>(
+            &self,
+            query: &[A; K],
+            filter: F,
+        ) -> Option<NearestNeighbour<A, T>>
+        where
+            D: DistanceMetric<A, K>,
+            F: Fn(T) -> bool,
+        {
+            self.nearest_n_filtered::<D, F>(query, 1, filter).pop()
+        }
+
+        /// Finds the nearest element to `query` whose coordinate on `axis` is greater than
+        /// `min_value`, or `None` if there are no such elements.
+        ///
+        /// This restricts the search to a half-space. For example, passing `query[axis]` as
+        /// `min_value` finds the nearest element that lies ahead of `query` along `axis`.
+        /// Parts of the tree that lie entirely outside of the half-space are not searched.
+        ///
+        /// # Panics
+        ///
+        /// Panics if `axis` is not less than `K`.
+        #[inline]
+        pub fn nearest_one_forward<D>(
+            &self,
+            query: &[A; K],
+            axis: usize,
+            min_value: A,
+        ) -> Option<NearestNeighbour<A, T>>
+        where
+            D: DistanceMetric<A, K>,
+        {
+            assert!(
+                axis < K,
+                "axis {} is out of range for a tree with {} dimensions",
+                axis,
+                K
+            );
+
+            let mut nearest = None;
+            let mut off = [A::zero(); K];
+
+            unsafe {
+                self.nearest_one_forward_recurse(
+                    &$crate::traits::StaticMetric::<D>::new(),
+                    query,
+                    axis,
+                    min_value,
+                    self.root_index,
+                    0,
+                    &mut nearest,
+                    &mut off,
+                    A::zero(),
+                );
+            }
+
+            nearest
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        unsafe fn nearest_one_forward_recurse<M>(
+            &self,
+            metric: &M,
+            query: &[A; K],
+            axis: usize,
+            min_value: A,
+            curr_node_idx: IDX,
+            split_dim: usize,
+            nearest: &mut Option<NearestNeighbour<A, T>>,
+            off: &mut [A; K],
+            rd: A,
+        ) where
+            M: $crate::traits::StatefulDistanceMetric<A, K>,
+        {
+            if is_stem_index(curr_node_idx) {
+                let node = &self.stems.get_unchecked(curr_node_idx.az::<usize>());
+
+                let old_off = off[split_dim];
+                let new_off = query[split_dim].saturating_dist(node.split_val);
+
+                let [closer_node_idx, further_node_idx] =
+                    if *query.get_unchecked(split_dim) < node.split_val {
+                        [node.left, node.right]
+                    } else {
+                        [node.right, node.left]
+                    };
+                let next_split_dim = (split_dim + 1).rem(K);
+
+                // everything to the left of a split on `axis` at or below `min_value`
+                // is outside of the half-space
+                let is_outside = |node_idx: IDX| {
+                    split_dim == axis && node_idx == node.left && node.split_val <= min_value
+                };
+
+                if !is_outside(closer_node_idx) {
+                    self.nearest_one_forward_recurse(
+                        metric,
                         query,
+                        axis,
+                        min_value,
                         closer_node_idx,
                         next_split_dim,
                         nearest,
                         off,
                         rd,
                     );
+                }
 
-                    if nearest_neighbour < nearest {
-                        nearest = nearest_neighbour;
-                    }
+                let rd = $crate::traits::accumulate_dist(
+                    rd,
+                    metric.dist1(new_off, old_off, split_dim),
+                    M::MAX_OF_AXES,
+                    Axis::rd_update,
+                );
 
-                    rd = Axis::rd_update(rd, D::dist1(new_off, old_off));
-
-                    if rd <= nearest.distance {
-                        off[split_dim] = new_off;
-                        let result = self.nearest_one_recurse::<D>(
-                            query,
-                            further_node_idx,
-                            next_split_dim,
-                            nearest,
-                            off,
-                            rd,
-                        );
-                        off[split_dim] = old_off;
-
-                        if result < nearest {
-                            nearest = result;
-                        }
-                    }
-                } else {
-                    let leaf_node = self
-                        .leaves
-                        .get_unchecked((curr_node_idx - IDX::leaf_offset()).az::<usize>());
-
-                    Self::search_content_for_nearest::<D>(
+                if !is_outside(further_node_idx)
+                    && nearest.is_none_or(|nearest| rd <= nearest.distance)
+                {
+                    off[split_dim] = new_off;
+                    self.nearest_one_forward_recurse(
+                        metric,
                         query,
-                        &mut nearest,
-                        leaf_node,
+                        axis,
+                        min_value,
+                        further_node_idx,
+                        next_split_dim,
+                        nearest,
+                        off,
+                        rd,
                     );
+                    off[split_dim] = old_off;
                 }
+            } else {
+                let leaf_node = self
+                    .leaves
+                    .get_unchecked((curr_node_idx - IDX::leaf_offset()).az::<usize>());
 
-                nearest
-            }
-
-            #[inline]
-            fn search_content_for_nearest<D>(
-                query: &[A; K],
-                nearest: &mut NearestNeighbour<A, T>,
-                leaf_node: &$leafnode<A, T, K, B, IDX>,
-            ) where
-                D: DistanceMetric<A, K>,
-            {
                 leaf_node
                     .content_points
                     .iter()
-                    .enumerate()
+                    .zip(leaf_node.content_items.iter())
                     .take(leaf_node.size.az::<usize>())
-                    .for_each(|(idx, entry)| {
-                        let dist = D::dist(query, entry);
-                        if dist < nearest.distance {
-                            nearest.distance = dist;
-                            nearest.item = unsafe { *leaf_node.content_items.get_unchecked(idx) };
+                    .filter(|(entry, _)| entry[axis] > min_value)
+                    .for_each(|(entry, &item)| {
+                        let distance = metric.dist(query, entry);
+                        if nearest.is_none_or(|nearest| distance < nearest.distance) {
+                            *nearest = Some(NearestNeighbour { distance, item });
                         }
                     });
             }
         }
+
+        /// Finds the nearest element to `query` that is less than `max_dist` from it.
+        /// If there isn't one, the returned distance is `max_dist`.
+        #[inline]
+        fn nearest_one_bounded<M, S>(
+            &self,
+            metric: &M,
+            query: &[A; K],
+            max_dist: A,
+            point: &mut [A; K],
+            stats: &mut S,
+        ) -> NearestNeighbour<A, T>
+        where
+            M: $crate::traits::StatefulDistanceMetric<A, K>,
+            S: $crate::traits::QueryStatsRecorder,
+        {
+            let mut off = [A::zero(); K];
+
+            unsafe {
+                self.nearest_one_recurse(
+                    metric,
+                    query,
+                    self.root_index,
+                    0,
+                    NearestNeighbour {
+                        distance: max_dist,
+                        item: T::zero(),
+                    },
+                    point,
+                    &mut off,
+                    A::zero(),
+                    stats,
+                )
+            }
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        unsafe fn nearest_one_recurse<M, S>(
+            &self,
+            metric: &M,
+            query: &[A; K],
+            curr_node_idx: IDX,
+            split_dim: usize,
+            mut nearest: NearestNeighbour<A, T>,
+            nearest_point: &mut [A; K],
+            off: &mut [A; K],
+            rd: A,
+            stats: &mut S,
+        ) -> NearestNeighbour<A, T>
+        where
+            M: $crate::traits::StatefulDistanceMetric<A, K>,
+            S: $crate::traits::QueryStatsRecorder,
+        {
+            if is_stem_index(curr_node_idx) {
+                stats.visit_stem();
+                let node = &self.stems.get_unchecked(curr_node_idx.az::<usize>());
+
+                let mut rd = rd;
+                let old_off = off[split_dim];
+                let new_off = query[split_dim].saturating_dist(node.split_val);
+
+                let [closer_node_idx, further_node_idx] =
+                    if *query.get_unchecked(split_dim) < node.split_val {
+                        [node.left, node.right]
+                    } else {
+                        [node.right, node.left]
+                    };
+                let next_split_dim = (split_dim + 1).rem(K);
+
+                let nearest_neighbour = self.nearest_one_recurse(
+                    metric,
+                    query,
+                    closer_node_idx,
+                    next_split_dim,
+                    nearest,
+                    nearest_point,
+                    off,
+                    rd,
+                    stats,
+                );
+
+                if nearest_neighbour < nearest {
+                    nearest = nearest_neighbour;
+                }
+
+                rd = $crate::traits::accumulate_dist(
+                    rd,
+                    metric.dist1(new_off, old_off, split_dim),
+                    M::MAX_OF_AXES,
+                    Axis::rd_update,
+                );
+
+                if rd <= nearest.distance {
+                    off[split_dim] = new_off;
+                    let result = self.nearest_one_recurse(
+                        metric,
+                        query,
+                        further_node_idx,
+                        next_split_dim,
+                        nearest,
+                        nearest_point,
+                        off,
+                        rd,
+                        stats,
+                    );
+                    off[split_dim] = old_off;
+
+                    if result < nearest {
+                        nearest = result;
+                    }
+                }
+            } else {
+                let leaf_node = self
+                    .leaves
+                    .get_unchecked((curr_node_idx - IDX::leaf_offset()).az::<usize>());
+
+                stats.scan_leaf(leaf_node.size.az::<usize>());
+                Self::search_content_for_nearest(
+                    metric,
+                    query,
+                    &mut nearest,
+                    nearest_point,
+                    leaf_node,
+                );
+            }
+
+            nearest
+        }
+
+        #[inline]
+        fn search_content_for_nearest<M>(
+            metric: &M,
+            query: &[A; K],
+            nearest: &mut NearestNeighbour<A, T>,
+            nearest_point: &mut [A; K],
+            leaf_node: &$leafnode<A, T, K, B, IDX>,
+        ) where
+            M: $crate::traits::StatefulDistanceMetric<A, K>,
+        {
+            leaf_node
+                .content_points
+                .iter()
+                .enumerate()
+                .take(leaf_node.size.az::<usize>())
+                .for_each(|(idx, entry)| {
+                    let dist = metric.dist(query, entry);
+                    if dist < nearest.distance {
+                        nearest.distance = dist;
+                        nearest.item = unsafe { *leaf_node.content_items.get_unchecked(idx) };
+                        *nearest_point = *entry;
+                    }
+                });
+        }
     };
 }
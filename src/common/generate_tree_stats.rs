@@ -0,0 +1,51 @@
+#[doc(hidden)]
+#[macro_export]
+macro_rules! generate_tree_stats {
+    () => {
+        /// Returns the number of stems between the root and the deepest leaf.
+        ///
+        /// Trees with a single leaf have a depth of zero. Takes time proportional to the
+        /// number of nodes in the tree.
+        pub fn max_depth(&self) -> usize {
+            let mut max_depth = 0;
+            let mut stack = alloc::vec![(self.root_index, 0)];
+
+            while let Some((node_idx, depth)) = stack.pop() {
+                if $crate::traits::is_stem_index(node_idx) {
+                    let stem_node = &self.stems[node_idx.az::<usize>()];
+                    stack.push((stem_node.left, depth + 1));
+                    stack.push((stem_node.right, depth + 1));
+                } else {
+                    max_depth = max_depth.max(depth);
+                }
+            }
+
+            max_depth
+        }
+
+        /// Returns the number of leaves in the tree, including any that have been emptied
+        /// by removals.
+        #[inline]
+        pub fn leaf_count(&self) -> usize {
+            self.leaves.len()
+        }
+
+        /// Returns the mean number of items per leaf, as a fraction of the bucket size.
+        ///
+        /// Leaves are split in two when they fill up, so this is usually somewhere between
+        /// one half and one for a tree that items have only been added to.
+        pub fn average_leaf_occupancy(&self) -> f64 {
+            self.generate_stats().average_leaf_occupancy
+        }
+
+        /// Returns a summary of how the tree's items are spread across its leaves, and of
+        /// how deep it is. See [`TreeStats`]($crate::tree_stats::TreeStats) for details.
+        pub fn generate_stats(&self) -> $crate::tree_stats::TreeStats {
+            $crate::tree_stats::TreeStats::from_leaf_sizes(
+                self.leaves.iter().map(|leaf| leaf.size.az::<usize>()),
+                self.max_depth(),
+                B,
+            )
+        }
+    };
+}
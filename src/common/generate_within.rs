@@ -14,5 +14,129 @@ macro_rules! generate_within {
                 matching_items
             }
         }
+
+        /// Finds all elements within `dist` of `query`, sorted by distance, using a
+        /// distance metric that is passed in as a value rather than as a type parameter.
+        ///
+        /// Behaves the same as `within`, but allows metrics with runtime
+        /// parameters, such as per-axis weights, to be used.
+        #[inline]
+        pub fn within_with_metric<M>(
+            &self,
+            metric: &M,
+            query: &[A; K],
+            dist: A,
+        ) -> Vec<NearestNeighbour<A, T>>
+        where
+            M: $crate::traits::StatefulDistanceMetric<A, K>,
+        {
+            let mut matching_items = self.within_unsorted_with_metric(metric, query, dist);
+            matching_items.sort();
+            matching_items
+        }
+
+        /// Finds all elements within `dist` of `query` whose item satisfies `filter`,
+        /// sorted by distance.
+        ///
+        /// `filter` is applied to each item as the tree is searched, rather than to the
+        /// results afterwards, so items that it rejects never take up space in the results.
+        #[inline]
+        pub fn within_filtered<D, F>(
+            &self,
+            query: &[A; K],
+            dist: A,
+            filter: F,
+        ) -> Vec<NearestNeighbour<A, T>>
+        where
+            D: DistanceMetric<A, K>,
+            F: Fn(T) -> bool,
+        {
+            let mut off = [A::zero(); K];
+            let mut matching_items = Vec::new();
+
+            unsafe {
+                self.within_unsorted_recurse(
+                    &$crate::traits::StaticMetric::<D>::new(),
+                    query,
+                    dist,
+                    self.root_index,
+                    0,
+                    &mut |entry| matching_items.push(entry),
+                    &mut off,
+                    A::zero(),
+                    &filter,
+                );
+            }
+
+            matching_items.sort();
+            matching_items
+        }
+
+        /// Finds all elements within `dist` of `query`, sorted by distance, writing them
+        /// into `out`.
+        ///
+        /// Behaves the same as `within`, but clears and reuses `out` rather than returning
+        /// a new `Vec`, so a loop that makes many queries with the same buffer only allocates
+        /// when `out` needs to grow.
+        #[inline]
+        pub fn within_into<D>(&self, query: &[A; K], dist: A, out: &mut Vec<NearestNeighbour<A, T>>)
+        where
+            D: DistanceMetric<A, K>,
+        {
+            let mut off = [A::zero(); K];
+            out.clear();
+
+            unsafe {
+                self.within_unsorted_recurse(
+                    &$crate::traits::StaticMetric::<D>::new(),
+                    query,
+                    dist,
+                    self.root_index,
+                    0,
+                    &mut |entry| out.push(entry),
+                    &mut off,
+                    A::zero(),
+                    &|_| true,
+                );
+            }
+
+            out.sort_unstable();
+        }
+
+        /// Finds all elements within `dist` of `query`, sorted by item rather than by
+        /// distance.
+        ///
+        /// Useful when the results are to be joined against data that is sorted by item,
+        /// as the matches are collected and then sorted by item directly, without being
+        /// sorted by distance first. Entries with equal items are in no particular order.
+        #[inline]
+        pub fn within_sorted_by_item<D>(
+            &self,
+            query: &[A; K],
+            dist: A,
+        ) -> Vec<NearestNeighbour<A, T>>
+        where
+            D: DistanceMetric<A, K>,
+        {
+            let mut matching_items = self.within_unsorted::<D>(query, dist);
+            matching_items.sort_unstable_by(|a, b| a.item.cmp(&b.item));
+            matching_items
+        }
+
+        /// Finds all elements within `dist` of `query`, sorted by distance, splitting
+        /// the traversal of a single query across the threads of the current rayon
+        /// thread pool.
+        ///
+        /// See `par_within_unsorted` for details. Only worthwhile for very large radii.
+        #[cfg(feature = "rayon")]
+        #[inline]
+        pub fn par_within<D>(&self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+        where
+            D: DistanceMetric<A, K>,
+        {
+            let mut matching_items = self.par_within_unsorted::<D>(query, dist);
+            rayon::slice::ParallelSliceMut::par_sort(matching_items.as_mut_slice());
+            matching_items
+        }
     };
 }
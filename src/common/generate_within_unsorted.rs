@@ -9,98 +9,398 @@ macro_rules! generate_within_unsorted {
             where
                 D: DistanceMetric<A, K>,
             {
-                let mut off = [A::zero(); K];
+                self.within_unsorted_with_metric(&$crate::traits::StaticMetric::<D>::new(), query, dist)
+            }
+        }
+
+        /// Finds all elements within `dist` of `query`, using a distance metric that is
+        /// passed in as a value rather than as a type parameter.
+        ///
+        /// Behaves the same as `within_unsorted`, but allows metrics with runtime
+        /// parameters, such as per-axis weights, to be used.
+        #[inline]
+        pub fn within_unsorted_with_metric<M>(&self, metric: &M, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+        where
+            M: $crate::traits::StatefulDistanceMetric<A, K>,
+        {
+            let mut off = [A::zero(); K];
+            let mut matching_items = Vec::new();
+
+            unsafe {
+                self.within_unsorted_recurse(
+                    metric,
+                    query,
+                    dist,
+                    self.root_index,
+                    0,
+                    &mut |entry| matching_items.push(entry),
+                    &mut off,
+                    A::zero(),
+                    &|_| true,
+                );
+            }
+
+            #[cfg(feature = "verify_results")]
+            $crate::verify::verify_nearest_n_within(self, metric, query, Some(dist), usize::MAX, &matching_items);
+
+            matching_items
+        }
+
+        /// Calls `visitor` with the item and distance of every element within `dist` of
+        /// `query`, in arbitrary order.
+        ///
+        /// Behaves the same as `within_unsorted`, but without collecting the results into
+        /// a `Vec`, so that they can be aggregated in place (for example counted, summed,
+        /// or reduced to a maximum) without allocating.
+        #[inline]
+        pub fn within_unsorted_visit<D, F>(&self, query: &[A; K], dist: A, mut visitor: F)
+        where
+            D: DistanceMetric<A, K>,
+            F: FnMut(T, A),
+        {
+            let mut off = [A::zero(); K];
+
+            unsafe {
+                self.within_unsorted_recurse(
+                    &$crate::traits::StaticMetric::<D>::new(),
+                    query,
+                    dist,
+                    self.root_index,
+                    0,
+                    &mut |entry: NearestNeighbour<A, T>| visitor(entry.item, entry.distance),
+                    &mut off,
+                    A::zero(),
+                    &|_| true,
+                );
+            }
+        }
+
+        /// Returns `true` if there is any element within `dist` of `query`.
+        ///
+        /// Stops searching as soon as a match is found, so is much faster than checking
+        /// whether the results of `within_unsorted` are empty.
+        #[inline]
+        pub fn any_within<D>(&self, query: &[A; K], dist: A) -> bool
+        where
+            D: DistanceMetric<A, K>,
+        {
+            let mut off = [A::zero(); K];
+
+            unsafe {
+                self.any_within_recurse(
+                    &$crate::traits::StaticMetric::<D>::new(),
+                    query,
+                    dist,
+                    self.root_index,
+                    0,
+                    &mut off,
+                    A::zero(),
+                )
+            }
+        }
+
+        /// Finds all elements within `dist` of `query`, splitting the traversal of a
+        /// single query across the threads of the current rayon thread pool.
+        ///
+        /// The first few stem levels are traversed in parallel, with each subtree below
+        /// them that intersects the query ball then being searched sequentially on its
+        /// own thread and the results concatenated. Only worthwhile for very large
+        /// radii that match a sizable fraction of the tree; for typical queries,
+        /// `within_unsorted` will be faster.
+        ///
+        /// Results are returned in arbitrary order.
+        #[cfg(feature = "rayon")]
+        #[inline]
+        pub fn par_within_unsorted<D>(&self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+        where
+            D: DistanceMetric<A, K>,
+        {
+            self.par_within_unsorted_with_metric(&$crate::traits::StaticMetric::<D>::new(), query, dist)
+        }
+
+        /// Finds all elements within `dist` of `query` in parallel, using a distance
+        /// metric that is passed in as a value rather than as a type parameter.
+        ///
+        /// Behaves the same as `par_within_unsorted`, but allows metrics with runtime
+        /// parameters, such as per-axis weights, to be used.
+        #[cfg(feature = "rayon")]
+        #[inline]
+        pub fn par_within_unsorted_with_metric<M>(&self, metric: &M, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+        where
+            M: $crate::traits::StatefulDistanceMetric<A, K> + Sync,
+        {
+            // split until there are a few tasks per thread, so that work-stealing
+            // can even out subtrees that contain differing numbers of matches
+            let parallel_levels = (rayon::current_num_threads() * 4)
+                .next_power_of_two()
+                .trailing_zeros();
+
+            let matching_items = unsafe {
+                self.par_within_unsorted_recurse(
+                    metric,
+                    query,
+                    dist,
+                    self.root_index,
+                    0,
+                    [A::zero(); K],
+                    A::zero(),
+                    parallel_levels,
+                )
+            };
+
+            #[cfg(feature = "verify_results")]
+            $crate::verify::verify_nearest_n_within(self, metric, query, Some(dist), usize::MAX, &matching_items);
+
+            matching_items
+        }
+
+        #[cfg(feature = "rayon")]
+        #[allow(clippy::too_many_arguments)]
+        unsafe fn par_within_unsorted_recurse<M>(
+            &self,
+            metric: &M,
+            query: &[A; K],
+            radius: A,
+            curr_node_idx: IDX,
+            split_dim: usize,
+            mut off: [A; K],
+            rd: A,
+            parallel_levels: u32,
+        ) -> Vec<NearestNeighbour<A, T>>
+        where
+            M: $crate::traits::StatefulDistanceMetric<A, K> + Sync,
+        {
+            if parallel_levels == 0 || !is_stem_index(curr_node_idx) {
                 let mut matching_items = Vec::new();
+                self.within_unsorted_recurse(
+                    metric,
+                    query,
+                    radius,
+                    curr_node_idx,
+                    split_dim,
+                    &mut |entry| matching_items.push(entry),
+                    &mut off,
+                    rd,
+                    &|_| true,
+                );
+                return matching_items;
+            }
 
-                unsafe {
-                    self.within_unsorted_recurse::<D>(
-                        query,
-                        dist,
-                        self.root_index,
-                        0,
-                        &mut matching_items,
-                        &mut off,
-                        A::zero(),
-                    );
-                }
+            let node = self.stems.get_unchecked(curr_node_idx.az::<usize>());
+
+            let old_off = off[split_dim];
+            let new_off = query[split_dim].saturating_dist(node.split_val);
+
+            let [closer_node_idx, further_node_idx] =
+                if *query.get_unchecked(split_dim) < node.split_val {
+                    [node.left, node.right]
+                } else {
+                    [node.right, node.left]
+                };
+            let next_split_dim = (split_dim + 1).rem(K);
 
-                matching_items
+            let further_rd = $crate::traits::accumulate_dist(
+                rd,
+                metric.dist1(new_off, old_off, split_dim),
+                M::MAX_OF_AXES,
+                Axis::rd_update,
+            );
+
+            if further_rd > radius {
+                return self.par_within_unsorted_recurse(
+                    metric,
+                    query,
+                    radius,
+                    closer_node_idx,
+                    next_split_dim,
+                    off,
+                    rd,
+                    parallel_levels - 1,
+                );
             }
 
-            #[allow(clippy::too_many_arguments)]
-            unsafe fn within_unsorted_recurse<D>(
-                &self,
-                query: &[A; K],
-                radius: A,
-                curr_node_idx: IDX,
-                split_dim: usize,
-                matching_items: &mut Vec<NearestNeighbour<A, T>>,
-                off: &mut [A; K],
-                rd: A,
-            ) where
-                D: DistanceMetric<A, K>,
-            {
-                if is_stem_index(curr_node_idx) {
-                    let node = self.stems.get_unchecked(curr_node_idx.az::<usize>());
-
-                    let mut rd = rd;
-                    let old_off = off[split_dim];
-                    let new_off = query[split_dim].saturating_dist(node.split_val);
-
-                    let [closer_node_idx, further_node_idx] =
-                        if *query.get_unchecked(split_dim) < node.split_val {
-                            [node.left, node.right]
-                        } else {
-                            [node.right, node.left]
-                        };
-                    let next_split_dim = (split_dim + 1).rem(K);
-
-                    self.within_unsorted_recurse::<D>(
+            let mut further_off = off;
+            further_off[split_dim] = new_off;
+
+            let (mut closer, further) = rayon::join(
+                || unsafe {
+                    self.par_within_unsorted_recurse(
+                        metric,
                         query,
                         radius,
                         closer_node_idx,
                         next_split_dim,
-                        matching_items,
                         off,
                         rd,
-                    );
+                        parallel_levels - 1,
+                    )
+                },
+                || unsafe {
+                    self.par_within_unsorted_recurse(
+                        metric,
+                        query,
+                        radius,
+                        further_node_idx,
+                        next_split_dim,
+                        further_off,
+                        further_rd,
+                        parallel_levels - 1,
+                    )
+                },
+            );
+
+            closer.extend(further);
+            closer
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        unsafe fn any_within_recurse<M>(
+            &self,
+            metric: &M,
+            query: &[A; K],
+            radius: A,
+            curr_node_idx: IDX,
+            split_dim: usize,
+            off: &mut [A; K],
+            rd: A,
+        ) -> bool
+        where
+            M: $crate::traits::StatefulDistanceMetric<A, K>,
+        {
+            if !is_stem_index(curr_node_idx) {
+                let leaf_node = self
+                    .leaves
+                    .get_unchecked((curr_node_idx - IDX::leaf_offset()).az::<usize>());
+
+                return leaf_node
+                    .content_points
+                    .iter()
+                    .take(leaf_node.size.az::<usize>())
+                    .any(|entry| metric.dist(query, entry) < radius);
+            }
 
-                    rd = Axis::rd_update(rd, D::dist1(new_off, old_off));
-
-                    if rd <= radius {
-                        off[split_dim] = new_off;
-                        self.within_unsorted_recurse::<D>(
-                            query,
-                            radius,
-                            further_node_idx,
-                            next_split_dim,
-                            matching_items,
-                            off,
-                            rd,
-                        );
-                        off[split_dim] = old_off;
-                    }
+            let node = self.stems.get_unchecked(curr_node_idx.az::<usize>());
+
+            let old_off = off[split_dim];
+            let new_off = query[split_dim].saturating_dist(node.split_val);
+
+            let [closer_node_idx, further_node_idx] =
+                if *query.get_unchecked(split_dim) < node.split_val {
+                    [node.left, node.right]
                 } else {
-                    let leaf_node = self
-                        .leaves
-                        .get_unchecked((curr_node_idx - IDX::leaf_offset()).az::<usize>());
-
-                    leaf_node
-                        .content_points
-                        .iter()
-                        .enumerate()
-                        .take(leaf_node.size.az::<usize>())
-                        .for_each(|(idx, entry)| {
-                            let distance = D::dist(query, entry);
-
-                            if distance < radius {
-                                matching_items.push(NearestNeighbour {
-                                    distance,
-                                    item: *leaf_node.content_items.get_unchecked(idx.az::<usize>()),
-                                })
-                            }
-                        });
+                    [node.right, node.left]
+                };
+            let next_split_dim = (split_dim + 1).rem(K);
+
+            if self.any_within_recurse(metric, query, radius, closer_node_idx, next_split_dim, off, rd) {
+                return true;
+            }
+
+            let rd = $crate::traits::accumulate_dist(
+                rd,
+                metric.dist1(new_off, old_off, split_dim),
+                M::MAX_OF_AXES,
+                Axis::rd_update,
+            );
+
+            if rd > radius {
+                return false;
+            }
+
+            off[split_dim] = new_off;
+            let found =
+                self.any_within_recurse(metric, query, radius, further_node_idx, next_split_dim, off, rd);
+            off[split_dim] = old_off;
+
+            found
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub(crate) unsafe fn within_unsorted_recurse<M, F, V>(
+            &self,
+            metric: &M,
+            query: &[A; K],
+            radius: A,
+            curr_node_idx: IDX,
+            split_dim: usize,
+            visitor: &mut V,
+            off: &mut [A; K],
+            rd: A,
+            filter: &F,
+        ) where
+            M: $crate::traits::StatefulDistanceMetric<A, K>,
+            F: Fn(T) -> bool,
+            V: FnMut(NearestNeighbour<A, T>),
+        {
+            if is_stem_index(curr_node_idx) {
+                let node = self.stems.get_unchecked(curr_node_idx.az::<usize>());
+
+                let mut rd = rd;
+                let old_off = off[split_dim];
+                let new_off = query[split_dim].saturating_dist(node.split_val);
+
+                let [closer_node_idx, further_node_idx] =
+                    if *query.get_unchecked(split_dim) < node.split_val {
+                        [node.left, node.right]
+                    } else {
+                        [node.right, node.left]
+                    };
+                let next_split_dim = (split_dim + 1).rem(K);
+
+                self.within_unsorted_recurse(
+                    metric,
+                    query,
+                    radius,
+                    closer_node_idx,
+                    next_split_dim,
+                    visitor,
+                    off,
+                    rd,
+                    filter,
+                );
+
+                rd = $crate::traits::accumulate_dist(
+                    rd,
+                    metric.dist1(new_off, old_off, split_dim),
+                    M::MAX_OF_AXES,
+                    Axis::rd_update,
+                );
+
+                if rd <= radius {
+                    off[split_dim] = new_off;
+                    self.within_unsorted_recurse(
+                        metric,
+                        query,
+                        radius,
+                        further_node_idx,
+                        next_split_dim,
+                        visitor,
+                        off,
+                        rd,
+                        filter,
+                    );
+                    off[split_dim] = old_off;
                 }
+            } else {
+                let leaf_node = self
+                    .leaves
+                    .get_unchecked((curr_node_idx - IDX::leaf_offset()).az::<usize>());
+
+                leaf_node
+                    .content_points
+                    .iter()
+                    .enumerate()
+                    .take(leaf_node.size.az::<usize>())
+                    .for_each(|(idx, entry)| {
+                        let distance = metric.dist(query, entry);
+
+                        if distance < radius {
+                            let item = *leaf_node.content_items.get_unchecked(idx.az::<usize>());
+                            if filter(item) {
+                                visitor(NearestNeighbour { distance, item })
+                            }
+                        }
+                    });
             }
         }
     };
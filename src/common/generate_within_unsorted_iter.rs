@@ -11,106 +11,67 @@ macro_rules! generate_within_unsorted_iter {
                 dist: A,
             ) -> WithinUnsortedIter<'a, A, T>
             where
-                D: DistanceMetric<A, K>,
+                D: DistanceMetric<A, K> + 'a,
             {
-                let mut off = [A::zero(); K];
+                // Nodes that are still to be visited, along with the split dimension,
+                // offsets and distance that recursing into them would have had. Children
+                // are pushed further first, so that the closer one is visited next.
+                let mut stack = vec![(self.root_index, 0, [A::zero(); K], A::zero())];
 
-                let gen = Gn::new_scoped(move |gen_scope| {
-                    unsafe {
-                        self.within_unsorted_iter_recurse::<D>(
-                            query,
-                            dist,
-                            self.root_index,
-                            0,
-                            gen_scope,
-                            &mut off,
-                            A::zero(),
-                        );
-                    }
+                // the index of the leaf currently being scanned, and the position within it
+                let mut leaf: Option<(usize, usize)> = None;
 
-                    done!();
-                });
+                WithinUnsortedIter::new(core::iter::from_fn(move || loop {
+                    if let Some((leaf_idx, idx)) = &mut leaf {
+                        let leaf_node = &self.leaves[*leaf_idx];
+                        while *idx < leaf_node.size.az::<usize>() {
+                            let entry_idx = *idx;
+                            *idx += 1;
 
-                WithinUnsortedIter::new(gen)
-            }
-
-            #[allow(clippy::too_many_arguments)]
-            unsafe fn within_unsorted_iter_recurse<'scope, D>(
-                &'a self,
-                query: &[A; K],
-                radius: A,
-                curr_node_idx: IDX,
-                split_dim: usize,
-                mut gen_scope: Scope<'scope, 'a, (), NearestNeighbour<A, T>>,
-                off: &mut [A; K],
-                rd: A,
-            ) -> Scope<'scope, 'a, (), NearestNeighbour<A, T>>
-            where
-                D: DistanceMetric<A, K>,
-            {
-                if is_stem_index(curr_node_idx) {
-                    let node = self.stems.get_unchecked(curr_node_idx.az::<usize>());
+                            let distance = D::dist(query, &leaf_node.content_points[entry_idx]);
+                            if distance < dist {
+                                return Some(NearestNeighbour {
+                                    distance,
+                                    item: leaf_node.content_items[entry_idx],
+                                });
+                            }
+                        }
+                        leaf = None;
+                    }
 
-                    let mut rd = rd;
-                    let old_off = off[split_dim];
-                    let new_off = query[split_dim].saturating_dist(node.split_val);
+                    let (node_idx, split_dim, off, rd): (IDX, usize, [A; K], A) = stack.pop()?;
 
-                    let [closer_node_idx, further_node_idx] =
-                        if *query.get_unchecked(split_dim) < node.split_val {
-                            [node.left, node.right]
-                        } else {
-                            [node.right, node.left]
-                        };
-                    let next_split_dim = (split_dim + 1).rem(K);
+                    if is_stem_index(node_idx) {
+                        let node = &self.stems[node_idx.az::<usize>()];
 
-                    gen_scope = self.within_unsorted_iter_recurse::<D>(
-                        query,
-                        radius,
-                        closer_node_idx,
-                        next_split_dim,
-                        gen_scope,
-                        off,
-                        rd,
-                    );
+                        let old_off = off[split_dim];
+                        let new_off = query[split_dim].saturating_dist(node.split_val);
 
-                    rd = Axis::rd_update(rd, D::dist1(new_off, old_off));
+                        let [closer_node_idx, further_node_idx] =
+                            if query[split_dim] < node.split_val {
+                                [node.left, node.right]
+                            } else {
+                                [node.right, node.left]
+                            };
+                        let next_split_dim = (split_dim + 1).rem(K);
 
-                    if rd <= radius {
-                        off[split_dim] = new_off;
-                        gen_scope = self.within_unsorted_iter_recurse::<D>(
-                            query,
-                            radius,
-                            further_node_idx,
-                            next_split_dim,
-                            gen_scope,
-                            off,
+                        let further_rd = $crate::traits::accumulate_dist(
                             rd,
+                            D::dist1(new_off, old_off),
+                            D::MAX_OF_AXES,
+                            Axis::rd_update,
                         );
-                        off[split_dim] = old_off;
-                    }
-                } else {
-                    let leaf_node = self
-                        .leaves
-                        .get_unchecked((curr_node_idx - IDX::leaf_offset()).az::<usize>());
 
-                    leaf_node
-                        .content_points
-                        .iter()
-                        .enumerate()
-                        .take(leaf_node.size.az::<usize>())
-                        .for_each(|(idx, entry)| {
-                            let distance = D::dist(query, entry);
-
-                            if distance < radius {
-                                gen_scope.yield_with(NearestNeighbour {
-                                    distance,
-                                    item: *leaf_node.content_items.get_unchecked(idx.az::<usize>()),
-                                });
-                            }
-                        });
-                }
-
-                gen_scope
+                        if further_rd <= dist {
+                            let mut further_off = off;
+                            further_off[split_dim] = new_off;
+                            stack.push((further_node_idx, next_split_dim, further_off, further_rd));
+                        }
+                        stack.push((closer_node_idx, next_split_dim, off, rd));
+                    } else {
+                        leaf = Some(((node_idx - IDX::leaf_offset()).az::<usize>(), 0));
+                    }
+                }))
             }
         }
     };
@@ -2,6 +2,8 @@ pub(crate) mod generate_best_n_within;
 pub(crate) mod generate_nearest_n;
 pub(crate) mod generate_nearest_n_within_unsorted;
 pub(crate) mod generate_nearest_one;
+pub(crate) mod generate_tree_stats;
 pub(crate) mod generate_within;
 pub(crate) mod generate_within_unsorted;
 pub(crate) mod generate_within_unsorted_iter;
+pub(crate) mod nearest_n_results;
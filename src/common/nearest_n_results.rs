@@ -0,0 +1,342 @@
+use alloc::collections::BinaryHeap;
+use alloc::vec::Vec;
+
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::Content;
+
+/// The collections that the mutable trees' `nearest_n` queries can gather their results into
+pub(crate) trait NearestNResults<A, T> {
+    /// Whether something at distance `dist` could be one of the nearest found so far
+    fn dist_belongs(&self, dist: A) -> bool;
+
+    /// Adds `element`, evicting the furthest result if the collection is full
+    fn add_nearest(&mut self, element: NearestNeighbour<A, T>);
+}
+
+impl<A: PartialOrd + Copy, T: Content> NearestNResults<A, T>
+    for BinaryHeap<NearestNeighbour<A, T>>
+{
+    #[inline]
+    fn dist_belongs(&self, dist: A) -> bool {
+        self.is_empty() || dist < self.peek().unwrap().distance || self.len() < self.capacity()
+    }
+
+    #[inline]
+    fn add_nearest(&mut self, element: NearestNeighbour<A, T>) {
+        if self.len() < self.capacity() {
+            self.push(element)
+        } else {
+            let mut top = self.peek_mut().unwrap();
+            if element.distance < top.distance {
+                *top = element;
+            }
+        }
+    }
+}
+
+/// The nearest `N` neighbours found so far, sorted by distance and stored inline, so that
+/// queries that collect into it do not need to allocate.
+///
+/// Until `N` results have been found, the remaining entries hold a placeholder
+/// with a distance of `empty_dist`.
+#[derive(Debug)]
+pub(crate) struct SortedArray<A, T, const N: usize> {
+    entries: [NearestNeighbour<A, T>; N],
+    len: usize,
+}
+
+impl<A: PartialOrd + Copy, T: Content, const N: usize> SortedArray<A, T, N> {
+    #[inline]
+    pub(crate) fn new(empty_dist: A) -> Self {
+        SortedArray {
+            entries: [NearestNeighbour {
+                distance: empty_dist,
+                item: T::zero(),
+            }; N],
+            len: 0,
+        }
+    }
+
+    /// The distance of the furthest result, if the array is full
+    #[inline]
+    pub(crate) fn max_dist(&self) -> Option<A> {
+        if self.len == N {
+            self.entries.last().map(|entry| entry.distance)
+        } else {
+            None
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn as_slice(&self) -> &[NearestNeighbour<A, T>] {
+        &self.entries[..self.len]
+    }
+
+    #[inline]
+    pub(crate) fn into_array(self) -> [NearestNeighbour<A, T>; N] {
+        self.entries
+    }
+}
+
+impl<A: PartialOrd + Copy, T: Content, const N: usize> NearestNResults<A, T>
+    for SortedArray<A, T, N>
+{
+    #[inline]
+    fn dist_belongs(&self, dist: A) -> bool {
+        self.max_dist().is_none_or(|max_dist| dist < max_dist)
+    }
+
+    #[inline]
+    fn add_nearest(&mut self, element: NearestNeighbour<A, T>) {
+        if N == 0 || !self.dist_belongs(element.distance) {
+            return;
+        }
+
+        // entries at the same distance keep the order in which they were found
+        let mut idx = self.len.min(N - 1);
+        while idx > 0 && element.distance < self.entries[idx - 1].distance {
+            self.entries[idx] = self.entries[idx - 1];
+            idx -= 1;
+        }
+        self.entries[idx] = element;
+        self.len = (self.len + 1).min(N);
+    }
+}
+
+/// The nearest `max_qty` neighbours found so far, sorted by distance and stored in a `Vec`
+/// handed over by the caller, so that queries that collect into it can reuse its
+/// allocation.
+///
+/// Unlike the `BinaryHeap` collections, the number of results kept does not depend on
+/// the capacity of the `Vec`, which may be larger than `max_qty` when it is reused.
+pub(crate) struct ResultBuffer<A, T> {
+    entries: Vec<NearestNeighbour<A, T>>,
+    max_qty: usize,
+}
+
+impl<A: PartialOrd + Copy, T: Content> ResultBuffer<A, T> {
+    /// Clears `entries`, and reserves space for `max_qty` results in it
+    #[inline]
+    pub(crate) fn new(mut entries: Vec<NearestNeighbour<A, T>>, max_qty: usize) -> Self {
+        entries.clear();
+        entries.reserve(max_qty.min(1024));
+
+        ResultBuffer { entries, max_qty }
+    }
+
+    /// The distance of the furthest result, if the buffer is full
+    #[inline]
+    pub(crate) fn max_dist(&self) -> Option<A> {
+        if self.entries.len() < self.max_qty {
+            None
+        } else {
+            self.entries.last().map(|entry| entry.distance)
+        }
+    }
+
+    #[inline]
+    pub(crate) fn into_vec(self) -> Vec<NearestNeighbour<A, T>> {
+        self.entries
+    }
+}
+
+impl<A: PartialOrd + Copy, T: Content> NearestNResults<A, T> for ResultBuffer<A, T> {
+    #[inline]
+    fn dist_belongs(&self, dist: A) -> bool {
+        self.max_qty > 0 && self.max_dist().is_none_or(|max_dist| dist < max_dist)
+    }
+
+    #[inline]
+    fn add_nearest(&mut self, element: NearestNeighbour<A, T>) {
+        if !self.dist_belongs(element.distance) {
+            return;
+        }
+        if self.entries.len() == self.max_qty {
+            self.entries.pop();
+        }
+
+        // entries at the same distance keep the order in which they were found
+        let idx = self
+            .entries
+            .partition_point(|entry| entry.distance <= element.distance);
+        self.entries.insert(idx, element);
+    }
+}
+
+/// The distances to the nearest `k` neighbours found so far, kept in a bounded max-heap,
+/// for queries that only need the distance to the k-th nearest neighbour and not the
+/// neighbours themselves.
+pub(crate) struct KthDistance<A> {
+    heap: Vec<A>,
+    k: usize,
+}
+
+impl<A: PartialOrd + Copy> KthDistance<A> {
+    #[inline]
+    pub(crate) fn new(k: usize) -> Self {
+        KthDistance {
+            heap: Vec::with_capacity(k.min(1024)),
+            k,
+        }
+    }
+
+    /// The distance to the k-th nearest neighbour, once `k` neighbours have been found
+    #[inline]
+    pub(crate) fn kth_dist(&self) -> Option<A> {
+        if self.heap.len() == self.k {
+            self.heap.first().copied()
+        } else {
+            None
+        }
+    }
+
+    /// Adds `dist`, evicting the furthest distance if the heap is full
+    #[inline]
+    pub(crate) fn push(&mut self, dist: A) {
+        if self.heap.len() < self.k {
+            self.heap.push(dist);
+
+            let mut idx = self.heap.len() - 1;
+            while idx > 0 {
+                let parent = (idx - 1) / 2;
+                if self.heap[parent] >= self.heap[idx] {
+                    break;
+                }
+                self.heap.swap(parent, idx);
+                idx = parent;
+            }
+        } else if self.k > 0 && dist < self.heap[0] {
+            self.heap[0] = dist;
+
+            let mut idx = 0;
+            loop {
+                let mut largest = idx;
+                for child in [2 * idx + 1, 2 * idx + 2] {
+                    if child < self.heap.len() && self.heap[child] > self.heap[largest] {
+                        largest = child;
+                    }
+                }
+                if largest == idx {
+                    break;
+                }
+                self.heap.swap(largest, idx);
+                idx = largest;
+            }
+        }
+    }
+}
+
+impl<A: PartialOrd + Copy, T: Content> NearestNResults<A, T> for KthDistance<A> {
+    #[inline]
+    fn dist_belongs(&self, dist: A) -> bool {
+        self.k > 0 && self.kth_dist().is_none_or(|kth_dist| dist < kth_dist)
+    }
+
+    #[inline]
+    fn add_nearest(&mut self, element: NearestNeighbour<A, T>) {
+        self.push(element.distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KthDistance, NearestNResults, ResultBuffer, SortedArray};
+    use crate::nearest_neighbour::NearestNeighbour;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn sorted_array_keeps_the_nearest_n() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(4);
+
+        let dists: Vec<u32> = (0..200).map(|_| rng.gen_range(0..50)).collect();
+        let mut expected = dists.clone();
+        expected.sort();
+
+        let mut results: SortedArray<u32, u32, 8> = SortedArray::new(u32::MAX);
+        for (item, &distance) in dists.iter().enumerate() {
+            results.add_nearest(NearestNeighbour {
+                distance,
+                item: item as u32,
+            });
+        }
+
+        let found: Vec<_> = results.as_slice().iter().map(|n| n.distance).collect();
+        assert_eq!(found, expected[..8]);
+
+        // underfull arrays are padded
+        let mut results: SortedArray<u32, u32, 4> = SortedArray::new(u32::MAX);
+        results.add_nearest(NearestNeighbour {
+            distance: 3,
+            item: 0,
+        });
+        results.add_nearest(NearestNeighbour {
+            distance: 1,
+            item: 1,
+        });
+        assert_eq!(results.as_slice().len(), 2);
+        let distances = results.into_array().map(|n| n.distance);
+        assert_eq!(distances, [1, 3, u32::MAX, u32::MAX]);
+    }
+
+    #[test]
+    fn result_buffer_keeps_the_nearest_n_regardless_of_capacity() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(66);
+
+        let dists: Vec<u32> = (0..200).map(|_| rng.gen_range(0..50)).collect();
+        let mut expected = dists.clone();
+        expected.sort();
+
+        let mut reused = Vec::with_capacity(100);
+        reused.push(NearestNeighbour {
+            distance: 0,
+            item: 999,
+        });
+        let mut results = ResultBuffer::new(reused, 8);
+        for (item, &distance) in dists.iter().enumerate() {
+            results.add_nearest(NearestNeighbour {
+                distance,
+                item: item as u32,
+            });
+        }
+
+        let found: Vec<_> = results.into_vec().iter().map(|n| n.distance).collect();
+        assert_eq!(found, expected[..8]);
+
+        let mut results: ResultBuffer<u32, u32> = ResultBuffer::new(Vec::new(), 0);
+        results.add_nearest(NearestNeighbour {
+            distance: 3,
+            item: 0,
+        });
+        assert!(results.into_vec().is_empty());
+    }
+
+    #[test]
+    fn kth_distance_keeps_the_kth_smallest() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(12);
+
+        let dists: Vec<u32> = (0..200).map(|_| rng.gen_range(0..500)).collect();
+        let mut expected = dists.clone();
+        expected.sort();
+
+        for k in [1, 2, 7, 200] {
+            let mut results = KthDistance::new(k);
+            for (idx, &dist) in dists.iter().enumerate() {
+                assert_eq!(
+                    results.kth_dist(),
+                    (idx >= k).then(|| {
+                        let mut seen = dists[..idx].to_vec();
+                        seen.sort();
+                        seen[k - 1]
+                    })
+                );
+                results.push(dist);
+            }
+            assert_eq!(results.kth_dist(), Some(expected[k - 1]));
+        }
+
+        let mut results = KthDistance::new(201);
+        dists.iter().for_each(|&dist| results.push(dist));
+        assert_eq!(results.kth_dist(), None);
+    }
+}
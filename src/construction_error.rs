@@ -0,0 +1,106 @@
+//! Errors returned by the fallible and validating tree constructors
+
+use core::fmt;
+
+#[cfg(feature = "std")]
+use num_traits::float::FloatCore;
+
+/// The reasons that a tree could not be constructed from the points that it was given,
+/// as returned by constructors such as
+/// [`ImmutableKdTree::try_new_from_slice`](`crate::immutable::float::kdtree::ImmutableKdTree::try_new_from_slice`).
+///
+/// The equivalent unchecked constructors either panic in these situations or build a
+/// tree that gives wrong answers to some queries.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConstructionError {
+    /// The point at `index` has a NaN coordinate, which cannot be ordered against any
+    /// other value and so cannot be placed in the tree
+    NanCoordinate {
+        /// The index of the offending point within the source
+        index: usize,
+    },
+    /// The point at `index` has an infinite coordinate. Only rejected by the constructors
+    /// that validate their input in full, such as
+    /// [`ImmutableKdTree::new_from_slice_checked`](`crate::immutable::float::kdtree::ImmutableKdTree::new_from_slice_checked`)
+    InfiniteCoordinate {
+        /// The index of the offending point within the source
+        index: usize,
+    },
+    /// The point at `index` does not have the number of dimensions that the tree has
+    DimensionMismatch {
+        /// The index of the offending point within the source
+        index: usize,
+        /// The number of dimensions that the point has
+        point_dims: usize,
+        /// The number of dimensions that the tree has
+        tree_dims: usize,
+    },
+    /// More items share a position on one axis than fit in a single bucket, so a full
+    /// bucket could not be split in two
+    DuplicatePositions,
+    /// There are `len` points, which is more than the tree's item or index types can
+    /// address
+    TooManyItems {
+        /// The number of points in the source
+        len: usize,
+    },
+    /// A co-ordinate is finite, but too large in magnitude to be represented by the type
+    /// that a tree is being converted to
+    CoordinateOutOfRange,
+    /// Construction was stopped early through a
+    /// [`CancellationToken`](`crate::build_progress::CancellationToken`)
+    Cancelled,
+}
+
+impl fmt::Display for ConstructionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstructionError::NanCoordinate { index } => {
+                write!(f, "point {index} has a NaN coordinate")
+            }
+            ConstructionError::InfiniteCoordinate { index } => {
+                write!(f, "point {index} has an infinite coordinate")
+            }
+            ConstructionError::DimensionMismatch {
+                index,
+                point_dims,
+                tree_dims,
+            } => write!(
+                f,
+                "point {index} has {point_dims} dimensions, but the tree has {tree_dims}"
+            ),
+            ConstructionError::DuplicatePositions => write!(
+                f,
+                "Too many items with the same position on one axis. Bucket size must be increased to at least 1 more than the number of items with the same position on one axis."
+            ),
+            ConstructionError::TooManyItems { len } => {
+                write!(f, "{len} points is too many for the tree's item or index type")
+            }
+            ConstructionError::CoordinateOutOfRange => write!(
+                f,
+                "a co-ordinate is too large in magnitude for the tree's new axis type"
+            ),
+            ConstructionError::Cancelled => write!(f, "construction was cancelled"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ConstructionError {}
+
+/// Checks that every coordinate of `point`, the point at `index` within its source,
+/// is finite
+#[cfg(feature = "std")]
+pub(crate) fn check_finite<A: FloatCore>(
+    index: usize,
+    point: &[A],
+) -> Result<(), ConstructionError> {
+    if point.iter().any(|val| val.is_nan()) {
+        Err(ConstructionError::NanCoordinate { index })
+    } else if point.iter().any(|val| val.is_infinite()) {
+        Err(ConstructionError::InfiniteCoordinate { index })
+    } else {
+        Ok(())
+    }
+}
@@ -0,0 +1,14 @@
+//! What to do when adding an item at co-ordinates that are already occupied
+
+/// Decides what `add_with_policy` does when an item is already stored at identical
+/// co-ordinates to the one being added.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Add the item alongside any that are already stored there, as `add` does
+    #[default]
+    Allow,
+    /// Leave the tree unchanged, and have `add_with_policy` return `false`
+    Reject,
+    /// Remove any items that are already stored there before adding the item
+    Replace,
+}
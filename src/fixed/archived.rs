@@ -0,0 +1,486 @@
+//! Querying fixed point k-d trees that have been serialized with `rkyv`, in place, without
+//! deserializing them first.
+//!
+//! The types in the [`Fixed`](https://docs.rs/fixed/latest/fixed) crate don't implement
+//! `rkyv::Archive`, so a [`KdTree`] is first converted into a [`KdTreeRK`] that stores the
+//! raw bits of each co-ordinate. Once archived, that can be memory-mapped and queried through
+//! an [`ArchivedFixedKdTree`], which reinterprets the bits as fixed point values again.
+
+use std::ops::Rem;
+
+use az::{Az, Cast};
+
+use crate::fixed::kdtree::{
+    ArchivedKdTreeRK, Axis, KdTree, KdTreeRK, LeafNode, LeafNodeRK, StemNode, StemNodeRK,
+};
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{is_stem_index, Content, DistanceMetric, Index};
+
+impl<A, T, const K: usize, const B: usize, IDX> From<&KdTree<A, T, K, B, IDX>>
+    for KdTreeRK<A::Bits, T, K, B, IDX>
+where
+    A: Axis,
+    A::Bits: num_traits::PrimInt,
+    T: Content,
+    IDX: Index<T = IDX>,
+{
+    /// Copies a [`KdTree`] into a [`KdTreeRK`], which can be serialized with `rkyv`
+    fn from(tree: &KdTree<A, T, K, B, IDX>) -> Self {
+        KdTreeRK {
+            leaves: tree.leaves.iter().map(LeafNodeRK::from).collect(),
+            stems: tree.stems.iter().map(StemNodeRK::from).collect(),
+            root_index: tree.root_index,
+            size: tree.size,
+        }
+    }
+}
+
+impl<A, const K: usize, IDX> From<&StemNode<A, K, IDX>> for StemNodeRK<A::Bits, K, IDX>
+where
+    A: Axis,
+    A::Bits: num_traits::PrimInt,
+    IDX: Index<T = IDX>,
+{
+    fn from(stem: &StemNode<A, K, IDX>) -> Self {
+        StemNodeRK {
+            left: stem.left,
+            right: stem.right,
+            split_val: stem.split_val.to_bits(),
+        }
+    }
+}
+
+impl<A, T, const K: usize, const B: usize, IDX> From<&LeafNode<A, T, K, B, IDX>>
+    for LeafNodeRK<A::Bits, T, K, B, IDX>
+where
+    A: Axis,
+    A::Bits: num_traits::PrimInt,
+    T: Content,
+    IDX: Index<T = IDX>,
+{
+    fn from(leaf: &LeafNode<A, T, K, B, IDX>) -> Self {
+        LeafNodeRK {
+            content_points: leaf
+                .content_points
+                .map(|point| point.map(|val| val.to_bits())),
+            content_items: leaf.content_items,
+            size: leaf.size,
+        }
+    }
+}
+
+/// An archived [`KdTreeRK`], queried with the fixed point type `A` whose raw bits its
+/// co-ordinates hold.
+///
+/// Queries behave the same as those of the same names on [`KdTree`].
+///
+/// # Examples
+///
+/// ```rust
+/// use fixed::types::extra::U0;
+/// use fixed::FixedU16;
+/// use kiddo::fixed::archived::ArchivedFixedKdTree;
+/// use kiddo::fixed::distance::SquaredEuclidean;
+/// use kiddo::fixed::kdtree::{KdTree, KdTreeRK};
+///
+/// type Fxd = FixedU16<U0>;
+///
+/// let mut tree: KdTree<Fxd, u32, 3, 32, u32> = KdTree::new();
+/// tree.add(&[Fxd::from_num(1), Fxd::from_num(2), Fxd::from_num(5)], 100);
+/// tree.add(&[Fxd::from_num(2), Fxd::from_num(3), Fxd::from_num(6)], 101);
+///
+/// // these bytes could just as well be written to a file and memory-mapped back in
+/// let bytes = rkyv::to_bytes::<_, 256>(&KdTreeRK::from(&tree)).unwrap();
+/// let archived = unsafe { rkyv::archived_root::<KdTreeRK<u16, u32, 3, 32, u32>>(&bytes) };
+/// let archived: ArchivedFixedKdTree<Fxd, u32, 3, 32, u32> = ArchivedFixedKdTree::new(archived);
+///
+/// let nearest = archived.nearest_one::<SquaredEuclidean>(&[Fxd::from_num(2), Fxd::from_num(3), Fxd::from_num(5)]);
+///
+/// assert_eq!(nearest.item, 101);
+/// assert_eq!(nearest.distance, Fxd::from_num(1));
+/// ```
+pub struct ArchivedFixedKdTree<'a, A, T, const K: usize, const B: usize, IDX>
+where
+    A: Axis,
+    A::Bits: num_traits::PrimInt + rkyv::Archive<Archived = A::Bits>,
+    T: Content + rkyv::Archive<Archived = T>,
+    IDX: Index<T = IDX> + rkyv::Archive<Archived = IDX>,
+{
+    tree: &'a ArchivedKdTreeRK<A::Bits, T, K, B, IDX>,
+}
+
+impl<'a, A, T, const K: usize, const B: usize, IDX> ArchivedFixedKdTree<'a, A, T, K, B, IDX>
+where
+    A: Axis,
+    A::Bits: num_traits::PrimInt + rkyv::Archive<Archived = A::Bits>,
+    T: Content + rkyv::Archive<Archived = T>,
+    IDX: Index<T = IDX> + rkyv::Archive<Archived = IDX>,
+    usize: Cast<IDX>,
+{
+    /// Wraps an archived [`KdTreeRK`] whose co-ordinates are the bits of values of type `A`
+    pub fn new(tree: &'a ArchivedKdTreeRK<A::Bits, T, K, B, IDX>) -> Self {
+        ArchivedFixedKdTree { tree }
+    }
+
+    /// Returns the current number of elements stored in the tree
+    #[inline]
+    pub fn size(&self) -> T {
+        self.tree.size
+    }
+
+    /// Finds the nearest element to `query`, using the specified distance metric function.
+    #[inline]
+    pub fn nearest_one<D>(&self, query: &[A; K]) -> NearestNeighbour<A, T>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut nearest = NearestNeighbour {
+            distance: A::MAX,
+            item: T::zero(),
+        };
+        let mut off = [A::ZERO; K];
+
+        self.nearest_one_recurse::<D>(
+            query,
+            self.tree.root_index,
+            0,
+            &mut nearest,
+            &mut off,
+            A::ZERO,
+        );
+
+        nearest
+    }
+
+    fn nearest_one_recurse<D>(
+        &self,
+        query: &[A; K],
+        curr_node_idx: IDX,
+        split_dim: usize,
+        nearest: &mut NearestNeighbour<A, T>,
+        off: &mut [A; K],
+        rd: A,
+    ) where
+        D: DistanceMetric<A, K>,
+    {
+        if is_stem_index(curr_node_idx) {
+            let node = &self.tree.stems[curr_node_idx.az::<usize>()];
+            let split_val = A::from_bits(node.split_val);
+
+            let old_off = off[split_dim];
+            let new_off = query[split_dim].saturating_dist(split_val);
+
+            let [closer_node_idx, further_node_idx] = if query[split_dim] < split_val {
+                [node.left, node.right]
+            } else {
+                [node.right, node.left]
+            };
+            let next_split_dim = (split_dim + 1).rem(K);
+
+            self.nearest_one_recurse::<D>(query, closer_node_idx, next_split_dim, nearest, off, rd);
+
+            let rd = crate::traits::accumulate_dist(
+                rd,
+                D::dist1(new_off, old_off),
+                D::MAX_OF_AXES,
+                A::rd_update,
+            );
+
+            if rd <= nearest.distance {
+                off[split_dim] = new_off;
+                self.nearest_one_recurse::<D>(
+                    query,
+                    further_node_idx,
+                    next_split_dim,
+                    nearest,
+                    off,
+                    rd,
+                );
+                off[split_dim] = old_off;
+            }
+        } else {
+            self.for_each_in_leaf::<D>(query, curr_node_idx, |distance, item| {
+                if distance < nearest.distance {
+                    *nearest = NearestNeighbour { distance, item };
+                }
+            });
+        }
+    }
+
+    /// Finds the nearest `qty` elements to `query`, using the specified distance metric
+    /// function, sorted by distance.
+    #[inline]
+    pub fn nearest_n<D>(&self, query: &[A; K], qty: usize) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut results = Vec::with_capacity(qty.min(1024) + 1);
+        let mut off = [A::ZERO; K];
+
+        if qty > 0 {
+            self.nearest_n_recurse::<D>(
+                query,
+                qty,
+                self.tree.root_index,
+                0,
+                &mut results,
+                &mut off,
+                A::ZERO,
+            );
+        }
+
+        results
+    }
+
+    /// Adds elements to `results`, which is kept sorted and no longer than `qty`
+    #[allow(clippy::too_many_arguments)]
+    fn nearest_n_recurse<D>(
+        &self,
+        query: &[A; K],
+        qty: usize,
+        curr_node_idx: IDX,
+        split_dim: usize,
+        results: &mut Vec<NearestNeighbour<A, T>>,
+        off: &mut [A; K],
+        rd: A,
+    ) where
+        D: DistanceMetric<A, K>,
+    {
+        let max_dist = |results: &Vec<NearestNeighbour<A, T>>| {
+            if results.len() < qty {
+                A::MAX
+            } else {
+                results[qty - 1].distance
+            }
+        };
+
+        if is_stem_index(curr_node_idx) {
+            let node = &self.tree.stems[curr_node_idx.az::<usize>()];
+            let split_val = A::from_bits(node.split_val);
+
+            let old_off = off[split_dim];
+            let new_off = query[split_dim].saturating_dist(split_val);
+
+            let [closer_node_idx, further_node_idx] = if query[split_dim] < split_val {
+                [node.left, node.right]
+            } else {
+                [node.right, node.left]
+            };
+            let next_split_dim = (split_dim + 1).rem(K);
+
+            self.nearest_n_recurse::<D>(
+                query,
+                qty,
+                closer_node_idx,
+                next_split_dim,
+                results,
+                off,
+                rd,
+            );
+
+            let rd = crate::traits::accumulate_dist(
+                rd,
+                D::dist1(new_off, old_off),
+                D::MAX_OF_AXES,
+                A::rd_update,
+            );
+
+            if rd <= max_dist(results) {
+                off[split_dim] = new_off;
+                self.nearest_n_recurse::<D>(
+                    query,
+                    qty,
+                    further_node_idx,
+                    next_split_dim,
+                    results,
+                    off,
+                    rd,
+                );
+                off[split_dim] = old_off;
+            }
+        } else {
+            self.for_each_in_leaf::<D>(query, curr_node_idx, |distance, item| {
+                if results.len() < qty || distance < max_dist(results) {
+                    let entry = NearestNeighbour { distance, item };
+                    let idx = results.partition_point(|other| *other <= entry);
+                    results.insert(idx, entry);
+                    results.truncate(qty);
+                }
+            });
+        }
+    }
+
+    /// Finds all elements within `dist` of `query`, using the specified distance metric
+    /// function, sorted by distance.
+    #[inline]
+    pub fn within<D>(&self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut results = Vec::new();
+        let mut off = [A::ZERO; K];
+
+        self.within_recurse::<D>(
+            query,
+            dist,
+            self.tree.root_index,
+            0,
+            &mut results,
+            &mut off,
+            A::ZERO,
+        );
+        results.sort();
+
+        results
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn within_recurse<D>(
+        &self,
+        query: &[A; K],
+        radius: A,
+        curr_node_idx: IDX,
+        split_dim: usize,
+        results: &mut Vec<NearestNeighbour<A, T>>,
+        off: &mut [A; K],
+        rd: A,
+    ) where
+        D: DistanceMetric<A, K>,
+    {
+        if is_stem_index(curr_node_idx) {
+            let node = &self.tree.stems[curr_node_idx.az::<usize>()];
+            let split_val = A::from_bits(node.split_val);
+
+            let old_off = off[split_dim];
+            let new_off = query[split_dim].saturating_dist(split_val);
+
+            let [closer_node_idx, further_node_idx] = if query[split_dim] < split_val {
+                [node.left, node.right]
+            } else {
+                [node.right, node.left]
+            };
+            let next_split_dim = (split_dim + 1).rem(K);
+
+            self.within_recurse::<D>(
+                query,
+                radius,
+                closer_node_idx,
+                next_split_dim,
+                results,
+                off,
+                rd,
+            );
+
+            let rd = crate::traits::accumulate_dist(
+                rd,
+                D::dist1(new_off, old_off),
+                D::MAX_OF_AXES,
+                A::rd_update,
+            );
+
+            if rd <= radius {
+                off[split_dim] = new_off;
+                self.within_recurse::<D>(
+                    query,
+                    radius,
+                    further_node_idx,
+                    next_split_dim,
+                    results,
+                    off,
+                    rd,
+                );
+                off[split_dim] = old_off;
+            }
+        } else {
+            self.for_each_in_leaf::<D>(query, curr_node_idx, |distance, item| {
+                if distance < radius {
+                    results.push(NearestNeighbour { distance, item });
+                }
+            });
+        }
+    }
+
+    /// Calls `visitor` with the distance from `query` and the item of each element
+    /// in the leaf at `leaf_node_idx`
+    #[inline]
+    fn for_each_in_leaf<D>(&self, query: &[A; K], leaf_node_idx: IDX, mut visitor: impl FnMut(A, T))
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let leaf = &self.tree.leaves[(leaf_node_idx - IDX::leaf_offset()).az::<usize>()];
+
+        leaf.content_points
+            .iter()
+            .zip(leaf.content_items.iter())
+            .take(leaf.size.az::<usize>())
+            .for_each(|(point, &item)| {
+                let point = point.map(A::from_bits);
+                visitor(D::dist(query, &point), item);
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fixed::archived::ArchivedFixedKdTree;
+    use crate::fixed::distance::{Manhattan, SquaredEuclidean};
+    use crate::fixed::kdtree::{KdTree, KdTreeRK};
+    use fixed::types::extra::U14;
+    use fixed::FixedU16;
+    use rand::{Rng, SeedableRng};
+
+    type Fxd = FixedU16<U14>;
+
+    #[test]
+    fn can_query_archived_tree() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(71);
+        let mut tree: KdTree<Fxd, u32, 3, 32, u32> = KdTree::new();
+        for idx in 0..5_000 {
+            let point: [f32; 3] = rng.gen();
+            tree.add(&point.map(Fxd::from_num), idx);
+        }
+
+        let bytes = rkyv::to_bytes::<_, 256>(&KdTreeRK::from(&tree)).unwrap();
+        let archived = unsafe { rkyv::archived_root::<KdTreeRK<u16, u32, 3, 32, u32>>(&bytes) };
+        let archived: ArchivedFixedKdTree<Fxd, u32, 3, 32, u32> =
+            ArchivedFixedKdTree::new(archived);
+
+        assert_eq!(archived.size(), tree.size());
+
+        for _ in 0..100 {
+            let query: [f32; 3] = rng.gen();
+            let query = query.map(Fxd::from_num);
+
+            assert_eq!(
+                archived.nearest_one::<Manhattan>(&query).distance,
+                tree.nearest_one::<Manhattan>(&query).distance
+            );
+            assert_eq!(
+                archived
+                    .nearest_n::<Manhattan>(&query, 5)
+                    .iter()
+                    .map(|n| n.distance)
+                    .collect::<Vec<_>>(),
+                tree.nearest_n::<Manhattan>(&query, 5)
+                    .iter()
+                    .map(|n| n.distance)
+                    .collect::<Vec<_>>()
+            );
+
+            let radius = Fxd::from_num(0.01);
+            let mut within: Vec<_> = archived
+                .within::<SquaredEuclidean>(&query, radius)
+                .into_iter()
+                .map(|n| (n.distance, n.item))
+                .collect();
+            let mut expected: Vec<_> = tree
+                .within::<SquaredEuclidean>(&query, radius)
+                .into_iter()
+                .map(|n| (n.distance, n.item))
+                .collect();
+            within.sort();
+            expected.sort();
+            assert_eq!(within, expected);
+        }
+    }
+}
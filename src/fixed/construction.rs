@@ -1,8 +1,9 @@
+use crate::duplicate_policy::DuplicatePolicy;
 use crate::fixed::kdtree::{Axis, KdTree, LeafNode, StemNode};
 use crate::mirror_select_nth_unstable_by::mirror_select_nth_unstable_by;
 use crate::traits::{is_stem_index, Content, Index};
 use az::{Az, Cast};
-use std::ops::Rem;
+use core::ops::Rem;
 
 impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
     KdTree<A, T, K, B, IDX>
@@ -153,6 +154,221 @@ where
         removed
     }
 
+    /// Removes every entry with the given item from the tree, wherever it is located.
+    ///
+    /// Useful when the item is known but its co-ordinates are not. Unlike
+    /// [`remove`](KdTree::remove), which only visits the leaf that the point belongs in,
+    /// this visits every leaf, so takes time proportional to the size of the tree.
+    ///
+    /// Returns the number of entries removed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fixed::FixedU16;
+    /// use fixed::types::extra::U0;
+    /// use kiddo::fixed::kdtree::KdTree;
+    ///
+    /// type Fxd = FixedU16<U0>;
+    ///
+    /// let mut tree: KdTree<Fxd, u32, 3, 32, u32> = KdTree::new();
+    ///
+    /// tree.add(&[Fxd::from_num(1), Fxd::from_num(2), Fxd::from_num(5)], 100);
+    /// tree.add(&[Fxd::from_num(2), Fxd::from_num(3), Fxd::from_num(6)], 200);
+    /// tree.add(&[Fxd::from_num(3), Fxd::from_num(4), Fxd::from_num(7)], 100);
+    ///
+    /// assert_eq!(tree.remove_item(100), 2);
+    /// assert_eq!(tree.size(), 1);
+    /// ```
+    pub fn remove_item(&mut self, item: T) -> usize {
+        let mut removed: usize = 0;
+
+        for leaf_node in &mut self.leaves {
+            let mut p_index = 0;
+            while p_index < leaf_node.size.az::<usize>() {
+                if leaf_node.content_items[p_index] == item {
+                    let last = leaf_node.size.az::<usize>() - 1;
+                    leaf_node.content_points[p_index] = leaf_node.content_points[last];
+                    leaf_node.content_items[p_index] = leaf_node.content_items[last];
+
+                    self.size -= T::one();
+                    removed += 1;
+                    leaf_node.size = leaf_node.size - IDX::one();
+                } else {
+                    p_index += 1;
+                }
+            }
+        }
+
+        removed
+    }
+
+    /// Adds an item to the tree, with `policy` deciding what happens if an item is already
+    /// stored at identical co-ordinates.
+    ///
+    /// With [`DuplicatePolicy::Allow`] this is the same as [`add`](KdTree::add). Otherwise,
+    /// only the leaf that the point belongs in needs to be checked for duplicates, so this is
+    /// both quicker and less error-prone than querying the tree before adding or removing.
+    ///
+    /// Returns `false`, leaving the tree unchanged, if the item was rejected as a duplicate.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fixed::FixedU16;
+    /// use fixed::types::extra::U14;
+    /// use kiddo::DuplicatePolicy;
+    /// use kiddo::fixed::kdtree::KdTree;
+    ///
+    /// type Fxd = FixedU16<U14>;
+    ///
+    /// let mut tree: KdTree<Fxd, u32, 3, 32, u32> = KdTree::new();
+    /// let point = [Fxd::from_num(1), Fxd::from_num(2), Fxd::from_num(3)];
+    /// tree.add(&point, 100);
+    ///
+    /// assert!(!tree.add_with_policy(&point, 101, DuplicatePolicy::Reject));
+    /// assert!(tree.add_with_policy(&point, 102, DuplicatePolicy::Replace));
+    ///
+    /// assert_eq!(tree.size(), 1);
+    /// assert_eq!(tree.remove(&point, 102), 1);
+    /// ```
+    pub fn add_with_policy(&mut self, query: &[A; K], item: T, policy: DuplicatePolicy) -> bool {
+        match policy {
+            DuplicatePolicy::Allow => {}
+            DuplicatePolicy::Reject => {
+                if self.contains_point(query) {
+                    return false;
+                }
+            }
+            DuplicatePolicy::Replace => {
+                self.remove_point(query);
+            }
+        }
+
+        self.add(query, item);
+        true
+    }
+
+    /// The index of the leaf that `query` belongs in
+    fn leaf_idx_for(&self, query: &[A; K]) -> usize {
+        let mut stem_idx = self.root_index;
+        let mut split_dim = 0;
+
+        while is_stem_index(stem_idx) {
+            let stem_node = &self.stems[stem_idx.az::<usize>()];
+            stem_idx = if query[split_dim] < stem_node.split_val {
+                stem_node.left
+            } else {
+                stem_node.right
+            };
+
+            split_dim = (split_dim + 1).rem(K);
+        }
+
+        (stem_idx - IDX::leaf_offset()).az::<usize>()
+    }
+
+    /// Whether any item is stored at exactly `query`
+    fn contains_point(&self, query: &[A; K]) -> bool {
+        let leaf_node = &self.leaves[self.leaf_idx_for(query)];
+
+        leaf_node.content_points[..leaf_node.size.az::<usize>()].contains(query)
+    }
+
+    /// Removes every item that is stored at exactly `query`, returning how many there were
+    fn remove_point(&mut self, query: &[A; K]) -> usize {
+        let leaf_idx = self.leaf_idx_for(query);
+        let leaf_node = &mut self.leaves[leaf_idx];
+        let mut removed: usize = 0;
+
+        let mut p_index = 0;
+        while p_index < leaf_node.size.az::<usize>() {
+            if &leaf_node.content_points[p_index] == query {
+                let last = leaf_node.size.az::<usize>() - 1;
+                leaf_node.content_points[p_index] = leaf_node.content_points[last];
+                leaf_node.content_items[p_index] = leaf_node.content_items[last];
+
+                self.size -= T::one();
+                removed += 1;
+                leaf_node.size = leaf_node.size - IDX::one();
+            } else {
+                p_index += 1;
+            }
+        }
+
+        removed
+    }
+
+    /// Moves an item from `old_point` to `new_point`.
+    ///
+    /// Equivalent to calling [`remove`](KdTree::remove) followed by [`add`](KdTree::add),
+    /// but when both points belong in the same leaf, as is usually the case for small
+    /// movements, the item's co-ordinates are updated in place with a single traversal
+    /// of the tree.
+    ///
+    /// Returns the number of entries moved, which is zero if `item` was not found at
+    /// `old_point`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fixed::FixedU16;
+    /// use fixed::types::extra::U14;
+    /// use kiddo::fixed::kdtree::KdTree;
+    ///
+    /// type Fxd = FixedU16<U14>;
+    ///
+    /// let mut tree: KdTree<Fxd, u32, 3, 32, u32> = KdTree::new();
+    /// let old_point = [Fxd::from_num(1), Fxd::from_num(2), Fxd::from_num(3)];
+    /// let new_point = [Fxd::from_num(1), Fxd::from_num(2), Fxd::from_num(2.5)];
+    /// tree.add(&old_point, 100);
+    ///
+    /// assert_eq!(tree.update(&old_point, &new_point, 100), 1);
+    /// assert_eq!(tree.remove(&new_point, 100), 1);
+    /// ```
+    pub fn update(&mut self, old_point: &[A; K], new_point: &[A; K], item: T) -> usize {
+        let mut stem_idx = self.root_index;
+        let mut split_dim = 0;
+        let mut same_leaf = true;
+
+        while is_stem_index(stem_idx) {
+            let stem_node = &self.stems[stem_idx.az::<usize>()];
+            let is_left = old_point[split_dim] < stem_node.split_val;
+            same_leaf &= is_left == (new_point[split_dim] < stem_node.split_val);
+
+            stem_idx = if is_left {
+                stem_node.left
+            } else {
+                stem_node.right
+            };
+
+            split_dim = (split_dim + 1).rem(K);
+        }
+
+        if !same_leaf {
+            let moved = self.remove(old_point, item);
+            for _ in 0..moved {
+                self.add(new_point, item);
+            }
+            return moved;
+        }
+
+        let leaf_node = &mut self.leaves[(stem_idx - IDX::leaf_offset()).az::<usize>()];
+        let size = leaf_node.size.az::<usize>();
+        let mut moved: usize = 0;
+        for (point, leaf_item) in leaf_node.content_points[..size]
+            .iter_mut()
+            .zip(&leaf_node.content_items[..size])
+        {
+            if point == old_point && *leaf_item == item {
+                *point = *new_point;
+                moved += 1;
+            }
+        }
+
+        moved
+    }
+
     unsafe fn split(
         &mut self,
         leaf_idx: IDX,
@@ -285,6 +501,65 @@ where
     }
 }
 
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
+    KdTree<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    /// Creates a fixed point tree containing the same items as a float
+    /// [`KdTree`](crate::float::kdtree::KdTree), with each co-ordinate multiplied by
+    /// `scale` before being converted to `A`.
+    ///
+    /// Returns `None` if any of the scaled co-ordinates cannot be represented by `A`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fixed::FixedU16;
+    /// use fixed::types::extra::U0;
+    /// use kiddo::fixed::distance::SquaredEuclidean;
+    /// use kiddo::fixed::kdtree::KdTree;
+    ///
+    /// type Fxd = FixedU16<U0>;
+    ///
+    /// let mut float_tree: kiddo::KdTree<f64, 2> = kiddo::KdTree::new();
+    /// float_tree.add(&[0.25, 0.5], 100);
+    /// float_tree.add(&[0.75, 0.5], 101);
+    ///
+    /// let tree: KdTree<Fxd, u64, 2, 32, u32> = KdTree::try_from_float(&float_tree, 100.0).unwrap();
+    ///
+    /// assert_eq!(tree.size(), 2);
+    /// let nearest = tree.nearest_one::<SquaredEuclidean>(&[Fxd::from_num(70), Fxd::from_num(50)]);
+    /// assert_eq!(nearest.item, 101);
+    ///
+    /// // 0.75 * 100,000 is too large for a FixedU16<U0>
+    /// assert!(KdTree::<Fxd, u64, 2, 32, u32>::try_from_float(&float_tree, 100_000.0).is_none());
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn try_from_float<F, const FB: usize, FIDX>(
+        tree: &crate::float::kdtree::KdTree<F, T, K, FB, FIDX>,
+        scale: F,
+    ) -> Option<Self>
+    where
+        F: crate::float::kdtree::Axis + fixed::traits::ToFixed,
+        FIDX: Index<T = FIDX>,
+        usize: Cast<FIDX>,
+    {
+        let (points, items) = tree.contents();
+
+        let mut fixed_tree = Self::with_capacity(points.len());
+        for (point, item) in points.iter().zip(items) {
+            let mut fixed_point = [A::ZERO; K];
+            for (fixed, &float) in fixed_point.iter_mut().zip(point) {
+                *fixed = A::checked_from_num(float * scale)?;
+            }
+            fixed_tree.add(&fixed_point, item);
+        }
+
+        Some(fixed_tree)
+    }
+}
+
 impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
     FromIterator<([A; K], T)> for KdTree<A, T, K, B, IDX>
 where
@@ -350,6 +625,7 @@ mod tests {
     use fixed::types::extra::U16;
     use fixed::FixedU16;
 
+    use crate::duplicate_policy::DuplicatePolicy;
     use crate::fixed::kdtree::KdTree;
 
     type Fxd = FixedU16<U16>;
@@ -434,4 +710,95 @@ mod tests {
         assert_eq!(removed, 1);
         assert_eq!(tree.size(), 15);
     }
+
+    #[test]
+    fn can_update_an_items_position() {
+        let mut tree: KdTree<Fxd, u32, 4, 32, u32> = KdTree::new();
+        let mut points: Vec<[Fxd; 4]> = (0..1000)
+            .map(|_| rand::random::<[f32; 4]>().map(|v| n(v * 0.9 + 0.05)))
+            .collect();
+        for (idx, point) in points.iter().enumerate() {
+            tree.add(point, idx as u32);
+        }
+
+        for (idx, point) in points.iter_mut().enumerate() {
+            let new_point = if idx % 2 == 0 {
+                point.map(|v| v + n(0.0001))
+            } else {
+                rand::random::<[f32; 4]>().map(|v| n(v * 0.9 + 0.05))
+            };
+            assert_eq!(tree.update(point, &new_point, idx as u32), 1);
+            *point = new_point;
+        }
+
+        assert_eq!(tree.size(), 1000);
+        for (idx, point) in points.iter().enumerate() {
+            assert_eq!(tree.remove(point, idx as u32), 1);
+        }
+        assert_eq!(tree.size(), 0);
+    }
+
+    #[test]
+    fn can_remove_an_item_without_its_point() {
+        let mut tree: KdTree<Fxd, u32, 4, 32, u32> = KdTree::new();
+        for idx in 0..1000u32 {
+            let point = rand::random::<[f32; 4]>().map(|v| n(v * 0.9 + 0.05));
+            tree.add(&point, idx % 100);
+        }
+
+        assert_eq!(tree.remove_item(42), 10);
+        assert_eq!(tree.size(), 990);
+        assert!(tree.iter().all(|(item, _)| item != 42));
+    }
+
+    #[test]
+    fn can_add_with_a_duplicate_policy() {
+        let mut tree: KdTree<Fxd, u32, 4, 4, u32> = KdTree::new();
+        let points: Vec<[Fxd; 4]> = (0..50)
+            .map(|_| rand::random::<[f32; 4]>().map(|v| n(v * 0.9 + 0.05)))
+            .collect();
+
+        for (idx, point) in points.iter().enumerate() {
+            assert!(tree.add_with_policy(point, idx as u32, DuplicatePolicy::Reject));
+        }
+        for (idx, point) in points.iter().enumerate() {
+            assert!(!tree.add_with_policy(point, 100 + idx as u32, DuplicatePolicy::Reject));
+        }
+        assert_eq!(tree.size(), 50);
+
+        for (idx, point) in points.iter().enumerate() {
+            assert!(tree.add_with_policy(point, 200 + idx as u32, DuplicatePolicy::Allow));
+            assert!(tree.add_with_policy(point, 300 + idx as u32, DuplicatePolicy::Replace));
+        }
+        assert_eq!(tree.size(), 50);
+
+        let mut items: Vec<u32> = tree.iter().map(|(item, _)| item).collect();
+        items.sort();
+        assert_eq!(items, (300..350).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn can_convert_from_a_float_tree() {
+        let mut float_tree: crate::float::kdtree::KdTree<f32, u32, 4, 32, u32> =
+            crate::float::kdtree::KdTree::new();
+        for idx in 0..1000 {
+            float_tree.add(&rand::random::<[f32; 4]>().map(|x| x * 0.5), idx);
+        }
+
+        let tree: KdTree<Fxd, u32, 4, 32, u32> = KdTree::try_from_float(&float_tree, 1.5).unwrap();
+        assert_eq!(tree.size(), 1000);
+
+        let mut expected: Vec<_> = float_tree
+            .iter()
+            .map(|(item, point)| (item, point.map(|x| Fxd::from_num(x * 1.5))))
+            .collect();
+        let mut actual: Vec<_> = tree.iter().collect();
+        expected.sort_by_key(|&(item, _)| item);
+        actual.sort_by_key(|&(item, _)| item);
+        assert_eq!(actual, expected);
+
+        // FixedU16<U16> can only represent values in [0, 1)
+        float_tree.add(&[0.5, 0.5, 0.5, 0.5], 1000);
+        assert!(KdTree::<Fxd, u32, 4, 32, u32>::try_from_float(&float_tree, 2.0).is_none());
+    }
 }
@@ -5,6 +5,8 @@
 // #[cfg(any(target_arch = "x86_64"))]
 // use std::arch::x86_64::*;
 
+use az::{Az, SaturatingCastFrom};
+
 use crate::fixed::kdtree::Axis;
 use crate::traits::DistanceMetric;
 
@@ -100,3 +102,161 @@ impl<A: Axis, const K: usize> DistanceMetric<A, K> for SquaredEuclidean {
         diff * diff
     }
 }
+
+/// Returns the Chebyshev / L∞ distance between two points: the largest of the
+/// distances along each axis.
+///
+/// # Examples
+///
+/// ```rust
+/// use fixed::types::extra::U0;
+/// use fixed::FixedU16;
+/// use kiddo::traits::DistanceMetric;
+/// use kiddo::fixed::distance::Chebyshev;
+/// type Fxd = FixedU16<U0>;
+///
+/// let ZERO = Fxd::from_num(0);
+/// let ONE = Fxd::from_num(1);
+/// let TWO = Fxd::from_num(2);
+///
+/// assert_eq!(Chebyshev::dist(&[ZERO, ZERO], &[ZERO, ZERO]), ZERO);
+/// assert_eq!(Chebyshev::dist(&[ZERO, ZERO], &[ONE, ZERO]), ONE);
+/// assert_eq!(Chebyshev::dist(&[ZERO, TWO], &[ONE, ZERO]), TWO);
+/// ```
+pub struct Chebyshev {}
+
+impl<A: Axis, const K: usize> DistanceMetric<A, K> for Chebyshev {
+    #[inline]
+    fn dist(a: &[A; K], b: &[A; K]) -> A {
+        a.iter()
+            .zip(b.iter())
+            .map(|(&a_val, &b_val)| a_val.dist(b_val))
+            .fold(A::ZERO, Ord::max)
+    }
+
+    #[inline]
+    fn dist1(a: A, b: A) -> A {
+        a.dist(b)
+    }
+
+    const MAX_OF_AXES: bool = true;
+}
+
+/// Returns the Minkowski distance of order `P` between two points, raised to the
+/// power `P`.
+///
+/// As with [`SquaredEuclidean`], the final root is not taken, which preserves the
+/// same distance ordering. `Minkowski<1>` is equivalent to [`Manhattan`] and
+/// `Minkowski<2>` to [`SquaredEuclidean`]. `P` must be at least 1.
+///
+/// # Examples
+///
+/// ```rust
+/// use fixed::types::extra::U0;
+/// use fixed::FixedU16;
+/// use kiddo::traits::DistanceMetric;
+/// use kiddo::fixed::distance::Minkowski;
+/// type Fxd = FixedU16<U0>;
+///
+/// let ZERO = Fxd::from_num(0);
+/// let ONE = Fxd::from_num(1);
+/// let TWO = Fxd::from_num(2);
+/// let NINE = Fxd::from_num(9);
+///
+/// assert_eq!(Minkowski::<3>::dist(&[ZERO, ZERO], &[ZERO, ZERO]), ZERO);
+/// assert_eq!(Minkowski::<3>::dist(&[ZERO, ZERO], &[ONE, ZERO]), ONE);
+/// assert_eq!(Minkowski::<3>::dist(&[ZERO, TWO], &[ONE, ZERO]), NINE);
+/// ```
+pub struct Minkowski<const P: u32> {}
+
+impl<A: Axis, const K: usize, const P: u32> DistanceMetric<A, K> for Minkowski<P> {
+    #[inline]
+    fn dist(a: &[A; K], b: &[A; K]) -> A {
+        a.iter()
+            .zip(b.iter())
+            .map(|(&a_val, &b_val)| <Self as DistanceMetric<A, K>>::dist1(a_val, b_val))
+            .fold(A::ZERO, |a, b| a.saturating_add(b))
+    }
+
+    #[inline]
+    fn dist1(a: A, b: A) -> A {
+        const { assert!(P >= 1, "Minkowski order P must be at least 1") };
+        let diff: A = a.dist(b);
+        (1..P).fold(diff, |acc, _| acc.saturating_mul(diff))
+    }
+}
+
+/// Returns the squared euclidean distance between two points, computed without overflow.
+///
+/// [`SquaredEuclidean`] squares and sums the distances along each axis in the
+/// coordinate type itself, so for points that are far apart the result is too large
+/// to represent. This overflows (panicking in debug builds and wrapping in release
+/// builds) and gives wrong neighbours. `SquaredEuclideanWide` instead accumulates the
+/// squares of the underlying integers in a `u128`, and only converts the total back
+/// to the coordinate type at the end, saturating at its maximum value. Distances that
+/// can be represented are the same as those of [`SquaredEuclidean`], and distances that
+/// can't all become the maximum value, so they are never mistaken for near ones. The
+/// exact total is available from [`dist_wide`](SquaredEuclideanWide::dist_wide).
+///
+/// The accumulator can only itself overflow, and saturate, for coordinate types of more
+/// than 32 bits. Coordinate types of 128 bits are not supported.
+///
+/// # Examples
+///
+/// ```rust
+/// use fixed::types::extra::U0;
+/// use fixed::FixedU16;
+/// use kiddo::traits::DistanceMetric;
+/// use kiddo::fixed::distance::SquaredEuclideanWide;
+/// type Fxd = FixedU16<U0>;
+///
+/// let ZERO = Fxd::from_num(0);
+/// let TWO = Fxd::from_num(2);
+/// let FAR = Fxd::from_num(60_000);
+///
+/// assert_eq!(SquaredEuclideanWide::dist(&[ZERO, ZERO], &[TWO, TWO]), Fxd::from_num(8));
+/// // 60_000² is far too large for a FixedU16, so the distance saturates
+/// assert_eq!(SquaredEuclideanWide::dist(&[ZERO, ZERO], &[FAR, ZERO]), Fxd::MAX);
+/// assert_eq!(SquaredEuclideanWide::dist_wide(&[ZERO, ZERO], &[FAR, ZERO]), 3_600_000_000);
+/// ```
+pub struct SquaredEuclideanWide {}
+
+impl SquaredEuclideanWide {
+    /// Returns the exact squared euclidean distance between two points, as the sum of the
+    /// squares of the differences between the underlying integers of their coordinates.
+    ///
+    /// This is scaled by `2^(2 * A::FRAC_NBITS)` relative to the distance in the
+    /// coordinate type, and saturates at `u128::MAX`.
+    #[inline]
+    pub fn dist_wide<A: Axis, const K: usize>(a: &[A; K], b: &[A; K]) -> u128 {
+        a.iter()
+            .zip(b.iter())
+            .map(|(&a_val, &b_val)| Self::dist1_wide(a_val, b_val))
+            .fold(0, u128::saturating_add)
+    }
+
+    #[inline]
+    fn dist1_wide<A: Axis>(a: A, b: A) -> u128 {
+        let diff = (a.to_bits().az::<i128>() - b.to_bits().az::<i128>()).unsigned_abs();
+        diff.saturating_mul(diff)
+    }
+
+    /// Converts a sum of squares from [`dist_wide`](SquaredEuclideanWide::dist_wide) into
+    /// the coordinate type, saturating at its maximum value
+    #[inline]
+    fn narrow<A: Axis>(wide: u128) -> A {
+        A::from_bits(A::Bits::saturating_cast_from(wide >> A::FRAC_NBITS))
+    }
+}
+
+impl<A: Axis, const K: usize> DistanceMetric<A, K> for SquaredEuclideanWide {
+    #[inline]
+    fn dist(a: &[A; K], b: &[A; K]) -> A {
+        Self::narrow(Self::dist_wide(a, b))
+    }
+
+    #[inline]
+    fn dist1(a: A, b: A) -> A {
+        Self::narrow(Self::dist1_wide(a, b))
+    }
+}
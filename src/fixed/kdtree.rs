@@ -3,13 +3,15 @@
 //! via the [`Fixed`](https://docs.rs/fixed/1.21.0/fixed) crate, eg [`FixedU16<U14>`](https://docs.rs/fixed/1.21.0/fixed/struct.FixedU16.html) for a 16-bit fixed point number with 14 bits after the
 //! decimal point.
 
+use alloc::vec::Vec;
 use az::{Az, Cast};
+use core::cmp::PartialEq;
+use core::fmt::Debug;
 use divrem::DivCeil;
 use fixed::traits::Fixed;
-use std::cmp::PartialEq;
-use std::fmt::Debug;
 
-use crate::iter::TreeIter;
+use crate::generate_tree_stats;
+use crate::iter::{TreeItems, TreeIter};
 use crate::{
     iter::IterableTreeData,
     traits::{Content, Index},
@@ -43,9 +45,10 @@ impl<T: num_traits::Zero + Default + Debug + rkyv::Archive> AxisRK for T {}
 ///
 /// This is only required when using Rkyv to serialize to / deserialize from
 /// a [`FixedKdTree`](crate::fixed::kdtree::KdTree). The types in the [`Fixed`](https://docs.rs/fixed/1.21.0/fixed)  crate do not support [`Rkyv`](https://crates.io/crates/rkyv/0.7.39) yet.
-/// As a workaround, we need to [`std::mem::transmute`] a [`crate::fixed::kdtree::KdTree`] into
-/// an equivalent [`crate::fixed::kdtree::KdTreeRK`] before serializing via Rkyv,
-/// and vice-versa when deserializing.
+/// As a workaround, a [`crate::fixed::kdtree::KdTree`] is converted with `KdTreeRK::from` into
+/// an equivalent [`crate::fixed::kdtree::KdTreeRK`], holding the raw bits of each co-ordinate, before
+/// serializing via Rkyv. The archived tree can then be queried in place by wrapping it in an
+/// [`ArchivedFixedKdTree`](`crate::fixed::archived::ArchivedFixedKdTree`).
 #[cfg_attr(
     feature = "rkyv",
     derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
@@ -236,6 +239,58 @@ where
         tree
     }
 
+    /// Reserves capacity for at least `additional` more items to be added to the tree
+    /// without the stems or leaves needing to be reallocated.
+    ///
+    /// This assumes that the new leaves will be filled, which is only the case for trees that
+    /// are built in a balanced way. Leaves created by splits when adding items one at a time
+    /// are usually only partly filled, so some reallocation may still happen in that case.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fixed::FixedU16;
+    /// use fixed::types::extra::U0;
+    /// use kiddo::fixed::kdtree::KdTree;
+    ///
+    /// type Fxd = FixedU16<U0>;
+    ///
+    /// let mut tree: KdTree<Fxd, u32, 3, 32, u32> = KdTree::new();
+    /// tree.reserve(10_000);
+    /// let before = tree.memory_usage();
+    ///
+    /// for i in 0..100 {
+    ///     let point = [Fxd::from_num(i), Fxd::from_num(i * 37 % 100), Fxd::from_num(i * 13 % 100)];
+    ///     tree.add(&point, i);
+    /// }
+    ///
+    /// tree.shrink_to_fit();
+    /// assert!(tree.memory_usage() < before);
+    /// ```
+    pub fn reserve(&mut self, additional: usize) {
+        let additional_leaves = DivCeil::div_ceil(additional, B);
+        self.leaves.reserve(additional_leaves);
+        self.stems.reserve(additional_leaves);
+    }
+
+    /// Releases any capacity that the tree has allocated but is not using, such as after
+    /// a [`reserve`](KdTree::reserve) that was larger than needed.
+    ///
+    /// Leaves that have been emptied by removals are not freed, as the tree still refers
+    /// to them.
+    pub fn shrink_to_fit(&mut self) {
+        self.leaves.shrink_to_fit();
+        self.stems.shrink_to_fit();
+    }
+
+    /// Returns the approximate number of bytes that the tree occupies, including the
+    /// capacity that has been allocated but not yet used.
+    pub fn memory_usage(&self) -> usize {
+        size_of::<Self>()
+            + self.leaves.capacity() * size_of::<LeafNode<A, T, K, B, IDX>>()
+            + self.stems.capacity() * size_of::<StemNode<A, K, IDX>>()
+    }
+
     /// Returns the current number of elements stored in the tree
     ///
     /// # Examples
@@ -279,6 +334,108 @@ where
     pub fn iter(&self) -> impl Iterator<Item = (T, [A; K])> + '_ {
         TreeIter::new(self, B)
     }
+
+    /// Returns the point that `item` is stored at, or `None` if it isn't in the tree.
+    ///
+    /// If `item` has been added more than once, any one of its points may be returned.
+    /// Every leaf is scanned, so this takes time proportional to the size of the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fixed::FixedU16;
+    /// use fixed::types::extra::U0;
+    /// use kiddo::fixed::kdtree::KdTree;
+    ///
+    /// type Fxd = FixedU16<U0>;
+    ///
+    /// let point = [Fxd::from_num(1), Fxd::from_num(2), Fxd::from_num(3)];
+    /// let mut tree: KdTree<Fxd, u32, 3, 32, u32> = KdTree::new();
+    /// tree.add(&point, 10);
+    ///
+    /// assert_eq!(tree.get(10), Some(point));
+    /// assert!(!tree.contains_item(20));
+    /// ```
+    pub fn get(&self, item: T) -> Option<[A; K]> {
+        self.leaves.iter().find_map(|leaf| {
+            leaf.content_items[..leaf.size.az::<usize>()]
+                .iter()
+                .position(|&stored| stored == item)
+                .map(|idx| leaf.content_points[idx])
+        })
+    }
+
+    /// Returns whether `item` is stored in the tree. See [`get`](KdTree::get) for details.
+    pub fn contains_item(&self, item: T) -> bool {
+        self.get(item).is_some()
+    }
+
+    /// Iterates over mutable references to every item stored in the tree, in arbitrary
+    /// order, so that items can be renumbered in place, such as after the table of
+    /// entities that they refer to has been compacted.
+    ///
+    /// The points that the items are stored at are unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fixed::FixedU16;
+    /// use fixed::types::extra::U0;
+    /// use kiddo::fixed::kdtree::KdTree;
+    ///
+    /// type Fxd = FixedU16<U0>;
+    ///
+    /// let point = [Fxd::from_num(1), Fxd::from_num(2), Fxd::from_num(3)];
+    /// let mut tree: KdTree<Fxd, u32, 3, 32, u32> = KdTree::new();
+    /// tree.add(&point, 10);
+    ///
+    /// tree.iter_mut_items().for_each(|item| *item /= 10);
+    ///
+    /// assert_eq!(tree.get(1), Some(point));
+    /// ```
+    pub fn iter_mut_items(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        self.leaves.iter_mut().flat_map(|leaf| {
+            let size = leaf.size.az::<usize>();
+            leaf.content_items[..size].iter_mut()
+        })
+    }
+
+    /// Replaces every occurrence of the item `old` with `new`, leaving the points that
+    /// they are stored at unchanged. Every leaf is scanned, so this takes time
+    /// proportional to the size of the tree; use [`iter_mut_items`](KdTree::iter_mut_items)
+    /// to renumber many items in a single pass.
+    ///
+    /// Returns the number of entries that were relabelled.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fixed::FixedU16;
+    /// use fixed::types::extra::U0;
+    /// use kiddo::fixed::kdtree::KdTree;
+    ///
+    /// type Fxd = FixedU16<U0>;
+    ///
+    /// let point = [Fxd::from_num(1), Fxd::from_num(2), Fxd::from_num(3)];
+    /// let mut tree: KdTree<Fxd, u32, 3, 32, u32> = KdTree::new();
+    /// tree.add(&point, 10);
+    ///
+    /// assert_eq!(tree.relabel(10, 11), 1);
+    /// assert_eq!(tree.get(11), Some(point));
+    /// ```
+    pub fn relabel(&mut self, old: T, new: T) -> usize {
+        let mut relabelled = 0;
+        for item in self.iter_mut_items() {
+            if *item == old {
+                *item = new;
+                relabelled += 1;
+            }
+        }
+
+        relabelled
+    }
+
+    generate_tree_stats!();
 }
 
 impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
@@ -298,6 +455,17 @@ impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
     }
 }
 
+impl<'a, A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>> IntoIterator
+    for &'a KdTree<A, T, K, B, IDX>
+{
+    type Item = (T, [A; K]);
+    type IntoIter = TreeItems<'a, A, T, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        TreeItems::new(TreeIter::new(self, B))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -330,6 +498,36 @@ mod tests {
         assert_eq!(tree.size(), 0);
     }
 
+    #[test]
+    fn can_reserve_and_shrink_to_fit() {
+        let mut tree: KdTree<Fxd, u32, 4, 32, u32> = KdTree::with_capacity(0);
+        let initial = tree.memory_usage();
+
+        tree.reserve(32 * 100);
+        assert!(tree.leaves.capacity() >= 101);
+        assert!(tree.stems.capacity() >= 100);
+        let reserved = tree.memory_usage();
+        assert!(reserved > initial);
+
+        for idx in 0..100u32 {
+            tree.add(
+                &[
+                    Fxd::from_num(idx as f32 / 32.0),
+                    Fxd::from_num((idx * 37 % 100) as f32 / 32.0),
+                    Fxd::from_num((idx * 13 % 100) as f32 / 32.0),
+                    Fxd::from_num((idx * 7 % 100) as f32 / 32.0),
+                ],
+                idx,
+            );
+        }
+        assert_eq!(tree.memory_usage(), reserved);
+
+        tree.shrink_to_fit();
+        assert_eq!(tree.leaves.capacity(), tree.leaves.len());
+        assert!(tree.memory_usage() < reserved);
+        assert_eq!(tree.size(), 100);
+    }
+
     #[cfg(feature = "serde")]
     #[test]
     fn can_serde() {
@@ -1,6 +1,6 @@
+use alloc::collections::BinaryHeap;
 use az::{Az, Cast};
-use std::collections::BinaryHeap;
-use std::ops::Rem;
+use core::ops::Rem;
 
 use crate::best_neighbour::BestNeighbour;
 use crate::fixed::kdtree::{Axis, KdTree, LeafNode};
@@ -3,6 +3,4 @@ pub mod nearest_n;
 pub mod nearest_one;
 pub mod within;
 pub mod within_unsorted;
-
-#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
 pub mod within_unsorted_iter;
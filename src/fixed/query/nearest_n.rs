@@ -1,6 +1,7 @@
+use alloc::collections::BinaryHeap;
+use alloc::vec::Vec;
 use az::{Az, Cast};
-use std::collections::BinaryHeap;
-use std::ops::Rem;
+use core::ops::Rem;
 
 use crate::fixed::kdtree::{Axis, KdTree};
 use crate::nearest_neighbour::NearestNeighbour;
@@ -1,5 +1,5 @@
 use az::{Az, Cast};
-use std::ops::Rem;
+use core::ops::Rem;
 
 use crate::fixed::kdtree::{Axis, KdTree, LeafNode};
 use crate::nearest_neighbour::NearestNeighbour;
@@ -46,14 +46,14 @@ to not needing to allocate memory or maintain sorted results.
 
 #[cfg(test)]
 mod tests {
-    use crate::fixed::distance::Manhattan;
+    use crate::fixed::distance::{Chebyshev, Manhattan, Minkowski, SquaredEuclideanWide};
     use crate::fixed::kdtree::{Axis, KdTree};
     use crate::nearest_neighbour::NearestNeighbour;
     use crate::test_utils::{rand_data_fixed_u16_entry, rand_data_fixed_u16_point};
     use crate::traits::DistanceMetric;
-    use fixed::types::extra::U14;
+    use fixed::types::extra::{U0, U14};
     use fixed::FixedU16;
-    use rand::Rng;
+    use rand::{Rng, SeedableRng};
 
     type Fxd = FixedU16<U14>;
 
@@ -61,6 +61,83 @@ mod tests {
         Fxd::from_num(num)
     }
 
+    #[test]
+    fn wide_metric_finds_nearest_among_far_apart_points() {
+        type Int = FixedU16<U0>;
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(64);
+
+        // squared distances between the two clusters are far too large for a FixedU16
+        let points: Vec<[Int; 2]> = (0..3_100)
+            .map(|idx| {
+                let range = if idx < 3_000 {
+                    0..2_000
+                } else {
+                    50_000..60_000
+                };
+                [rng.gen_range(range.clone()), rng.gen_range(range)]
+            })
+            .map(|[x, y]: [u16; 2]| [Int::from_num(x), Int::from_num(y)])
+            .collect();
+
+        let mut tree: KdTree<Int, u32, 2, 32, u32> = KdTree::new();
+        for (idx, point) in points.iter().enumerate() {
+            tree.add(point, idx as u32);
+        }
+
+        for _ in 0..200 {
+            let query = [
+                Int::from_num(rng.gen_range(0u16..2_000)),
+                Int::from_num(rng.gen_range(0u16..2_000)),
+            ];
+            let expected = points
+                .iter()
+                .map(|point| SquaredEuclideanWide::dist_wide(&query, point))
+                .min()
+                .unwrap();
+
+            let result = tree.nearest_one::<SquaredEuclideanWide>(&query);
+
+            assert_eq!(
+                SquaredEuclideanWide::dist_wide(&query, &points[result.item as usize]),
+                expected
+            );
+            assert_eq!(result.distance.to_num::<u128>(), expected);
+        }
+    }
+
+    #[test]
+    fn minkowski_saturates_for_near_max_coordinates() {
+        type Int = FixedU16<U0>;
+
+        let far = [Int::MAX, Int::MAX - Int::ONE];
+        let near = [Int::from_num(3), Int::from_num(1)];
+        assert_eq!(
+            Minkowski::<3>::dist(&[Int::ZERO, Int::ZERO], &far),
+            Int::MAX
+        );
+        assert_eq!(
+            <Minkowski<3> as DistanceMetric<Int, 2>>::dist1(Int::MAX, Int::ZERO),
+            Int::MAX
+        );
+
+        let mut tree: KdTree<Int, u32, 2, 32, u32> = KdTree::new();
+        tree.add(&far, 0);
+        tree.add(&near, 1);
+
+        let result = tree.nearest_one::<Minkowski<3>>(&[Int::ZERO, Int::ZERO]);
+        assert_eq!(
+            result,
+            NearestNeighbour {
+                distance: Int::from_num(28),
+                item: 1
+            }
+        );
+        assert_eq!(
+            tree.nearest_one::<Minkowski<3>>(&[Int::MAX, Int::MAX]).item,
+            0
+        );
+    }
+
     #[test]
     fn can_query_nearest_one_item() {
         let mut tree: KdTree<Fxd, u32, 4, 4, u32> = KdTree::new();
@@ -143,6 +220,65 @@ mod tests {
         }
     }
 
+    #[test]
+    fn can_query_nearest_one_item_with_chebyshev() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+
+        let content_to_add: Vec<([Fxd; 4], u32)> = (0..TREE_SIZE)
+            .map(|_| rand_data_fixed_u16_entry::<U14, u32, 4>())
+            .collect();
+
+        let mut tree: KdTree<Fxd, u32, 4, 4, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        for _ in 0..NUM_QUERIES {
+            let query_point = rand_data_fixed_u16_point::<U14, 4>();
+
+            let expected = content_to_add
+                .iter()
+                .map(|(p, _)| Chebyshev::dist(&query_point, p))
+                .min()
+                .unwrap();
+
+            let result = tree.nearest_one::<Chebyshev>(&query_point);
+
+            assert_eq!(result.distance, expected);
+        }
+    }
+
+    #[test]
+    fn can_query_nearest_one_within() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+
+        let content_to_add: Vec<([Fxd; 4], u32)> = (0..TREE_SIZE)
+            .map(|_| rand_data_fixed_u16_entry::<U14, u32, 4>())
+            .collect();
+
+        let mut tree: KdTree<Fxd, u32, 4, 4, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        for _ in 0..NUM_QUERIES {
+            let query_point = rand_data_fixed_u16_point::<U14, 4>();
+            let expected = linear_search(&content_to_add, &query_point);
+
+            for max_dist in [n(0.05), n(0.2), n(0.8)] {
+                let result = tree.nearest_one_within::<Manhattan>(&query_point, max_dist);
+
+                if expected.distance < max_dist {
+                    assert_eq!(result.unwrap().distance, expected.distance);
+                } else {
+                    assert_eq!(result, None);
+                }
+            }
+        }
+    }
+
     fn linear_search<A: Axis, const K: usize>(
         content: &[([A; K], u32)],
         query_point: &[A; K],
@@ -1,3 +1,4 @@
+use alloc::vec::Vec;
 use az::Cast;
 
 use crate::fixed::kdtree::{Axis, KdTree};
@@ -1,5 +1,6 @@
+use alloc::vec::Vec;
 use az::{Az, Cast};
-use std::ops::Rem;
+use core::ops::Rem;
 
 use crate::fixed::kdtree::{Axis, KdTree};
 use crate::nearest_neighbour::NearestNeighbour;
@@ -157,6 +158,57 @@ mod tests {
         }
     }
 
+    #[test]
+    fn within_unsorted_visit_visits_the_same_items_as_within_unsorted() {
+        const TREE_SIZE: usize = 10_000;
+        let radius: Fxd = n(0.2);
+
+        let content_to_add: Vec<([Fxd; 4], u32)> = (0..TREE_SIZE)
+            .map(|_| rand_data_fixed_u16_entry::<U14, u32, 4>())
+            .collect();
+
+        let mut tree: KdTree<Fxd, u32, 4, 4, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        for _ in 0..100 {
+            let query_point = rand_data_fixed_u16_point::<U14, 4>();
+            let expected = linear_search(&content_to_add, &query_point, radius);
+
+            let mut visited = vec![];
+            tree.within_unsorted_visit::<Manhattan, _>(&query_point, radius, |item, dist| {
+                visited.push((dist, item))
+            });
+
+            stabilize_sort(&mut visited);
+            assert_eq!(visited, expected);
+        }
+    }
+
+    #[test]
+    fn any_within_matches_whether_within_unsorted_is_empty() {
+        const TREE_SIZE: usize = 10_000;
+
+        let content_to_add: Vec<([Fxd; 4], u32)> = (0..TREE_SIZE)
+            .map(|_| rand_data_fixed_u16_entry::<U14, u32, 4>())
+            .collect();
+
+        let mut tree: KdTree<Fxd, u32, 4, 4, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        for radius in [n(0.05), n(0.1), n(0.2)] {
+            for _ in 0..100 {
+                let query_point = rand_data_fixed_u16_point::<U14, 4>();
+                let expected = !linear_search(&content_to_add, &query_point, radius).is_empty();
+
+                assert_eq!(tree.any_within::<Manhattan>(&query_point, radius), expected);
+            }
+        }
+    }
+
     fn linear_search<A: Axis, const K: usize>(
         content: &[([A; K], u32)],
         query_point: &[A; K],
@@ -1,6 +1,6 @@
+use alloc::vec;
 use az::{Az, Cast};
-use generator::{done, Gn, Scope};
-use std::ops::Rem;
+use core::ops::Rem;
 
 use crate::fixed::kdtree::{Axis, KdTree};
 use crate::nearest_neighbour::NearestNeighbour;
@@ -19,8 +19,6 @@ where
         (r#"Finds all elements within `dist` of `query`, using the specified
 distance metric function.
 
-Only available on x86_64 and aarch64 target architectures (this is due to a dependency
-on the generator crate).
 Returns an Iterator. Results are returned in arbitrary order. Faster than `within`.
 
 # Examples
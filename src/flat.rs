@@ -0,0 +1,304 @@
+//! Batch queries that read their query points straight out of a flat buffer of
+//! co-ordinates, such as an interleaved vertex buffer, rather than from `[A; K]` arrays.
+//!
+//! Each query point is `K` consecutive co-ordinates of the buffer. The first starts at
+//! `offset`, and each one after it starts `stride` elements after the one before, so
+//! `stride` is `K` for tightly packed points (`XYZXYZ...`), and larger for points that are
+//! padded (`XYZWXYZW...`) or interleaved with other attributes, such as normals or colours.
+//! Any elements after the last complete point are ignored.
+use std::num::NonZero;
+
+use az::Cast;
+
+use crate::float::kdtree::{Axis, KdTree};
+use crate::float_leaf_slice::leaf_slice::{LeafSliceFloat, LeafSliceFloatChunk};
+use crate::immutable::float::kdtree::ImmutableKdTree;
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{Content, DistanceMetric, Index};
+
+/// An iterator over the points held in a flat buffer of co-ordinates, returned by
+/// [`flat_points`].
+#[derive(Debug, Clone)]
+pub struct FlatPoints<'a, A, const K: usize> {
+    flat: &'a [A],
+    next: usize,
+    stride: usize,
+}
+
+/// Iterates over the points held in `flat`, the first of which starts at `offset`, and
+/// each of which starts `stride` elements after the one before.
+///
+/// Each point is copied into an array as it is reached, so the buffer as a whole is never
+/// copied. This allows any query to be made for each point in the buffer.
+///
+/// # Panics
+///
+/// Panics if `stride` is less than `K`, or is zero.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::flat::flat_points;
+///
+/// // positions, each followed by a texture co-ordinate
+/// let vertices = [1.0, 2.0, 3.0, 0.5, 4.0, 5.0, 6.0, 0.25];
+/// let points: Vec<[f64; 3]> = flat_points(&vertices, 0, 4).collect();
+///
+/// assert_eq!(points, vec![[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]);
+/// ```
+pub fn flat_points<A: Copy, const K: usize>(
+    flat: &[A],
+    offset: usize,
+    stride: usize,
+) -> FlatPoints<'_, A, K> {
+    assert!(
+        stride >= K && stride > 0,
+        "stride must be at least {K} and non-zero, but got {stride}"
+    );
+
+    FlatPoints {
+        flat,
+        next: offset,
+        stride,
+    }
+}
+
+impl<A: Copy, const K: usize> Iterator for FlatPoints<'_, A, K> {
+    type Item = [A; K];
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let point = self.flat.get(self.next..self.next.checked_add(K)?)?;
+        self.next = self.next.saturating_add(self.stride);
+
+        Some(std::array::from_fn(|dim| point[dim]))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = match self.flat.len().checked_sub(self.next) {
+            Some(len) if len >= K => (len - K) / self.stride + 1,
+            _ => 0,
+        };
+
+        (remaining, Some(remaining))
+    }
+}
+
+impl<A: Copy, const K: usize> ExactSizeIterator for FlatPoints<'_, A, K> {}
+
+impl<A, T, const K: usize, const B: usize> ImmutableKdTree<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    usize: Cast<T>,
+{
+    /// Finds the nearest element to each point held in the flat buffer `flat`, using the
+    /// specified distance metric function.
+    ///
+    /// The points are read as described by [`flat_points`]. Returns one result per point, in
+    /// the order that the points lie in the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stride` is less than `K`, or is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    /// use kiddo::SquaredEuclidean;
+    ///
+    /// let content: Vec<[f32; 3]> = vec![[1.0, 2.0, 5.0], [2.0, 3.0, 6.0]];
+    /// let tree: ImmutableKdTree<f32, u32, 3, 32> = ImmutableKdTree::new_from_slice(&content);
+    ///
+    /// // XYZW vertices, with a header of two elements before the first
+    /// let vertices = [0.0, 0.0, 2.0, 3.0, 5.9, 1.0, 1.0, 2.0, 5.1, 1.0];
+    /// let nearest = tree.nearest_one_flat::<SquaredEuclidean>(&vertices, 2, 4);
+    ///
+    /// assert_eq!(nearest.iter().map(|n| n.item).collect::<Vec<_>>(), vec![1, 0]);
+    /// ```
+    pub fn nearest_one_flat<D>(
+        &self,
+        flat: &[A],
+        offset: usize,
+        stride: usize,
+    ) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        flat_points(flat, offset, stride)
+            .map(|query| self.nearest_one::<D>(&query))
+            .collect()
+    }
+
+    /// Finds the nearest `max_qty` elements to each point held in the flat buffer `flat`,
+    /// sorted by distance.
+    ///
+    /// The points are read as described by [`flat_points`]. Returns one `Vec` of results per
+    /// point, in the order that the points lie in the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stride` is less than `K`, or is zero.
+    pub fn nearest_n_flat<D>(
+        &self,
+        flat: &[A],
+        offset: usize,
+        stride: usize,
+        max_qty: NonZero<usize>,
+    ) -> Vec<Vec<NearestNeighbour<A, T>>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        flat_points(flat, offset, stride)
+            .map(|query| self.nearest_n::<D>(&query, max_qty))
+            .collect()
+    }
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
+    KdTree<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    /// Finds the nearest element to each point held in the flat buffer `flat`, using the
+    /// specified distance metric function.
+    ///
+    /// The points are read as described by [`flat_points`]. Returns one result per point, in
+    /// the order that the points lie in the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stride` is less than `K`, or is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{KdTree, SquaredEuclidean};
+    ///
+    /// let mut tree: KdTree<f64, 2> = KdTree::new();
+    /// tree.add(&[1.0, 2.0], 100);
+    /// tree.add(&[3.0, 4.0], 101);
+    ///
+    /// let nearest = tree.nearest_one_flat::<SquaredEuclidean>(&[3.1, 4.1, 0.9, 2.1], 0, 2);
+    ///
+    /// assert_eq!(nearest.iter().map(|n| n.item).collect::<Vec<_>>(), vec![101, 100]);
+    /// ```
+    pub fn nearest_one_flat<D>(
+        &self,
+        flat: &[A],
+        offset: usize,
+        stride: usize,
+    ) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        flat_points(flat, offset, stride)
+            .map(|query| self.nearest_one::<D>(&query))
+            .collect()
+    }
+
+    /// Finds the nearest `max_qty` elements to each point held in the flat buffer `flat`,
+    /// sorted by distance.
+    ///
+    /// The points are read as described by [`flat_points`]. Returns one `Vec` of results per
+    /// point, in the order that the points lie in the buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stride` is less than `K`, or is zero.
+    pub fn nearest_n_flat<D>(
+        &self,
+        flat: &[A],
+        offset: usize,
+        stride: usize,
+        max_qty: usize,
+    ) -> Vec<Vec<NearestNeighbour<A, T>>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        flat_points(flat, offset, stride)
+            .map(|query| self.nearest_n::<D>(&query, max_qty))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::flat_points;
+    use crate::float::kdtree::KdTree;
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use crate::SquaredEuclidean;
+    use rand::{Rng, SeedableRng};
+    use std::num::NonZero;
+
+    #[test]
+    fn reads_padded_and_offset_points() {
+        let flat: Vec<u32> = (0..23).collect();
+
+        let points: Vec<[u32; 3]> = flat_points(&flat, 2, 5).collect();
+        assert_eq!(
+            points,
+            vec![[2, 3, 4], [7, 8, 9], [12, 13, 14], [17, 18, 19]]
+        );
+        assert_eq!(flat_points::<_, 3>(&flat, 2, 5).len(), 4);
+
+        // the last point needs only its co-ordinates to be present, not its padding
+        assert_eq!(flat_points::<_, 3>(&flat, 0, 5).len(), 5);
+        assert_eq!(flat_points::<_, 3>(&flat, 21, 3).len(), 0);
+        assert_eq!(flat_points::<_, 3>(&flat, 100, 3).len(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_a_stride_shorter_than_a_point() {
+        let _ = flat_points::<f64, 3>(&[0.0; 9], 0, 2);
+    }
+
+    #[test]
+    fn flat_queries_match_array_queries() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(19);
+        let points: Vec<[f64; 3]> = (0..2_000).map(|_| rng.gen()).collect();
+        let queries: Vec<[f64; 3]> = (0..100).map(|_| rng.gen()).collect();
+
+        // XYZW, after a single element of header
+        let mut flat = vec![-1.0];
+        for query in &queries {
+            flat.extend_from_slice(query);
+            flat.push(f64::NAN);
+        }
+
+        let immutable: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+        let mut mutable: KdTree<f64, u32, 3, 32, u32> = KdTree::new();
+        points
+            .iter()
+            .enumerate()
+            .for_each(|(idx, point)| mutable.add(point, idx as u32));
+
+        let max_qty = NonZero::new(5).unwrap();
+        let immutable_one = immutable.nearest_one_flat::<SquaredEuclidean>(&flat, 1, 4);
+        let immutable_n = immutable.nearest_n_flat::<SquaredEuclidean>(&flat, 1, 4, max_qty);
+        let mutable_one = mutable.nearest_one_flat::<SquaredEuclidean>(&flat, 1, 4);
+        let mutable_n = mutable.nearest_n_flat::<SquaredEuclidean>(&flat, 1, 4, max_qty.get());
+
+        assert_eq!(immutable_one.len(), queries.len());
+        for (idx, query) in queries.iter().enumerate() {
+            assert_eq!(
+                immutable_one[idx],
+                immutable.nearest_one::<SquaredEuclidean>(query)
+            );
+            assert_eq!(
+                immutable_n[idx],
+                immutable.nearest_n::<SquaredEuclidean>(query, max_qty)
+            );
+            assert_eq!(
+                mutable_one[idx],
+                mutable.nearest_one::<SquaredEuclidean>(query)
+            );
+            assert_eq!(
+                mutable_n[idx],
+                mutable.nearest_n::<SquaredEuclidean>(query, max_qty.get())
+            );
+        }
+    }
+}
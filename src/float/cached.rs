@@ -0,0 +1,410 @@
+//! A wrapper around the float [`KdTree`] that caches the results of recent queries.
+//!
+//! Useful when the same queries get issued over and over again, for example by a
+//! dashboard that repeatedly asks for what is "near me".
+
+use std::any::TypeId;
+use std::collections::{BTreeMap, HashMap};
+
+use az::Cast;
+
+use crate::float::kdtree::{Axis, KdTree};
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{Content, DistanceMetric, Index};
+
+/// Wraps a float [`KdTree`], caching the results of up to `capacity` of the most
+/// recently used queries.
+///
+/// Queries are cached per query point, query method, metric and parameters. The cache
+/// is cleared whenever the tree is modified through [`add`](CachedTree::add) or
+/// [`remove`](CachedTree::remove), so cached results are always the same as those
+/// that the wrapped tree would return.
+///
+/// By default, only queries for exactly the same point share a cache entry. Setting a
+/// resolution with [`with_resolution`](CachedTree::with_resolution) snaps each query
+/// point to the nearest multiple of the resolution before the query is performed,
+/// allowing nearby queries to share a cache entry, at the cost of the results being
+/// for the snapped query point.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::float::cached::CachedTree;
+/// use kiddo::{KdTree, SquaredEuclidean};
+///
+/// let mut tree: KdTree<f64, 2> = KdTree::new();
+/// tree.add(&[1.0, 2.0], 100);
+/// tree.add(&[5.0, 5.0], 101);
+///
+/// let mut cached = CachedTree::new(tree, 128);
+///
+/// let nearest = cached.nearest_one::<SquaredEuclidean>(&[1.0, 1.0]);
+/// assert_eq!(nearest.item, 100);
+/// let nearest = cached.nearest_one::<SquaredEuclidean>(&[1.0, 1.0]);
+/// assert_eq!(nearest.item, 100);
+/// assert_eq!((cached.hits(), cached.misses()), (1, 1));
+///
+/// // modifying the tree invalidates the cache
+/// cached.add(&[1.0, 1.0], 102);
+/// assert_eq!(cached.nearest_one::<SquaredEuclidean>(&[1.0, 1.0]).item, 102);
+/// ```
+#[derive(Debug)]
+pub struct CachedTree<A: Axis, T: Content, const K: usize, const B: usize, IDX> {
+    tree: KdTree<A, T, K, B, IDX>,
+    capacity: usize,
+    resolution: Option<A>,
+    entries: HashMap<QueryKey<K>, (CachedResult<A, T>, u64)>,
+    recency: BTreeMap<u64, QueryKey<K>>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum QueryKind {
+    NearestOne,
+    NearestN(usize),
+    Within(FloatBits),
+    WithinUnsorted(FloatBits),
+}
+
+type FloatBits = (u64, i16, i8);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct QueryKey<const K: usize> {
+    kind: QueryKind,
+    metric: TypeId,
+    query: [FloatBits; K],
+}
+
+#[derive(Debug, Clone)]
+enum CachedResult<A, T> {
+    One(NearestNeighbour<A, T>),
+    Many(Vec<NearestNeighbour<A, T>>),
+}
+
+impl<A, T, const K: usize, const B: usize, IDX> CachedTree<A, T, K, B, IDX>
+where
+    A: Axis,
+    T: Content,
+    IDX: Index<T = IDX>,
+    usize: Cast<IDX>,
+{
+    /// Wraps `tree`, caching the results of up to `capacity` of the most recent queries.
+    pub fn new(tree: KdTree<A, T, K, B, IDX>, capacity: usize) -> Self {
+        Self {
+            tree,
+            capacity,
+            resolution: None,
+            entries: HashMap::with_capacity(capacity),
+            recency: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Snaps query points to the nearest multiple of `resolution` on each axis before
+    /// querying, so that queries for nearby points share a cache entry.
+    pub fn with_resolution(mut self, resolution: A) -> Self {
+        assert!(resolution > A::zero(), "resolution must be positive");
+        self.resolution = Some(resolution);
+        self.clear_cache();
+        self
+    }
+
+    /// Returns a reference to the wrapped tree
+    pub fn tree(&self) -> &KdTree<A, T, K, B, IDX> {
+        &self.tree
+    }
+
+    /// Unwraps the tree, discarding the cache
+    pub fn into_inner(self) -> KdTree<A, T, K, B, IDX> {
+        self.tree
+    }
+
+    /// Adds an item to the wrapped tree, clearing the cache.
+    pub fn add(&mut self, query: &[A; K], item: T) {
+        self.tree.add(query, item);
+        self.clear_cache();
+    }
+
+    /// Removes an item from the wrapped tree, clearing the cache if anything was removed.
+    pub fn remove(&mut self, query: &[A; K], item: T) -> usize {
+        let removed = self.tree.remove(query, item);
+        if removed > 0 {
+            self.clear_cache();
+        }
+        removed
+    }
+
+    /// Discards all cached results.
+    pub fn clear_cache(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    /// Returns the number of queries whose results are currently cached
+    pub fn cached_len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the number of queries that were answered from the cache
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Returns the number of queries that had to be performed on the wrapped tree
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Finds the nearest element to `query`, using the cached result if present.
+    ///
+    /// See [`KdTree::nearest_one`] for details.
+    pub fn nearest_one<D>(&mut self, query: &[A; K]) -> NearestNeighbour<A, T>
+    where
+        D: DistanceMetric<A, K> + 'static,
+    {
+        let query = self.snap(query);
+        let result = self.cached_or_perform::<D>(QueryKind::NearestOne, &query, |tree| {
+            CachedResult::One(tree.nearest_one::<D>(&query))
+        });
+        match result {
+            CachedResult::One(nearest) => nearest,
+            CachedResult::Many(_) => unreachable!(),
+        }
+    }
+
+    /// Finds the nearest `qty` elements to `query`, using the cached result if present.
+    ///
+    /// See [`KdTree::nearest_n`] for details.
+    pub fn nearest_n<D>(&mut self, query: &[A; K], qty: usize) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K> + 'static,
+    {
+        let query = self.snap(query);
+        self.get_many::<D>(QueryKind::NearestN(qty), &query, |tree| {
+            tree.nearest_n::<D>(&query, qty)
+        })
+    }
+
+    /// Finds all elements within `dist` of `query`, sorted by distance, using the cached
+    /// result if present.
+    ///
+    /// See [`KdTree::within`] for details.
+    pub fn within<D>(&mut self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K> + 'static,
+    {
+        let query = self.snap(query);
+        self.get_many::<D>(QueryKind::Within(dist.integer_decode()), &query, |tree| {
+            tree.within::<D>(&query, dist)
+        })
+    }
+
+    /// Finds all elements within `dist` of `query`, in arbitrary order, using the cached
+    /// result if present.
+    ///
+    /// See [`KdTree::within_unsorted`] for details.
+    pub fn within_unsorted<D>(&mut self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K> + 'static,
+    {
+        let query = self.snap(query);
+        self.get_many::<D>(
+            QueryKind::WithinUnsorted(dist.integer_decode()),
+            &query,
+            |tree| tree.within_unsorted::<D>(&query, dist),
+        )
+    }
+
+    fn snap(&self, query: &[A; K]) -> [A; K] {
+        match self.resolution {
+            // adding zero normalizes -0.0 to 0.0, so that both share a cache entry
+            Some(resolution) => query.map(|v| (v / resolution).round() * resolution + A::zero()),
+            None => *query,
+        }
+    }
+
+    fn get_many<D>(
+        &mut self,
+        kind: QueryKind,
+        query: &[A; K],
+        perform: impl FnOnce(&KdTree<A, T, K, B, IDX>) -> Vec<NearestNeighbour<A, T>>,
+    ) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K> + 'static,
+    {
+        let result =
+            self.cached_or_perform::<D>(kind, query, |tree| CachedResult::Many(perform(tree)));
+        match result {
+            CachedResult::Many(items) => items,
+            CachedResult::One(_) => unreachable!(),
+        }
+    }
+
+    fn cached_or_perform<D>(
+        &mut self,
+        kind: QueryKind,
+        query: &[A; K],
+        perform: impl FnOnce(&KdTree<A, T, K, B, IDX>) -> CachedResult<A, T>,
+    ) -> CachedResult<A, T>
+    where
+        D: DistanceMetric<A, K> + 'static,
+    {
+        let key = QueryKey {
+            kind,
+            metric: TypeId::of::<D>(),
+            query: query.map(|v| v.integer_decode()),
+        };
+
+        self.tick += 1;
+        let tick = self.tick;
+
+        if let Some((result, last_used)) = self.entries.get_mut(&key) {
+            self.recency.remove(last_used);
+            *last_used = tick;
+            self.recency.insert(tick, key);
+            self.hits += 1;
+            return result.clone();
+        }
+
+        self.misses += 1;
+        let result = perform(&self.tree);
+
+        if self.capacity == 0 {
+            return result;
+        }
+
+        if self.entries.len() >= self.capacity {
+            if let Some((_, evicted)) = self.recency.pop_first() {
+                self.entries.remove(&evicted);
+            }
+        }
+
+        self.recency.insert(tick, key);
+        self.entries.insert(key, (result.clone(), tick));
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CachedTree;
+    use crate::float::distance::{Manhattan, SquaredEuclidean};
+    use crate::float::kdtree::KdTree;
+    use rand::{Rng, SeedableRng};
+
+    fn build_tree() -> KdTree<f64, u32, 2, 32, u32> {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(1);
+        let mut tree = KdTree::new();
+        for idx in 0..1000 {
+            tree.add(&rng.gen::<[f64; 2]>(), idx);
+        }
+        tree
+    }
+
+    #[test]
+    fn cached_results_match_the_tree() {
+        let tree = build_tree();
+        let expected_one = tree.nearest_one::<SquaredEuclidean>(&[0.5, 0.5]);
+        let expected_n = tree.nearest_n::<SquaredEuclidean>(&[0.5, 0.5], 5);
+        let expected_within = tree.within::<SquaredEuclidean>(&[0.5, 0.5], 0.01);
+
+        let mut cached = CachedTree::new(tree, 16);
+        for _ in 0..3 {
+            assert_eq!(
+                cached.nearest_one::<SquaredEuclidean>(&[0.5, 0.5]),
+                expected_one
+            );
+            assert_eq!(
+                cached.nearest_n::<SquaredEuclidean>(&[0.5, 0.5], 5),
+                expected_n
+            );
+            assert_eq!(
+                cached.within::<SquaredEuclidean>(&[0.5, 0.5], 0.01),
+                expected_within
+            );
+        }
+
+        assert_eq!(cached.misses(), 3);
+        assert_eq!(cached.hits(), 6);
+        assert_eq!(cached.cached_len(), 3);
+    }
+
+    #[test]
+    fn metric_and_parameters_are_part_of_the_key() {
+        let mut cached = CachedTree::new(build_tree(), 16);
+
+        cached.nearest_n::<SquaredEuclidean>(&[0.5, 0.5], 5);
+        cached.nearest_n::<Manhattan>(&[0.5, 0.5], 5);
+        cached.nearest_n::<SquaredEuclidean>(&[0.5, 0.5], 6);
+        cached.within::<SquaredEuclidean>(&[0.5, 0.5], 0.1);
+        cached.within::<SquaredEuclidean>(&[0.5, 0.5], 0.2);
+
+        assert_eq!(cached.misses(), 5);
+        assert_eq!(cached.hits(), 0);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cached = CachedTree::new(build_tree(), 2);
+
+        cached.nearest_one::<SquaredEuclidean>(&[0.1, 0.1]);
+        cached.nearest_one::<SquaredEuclidean>(&[0.2, 0.2]);
+        // touch the first query so that the second becomes least recently used
+        cached.nearest_one::<SquaredEuclidean>(&[0.1, 0.1]);
+        cached.nearest_one::<SquaredEuclidean>(&[0.3, 0.3]);
+        assert_eq!(cached.cached_len(), 2);
+        assert_eq!((cached.hits(), cached.misses()), (1, 3));
+
+        cached.nearest_one::<SquaredEuclidean>(&[0.1, 0.1]);
+        assert_eq!((cached.hits(), cached.misses()), (2, 3));
+        cached.nearest_one::<SquaredEuclidean>(&[0.2, 0.2]);
+        assert_eq!((cached.hits(), cached.misses()), (2, 4));
+    }
+
+    #[test]
+    fn mutation_invalidates_cache() {
+        let mut cached = CachedTree::new(build_tree(), 16);
+
+        let before = cached.nearest_one::<SquaredEuclidean>(&[0.5, 0.5]);
+        cached.add(&[0.5, 0.5], 5000);
+        assert_eq!(cached.cached_len(), 0);
+        assert_eq!(
+            cached.nearest_one::<SquaredEuclidean>(&[0.5, 0.5]).item,
+            5000
+        );
+
+        assert_eq!(cached.remove(&[0.5, 0.5], 5000), 1);
+        assert_eq!(cached.nearest_one::<SquaredEuclidean>(&[0.5, 0.5]), before);
+    }
+
+    #[test]
+    fn resolution_snaps_nearby_queries_to_the_same_entry() {
+        let tree = build_tree();
+        let expected = tree.nearest_n::<SquaredEuclidean>(&[0.5, 0.25], 3);
+
+        let mut cached = CachedTree::new(tree, 16).with_resolution(0.25);
+        assert_eq!(
+            cached.nearest_n::<SquaredEuclidean>(&[0.49, 0.26], 3),
+            expected
+        );
+        assert_eq!(
+            cached.nearest_n::<SquaredEuclidean>(&[0.51, 0.24], 3),
+            expected
+        );
+        assert_eq!((cached.hits(), cached.misses()), (1, 1));
+    }
+
+    #[test]
+    fn zero_capacity_never_caches() {
+        let mut cached = CachedTree::new(build_tree(), 0);
+        let first = cached.nearest_one::<SquaredEuclidean>(&[0.5, 0.5]);
+        let second = cached.nearest_one::<SquaredEuclidean>(&[0.5, 0.5]);
+        assert_eq!(first, second);
+        assert_eq!(cached.misses(), 2);
+    }
+}
@@ -1,19 +1,64 @@
+use crate::construction_error::{check_finite, ConstructionError};
+use crate::duplicate_policy::DuplicatePolicy;
 use crate::float::kdtree::{Axis, KdTree, LeafNode, StemNode};
+use crate::float_leaf_slice::leaf_slice::{LeafSliceFloat, LeafSliceFloatChunk};
+use crate::immutable::float::kdtree::ImmutableKdTree;
 use crate::mirror_select_nth_unstable_by::mirror_select_nth_unstable_by;
+use crate::split_policy::SplitPolicy;
 use crate::traits::{is_stem_index, Content, Index};
 use az::{Az, Cast};
+use divrem::DivCeil;
+use std::collections::BTreeMap;
 use std::ops::Rem;
 
+/// [`KdTree::merge`] rebuilds the merged tree, rather than adding the smaller tree's items
+/// to the larger one, when the smaller tree holds more than one in this many of the items
+const MERGE_REBUILD_RATIO: usize = 4;
+
 impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
     KdTree<A, T, K, B, IDX>
 where
     usize: Cast<IDX>,
 {
+    /// Adds an item to the tree, after checking that every coordinate of `query` is
+    /// finite.
+    ///
+    /// NaN coordinates cannot be ordered against the split values of the tree, and
+    /// infinite ones can end up as split values that no query can get past, so neither
+    /// can be stored reliably. [`add`](KdTree::add) only checks for NaNs, and only in
+    /// debug builds. Returns an error with an `index` of zero, leaving the tree
+    /// unchanged, if `query` has a non-finite coordinate.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ConstructionError, KdTree};
+    ///
+    /// let mut tree: KdTree<f64, 3> = KdTree::new();
+    ///
+    /// assert!(tree.add_checked(&[1.0, 2.0, 5.0], 100).is_ok());
+    /// assert_eq!(
+    ///     tree.add_checked(&[1.0, f64::NAN, 5.0], 101),
+    ///     Err(ConstructionError::NanCoordinate { index: 0 })
+    /// );
+    /// assert_eq!(tree.size(), 1);
+    /// ```
+    #[inline]
+    pub fn add_checked(&mut self, query: &[A; K], item: T) -> Result<(), ConstructionError> {
+        check_finite(0, query)?;
+        self.add(query, item);
+        Ok(())
+    }
+
     /// Adds an item to the tree.
     ///
     /// The first argument specifies co-ordinates of the point where the item is located.
     /// The second argument is an integer identifier / index for the item being stored.
     ///
+    /// Any number of items can share the same point, or the same value on an axis, even
+    /// more than fit in a bucket. Leaves that are full of them are chained together rather
+    /// than split, although queries near such points slow down as the chains grow.
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -27,6 +72,11 @@ where
     /// ```
     #[inline]
     pub fn add(&mut self, query: &[A; K], item: T) {
+        debug_assert!(
+            !query.iter().any(|val| val.is_nan()),
+            "point {query:?} has a NaN coordinate"
+        );
+
         unsafe {
             let mut stem_idx = self.root_index;
             let mut split_dim = 0;
@@ -53,7 +103,13 @@ where
             let mut leaf_node = self.leaves.get_unchecked_mut(leaf_idx.az::<usize>());
 
             if leaf_node.size == B.az::<IDX>() {
-                stem_idx = self.split(leaf_idx, split_dim, parent_idx, is_left_child);
+                stem_idx = self.split(
+                    leaf_idx,
+                    split_dim,
+                    *query.get_unchecked(split_dim),
+                    parent_idx,
+                    is_left_child,
+                );
                 let node = self.stems.get_unchecked_mut(stem_idx.az::<usize>());
 
                 leaf_idx = (if *query.get_unchecked(split_dim) < node.split_val {
@@ -101,15 +157,101 @@ where
     /// ```
     #[inline]
     pub fn remove(&mut self, query: &[A; K], item: T) -> usize {
+        self.remove_at_point_where(query, |stored| stored == item)
+    }
+
+    /// Removes every entry with the given item from the tree, wherever it is located.
+    ///
+    /// Useful when the item is known but its co-ordinates are not. Unlike
+    /// [`remove`](KdTree::remove), which only visits the leaf that the point belongs in,
+    /// this visits every leaf, so takes time proportional to the size of the tree.
+    ///
+    /// Returns the number of entries removed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, 3> = KdTree::new();
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100);
+    /// tree.add(&[2.0, 3.0, 6.0], 200);
+    /// tree.add(&[3.0, 4.0, 7.0], 100);
+    ///
+    /// assert_eq!(tree.remove_item(100), 2);
+    /// assert_eq!(tree.size(), 1);
+    /// ```
+    pub fn remove_item(&mut self, item: T) -> usize {
+        let mut removed: usize = 0;
+
+        for leaf_node in &mut self.leaves {
+            let mut p_index = 0;
+            while p_index < leaf_node.size.az::<usize>() {
+                if leaf_node.content_items[p_index] == item {
+                    let last = leaf_node.size.az::<usize>() - 1;
+                    leaf_node.content_points[p_index] = leaf_node.content_points[last];
+                    leaf_node.content_items[p_index] = leaf_node.content_items[last];
+
+                    self.size -= T::one();
+                    removed += 1;
+                    leaf_node.size = leaf_node.size - IDX::one();
+                } else {
+                    p_index += 1;
+                }
+            }
+        }
+
+        removed + self.remove_deferred_where(|_, deferred_item| deferred_item == item)
+    }
+
+    /// Adds an item to the tree, with `policy` deciding what happens if an item is already
+    /// stored at identical co-ordinates.
+    ///
+    /// With [`DuplicatePolicy::Allow`] this is the same as [`add`](KdTree::add). Otherwise,
+    /// only the leaf that the point belongs in needs to be checked for duplicates, so this is
+    /// both quicker and less error-prone than querying the tree before adding or removing.
+    ///
+    /// Returns `false`, leaving the tree unchanged, if the item was rejected as a duplicate.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{DuplicatePolicy, KdTree, SquaredEuclidean};
+    ///
+    /// let mut tree: KdTree<f64, 3> = KdTree::new();
+    /// tree.add(&[1.0, 2.0, 5.0], 100);
+    ///
+    /// assert!(!tree.add_with_policy(&[1.0, 2.0, 5.0], 101, DuplicatePolicy::Reject));
+    /// assert!(tree.add_with_policy(&[1.0, 2.0, 5.0], 102, DuplicatePolicy::Replace));
+    ///
+    /// assert_eq!(tree.size(), 1);
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[1.0, 2.0, 5.0]).item, 102);
+    /// ```
+    pub fn add_with_policy(&mut self, query: &[A; K], item: T, policy: DuplicatePolicy) -> bool {
+        match policy {
+            DuplicatePolicy::Allow => {}
+            DuplicatePolicy::Reject => {
+                if self.contains_point(query) {
+                    return false;
+                }
+            }
+            DuplicatePolicy::Replace => {
+                self.remove_point(query);
+            }
+        }
+
+        self.add(query, item);
+        true
+    }
+
+    /// The index of the leaf that `add` would put an item at `query` in
+    fn leaf_idx_for(&self, query: &[A; K]) -> usize {
         let mut stem_idx = self.root_index;
         let mut split_dim = 0;
-        let mut removed: usize = 0;
 
         while is_stem_index(stem_idx) {
-            let Some(stem_node) = self.stems.get_mut(stem_idx.az::<usize>()) else {
-                return removed;
-            };
-
+            let stem_node = &self.stems[stem_idx.az::<usize>()];
             stem_idx = if query[split_dim] < stem_node.split_val {
                 stem_node.left
             } else {
@@ -119,44 +261,928 @@ where
             split_dim = (split_dim + 1).rem(K);
         }
 
-        let leaf_idx = stem_idx - IDX::leaf_offset();
+        (stem_idx - IDX::leaf_offset()).az::<usize>()
+    }
 
-        if let Some(leaf_node) = self.leaves.get_mut(leaf_idx.az::<usize>()) {
-            let mut p_index = 0;
-            while p_index < leaf_node.size.az::<usize>() {
-                if &leaf_node.content_points[p_index] == query
-                    && leaf_node.content_items[p_index] == item
-                {
-                    leaf_node.content_points[p_index] =
-                        leaf_node.content_points[leaf_node.size.az::<usize>() - 1];
-                    leaf_node.content_items[p_index] =
-                        leaf_node.content_items[leaf_node.size.az::<usize>() - 1];
+    /// Whether any item is stored at exactly `query`
+    fn contains_point(&self, query: &[A; K]) -> bool {
+        let mut found = false;
+        for_each_leaf_idx_for(&self.stems, self.root_index, 0, query, &mut |leaf_idx| {
+            let leaf_node = &self.leaves[leaf_idx];
+            found |= leaf_node.content_points[..leaf_node.size.az::<usize>()].contains(query);
+        });
 
-                    self.size -= T::one();
-                    removed += 1;
-                    leaf_node.size = leaf_node.size - IDX::one();
-                } else {
-                    p_index += 1;
+        found
+            || self
+                .deferred_points
+                .chunks_exact(K)
+                .any(|point| point == query)
+    }
+
+    /// Removes every item that is stored at exactly `query`, returning how many there were
+    fn remove_point(&mut self, query: &[A; K]) -> usize {
+        self.remove_at_point_where(query, |_| true)
+            + self.remove_deferred_where(|point, _| point == query)
+    }
+
+    /// Removes the items stored at exactly `query` that match `predicate`, from every leaf
+    /// that they could be in, returning how many there were
+    fn remove_at_point_where(&mut self, query: &[A; K], predicate: impl Fn(T) -> bool) -> usize {
+        let mut removed: usize = 0;
+        let leaves = &mut self.leaves;
+        let size = &mut self.size;
+
+        for_each_leaf_idx_for(&self.stems, self.root_index, 0, query, &mut |leaf_idx| {
+            if let Some(leaf_node) = leaves.get_mut(leaf_idx) {
+                let leaf_removed =
+                    leaf_node.remove_where(|point, stored| point == query && predicate(stored));
+                for _ in 0..leaf_removed {
+                    *size -= T::one();
                 }
+                removed += leaf_removed;
+            }
+        });
+
+        removed
+    }
+
+    /// Removes the deferred entries that match `predicate`, returning how many there were.
+    ///
+    /// Items that are still waiting for `finalize_splits` are not counted in `size`.
+    fn remove_deferred_where(&mut self, predicate: impl Fn(&[A], T) -> bool) -> usize {
+        let mut removed: usize = 0;
+
+        let mut deferred_idx = 0;
+        while deferred_idx < self.deferred_items.len() {
+            let point = &self.deferred_points[deferred_idx * K..(deferred_idx + 1) * K];
+            if predicate(point, self.deferred_items[deferred_idx]) {
+                self.deferred_items.swap_remove(deferred_idx);
+                let last = self.deferred_items.len();
+                for dim in 0..K {
+                    self.deferred_points[deferred_idx * K + dim] =
+                        self.deferred_points[last * K + dim];
+                }
+                self.deferred_points.truncate(last * K);
+                removed += 1;
+            } else {
+                deferred_idx += 1;
             }
         }
 
         removed
     }
 
+    /// Moves an item from `old_point` to `new_point`.
+    ///
+    /// Equivalent to calling [`remove`](KdTree::remove) followed by [`add`](KdTree::add),
+    /// but when both points belong in the same leaf, as is usually the case for small
+    /// movements, the item's co-ordinates are updated in place rather than it being
+    /// removed and added again.
+    ///
+    /// Returns the number of entries moved, which is zero if `item` was not found at
+    /// `old_point`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{KdTree, SquaredEuclidean};
+    ///
+    /// let mut tree: KdTree<f64, 3> = KdTree::new();
+    /// tree.add(&[1.0, 2.0, 5.0], 100);
+    /// tree.add(&[2.0, 3.0, 6.0], 200);
+    ///
+    /// assert_eq!(tree.update(&[1.0, 2.0, 5.0], &[1.1, 2.0, 5.0], 100), 1);
+    ///
+    /// assert_eq!(tree.size(), 2);
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[1.1, 2.0, 5.0]).distance, 0.0);
+    /// ```
+    pub fn update(&mut self, old_point: &[A; K], new_point: &[A; K], item: T) -> usize {
+        let new_leaf_idx = self.leaf_idx_for(new_point);
+        let mut moved: usize = 0;
+        let mut removed: usize = 0;
+        let leaves = &mut self.leaves;
+
+        for_each_leaf_idx_for(
+            &self.stems,
+            self.root_index,
+            0,
+            old_point,
+            &mut |leaf_idx| {
+                let leaf_node = &mut leaves[leaf_idx];
+                let is_match = |point: &[A; K], stored: T| point == old_point && stored == item;
+
+                if leaf_idx != new_leaf_idx {
+                    removed += leaf_node.remove_where(is_match);
+                    return;
+                }
+
+                let size = leaf_node.size.az::<usize>();
+                for (point, leaf_item) in leaf_node.content_points[..size]
+                    .iter_mut()
+                    .zip(&leaf_node.content_items[..size])
+                {
+                    if is_match(point, *leaf_item) {
+                        *point = *new_point;
+                        moved += 1;
+                    }
+                }
+            },
+        );
+
+        for _ in 0..removed {
+            self.size -= T::one();
+            self.add(new_point, item);
+        }
+
+        moved + removed
+    }
+
     unsafe fn split(
         &mut self,
         leaf_idx: IDX,
         split_dim: usize,
+        query_val: A,
         parent_idx: IDX,
         was_parents_left: bool,
     ) -> IDX {
-        let orig = self.leaves.get_unchecked_mut(leaf_idx.az::<usize>());
+        let (split_val, right) = self
+            .leaves
+            .get_unchecked_mut(leaf_idx.az::<usize>())
+            .split_off(split_dim, query_val);
+
+        self.leaves.push(right);
+
+        self.stems.push(StemNode {
+            left: leaf_idx + IDX::leaf_offset(),
+            right: (self.leaves.len().az::<IDX>()) - IDX::one() + IDX::leaf_offset(),
+            split_val,
+        });
+        let new_stem_index: IDX = (self.stems.len().az::<IDX>()) - IDX::one();
+
+        if parent_idx != <IDX as Index>::max() {
+            let parent_node = self.stems.get_unchecked_mut(parent_idx.az::<usize>());
+            if was_parents_left {
+                parent_node.left = new_stem_index;
+            } else {
+                parent_node.right = new_stem_index;
+            }
+        } else {
+            self.root_index = new_stem_index;
+        }
+
+        new_stem_index
+    }
+
+    /// Adds a batch of items to the tree, deferring any leaf splits that they would
+    /// cause until [`finalize_splits`](KdTree::finalize_splits) is called.
+    ///
+    /// When many items arrive at once, adding them one at a time with [`add`](KdTree::add)
+    /// can split the same busy leaf over and over. Deferring the splits means that each
+    /// leaf that overflows is split just once, into as many new leaves as it needs,
+    /// however many `add_deferred` calls there were beforehand.
+    ///
+    /// Items added this way are not visible to queries, nor included in [`size`](KdTree::size),
+    /// until `finalize_splits` has been called.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{KdTree, SquaredEuclidean};
+    ///
+    /// let mut tree: KdTree<f64, 2> = KdTree::new();
+    ///
+    /// tree.add_deferred((0..500).map(|i| ([i as f64, 0.0], i)));
+    /// tree.add_deferred((500..1000).map(|i| ([i as f64, 1.0], i)));
+    /// assert_eq!(tree.size(), 0);
+    ///
+    /// tree.finalize_splits();
+    /// assert_eq!(tree.size(), 1000);
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[600.1, 0.9]).item, 600);
+    /// ```
+    pub fn add_deferred(&mut self, batch: impl IntoIterator<Item = ([A; K], T)>) {
+        for (point, item) in batch {
+            self.deferred_points.extend_from_slice(&point);
+            self.deferred_items.push(item);
+        }
+    }
+
+    /// Adds all of the items passed to [`add_deferred`](KdTree::add_deferred) since the
+    /// last call to this method to the tree, splitting each leaf that they overflow once.
+    ///
+    /// Each overflowing leaf is replaced by a balanced subtree built from its existing
+    /// contents and the new items destined for it.
+    pub fn finalize_splits(&mut self) {
+        let (points, items) = self.take_deferred();
+        if points.is_empty() {
+            return;
+        }
+
+        // group the new items by the leaf that they belong in
+        let mut by_leaf: BTreeMap<usize, DeferredLeafBatch<A, T, K, IDX>> = BTreeMap::new();
+        for (point, item) in points.into_iter().zip(items) {
+            let mut stem_idx = self.root_index;
+            let mut split_dim = 0;
+            let mut parent_idx = <IDX as Index>::max();
+            let mut is_left_child = false;
+
+            while is_stem_index(stem_idx) {
+                parent_idx = stem_idx;
+                let stem_node = &self.stems[stem_idx.az::<usize>()];
+
+                is_left_child = point[split_dim] < stem_node.split_val;
+                stem_idx = if is_left_child {
+                    stem_node.left
+                } else {
+                    stem_node.right
+                };
+
+                split_dim = (split_dim + 1).rem(K);
+            }
+
+            let batch = by_leaf
+                .entry((stem_idx - IDX::leaf_offset()).az::<usize>())
+                .or_insert_with(|| DeferredLeafBatch {
+                    parent_idx,
+                    is_left_child,
+                    split_dim,
+                    points: vec![],
+                    items: vec![],
+                });
+            batch.points.push(point);
+            batch.items.push(item);
+
+            self.size = self.size + T::one();
+        }
+
+        for (leaf_idx, mut batch) in by_leaf {
+            let leaf = &mut self.leaves[leaf_idx];
+            let leaf_size = leaf.size.az::<usize>();
+
+            if leaf_size + batch.points.len() <= B {
+                let new_size = leaf_size + batch.points.len();
+                leaf.content_points[leaf_size..new_size].copy_from_slice(&batch.points);
+                leaf.content_items[leaf_size..new_size].copy_from_slice(&batch.items);
+                leaf.size = new_size.az::<IDX>();
+                continue;
+            }
+
+            batch
+                .points
+                .extend_from_slice(&leaf.content_points[..leaf_size]);
+            batch
+                .items
+                .extend_from_slice(&leaf.content_items[..leaf_size]);
+            leaf.size = IDX::zero();
+
+            let first_new_stem = self.stems.len();
+            let mut subtree_root =
+                self.build_balanced(&mut batch.points, &mut batch.items, batch.split_dim, 0);
+
+            // move the last of the new leaves into the slot of the one that was split
+            let last_leaf_idx = self.leaves.len() - 1;
+            self.leaves.swap(leaf_idx, last_leaf_idx);
+            self.leaves.pop();
+            let moved_from = last_leaf_idx.az::<IDX>() + IDX::leaf_offset();
+            let moved_to = leaf_idx.az::<IDX>() + IDX::leaf_offset();
+            if subtree_root == moved_from {
+                subtree_root = moved_to;
+            }
+            for stem in &mut self.stems[first_new_stem..] {
+                if stem.left == moved_from {
+                    stem.left = moved_to;
+                }
+                if stem.right == moved_from {
+                    stem.right = moved_to;
+                }
+            }
+
+            if batch.parent_idx == <IDX as Index>::max() {
+                self.root_index = subtree_root;
+            } else {
+                let parent = &mut self.stems[batch.parent_idx.az::<usize>()];
+                if batch.is_left_child {
+                    parent.left = subtree_root;
+                } else {
+                    parent.right = subtree_root;
+                }
+            }
+        }
+    }
+
+    fn take_deferred(&mut self) -> (Vec<[A; K]>, Vec<T>) {
+        let points = std::mem::take(&mut self.deferred_points)
+            .chunks_exact(K)
+            .map(|chunk| std::array::from_fn(|dim| chunk[dim]))
+            .collect();
+
+        (points, std::mem::take(&mut self.deferred_items))
+    }
+
+    /// Creates a balanced KdTree from a slice of points, with each point's item being its
+    /// index within the slice.
+    ///
+    /// Unlike the `From<&Vec<[A; K]>>` implementation, which adds the points one at a time, this bulk-loads the tree by recursively
+    /// splitting the points at their median on each axis in turn. This is considerably
+    /// faster, and results in a tree whose shape does not depend on the order of the points,
+    /// which in turn gives better query performance. The tree can still be modified afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{KdTree, SquaredEuclidean};
+    ///
+    /// let points: Vec<[f64; 3]> = (0..1000).map(|i| [i as f64, (i % 10) as f64, 0.0]).collect();
+    /// let mut tree: KdTree<f64, 3> = KdTree::from_slice_balanced(&points);
+    /// assert_eq!(tree.size(), 1000);
+    ///
+    /// tree.add(&[0.5, 0.5, 0.0], 1000);
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[0.4, 0.4, 0.0]).item, 1000);
+    /// ```
+    pub fn from_slice_balanced(source: &[[A; K]]) -> Self
+    where
+        usize: Cast<T>,
+    {
+        let items = (0..source.len()).map(|idx| idx.az::<T>()).collect();
+
+        Self::from_points_and_items_balanced(source.to_vec(), items, source.len().az::<T>())
+    }
+
+    /// Rebuilds the tree in place from its current contents, restoring the balanced
+    /// layout that [`from_slice_balanced`](KdTree::from_slice_balanced) would give.
+    ///
+    /// The shape of a tree that has had many items added and removed depends on the
+    /// order in which that happened, and query performance degrades as it drifts away
+    /// from being balanced. Calling this periodically restores it. The items stored in
+    /// the tree are unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{KdTree, SquaredEuclidean};
+    ///
+    /// let mut tree: KdTree<f64, 2> = KdTree::new();
+    /// for i in 0..1000 {
+    ///     tree.add(&[i as f64, i as f64], i);
+    /// }
+    /// for i in 0..500 {
+    ///     tree.remove(&[i as f64, i as f64], i);
+    /// }
+    ///
+    /// tree.rebalance();
+    ///
+    /// assert_eq!(tree.size(), 500);
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[0.0, 0.0]).item, 500);
+    /// ```
+    pub fn rebalance(&mut self) {
+        let size = self
+            .deferred_items
+            .iter()
+            .fold(self.size, |size, _| size + T::one());
+        let (points, items) = self.take_contents();
+
+        *self = Self::from_points_and_items_balanced(points, items, size);
+    }
+
+    /// Moves every item in `other` into this tree.
+    ///
+    /// If `other` is much smaller than this tree, its items are added one at a time.
+    /// Otherwise adding them would cost more than starting again, so the merged tree is
+    /// instead rebuilt from the items of both, as [`rebalance`](KdTree::rebalance) would.
+    /// Either way, any deferred items of `other` are added as normal items.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{KdTree, SquaredEuclidean};
+    ///
+    /// let mut tree: KdTree<f64, 2> = KdTree::new();
+    /// tree.add(&[1.0, 2.0], 100);
+    ///
+    /// let mut shard: KdTree<f64, 2> = KdTree::new();
+    /// shard.add(&[3.0, 4.0], 200);
+    ///
+    /// tree.merge(shard);
+    ///
+    /// assert_eq!(tree.size(), 2);
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[3.1, 4.1]).item, 200);
+    /// ```
+    pub fn merge(&mut self, mut other: Self) {
+        let (other_points, other_items) = other.take_contents();
+        let own_len = self
+            .leaves
+            .iter()
+            .fold(self.deferred_items.len(), |len, leaf| {
+                len + leaf.size.az::<usize>()
+            });
+
+        if other_items.len() * MERGE_REBUILD_RATIO > own_len + other_items.len() {
+            let size = other_items
+                .iter()
+                .chain(self.deferred_items.iter())
+                .fold(self.size, |size, _| size + T::one());
+            let (mut points, mut items) = self.take_contents();
+            points.extend(other_points);
+            items.extend(other_items);
+
+            *self = Self::from_points_and_items_balanced(points, items, size);
+        } else {
+            for (point, item) in other_points.iter().zip(other_items) {
+                self.add(point, item);
+            }
+        }
+    }
+
+    /// Reorders the tree's nodes in memory so that they are stored in the order in which
+    /// a depth-first query visits them, without changing the shape of the tree.
+    ///
+    /// Adding items allocates stems and leaves in the order that splits happen to occur,
+    /// scattering neighbouring nodes throughout memory. After compacting, each stem is
+    /// followed by its left subtree and then its right subtree, and leaves are stored in
+    /// left-to-right order, so queries touch fewer cache lines. Any nodes that are no
+    /// longer reachable from the root are dropped. The tree can still be modified
+    /// afterwards, although later splits will again be allocated at the end.
+    ///
+    /// Unlike [`rebalance`](KdTree::rebalance), the split planes are left as they are.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{KdTree, SquaredEuclidean};
+    ///
+    /// let mut tree: KdTree<f64, 2> = KdTree::new();
+    /// for i in 0..1000 {
+    ///     tree.add(&[(i * 37 % 1000) as f64, i as f64], i);
+    /// }
+    ///
+    /// tree.compact();
+    ///
+    /// assert_eq!(tree.size(), 1000);
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[37.0, 1.0]).item, 1);
+    /// ```
+    pub fn compact(&mut self) {
+        let mut stems = Vec::with_capacity(self.stems.len());
+        let mut leaves = Vec::with_capacity(self.leaves.len());
+
+        // new stems are pushed in pre-order, leaving their children to be patched up
+        // once the children's new indices are known
+        let mut stack = vec![(self.root_index, None::<(usize, bool)>)];
+        let mut new_root = self.root_index;
+
+        while let Some((old_idx, parent)) = stack.pop() {
+            let new_idx = if is_stem_index(old_idx) {
+                let stem = &self.stems[old_idx.az::<usize>()];
+                stems.push(StemNode {
+                    left: IDX::zero(),
+                    right: IDX::zero(),
+                    split_val: stem.split_val,
+                });
+                let new_idx = stems.len() - 1;
+                stack.push((stem.right, Some((new_idx, true))));
+                stack.push((stem.left, Some((new_idx, false))));
+
+                new_idx.az::<IDX>()
+            } else {
+                leaves.push(self.leaves[(old_idx - IDX::leaf_offset()).az::<usize>()].clone());
+
+                (leaves.len() - 1).az::<IDX>() + IDX::leaf_offset()
+            };
+
+            match parent {
+                Some((parent_idx, true)) => stems[parent_idx].right = new_idx,
+                Some((parent_idx, false)) => stems[parent_idx].left = new_idx,
+                None => new_root = new_idx,
+            }
+        }
+
+        self.stems = stems;
+        self.leaves = leaves;
+        self.root_index = new_root;
+    }
+
+    /// Consumes the tree, returning an [`ImmutableKdTree`] that contains the same items.
+    ///
+    /// Useful for when a tree is built up incrementally but is then only queried, as
+    /// an [`ImmutableKdTree`] is faster to query and more compact in memory.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree, SquaredEuclidean};
+    ///
+    /// let mut tree: KdTree<f64, 2> = KdTree::new();
+    /// tree.add(&[1.0, 2.0], 100);
+    /// tree.add(&[3.0, 4.0], 200);
+    ///
+    /// let immutable: ImmutableKdTree<f64, 2> = tree.into_immutable();
+    ///
+    /// assert_eq!(immutable.size(), 2);
+    /// assert_eq!(immutable.nearest_one::<SquaredEuclidean>(&[3.1, 4.1]).item, 200);
+    /// ```
+    pub fn into_immutable(mut self) -> ImmutableKdTree<A, T, K, B>
+    where
+        A: LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+        usize: Cast<T>,
+    {
+        let (points, items) = self.take_contents();
+
+        ImmutableKdTree::new_from_slice_with_items(
+            &points,
+            &|idx| items[idx],
+            SplitPolicy::RoundRobin,
+        )
+    }
+
+    /// Copies the points and items out of every leaf, and any that are deferred.
+    pub(crate) fn contents(&self) -> (Vec<[A; K]>, Vec<T>) {
+        let mut points = Vec::with_capacity(self.leaves.len() * B + self.deferred_items.len());
+        let mut items = Vec::with_capacity(self.leaves.len() * B + self.deferred_items.len());
+
+        for leaf in &self.leaves {
+            let size = leaf.size.az::<usize>();
+            points.extend_from_slice(&leaf.content_points[..size]);
+            items.extend_from_slice(&leaf.content_items[..size]);
+        }
+
+        points.extend(
+            self.deferred_points
+                .chunks_exact(K)
+                .map(|point| std::array::from_fn::<A, K, _>(|dim| point[dim])),
+        );
+        items.extend_from_slice(&self.deferred_items);
+
+        (points, items)
+    }
+
+    /// Moves the points and items out of every leaf, and any that are deferred,
+    /// leaving the tree's nodes empty.
+    fn take_contents(&mut self) -> (Vec<[A; K]>, Vec<T>) {
+        let mut points = Vec::with_capacity(self.leaves.len() * B);
+        let mut items = Vec::with_capacity(self.leaves.len() * B);
+
+        for leaf in std::mem::take(&mut self.leaves) {
+            let size = leaf.size.az::<usize>();
+            points.extend_from_slice(&leaf.content_points[..size]);
+            items.extend_from_slice(&leaf.content_items[..size]);
+        }
+        self.stems.clear();
+
+        let (deferred_points, deferred_items) = self.take_deferred();
+        points.extend(deferred_points);
+        items.extend(deferred_items);
+
+        (points, items)
+    }
+
+    fn from_points_and_items_balanced(mut points: Vec<[A; K]>, mut items: Vec<T>, size: T) -> Self {
+        assert!(points.len() <= <IDX as Index>::capacity_with_bucket_size(B));
+
+        let leaf_count = DivCeil::div_ceil(points.len(), B).max(1) * 2;
+        let mut tree = Self {
+            size,
+            stems: Vec::with_capacity(leaf_count),
+            leaves: Vec::with_capacity(leaf_count),
+            root_index: <IDX as Index>::leaf_offset(),
+            deferred_points: Vec::new(),
+            deferred_items: Vec::new(),
+        };
+
+        tree.root_index = tree.build_balanced(&mut points, &mut items, 0, 0);
+
+        tree
+    }
+
+    fn build_balanced(
+        &mut self,
+        points: &mut [[A; K]],
+        items: &mut [T],
+        split_dim: usize,
+        stalled_dims: usize,
+    ) -> IDX {
+        if points.len() <= B {
+            let mut leaf = LeafNode::new();
+            leaf.content_points[..points.len()].copy_from_slice(points);
+            leaf.content_items[..items.len()].copy_from_slice(items);
+            leaf.size = points.len().az::<IDX>();
+            self.leaves.push(leaf);
+
+            return (self.leaves.len() - 1).az::<IDX>() + IDX::leaf_offset();
+        }
+
+        let (split_val, pivot_idx, stalled_dims) = if stalled_dims == K {
+            // every point is identical, so they can never be separated. Chain a full leaf
+            // of them to the left of a split at their shared value, as `split_off` does
+            (points[0][split_dim], B, K)
+        } else {
+            let (split_val, pivot_idx) = Self::partition_balanced(points, items, split_dim);
+
+            // an empty left child means this split made no progress; track how many axes
+            // in a row that this happens on, to detect points that can never be separated
+            let stalled_dims = if pivot_idx == 0 { stalled_dims + 1 } else { 0 };
+
+            (split_val, pivot_idx, stalled_dims)
+        };
+        let next_split_dim = (split_dim + 1).rem(K);
+
+        let stem_idx = self.stems.len();
+        self.stems.push(StemNode {
+            left: IDX::zero(),
+            right: IDX::zero(),
+            split_val,
+        });
+
+        let (left_points, right_points) = points.split_at_mut(pivot_idx);
+        let (left_items, right_items) = items.split_at_mut(pivot_idx);
+        let left = self.build_balanced(left_points, left_items, next_split_dim, stalled_dims);
+        let right = self.build_balanced(right_points, right_items, next_split_dim, stalled_dims);
+
+        let stem = &mut self.stems[stem_idx];
+        stem.left = left;
+        stem.right = right;
+
+        stem_idx.az::<IDX>()
+    }
+
+    /// Reorders `points` (and `items` alongside them) so that all points before the
+    /// returned pivot index are less than the returned split value on `split_dim`,
+    /// and all points from the pivot index onwards are greater than or equal to it.
+    fn partition_balanced(points: &mut [[A; K]], items: &mut [T], split_dim: usize) -> (A, usize) {
+        let mid = points.len() / 2;
+
+        mirror_select_nth_unstable_by(points, items, mid, |a, b| {
+            a[split_dim]
+                .partial_cmp(&b[split_dim])
+                .expect("Leaf node sort failed.")
+        });
+        let split_val = points[mid][split_dim];
+
+        // items equal to the split value can be either side of the median, but must all
+        // end up to the right of the split
+        let pivot_idx = mirror_partition(&mut points[..mid], &mut items[..mid], |p| {
+            p[split_dim] < split_val
+        });
+        if pivot_idx > 0 {
+            return (split_val, pivot_idx);
+        }
+
+        // the median is the smallest value on this axis. Split just above it instead.
+        let pivot_idx = mirror_partition(points, items, |p| p[split_dim] <= split_val);
+        match points[pivot_idx..]
+            .iter()
+            .map(|p| p[split_dim])
+            .reduce(A::min)
+        {
+            Some(next_val) => (next_val, pivot_idx),
+            None => (split_val, 0),
+        }
+    }
+}
+
+/// The items from a call to `finalize_splits` that belong in a particular leaf,
+/// along with where that leaf is in the tree.
+struct DeferredLeafBatch<A, T, const K: usize, IDX> {
+    parent_idx: IDX,
+    is_left_child: bool,
+    split_dim: usize,
+    points: Vec<[A; K]>,
+    items: Vec<T>,
+}
+
+fn mirror_partition<P, I>(points: &mut [P], items: &mut [I], pred: impl Fn(&P) -> bool) -> usize {
+    let mut split = 0;
+    for idx in 0..points.len() {
+        if pred(&points[idx]) {
+            points.swap(split, idx);
+            items.swap(split, idx);
+            split += 1;
+        }
+    }
+    split
+}
+
+/// Calls `visit` with the index of each leaf below `node_idx` that an item at exactly
+/// `query` could be stored in.
+///
+/// Items whose value on a stem's split dimension equals its split value are usually to its
+/// right, but a leaf whose items all share that value may be chained to its left by
+/// [`LeafNode::split_off`], so both sides of such a split are visited.
+pub(crate) fn for_each_leaf_idx_for<A: Axis, const K: usize, IDX: Index<T = IDX>>(
+    stems: &[StemNode<A, K, IDX>],
+    mut node_idx: IDX,
+    mut split_dim: usize,
+    query: &[A; K],
+    visit: &mut impl FnMut(usize),
+) {
+    while is_stem_index(node_idx) {
+        let stem_node = &stems[node_idx.az::<usize>()];
+        let next_split_dim = (split_dim + 1).rem(K);
+
+        if query[split_dim] == stem_node.split_val {
+            for_each_leaf_idx_for(stems, stem_node.left, next_split_dim, query, visit);
+        }
+
+        node_idx = if query[split_dim] < stem_node.split_val {
+            stem_node.left
+        } else {
+            stem_node.right
+        };
+        split_dim = next_split_dim;
+    }
+
+    visit((node_idx - IDX::leaf_offset()).az::<usize>());
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
+    FromIterator<([A; K], T)> for KdTree<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = ([A; K], T)>>(iter: I) -> Self {
+        let iter = iter.into_iter();
+        let mut tree = Self::with_capacity(iter.size_hint().0);
+        for (point, item) in iter {
+            tree.add(&point, item);
+        }
+        tree
+    }
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>, const N: usize>
+    From<[([A; K], T); N]> for KdTree<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    #[inline]
+    fn from(value: [([A; K], T); N]) -> Self {
+        value.into_iter().collect()
+    }
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>> Extend<([A; K], T)>
+    for KdTree<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    /// Adds a batch of items to the tree.
+    ///
+    /// This is faster than calling [`add`](KdTree::add) for each item: the items are
+    /// grouped by the leaf that they belong in, and each leaf that they overflow is split
+    /// just once, as [`finalize_splits`](KdTree::finalize_splits) does. Any items still
+    /// waiting from earlier calls to [`add_deferred`](KdTree::add_deferred) are added too.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{KdTree, SquaredEuclidean};
+    ///
+    /// let mut tree: KdTree<f64, 2> = KdTree::new();
+    ///
+    /// for chunk in 0..10 {
+    ///     tree.extend((chunk * 1000..(chunk + 1) * 1000).map(|i| ([i as f64, (i % 7) as f64], i)));
+    /// }
+    ///
+    /// assert_eq!(tree.size(), 10_000);
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[4321.1, 2.0]).item, 4321);
+    /// ```
+    #[inline]
+    fn extend<I: IntoIterator<Item = ([A; K], T)>>(&mut self, iter: I) {
+        self.add_deferred(iter);
+        self.finalize_splits();
+    }
+}
+
+impl<
+        'a,
+        't,
+        A: Axis + Copy,
+        T: Content + Copy,
+        const K: usize,
+        const B: usize,
+        IDX: Index<T = IDX>,
+    > Extend<(&'a [A; K], &'t T)> for KdTree<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    #[inline]
+    fn extend<I: IntoIterator<Item = (&'a [A; K], &'t T)>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().map(|(point, item)| (*point, *item)));
+    }
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
+    From<&KdTree<A, T, K, B, IDX>> for ImmutableKdTree<A, T, K, B>
+where
+    A: LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    usize: Cast<IDX> + Cast<T>,
+{
+    /// Creates an [`ImmutableKdTree`] containing the same points and items as a [`KdTree`],
+    /// including any that were added with [`add_deferred`](KdTree::add_deferred) and not yet
+    /// finalized, leaving the `KdTree` unchanged.
+    /// Use [`into_immutable`](KdTree::into_immutable) if the `KdTree` is no longer needed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree, SquaredEuclidean};
+    ///
+    /// let mut tree: KdTree<f64, 2> = KdTree::new();
+    /// tree.add(&[1.0, 2.0], 100);
+    /// tree.add(&[3.0, 4.0], 200);
+    ///
+    /// let immutable: ImmutableKdTree<f64, 2> = (&tree).into();
+    ///
+    /// assert_eq!(immutable.size(), 2);
+    /// assert_eq!(immutable.nearest_one::<SquaredEuclidean>(&[3.1, 4.1]).item, 200);
+    /// ```
+    fn from(tree: &KdTree<A, T, K, B, IDX>) -> Self {
+        let (points, items) = tree.contents();
+
+        ImmutableKdTree::new_from_slice_with_items(
+            &points,
+            &|idx| items[idx],
+            SplitPolicy::RoundRobin,
+        )
+    }
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
+    From<&ImmutableKdTree<A, T, K, B>> for KdTree<A, T, K, B, IDX>
+where
+    A: LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    usize: Cast<IDX> + Cast<T>,
+{
+    /// Creates a balanced [`KdTree`] containing the same points and items as an
+    /// [`ImmutableKdTree`], so that they can be modified.
+    ///
+    /// # Panics
+    ///
+    /// As with [`from_slice_balanced`](KdTree::from_slice_balanced), panics if the tree is
+    /// too large for `IDX`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, KdTree, SquaredEuclidean};
+    ///
+    /// let points = vec![[1.0, 2.0], [3.0, 4.0]];
+    /// let immutable: ImmutableKdTree<f64, 2> = ImmutableKdTree::new_from_slice(&points);
+    ///
+    /// let mut tree: KdTree<f64, 2> = (&immutable).into();
+    /// tree.add(&[5.0, 6.0], 2);
+    ///
+    /// assert_eq!(tree.size(), 3);
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[3.1, 4.1]).item, 1);
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[5.1, 6.1]).item, 2);
+    /// ```
+    fn from(tree: &ImmutableKdTree<A, T, K, B>) -> Self {
+        let (items, points): (Vec<T>, Vec<[A; K]>) = tree.iter().unzip();
+
+        Self::from_points_and_items_balanced(points, items, tree.size().az::<T>())
+    }
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
+    LeafNode<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    /// Removes the items that match `predicate`, returning how many there were.
+    pub(crate) fn remove_where(&mut self, predicate: impl Fn(&[A; K], T) -> bool) -> usize {
+        let mut removed: usize = 0;
+
+        let mut p_index = 0;
+        while p_index < self.size.az::<usize>() {
+            if predicate(&self.content_points[p_index], self.content_items[p_index]) {
+                let last = self.size.az::<usize>() - 1;
+                self.content_points[p_index] = self.content_points[last];
+                self.content_items[p_index] = self.content_items[last];
+
+                removed += 1;
+                self.size = self.size - IDX::one();
+            } else {
+                p_index += 1;
+            }
+        }
+
+        removed
+    }
+
+    /// Splits a full leaf on `split_dim`, leaving the items below the returned split value
+    /// in `self` and moving the rest into the returned leaf.
+    ///
+    /// If every item shares the same value on `split_dim`, as happens with lattice data or
+    /// many identical points, they cannot be separated. The leaf is then kept whole and
+    /// paired with an empty one on whichever side of it `query_val`, the value on
+    /// `split_dim` of the item about to be added, belongs. When `query_val` equals the shared
+    /// value, the full leaf is chained to the left of a split at that same value, so that
+    /// lookups of an exact point have to search both sides of such a split
+    /// (see [`for_each_leaf_idx_for`]).
+    pub(crate) unsafe fn split_off(&mut self, split_dim: usize, query_val: A) -> (A, Self) {
         let mut pivot_idx = (B / 2).az::<IDX>();
 
         mirror_select_nth_unstable_by(
-            &mut orig.content_points,
-            &mut orig.content_items,
+            &mut self.content_points,
+            &mut self.content_items,
             pivot_idx.az::<usize>(),
             |a, b| unsafe {
                 a.get_unchecked(split_dim)
@@ -165,14 +1191,14 @@ where
             },
         );
 
-        let mut split_val = *orig
+        let mut split_val = *self
             .content_points
             .get_unchecked(pivot_idx.az::<usize>())
             .get_unchecked(split_dim);
 
         // if the chosen pivot point would result in some items whose position on the split
         // dimension is the same as the split value being on the wrong side of the split:
-        if *orig
+        if *self
             .content_points
             .get_unchecked(pivot_idx.az::<usize>() - 1)
             .get_unchecked(split_dim)
@@ -185,7 +1211,7 @@ where
             // move the pivot to prevent this. We first try moving down, since that's the only
             // part of the bucket that was sorted by mirror_select_nth_unstable_by
             while pivot_idx > IDX::zero()
-                && *orig
+                && *self
                     .content_points
                     .get_unchecked(pivot_idx.az::<usize>() - 1)
                     .get_unchecked(split_dim)
@@ -200,8 +1226,8 @@ where
             // is sorted
             if pivot_idx == IDX::zero() {
                 mirror_select_nth_unstable_by(
-                    &mut orig.content_points,
-                    &mut orig.content_items,
+                    &mut self.content_points,
+                    &mut self.content_items,
                     B - 1,
                     |a, b| unsafe {
                         a.get_unchecked(split_dim)
@@ -211,7 +1237,7 @@ where
                 );
 
                 pivot_idx = orig_pivot_idx;
-                while *orig
+                while *self
                     .content_points
                     .get_unchecked(pivot_idx.az::<usize>())
                     .get_unchecked(split_dim)
@@ -220,125 +1246,56 @@ where
                     pivot_idx = pivot_idx + IDX::one();
 
                     if pivot_idx.az::<usize>() == B {
-                        panic!("Too many items with the same position on one axis. Bucket size must be increased to at least 1 more than the number of items with the same position on one axis.");
+                        return if query_val < split_val {
+                            (split_val, std::mem::replace(self, LeafNode::new()))
+                        } else {
+                            (query_val, LeafNode::new())
+                        };
                     }
                 }
             }
 
-            split_val = *orig
+            split_val = *self
                 .content_points
                 .get_unchecked(pivot_idx.az::<usize>())
                 .get_unchecked(split_dim);
         }
 
         let mut right = LeafNode::new();
-        orig.size = pivot_idx;
+        self.size = pivot_idx;
         let dest_slice_end = B - pivot_idx.az::<usize>();
 
         right
             .content_points
             .get_unchecked_mut(..dest_slice_end)
             .copy_from_slice(
-                orig.content_points
+                self.content_points
                     .get_unchecked((pivot_idx.az::<usize>())..),
             );
         right
             .content_items
             .get_unchecked_mut(..dest_slice_end)
             .copy_from_slice(
-                orig.content_items
+                self.content_items
                     .get_unchecked((pivot_idx.az::<usize>())..),
             );
 
         right.size = (B.az::<IDX>()) - pivot_idx;
 
-        self.leaves.push(right);
-
-        self.stems.push(StemNode {
-            left: leaf_idx + IDX::leaf_offset(),
-            right: (self.leaves.len().az::<IDX>()) - IDX::one() + IDX::leaf_offset(),
-            split_val,
-        });
-        let new_stem_index: IDX = (self.stems.len().az::<IDX>()) - IDX::one();
-
-        if parent_idx != <IDX as Index>::max() {
-            let parent_node = self.stems.get_unchecked_mut(parent_idx.az::<usize>());
-            if was_parents_left {
-                parent_node.left = new_stem_index;
-            } else {
-                parent_node.right = new_stem_index;
-            }
-        } else {
-            self.root_index = new_stem_index;
-        }
-
-        new_stem_index
-    }
-}
-
-impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
-    FromIterator<([A; K], T)> for KdTree<A, T, K, B, IDX>
-where
-    usize: Cast<IDX>,
-{
-    #[inline]
-    fn from_iter<I: IntoIterator<Item = ([A; K], T)>>(iter: I) -> Self {
-        let iter = iter.into_iter();
-        let mut tree = Self::with_capacity(iter.size_hint().0);
-        for (point, item) in iter {
-            tree.add(&point, item);
-        }
-        tree
-    }
-}
-
-impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>, const N: usize>
-    From<[([A; K], T); N]> for KdTree<A, T, K, B, IDX>
-where
-    usize: Cast<IDX>,
-{
-    #[inline]
-    fn from(value: [([A; K], T); N]) -> Self {
-        value.into_iter().collect()
-    }
-}
-
-impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>> Extend<([A; K], T)>
-    for KdTree<A, T, K, B, IDX>
-where
-    usize: Cast<IDX>,
-{
-    #[inline]
-    fn extend<I: IntoIterator<Item = ([A; K], T)>>(&mut self, iter: I) {
-        for (point, item) in iter {
-            self.add(&point, item);
-        }
-    }
-}
-
-impl<
-        'a,
-        't,
-        A: Axis + Copy,
-        T: Content + Copy,
-        const K: usize,
-        const B: usize,
-        IDX: Index<T = IDX>,
-    > Extend<(&'a [A; K], &'t T)> for KdTree<A, T, K, B, IDX>
-where
-    usize: Cast<IDX>,
-{
-    #[inline]
-    fn extend<I: IntoIterator<Item = (&'a [A; K], &'t T)>>(&mut self, iter: I) {
-        for (point, item) in iter.into_iter() {
-            self.add(point, *item);
-        }
+        (split_val, right)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::construction_error::ConstructionError;
+    use crate::duplicate_policy::DuplicatePolicy;
+    use crate::float::distance::SquaredEuclidean;
     use crate::float::kdtree::KdTree;
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use crate::nearest_neighbour::NearestNeighbour;
+    use crate::traits::{is_stem_index, Index};
+    use az::Az;
     use rand::Rng;
 
     type Flt = f32;
@@ -424,6 +1381,117 @@ mod tests {
         assert_eq!(tree.size(), 15);
     }
 
+    #[test]
+    fn can_update_an_items_position() {
+        let mut tree: KdTree<Flt, u32, 3, 32, u32> = KdTree::new();
+        let mut points: Vec<[Flt; 3]> = (0..1000).map(|_| rand::random::<[Flt; 3]>()).collect();
+        for (idx, point) in points.iter().enumerate() {
+            tree.add(point, idx as u32);
+        }
+
+        // small movements mostly stay within the same leaf, large ones mostly do not
+        for step in 0..10 {
+            for (idx, point) in points.iter_mut().enumerate() {
+                let new_point = if step % 2 == 0 {
+                    point.map(|v| v + 0.0001)
+                } else {
+                    rand::random::<[Flt; 3]>()
+                };
+                assert_eq!(tree.update(point, &new_point, idx as u32), 1);
+                *point = new_point;
+            }
+        }
+        assert_eq!(tree.size(), 1000);
+
+        for (idx, point) in points.iter().enumerate() {
+            let nearest = tree.nearest_one::<SquaredEuclidean>(point);
+            assert_eq!(nearest.distance, 0.0);
+            assert_eq!(nearest.item, idx as u32);
+        }
+
+        assert_eq!(tree.update(&[2.0, 2.0, 2.0], &points[0], 0), 0);
+        assert_eq!(tree.update(&points[0], &[0.5, 0.5, 0.5], 1), 0);
+        assert_eq!(tree.size(), 1000);
+    }
+
+    #[test]
+    fn can_remove_an_item_without_its_point() {
+        let mut tree: KdTree<Flt, u32, 3, 32, u32> = KdTree::new();
+        let points: Vec<[Flt; 3]> = (0..1000).map(|_| rand::random::<[Flt; 3]>()).collect();
+        for (idx, point) in points.iter().enumerate() {
+            tree.add(point, idx as u32 % 100);
+        }
+        tree.add_deferred([([0.5, 0.5, 0.5], 7), ([0.6, 0.6, 0.6], 8)]);
+
+        assert_eq!(tree.remove_item(7), 11);
+        assert_eq!(tree.size(), 990);
+        assert_eq!(tree.remove_item(7), 0);
+        assert!(tree.iter().all(|(item, _)| item != 7));
+
+        tree.finalize_splits();
+        assert_eq!(tree.size(), 991);
+
+        for (idx, point) in points.iter().enumerate() {
+            let nearest = tree.nearest_one::<SquaredEuclidean>(point);
+            if idx % 100 == 7 {
+                assert_ne!(nearest.item, 7);
+            } else {
+                assert_eq!(nearest.distance, 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn add_checked_rejects_non_finite_points() {
+        let mut tree: KdTree<Flt, u32, 2, 4, u32> = KdTree::new();
+        for idx in 0..20u32 {
+            tree.add_checked(&[idx as Flt, (idx * 7 % 20) as Flt], idx)
+                .unwrap();
+        }
+
+        assert_eq!(
+            tree.add_checked(&[1.0, Flt::INFINITY], 100),
+            Err(ConstructionError::InfiniteCoordinate { index: 0 })
+        );
+        assert_eq!(
+            tree.add_checked(&[Flt::NAN, 1.0], 101),
+            Err(ConstructionError::NanCoordinate { index: 0 })
+        );
+        assert_eq!(tree.size(), 20);
+    }
+
+    #[test]
+    fn can_add_with_a_duplicate_policy() {
+        let mut tree: KdTree<Flt, u32, 3, 4, u32> = KdTree::new();
+        let points: Vec<[Flt; 3]> = (0..50).map(|_| rand::random::<[Flt; 3]>()).collect();
+
+        for (idx, point) in points.iter().enumerate() {
+            assert!(tree.add_with_policy(point, idx as u32, DuplicatePolicy::Reject));
+        }
+        for (idx, point) in points.iter().enumerate() {
+            assert!(!tree.add_with_policy(point, 100 + idx as u32, DuplicatePolicy::Reject));
+        }
+        assert_eq!(tree.size(), 50);
+
+        for (idx, point) in points.iter().enumerate() {
+            assert!(tree.add_with_policy(point, 200 + idx as u32, DuplicatePolicy::Allow));
+            assert!(tree.add_with_policy(point, 300 + idx as u32, DuplicatePolicy::Replace));
+        }
+        assert_eq!(tree.size(), 50);
+
+        let mut items: Vec<u32> = tree.iter().map(|(item, _)| item).collect();
+        items.sort();
+        assert_eq!(items, (300..350).collect::<Vec<_>>());
+
+        // deferred items are checked too
+        tree.add_deferred([([2.0, 2.0, 2.0], 400)]);
+        assert!(!tree.add_with_policy(&[2.0, 2.0, 2.0], 401, DuplicatePolicy::Reject));
+        assert!(tree.add_with_policy(&[2.0, 2.0, 2.0], 402, DuplicatePolicy::Replace));
+        tree.finalize_splits();
+        assert_eq!(tree.size(), 51);
+        assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[2.0; 3]).item, 402);
+    }
+
     #[test]
     fn can_add_shitloads_of_points() {
         let mut tree: KdTree<Flt, u32, 4, 5, u32> = KdTree::new();
@@ -544,4 +1612,516 @@ mod tests {
             assert_eq!(tree.remove(pt, i), 1, "failed to remove point {i}");
         }
     }
+
+    #[test]
+    fn can_build_balanced_tree_from_slice() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+
+        let points: Vec<[Flt; 3]> = (0..TREE_SIZE).map(|_| rand::random::<[Flt; 3]>()).collect();
+
+        let mut tree: KdTree<Flt, u32, 3, 32, u32> = KdTree::from_slice_balanced(&points);
+        let incremental: KdTree<Flt, u32, 3, 32, u32> = (&points).into();
+        assert_eq!(tree.size(), TREE_SIZE as u32);
+
+        // every leaf of a median-split tree is at the same depth, give or take one
+        let mut depths = vec![];
+        let mut stack = vec![(tree.root_index, 0)];
+        while let Some((node_idx, depth)) = stack.pop() {
+            if is_stem_index(node_idx) {
+                let stem = &tree.stems[node_idx.az::<usize>()];
+                stack.push((stem.left, depth + 1));
+                stack.push((stem.right, depth + 1));
+            } else {
+                depths.push(depth);
+            }
+        }
+        assert!(depths.iter().max().unwrap() - depths.iter().min().unwrap() <= 1);
+
+        for _ in 0..NUM_QUERIES {
+            let query = rand::random::<[Flt; 3]>();
+            assert_eq!(
+                tree.nearest_one::<SquaredEuclidean>(&query),
+                incremental.nearest_one::<SquaredEuclidean>(&query)
+            );
+            assert_eq!(
+                tree.within::<SquaredEuclidean>(&query, 0.01),
+                incremental.within::<SquaredEuclidean>(&query, 0.01)
+            );
+        }
+
+        // the tree remains modifiable after being bulk-loaded
+        for (idx, point) in points.iter().enumerate().take(100) {
+            assert_eq!(tree.remove(point, idx as u32), 1);
+        }
+        tree.add(&[0.5, 0.5, 0.5], TREE_SIZE as u32);
+        assert_eq!(tree.size(), (TREE_SIZE - 99) as u32);
+        assert_eq!(
+            tree.nearest_one::<SquaredEuclidean>(&[0.5, 0.5, 0.5]).item,
+            TREE_SIZE as u32
+        );
+    }
+
+    #[test]
+    fn can_build_balanced_tree_from_slice_with_repeated_values() {
+        // many more points share each x value than fit in a bucket
+        let points: Vec<[Flt; 2]> = (0..1000)
+            .map(|i| [(i % 3) as Flt, (i / 3) as Flt])
+            .collect();
+
+        let tree: KdTree<Flt, u32, 2, 4, u32> = KdTree::from_slice_balanced(&points);
+        assert_eq!(tree.size(), 1000);
+
+        for (idx, point) in points.iter().enumerate() {
+            let nearest = tree.nearest_one::<SquaredEuclidean>(point);
+            assert_eq!(nearest.distance, 0.0);
+            assert_eq!(nearest.item, idx as u32);
+        }
+    }
+
+    #[test]
+    fn can_rebalance_after_many_adds_and_removes() {
+        const TREE_SIZE: usize = 10_000;
+
+        let points: Vec<[Flt; 3]> = (0..TREE_SIZE).map(|_| rand::random::<[Flt; 3]>()).collect();
+
+        // adding in sorted order gives a very unbalanced tree
+        let mut sorted_points: Vec<(usize, [Flt; 3])> =
+            points.iter().copied().enumerate().collect();
+        sorted_points.sort_by(|a, b| a.1[0].partial_cmp(&b.1[0]).unwrap());
+        let mut tree: KdTree<Flt, u32, 3, 32, u32> = KdTree::new();
+        for (idx, point) in &sorted_points {
+            tree.add(point, *idx as u32);
+        }
+        for (idx, point) in points.iter().enumerate().step_by(2) {
+            assert_eq!(tree.remove(point, idx as u32), 1);
+        }
+
+        let before = tree.clone();
+        tree.rebalance();
+        assert_eq!(tree.size(), (TREE_SIZE / 2) as u32);
+        assert!(tree.leaves.len() < before.leaves.len());
+
+        for _ in 0..100 {
+            let query = rand::random::<[Flt; 3]>();
+            assert_eq!(
+                tree.nearest_one::<SquaredEuclidean>(&query),
+                before.nearest_one::<SquaredEuclidean>(&query)
+            );
+            assert_eq!(
+                tree.within::<SquaredEuclidean>(&query, 0.01),
+                before.within::<SquaredEuclidean>(&query, 0.01)
+            );
+        }
+
+        // the tree remains modifiable after being rebalanced
+        tree.add(&[0.5, 0.5, 0.5], TREE_SIZE as u32);
+        assert_eq!(
+            tree.nearest_one::<SquaredEuclidean>(&[0.5, 0.5, 0.5]).item,
+            TREE_SIZE as u32
+        );
+
+        let mut empty: KdTree<Flt, u32, 3, 32, u32> = KdTree::new();
+        empty.rebalance();
+        assert_eq!(empty.size(), 0);
+    }
+
+    #[test]
+    fn merge_combines_the_items_of_both_trees() {
+        let points: Vec<[Flt; 3]> = (0..6_000).map(|_| rand::random::<[Flt; 3]>()).collect();
+        let build = |range: std::ops::Range<usize>| {
+            let mut tree: KdTree<Flt, u32, 3, 32, u32> = KdTree::new();
+            for idx in range {
+                tree.add(&points[idx], idx as u32);
+            }
+            tree
+        };
+
+        // a small tree's items are added one by one, while similarly sized trees are rebuilt
+        for split in [5_800, 2_500] {
+            let mut tree = build(0..split);
+            let mut other = build(split..6_000);
+            other.add_deferred([([0.5, 0.5, 0.5], 6_000)]);
+
+            tree.merge(other);
+            assert_eq!(tree.size(), 6_001);
+
+            let mut items: Vec<u32> = tree.iter().map(|(item, _)| item).collect();
+            items.sort();
+            assert_eq!(items, (0..6_001).collect::<Vec<_>>());
+
+            for (idx, point) in points.iter().enumerate().step_by(100) {
+                assert_eq!(
+                    tree.nearest_one::<SquaredEuclidean>(point),
+                    NearestNeighbour {
+                        distance: 0.0,
+                        item: idx as u32
+                    }
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn can_compact_into_traversal_order() {
+        const TREE_SIZE: usize = 10_000;
+
+        let points: Vec<[Flt; 3]> = (0..TREE_SIZE).map(|_| rand::random::<[Flt; 3]>()).collect();
+        let mut tree: KdTree<Flt, u32, 3, 32, u32> = KdTree::new();
+        for (idx, point) in points.iter().enumerate() {
+            tree.add(point, idx as u32);
+        }
+
+        let before = tree.clone();
+        tree.compact();
+        assert_eq!(tree.size(), TREE_SIZE as u32);
+        assert_eq!(tree.stems.len(), before.stems.len());
+        assert_eq!(tree.leaves.len(), before.leaves.len());
+
+        // every stem's left child directly follows it, and leaves are visited in order
+        assert_eq!(tree.root_index, 0);
+        let mut next_leaf = 0;
+        let mut stack = vec![tree.root_index];
+        while let Some(idx) = stack.pop() {
+            if is_stem_index(idx) {
+                let stem = &tree.stems[idx as usize];
+                if is_stem_index(stem.left) {
+                    assert_eq!(stem.left, idx + 1);
+                }
+                stack.push(stem.right);
+                stack.push(stem.left);
+            } else {
+                assert_eq!(idx - u32::leaf_offset(), next_leaf);
+                next_leaf += 1;
+            }
+        }
+
+        for _ in 0..100 {
+            let query = rand::random::<[Flt; 3]>();
+            assert_eq!(
+                tree.nearest_n::<SquaredEuclidean>(&query, 5),
+                before.nearest_n::<SquaredEuclidean>(&query, 5)
+            );
+            assert_eq!(
+                tree.within::<SquaredEuclidean>(&query, 0.01),
+                before.within::<SquaredEuclidean>(&query, 0.01)
+            );
+        }
+
+        // the tree remains modifiable after being compacted
+        tree.add(&[0.5, 0.5, 0.5], TREE_SIZE as u32);
+        assert_eq!(tree.remove(&points[0], 0), 1);
+        assert_eq!(
+            tree.nearest_one::<SquaredEuclidean>(&[0.5, 0.5, 0.5]).item,
+            TREE_SIZE as u32
+        );
+
+        let mut empty: KdTree<Flt, u32, 3, 32, u32> = KdTree::new();
+        empty.compact();
+        assert_eq!(empty.size(), 0);
+        assert_eq!(empty.leaves.len(), 1);
+    }
+
+    #[test]
+    fn can_convert_into_immutable() {
+        const TREE_SIZE: usize = 10_000;
+
+        let points: Vec<[Flt; 3]> = (0..TREE_SIZE).map(|_| rand::random::<[Flt; 3]>()).collect();
+        let mut tree: KdTree<Flt, u32, 3, 32, u32> = KdTree::new();
+        for (idx, point) in points.iter().enumerate() {
+            tree.add(point, idx as u32 * 10);
+        }
+        let mutable = tree.clone();
+
+        let immutable: ImmutableKdTree<Flt, u32, 3, 32> = tree.into_immutable();
+        assert_eq!(immutable.size(), TREE_SIZE);
+
+        for _ in 0..100 {
+            let query = rand::random::<[Flt; 3]>();
+            assert_eq!(
+                immutable.nearest_one::<SquaredEuclidean>(&query),
+                mutable.nearest_one::<SquaredEuclidean>(&query)
+            );
+            assert_eq!(
+                immutable.within::<SquaredEuclidean>(&query, 0.01),
+                mutable.within::<SquaredEuclidean>(&query, 0.01)
+            );
+        }
+    }
+
+    #[test]
+    fn can_convert_between_mutable_and_immutable_by_reference() {
+        const TREE_SIZE: usize = 10_000;
+
+        let mut tree: KdTree<Flt, u32, 3, 32, u32> = KdTree::new();
+        for idx in 0..TREE_SIZE {
+            tree.add(&rand::random::<[Flt; 3]>(), idx as u32 * 10);
+        }
+        tree.add_deferred([([0.5, 0.5, 0.5], 1)]);
+
+        let immutable: ImmutableKdTree<Flt, u32, 3, 32> = (&tree).into();
+        assert_eq!(immutable.size(), TREE_SIZE + 1);
+        assert_eq!(
+            immutable
+                .nearest_one::<SquaredEuclidean>(&[0.5, 0.5, 0.5])
+                .item,
+            1
+        );
+
+        let mutable: KdTree<Flt, u32, 3, 32, u32> = (&immutable).into();
+        assert_eq!(mutable.size(), TREE_SIZE as u32 + 1);
+
+        let mut expected: Vec<_> = tree.contents().1;
+        let mut actual: Vec<_> = mutable.iter().map(|(item, _)| item).collect();
+        expected.sort();
+        actual.sort();
+        assert_eq!(actual, expected);
+
+        for _ in 0..100 {
+            let query = rand::random::<[Flt; 3]>();
+            assert_eq!(
+                mutable.nearest_one::<SquaredEuclidean>(&query),
+                immutable.nearest_one::<SquaredEuclidean>(&query)
+            );
+            assert_eq!(
+                mutable.within::<SquaredEuclidean>(&query, 0.01),
+                immutable.within::<SquaredEuclidean>(&query, 0.01)
+            );
+        }
+    }
+
+    #[test]
+    fn can_add_deferred_bursts_and_finalize_splits() {
+        const INITIAL_SIZE: usize = 1_000;
+        const BURST_SIZE: usize = 2_000;
+        const NUM_BURSTS: usize = 5;
+
+        let mut tree: KdTree<Flt, u32, 3, 32, u32> = KdTree::new();
+        let mut incremental: KdTree<Flt, u32, 3, 32, u32> = KdTree::new();
+        let mut points: Vec<[Flt; 3]> = vec![];
+
+        for _ in 0..INITIAL_SIZE {
+            let point = rand::random::<[Flt; 3]>();
+            tree.add(&point, points.len() as u32);
+            incremental.add(&point, points.len() as u32);
+            points.push(point);
+        }
+
+        for _ in 0..NUM_BURSTS {
+            // each burst is concentrated in a small region, so that the same leaves overflow
+            let burst: Vec<([Flt; 3], u32)> = (0..BURST_SIZE)
+                .map(|idx| {
+                    let point = rand::random::<[Flt; 3]>().map(|x| 0.4 + x * 0.1);
+                    (point, (points.len() + idx) as u32)
+                })
+                .collect();
+            for (point, item) in &burst {
+                incremental.add(point, *item);
+            }
+            points.extend(burst.iter().map(|(point, _)| *point));
+
+            tree.add_deferred(burst);
+            assert_eq!(tree.size(), incremental.size() - BURST_SIZE as u32);
+            tree.finalize_splits();
+            assert_eq!(tree.size(), incremental.size());
+        }
+
+        let mut items: Vec<u32> = tree.iter().map(|(item, _)| item).collect();
+        items.sort();
+        assert_eq!(items, (0..points.len() as u32).collect::<Vec<_>>());
+
+        for (idx, point) in points.iter().enumerate() {
+            let nearest = tree.nearest_one::<SquaredEuclidean>(point);
+            assert_eq!(nearest.distance, 0.0);
+            assert_eq!(nearest.item, idx as u32);
+        }
+
+        for _ in 0..100 {
+            let query = rand::random::<[Flt; 3]>();
+            // sort equidistant results by item, as their order is otherwise arbitrary
+            let sorted = |mut results: Vec<NearestNeighbour<Flt, u32>>| {
+                results.sort_by(|a, b| {
+                    a.distance
+                        .partial_cmp(&b.distance)
+                        .unwrap()
+                        .then(a.item.cmp(&b.item))
+                });
+                results
+            };
+            assert_eq!(
+                sorted(tree.within::<SquaredEuclidean>(&query, 0.01)),
+                sorted(incremental.within::<SquaredEuclidean>(&query, 0.01))
+            );
+        }
+
+        // the tree can still be modified normally afterwards
+        for (idx, point) in points.iter().enumerate().take(100) {
+            assert_eq!(tree.remove(point, idx as u32), 1);
+        }
+        tree.add(&[0.45, 0.45, 0.45], points.len() as u32);
+        assert_eq!(
+            tree.nearest_one::<SquaredEuclidean>(&[0.45, 0.45, 0.45])
+                .item,
+            points.len() as u32
+        );
+    }
+
+    #[test]
+    fn can_extend_with_batches_of_items() {
+        let mut tree: KdTree<Flt, u32, 3, 32, u32> = KdTree::new();
+        let mut points: Vec<[Flt; 3]> = vec![];
+
+        for _ in 0..10 {
+            let chunk: Vec<([Flt; 3], u32)> = (0..1_000)
+                .map(|idx| (rand::random::<[Flt; 3]>(), (points.len() + idx) as u32))
+                .collect();
+            points.extend(chunk.iter().map(|(point, _)| *point));
+
+            tree.extend(chunk);
+            assert_eq!(tree.size(), points.len() as u32);
+        }
+
+        tree.extend([(&[0.5, 0.5, 0.5], &(points.len() as u32))]);
+        points.push([0.5, 0.5, 0.5]);
+
+        for (idx, point) in points.iter().enumerate() {
+            let nearest = tree.nearest_one::<SquaredEuclidean>(point);
+            assert_eq!(nearest.distance, 0.0);
+            assert_eq!(nearest.item, idx as u32);
+        }
+    }
+
+    #[test]
+    fn rebalance_includes_deferred_items() {
+        let mut tree: KdTree<Flt, u32, 2, 4, u32> = KdTree::new();
+        tree.add(&[0.0, 0.0], 0);
+        tree.add_deferred((1..100).map(|idx| ([idx as Flt, 0.0], idx)));
+        tree.finalize_splits();
+        tree.add_deferred([([-1.0, 0.0], 100)]);
+
+        tree.rebalance();
+
+        assert_eq!(tree.size(), 101);
+        assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[-0.9, 0.0]).item, 100);
+
+        // finalizing with nothing deferred does nothing
+        let before = tree.clone();
+        tree.finalize_splits();
+        assert_eq!(tree, before);
+    }
+
+    #[test]
+    fn from_slice_balanced_chains_leaves_of_identical_points() {
+        let points = vec![[1.0 as Flt, 2.0]; 10];
+        let mut tree: KdTree<Flt, u32, 2, 4, u32> = KdTree::from_slice_balanced(&points);
+        assert_eq!(tree.size(), 10);
+
+        let mut found: Vec<u32> = tree
+            .within_unsorted::<SquaredEuclidean>(&[n(1.0), n(2.0)], n(0.01))
+            .iter()
+            .map(|neighbour| neighbour.item)
+            .collect();
+        found.sort_unstable();
+        assert_eq!(found, (0..10).collect::<Vec<_>>());
+        assert_eq!(
+            tree.nearest_n::<SquaredEuclidean>(&[n(1.1), n(2.0)], 10)
+                .iter()
+                .filter(|neighbour| neighbour.distance < n(0.011))
+                .count(),
+            10
+        );
+
+        for item in 0..10 {
+            assert_eq!(tree.remove(&[n(1.0), n(2.0)], item), 1);
+        }
+        assert_eq!(tree.size(), 0);
+    }
+
+    #[test]
+    fn can_add_and_remove_more_identical_points_than_fit_in_a_leaf() {
+        let mut tree: KdTree<Flt, u32, 2, 4, u32> = KdTree::new();
+        for item in 0..20 {
+            tree.add(&[n(1.0), n(2.0)], item);
+        }
+        tree.add(&[n(0.5), n(2.0)], 20);
+        tree.add(&[n(1.5), n(2.0)], 21);
+        assert_eq!(tree.size(), 22);
+
+        let nearest = tree.nearest_n::<SquaredEuclidean>(&[n(1.0), n(2.0)], 20);
+        assert!(nearest
+            .iter()
+            .all(|neighbour| neighbour.distance == 0.0 && neighbour.item < 20));
+        assert_eq!(
+            tree.within::<SquaredEuclidean>(&[n(1.0), n(2.0)], n(0.01))
+                .len(),
+            20
+        );
+        assert_eq!(
+            tree.within_unsorted::<SquaredEuclidean>(&[n(1.1), n(2.0)], n(0.2))
+                .len(),
+            21
+        );
+        assert_eq!(
+            tree.nearest_one::<SquaredEuclidean>(&[n(0.4), n(2.0)]).item,
+            20
+        );
+
+        assert!(!tree.add_with_policy(&[n(1.0), n(2.0)], 99, DuplicatePolicy::Reject));
+        assert_eq!(tree.update(&[n(1.0), n(2.0)], &[n(3.0), n(3.0)], 7), 1);
+        assert_eq!(
+            tree.nearest_one::<SquaredEuclidean>(&[n(3.0), n(3.0)]).item,
+            7
+        );
+
+        for item in (0..20).filter(|&item| item != 7) {
+            assert_eq!(tree.remove(&[n(1.0), n(2.0)], item), 1);
+        }
+        assert_eq!(tree.size(), 3);
+        assert!(tree.add_with_policy(&[n(1.0), n(2.0)], 99, DuplicatePolicy::Reject));
+    }
+
+    #[test]
+    fn can_add_lattice_points_shared_by_more_items_than_fit_in_a_leaf() {
+        // 16 lattice points, each shared by 25 items, added in a scrambled order
+        let points: Vec<[Flt; 2]> = (0..400)
+            .map(|i| (i * 263) % 400)
+            .map(|i| [(i % 4) as Flt, ((i / 4) % 4) as Flt])
+            .collect();
+
+        let mut tree: KdTree<Flt, u32, 2, 4, u32> = KdTree::new();
+        for (idx, point) in points.iter().enumerate() {
+            tree.add(point, idx as u32);
+        }
+        assert_eq!(tree.size(), 400);
+
+        for x in 0..4 {
+            for y in 0..4 {
+                let query = [x as Flt, y as Flt];
+                let found = tree.within_unsorted::<SquaredEuclidean>(&query, n(0.5));
+                assert_eq!(found.len(), 25);
+                assert!(found
+                    .iter()
+                    .all(|neighbour| points[neighbour.item as usize] == query));
+
+                // from either side of the lattice point, so that both sides of each
+                // split at its coordinates are searched first
+                for offset in [n(-0.1), n(0.1)] {
+                    let nearby = [query[0] + offset, query[1] + offset];
+                    let nearest = tree.nearest_n::<SquaredEuclidean>(&nearby, 25);
+                    assert!(nearest
+                        .iter()
+                        .all(|neighbour| points[neighbour.item as usize] == query));
+                    assert_eq!(
+                        points[tree.nearest_one::<SquaredEuclidean>(&nearby).item as usize],
+                        query
+                    );
+                }
+            }
+        }
+
+        for (idx, point) in points.iter().enumerate() {
+            assert_eq!(tree.remove(point, idx as u32), 1);
+        }
+        assert_eq!(tree.size(), 0);
+    }
 }
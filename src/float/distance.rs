@@ -5,7 +5,7 @@
 // use std::arch::x86_64::*;
 
 use crate::float::kdtree::Axis;
-use crate::traits::DistanceMetric;
+use crate::traits::{DistanceMetric, QueryMetric, StatefulDistanceMetric};
 
 /// Returns the Manhattan / "taxi cab" distance between two points.
 ///
@@ -73,3 +73,244 @@ impl<A: Axis, const K: usize> DistanceMetric<A, K> for SquaredEuclidean {
         (a - b) * (a - b)
     }
 }
+
+/// Returns the euclidean distance between two points, in the same units as their
+/// coordinates.
+///
+/// Searches the tree using [`SquaredEuclidean`], so is just as fast, but takes the maximum
+/// distance of a query and reports the distances of its results unsquared, sparing callers
+/// from having to remember to square and take square roots themselves.
+///
+/// Distances are converted at the boundary of the query builder, so `Euclidean` is chosen
+/// with [`QueryBuilder::query_metric`](`crate::query_builder::QueryBuilder::query_metric`)
+/// rather than passed directly to query methods such as `nearest_n`.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::float::distance::Euclidean;
+/// use kiddo::ImmutableKdTree;
+///
+/// let points = vec![[0.0f64, 0.0], [3.0, 4.0], [6.0, 8.0]];
+/// let tree: ImmutableKdTree<f64, 2> = ImmutableKdTree::new_from_slice(&points);
+///
+/// let within = tree.query(&[0.0, 0.0]).query_metric::<Euclidean>().max_dist(6.0).run();
+///
+/// assert_eq!(within.iter().map(|n| n.distance).collect::<Vec<_>>(), vec![0.0, 5.0]);
+/// ```
+pub struct Euclidean {}
+
+impl<A: Axis + num_traits::Float, const K: usize> QueryMetric<A, K> for Euclidean {
+    type Search = SquaredEuclidean;
+
+    #[inline]
+    fn to_search_units(dist: A) -> A {
+        // negative maximum distances match nothing, so must stay negative when squared
+        if dist > A::zero() {
+            dist * dist
+        } else {
+            dist
+        }
+    }
+
+    #[inline]
+    fn from_search_units(dist: A) -> A {
+        dist.sqrt()
+    }
+}
+
+/// Returns the cosine distance, `1 - cos θ`, between two points that are unit vectors,
+/// where `θ` is the angle between them.
+///
+/// Cosine distance is not a metric that can prune a k-d tree search in general, so both
+/// the points in the tree and the query points must first be scaled to unit length, for
+/// example with [`Cosine::normalize`]. For unit vectors, `1 - cos θ` is exactly half of
+/// the squared euclidean distance between them, which is how it is calculated here, so
+/// searches are pruned correctly and give exact results. Ordering results by cosine
+/// distance gives the same order as by cosine similarity, with the most similar first.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::float::distance::Cosine;
+/// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+///
+/// let embeddings: Vec<[f32; 3]> = vec![[3.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 0.0, 2.0]]
+///     .iter()
+///     .map(Cosine::normalize)
+///     .collect();
+/// let tree: ImmutableKdTree<f32, u32, 3, 32> = ImmutableKdTree::new_from_slice(&embeddings);
+///
+/// let nearest = tree.nearest_one::<Cosine>(&Cosine::normalize(&[5.0, 4.0, 0.0]));
+/// assert_eq!(nearest.item, 1);
+///
+/// let orthogonal = tree.nearest_one::<Cosine>(&Cosine::normalize(&[0.0, 0.0, -1.0]));
+/// assert!((orthogonal.distance - 1.0).abs() < 1e-6);
+/// ```
+pub struct Cosine {}
+
+impl Cosine {
+    /// Returns `point` scaled to unit length, for use with [`Cosine`].
+    ///
+    /// A point at the origin has no direction, and is returned unchanged.
+    pub fn normalize<A: Axis + num_traits::Float, const K: usize>(point: &[A; K]) -> [A; K] {
+        let length = point
+            .iter()
+            .fold(A::zero(), |acc, &val| acc + val * val)
+            .sqrt();
+
+        if length == A::zero() {
+            return *point;
+        }
+
+        point.map(|val| val / length)
+    }
+}
+
+impl<A: Axis, const K: usize> DistanceMetric<A, K> for Cosine {
+    #[inline]
+    fn dist(a: &[A; K], b: &[A; K]) -> A {
+        a.iter()
+            .zip(b.iter())
+            .map(|(&a_val, &b_val)| <Self as DistanceMetric<A, K>>::dist1(a_val, b_val))
+            .fold(A::zero(), std::ops::Add::add)
+    }
+
+    #[inline]
+    fn dist1(a: A, b: A) -> A {
+        let half = A::one() / (A::one() + A::one());
+        (a - b) * (a - b) * half
+    }
+}
+
+/// Returns the Chebyshev / L∞ distance between two points: the largest of the
+/// distances along each axis.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::traits::DistanceMetric;
+/// use kiddo::float::distance::Chebyshev;
+///
+/// assert_eq!(0f32, Chebyshev::dist(&[0f32, 0f32], &[0f32, 0f32]));
+/// assert_eq!(1f32, Chebyshev::dist(&[0f32, 0f32], &[1f32, 0f32]));
+/// assert_eq!(2f32, Chebyshev::dist(&[0f32, 0f32], &[1f32, -2f32]));
+/// ```
+pub struct Chebyshev {}
+
+impl<A: Axis, const K: usize> DistanceMetric<A, K> for Chebyshev {
+    #[inline]
+    fn dist(a: &[A; K], b: &[A; K]) -> A {
+        a.iter()
+            .zip(b.iter())
+            .map(|(&a_val, &b_val)| (a_val - b_val).abs())
+            .fold(A::zero(), A::max)
+    }
+
+    #[inline]
+    fn dist1(a: A, b: A) -> A {
+        (a - b).abs()
+    }
+
+    const MAX_OF_AXES: bool = true;
+}
+
+/// Returns the Minkowski distance of order `P` between two points, raised to the
+/// power `P`.
+///
+/// As with [`SquaredEuclidean`], the final root is not taken, which preserves the
+/// same distance ordering. `Minkowski<1>` is equivalent to [`Manhattan`] and
+/// `Minkowski<2>` to [`SquaredEuclidean`]. `P` must be at least 1.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::traits::DistanceMetric;
+/// use kiddo::float::distance::Minkowski;
+///
+/// assert_eq!(0f32, Minkowski::<3>::dist(&[0f32, 0f32], &[0f32, 0f32]));
+/// assert_eq!(8f32, Minkowski::<3>::dist(&[0f32, 0f32], &[2f32, 0f32]));
+/// assert_eq!(9f32, Minkowski::<3>::dist(&[0f32, 0f32], &[1f32, -2f32]));
+/// ```
+pub struct Minkowski<const P: u32> {}
+
+impl<A: Axis, const K: usize, const P: u32> DistanceMetric<A, K> for Minkowski<P> {
+    #[inline]
+    fn dist(a: &[A; K], b: &[A; K]) -> A {
+        a.iter()
+            .zip(b.iter())
+            .map(|(&a_val, &b_val)| <Self as DistanceMetric<A, K>>::dist1(a_val, b_val))
+            .fold(A::zero(), std::ops::Add::add)
+    }
+
+    #[inline]
+    fn dist1(a: A, b: A) -> A {
+        const { assert!(P >= 1, "Minkowski order P must be at least 1") };
+        (a - b).abs().powi(P as i32)
+    }
+}
+
+/// Returns the squared euclidean distance between two points, with the contribution
+/// of each axis scaled by a per-axis weight.
+///
+/// Useful when the axes of the points in the tree have heterogeneous scales, or when
+/// their relative importance is only known at query time. Unlike the other metrics in this
+/// module, it carries state, and so is used with the `*_with_metric` query methods
+/// rather than via a type parameter.
+///
+/// All weights must be non-negative.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::KdTree;
+/// use kiddo::float::distance::WeightedSquaredEuclidean;
+/// use kiddo::traits::StatefulDistanceMetric;
+///
+/// let metric = WeightedSquaredEuclidean::new([1f32, 4f32]);
+///
+/// assert_eq!(1f32, metric.dist(&[0f32, 0f32], &[1f32, 0f32]));
+/// assert_eq!(4f32, metric.dist(&[0f32, 0f32], &[0f32, 1f32]));
+///
+/// let mut tree: KdTree<f32, 2> = KdTree::new();
+/// tree.add(&[2f32, 0f32], 100);
+/// tree.add(&[0f32, 1.5f32], 101);
+///
+/// assert_eq!(tree.nearest_one_with_metric(&metric, &[0f32, 0f32]).item, 100);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WeightedSquaredEuclidean<A, const K: usize> {
+    weights: [A; K],
+}
+
+impl<A: Axis, const K: usize> WeightedSquaredEuclidean<A, K> {
+    /// Creates a new `WeightedSquaredEuclidean` metric with the given per-axis weights
+    pub fn new(weights: [A; K]) -> Self {
+        debug_assert!(
+            weights.iter().all(|&w| w >= A::zero()),
+            "weights must be non-negative"
+        );
+        Self { weights }
+    }
+
+    /// Returns the per-axis weights used by this metric
+    pub fn weights(&self) -> &[A; K] {
+        &self.weights
+    }
+}
+
+impl<A: Axis, const K: usize> StatefulDistanceMetric<A, K> for WeightedSquaredEuclidean<A, K> {
+    #[inline]
+    fn dist(&self, a: &[A; K], b: &[A; K]) -> A {
+        a.iter()
+            .zip(b.iter())
+            .zip(self.weights.iter())
+            .map(|((&a_val, &b_val), &w)| w * (a_val - b_val) * (a_val - b_val))
+            .fold(A::zero(), std::ops::Add::add)
+    }
+
+    #[inline]
+    fn dist1(&self, a: A, b: A, dim: usize) -> A {
+        self.weights[dim] * (a - b) * (a - b)
+    }
+}
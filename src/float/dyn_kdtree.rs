@@ -0,0 +1,1047 @@
+//! Floating point k-d tree whose number of dimensions is chosen at runtime.
+//!
+//! [`DynKdTree`] is for when the number of dimensions is not known at compile time,
+//! for example when loading arbitrary datasets or when being called from another
+//! language. Points are passed as slices rather than arrays. Queries are somewhat slower
+//! than those of [`KdTree`](`crate::float::kdtree::KdTree`), which should be preferred
+//! whenever the number of dimensions is known up-front.
+use std::collections::BinaryHeap;
+
+use az::{Cast, CheckedCast};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::common::nearest_n_results::NearestNResults;
+use crate::construction_error::ConstructionError;
+use crate::float::kdtree::Axis;
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{accumulate_dist, is_stem_index, Content, DistanceMetric, Index};
+
+/// The bucket size used by [`DynKdTree::new`]
+pub const DEFAULT_BUCKET_SIZE: usize = 32;
+
+/// Floating point k-d tree with a number of dimensions that is set at runtime
+///
+/// Behaves like the standard [`KdTree`](`crate::float::kdtree::KdTree`), other than
+/// points being passed as slices, each of which must have a length equal to
+/// [`dims`](DynKdTree::dims). The bucket size is also set at runtime.
+///
+/// Distances are calculated one axis at a time using [`DistanceMetric::dist1`], so
+/// queries accept any metric that implements [`DistanceMetric`] for every `K`, such as
+/// [`SquaredEuclidean`](`crate::SquaredEuclidean`) or [`Manhattan`](`crate::Manhattan`).
+/// The `1` in the `DistanceMetric<A, 1>` bounds on the query methods reflects this.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::float::dyn_kdtree::DynKdTree;
+/// use kiddo::SquaredEuclidean;
+///
+/// let dims = 4;
+/// let mut tree: DynKdTree<f64, u32> = DynKdTree::new(dims);
+///
+/// tree.add(&[1.0, 2.0, 5.0, 1.0], 100);
+/// tree.add(&[2.0, 3.0, 6.0, 1.0], 101);
+///
+/// let nearest = tree.nearest_one::<SquaredEuclidean>(&[1.0, 2.0, 5.1, 1.0]);
+/// assert_eq!(nearest.item, 100);
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DynKdTree<A, T> {
+    dims: usize,
+    bucket_size: usize,
+    leaves: Vec<DynLeafNode<A, T>>,
+    stems: Vec<DynStemNode<A>>,
+    root_index: u32,
+    size: usize,
+}
+
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+struct DynStemNode<A> {
+    left: u32,
+    right: u32,
+    split_val: A,
+}
+
+/// The points in a leaf are stored one after another, `dims` values at a time
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+struct DynLeafNode<A, T> {
+    content_points: Vec<A>,
+    content_items: Vec<T>,
+}
+
+impl<A: Axis, T: Content> DynKdTree<A, T> {
+    /// Creates a new, empty tree for points with `dims` dimensions, using the
+    /// default bucket size of 32.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dims` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::float::dyn_kdtree::DynKdTree;
+    ///
+    /// let tree: DynKdTree<f64, u32> = DynKdTree::new(3);
+    ///
+    /// assert_eq!(tree.dims(), 3);
+    /// assert_eq!(tree.size(), 0);
+    /// ```
+    pub fn new(dims: usize) -> Self {
+        Self::with_bucket_size(dims, DEFAULT_BUCKET_SIZE)
+    }
+
+    /// Creates a new, empty tree for points with `dims` dimensions, whose leaves
+    /// each hold up to `bucket_size` points.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dims` is zero or `bucket_size` is less than two.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::float::dyn_kdtree::DynKdTree;
+    ///
+    /// let tree: DynKdTree<f64, u32> = DynKdTree::with_bucket_size(3, 64);
+    ///
+    /// assert_eq!(tree.bucket_size(), 64);
+    /// ```
+    pub fn with_bucket_size(dims: usize, bucket_size: usize) -> Self {
+        assert!(dims > 0, "DynKdTree must have at least one dimension");
+        assert!(bucket_size >= 2, "DynKdTree bucket size must be at least 2");
+
+        DynKdTree {
+            dims,
+            bucket_size,
+            leaves: vec![DynLeafNode::with_capacity(dims, bucket_size)],
+            stems: Vec::new(),
+            root_index: u32::leaf_offset(),
+            size: 0,
+        }
+    }
+
+    /// Returns the number of dimensions of the points stored in the tree
+    #[inline]
+    pub fn dims(&self) -> usize {
+        self.dims
+    }
+
+    /// Returns the maximum number of points that are stored in each leaf
+    #[inline]
+    pub fn bucket_size(&self) -> usize {
+        self.bucket_size
+    }
+
+    /// Returns the current number of elements stored in the tree
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Iterate over all `(item, point)` tuples in arbitrary order.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::float::dyn_kdtree::DynKdTree;
+    ///
+    /// let mut tree: DynKdTree<f64, u32> = DynKdTree::new(2);
+    /// tree.add(&[1.0, 2.0], 10);
+    ///
+    /// let pairs: Vec<_> = tree.iter().collect();
+    /// assert_eq!(pairs, vec![(10, &[1.0, 2.0][..])]);
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (T, &[A])> + '_ {
+        self.leaves.iter().flat_map(move |leaf| {
+            leaf.content_items
+                .iter()
+                .copied()
+                .zip(leaf.content_points.chunks_exact(self.dims))
+        })
+    }
+
+    /// Returns the point that `item` is stored at, or `None` if it isn't in the tree.
+    ///
+    /// If `item` has been added more than once, any one of its points may be returned.
+    /// Every leaf is scanned, so this takes time proportional to the size of the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::float::dyn_kdtree::DynKdTree;
+    ///
+    /// let mut tree: DynKdTree<f64, u32> = DynKdTree::new(2);
+    /// tree.add(&[1.0, 2.0], 10);
+    ///
+    /// assert_eq!(tree.get(10), Some(&[1.0, 2.0][..]));
+    /// assert!(!tree.contains_item(20));
+    /// ```
+    pub fn get(&self, item: T) -> Option<&[A]> {
+        self.iter()
+            .find(|&(stored, _)| stored == item)
+            .map(|(_, point)| point)
+    }
+
+    /// Returns whether `item` is stored in the tree. See [`get`](DynKdTree::get) for details.
+    pub fn contains_item(&self, item: T) -> bool {
+        self.leaves
+            .iter()
+            .any(|leaf| leaf.content_items.contains(&item))
+    }
+
+    /// Adds an item to the tree.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `point` does not have [`dims`](DynKdTree::dims) elements, or if more than
+    /// `bucket_size - 1` items are added with the same position on one axis.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::float::dyn_kdtree::DynKdTree;
+    ///
+    /// let mut tree: DynKdTree<f64, u32> = DynKdTree::new(3);
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100);
+    ///
+    /// assert_eq!(tree.size(), 1);
+    /// ```
+    pub fn add(&mut self, point: &[A], item: T) {
+        self.check_dims(point);
+
+        if let Err(err) = self.add_unchecked(point, item) {
+            panic!("{err}");
+        }
+    }
+
+    /// Adds an item to the tree without checking the number of dimensions of `point`,
+    /// leaving the tree unchanged if a leaf needed splitting but could not be
+    fn add_unchecked(&mut self, point: &[A], item: T) -> Result<(), ConstructionError> {
+        let mut node_idx = self.root_index;
+        let mut split_dim = 0;
+        let mut parent: Option<(u32, bool)> = None;
+
+        while is_stem_index(node_idx) {
+            let stem = &self.stems[node_idx as usize];
+            let is_left_child = point[split_dim] < stem.split_val;
+            parent = Some((node_idx, is_left_child));
+            node_idx = if is_left_child { stem.left } else { stem.right };
+            split_dim = (split_dim + 1) % self.dims;
+        }
+
+        let mut leaf_idx = (node_idx - u32::leaf_offset()) as usize;
+        if self.leaves[leaf_idx].content_items.len() == self.bucket_size {
+            let stem_idx = self.split(leaf_idx, split_dim, parent)?;
+            let stem = &self.stems[stem_idx as usize];
+            leaf_idx = (if point[split_dim] < stem.split_val {
+                stem.left
+            } else {
+                stem.right
+            } - u32::leaf_offset()) as usize;
+        }
+
+        let leaf = &mut self.leaves[leaf_idx];
+        leaf.content_points.extend_from_slice(point);
+        leaf.content_items.push(item);
+        self.size += 1;
+
+        Ok(())
+    }
+
+    /// Removes an item from the tree.
+    ///
+    /// Returns the number of entries removed, which may be more than one if the same
+    /// item was added at the same point more than once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `point` does not have [`dims`](DynKdTree::dims) elements.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::float::dyn_kdtree::DynKdTree;
+    ///
+    /// let mut tree: DynKdTree<f64, u32> = DynKdTree::new(3);
+    /// tree.add(&[1.0, 2.0, 5.0], 100);
+    /// tree.add(&[1.0, 2.0, 5.0], 200);
+    ///
+    /// assert_eq!(tree.remove(&[1.0, 2.0, 5.0], 100), 1);
+    /// assert_eq!(tree.size(), 1);
+    /// ```
+    pub fn remove(&mut self, point: &[A], item: T) -> usize {
+        self.check_dims(point);
+
+        let mut node_idx = self.root_index;
+        let mut split_dim = 0;
+        while is_stem_index(node_idx) {
+            let stem = &self.stems[node_idx as usize];
+            node_idx = if point[split_dim] < stem.split_val {
+                stem.left
+            } else {
+                stem.right
+            };
+            split_dim = (split_dim + 1) % self.dims;
+        }
+
+        let dims = self.dims;
+        let leaf = &mut self.leaves[(node_idx - u32::leaf_offset()) as usize];
+        let mut removed = 0;
+        let mut idx = 0;
+        while idx < leaf.content_items.len() {
+            if leaf.content_items[idx] == item
+                && &leaf.content_points[idx * dims..(idx + 1) * dims] == point
+            {
+                leaf.swap_remove(idx, dims);
+                removed += 1;
+            } else {
+                idx += 1;
+            }
+        }
+        self.size -= removed;
+
+        removed
+    }
+
+    /// Finds the nearest element to `query`, using the specified distance metric.
+    ///
+    /// If the tree is empty, the returned distance is infinite.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `query` does not have [`dims`](DynKdTree::dims) elements.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::float::dyn_kdtree::DynKdTree;
+    /// use kiddo::SquaredEuclidean;
+    ///
+    /// let mut tree: DynKdTree<f64, u32> = DynKdTree::new(3);
+    /// tree.add(&[1.0, 2.0, 5.0], 100);
+    /// tree.add(&[2.0, 3.0, 6.0], 101);
+    ///
+    /// let nearest = tree.nearest_one::<SquaredEuclidean>(&[1.0, 2.0, 5.1]);
+    ///
+    /// assert!((nearest.distance - 0.01f64).abs() < f64::EPSILON);
+    /// assert_eq!(nearest.item, 100);
+    /// ```
+    pub fn nearest_one<D>(&self, query: &[A]) -> NearestNeighbour<A, T>
+    where
+        D: DistanceMetric<A, 1>,
+    {
+        self.check_dims(query);
+
+        let mut nearest = NearestNeighbour {
+            distance: A::infinity(),
+            item: T::zero(),
+        };
+        let mut off = vec![A::zero(); self.dims];
+        self.nearest_one_recurse::<D>(query, self.root_index, 0, &mut nearest, &mut off, A::zero());
+
+        nearest
+    }
+
+    /// Finds up to `qty` elements, sorted by distance from `query` (nearest first),
+    /// using the specified distance metric.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `query` does not have [`dims`](DynKdTree::dims) elements.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::float::dyn_kdtree::DynKdTree;
+    /// use kiddo::SquaredEuclidean;
+    ///
+    /// let mut tree: DynKdTree<f64, u32> = DynKdTree::new(3);
+    /// tree.add(&[1.0, 2.0, 5.0], 100);
+    /// tree.add(&[2.0, 3.0, 6.0], 101);
+    /// tree.add(&[8.0, 8.0, 8.0], 102);
+    ///
+    /// let nearest: Vec<_> = tree.nearest_n::<SquaredEuclidean>(&[1.0, 2.0, 5.1], 2);
+    ///
+    /// assert_eq!(nearest.len(), 2);
+    /// assert_eq!(nearest[0].item, 100);
+    /// assert_eq!(nearest[1].item, 101);
+    /// ```
+    pub fn nearest_n<D>(&self, query: &[A], qty: usize) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, 1>,
+    {
+        self.check_dims(query);
+
+        if qty == 0 {
+            return Vec::new();
+        }
+
+        let mut results: BinaryHeap<NearestNeighbour<A, T>> = BinaryHeap::with_capacity(qty);
+        let mut off = vec![A::zero(); self.dims];
+        self.nearest_n_recurse::<D>(query, self.root_index, 0, &mut results, &mut off, A::zero());
+
+        results.into_sorted_vec()
+    }
+
+    /// Finds all elements within `dist` of `query`, sorted by distance (nearest first),
+    /// using the specified distance metric.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `query` does not have [`dims`](DynKdTree::dims) elements.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::float::dyn_kdtree::DynKdTree;
+    /// use kiddo::SquaredEuclidean;
+    ///
+    /// let mut tree: DynKdTree<f64, u32> = DynKdTree::new(3);
+    /// tree.add(&[1.0, 2.0, 5.0], 100);
+    /// tree.add(&[2.0, 3.0, 6.0], 101);
+    /// tree.add(&[8.0, 8.0, 8.0], 102);
+    ///
+    /// let within = tree.within::<SquaredEuclidean>(&[1.0, 2.0, 5.0], 10f64);
+    ///
+    /// assert_eq!(within.len(), 2);
+    /// assert_eq!(within[0].item, 100);
+    /// ```
+    pub fn within<D>(&self, query: &[A], dist: A) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, 1>,
+    {
+        let mut results = self.within_unsorted::<D>(query, dist);
+        results.sort();
+
+        results
+    }
+
+    /// Finds all elements within `dist` of `query`, in arbitrary order, using the
+    /// specified distance metric. Faster than [`within`](DynKdTree::within).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `query` does not have [`dims`](DynKdTree::dims) elements.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::float::dyn_kdtree::DynKdTree;
+    /// use kiddo::SquaredEuclidean;
+    ///
+    /// let mut tree: DynKdTree<f64, u32> = DynKdTree::new(3);
+    /// tree.add(&[1.0, 2.0, 5.0], 100);
+    /// tree.add(&[2.0, 3.0, 6.0], 101);
+    ///
+    /// let within = tree.within_unsorted::<SquaredEuclidean>(&[1.0, 2.0, 5.0], 10f64);
+    ///
+    /// assert_eq!(within.len(), 2);
+    /// ```
+    pub fn within_unsorted<D>(&self, query: &[A], dist: A) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, 1>,
+    {
+        self.check_dims(query);
+
+        let mut results = Vec::new();
+        let mut off = vec![A::zero(); self.dims];
+        self.within_recurse::<D>(
+            query,
+            dist,
+            self.root_index,
+            0,
+            &mut results,
+            &mut off,
+            A::zero(),
+        );
+
+        results
+    }
+
+    /// Returns `true` if there is any element within `dist` of `query`, using the
+    /// specified distance metric.
+    ///
+    /// Stops searching as soon as a match is found, so is much faster than checking
+    /// whether the results of [`within_unsorted`](DynKdTree::within_unsorted) are empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `query` does not have [`dims`](DynKdTree::dims) elements.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::float::dyn_kdtree::DynKdTree;
+    /// use kiddo::SquaredEuclidean;
+    ///
+    /// let mut tree: DynKdTree<f64, u32> = DynKdTree::new(3);
+    /// tree.add(&[1.0, 2.0, 5.0], 100);
+    /// tree.add(&[2.0, 3.0, 6.0], 101);
+    ///
+    /// assert!(tree.any_within::<SquaredEuclidean>(&[1.0, 2.0, 4.0], 2f64));
+    /// assert!(!tree.any_within::<SquaredEuclidean>(&[9.0, 9.0, 9.0], 2f64));
+    /// ```
+    pub fn any_within<D>(&self, query: &[A], dist: A) -> bool
+    where
+        D: DistanceMetric<A, 1>,
+    {
+        self.check_dims(query);
+
+        let mut off = vec![A::zero(); self.dims];
+        self.any_within_recurse::<D>(query, dist, self.root_index, 0, &mut off, A::zero())
+    }
+
+    fn check_dims(&self, point: &[A]) {
+        assert_eq!(
+            point.len(),
+            self.dims,
+            "point has {} dimensions, but the tree has {}",
+            point.len(),
+            self.dims
+        );
+    }
+
+    /// Splits the full leaf at `leaf_idx` in two on `split_dim`, replacing it in its
+    /// parent with a new stem, whose index is returned. The tree is left unchanged if
+    /// every item in the leaf has the same position on `split_dim`.
+    fn split(
+        &mut self,
+        leaf_idx: usize,
+        split_dim: usize,
+        parent: Option<(u32, bool)>,
+    ) -> Result<u32, ConstructionError> {
+        let dims = self.dims;
+        let orig = &mut self.leaves[leaf_idx];
+
+        let mut vals: Vec<A> = orig
+            .content_points
+            .chunks_exact(dims)
+            .map(|point| point[split_dim])
+            .collect();
+        vals.sort_unstable_by(|a, b| a.partial_cmp(b).expect("Leaf node sort failed."));
+
+        // items that share a position with the median on the split dimension must all
+        // end up on the same side of the split, so move the pivot down past them, or
+        // if that would leave the left side empty, up past them instead
+        let mid = vals.len() / 2;
+        let mut pivot_idx = mid;
+        while pivot_idx > 0 && vals[pivot_idx - 1] == vals[mid] {
+            pivot_idx -= 1;
+        }
+        if pivot_idx == 0 {
+            pivot_idx = mid;
+            while vals[pivot_idx] == vals[mid] {
+                pivot_idx += 1;
+
+                if pivot_idx == vals.len() {
+                    return Err(ConstructionError::DuplicatePositions);
+                }
+            }
+        }
+        let split_val = vals[pivot_idx];
+
+        let mut right = DynLeafNode::with_capacity(dims, self.bucket_size);
+        let mut idx = 0;
+        while idx < orig.content_items.len() {
+            if orig.content_points[idx * dims + split_dim] >= split_val {
+                right
+                    .content_points
+                    .extend_from_slice(&orig.content_points[idx * dims..(idx + 1) * dims]);
+                right.content_items.push(orig.content_items[idx]);
+                orig.swap_remove(idx, dims);
+            } else {
+                idx += 1;
+            }
+        }
+
+        self.leaves.push(right);
+        self.stems.push(DynStemNode {
+            left: leaf_idx as u32 + u32::leaf_offset(),
+            right: (self.leaves.len() - 1) as u32 + u32::leaf_offset(),
+            split_val,
+        });
+        let new_stem_idx = (self.stems.len() - 1) as u32;
+
+        match parent {
+            Some((parent_idx, true)) => self.stems[parent_idx as usize].left = new_stem_idx,
+            Some((parent_idx, false)) => self.stems[parent_idx as usize].right = new_stem_idx,
+            None => self.root_index = new_stem_idx,
+        }
+
+        Ok(new_stem_idx)
+    }
+
+    /// Returns the children of a stem, closest to `query` first, along with the new
+    /// offset of `query` from the split
+    #[inline]
+    fn children(&self, stem_idx: u32, query: &[A], split_dim: usize) -> ([u32; 2], A) {
+        let stem = &self.stems[stem_idx as usize];
+        let children = if query[split_dim] < stem.split_val {
+            [stem.left, stem.right]
+        } else {
+            [stem.right, stem.left]
+        };
+
+        (children, query[split_dim].saturating_dist(stem.split_val))
+    }
+
+    #[inline]
+    fn leaf(&self, node_idx: u32) -> &DynLeafNode<A, T> {
+        &self.leaves[(node_idx - u32::leaf_offset()) as usize]
+    }
+
+    fn nearest_one_recurse<D>(
+        &self,
+        query: &[A],
+        node_idx: u32,
+        split_dim: usize,
+        nearest: &mut NearestNeighbour<A, T>,
+        off: &mut [A],
+        rd: A,
+    ) where
+        D: DistanceMetric<A, 1>,
+    {
+        if !is_stem_index(node_idx) {
+            let leaf = self.leaf(node_idx);
+            for (point, &item) in leaf
+                .content_points
+                .chunks_exact(self.dims)
+                .zip(&leaf.content_items)
+            {
+                let distance = dist::<A, D>(query, point);
+                if distance < nearest.distance {
+                    *nearest = NearestNeighbour { distance, item };
+                }
+            }
+            return;
+        }
+
+        let ([closer, further], new_off) = self.children(node_idx, query, split_dim);
+        let next_split_dim = (split_dim + 1) % self.dims;
+
+        self.nearest_one_recurse::<D>(query, closer, next_split_dim, nearest, off, rd);
+
+        let old_off = off[split_dim];
+        let rd = accumulate_dist(rd, D::dist1(new_off, old_off), D::MAX_OF_AXES, A::rd_update);
+        if rd <= nearest.distance {
+            off[split_dim] = new_off;
+            self.nearest_one_recurse::<D>(query, further, next_split_dim, nearest, off, rd);
+            off[split_dim] = old_off;
+        }
+    }
+
+    fn nearest_n_recurse<D>(
+        &self,
+        query: &[A],
+        node_idx: u32,
+        split_dim: usize,
+        results: &mut BinaryHeap<NearestNeighbour<A, T>>,
+        off: &mut [A],
+        rd: A,
+    ) where
+        D: DistanceMetric<A, 1>,
+    {
+        if !is_stem_index(node_idx) {
+            let leaf = self.leaf(node_idx);
+            for (point, &item) in leaf
+                .content_points
+                .chunks_exact(self.dims)
+                .zip(&leaf.content_items)
+            {
+                let distance = dist::<A, D>(query, point);
+                if results.dist_belongs(distance) {
+                    results.add_nearest(NearestNeighbour { distance, item });
+                }
+            }
+            return;
+        }
+
+        let ([closer, further], new_off) = self.children(node_idx, query, split_dim);
+        let next_split_dim = (split_dim + 1) % self.dims;
+
+        self.nearest_n_recurse::<D>(query, closer, next_split_dim, results, off, rd);
+
+        let old_off = off[split_dim];
+        let rd = accumulate_dist(rd, D::dist1(new_off, old_off), D::MAX_OF_AXES, A::rd_update);
+        if results.dist_belongs(rd) {
+            off[split_dim] = new_off;
+            self.nearest_n_recurse::<D>(query, further, next_split_dim, results, off, rd);
+            off[split_dim] = old_off;
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn within_recurse<D>(
+        &self,
+        query: &[A],
+        radius: A,
+        node_idx: u32,
+        split_dim: usize,
+        results: &mut Vec<NearestNeighbour<A, T>>,
+        off: &mut [A],
+        rd: A,
+    ) where
+        D: DistanceMetric<A, 1>,
+    {
+        if !is_stem_index(node_idx) {
+            let leaf = self.leaf(node_idx);
+            for (point, &item) in leaf
+                .content_points
+                .chunks_exact(self.dims)
+                .zip(&leaf.content_items)
+            {
+                let distance = dist::<A, D>(query, point);
+                if distance < radius {
+                    results.push(NearestNeighbour { distance, item });
+                }
+            }
+            return;
+        }
+
+        let ([closer, further], new_off) = self.children(node_idx, query, split_dim);
+        let next_split_dim = (split_dim + 1) % self.dims;
+
+        self.within_recurse::<D>(query, radius, closer, next_split_dim, results, off, rd);
+
+        let old_off = off[split_dim];
+        let rd = accumulate_dist(rd, D::dist1(new_off, old_off), D::MAX_OF_AXES, A::rd_update);
+        if rd <= radius {
+            off[split_dim] = new_off;
+            self.within_recurse::<D>(query, radius, further, next_split_dim, results, off, rd);
+            off[split_dim] = old_off;
+        }
+    }
+
+    fn any_within_recurse<D>(
+        &self,
+        query: &[A],
+        radius: A,
+        node_idx: u32,
+        split_dim: usize,
+        off: &mut [A],
+        rd: A,
+    ) -> bool
+    where
+        D: DistanceMetric<A, 1>,
+    {
+        if !is_stem_index(node_idx) {
+            return self
+                .leaf(node_idx)
+                .content_points
+                .chunks_exact(self.dims)
+                .any(|point| dist::<A, D>(query, point) < radius);
+        }
+
+        let ([closer, further], new_off) = self.children(node_idx, query, split_dim);
+        let next_split_dim = (split_dim + 1) % self.dims;
+
+        if self.any_within_recurse::<D>(query, radius, closer, next_split_dim, off, rd) {
+            return true;
+        }
+
+        let old_off = off[split_dim];
+        let rd = accumulate_dist(rd, D::dist1(new_off, old_off), D::MAX_OF_AXES, A::rd_update);
+        if rd > radius {
+            return false;
+        }
+
+        off[split_dim] = new_off;
+        let found = self.any_within_recurse::<D>(query, radius, further, next_split_dim, off, rd);
+        off[split_dim] = old_off;
+
+        found
+    }
+}
+
+impl<A: Axis, T: Content> DynLeafNode<A, T> {
+    fn with_capacity(dims: usize, bucket_size: usize) -> Self {
+        DynLeafNode {
+            content_points: Vec::with_capacity(dims * bucket_size),
+            content_items: Vec::with_capacity(bucket_size),
+        }
+    }
+
+    /// Removes the entry at `idx`, replacing it with the last entry
+    fn swap_remove(&mut self, idx: usize, dims: usize) {
+        let last = self.content_items.len() - 1;
+        self.content_items.swap_remove(idx);
+        self.content_points
+            .copy_within(last * dims..(last + 1) * dims, idx * dims);
+        self.content_points.truncate(last * dims);
+    }
+}
+
+/// The distance between `a` and `b`, combining the per-axis distances given by `D`
+#[inline]
+fn dist<A: Axis, D: DistanceMetric<A, 1>>(a: &[A], b: &[A]) -> A {
+    a.iter().zip(b).fold(A::zero(), |acc, (&a, &b)| {
+        accumulate_dist(acc, D::dist1(a, b), D::MAX_OF_AXES, |x, y| x + y)
+    })
+}
+
+impl<A: Axis, T: Content> DynKdTree<A, T>
+where
+    usize: Cast<T>,
+{
+    /// Creates a tree containing `points`, each of which must have `dims` elements,
+    /// using each point's index as its item.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any point does not have `dims` elements.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::float::dyn_kdtree::DynKdTree;
+    /// use kiddo::SquaredEuclidean;
+    ///
+    /// let points = vec![vec![1.0, 2.0], vec![3.0, 4.0]];
+    /// let tree: DynKdTree<f64, u32> = DynKdTree::from_points(2, &points);
+    ///
+    /// assert_eq!(tree.size(), 2);
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[3.1, 4.1]).item, 1);
+    /// ```
+    pub fn from_points<P: AsRef<[A]>>(dims: usize, points: &[P]) -> Self {
+        let mut tree = Self::new(dims);
+        for (idx, point) in points.iter().enumerate() {
+            tree.add(point.as_ref(), idx.cast());
+        }
+
+        tree
+    }
+
+    /// Creates a tree containing `points`, each of which must have `dims` elements,
+    /// using each point's index as its item, or returns an error instead of panicking
+    /// if that isn't possible.
+    ///
+    /// Fails if any point does not have `dims` elements or has a NaN coordinate, if
+    /// there are too many points for their indices to fit in `T`, or if more points
+    /// share a position on one axis than fit in a bucket.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::float::dyn_kdtree::DynKdTree;
+    /// use kiddo::ConstructionError;
+    ///
+    /// let points = vec![vec![1.0, 2.0], vec![3.0, f64::NAN]];
+    /// let result = DynKdTree::<f64, u32>::try_from_points(2, &points);
+    ///
+    /// assert_eq!(result, Err(ConstructionError::NanCoordinate { index: 1 }));
+    /// ```
+    pub fn try_from_points<P: AsRef<[A]>>(
+        dims: usize,
+        points: &[P],
+    ) -> Result<Self, ConstructionError>
+    where
+        usize: CheckedCast<T>,
+    {
+        for (index, point) in points.iter().enumerate() {
+            let point = point.as_ref();
+            if point.len() != dims {
+                return Err(ConstructionError::DimensionMismatch {
+                    index,
+                    point_dims: point.len(),
+                    tree_dims: dims,
+                });
+            }
+            if point.iter().any(|val| val.is_nan()) {
+                return Err(ConstructionError::NanCoordinate { index });
+            }
+        }
+
+        let mut tree = Self::new(dims);
+        for (idx, point) in points.iter().enumerate() {
+            let item = idx
+                .checked_cast()
+                .ok_or(ConstructionError::TooManyItems { len: points.len() })?;
+            tree.add_unchecked(point.as_ref(), item)?;
+        }
+
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DynKdTree;
+    use crate::construction_error::ConstructionError;
+    use crate::float::distance::{Chebyshev, Manhattan, SquaredEuclidean};
+    use crate::float::kdtree::KdTree;
+    use crate::nearest_neighbour::NearestNeighbour;
+    use crate::traits::DistanceMetric;
+    use rand::{Rng, SeedableRng};
+
+    fn brute_force<D: DistanceMetric<f64, 1>>(
+        points: &[Vec<f64>],
+        query: &[f64],
+    ) -> Vec<NearestNeighbour<f64, u32>> {
+        let mut results: Vec<_> = points
+            .iter()
+            .enumerate()
+            .map(|(item, point)| NearestNeighbour {
+                distance: super::dist::<f64, D>(query, point),
+                item: item as u32,
+            })
+            .collect();
+        results.sort();
+
+        results
+    }
+
+    #[test]
+    fn queries_match_brute_force_for_various_dimensions() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(7);
+
+        for dims in 1..=6 {
+            let points: Vec<Vec<f64>> = (0..2_000)
+                .map(|_| (0..dims).map(|_| rng.gen()).collect())
+                .collect();
+            let tree: DynKdTree<f64, u32> = DynKdTree::from_points(dims, &points);
+            assert_eq!(tree.size(), points.len());
+
+            for _ in 0..20 {
+                let query: Vec<f64> = (0..dims).map(|_| rng.gen()).collect();
+                let expected = brute_force::<SquaredEuclidean>(&points, &query);
+
+                assert_eq!(
+                    tree.nearest_one::<SquaredEuclidean>(&query).distance,
+                    expected[0].distance
+                );
+
+                let nearest_n = tree.nearest_n::<SquaredEuclidean>(&query, 10);
+                let distances: Vec<_> = nearest_n.iter().map(|n| n.distance).collect();
+                let expected_distances: Vec<_> =
+                    expected[..10].iter().map(|n| n.distance).collect();
+                assert_eq!(distances, expected_distances);
+
+                let radius = 0.05;
+                let within = tree.within::<SquaredEuclidean>(&query, radius);
+                let expected_within: Vec<_> = expected
+                    .iter()
+                    .copied()
+                    .filter(|n| n.distance < radius)
+                    .collect();
+                assert_eq!(within, expected_within);
+
+                let expected = brute_force::<Manhattan>(&points, &query);
+                assert_eq!(
+                    tree.nearest_one::<Manhattan>(&query).distance,
+                    expected[0].distance
+                );
+
+                let expected = brute_force::<Chebyshev>(&points, &query);
+                assert_eq!(
+                    tree.nearest_one::<Chebyshev>(&query).distance,
+                    expected[0].distance
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn matches_the_const_generic_tree() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(8);
+        let points: Vec<[f64; 3]> = (0..5_000).map(|_| rng.gen()).collect();
+
+        let mut tree: DynKdTree<f64, u32> = DynKdTree::new(3);
+        let mut const_tree: KdTree<f64, u32, 3, 32, u32> = KdTree::new();
+        for (idx, point) in points.iter().enumerate() {
+            tree.add(point, idx as u32);
+            const_tree.add(point, idx as u32);
+        }
+
+        for _ in 0..100 {
+            let query: [f64; 3] = rng.gen();
+            assert_eq!(
+                tree.nearest_n::<SquaredEuclidean>(&query, 5),
+                const_tree.nearest_n::<SquaredEuclidean>(&query, 5)
+            );
+            assert_eq!(
+                tree.within::<SquaredEuclidean>(&query, 0.01),
+                const_tree.within::<SquaredEuclidean>(&query, 0.01)
+            );
+            assert_eq!(
+                tree.any_within::<SquaredEuclidean>(&query, 0.0005),
+                const_tree.any_within::<SquaredEuclidean>(&query, 0.0005)
+            );
+        }
+    }
+
+    #[test]
+    fn can_remove_items_and_iterate() {
+        let mut tree: DynKdTree<f32, u32> = DynKdTree::with_bucket_size(2, 4);
+        for idx in 0..100u32 {
+            tree.add(&[idx as f32, (idx * 37 % 100) as f32], idx);
+        }
+        assert_eq!(tree.iter().count(), 100);
+
+        assert_eq!(tree.remove(&[50.0, 50.0], 50), 1);
+        assert_eq!(tree.remove(&[50.0, 50.0], 50), 0);
+        assert_eq!(tree.size(), 99);
+        assert!(tree.iter().all(|(item, _)| item != 50));
+        assert_ne!(tree.nearest_one::<SquaredEuclidean>(&[50.0, 50.0]).item, 50);
+        assert!(tree
+            .iter()
+            .all(|(item, point)| point == [item as f32, (item * 37 % 100) as f32]));
+    }
+
+    #[test]
+    fn empty_trees_return_nothing() {
+        let tree: DynKdTree<f64, u32> = DynKdTree::new(2);
+
+        assert_eq!(
+            tree.nearest_one::<SquaredEuclidean>(&[0.0, 0.0]).distance,
+            f64::INFINITY
+        );
+        assert!(tree
+            .nearest_n::<SquaredEuclidean>(&[0.0, 0.0], 3)
+            .is_empty());
+        assert!(tree.within::<SquaredEuclidean>(&[0.0, 0.0], 1.0).is_empty());
+        assert!(!tree.any_within::<SquaredEuclidean>(&[0.0, 0.0], 1.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "point has 2 dimensions, but the tree has 3")]
+    fn panics_when_dimensions_do_not_match() {
+        let mut tree: DynKdTree<f64, u32> = DynKdTree::new(3);
+        tree.add(&[1.0, 2.0], 0);
+    }
+
+    #[test]
+    fn try_from_points_reports_unusable_input() {
+        let mut points: Vec<Vec<f64>> = (0..100)
+            .map(|i| vec![i as f64, (i * 37 % 100) as f64])
+            .collect();
+        assert_eq!(
+            DynKdTree::<f64, u32>::try_from_points(2, &points)
+                .unwrap()
+                .size(),
+            100
+        );
+
+        points[10] = vec![1.0, 2.0, 3.0];
+        assert_eq!(
+            DynKdTree::<f64, u32>::try_from_points(2, &points),
+            Err(ConstructionError::DimensionMismatch {
+                index: 10,
+                point_dims: 3,
+                tree_dims: 2
+            })
+        );
+
+        // more points share an x value than fit in a bucket
+        let stacked: Vec<Vec<f64>> = (0..100).map(|i| vec![1.0, i as f64]).collect();
+        assert_eq!(
+            DynKdTree::<f64, u32>::try_from_points(2, &stacked),
+            Err(ConstructionError::DuplicatePositions)
+        );
+    }
+}
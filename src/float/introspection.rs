@@ -0,0 +1,292 @@
+//! Introspection of the structure of a [`KdTree`], for visualization and for debugging.
+
+use az::{Az, Cast};
+use std::ops::Rem;
+
+use crate::float::kdtree::{Axis, KdTree};
+use crate::generate_tree_stats;
+use crate::traits::{is_stem_index, Content, Index};
+
+/// Describes a single node of a [`KdTree`], as yielded by [`KdTree::debug_nodes`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NodeInfo<A, const K: usize> {
+    /// The number of stems between this node and the root. The root has a depth of zero
+    pub depth: usize,
+    /// The lower bounds, on each axis, of the region of space covered by this node.
+    /// Axes that no ancestor splits on are unbounded, with a value of negative infinity
+    pub min: [A; K],
+    /// The upper bounds, on each axis, of the region of space covered by this node.
+    /// Axes that no ancestor splits on are unbounded, with a value of infinity
+    pub max: [A; K],
+    /// Whether this is a stem or a leaf, along with the details that are specific to each
+    pub kind: NodeKind<A>,
+}
+
+/// The details that are specific to stems or to leaves, within a [`NodeInfo`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NodeKind<A> {
+    /// A stem, whose left subtree holds the points that are less than `split_val` on
+    /// axis `split_dim`, and whose right subtree holds the rest. The exception is a leaf
+    /// of points that all equal `split_val` on `split_dim`, which may be chained to the left
+    /// of it when there are too many of them to fit in a single leaf
+    Stem {
+        /// The axis that this stem splits on
+        split_dim: usize,
+        /// The value that this stem splits at
+        split_val: A,
+    },
+    /// A leaf, holding `size` items
+    Leaf {
+        /// The number of items stored in this leaf
+        size: usize,
+    },
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
+    KdTree<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    /// Iterates over every node in the tree, in pre-order: each stem is followed by its
+    /// left subtree and then by its right subtree.
+    ///
+    /// Useful for visualizing the tree, and for spotting degenerate splits, such as
+    /// stems that split at the edge of their region or very unevenly filled leaves.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    /// use kiddo::float::introspection::NodeKind;
+    ///
+    /// let mut tree: KdTree<f64, 2> = KdTree::new();
+    /// for i in 0..100 {
+    ///     tree.add(&[(i * 37 % 100) as f64, i as f64], i);
+    /// }
+    ///
+    /// let nodes: Vec<_> = tree.debug_nodes().collect();
+    ///
+    /// assert!(matches!(nodes[0].kind, NodeKind::Stem { split_dim: 0, .. }));
+    /// let leaf_sizes: usize = nodes.iter().map(|node| match node.kind {
+    ///     NodeKind::Leaf { size } => size,
+    ///     NodeKind::Stem { .. } => 0,
+    /// }).sum();
+    /// assert_eq!(leaf_sizes, 100);
+    /// ```
+    pub fn debug_nodes(&self) -> impl Iterator<Item = NodeInfo<A, K>> + '_ {
+        let mut stack = vec![(
+            self.root_index,
+            0,
+            [A::neg_infinity(); K],
+            [A::infinity(); K],
+        )];
+
+        std::iter::from_fn(move || {
+            let (node_idx, depth, min, max) = stack.pop()?;
+
+            if !is_stem_index(node_idx) {
+                let leaf_node = &self.leaves[(node_idx - IDX::leaf_offset()).az::<usize>()];
+                return Some(NodeInfo {
+                    depth,
+                    min,
+                    max,
+                    kind: NodeKind::Leaf {
+                        size: leaf_node.size.az::<usize>(),
+                    },
+                });
+            }
+
+            let stem_node = &self.stems[node_idx.az::<usize>()];
+            let split_dim = depth.rem(K);
+
+            let mut left_max = max;
+            left_max[split_dim] = stem_node.split_val;
+            let mut right_min = min;
+            right_min[split_dim] = stem_node.split_val;
+
+            // the right child is pushed first so that the left is visited first
+            stack.push((stem_node.right, depth + 1, right_min, max));
+            stack.push((stem_node.left, depth + 1, min, left_max));
+
+            Some(NodeInfo {
+                depth,
+                min,
+                max,
+                kind: NodeKind::Stem {
+                    split_dim,
+                    split_val: stem_node.split_val,
+                },
+            })
+        })
+    }
+
+    /// Renders the structure of the tree in the [Graphviz](https://graphviz.org/) DOT
+    /// language.
+    ///
+    /// Stems are labelled with their split, and leaves with the number of items that they
+    /// hold. Requires the `debug_viz` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, 2> = KdTree::new();
+    /// for i in 0..100 {
+    ///     tree.add(&[(i * 37 % 100) as f64, i as f64], i);
+    /// }
+    ///
+    /// let dot = tree.to_dot();
+    ///
+    /// assert!(dot.starts_with("digraph kdtree {"));
+    /// assert!(dot.contains("n0 -> n1"));
+    /// ```
+    #[cfg(feature = "debug_viz")]
+    pub fn to_dot(&self) -> String {
+        use std::fmt::Write;
+
+        let mut dot = String::from("digraph kdtree {\n");
+
+        // the ids of the stems above the current node, and whether their left child
+        // has been written yet
+        let mut ancestors: Vec<(usize, bool)> = Vec::new();
+
+        for (id, node) in self.debug_nodes().enumerate() {
+            ancestors.truncate(node.depth);
+
+            match node.kind {
+                NodeKind::Stem {
+                    split_dim,
+                    split_val,
+                } => writeln!(
+                    dot,
+                    "    n{id} [label=\"axis {split_dim} < {split_val:?}\"];"
+                ),
+                NodeKind::Leaf { size } => {
+                    writeln!(dot, "    n{id} [shape=box, label=\"{size} items\"];")
+                }
+            }
+            .unwrap();
+
+            if let Some((parent_id, has_left)) = ancestors.last_mut() {
+                let label = if *has_left { "no" } else { "yes" };
+                *has_left = true;
+                writeln!(dot, "    n{parent_id} -> n{id} [label=\"{label}\"];").unwrap();
+            }
+
+            if matches!(node.kind, NodeKind::Stem { .. }) {
+                ancestors.push((id, false));
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    generate_tree_stats!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NodeKind;
+    use crate::float::kdtree::KdTree;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn debug_nodes_describe_every_point() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(9);
+        let points: Vec<[f32; 3]> = (0..1_000).map(|_| rng.gen()).collect();
+
+        let mut tree: KdTree<f32, u32, 3, 8, u32> = KdTree::new();
+        for (idx, point) in points.iter().enumerate() {
+            tree.add(point, idx as u32);
+        }
+
+        let nodes: Vec<_> = tree.debug_nodes().collect();
+        let leaves: Vec<_> = nodes
+            .iter()
+            .filter(|node| matches!(node.kind, NodeKind::Leaf { .. }))
+            .collect();
+
+        assert_eq!(nodes.len(), tree.stems.len() + tree.leaves.len());
+        assert_eq!(leaves.len(), tree.leaves.len());
+
+        // every point lies within the bounds of exactly one leaf
+        for point in &points {
+            let containing = leaves
+                .iter()
+                .filter(|leaf| {
+                    (0..3).all(|dim| point[dim] >= leaf.min[dim] && point[dim] < leaf.max[dim])
+                })
+                .count();
+            assert_eq!(containing, 1);
+        }
+
+        let total: usize = leaves
+            .iter()
+            .map(|leaf| match leaf.kind {
+                NodeKind::Leaf { size } => size,
+                NodeKind::Stem { .. } => 0,
+            })
+            .sum();
+        assert_eq!(total, 1_000);
+    }
+
+    #[cfg(feature = "debug_viz")]
+    #[test]
+    fn to_dot_has_an_edge_for_every_non_root_node() {
+        let mut tree: KdTree<f32, u32, 2, 4, u32> = KdTree::new();
+        for idx in 0..50u32 {
+            tree.add(&[(idx * 37 % 50) as f32, idx as f32], idx);
+        }
+
+        let dot = tree.to_dot();
+        let nodes = tree.stems.len() + tree.leaves.len();
+
+        assert_eq!(dot.matches(" -> ").count(), nodes - 1);
+        assert_eq!(dot.matches("shape=box").count(), tree.leaves.len());
+    }
+
+    #[test]
+    fn generate_stats_matches_debug_nodes() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(13);
+        let points: Vec<[f64; 2]> = (0..2_000).map(|_| rng.gen()).collect();
+
+        let mut tree: KdTree<f64, u32, 2, 16, u32> = KdTree::new();
+        for (idx, point) in points.iter().enumerate() {
+            tree.add(point, idx as u32);
+        }
+        for (idx, point) in points.iter().enumerate().take(500) {
+            tree.remove(point, idx as u32);
+        }
+
+        let nodes: Vec<_> = tree.debug_nodes().collect();
+        let leaf_sizes: Vec<usize> = nodes
+            .iter()
+            .filter_map(|node| match node.kind {
+                NodeKind::Leaf { size } => Some(size),
+                NodeKind::Stem { .. } => None,
+            })
+            .collect();
+
+        let stats = tree.generate_stats();
+        assert_eq!(stats.size, 1_500);
+        assert_eq!(stats.leaf_count, leaf_sizes.len());
+        assert_eq!(stats.leaf_count, tree.leaf_count());
+        assert_eq!(stats.max_leaf_size, *leaf_sizes.iter().max().unwrap());
+        assert_eq!(stats.min_leaf_size, *leaf_sizes.iter().min().unwrap());
+        assert_eq!(
+            stats.empty_leaf_count,
+            leaf_sizes.iter().filter(|&&size| size == 0).count()
+        );
+        assert_eq!(
+            stats.max_depth,
+            nodes.iter().map(|node| node.depth).max().unwrap()
+        );
+        assert_eq!(stats.max_depth, tree.max_depth());
+        assert!(
+            (tree.average_leaf_occupancy() - 1_500.0 / (16.0 * leaf_sizes.len() as f64)).abs()
+                < 1e-12
+        );
+    }
+}
@@ -10,10 +10,13 @@ use divrem::DivCeil;
 use num_traits::float::FloatCore;
 use std::cmp::PartialEq;
 use std::fmt::Debug;
+use std::num::NonZero;
 
 use crate::{
-    iter::{IterableTreeData, TreeIter},
-    traits::{Content, Index},
+    best_neighbour::BestNeighbour,
+    iter::{IterableTreeData, TreeItems, TreeIter},
+    nearest_neighbour::NearestNeighbour,
+    traits::{Content, DistanceMetric, Index, KdTreeQuery},
 };
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -56,7 +59,6 @@ impl<T: FloatCore + Default + Debug + Copy + Sync + Send + std::ops::AddAssign>
 /// or [`f16`](https://docs.rs/half/latest/half/struct.f16.html) if the `f16` feature is enabled
 ///
 /// A convenient type alias exists for KdTree with some sensible defaults set: [`kiddo::KdTree`](`crate::KdTree`).
-
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(
     feature = "rkyv",
@@ -68,6 +70,13 @@ pub struct KdTree<A: Copy + Default, T: Copy + Default, const K: usize, const B:
     pub(crate) stems: Vec<StemNode<A, K, IDX>>,
     pub(crate) root_index: IDX,
     pub(crate) size: T,
+
+    /// Points added by `add_deferred` that are waiting for `finalize_splits`,
+    /// flattened to `K` values per point.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) deferred_points: Vec<A>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) deferred_items: Vec<T>,
 }
 
 #[doc(hidden)]
@@ -191,6 +200,8 @@ where
             stems: Vec::with_capacity(capacity.max(1).ilog2() as usize),
             leaves: Vec::with_capacity(DivCeil::div_ceil(capacity, B.az::<usize>())),
             root_index: <IDX as Index>::leaf_offset(),
+            deferred_points: Vec::new(),
+            deferred_items: Vec::new(),
         };
 
         tree.leaves.push(LeafNode::new());
@@ -198,6 +209,57 @@ where
         tree
     }
 
+    /// Reserves capacity for at least `additional` more items to be added to the tree
+    /// without the stems or leaves needing to be reallocated.
+    ///
+    /// This assumes that the new leaves will be filled, which is only the case for trees that
+    /// are built in a balanced way. Leaves created by splits when adding items one at a time
+    /// are usually only partly filled, so some reallocation may still happen in that case.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, 3> = KdTree::new();
+    /// tree.reserve(10_000);
+    /// let before = tree.memory_usage();
+    ///
+    /// for i in 0..100 {
+    ///     tree.add(&[i as f64, (i * 37 % 100) as f64, (i * 13 % 100) as f64], i);
+    /// }
+    ///
+    /// tree.shrink_to_fit();
+    /// assert!(tree.memory_usage() < before);
+    /// ```
+    pub fn reserve(&mut self, additional: usize) {
+        let additional_leaves = DivCeil::div_ceil(additional, B);
+        self.leaves.reserve(additional_leaves);
+        self.stems.reserve(additional_leaves);
+    }
+
+    /// Releases any capacity that the tree has allocated but is not using, such as after
+    /// a [`reserve`](KdTree::reserve) that was larger than needed.
+    ///
+    /// Leaves that have been emptied by removals are not freed, as the tree still refers
+    /// to them.
+    pub fn shrink_to_fit(&mut self) {
+        self.leaves.shrink_to_fit();
+        self.stems.shrink_to_fit();
+        self.deferred_points.shrink_to_fit();
+        self.deferred_items.shrink_to_fit();
+    }
+
+    /// Returns the approximate number of bytes that the tree occupies, including the
+    /// capacity that has been allocated but not yet used.
+    pub fn memory_usage(&self) -> usize {
+        size_of::<Self>()
+            + self.leaves.capacity() * size_of::<LeafNode<A, T, K, B, IDX>>()
+            + self.stems.capacity() * size_of::<StemNode<A, K, IDX>>()
+            + self.deferred_points.capacity() * size_of::<A>()
+            + self.deferred_items.capacity() * size_of::<T>()
+    }
+
     /// Iterate over all `(index, point)` tuples in arbitrary order.
     ///
     /// ```
@@ -216,6 +278,101 @@ where
     pub fn iter(&self) -> impl Iterator<Item = (T, [A; K])> + '_ {
         TreeIter::new(self, B)
     }
+
+    /// Returns the point that `item` is stored at, or `None` if it isn't in the tree.
+    ///
+    /// If `item` has been added more than once, any one of its points may be returned.
+    /// Items that are waiting for [`finalize_splits`](KdTree::finalize_splits) are not
+    /// found. Every leaf is scanned, so this takes time proportional to the size of the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, 3> = KdTree::new();
+    /// tree.add(&[1.0, 2.0, 3.0], 10);
+    ///
+    /// assert_eq!(tree.get(10), Some([1.0, 2.0, 3.0]));
+    /// assert_eq!(tree.get(20), None);
+    /// ```
+    pub fn get(&self, item: T) -> Option<[A; K]> {
+        self.leaves.iter().find_map(|leaf| {
+            leaf.content_items[..leaf.size.az::<usize>()]
+                .iter()
+                .position(|&stored| stored == item)
+                .map(|idx| leaf.content_points[idx])
+        })
+    }
+
+    /// Returns whether `item` is stored in the tree. See [`get`](KdTree::get) for details.
+    pub fn contains_item(&self, item: T) -> bool {
+        self.get(item).is_some()
+    }
+
+    /// Iterates over mutable references to every item stored in the tree, in arbitrary
+    /// order, so that items can be renumbered in place, such as after the table of
+    /// entities that they refer to has been compacted. This includes items that are waiting
+    /// for [`finalize_splits`](KdTree::finalize_splits).
+    ///
+    /// The points that the items are stored at are unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, 3> = KdTree::new();
+    /// tree.add(&[1.0, 2.0, 3.0], 10);
+    /// tree.add(&[4.0, 5.0, 6.0], 20);
+    ///
+    /// tree.iter_mut_items().for_each(|item| *item /= 10);
+    ///
+    /// assert_eq!(tree.get(1), Some([1.0, 2.0, 3.0]));
+    /// assert_eq!(tree.get(2), Some([4.0, 5.0, 6.0]));
+    /// ```
+    pub fn iter_mut_items(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        let deferred = self.deferred_items.iter_mut();
+
+        self.leaves
+            .iter_mut()
+            .flat_map(|leaf| {
+                let size = leaf.size.az::<usize>();
+                leaf.content_items[..size].iter_mut()
+            })
+            .chain(deferred)
+    }
+
+    /// Replaces every occurrence of the item `old` with `new`, leaving the points that
+    /// they are stored at unchanged. Every leaf is scanned, so this takes time
+    /// proportional to the size of the tree; use [`iter_mut_items`](KdTree::iter_mut_items)
+    /// to renumber many items in a single pass.
+    ///
+    /// Returns the number of entries that were relabelled.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, 3> = KdTree::new();
+    /// tree.add(&[1.0, 2.0, 3.0], 10);
+    ///
+    /// assert_eq!(tree.relabel(10, 11), 1);
+    /// assert_eq!(tree.get(11), Some([1.0, 2.0, 3.0]));
+    /// assert!(!tree.contains_item(10));
+    /// ```
+    pub fn relabel(&mut self, old: T, new: T) -> usize {
+        let mut relabelled = 0;
+        for item in self.iter_mut_items() {
+            if *item == old {
+                *item = new;
+                relabelled += 1;
+            }
+        }
+
+        relabelled
+    }
 }
 
 impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
@@ -235,6 +392,17 @@ impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
     }
 }
 
+impl<'a, A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>> IntoIterator
+    for &'a KdTree<A, T, K, B, IDX>
+{
+    type Item = (T, [A; K]);
+    type IntoIter = TreeItems<'a, A, T, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        TreeItems::new(TreeIter::new(self, B))
+    }
+}
+
 impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>> From<&Vec<[A; K]>>
     for KdTree<A, T, K, B, IDX>
 where
@@ -299,6 +467,74 @@ where
     generate_common_methods!(ArchivedKdTree);
 }
 
+#[cfg(feature = "rkyv")]
+impl<
+        A: Axis + rkyv::Archive<Archived = A>,
+        T: Content + rkyv::Archive<Archived = T>,
+        const K: usize,
+        const B: usize,
+        IDX: Index<T = IDX> + rkyv::Archive<Archived = IDX>,
+    > IterableTreeData<A, T, K> for ArchivedKdTree<A, T, K, B, IDX>
+{
+    fn get_leaf_data(&self, idx: usize, out: &mut Vec<(T, [A; K])>) -> Option<usize> {
+        let leaf = self.leaves.get(idx)?;
+        let max = leaf.size.cast();
+        out.extend(
+            leaf.content_items
+                .iter()
+                .cloned()
+                .zip(leaf.content_points.iter().cloned())
+                .take(max),
+        );
+        Some(max)
+    }
+}
+
+impl<A, T, const K: usize, const B: usize, IDX> KdTreeQuery<A, T, K> for KdTree<A, T, K, B, IDX>
+where
+    A: Axis,
+    T: Content,
+    IDX: Index<T = IDX>,
+    usize: Cast<IDX>,
+{
+    #[inline]
+    fn nearest_one<D>(&self, query: &[A; K]) -> NearestNeighbour<A, T>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.nearest_one::<D>(query)
+    }
+
+    #[inline]
+    fn nearest_n<D>(&self, query: &[A; K], max_qty: NonZero<usize>) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.nearest_n::<D>(query, max_qty.get())
+    }
+
+    #[inline]
+    fn within<D>(&self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.within::<D>(query, dist)
+    }
+
+    #[inline]
+    fn best_n_within<D>(
+        &self,
+        query: &[A; K],
+        dist: A,
+        max_qty: NonZero<usize>,
+    ) -> impl Iterator<Item = BestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.best_n_within::<D>(query, dist, max_qty.get())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
@@ -327,6 +563,36 @@ mod tests {
         assert_eq!(tree.size(), 0);
     }
 
+    #[test]
+    fn can_reserve_and_shrink_to_fit() {
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::with_capacity(0);
+        let initial = tree.memory_usage();
+
+        tree.reserve(32 * 100);
+        assert!(tree.leaves.capacity() >= 101);
+        assert!(tree.stems.capacity() >= 100);
+        let reserved = tree.memory_usage();
+        assert!(reserved > initial);
+
+        for idx in 0..100u32 {
+            tree.add(
+                &[
+                    idx as AX,
+                    (idx * 37 % 100) as AX,
+                    (idx * 13 % 100) as AX,
+                    (idx * 7 % 100) as AX,
+                ],
+                idx,
+            );
+        }
+        assert_eq!(tree.memory_usage(), reserved);
+
+        tree.shrink_to_fit();
+        assert_eq!(tree.leaves.capacity(), tree.leaves.len());
+        assert!(tree.memory_usage() < reserved);
+        assert_eq!(tree.size(), 100);
+    }
+
     #[cfg(feature = "serde")]
     #[test]
     fn can_serde() {
@@ -380,4 +646,106 @@ mod tests {
         let actual: HashMap<_, _> = t.iter().collect();
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn can_collect_extend_and_iterate_by_reference() {
+        let mut t: KdTree<f64, u32, 2, 32, u32> = (0..500u32)
+            .map(|i| ([i as f64, (i * 37 % 500) as f64], i))
+            .collect();
+        t.extend((500..1_000u32).map(|i| ([i as f64, (i * 37 % 500) as f64], i)));
+
+        let mut items: Vec<_> = (&t).into_iter().map(|(item, _)| item).collect();
+        items.sort_unstable();
+        assert_eq!(items, (0..1_000u32).collect::<Vec<_>>());
+
+        for (item, point) in &t {
+            assert_eq!(point, [item as f64, (item * 37 % 500) as f64]);
+        }
+    }
+
+    #[test]
+    fn can_get_the_point_of_an_item() {
+        let mut t: KdTree<f64, u32, 2, 32, u32> = KdTree::new();
+        for i in 0..1_000u32 {
+            t.add(&[i as f64, (i * 37 % 1_000) as f64], i);
+        }
+
+        for i in (0..1_000u32).step_by(7) {
+            assert_eq!(t.get(i), Some([i as f64, (i * 37 % 1_000) as f64]));
+            assert!(t.contains_item(i));
+        }
+        assert_eq!(t.get(1_000), None);
+        assert!(!t.contains_item(1_000));
+    }
+
+    #[test]
+    fn can_relabel_items_in_place() {
+        let mut t: KdTree<f64, u32, 2, 32, u32> = KdTree::new();
+        for i in 0..1_000u32 {
+            t.add(&[i as f64, (i * 37 % 1_000) as f64], i);
+        }
+        t.add_deferred((1_000..1_010u32).map(|i| ([i as f64, 0.5], i)));
+
+        t.iter_mut_items().for_each(|item| *item += 5_000);
+        assert_eq!(t.relabel(5_003, 3), 1);
+        assert_eq!(t.relabel(5_003, 3), 0);
+
+        t.finalize_splits();
+        assert_eq!(t.size(), 1_010);
+        assert_eq!(t.get(3), Some([3.0, 111.0]));
+        for i in (0..1_010u32).filter(|&i| i != 3) {
+            assert!(t.contains_item(i + 5_000));
+            assert!(!t.contains_item(i));
+        }
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn can_query_archived_tree() {
+        use crate::float::kdtree::ArchivedKdTree;
+        use crate::SquaredEuclidean;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(67);
+        let mut tree: KdTree<AX, u32, 3, 32, u32> = KdTree::new();
+        for idx in 0..10_000 {
+            tree.add(&rng.gen::<[AX; 3]>(), idx);
+        }
+
+        let bytes = rkyv::to_bytes::<_, 256>(&tree).unwrap();
+        let archived: &ArchivedKdTree<AX, u32, 3, 32, u32> =
+            unsafe { rkyv::archived_root::<KdTree<AX, u32, 3, 32, u32>>(&bytes) };
+
+        assert_eq!(archived.size(), tree.size());
+
+        for _ in 0..100 {
+            let query = rng.gen::<[AX; 3]>();
+            let end = rng.gen::<[AX; 3]>();
+
+            assert_eq!(
+                archived.nearest_one::<SquaredEuclidean>(&query),
+                tree.nearest_one::<SquaredEuclidean>(&query)
+            );
+            assert_eq!(
+                archived.nearest_n::<SquaredEuclidean>(&query, 5),
+                tree.nearest_n::<SquaredEuclidean>(&query, 5)
+            );
+            assert_eq!(
+                archived.within::<SquaredEuclidean>(&query, 0.01),
+                tree.within::<SquaredEuclidean>(&query, 0.01)
+            );
+            assert_eq!(
+                archived.nearest_n_in_slab::<SquaredEuclidean>(&query, 2, 0.2, 0.3, 5),
+                tree.nearest_n_in_slab::<SquaredEuclidean>(&query, 2, 0.2, 0.3, 5)
+            );
+            assert_eq!(
+                archived.nearest_to_segment::<SquaredEuclidean>(&query, &end),
+                tree.nearest_to_segment::<SquaredEuclidean>(&query, &end)
+            );
+            assert_eq!(
+                archived.within_of_segment::<SquaredEuclidean>(&query, &end, 0.001),
+                tree.within_of_segment::<SquaredEuclidean>(&query, &end, 0.001)
+            );
+        }
+    }
 }
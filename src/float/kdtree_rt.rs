@@ -0,0 +1,240 @@
+//! Floating point k-d tree whose bucket size is chosen at runtime.
+//!
+//! The bucket size of the standard [`KdTree`](`crate::float::kdtree::KdTree`) is a const
+//! generic, which must be known at compile time. The [`KdTree`] in this module takes it as
+//! an argument to [`with_bucket_size`](KdTree::with_bucket_size) instead, for libraries
+//! that wrap Kiddo and let their users choose it, or for data such as lattices that need
+//! unusually large buckets. The number of dimensions is still a const generic.
+//!
+//! Leaves are backed by `Vec`s that grow as points are added, rather than by fixed-size
+//! arrays, so queries are somewhat slower than those of the standard tree.
+use az::Cast;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::float::dyn_kdtree::{DynKdTree, DEFAULT_BUCKET_SIZE};
+use crate::float::kdtree::Axis;
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{Content, DistanceMetric};
+
+/// Floating point k-d tree with a bucket size that is set at runtime
+///
+/// Behaves like the standard [`KdTree`](`crate::float::kdtree::KdTree`), other than the
+/// bucket size being passed to [`with_bucket_size`](KdTree::with_bucket_size).
+///
+/// As with [`DynKdTree`], which this tree is built on, distances are calculated one axis
+/// at a time using [`DistanceMetric::dist1`], so queries accept any metric that implements
+/// [`DistanceMetric`] for every `K`, such as [`SquaredEuclidean`](`crate::SquaredEuclidean`)
+/// or [`Manhattan`](`crate::Manhattan`). The `1` in the `DistanceMetric<A, 1>` bounds on
+/// the query methods reflects this.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::float::kdtree_rt::KdTree;
+/// use kiddo::SquaredEuclidean;
+///
+/// let mut tree: KdTree<f64, u32, 3> = KdTree::with_bucket_size(1001);
+///
+/// tree.add(&[1.0, 2.0, 5.0], 100);
+/// tree.add(&[2.0, 3.0, 6.0], 101);
+///
+/// assert_eq!(tree.bucket_size(), 1001);
+/// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[1.0, 2.0, 5.1]).item, 100);
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct KdTree<A, T, const K: usize> {
+    tree: DynKdTree<A, T>,
+}
+
+impl<A: Axis, T: Content, const K: usize> Default for KdTree<A, T, K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: Axis, T: Content, const K: usize> KdTree<A, T, K> {
+    /// Creates a new, empty tree, using the default bucket size of 32.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `K` is zero.
+    pub fn new() -> Self {
+        Self::with_bucket_size(DEFAULT_BUCKET_SIZE)
+    }
+
+    /// Creates a new, empty tree whose leaves each hold up to `bucket_size` points.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `K` is zero or `bucket_size` is less than two.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::float::kdtree_rt::KdTree;
+    ///
+    /// let tree: KdTree<f64, u32, 2> = KdTree::with_bucket_size(64);
+    ///
+    /// assert_eq!(tree.bucket_size(), 64);
+    /// assert_eq!(tree.size(), 0);
+    /// ```
+    pub fn with_bucket_size(bucket_size: usize) -> Self {
+        KdTree {
+            tree: DynKdTree::with_bucket_size(K, bucket_size),
+        }
+    }
+
+    /// Returns the maximum number of points that are stored in each leaf
+    #[inline]
+    pub fn bucket_size(&self) -> usize {
+        self.tree.bucket_size()
+    }
+
+    /// Returns the current number of elements stored in the tree
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.tree.size()
+    }
+
+    /// Iterate over all `(item, point)` tuples in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (T, [A; K])> + '_ {
+        self.tree
+            .iter()
+            .map(|(item, point)| (item, std::array::from_fn(|dim| point[dim])))
+    }
+
+    /// Adds an item to the tree.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more points share a position on one axis than fit in a bucket.
+    #[inline]
+    pub fn add(&mut self, point: &[A; K], item: T) {
+        self.tree.add(point, item);
+    }
+
+    /// Removes every entry with the given point and item from the tree, returning the
+    /// number of entries that were removed.
+    #[inline]
+    pub fn remove(&mut self, point: &[A; K], item: T) -> usize {
+        self.tree.remove(point, item)
+    }
+
+    /// Finds the nearest element to `query`, using the specified distance metric.
+    #[inline]
+    pub fn nearest_one<D>(&self, query: &[A; K]) -> NearestNeighbour<A, T>
+    where
+        D: DistanceMetric<A, 1>,
+    {
+        self.tree.nearest_one::<D>(query)
+    }
+
+    /// Finds up to `qty` elements nearest to `query`, sorted by distance, using the
+    /// specified distance metric.
+    #[inline]
+    pub fn nearest_n<D>(&self, query: &[A; K], qty: usize) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, 1>,
+    {
+        self.tree.nearest_n::<D>(query, qty)
+    }
+
+    /// Finds all elements within `dist` of `query`, sorted by distance, using the
+    /// specified distance metric.
+    #[inline]
+    pub fn within<D>(&self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, 1>,
+    {
+        self.tree.within::<D>(query, dist)
+    }
+
+    /// Finds all elements within `dist` of `query`, in arbitrary order, using the
+    /// specified distance metric.
+    #[inline]
+    pub fn within_unsorted<D>(&self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, 1>,
+    {
+        self.tree.within_unsorted::<D>(query, dist)
+    }
+
+    /// Returns `true` if there is any element within `dist` of `query`, stopping as soon
+    /// as one is found.
+    #[inline]
+    pub fn any_within<D>(&self, query: &[A; K], dist: A) -> bool
+    where
+        D: DistanceMetric<A, 1>,
+    {
+        self.tree.any_within::<D>(query, dist)
+    }
+}
+
+impl<A: Axis, T: Content, const K: usize> KdTree<A, T, K>
+where
+    usize: Cast<T>,
+{
+    /// Creates a tree whose leaves each hold up to `bucket_size` points, containing
+    /// `points`, using each point's index as its item.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::float::kdtree_rt::KdTree;
+    /// use kiddo::SquaredEuclidean;
+    ///
+    /// let points = vec![[1.0, 2.0], [3.0, 4.0]];
+    /// let tree: KdTree<f64, u32, 2> = KdTree::from_points(8, &points);
+    ///
+    /// assert_eq!(tree.size(), 2);
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[3.1, 4.1]).item, 1);
+    /// ```
+    pub fn from_points(bucket_size: usize, points: &[[A; K]]) -> Self {
+        let mut tree = Self::with_bucket_size(bucket_size);
+        for (idx, point) in points.iter().enumerate() {
+            tree.add(point, idx.cast());
+        }
+
+        tree
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KdTree;
+    use crate::float::kdtree::KdTree as ConstKdTree;
+    use crate::SquaredEuclidean;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn matches_the_const_bucket_size_tree() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(89);
+        let points: Vec<[f64; 3]> = (0..20_000).map(|_| rng.gen()).collect();
+
+        let tree: KdTree<f64, u32, 3> = KdTree::from_points(1001, &points);
+        let mut const_tree: ConstKdTree<f64, u32, 3, 1001, u32> = ConstKdTree::new();
+        for (idx, point) in points.iter().enumerate() {
+            const_tree.add(point, idx as u32);
+        }
+
+        assert_eq!(tree.size(), points.len());
+        assert_eq!(tree.iter().count(), points.len());
+
+        for _ in 0..100 {
+            let query: [f64; 3] = rng.gen();
+            assert_eq!(
+                tree.nearest_n::<SquaredEuclidean>(&query, 5),
+                const_tree.nearest_n::<SquaredEuclidean>(&query, 5)
+            );
+            assert_eq!(
+                tree.within::<SquaredEuclidean>(&query, 0.01),
+                const_tree.within::<SquaredEuclidean>(&query, 0.01)
+            );
+        }
+
+        let (item, point) = tree.iter().next().unwrap();
+        assert_eq!(point, points[item as usize]);
+    }
+}
@@ -6,10 +6,15 @@
 //! NB if you don't need to be able to add or remove items from the tree after construction /
 //! deserialization, you may get better performance from [`immutable::float::kdtree::ImmutableKdTree`](`crate::immutable::float::kdtree::ImmutableKdTree`)
 
+pub mod cached;
 #[doc(hidden)]
 pub mod construction;
 pub mod distance;
+pub mod dyn_kdtree;
+pub mod introspection;
 pub mod kdtree;
+pub mod kdtree_rt;
 #[doc(hidden)]
 pub mod query;
 pub(crate) mod result_collection;
+pub mod snapshot;
@@ -2,10 +2,11 @@ use az::{Az, Cast};
 use std::collections::BinaryHeap;
 use std::ops::Rem;
 
-use crate::best_neighbour::BestNeighbour;
+use crate::best_neighbour::{BestByScore, BestNeighbour};
 use crate::float::kdtree::{Axis, KdTree, LeafNode};
-use crate::traits::DistanceMetric;
+use crate::float::result_collection::VisitedResults;
 use crate::traits::{is_stem_index, Content, Index};
+use crate::traits::{DistanceMetric, StaticMetric};
 
 use crate::generate_best_n_within;
 
@@ -38,6 +39,39 @@ Returns an iterator.
 ```"
             )
         );
+
+        /// Finds the "best" `max_qty` elements within `dist` of `query`, where "best" means
+        /// having the lowest score, as returned by `score` for the item and its distance.
+        ///
+        /// Generalizes `best_n_within`, which always prefers the lowest items, to any
+        /// ordering, such as by a property of each item that is stored outside the tree.
+        /// Results are sorted by score, lowest first.
+        #[inline]
+        pub fn best_n_within_by<D, S, F>(
+            &self,
+            query: &[A; K],
+            dist: A,
+            max_qty: usize,
+            score: F,
+        ) -> Vec<BestNeighbour<A, T>>
+        where
+            D: DistanceMetric<A, K>,
+            S: PartialOrd,
+            F: Fn(T, A) -> S,
+        {
+            let mut best_items = BestByScore::new(max_qty);
+            let mut visitor = |item, distance| best_items.add(distance, item, score(item, distance));
+
+            self.nearest_n_within_stub(
+                &StaticMetric::<D>::new(),
+                query,
+                dist,
+                VisitedResults { visitor: &mut visitor },
+                false,
+            );
+
+            best_items.into_sorted_vec()
+        }
     };
 }
 
@@ -187,6 +221,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn best_n_within_by_keeps_the_lowest_scores() {
+        let mut rng = rand::thread_rng();
+        let points: Vec<[AX; 2]> = (0..5_000).map(|_| rng.gen()).collect();
+        let magnitudes: Vec<f64> = (0..points.len()).map(|_| rng.gen()).collect();
+
+        let mut tree: KdTree<AX, i32, 2, 32, u32> = KdTree::new();
+        for (idx, point) in points.iter().enumerate() {
+            tree.add(point, idx as i32);
+        }
+
+        for _ in 0..50 {
+            let query: [AX; 2] = rng.gen();
+            let radius = 0.01;
+            let score = |item: i32, _distance: f64| magnitudes[item as usize];
+
+            let mut expected: Vec<_> = points
+                .iter()
+                .enumerate()
+                .map(|(idx, point)| (SquaredEuclidean::dist(&query, point), idx as i32))
+                .filter(|&(distance, _)| distance < radius)
+                .collect();
+            expected.sort_by(|a, b| score(a.1, a.0).partial_cmp(&score(b.1, b.0)).unwrap());
+            expected.truncate(5);
+
+            let result: Vec<_> = tree
+                .best_n_within_by::<SquaredEuclidean, _, _>(&query, radius, 5, score)
+                .into_iter()
+                .map(|n| (n.distance, n.item))
+                .collect();
+            assert_eq!(result, expected);
+        }
+    }
+
     fn linear_search(
         content: &[([f64; 2], i32)],
         query: &[f64; 2],
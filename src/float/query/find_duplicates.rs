@@ -0,0 +1,244 @@
+use az::{Az, Cast};
+
+use super::join_within::{split_node, JoinNode};
+use crate::float::kdtree::{Axis, KdTree};
+use crate::traits::{is_stem_index, Content, DistanceMetric, Index};
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
+    KdTree<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    /// Finds groups of items whose points are within `tolerance` of each other, using the
+    /// specified distance metric function.
+    ///
+    /// Two items are in the same group if their points are no more than `tolerance` apart,
+    /// or if they are both in the same group as a third item, so a `tolerance` of zero groups
+    /// together items that are stored at exactly the same point. Items that are not near any
+    /// other item are not returned. Each group is sorted by item, and the groups are sorted
+    /// by their first item.
+    ///
+    /// The tree is joined with itself, visiting each pair of nearby points once, which is
+    /// much faster than calling `within` for every point in the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    ///     use kiddo::KdTree;
+    ///     use kiddo::SquaredEuclidean;
+    ///
+    ///     let mut tree: KdTree<f64, 2> = KdTree::new();
+    ///     tree.add(&[0.0, 0.0], 1);
+    ///     tree.add(&[5.0, 5.0], 2);
+    ///     tree.add(&[0.0, 0.05], 3);
+    ///     tree.add(&[5.0, 5.0], 4);
+    ///     tree.add(&[9.0, 0.0], 5);
+    ///
+    ///     let groups = tree.find_duplicates::<SquaredEuclidean>(0.01);
+    ///
+    ///     assert_eq!(groups, vec![vec![1, 3], vec![2, 4]]);
+    /// ```
+    pub fn find_duplicates<D>(&self, tolerance: A) -> Vec<Vec<T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut pairs = Vec::new();
+
+        let root = JoinNode::root(self.root_index);
+        self.find_duplicates_recurse::<D>(tolerance, root, root, &mut pairs);
+
+        group_pairs(pairs)
+    }
+
+    /// Adds the pairs of items within `tolerance` of each other, one from `node` and one from
+    /// `other_node`, to `pairs`. When both are the same node, each pair is only added once.
+    fn find_duplicates_recurse<D>(
+        &self,
+        tolerance: A,
+        node: JoinNode<A, K, IDX>,
+        other_node: JoinNode<A, K, IDX>,
+        pairs: &mut Vec<(T, T)>,
+    ) where
+        D: DistanceMetric<A, K>,
+    {
+        if node.min_dist::<D>(&other_node) > tolerance {
+            return;
+        }
+
+        let is_stem = is_stem_index(node.idx);
+        let other_is_stem = is_stem_index(other_node.idx);
+
+        if node.idx == other_node.idx {
+            if is_stem {
+                // pairs within each child, then pairs with one point in each
+                let stem = &self.stems[node.idx.az::<usize>()];
+                let [left, right] = split_node(&node, stem.left, stem.right, stem.split_val);
+                self.find_duplicates_recurse::<D>(tolerance, left, left, pairs);
+                self.find_duplicates_recurse::<D>(tolerance, right, right, pairs);
+                self.find_duplicates_recurse::<D>(tolerance, left, right, pairs);
+            } else {
+                let leaf = &self.leaves[(node.idx - IDX::leaf_offset()).az::<usize>()];
+                let size = leaf.size.az::<usize>();
+
+                for i in 0..size {
+                    for j in (i + 1)..size {
+                        let distance = D::dist(&leaf.content_points[i], &leaf.content_points[j]);
+                        if distance <= tolerance {
+                            pairs.push((leaf.content_items[i], leaf.content_items[j]));
+                        }
+                    }
+                }
+            }
+        } else if is_stem && (!other_is_stem || node.depth <= other_node.depth) {
+            let stem = &self.stems[node.idx.az::<usize>()];
+            let [left, right] = split_node(&node, stem.left, stem.right, stem.split_val);
+            for child in [left, right] {
+                self.find_duplicates_recurse::<D>(tolerance, child, other_node, pairs);
+            }
+        } else if other_is_stem {
+            let stem = &self.stems[other_node.idx.az::<usize>()];
+            let [left, right] = split_node(&other_node, stem.left, stem.right, stem.split_val);
+            for child in [left, right] {
+                self.find_duplicates_recurse::<D>(tolerance, node, child, pairs);
+            }
+        } else {
+            let leaf = &self.leaves[(node.idx - IDX::leaf_offset()).az::<usize>()];
+            let other_leaf = &self.leaves[(other_node.idx - IDX::leaf_offset()).az::<usize>()];
+
+            let other_content = || {
+                other_leaf
+                    .content_points
+                    .iter()
+                    .zip(other_leaf.content_items.iter())
+                    .take(other_leaf.size.az::<usize>())
+            };
+
+            leaf.content_points
+                .iter()
+                .zip(leaf.content_items.iter())
+                .take(leaf.size.az::<usize>())
+                .for_each(|(point, &item)| {
+                    for (other_point, &other_item) in other_content() {
+                        if D::dist(point, other_point) <= tolerance {
+                            pairs.push((item, other_item));
+                        }
+                    }
+                });
+        }
+    }
+}
+
+/// Merges pairs of items into groups of items that are connected by a chain of pairs,
+/// with each group sorted, and the groups sorted by their first item
+fn group_pairs<T: Content>(pairs: Vec<(T, T)>) -> Vec<Vec<T>> {
+    let mut items: Vec<T> = pairs.iter().flat_map(|&(a, b)| [a, b]).collect();
+    items.sort_unstable();
+    items.dedup();
+
+    let position = |item: T| items.binary_search(&item).unwrap();
+
+    // union-find over the positions of the items, with each set's root being the
+    // position of its smallest item
+    let mut parent: Vec<usize> = (0..items.len()).collect();
+    fn find(parent: &mut [usize], mut idx: usize) -> usize {
+        while parent[idx] != idx {
+            parent[idx] = parent[parent[idx]];
+            idx = parent[idx];
+        }
+        idx
+    }
+    for (a, b) in pairs {
+        let root_a = find(&mut parent, position(a));
+        let root_b = find(&mut parent, position(b));
+        parent[root_a.max(root_b)] = root_a.min(root_b);
+    }
+
+    let mut groups: Vec<Vec<T>> = Vec::new();
+    let mut group_of_root = vec![usize::MAX; items.len()];
+    for (idx, &item) in items.iter().enumerate() {
+        let root = find(&mut parent, idx);
+        if group_of_root[root] == usize::MAX {
+            group_of_root[root] = groups.len();
+            groups.push(Vec::new());
+        }
+        groups[group_of_root[root]].push(item);
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::float::distance::{Manhattan, SquaredEuclidean};
+    use crate::float::kdtree::KdTree;
+    use crate::traits::DistanceMetric;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn find_duplicates_groups_exact_duplicates_with_zero_tolerance() {
+        let mut tree: KdTree<f64, u32, 2, 8, u32> = KdTree::new();
+        for idx in 0..50u32 {
+            tree.add(&[(idx % 10) as f64, (idx % 10) as f64 * 0.5], idx);
+        }
+
+        let groups = tree.find_duplicates::<SquaredEuclidean>(0.0);
+
+        let expected: Vec<Vec<u32>> = (0..10u32)
+            .map(|first| (first..50).step_by(10).collect())
+            .collect();
+        assert_eq!(groups, expected);
+    }
+
+    #[test]
+    fn find_duplicates_matches_a_brute_force_search() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(97);
+        let points: Vec<[f64; 3]> = (0..2_000).map(|_| rng.gen()).collect();
+
+        let mut tree: KdTree<f64, u32, 3, 32, u32> = KdTree::new();
+        for (idx, point) in points.iter().enumerate() {
+            tree.add(point, idx as u32);
+        }
+
+        check_groups::<SquaredEuclidean>(&tree, &points, 0.0005);
+        check_groups::<Manhattan>(&tree, &points, 0.03);
+    }
+
+    fn check_groups<D: DistanceMetric<f64, 3>>(
+        tree: &KdTree<f64, u32, 3, 32, u32>,
+        points: &[[f64; 3]],
+        tolerance: f64,
+    ) {
+        // label each point with the smallest item reachable from it
+        let mut labels: Vec<usize> = (0..points.len()).collect();
+        loop {
+            let mut changed = false;
+            for a in 0..points.len() {
+                for b in (a + 1)..points.len() {
+                    if D::dist(&points[a], &points[b]) <= tolerance && labels[a] != labels[b] {
+                        let label = labels[a].min(labels[b]);
+                        labels[a] = label;
+                        labels[b] = label;
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let mut expected: Vec<Vec<u32>> = Vec::new();
+        for label in 0..points.len() {
+            let group: Vec<u32> = (0..points.len())
+                .filter(|&idx| labels[idx] == label)
+                .map(|idx| idx as u32)
+                .collect();
+            if group.len() > 1 {
+                expected.push(group);
+            }
+        }
+        assert!(!expected.is_empty());
+
+        assert_eq!(tree.find_duplicates::<D>(tolerance), expected);
+    }
+}
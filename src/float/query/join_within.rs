@@ -0,0 +1,230 @@
+use az::{Az, Cast};
+use std::ops::Rem;
+
+use crate::float::kdtree::{Axis, KdTree};
+use crate::traits::{accumulate_dist, is_stem_index, Content, DistanceMetric, Index};
+
+/// A node of one of the trees being joined, along with the region of space that it covers
+#[derive(Clone, Copy)]
+pub(super) struct JoinNode<A, const K: usize, IDX> {
+    pub(super) idx: IDX,
+    pub(super) depth: usize,
+    pub(super) min: [A; K],
+    pub(super) max: [A; K],
+}
+
+impl<A: Axis, const K: usize, IDX> JoinNode<A, K, IDX> {
+    /// A node covering the whole of space
+    pub(super) fn root(idx: IDX) -> Self {
+        JoinNode {
+            idx,
+            depth: 0,
+            min: [A::neg_infinity(); K],
+            max: [A::infinity(); K],
+        }
+    }
+
+    /// A lower bound on the distance between any point in this node and any point in
+    /// `other`, from the gap between their regions on each axis
+    pub(super) fn min_dist<D: DistanceMetric<A, K>>(&self, other: &Self) -> A {
+        (0..K).fold(A::zero(), |acc, dim| {
+            let gap = if self.max[dim] < other.min[dim] {
+                D::dist1(other.min[dim], self.max[dim])
+            } else if other.max[dim] < self.min[dim] {
+                D::dist1(self.min[dim], other.max[dim])
+            } else {
+                A::zero()
+            };
+            accumulate_dist(acc, gap, D::MAX_OF_AXES, A::rd_update)
+        })
+    }
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
+    KdTree<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    /// Finds every pair of an item in this tree and an item in `other` whose points are
+    /// within `radius` of each other, using the specified distance metric function.
+    ///
+    /// Returns `(item, other_item, distance)` triples, in arbitrary order. Both trees
+    /// are traversed together, and pairs of subtrees that are too far apart for any of
+    /// their points to be in range of each other are skipped without being searched.
+    /// This is much faster than calling `within` for every point of one of the trees.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    ///     use kiddo::KdTree;
+    ///     use kiddo::SquaredEuclidean;
+    ///
+    ///     let mut sensors: KdTree<f64, 2> = KdTree::new();
+    ///     sensors.add(&[0.0, 0.0], 1);
+    ///     sensors.add(&[10.0, 10.0], 2);
+    ///
+    ///     let mut vehicles: KdTree<f64, 2> = KdTree::new();
+    ///     vehicles.add(&[0.5, 0.5], 100);
+    ///     vehicles.add(&[5.0, 5.0], 101);
+    ///
+    ///     let pairs = sensors.join_within::<SquaredEuclidean>(&vehicles, 1.0);
+    ///
+    ///     assert_eq!(pairs, vec![(1, 100, 0.5)]);
+    /// ```
+    pub fn join_within<D>(&self, other: &Self, radius: A) -> Vec<(T, T, A)>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut results = Vec::new();
+
+        self.join_within_recurse::<D>(
+            other,
+            radius,
+            JoinNode::root(self.root_index),
+            JoinNode::root(other.root_index),
+            &mut results,
+        );
+
+        results
+    }
+
+    fn join_within_recurse<D>(
+        &self,
+        other: &Self,
+        radius: A,
+        node: JoinNode<A, K, IDX>,
+        other_node: JoinNode<A, K, IDX>,
+        results: &mut Vec<(T, T, A)>,
+    ) where
+        D: DistanceMetric<A, K>,
+    {
+        if node.min_dist::<D>(&other_node) >= radius {
+            return;
+        }
+
+        let is_stem = is_stem_index(node.idx);
+        let other_is_stem = is_stem_index(other_node.idx);
+
+        // descend into whichever node is the shallower of the two, so that the regions
+        // being compared stay of a similar size
+        if is_stem && (!other_is_stem || node.depth <= other_node.depth) {
+            let stem = &self.stems[node.idx.az::<usize>()];
+            let [left, right] = split_node(&node, stem.left, stem.right, stem.split_val);
+            for child in [left, right] {
+                self.join_within_recurse::<D>(other, radius, child, other_node, results);
+            }
+        } else if other_is_stem {
+            let stem = &other.stems[other_node.idx.az::<usize>()];
+            let [left, right] = split_node(&other_node, stem.left, stem.right, stem.split_val);
+            for child in [left, right] {
+                self.join_within_recurse::<D>(other, radius, node, child, results);
+            }
+        } else {
+            let leaf = &self.leaves[(node.idx - IDX::leaf_offset()).az::<usize>()];
+            let other_leaf = &other.leaves[(other_node.idx - IDX::leaf_offset()).az::<usize>()];
+
+            let other_content = || {
+                other_leaf
+                    .content_points
+                    .iter()
+                    .zip(other_leaf.content_items.iter())
+                    .take(other_leaf.size.az::<usize>())
+            };
+
+            leaf.content_points
+                .iter()
+                .zip(leaf.content_items.iter())
+                .take(leaf.size.az::<usize>())
+                .for_each(|(point, &item)| {
+                    for (other_point, &other_item) in other_content() {
+                        let distance = D::dist(point, other_point);
+                        if distance < radius {
+                            results.push((item, other_item, distance));
+                        }
+                    }
+                });
+        }
+    }
+}
+
+/// Splits `node`, a stem splitting at `split_val`, into its left and right children
+pub(super) fn split_node<A: Copy, const K: usize, IDX>(
+    node: &JoinNode<A, K, IDX>,
+    left: IDX,
+    right: IDX,
+    split_val: A,
+) -> [JoinNode<A, K, IDX>; 2] {
+    let split_dim = node.depth.rem(K);
+
+    let mut left_max = node.max;
+    left_max[split_dim] = split_val;
+    let mut right_min = node.min;
+    right_min[split_dim] = split_val;
+
+    [
+        JoinNode {
+            idx: left,
+            depth: node.depth + 1,
+            min: node.min,
+            max: left_max,
+        },
+        JoinNode {
+            idx: right,
+            depth: node.depth + 1,
+            min: right_min,
+            max: node.max,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::float::distance::{Chebyshev, Manhattan, SquaredEuclidean};
+    use crate::float::kdtree::KdTree;
+    use crate::traits::DistanceMetric;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn join_within_finds_every_pair_in_range() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(55);
+        let points_a: Vec<[f64; 3]> = (0..2_000).map(|_| rng.gen()).collect();
+        let points_b: Vec<[f64; 3]> = (0..3_000).map(|_| rng.gen()).collect();
+
+        let mut tree_a: KdTree<f64, u32, 3, 32, u32> = KdTree::new();
+        for (idx, point) in points_a.iter().enumerate() {
+            tree_a.add(point, idx as u32);
+        }
+        let mut tree_b: KdTree<f64, u32, 3, 32, u32> = KdTree::new();
+        for (idx, point) in points_b.iter().enumerate() {
+            tree_b.add(point, idx as u32);
+        }
+
+        check_join::<SquaredEuclidean>(&tree_a, &tree_b, &points_a, &points_b, 0.002);
+        check_join::<Manhattan>(&tree_a, &tree_b, &points_a, &points_b, 0.05);
+        check_join::<Chebyshev>(&tree_a, &tree_b, &points_a, &points_b, 0.03);
+    }
+
+    fn check_join<D: DistanceMetric<f64, 3>>(
+        tree_a: &KdTree<f64, u32, 3, 32, u32>,
+        tree_b: &KdTree<f64, u32, 3, 32, u32>,
+        points_a: &[[f64; 3]],
+        points_b: &[[f64; 3]],
+        radius: f64,
+    ) {
+        let mut expected = Vec::new();
+        for (idx_a, point_a) in points_a.iter().enumerate() {
+            for (idx_b, point_b) in points_b.iter().enumerate() {
+                let distance = D::dist(point_a, point_b);
+                if distance < radius {
+                    expected.push((idx_a as u32, idx_b as u32, distance));
+                }
+            }
+        }
+        assert!(!expected.is_empty());
+
+        let mut result = tree_a.join_within::<D>(tree_b, radius);
+        result.sort_by_key(|&(a, b, _)| (a, b));
+
+        assert_eq!(result, expected);
+    }
+}
@@ -1,9 +1,13 @@
 pub mod best_n_within;
+pub mod find_duplicates;
+pub mod join_within;
 pub mod nearest_n;
+pub mod nearest_n_in_slab;
 pub mod nearest_n_within;
 pub mod nearest_one;
+pub mod nearest_to_segment;
 pub mod within;
+pub mod within_annulus;
+pub mod within_polygon;
 pub mod within_unsorted;
-
-#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
 pub mod within_unsorted_iter;
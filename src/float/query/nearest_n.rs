@@ -70,9 +70,9 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::float::distance::SquaredEuclidean;
+    use crate::float::distance::{SquaredEuclidean, WeightedSquaredEuclidean};
     use crate::float::kdtree::{Axis, KdTree};
-    use crate::traits::DistanceMetric;
+    use crate::traits::{DistanceMetric, StatefulDistanceMetric};
     use rand::Rng;
 
     type AX = f32;
@@ -141,6 +141,43 @@ mod tests {
         }
     }
 
+    #[test]
+    fn can_query_into_a_reused_buffer() {
+        let content_to_add: Vec<([f32; 4], u32)> = (0..5_000)
+            .map(|_| rand::random::<([f32; 4], u32)>())
+            .collect();
+
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::new();
+        content_to_add
+            .iter()
+            .for_each(|(point, item)| tree.add(point, *item));
+
+        // start with a buffer larger than any query needs, holding stale results
+        let mut out = tree.within::<SquaredEuclidean>(&[0.5; 4], 0.5);
+        let dists = |results: &[crate::nearest_neighbour::NearestNeighbour<AX, u32>]| {
+            results.iter().map(|n| n.distance).collect::<Vec<_>>()
+        };
+
+        tree.nearest_n_into::<SquaredEuclidean>(&[0.5; 4], 0, &mut out);
+        assert!(out.is_empty());
+
+        for qty in [1, 10, 100] {
+            let query = rand::random::<[f32; 4]>();
+
+            tree.nearest_n_into::<SquaredEuclidean>(&query, qty, &mut out);
+            assert_eq!(
+                dists(&out),
+                dists(&tree.nearest_n::<SquaredEuclidean>(&query, qty))
+            );
+
+            tree.within_into::<SquaredEuclidean>(&query, 0.05, &mut out);
+            assert_eq!(
+                dists(&out),
+                dists(&tree.within::<SquaredEuclidean>(&query, 0.05))
+            );
+        }
+    }
+
     #[test]
     fn can_query_nearest_10_items_large_scale() {
         const TREE_SIZE: usize = 100_000;
@@ -177,6 +214,175 @@ mod tests {
         }
     }
 
+    #[test]
+    fn can_query_nearest_n_items_with_weighted_metric() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+        const N: usize = 10;
+
+        let metric = WeightedSquaredEuclidean::new([4f32, 0.25f32, 1f32, 10f32]);
+
+        let content_to_add: Vec<([f32; 4], u32)> = (0..TREE_SIZE)
+            .map(|_| rand::random::<([f32; 4], u32)>())
+            .collect();
+
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        let query_points: Vec<[f32; 4]> = (0..NUM_QUERIES)
+            .map(|_| rand::random::<[f32; 4]>())
+            .collect();
+
+        for query_point in query_points {
+            let mut expected_dists: Vec<_> = content_to_add
+                .iter()
+                .map(|(p, _)| metric.dist(&query_point, p))
+                .collect();
+            expected_dists.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            expected_dists.truncate(N);
+
+            let result_dists: Vec<_> = tree
+                .nearest_n_with_metric(&metric, &query_point, N)
+                .into_iter()
+                .map(|n| n.distance)
+                .collect();
+
+            assert_eq!(result_dists, expected_dists);
+        }
+    }
+
+    #[test]
+    fn can_query_nearest_n_const() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+
+        let content_to_add: Vec<([f32; 4], u32)> = (0..TREE_SIZE)
+            .map(|_| rand::random::<([f32; 4], u32)>())
+            .collect();
+
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        for _ in 0..NUM_QUERIES {
+            let query_point = rand::random::<[f32; 4]>();
+            let expected = linear_search(&content_to_add, 10, &query_point);
+
+            let result: Vec<_> = tree
+                .nearest_n_const::<SquaredEuclidean, 10>(&query_point)
+                .into_iter()
+                .map(|n| (n.distance, n.item))
+                .collect();
+
+            assert_eq!(result, expected);
+        }
+
+        // fewer items than N
+        let mut small: KdTree<AX, u32, 4, 32, u32> = KdTree::new();
+        small.add(&[0.5; 4], 7);
+        let result = small.nearest_n_const::<SquaredEuclidean, 3>(&[0.0; 4]);
+        assert_eq!(result[0].item, 7);
+        assert_eq!(result[0].distance, 1.0);
+        assert_eq!(result[1].distance, AX::MAX);
+        assert_eq!(result[2].distance, AX::MAX);
+    }
+
+    #[test]
+    fn can_query_kth_nearest_distance() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+
+        let content_to_add: Vec<([f32; 4], u32)> = (0..TREE_SIZE)
+            .map(|_| rand::random::<([f32; 4], u32)>())
+            .collect();
+
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        for _ in 0..NUM_QUERIES {
+            let query_point = rand::random::<[f32; 4]>();
+            let expected = linear_search(&content_to_add, 20, &query_point);
+
+            for k in [1, 5, 20] {
+                assert_eq!(
+                    tree.kth_nearest_distance::<SquaredEuclidean>(&query_point, k),
+                    expected[k - 1].0
+                );
+            }
+        }
+
+        // fewer items than k
+        let mut small: KdTree<AX, u32, 4, 32, u32> = KdTree::new();
+        small.add(&[0.5; 4], 7);
+        assert_eq!(
+            small.kth_nearest_distance::<SquaredEuclidean>(&[0.0; 4], 1),
+            1.0
+        );
+        assert_eq!(
+            small.kth_nearest_distance::<SquaredEuclidean>(&[0.0; 4], 2),
+            AX::MAX
+        );
+    }
+
+    #[test]
+    fn can_query_nearest_n_items_filtered() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+        const N: usize = 10;
+
+        let content_to_add: Vec<([f32; 4], u32)> = (0..TREE_SIZE)
+            .map(|idx| (rand::random::<[f32; 4]>(), idx as u32))
+            .collect();
+
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        let matching: Vec<([f32; 4], u32)> = content_to_add
+            .iter()
+            .copied()
+            .filter(|&(_, item)| item % 3 == 0)
+            .collect();
+
+        for _ in 0..NUM_QUERIES {
+            let query_point = rand::random::<[f32; 4]>();
+            let expected = linear_search(&matching, N, &query_point);
+
+            let result: Vec<_> = tree
+                .nearest_n_filtered::<SquaredEuclidean, _>(&query_point, N, |item| item % 3 == 0)
+                .into_iter()
+                .map(|n| (n.distance, n.item))
+                .collect();
+
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    fn nearest_one_filtered_can_exclude_a_duplicate_of_the_query() {
+        let mut tree: KdTree<AX, u32, 2, 8, u32> = KdTree::new();
+        tree.add(&[0.5, 0.5], 0);
+        tree.add(&[0.5, 0.5], 1);
+        tree.add(&[0.6, 0.5], 2);
+
+        let result = tree
+            .nearest_one_filtered::<SquaredEuclidean, _>(&[0.5, 0.5], |item| item != 0)
+            .unwrap();
+        assert_eq!(result.item, 1);
+        assert_eq!(result.distance, 0.0);
+
+        assert_eq!(
+            tree.nearest_one_filtered::<SquaredEuclidean, _>(&[0.5, 0.5], |_| false),
+            None
+        );
+    }
+
     fn linear_search<A: Axis, const K: usize>(
         content: &[([A; K], u32)],
         qty: usize,
@@ -0,0 +1,267 @@
+use az::{Az, Cast};
+use std::collections::BinaryHeap;
+use std::ops::Rem;
+
+use crate::common::nearest_n_results::NearestNResults;
+use crate::float::kdtree::{Axis, KdTree, LeafNode};
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{accumulate_dist, is_stem_index, Content, DistanceMetric, Index};
+
+/// The range of values that a query's results must have on one axis
+struct Slab<A> {
+    axis: usize,
+    min: A,
+    max: A,
+}
+
+macro_rules! generate_float_nearest_n_in_slab {
+    ($leafnode:ident, $doctest_build_tree:tt) => {
+        doc_comment! {
+            concat!(
+                "Finds the nearest `qty` elements to `query` whose coordinate on `axis` lies within
+`min..=max`, using the specified distance metric function. Results are returned
+sorted nearest-first.
+
+Subtrees that lie wholly outside of the slab between `min` and `max` are not
+searched, which makes this much faster than filtering the results of a larger
+`nearest_n` query. A typical use is a tree of `[x, y, t]` points, queried for the
+nearest neighbours in space within a window of time.
+
+# Panics
+
+Panics if `axis` is not less than `K`.
+
+# Examples
+
+```rust
+    use kiddo::KdTree;
+    use kiddo::SquaredEuclidean;
+
+    ",
+                $doctest_build_tree,
+                "
+
+    let nearest = tree.nearest_n_in_slab::<SquaredEuclidean>(&[1.0, 2.0, 5.0], 2, 5.5, 7.0, 1);
+
+    assert_eq!(nearest.len(), 1);
+    assert_eq!(nearest[0].item, 101);
+```"
+            ),
+            pub fn nearest_n_in_slab<D>(
+                &self,
+                query: &[A; K],
+                axis: usize,
+                min: A,
+                max: A,
+                qty: usize,
+            ) -> Vec<NearestNeighbour<A, T>>
+            where
+                D: DistanceMetric<A, K>,
+            {
+                assert!(axis < K, "axis must be less than K");
+                if qty == 0 || min > max {
+                    return Vec::new();
+                }
+
+                // Every result is at least as far from the query as it is from the query moved
+                // into the slab, so distances from that are used to prune the search
+                let mut bound_query = *query;
+                bound_query[axis] = bound_query[axis].max(min).min(max);
+
+                let mut results: BinaryHeap<NearestNeighbour<A, T>> = BinaryHeap::with_capacity(qty);
+                self.nearest_n_in_slab_recurse::<D>(
+                    query,
+                    &bound_query,
+                    &Slab { axis, min, max },
+                    self.root_index,
+                    0,
+                    &mut results,
+                    &mut [A::zero(); K],
+                    A::zero(),
+                );
+
+                results.into_sorted_vec()
+            }
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        fn nearest_n_in_slab_recurse<D>(
+            &self,
+            query: &[A; K],
+            bound_query: &[A; K],
+            slab: &Slab<A>,
+            curr_node_idx: IDX,
+            split_dim: usize,
+            results: &mut BinaryHeap<NearestNeighbour<A, T>>,
+            off: &mut [A; K],
+            rd: A,
+        ) where
+            D: DistanceMetric<A, K>,
+        {
+            if is_stem_index(curr_node_idx) {
+                let node = &self.stems[curr_node_idx.az::<usize>()];
+                let next_split_dim = (split_dim + 1).rem(K);
+
+                // the left subtree holds points below the split value, and the right subtree
+                // those at or above it. A leaf of points that all share the split value can
+                // also be chained to the left of it, though
+                let left_in_slab = split_dim != slab.axis || slab.min <= node.split_val;
+                let right_in_slab = split_dim != slab.axis || slab.max >= node.split_val;
+
+                let old_off = off[split_dim];
+                let new_off = bound_query[split_dim].saturating_dist(node.split_val);
+
+                let [(closer_node_idx, closer_in_slab), (further_node_idx, further_in_slab)] =
+                    if bound_query[split_dim] < node.split_val {
+                        [(node.left, left_in_slab), (node.right, right_in_slab)]
+                    } else {
+                        [(node.right, right_in_slab), (node.left, left_in_slab)]
+                    };
+
+                if closer_in_slab {
+                    self.nearest_n_in_slab_recurse::<D>(
+                        query,
+                        bound_query,
+                        slab,
+                        closer_node_idx,
+                        next_split_dim,
+                        results,
+                        off,
+                        rd,
+                    );
+                }
+
+                let rd = accumulate_dist(rd, D::dist1(new_off, old_off), D::MAX_OF_AXES, A::rd_update);
+
+                if further_in_slab && results.dist_belongs(rd) {
+                    off[split_dim] = new_off;
+                    self.nearest_n_in_slab_recurse::<D>(
+                        query,
+                        bound_query,
+                        slab,
+                        further_node_idx,
+                        next_split_dim,
+                        results,
+                        off,
+                        rd,
+                    );
+                    off[split_dim] = old_off;
+                }
+            } else {
+                let leaf_node: &$leafnode<A, T, K, B, IDX> =
+                    &self.leaves[(curr_node_idx - IDX::leaf_offset()).az::<usize>()];
+
+                leaf_node
+                    .content_points
+                    .iter()
+                    .zip(leaf_node.content_items.iter())
+                    .take(leaf_node.size.az::<usize>())
+                    .filter(|(point, _)| point[slab.axis] >= slab.min && point[slab.axis] <= slab.max)
+                    .for_each(|(point, &item)| {
+                        let distance = D::dist(query, point);
+                        if results.dist_belongs(distance) {
+                            results.add_nearest(NearestNeighbour { distance, item });
+                        }
+                    });
+            }
+        }
+    };
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
+    KdTree<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    generate_float_nearest_n_in_slab!(
+        LeafNode,
+        "let mut tree: KdTree<f64, 3> = KdTree::new();
+    tree.add(&[1.0, 2.0, 5.0], 100);
+    tree.add(&[2.0, 3.0, 6.0], 101);"
+    );
+}
+
+#[cfg(feature = "rkyv")]
+use crate::float::kdtree::{ArchivedKdTree, ArchivedLeafNode};
+#[cfg(feature = "rkyv")]
+impl<
+        A: Axis + rkyv::Archive<Archived = A>,
+        T: Content + rkyv::Archive<Archived = T>,
+        const K: usize,
+        const B: usize,
+        IDX: Index<T = IDX> + rkyv::Archive<Archived = IDX>,
+    > ArchivedKdTree<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    generate_float_nearest_n_in_slab!(
+        ArchivedLeafNode,
+        "use std::fs::File;
+    use memmap::MmapOptions;
+
+    let mmap = unsafe { MmapOptions::new().map(&File::open(\"./examples/float-doctest-tree.rkyv\").unwrap()).unwrap() };
+    let tree = unsafe { rkyv::archived_root::<KdTree<f64, 3>>(&mmap) };"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::float::distance::{Manhattan, SquaredEuclidean};
+    use crate::float::kdtree::KdTree;
+    use crate::nearest_neighbour::NearestNeighbour;
+    use crate::traits::DistanceMetric;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn can_query_nearest_n_in_slab() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(63);
+        let content_to_add: Vec<([f64; 3], u32)> =
+            (0..5_000).map(|idx| (rng.gen::<[f64; 3]>(), idx)).collect();
+
+        let mut tree: KdTree<f64, u32, 3, 32, u32> = KdTree::new();
+        content_to_add
+            .iter()
+            .for_each(|(point, item)| tree.add(point, *item));
+
+        for _ in 0..100 {
+            let query: [f64; 3] = rng.gen();
+            let axis = rng.gen_range(0..3);
+            let min: f64 = rng.gen_range(-0.1..0.9);
+            let max = min + rng.gen_range(0.0..0.2);
+
+            check_slab_query::<SquaredEuclidean>(&tree, &content_to_add, &query, axis, min, max);
+            check_slab_query::<Manhattan>(&tree, &content_to_add, &query, axis, min, max);
+        }
+
+        assert!(tree
+            .nearest_n_in_slab::<SquaredEuclidean>(&[0.5; 3], 0, 0.6, 0.4, 5)
+            .is_empty());
+    }
+
+    fn check_slab_query<D: DistanceMetric<f64, 3>>(
+        tree: &KdTree<f64, u32, 3, 32, u32>,
+        content: &[([f64; 3], u32)],
+        query: &[f64; 3],
+        axis: usize,
+        min: f64,
+        max: f64,
+    ) {
+        let mut expected: Vec<NearestNeighbour<f64, u32>> = content
+            .iter()
+            .filter(|(point, _)| point[axis] >= min && point[axis] <= max)
+            .map(|(point, item)| NearestNeighbour {
+                distance: D::dist(query, point),
+                item: *item,
+            })
+            .collect();
+        expected.sort();
+        expected.truncate(10);
+
+        let result = tree.nearest_n_in_slab::<D>(query, axis, min, max, 10);
+
+        assert_eq!(
+            result.iter().map(|n| n.distance).collect::<Vec<_>>(),
+            expected.iter().map(|n| n.distance).collect::<Vec<_>>()
+        );
+    }
+}
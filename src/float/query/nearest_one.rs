@@ -76,10 +76,10 @@ where
 
 #[cfg(test)]
 mod tests {
-    use crate::float::distance::Manhattan;
+    use crate::float::distance::{Chebyshev, Manhattan, Minkowski, WeightedSquaredEuclidean};
     use crate::float::kdtree::{Axis, KdTree};
     use crate::nearest_neighbour::NearestNeighbour;
-    use crate::traits::DistanceMetric;
+    use crate::traits::{DistanceMetric, StatefulDistanceMetric};
     use rand::Rng;
 
     type AX = f32;
@@ -168,6 +168,173 @@ mod tests {
         }
     }
 
+    #[test]
+    fn can_query_nearest_one_item_with_weighted_metric() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+
+        let metric = WeightedSquaredEuclidean::new([1f32, 100f32, 0.01f32, 0f32]);
+
+        let content_to_add: Vec<([f32; 4], u32)> = (0..TREE_SIZE)
+            .map(|_| rand::random::<([f32; 4], u32)>())
+            .collect();
+
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        let query_points: Vec<[f32; 4]> = (0..NUM_QUERIES)
+            .map(|_| rand::random::<[f32; 4]>())
+            .collect();
+
+        for query_point in query_points {
+            let expected = content_to_add
+                .iter()
+                .map(|(p, _)| metric.dist(&query_point, p))
+                .fold(f32::INFINITY, f32::min);
+
+            let result = tree.nearest_one_with_metric(&metric, &query_point);
+
+            assert_eq!(result.distance, expected);
+        }
+    }
+
+    #[test]
+    fn can_query_nearest_one_point() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+
+        let content_to_add: Vec<([f32; 4], u32)> = (0..TREE_SIZE)
+            .map(|i| (rand::random::<[f32; 4]>(), i as u32))
+            .collect();
+
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        for _ in 0..NUM_QUERIES {
+            let query_point = rand::random::<[f32; 4]>();
+            let expected = linear_search(&content_to_add, &query_point);
+
+            let result = tree.nearest_one_point::<Manhattan>(&query_point);
+
+            assert_eq!(result.distance, expected.distance);
+            assert_eq!(result.item, expected.item);
+            assert_eq!(result.point, content_to_add[result.item as usize].0);
+        }
+    }
+
+    #[test]
+    fn can_query_nearest_one_item_with_chebyshev_and_minkowski() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+
+        let content_to_add: Vec<([f32; 4], u32)> = (0..TREE_SIZE)
+            .map(|_| rand::random::<([f32; 4], u32)>())
+            .collect();
+
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        let query_points: Vec<[f32; 4]> = (0..NUM_QUERIES)
+            .map(|_| rand::random::<[f32; 4]>())
+            .collect();
+
+        for query_point in query_points {
+            let expected = content_to_add
+                .iter()
+                .map(|(p, _)| Chebyshev::dist(&query_point, p))
+                .fold(f32::INFINITY, f32::min);
+            let result = tree.nearest_one::<Chebyshev>(&query_point);
+            assert_eq!(result.distance, expected);
+
+            let expected = content_to_add
+                .iter()
+                .map(|(p, _)| Minkowski::<3>::dist(&query_point, p))
+                .fold(f32::INFINITY, f32::min);
+            let result = tree.nearest_one::<Minkowski<3>>(&query_point);
+            assert_eq!(result.distance, expected);
+        }
+    }
+
+    #[test]
+    fn can_query_nearest_one_within() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+
+        let content_to_add: Vec<([f32; 4], u32)> = (0..TREE_SIZE)
+            .map(|_| rand::random::<([f32; 4], u32)>())
+            .collect();
+
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        for _ in 0..NUM_QUERIES {
+            let query_point = rand::random::<[f32; 4]>();
+            let expected = linear_search(&content_to_add, &query_point);
+
+            for max_dist in [0.01, 0.1, 0.5] {
+                let result = tree.nearest_one_within::<Manhattan>(&query_point, max_dist);
+
+                if expected.distance < max_dist {
+                    assert_eq!(result, Some(expected));
+                } else {
+                    assert_eq!(result, None);
+                }
+            }
+        }
+
+        let empty: KdTree<AX, u32, 4, 32, u32> = KdTree::new();
+        assert_eq!(
+            empty.nearest_one_within::<Manhattan>(&[0.0; 4], AX::MAX),
+            None
+        );
+    }
+
+    #[test]
+    fn can_query_nearest_one_forward() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+
+        let content_to_add: Vec<([f32; 4], u32)> = (0..TREE_SIZE)
+            .map(|_| rand::random::<([f32; 4], u32)>())
+            .collect();
+
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        for _ in 0..NUM_QUERIES {
+            let query_point = rand::random::<[f32; 4]>();
+
+            for axis in 0..4 {
+                let ahead: Vec<_> = content_to_add
+                    .iter()
+                    .filter(|(point, _)| point[axis] > query_point[axis])
+                    .copied()
+                    .collect();
+                let expected = (!ahead.is_empty()).then(|| linear_search(&ahead, &query_point));
+
+                let result =
+                    tree.nearest_one_forward::<Manhattan>(&query_point, axis, query_point[axis]);
+
+                assert_eq!(result, expected);
+            }
+        }
+
+        assert_eq!(
+            tree.nearest_one_forward::<Manhattan>(&[0.5; 4], 2, 1.0),
+            None
+        );
+    }
+
     fn linear_search<A: Axis, const K: usize>(
         content: &[([A; K], u32)],
         query_point: &[A; K],
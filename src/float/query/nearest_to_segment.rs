@@ -0,0 +1,367 @@
+use az::{Az, Cast};
+use std::ops::Rem;
+
+use crate::float::kdtree::{Axis, KdTree, LeafNode};
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{accumulate_dist, is_stem_index, Content, DistanceMetric, Index};
+
+macro_rules! generate_float_segment_queries {
+    ($leafnode:ident, $doctest_build_tree:tt) => {
+        doc_comment! {
+            concat!(
+                "Finds the nearest element to the line segment from `start` to `end`, using the
+specified distance metric function.
+
+The distance of each element is its distance from the closest point on the
+segment to it, rather than from either end. This needs the distance metric to be
+convex along a line, as all of those in [`float::distance`](crate::float::distance) are.
+
+# Examples
+
+```rust
+    use kiddo::KdTree;
+    use kiddo::SquaredEuclidean;
+
+    ",
+                $doctest_build_tree,
+                "
+
+    let nearest = tree.nearest_to_segment::<SquaredEuclidean>(&[0.0, 0.0, 6.0], &[10.0, 0.0, 6.0]);
+
+    assert!((nearest.distance - 5.0).abs() < 1e-9);
+    assert_eq!(nearest.item, 100);
+```"
+            ),
+            pub fn nearest_to_segment<D>(&self, start: &[A; K], end: &[A; K]) -> NearestNeighbour<A, T>
+            where
+                D: DistanceMetric<A, K>,
+            {
+                let mut nearest = NearestNeighbour {
+                    distance: A::max_value(),
+                    item: T::zero(),
+                };
+
+                self.segment_recurse::<D>(
+                    &Segment::new(start, end),
+                    self.root_index,
+                    0,
+                    &mut [A::neg_infinity(); K],
+                    &mut [A::infinity(); K],
+                    &mut |distance, item| {
+                        if distance < nearest.distance {
+                            nearest = NearestNeighbour { distance, item };
+                        }
+                        nearest.distance
+                    },
+                );
+
+                nearest
+            }
+        }
+
+        doc_comment! {
+            concat!(
+                "Finds all elements within `dist` of the line segment from `start` to `end`, using
+the specified distance metric function. Results are returned sorted nearest-first.
+
+As with [`nearest_to_segment`](KdTree::nearest_to_segment), the distance of each
+element is its distance from the closest point on the segment to it. This makes it
+a search of a corridor, or a capsule when using [`SquaredEuclidean`](crate::SquaredEuclidean),
+around the segment.
+
+# Examples
+
+```rust
+    use kiddo::KdTree;
+    use kiddo::SquaredEuclidean;
+
+    ",
+                $doctest_build_tree,
+                "
+
+    let within = tree.within_of_segment::<SquaredEuclidean>(&[0.0, 0.0, 6.0], &[10.0, 0.0, 6.0], 6.0);
+
+    assert_eq!(within.len(), 1);
+    assert_eq!(within[0].item, 100);
+```"
+            ),
+            pub fn within_of_segment<D>(
+                &self,
+                start: &[A; K],
+                end: &[A; K],
+                dist: A,
+            ) -> Vec<NearestNeighbour<A, T>>
+            where
+                D: DistanceMetric<A, K>,
+            {
+                let mut results = Vec::new();
+
+                self.segment_recurse::<D>(
+                    &Segment::new(start, end),
+                    self.root_index,
+                    0,
+                    &mut [A::neg_infinity(); K],
+                    &mut [A::infinity(); K],
+                    &mut |distance, item| {
+                        if distance < dist {
+                            results.push(NearestNeighbour { distance, item });
+                        }
+                        dist
+                    },
+                );
+
+                results.sort();
+                results
+            }
+        }
+
+        /// Visits every element that might be closer to `segment` than the distance returned
+        /// by `visit`, calling `visit` with the distance and item of each.
+        fn segment_recurse<D>(
+            &self,
+            segment: &Segment<A, K>,
+            curr_node_idx: IDX,
+            split_dim: usize,
+            min: &mut [A; K],
+            max: &mut [A; K],
+            visit: &mut impl FnMut(A, T) -> A,
+        ) -> A
+        where
+            D: DistanceMetric<A, K>,
+        {
+            if is_stem_index(curr_node_idx) {
+                let node = &self.stems[curr_node_idx.az::<usize>()];
+                let next_split_dim = (split_dim + 1).rem(K);
+
+                // the child on the same side as the middle of the segment is more likely
+                // to hold close elements, so it is searched first to tighten the bound
+                let mid = (segment.lo[split_dim] + segment.hi[split_dim]) / (A::one() + A::one());
+                let children = if mid < node.split_val {
+                    [(node.left, true), (node.right, false)]
+                } else {
+                    [(node.right, false), (node.left, true)]
+                };
+
+                let mut max_dist = A::max_value();
+                for (child_idx, is_left) in children {
+                    let old = if is_left {
+                        std::mem::replace(&mut max[split_dim], node.split_val)
+                    } else {
+                        std::mem::replace(&mut min[split_dim], node.split_val)
+                    };
+
+                    if segment.box_dist::<D>(min, max) <= max_dist {
+                        max_dist = self.segment_recurse::<D>(
+                            segment,
+                            child_idx,
+                            next_split_dim,
+                            min,
+                            max,
+                            visit,
+                        );
+                    }
+
+                    if is_left {
+                        max[split_dim] = old;
+                    } else {
+                        min[split_dim] = old;
+                    }
+                }
+
+                max_dist
+            } else {
+                let leaf_node: &$leafnode<A, T, K, B, IDX> =
+                    &self.leaves[(curr_node_idx - IDX::leaf_offset()).az::<usize>()];
+
+                let mut max_dist = A::max_value();
+                leaf_node
+                    .content_points
+                    .iter()
+                    .zip(leaf_node.content_items.iter())
+                    .take(leaf_node.size.az::<usize>())
+                    .for_each(|(point, &item)| {
+                        max_dist = visit(segment.dist::<D>(point), item);
+                    });
+
+                max_dist
+            }
+        }
+    };
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
+    KdTree<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    generate_float_segment_queries!(
+        LeafNode,
+        "let mut tree: KdTree<f64, 3> = KdTree::new();
+    tree.add(&[1.0, 2.0, 5.0], 100);
+    tree.add(&[2.0, 3.0, 6.0], 101);"
+    );
+}
+
+#[cfg(feature = "rkyv")]
+use crate::float::kdtree::{ArchivedKdTree, ArchivedLeafNode};
+#[cfg(feature = "rkyv")]
+impl<
+        A: Axis + rkyv::Archive<Archived = A>,
+        T: Content + rkyv::Archive<Archived = T>,
+        const K: usize,
+        const B: usize,
+        IDX: Index<T = IDX> + rkyv::Archive<Archived = IDX>,
+    > ArchivedKdTree<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    generate_float_segment_queries!(
+        ArchivedLeafNode,
+        "use std::fs::File;
+    use memmap::MmapOptions;
+
+    let mmap = unsafe { MmapOptions::new().map(&File::open(\"./examples/float-doctest-tree.rkyv\").unwrap()).unwrap() };
+    let tree = unsafe { rkyv::archived_root::<KdTree<f64, 3>>(&mmap) };"
+    );
+}
+
+/// A line segment, along with its axis-aligned bounding box
+struct Segment<A, const K: usize> {
+    start: [A; K],
+    end: [A; K],
+    lo: [A; K],
+    hi: [A; K],
+}
+
+impl<A: Axis, const K: usize> Segment<A, K> {
+    fn new(start: &[A; K], end: &[A; K]) -> Self {
+        Self {
+            start: *start,
+            end: *end,
+            lo: std::array::from_fn(|dim| start[dim].min(end[dim])),
+            hi: std::array::from_fn(|dim| start[dim].max(end[dim])),
+        }
+    }
+
+    fn at(&self, t: A) -> [A; K] {
+        std::array::from_fn(|dim| self.start[dim] + (self.end[dim] - self.start[dim]) * t)
+    }
+
+    /// The distance from `point` to the closest point on the segment to it.
+    ///
+    /// The distance from `point` to a point moving along the segment is convex, so its
+    /// minimum is found with a golden-section search over the position along the segment.
+    fn dist<D: DistanceMetric<A, K>>(&self, point: &[A; K]) -> A {
+        let inv_phi = A::from(0.618_033_988_749_895).unwrap();
+        let dist_at = |t: A| D::dist(point, &self.at(t));
+
+        let (mut lo, mut hi) = (A::zero(), A::one());
+        let mut a = hi - (hi - lo) * inv_phi;
+        let mut b = lo + (hi - lo) * inv_phi;
+        let (mut dist_a, mut dist_b) = (dist_at(a), dist_at(b));
+
+        while hi - lo > A::epsilon() {
+            if dist_a < dist_b {
+                hi = b;
+                b = a;
+                dist_b = dist_a;
+                a = hi - (hi - lo) * inv_phi;
+                dist_a = dist_at(a);
+            } else {
+                lo = a;
+                a = b;
+                dist_a = dist_b;
+                b = lo + (hi - lo) * inv_phi;
+                dist_b = dist_at(b);
+            }
+        }
+
+        // the minimum can be at either end, which the search only ever approaches
+        dist_a
+            .min(dist_b)
+            .min(dist_at(A::zero()))
+            .min(dist_at(A::one()))
+    }
+
+    /// A lower bound on the distance between any point within the box from `min` to `max`
+    /// and any point on the segment, from the gap between the box and the segment's
+    /// bounding box on each axis
+    fn box_dist<D: DistanceMetric<A, K>>(&self, min: &[A; K], max: &[A; K]) -> A {
+        (0..K).fold(A::zero(), |acc, dim| {
+            let gap = if max[dim] < self.lo[dim] {
+                D::dist1(self.lo[dim], max[dim])
+            } else if min[dim] > self.hi[dim] {
+                D::dist1(min[dim], self.hi[dim])
+            } else {
+                A::zero()
+            };
+            accumulate_dist(acc, gap, D::MAX_OF_AXES, A::rd_update)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Segment;
+    use crate::float::distance::{Chebyshev, Manhattan, SquaredEuclidean};
+    use crate::float::kdtree::KdTree;
+    use crate::nearest_neighbour::NearestNeighbour;
+    use crate::traits::DistanceMetric;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn segment_dist_is_the_distance_to_the_closest_point() {
+        let segment = Segment::new(&[0.0f64, 0.0], &[4.0, 0.0]);
+
+        assert!((segment.dist::<SquaredEuclidean>(&[2.0, 3.0]) - 9.0).abs() < 1e-9);
+        assert!((segment.dist::<SquaredEuclidean>(&[-3.0, 4.0]) - 25.0).abs() < 1e-9);
+        assert!((segment.dist::<SquaredEuclidean>(&[6.0, 0.0]) - 4.0).abs() < 1e-9);
+        assert!((segment.dist::<Manhattan>(&[5.0, -1.0]) - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn can_query_nearest_to_and_within_of_segment() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(53);
+        let content_to_add: Vec<([f64; 3], u32)> =
+            (0..5_000).map(|idx| (rng.gen::<[f64; 3]>(), idx)).collect();
+
+        let mut tree: KdTree<f64, u32, 3, 32, u32> = KdTree::new();
+        content_to_add
+            .iter()
+            .for_each(|(point, item)| tree.add(point, *item));
+
+        for _ in 0..50 {
+            let start: [f64; 3] = rng.gen();
+            let end: [f64; 3] = rng.gen();
+
+            check_segment_queries::<SquaredEuclidean>(&tree, &content_to_add, &start, &end, 0.01);
+            check_segment_queries::<Manhattan>(&tree, &content_to_add, &start, &end, 0.1);
+            check_segment_queries::<Chebyshev>(&tree, &content_to_add, &start, &end, 0.05);
+        }
+    }
+
+    fn check_segment_queries<D: DistanceMetric<f64, 3>>(
+        tree: &KdTree<f64, u32, 3, 32, u32>,
+        content: &[([f64; 3], u32)],
+        start: &[f64; 3],
+        end: &[f64; 3],
+        radius: f64,
+    ) {
+        let segment = Segment::new(start, end);
+        let mut expected: Vec<NearestNeighbour<f64, u32>> = content
+            .iter()
+            .map(|(point, item)| NearestNeighbour {
+                distance: segment.dist::<D>(point),
+                item: *item,
+            })
+            .collect();
+        expected.sort();
+
+        let nearest = tree.nearest_to_segment::<D>(start, end);
+        assert_eq!(nearest.distance, expected[0].distance);
+
+        expected.retain(|neighbour| neighbour.distance < radius);
+        assert!(!expected.is_empty());
+        assert_eq!(tree.within_of_segment::<D>(start, end, radius), expected);
+    }
+}
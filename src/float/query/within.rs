@@ -168,6 +168,62 @@ mod tests {
         }
     }
 
+    #[test]
+    fn can_query_items_within_radius_with_filter() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+        const RADIUS: f32 = 0.2;
+
+        let content_to_add: Vec<([f32; 4], u32)> = (0..TREE_SIZE)
+            .map(|_| rand::random::<([f32; 4], u32)>())
+            .collect();
+
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        for _ in 0..NUM_QUERIES {
+            let query_point = rand::random::<[f32; 4]>();
+
+            let mut expected = linear_search(&content_to_add, &query_point, RADIUS);
+            expected.retain(|nn| nn.item % 3 == 0);
+
+            let mut result =
+                tree.within_filtered::<Manhattan, _>(&query_point, RADIUS, |item| item % 3 == 0);
+            stabilize_sort(&mut result);
+
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    fn can_query_items_within_radius_sorted_by_item() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+        const RADIUS: f32 = 0.2;
+
+        let content_to_add: Vec<([f32; 4], u32)> = (0..TREE_SIZE)
+            .map(|idx| (rand::random::<[f32; 4]>(), idx as u32))
+            .collect();
+
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        for _ in 0..NUM_QUERIES {
+            let query_point = rand::random::<[f32; 4]>();
+
+            let mut expected = linear_search(&content_to_add, &query_point, RADIUS);
+            expected.sort_by_key(|nn| nn.item);
+
+            let result = tree.within_sorted_by_item::<Manhattan>(&query_point, RADIUS);
+
+            assert_eq!(result, expected);
+        }
+    }
+
     fn linear_search<A: Axis, const K: usize>(
         content: &[([A; K], u32)],
         query_point: &[A; K],
@@ -0,0 +1,196 @@
+use az::{Az, Cast};
+
+use crate::float::kdtree::{Axis, KdTree, LeafNode};
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{accumulate_dist, is_stem_index, Content, DistanceMetric, Index};
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
+    KdTree<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    /// Finds all elements whose distance from `query` is at least `min_dist` and at most
+    /// `max_dist`, using the specified distance metric function, sorted by distance.
+    ///
+    /// Subtrees that are entirely further away than `max_dist`, or entirely closer than
+    /// `min_dist`, are skipped without being searched, so this is much faster than
+    /// filtering the results of `within` when `min_dist` excludes many elements.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    ///     use kiddo::KdTree;
+    ///     use kiddo::SquaredEuclidean;
+    ///
+    ///     let mut tree: KdTree<f64, 2> = KdTree::new();
+    ///     tree.add(&[0.5, 0.0], 100);
+    ///     tree.add(&[2.0, 0.0], 101);
+    ///     tree.add(&[5.0, 0.0], 102);
+    ///
+    ///     let within = tree.within_annulus::<SquaredEuclidean>(&[0.0, 0.0], 1.0, 9.0);
+    ///
+    ///     assert_eq!(within.len(), 1);
+    ///     assert_eq!(within[0].item, 101);
+    /// ```
+    pub fn within_annulus<D>(
+        &self,
+        query: &[A; K],
+        min_dist: A,
+        max_dist: A,
+    ) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut results = Vec::new();
+
+        self.within_annulus_recurse::<D>(
+            query,
+            min_dist,
+            max_dist,
+            self.root_index,
+            0,
+            &mut [A::neg_infinity(); K],
+            &mut [A::infinity(); K],
+            &mut results,
+        );
+
+        results.sort();
+        results
+    }
+
+    /// `min` and `max` are the corners of the region of space covered by `curr_node_idx`
+    #[allow(clippy::too_many_arguments)]
+    fn within_annulus_recurse<D>(
+        &self,
+        query: &[A; K],
+        min_dist: A,
+        max_dist: A,
+        curr_node_idx: IDX,
+        split_dim: usize,
+        min: &mut [A; K],
+        max: &mut [A; K],
+        results: &mut Vec<NearestNeighbour<A, T>>,
+    ) where
+        D: DistanceMetric<A, K>,
+    {
+        // bounds on the distance from the query to the nearest and furthest points of
+        // the region covered by this node
+        let (nearest, furthest) = (0..K).fold((A::zero(), A::zero()), |(near, far), dim| {
+            let to_min = D::dist1(query[dim], min[dim]);
+            let to_max = D::dist1(query[dim], max[dim]);
+            let gap = if query[dim] < min[dim] {
+                to_min
+            } else if query[dim] > max[dim] {
+                to_max
+            } else {
+                A::zero()
+            };
+            (
+                accumulate_dist(near, gap, D::MAX_OF_AXES, A::rd_update),
+                accumulate_dist(far, to_min.max(to_max), D::MAX_OF_AXES, A::rd_update),
+            )
+        });
+        if nearest > max_dist || furthest < min_dist {
+            return;
+        }
+
+        if is_stem_index(curr_node_idx) {
+            let node = &self.stems[curr_node_idx.az::<usize>()];
+            let next_split_dim = (split_dim + 1) % K;
+
+            let old_max = max[split_dim];
+            max[split_dim] = node.split_val;
+            self.within_annulus_recurse::<D>(
+                query,
+                min_dist,
+                max_dist,
+                node.left,
+                next_split_dim,
+                min,
+                max,
+                results,
+            );
+            max[split_dim] = old_max;
+
+            let old_min = min[split_dim];
+            min[split_dim] = node.split_val;
+            self.within_annulus_recurse::<D>(
+                query,
+                min_dist,
+                max_dist,
+                node.right,
+                next_split_dim,
+                min,
+                max,
+                results,
+            );
+            min[split_dim] = old_min;
+        } else {
+            let leaf_node: &LeafNode<A, T, K, B, IDX> =
+                &self.leaves[(curr_node_idx - IDX::leaf_offset()).az::<usize>()];
+
+            leaf_node
+                .content_points
+                .iter()
+                .zip(leaf_node.content_items.iter())
+                .take(leaf_node.size.az::<usize>())
+                .for_each(|(point, &item)| {
+                    let distance = D::dist(query, point);
+                    if distance >= min_dist && distance <= max_dist {
+                        results.push(NearestNeighbour { distance, item });
+                    }
+                });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::float::distance::{Chebyshev, Manhattan, SquaredEuclidean};
+    use crate::float::kdtree::KdTree;
+    use crate::traits::DistanceMetric;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn can_query_items_within_annulus() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(99);
+        let content_to_add: Vec<[f64; 3]> = (0..5_000).map(|_| rng.gen()).collect();
+
+        let mut tree: KdTree<f64, u32, 3, 32, u32> = KdTree::new();
+        for (idx, point) in content_to_add.iter().enumerate() {
+            tree.add(point, idx as u32);
+        }
+
+        for _ in 0..20 {
+            let query: [f64; 3] = rng.gen();
+            check_annulus::<SquaredEuclidean>(&tree, &content_to_add, &query, 0.01, 0.04);
+            check_annulus::<Manhattan>(&tree, &content_to_add, &query, 0.1, 0.3);
+            check_annulus::<Chebyshev>(&tree, &content_to_add, &query, 0.05, 0.15);
+        }
+    }
+
+    fn check_annulus<D: DistanceMetric<f64, 3>>(
+        tree: &KdTree<f64, u32, 3, 32, u32>,
+        content: &[[f64; 3]],
+        query: &[f64; 3],
+        min_dist: f64,
+        max_dist: f64,
+    ) {
+        let mut expected: Vec<(f64, u32)> = content
+            .iter()
+            .enumerate()
+            .map(|(idx, point)| (D::dist(query, point), idx as u32))
+            .filter(|&(distance, _)| distance >= min_dist && distance <= max_dist)
+            .collect();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let mut result: Vec<(f64, u32)> = tree
+            .within_annulus::<D>(query, min_dist, max_dist)
+            .into_iter()
+            .map(|n| (n.distance, n.item))
+            .collect();
+        result.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        assert_eq!(result, expected);
+    }
+}
@@ -0,0 +1,165 @@
+use az::{Az, Cast};
+
+use crate::float::kdtree::{Axis, KdTree, LeafNode};
+use crate::traits::{is_stem_index, Content, Index};
+
+impl<A: Axis, T: Content, const B: usize, IDX: Index<T = IDX>> KdTree<A, T, 2, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    /// Finds all items whose points lie inside `polygon`, in no specified order.
+    ///
+    /// `polygon` is a list of vertices, in either winding order, and is implicitly closed
+    /// by an edge from the last vertex back to the first. It can be any simple polygon,
+    /// convex or not. Subtrees that lie outside of the polygon's bounding box are not
+    /// searched, and each remaining point is then tested with the even-odd rule. Points
+    /// that lie exactly on an edge of the polygon may or may not be included.
+    ///
+    /// Polygons with fewer than three vertices contain no points.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    ///     use kiddo::KdTree;
+    ///
+    ///     let mut tree: KdTree<f64, 2> = KdTree::new();
+    ///     tree.add(&[1.5, 1.0], 100);
+    ///     tree.add(&[2.5, 1.0], 101);
+    ///     tree.add(&[2.0, 4.0], 102);
+    ///
+    ///     let triangle = [[0.0, 0.0], [4.0, 0.0], [2.0, 2.0]];
+    ///     let mut within = tree.within_polygon(&triangle);
+    ///     within.sort();
+    ///
+    ///     assert_eq!(within, vec![100, 101]);
+    /// ```
+    pub fn within_polygon(&self, polygon: &[[A; 2]]) -> Vec<T> {
+        let mut results = Vec::new();
+
+        if polygon.len() < 3 {
+            return results;
+        }
+
+        let mut min = polygon[0];
+        let mut max = polygon[0];
+        for vertex in polygon {
+            for dim in 0..2 {
+                min[dim] = min[dim].min(vertex[dim]);
+                max[dim] = max[dim].max(vertex[dim]);
+            }
+        }
+
+        self.within_polygon_recurse(polygon, &min, &max, self.root_index, 0, &mut results);
+
+        results
+    }
+
+    fn within_polygon_recurse(
+        &self,
+        polygon: &[[A; 2]],
+        min: &[A; 2],
+        max: &[A; 2],
+        curr_node_idx: IDX,
+        split_dim: usize,
+        results: &mut Vec<T>,
+    ) {
+        if is_stem_index(curr_node_idx) {
+            let node = &self.stems[curr_node_idx.az::<usize>()];
+            let next_split_dim = (split_dim + 1) % 2;
+
+            // `<=` rather than `<`, as a leaf of points that all share the split value can
+            // be chained to the left of it
+            if min[split_dim] <= node.split_val {
+                self.within_polygon_recurse(polygon, min, max, node.left, next_split_dim, results);
+            }
+            if max[split_dim] >= node.split_val {
+                self.within_polygon_recurse(polygon, min, max, node.right, next_split_dim, results);
+            }
+        } else {
+            let leaf_node: &LeafNode<A, T, 2, B, IDX> =
+                &self.leaves[(curr_node_idx - IDX::leaf_offset()).az::<usize>()];
+
+            leaf_node
+                .content_points
+                .iter()
+                .zip(leaf_node.content_items.iter())
+                .take(leaf_node.size.az::<usize>())
+                .filter(|(point, _)| {
+                    (0..2).all(|dim| point[dim] >= min[dim] && point[dim] <= max[dim])
+                        && polygon_contains(polygon, point)
+                })
+                .for_each(|(_, &item)| results.push(item));
+        }
+    }
+}
+
+/// Whether `point` is inside `polygon`, by counting the edges that a ray cast from
+/// `point` in the direction of increasing x crosses
+fn polygon_contains<A: Axis>(polygon: &[[A; 2]], point: &[A; 2]) -> bool {
+    let [x, y] = *point;
+    let mut inside = false;
+
+    let mut prev = polygon[polygon.len() - 1];
+    for &curr in polygon {
+        if (curr[1] > y) != (prev[1] > y)
+            && x < (prev[0] - curr[0]) * (y - curr[1]) / (prev[1] - curr[1]) + curr[0]
+        {
+            inside = !inside;
+        }
+        prev = curr;
+    }
+
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::polygon_contains;
+    use crate::float::kdtree::KdTree;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn can_query_items_within_polygon() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(7);
+        let content_to_add: Vec<([f64; 2], u32)> =
+            (0..5_000).map(|idx| (rng.gen::<[f64; 2]>(), idx)).collect();
+
+        let mut tree: KdTree<f64, u32, 2, 32, u32> = KdTree::new();
+        content_to_add
+            .iter()
+            .for_each(|(point, item)| tree.add(point, *item));
+
+        // a concave, arrow-shaped polygon, and a convex one wound the other way
+        let polygons: [&[[f64; 2]]; 2] = [
+            &[[0.1, 0.1], [0.9, 0.5], [0.1, 0.9], [0.4, 0.5]],
+            &[[0.2, 0.3], [0.3, 0.8], [0.7, 0.6], [0.6, 0.2]],
+        ];
+
+        for polygon in polygons {
+            let mut expected: Vec<u32> = content_to_add
+                .iter()
+                .filter(|(point, _)| polygon_contains(polygon, point))
+                .map(|(_, item)| *item)
+                .collect();
+            expected.sort();
+            assert!(!expected.is_empty());
+
+            let mut result = tree.within_polygon(polygon);
+            result.sort();
+
+            assert_eq!(result, expected);
+        }
+
+        assert!(tree.within_polygon(&[[0.0, 0.0], [1.0, 1.0]]).is_empty());
+    }
+
+    #[test]
+    fn polygon_contains_handles_concave_polygons() {
+        let polygon = [[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [2.0, 1.0], [0.0, 4.0]];
+
+        assert!(polygon_contains(&polygon, &[1.0, 0.5]));
+        assert!(polygon_contains(&polygon, &[3.5, 3.0]));
+        assert!(!polygon_contains(&polygon, &[2.0, 3.0]));
+        assert!(!polygon_contains(&polygon, &[5.0, 1.0]));
+    }
+}
@@ -70,9 +70,9 @@ let tree = unsafe { rkyv::archived_root::<KdTree<f64, 3>>(&mmap) };"
 
 #[cfg(test)]
 mod tests {
-    use crate::float::distance::SquaredEuclidean;
+    use crate::float::distance::{Chebyshev, SquaredEuclidean, WeightedSquaredEuclidean};
     use crate::float::kdtree::{Axis, KdTree};
-    use crate::traits::DistanceMetric;
+    use crate::traits::{DistanceMetric, StatefulDistanceMetric};
     use rand::Rng;
     use std::cmp::Ordering;
 
@@ -175,6 +175,177 @@ mod tests {
         }
     }
 
+    #[test]
+    fn within_unsorted_visit_visits_the_same_items_as_within_unsorted() {
+        const TREE_SIZE: usize = 10_000;
+        const RADIUS: f32 = 0.2;
+
+        let content_to_add: Vec<([f32; 4], u32)> = (0..TREE_SIZE)
+            .map(|_| rand::random::<([f32; 4], u32)>())
+            .collect();
+
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        for _ in 0..100 {
+            let query_point = rand::random::<[f32; 4]>();
+            let expected = linear_search(&content_to_add, &query_point, RADIUS);
+
+            let mut visited = vec![];
+            tree.within_unsorted_visit::<SquaredEuclidean, _>(
+                &query_point,
+                RADIUS,
+                |item, dist| visited.push((dist, item)),
+            );
+
+            stabilize_sort(&mut visited);
+            assert_eq!(visited, expected);
+        }
+    }
+
+    #[test]
+    fn any_within_matches_whether_within_unsorted_is_empty() {
+        const TREE_SIZE: usize = 10_000;
+
+        let content_to_add: Vec<([f32; 4], u32)> = (0..TREE_SIZE)
+            .map(|_| rand::random::<([f32; 4], u32)>())
+            .collect();
+
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        for radius in [0.001, 0.01, 0.2] {
+            for _ in 0..100 {
+                let query_point = rand::random::<[f32; 4]>();
+                let expected = !linear_search(&content_to_add, &query_point, radius).is_empty();
+
+                assert_eq!(
+                    tree.any_within::<SquaredEuclidean>(&query_point, radius),
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn can_query_items_unsorted_within_radius_with_weighted_metric() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+        const RADIUS: f32 = 0.2;
+
+        let metric = WeightedSquaredEuclidean::new([0.5f32, 2f32, 8f32, 0.1f32]);
+
+        let content_to_add: Vec<([f32; 4], u32)> = (0..TREE_SIZE)
+            .map(|_| rand::random::<([f32; 4], u32)>())
+            .collect();
+
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        let query_points: Vec<[f32; 4]> = (0..NUM_QUERIES)
+            .map(|_| rand::random::<[f32; 4]>())
+            .collect();
+
+        for query_point in query_points {
+            let mut expected: Vec<_> = content_to_add
+                .iter()
+                .map(|&(p, item)| (metric.dist(&query_point, &p), item))
+                .filter(|&(dist, _)| dist < RADIUS)
+                .collect();
+            stabilize_sort(&mut expected);
+
+            let mut result: Vec<_> = tree
+                .within_unsorted_with_metric(&metric, &query_point, RADIUS)
+                .into_iter()
+                .map(|n| (n.distance, n.item))
+                .collect();
+            stabilize_sort(&mut result);
+
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    fn can_query_items_unsorted_within_radius_with_chebyshev() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+        const RADIUS: f32 = 0.1;
+
+        let content_to_add: Vec<([f32; 4], u32)> = (0..TREE_SIZE)
+            .map(|_| rand::random::<([f32; 4], u32)>())
+            .collect();
+
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        let query_points: Vec<[f32; 4]> = (0..NUM_QUERIES)
+            .map(|_| rand::random::<[f32; 4]>())
+            .collect();
+
+        for query_point in query_points {
+            let mut expected: Vec<_> = content_to_add
+                .iter()
+                .map(|&(p, item)| (Chebyshev::dist(&query_point, &p), item))
+                .filter(|&(dist, _)| dist < RADIUS)
+                .collect();
+            stabilize_sort(&mut expected);
+
+            let mut result: Vec<_> = tree
+                .within_unsorted::<Chebyshev>(&query_point, RADIUS)
+                .into_iter()
+                .map(|n| (n.distance, n.item))
+                .collect();
+            stabilize_sort(&mut result);
+
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_within_unsorted_matches_within_unsorted() {
+        const TREE_SIZE: usize = 50_000;
+        const NUM_QUERIES: usize = 20;
+        const RADIUS: f32 = 0.3;
+
+        let content_to_add: Vec<([f32; 4], u32)> = (0..TREE_SIZE)
+            .map(|_| rand::random::<([f32; 4], u32)>())
+            .collect();
+
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::with_capacity(TREE_SIZE);
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        for _ in 0..NUM_QUERIES {
+            let query_point = rand::random::<[f32; 4]>();
+
+            let mut expected: Vec<_> = tree
+                .within_unsorted::<SquaredEuclidean>(&query_point, RADIUS)
+                .into_iter()
+                .map(|n| (n.distance, n.item))
+                .collect();
+            stabilize_sort(&mut expected);
+
+            let mut result: Vec<_> = tree
+                .par_within_unsorted::<SquaredEuclidean>(&query_point, RADIUS)
+                .into_iter()
+                .map(|n| (n.distance, n.item))
+                .collect();
+            stabilize_sort(&mut result);
+
+            assert_eq!(result, expected);
+        }
+    }
+
     fn linear_search<A: Axis, const K: usize>(
         content: &[([A; K], u32)],
         query_point: &[A; K],
@@ -1,5 +1,4 @@
 use az::{Az, Cast};
-use generator::{done, Gn, Scope};
 use std::ops::Rem;
 
 use crate::float::kdtree::{Axis, KdTree};
@@ -173,6 +172,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn yields_lazily_in_the_same_order_as_within_unsorted() {
+        let content_to_add: Vec<([f32; 4], u32)> = (0..10_000)
+            .map(|_| rand::random::<([f32; 4], u32)>())
+            .collect();
+
+        let mut tree: KdTree<AX, u32, 4, 32, u32> = KdTree::new();
+        content_to_add
+            .iter()
+            .for_each(|(point, content)| tree.add(point, *content));
+
+        let query_point = rand::random::<[f32; 4]>();
+        let expected = tree.within_unsorted::<Manhattan>(&query_point, 0.5);
+        assert!(expected.len() > 10);
+
+        let result: Vec<_> = tree
+            .within_unsorted_iter::<Manhattan>(&query_point, 0.5)
+            .collect();
+        assert_eq!(result, expected);
+
+        let first: Vec<_> = tree
+            .within_unsorted_iter::<Manhattan>(&query_point, 0.5)
+            .take(10)
+            .collect();
+        assert_eq!(first, expected[..10]);
+    }
+
     fn linear_search<A: Axis, const K: usize>(
         content: &[([A; K], u32)],
         query_point: &[A; K],
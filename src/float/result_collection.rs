@@ -1,11 +1,12 @@
+use crate::common::nearest_n_results::{KthDistance, NearestNResults, ResultBuffer, SortedArray};
 use crate::float::kdtree::Axis;
 use crate::nearest_neighbour::NearestNeighbour;
 use crate::traits::Content;
 use sorted_vec::SortedVec;
+use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 
 pub trait ResultCollection<A: Axis, T: Content> {
-    fn new_with_capacity(capacity: usize) -> Self;
     fn add(&mut self, entry: NearestNeighbour<A, T>);
     fn max_dist(&self) -> A;
     fn into_vec(self) -> Vec<NearestNeighbour<A, T>>;
@@ -13,9 +14,6 @@ pub trait ResultCollection<A: Axis, T: Content> {
 }
 
 impl<A: Axis, T: Content> ResultCollection<A, T> for BinaryHeap<NearestNeighbour<A, T>> {
-    fn new_with_capacity(capacity: usize) -> Self {
-        BinaryHeap::with_capacity(capacity)
-    }
     fn add(&mut self, entry: NearestNeighbour<A, T>) {
         let k = self.capacity();
         if self.len() < k {
@@ -43,10 +41,6 @@ impl<A: Axis, T: Content> ResultCollection<A, T> for BinaryHeap<NearestNeighbour
 }
 
 impl<A: Axis, T: Content> ResultCollection<A, T> for Vec<NearestNeighbour<A, T>> {
-    fn new_with_capacity(capacity: usize) -> Self {
-        Vec::with_capacity(capacity)
-    }
-
     fn add(&mut self, entry: NearestNeighbour<A, T>) {
         self.push(entry)
     }
@@ -66,10 +60,6 @@ impl<A: Axis, T: Content> ResultCollection<A, T> for Vec<NearestNeighbour<A, T>>
 }
 
 impl<A: Axis, T: Content> ResultCollection<A, T> for SortedVec<NearestNeighbour<A, T>> {
-    fn new_with_capacity(capacity: usize) -> Self {
-        SortedVec::with_capacity(capacity)
-    }
-
     fn add(&mut self, entry: NearestNeighbour<A, T>) {
         let len = self.len();
         if len < self.capacity() {
@@ -96,3 +86,210 @@ impl<A: Axis, T: Content> ResultCollection<A, T> for SortedVec<NearestNeighbour<
         self.into_vec()
     }
 }
+
+impl<A: Axis, T: Content, const N: usize> ResultCollection<A, T> for SortedArray<A, T, N> {
+    fn add(&mut self, entry: NearestNeighbour<A, T>) {
+        self.add_nearest(entry)
+    }
+
+    fn max_dist(&self) -> A {
+        SortedArray::max_dist(self).unwrap_or(A::infinity())
+    }
+
+    fn into_vec(self) -> Vec<NearestNeighbour<A, T>> {
+        self.as_slice().to_vec()
+    }
+
+    fn into_sorted_vec(self) -> Vec<NearestNeighbour<A, T>> {
+        self.as_slice().to_vec()
+    }
+}
+
+impl<A: Axis, T: Content> ResultCollection<A, T> for ResultBuffer<A, T> {
+    fn add(&mut self, entry: NearestNeighbour<A, T>) {
+        self.add_nearest(entry)
+    }
+
+    fn max_dist(&self) -> A {
+        ResultBuffer::max_dist(self).unwrap_or(A::infinity())
+    }
+
+    fn into_vec(self) -> Vec<NearestNeighbour<A, T>> {
+        ResultBuffer::into_vec(self)
+    }
+
+    fn into_sorted_vec(self) -> Vec<NearestNeighbour<A, T>> {
+        ResultBuffer::into_vec(self)
+    }
+}
+
+impl<A: Axis, T: Content> ResultCollection<A, T> for KthDistance<A> {
+    fn add(&mut self, entry: NearestNeighbour<A, T>) {
+        self.push(entry.distance)
+    }
+
+    fn max_dist(&self) -> A {
+        self.kth_dist().unwrap_or(A::infinity())
+    }
+
+    fn into_vec(self) -> Vec<NearestNeighbour<A, T>> {
+        Vec::new()
+    }
+
+    fn into_sorted_vec(self) -> Vec<NearestNeighbour<A, T>> {
+        Vec::new()
+    }
+}
+
+/// Orders entries by distance, and entries at the same distance by item
+fn cmp_breaking_ties_by_item<A: Axis, T: Content>(
+    a: &NearestNeighbour<A, T>,
+    b: &NearestNeighbour<A, T>,
+) -> Ordering {
+    a.cmp(b).then(a.item.cmp(&b.item))
+}
+
+/// Sorts `results` by distance, and results at the same distance by item.
+pub(crate) fn sort_breaking_ties_by_item<A: Axis, T: Content>(
+    results: &mut [NearestNeighbour<A, T>],
+) {
+    results.sort_unstable_by(cmp_breaking_ties_by_item);
+}
+
+/// Keeps the `max_qty` nearest entries, with entries at the same distance ordered by
+/// item, so that which entries are kept and the order in which they are returned do
+/// not depend on the order in which the tree is searched.
+///
+/// Queries using this must not prune parts of the tree that are exactly
+/// [`max_dist`](ResultCollection::max_dist) away, as they may hold entries that tie
+/// with the furthest one kept but have a lower item.
+pub(crate) struct TieBrokenResults<A, T> {
+    entries: Vec<NearestNeighbour<A, T>>,
+    max_qty: usize,
+}
+
+impl<A, T> TieBrokenResults<A, T> {
+    pub(crate) fn new(max_qty: usize) -> Self {
+        TieBrokenResults {
+            entries: Vec::with_capacity(max_qty.min(1024)),
+            max_qty,
+        }
+    }
+}
+
+impl<A: Axis, T: Content> ResultCollection<A, T> for TieBrokenResults<A, T> {
+    fn add(&mut self, entry: NearestNeighbour<A, T>) {
+        if self.entries.len() == self.max_qty {
+            match self.entries.last() {
+                Some(last) if cmp_breaking_ties_by_item(&entry, last) == Ordering::Less => {
+                    self.entries.pop();
+                }
+                _ => return,
+            }
+        }
+
+        let idx = self
+            .entries
+            .partition_point(|e| cmp_breaking_ties_by_item(e, &entry) == Ordering::Less);
+        self.entries.insert(idx, entry);
+    }
+
+    fn max_dist(&self) -> A {
+        if self.entries.len() < self.max_qty {
+            A::infinity()
+        } else {
+            self.entries.last().map_or(A::infinity(), |n| n.distance)
+        }
+    }
+
+    fn into_vec(self) -> Vec<NearestNeighbour<A, T>> {
+        self.entries
+    }
+
+    fn into_sorted_vec(self) -> Vec<NearestNeighbour<A, T>> {
+        self.entries
+    }
+}
+
+/// Wraps another [`ResultCollection`], discarding any entries whose item does
+/// not satisfy `filter` so that they never take up space in it.
+pub(crate) struct FilteredResults<'f, R, F> {
+    pub(crate) results: R,
+    pub(crate) filter: &'f F,
+}
+
+impl<A: Axis, T: Content, R: ResultCollection<A, T>, F: Fn(T) -> bool> ResultCollection<A, T>
+    for FilteredResults<'_, R, F>
+{
+    fn add(&mut self, entry: NearestNeighbour<A, T>) {
+        if (self.filter)(entry.item) {
+            self.results.add(entry)
+        }
+    }
+
+    fn max_dist(&self) -> A {
+        self.results.max_dist()
+    }
+
+    fn into_vec(self) -> Vec<NearestNeighbour<A, T>> {
+        self.results.into_vec()
+    }
+
+    fn into_sorted_vec(self) -> Vec<NearestNeighbour<A, T>> {
+        self.results.into_sorted_vec()
+    }
+}
+
+/// Passes each entry to `visitor` as soon as it is found, rather than storing it, for
+/// queries whose results are aggregated in place instead of being collected.
+pub(crate) struct VisitedResults<'v, V> {
+    pub(crate) visitor: &'v mut V,
+}
+
+impl<A: Axis, T: Content, V: FnMut(T, A)> ResultCollection<A, T> for VisitedResults<'_, V> {
+    fn add(&mut self, entry: NearestNeighbour<A, T>) {
+        (self.visitor)(entry.item, entry.distance)
+    }
+
+    fn max_dist(&self) -> A {
+        A::infinity()
+    }
+
+    fn into_vec(self) -> Vec<NearestNeighbour<A, T>> {
+        Vec::new()
+    }
+
+    fn into_sorted_vec(self) -> Vec<NearestNeighbour<A, T>> {
+        Vec::new()
+    }
+}
+
+/// Records only whether any entry has been found, after which `max_dist` prunes every
+/// remaining branch of the traversal, for queries that only need to know whether there
+/// is a match at all.
+#[derive(Default)]
+pub(crate) struct AnyResult {
+    pub(crate) found: bool,
+}
+
+impl<A: Axis, T: Content> ResultCollection<A, T> for AnyResult {
+    fn add(&mut self, _entry: NearestNeighbour<A, T>) {
+        self.found = true;
+    }
+
+    fn max_dist(&self) -> A {
+        if self.found {
+            A::neg_infinity()
+        } else {
+            A::infinity()
+        }
+    }
+
+    fn into_vec(self) -> Vec<NearestNeighbour<A, T>> {
+        Vec::new()
+    }
+
+    fn into_sorted_vec(self) -> Vec<NearestNeighbour<A, T>> {
+        Vec::new()
+    }
+}
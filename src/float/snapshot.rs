@@ -0,0 +1,472 @@
+//! A persistent version of the float [`KdTree`] whose snapshots share their leaves.
+//!
+//! Useful when queries need to carry on against a consistent view of the tree whilst
+//! it is being updated, for example by handing a snapshot to reader threads and
+//! publishing a new one after each batch of updates.
+
+use std::collections::BinaryHeap;
+use std::ops::Rem;
+use std::sync::Arc;
+
+use az::{Az, Cast};
+
+use crate::float::construction::for_each_leaf_idx_for;
+use crate::float::kdtree::{Axis, KdTree, LeafNode, StemNode};
+use crate::iter::IterableTreeData;
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{is_stem_index, Content, DistanceMetric, Index};
+use crate::{
+    generate_nearest_n, generate_nearest_one, generate_tree_stats, generate_within,
+    generate_within_unsorted,
+};
+
+/// A float k-d tree with copy-on-write structural sharing at leaf granularity.
+///
+/// Taking a [`snapshot`](KdTreeSnapshot::snapshot) (or cloning) is cheap, as the
+/// snapshot shares every leaf and stem with the tree that it was taken from. A leaf
+/// is only copied when it is first modified by [`add`](KdTreeSnapshot::add) or
+/// [`remove`](KdTreeSnapshot::remove) whilst it is still shared, and the stems are
+/// only copied when a leaf needs to be split. Updates to a tree are therefore never
+/// seen by snapshots that were taken from it beforehand, and vice versa.
+///
+/// A snapshot of a [`KdTree`] can be taken with [`KdTree::snapshot`], which copies
+/// the tree once, or by converting it with `From`, which moves its leaves instead.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::float::snapshot::KdTreeSnapshot;
+/// use kiddo::{KdTree, SquaredEuclidean};
+///
+/// let mut tree: KdTree<f64, 2> = KdTree::new();
+/// tree.add(&[1.0, 2.0], 100);
+///
+/// let mut tree: KdTreeSnapshot<f64, u64, 2, 32, u32> = tree.into();
+/// let before = tree.snapshot();
+///
+/// tree.add(&[1.5, 2.5], 101);
+///
+/// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[1.5, 2.5]).item, 101);
+/// assert_eq!(before.nearest_one::<SquaredEuclidean>(&[1.5, 2.5]).item, 100);
+/// assert_eq!((tree.size(), before.size()), (2, 1));
+/// ```
+#[derive(Clone, Debug)]
+pub struct KdTreeSnapshot<A: Copy + Default, T: Copy + Default, const K: usize, const B: usize, IDX>
+{
+    leaves: Arc<Vec<Arc<LeafNode<A, T, K, B, IDX>>>>,
+    stems: Arc<Vec<StemNode<A, K, IDX>>>,
+    root_index: IDX,
+    size: T,
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX> + Send>
+    KdTreeSnapshot<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    /// Creates a new empty tree.
+    pub fn new() -> Self {
+        KdTree::new().into()
+    }
+
+    /// Returns a snapshot of the tree as it is now, sharing all of its leaves and stems.
+    ///
+    /// Subsequent modifications of either the tree or the snapshot are not visible
+    /// to the other.
+    #[inline]
+    pub fn snapshot(&self) -> Self {
+        self.clone()
+    }
+
+    /// Returns the current number of elements stored in the tree
+    #[inline]
+    pub fn size(&self) -> T {
+        self.size
+    }
+
+    /// Returns the number of leaves that are shared with at least one other snapshot.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::float::snapshot::KdTreeSnapshot;
+    ///
+    /// let mut tree: KdTreeSnapshot<f64, u64, 2, 32, u32> = KdTreeSnapshot::new();
+    /// tree.add(&[1.0, 2.0], 100);
+    /// assert_eq!(tree.shared_leaf_count(), 0);
+    ///
+    /// let snapshot = tree.snapshot();
+    /// assert_eq!(tree.shared_leaf_count(), 1);
+    ///
+    /// tree.add(&[1.5, 2.5], 101);
+    /// assert_eq!(tree.shared_leaf_count(), 0);
+    /// # drop(snapshot);
+    /// ```
+    pub fn shared_leaf_count(&self) -> usize {
+        let leaves_shared = Arc::strong_count(&self.leaves) > 1;
+
+        self.leaves
+            .iter()
+            .filter(|leaf| leaves_shared || Arc::strong_count(leaf) > 1)
+            .count()
+    }
+
+    /// Adds an item to the tree, copying the leaf that it goes into if that leaf is
+    /// shared with a snapshot.
+    ///
+    /// The first argument specifies co-ordinates of the point where the item is located.
+    /// The second argument is an integer identifier / index for the item being stored.
+    pub fn add(&mut self, query: &[A; K], item: T) {
+        debug_assert!(
+            !query.iter().any(|val| val.is_nan()),
+            "point {query:?} has a NaN coordinate"
+        );
+
+        let mut stem_idx = self.root_index;
+        let mut split_dim = 0;
+        let mut parent_idx = <IDX as Index>::max();
+        let mut is_left_child = false;
+
+        while is_stem_index(stem_idx) {
+            parent_idx = stem_idx;
+            let stem_node = &self.stems[stem_idx.az::<usize>()];
+
+            is_left_child = query[split_dim] < stem_node.split_val;
+            stem_idx = if is_left_child {
+                stem_node.left
+            } else {
+                stem_node.right
+            };
+
+            split_dim = (split_dim + 1).rem(K);
+        }
+
+        let leaves = Arc::make_mut(&mut self.leaves);
+        let mut leaf_idx = (stem_idx - IDX::leaf_offset()).az::<usize>();
+
+        if leaves[leaf_idx].size == B.az::<IDX>() {
+            let (split_val, right) = unsafe {
+                Arc::make_mut(&mut leaves[leaf_idx]).split_off(split_dim, query[split_dim])
+            };
+            leaves.push(Arc::new(right));
+
+            let stems = Arc::make_mut(&mut self.stems);
+            stems.push(StemNode {
+                left: leaf_idx.az::<IDX>() + IDX::leaf_offset(),
+                right: (leaves.len() - 1).az::<IDX>() + IDX::leaf_offset(),
+                split_val,
+            });
+            let new_stem_index = (stems.len() - 1).az::<IDX>();
+
+            if parent_idx == <IDX as Index>::max() {
+                self.root_index = new_stem_index;
+            } else if is_left_child {
+                stems[parent_idx.az::<usize>()].left = new_stem_index;
+            } else {
+                stems[parent_idx.az::<usize>()].right = new_stem_index;
+            }
+
+            if query[split_dim] >= split_val {
+                leaf_idx = leaves.len() - 1;
+            }
+        }
+
+        let leaf_node = Arc::make_mut(&mut leaves[leaf_idx]);
+        let leaf_size = leaf_node.size.az::<usize>();
+        leaf_node.content_points[leaf_size] = *query;
+        leaf_node.content_items[leaf_size] = item;
+        leaf_node.size = leaf_node.size + IDX::one();
+
+        self.size = self.size + T::one();
+    }
+
+    /// Removes an item from the tree, copying the leaf that it is in if that leaf is
+    /// shared with a snapshot.
+    ///
+    /// Returns the number of entries removed. Leaves that do not contain the item are
+    /// never copied.
+    pub fn remove(&mut self, query: &[A; K], item: T) -> usize {
+        let is_match = |point: &[A; K], stored: T| point == query && stored == item;
+
+        let mut matching_leaf_idxs = vec![];
+        for_each_leaf_idx_for(&self.stems, self.root_index, 0, query, &mut |leaf_idx| {
+            let leaf_node = &self.leaves[leaf_idx];
+            let size = leaf_node.size.az::<usize>();
+
+            if leaf_node.content_points[..size]
+                .iter()
+                .zip(&leaf_node.content_items[..size])
+                .any(|(point, &stored)| is_match(point, stored))
+            {
+                matching_leaf_idxs.push(leaf_idx);
+            }
+        });
+
+        let mut removed: usize = 0;
+        for leaf_idx in matching_leaf_idxs {
+            let leaf_node = Arc::make_mut(&mut Arc::make_mut(&mut self.leaves)[leaf_idx]);
+            removed += leaf_node.remove_where(is_match);
+        }
+
+        for _ in 0..removed {
+            self.size -= T::one();
+        }
+
+        removed
+    }
+
+    /// Copies the contents of the tree into a new [`KdTree`], which shares nothing
+    /// with this tree or its snapshots.
+    pub fn to_kdtree(&self) -> KdTree<A, T, K, B, IDX> {
+        KdTree {
+            leaves: self.leaves.iter().map(|leaf| (**leaf).clone()).collect(),
+            stems: (*self.stems).clone(),
+            root_index: self.root_index,
+            size: self.size,
+            deferred_points: Vec::new(),
+            deferred_items: Vec::new(),
+        }
+    }
+
+    generate_tree_stats!();
+
+    generate_nearest_one!(
+        LeafNode,
+        ("Finds the nearest element to `query`, using the specified
+distance metric function.
+
+# Examples
+
+```rust
+    use kiddo::float::snapshot::KdTreeSnapshot;
+    use kiddo::SquaredEuclidean;
+
+    let mut tree: KdTreeSnapshot<f64, u64, 3, 32, u32> = KdTreeSnapshot::new();
+    tree.add(&[1.0, 2.0, 5.0], 100);
+    tree.add(&[2.0, 3.0, 6.0], 101);
+
+    let nearest = tree.nearest_one::<SquaredEuclidean>(&[1.0, 2.0, 5.1]);
+
+    assert!((nearest.distance - 0.01f64).abs() < f64::EPSILON);
+    assert_eq!(nearest.item, 100);
+```")
+    );
+
+    generate_nearest_n!(
+        ("Finds the nearest `qty` elements to `query`, using the specified
+distance metric function.
+
+# Examples
+
+```rust
+    use kiddo::float::snapshot::KdTreeSnapshot;
+    use kiddo::SquaredEuclidean;
+
+    let mut tree: KdTreeSnapshot<f64, u64, 3, 32, u32> = KdTreeSnapshot::new();
+    tree.add(&[1.0, 2.0, 5.0], 100);
+    tree.add(&[2.0, 3.0, 6.0], 101);
+
+    let nearest: Vec<_> = tree.nearest_n::<SquaredEuclidean>(&[1.0, 2.0, 5.1], 1);
+
+    assert_eq!(nearest.len(), 1);
+    assert_eq!(nearest[0].item, 100);
+```")
+    );
+
+    generate_within_unsorted!(
+        ("Finds all elements within `dist` of `query`, using the specified
+distance metric function.
+
+Results are returned in arbitrary order. Faster than `within`.
+
+# Examples
+
+```rust
+    use kiddo::float::snapshot::KdTreeSnapshot;
+    use kiddo::SquaredEuclidean;
+
+    let mut tree: KdTreeSnapshot<f64, u64, 3, 32, u32> = KdTreeSnapshot::new();
+    tree.add(&[1.0, 2.0, 5.0], 100);
+    tree.add(&[2.0, 3.0, 6.0], 101);
+
+    let within = tree.within_unsorted::<SquaredEuclidean>(&[1.0, 2.0, 5.0], 10f64);
+
+    assert_eq!(within.len(), 2);
+```")
+    );
+
+    generate_within!(
+        ("Finds all elements within `dist` of `query`, using the specified
+distance metric function.
+
+Results are returned sorted nearest-first.
+
+# Examples
+
+```rust
+    use kiddo::float::snapshot::KdTreeSnapshot;
+    use kiddo::SquaredEuclidean;
+
+    let mut tree: KdTreeSnapshot<f64, u64, 3, 32, u32> = KdTreeSnapshot::new();
+    tree.add(&[1.0, 2.0, 5.0], 100);
+    tree.add(&[2.0, 3.0, 6.0], 101);
+
+    let within = tree.within::<SquaredEuclidean>(&[1.0, 2.0, 5.0], 10f64);
+
+    assert_eq!(within.len(), 2);
+    assert_eq!(within[0].item, 100);
+```")
+    );
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX> + Send> Default
+    for KdTreeSnapshot<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
+    From<KdTree<A, T, K, B, IDX>> for KdTreeSnapshot<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    /// Converts a [`KdTree`] into a persistent tree, moving its leaves rather than
+    /// copying them. Any items still waiting for
+    /// [`finalize_splits`](KdTree::finalize_splits) are added as normal items.
+    fn from(mut tree: KdTree<A, T, K, B, IDX>) -> Self {
+        tree.finalize_splits();
+
+        KdTreeSnapshot {
+            leaves: Arc::new(tree.leaves.into_iter().map(Arc::new).collect()),
+            stems: Arc::new(tree.stems),
+            root_index: tree.root_index,
+            size: tree.size,
+        }
+    }
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
+    KdTree<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+{
+    /// Returns a [`KdTreeSnapshot`] of the tree, whose own snapshots share their leaves
+    /// with each other.
+    ///
+    /// The tree is copied once by this call; the snapshots taken from the returned
+    /// tree are then cheap. See [`KdTreeSnapshot`] for details.
+    pub fn snapshot(&self) -> KdTreeSnapshot<A, T, K, B, IDX> {
+        self.clone().into()
+    }
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
+    IterableTreeData<A, T, K> for KdTreeSnapshot<A, T, K, B, IDX>
+{
+    fn get_leaf_data(&self, idx: usize, out: &mut Vec<(T, [A; K])>) -> Option<usize> {
+        let leaf = self.leaves.get(idx)?;
+        let max = leaf.size.cast();
+        out.extend(
+            leaf.content_items
+                .iter()
+                .cloned()
+                .zip(leaf.content_points.iter().cloned())
+                .take(max),
+        );
+        Some(max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KdTreeSnapshot;
+    use crate::float::distance::SquaredEuclidean;
+    use crate::float::kdtree::KdTree;
+    use rand::Rng;
+
+    #[test]
+    fn snapshots_are_unaffected_by_later_updates() {
+        let mut rng = rand::thread_rng();
+        let points: Vec<[f64; 3]> = (0..2000).map(|_| rng.gen::<[f64; 3]>()).collect();
+
+        let mut kdtree: KdTree<f64, u32, 3, 32, u32> = KdTree::new();
+        let mut tree: KdTreeSnapshot<f64, u32, 3, 32, u32> = KdTreeSnapshot::new();
+        for (idx, point) in points.iter().enumerate().take(1000) {
+            kdtree.add(point, idx as u32);
+            tree.add(point, idx as u32);
+        }
+
+        let snapshot = tree.snapshot();
+        let expected_before = kdtree.clone();
+
+        for (idx, point) in points.iter().enumerate().skip(1000) {
+            kdtree.add(point, idx as u32);
+            tree.add(point, idx as u32);
+        }
+        for (idx, point) in points.iter().enumerate().step_by(3) {
+            assert_eq!(kdtree.remove(point, idx as u32), 1);
+            assert_eq!(tree.remove(point, idx as u32), 1);
+        }
+
+        assert_eq!(tree.size(), kdtree.size());
+        assert_eq!(snapshot.size(), 1000);
+
+        for _ in 0..100 {
+            let query = rng.gen::<[f64; 3]>();
+
+            assert_eq!(
+                tree.nearest_n::<SquaredEuclidean>(&query, 5),
+                kdtree.nearest_n::<SquaredEuclidean>(&query, 5)
+            );
+            assert_eq!(
+                tree.within::<SquaredEuclidean>(&query, 0.01),
+                kdtree.within::<SquaredEuclidean>(&query, 0.01)
+            );
+            assert_eq!(
+                snapshot.nearest_n::<SquaredEuclidean>(&query, 5),
+                expected_before.nearest_n::<SquaredEuclidean>(&query, 5)
+            );
+            assert_eq!(
+                snapshot.nearest_one::<SquaredEuclidean>(&query),
+                expected_before.nearest_one::<SquaredEuclidean>(&query)
+            );
+        }
+
+        let mut round_tripped = tree.to_kdtree();
+        let mut reconverted = KdTreeSnapshot::from(round_tripped.clone());
+        round_tripped.add(&[2.0, 2.0, 2.0], 9999);
+        reconverted.add(&[2.0, 2.0, 2.0], 9999);
+        assert_eq!(
+            round_tripped
+                .nearest_one::<SquaredEuclidean>(&[2.0; 3])
+                .item,
+            reconverted.nearest_one::<SquaredEuclidean>(&[2.0; 3]).item
+        );
+        assert_ne!(tree.nearest_one::<SquaredEuclidean>(&[2.0; 3]).item, 9999);
+    }
+
+    #[test]
+    fn can_add_and_remove_more_identical_points_than_fit_in_a_leaf() {
+        let mut tree: KdTreeSnapshot<f64, u32, 2, 4, u32> = KdTreeSnapshot::new();
+        for item in 0..20 {
+            tree.add(&[1.0, 2.0], item);
+        }
+        let snapshot = tree.snapshot();
+
+        for item in 0..20 {
+            assert_eq!(tree.remove(&[1.0, 2.0], item), 1);
+        }
+
+        assert_eq!(tree.size(), 0);
+        assert_eq!(
+            snapshot
+                .within_unsorted::<SquaredEuclidean>(&[1.0, 2.0], 0.01)
+                .len(),
+            20
+        );
+    }
+}
@@ -0,0 +1,150 @@
+//! Mixed-precision leaf-scanning for `f16` trees.
+//!
+//! Points are stored as `f16`, halving the memory used by the leaves, but the per-axis
+//! distances are widened to `f32` before being summed, and all comparisons made whilst
+//! scanning a leaf (including against the best distance found so far) are performed in
+//! `f32`. Summing in `f16` rounds away small differences
+//! between nearby points, producing frequent spurious ties and, in higher dimensions,
+//! incorrect rankings. Distances are only narrowed back to `f16` when they are written
+//! into a result.
+use az::Cast;
+use half::f16;
+use std::collections::BinaryHeap;
+
+use super::fallback::{push_best, update_nearest_dist_autovec};
+use super::leaf_slice::{LeafSliceFloat, LeafSliceFloatChunk};
+use crate::float::result_collection::ResultCollection;
+use crate::traits::{accumulate_dist, Content, DistanceMetric};
+use crate::{BestNeighbour, NearestNeighbour};
+
+impl<T: Content> LeafSliceFloat<T> for f16
+where
+    T: Content,
+    usize: Cast<T>,
+{
+    type Acc = f32;
+
+    #[inline]
+    fn widen(self) -> f32 {
+        self.to_f32()
+    }
+
+    #[inline]
+    fn narrow(acc: f32) -> f16 {
+        f16::from_f32(acc)
+    }
+
+    #[inline]
+    fn update_nearest_dist<const C: usize>(
+        acc: [f32; C],
+        items: &[T; C],
+        best_dist: &mut f32,
+        best_item: &mut T,
+    ) {
+        update_nearest_dist_autovec(&acc, items, best_dist, best_item)
+    }
+
+    #[inline]
+    fn update_nearest_dists_within<R, const C: usize>(
+        acc: [f32; C],
+        items: &[T; C],
+        radius: f16,
+        results: &mut R,
+    ) where
+        R: ResultCollection<f16, T>,
+    {
+        let radius = radius.to_f32();
+
+        acc.iter()
+            .zip(items.iter())
+            .filter(|(&distance, _)| distance <= radius)
+            .for_each(|(&distance, &item)| {
+                results.add(NearestNeighbour {
+                    distance: f16::from_f32(distance),
+                    item,
+                });
+            });
+    }
+
+    #[inline]
+    fn update_best_dists_within<const C: usize>(
+        acc: [f32; C],
+        items: &[T; C],
+        radius: f16,
+        max_qty: usize,
+        results: &mut BinaryHeap<BestNeighbour<f16, T>>,
+    ) {
+        let radius = radius.to_f32();
+
+        acc.iter()
+            .zip(items.iter())
+            .filter(|(&distance, _)| distance <= radius)
+            .for_each(|(&distance, &item)| {
+                push_best(results, max_qty, f16::from_f32(distance), item)
+            });
+    }
+}
+
+impl<T: Content, const K: usize> LeafSliceFloatChunk<T, K> for f16
+where
+    T: Content,
+    usize: Cast<T>,
+{
+    #[inline]
+    fn dists_for_chunk<D, const C: usize>(chunk: [&[Self; C]; K], query: &[Self; K]) -> [f32; C]
+    where
+        D: DistanceMetric<Self, K>,
+        Self: Sized,
+    {
+        let mut acc = [0f32; C];
+        (0..K).step_by(1).for_each(|dim| {
+            let qd = [query[dim]; C];
+
+            (0..C).step_by(1).for_each(|idx| {
+                acc[idx] = accumulate_dist(
+                    acc[idx],
+                    D::dist1(chunk[dim][idx], qd[idx]).to_f32(),
+                    D::MAX_OF_AXES,
+                    |a, b| a + b,
+                );
+            });
+        });
+
+        acc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use half::f16;
+
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use crate::SquaredEuclidean;
+
+    #[test]
+    fn f16_leaf_distances_are_accumulated_in_f32() {
+        let small = f16::from_f32(1.0 / 64.0);
+
+        // per-axis squared distances from the origin of 1 and 2^-10. Exact in f16.
+        let nearer = [
+            f16::ONE,
+            f16::from_f32(1.0 / 32.0),
+            f16::ZERO,
+            f16::ZERO,
+            f16::ZERO,
+            f16::ZERO,
+        ];
+
+        // per-axis squared distances of 1 and five lots of 2^-12. Summed in f16, each
+        // 2^-12 is rounded away, making this point appear to be nearer than `nearer`.
+        let further = [f16::ONE, small, small, small, small, small];
+
+        let tree: ImmutableKdTree<f16, u32, 6, 32> =
+            ImmutableKdTree::new_from_slice(&[further, nearer]);
+
+        let result = tree.nearest_one::<SquaredEuclidean>(&[f16::ZERO; 6]);
+
+        assert_eq!(result.item, 1);
+        assert_eq!(result.distance, f16::from_f32(1.0 + 1.0 / 1024.0));
+    }
+}
@@ -1,61 +1,170 @@
-use core::arch::x86_64::_mm256_testz_si256;
-use std::arch::x86_64::{
-    _mm256_add_epi32, _mm256_blendv_ps, _mm256_cmp_ps, _mm256_loadu_ps, _mm256_min_ps,
-    _mm256_set1_epi32, _mm256_set_epi32, _mm256_store_si256, _mm256_storeu_ps, _CMP_LT_OQ,
+//! AVX2 leaf-scanning kernels for `f32`, processing eight distances per instruction.
+//!
+//! Only compiled when the `simd` feature is enabled and AVX2 is available at compile time
+//! (e.g. via `RUSTFLAGS="-C target-cpu=native"`). Results are identical to those of the
+//! portable kernels in [`fallback`](super::fallback), including tie-breaking.
+use core::arch::x86_64::{
+    __m256i, _mm256_add_epi32, _mm256_blendv_ps, _mm256_castps_si256, _mm256_castsi256_ps,
+    _mm256_cmp_ps, _mm256_loadu_ps, _mm256_movemask_ps, _mm256_set1_epi32, _mm256_set1_ps,
+    _mm256_setr_epi32, _mm256_storeu_ps, _mm256_storeu_si256, _CMP_LE_OQ, _CMP_LT_OQ,
 };
 
-pub(crate) unsafe fn get_best_from_dists_f32_avx2<T: crate::types::Content, const B: usize>(
-    acc: &[f32; B],
-    items: &[T; B],
+use crate::traits::Content;
+
+const LANES: usize = 8;
+
+/// Finds the smallest of `dists`, and replaces `best_dist` / `best_item` with it if it
+/// is smaller than `best_dist`. If several dists are equally smallest, the first wins.
+///
+/// # Safety
+///
+/// The CPU must support AVX2, and `items` must be at least as long as `dists`.
+#[allow(unused_unsafe)]
+#[inline]
+pub(crate) unsafe fn update_nearest_dist_f32_avx2<T: Content>(
+    dists: &[f32],
+    items: &[T],
     best_dist: &mut f32,
     best_item: &mut T,
 ) {
-    let mut index_v = _mm256_set_epi32(0, 1, 2, 3, 4, 5, 6, 7);
-    let mut min_dist_indexes_v = _mm256_set1_epi32(-1);
-    let all_eights = _mm256_set1_epi32(8);
-    let mut min_dists = [*best_dist; 8];
-    let mut min_dists_v = _mm256_loadu_ps(std::ptr::addr_of!(min_dists[0]));
+    let chunks = dists.chunks_exact(LANES);
+    let remainder = chunks.remainder();
+
+    let mut lane_dists = [0f32; LANES];
+    let mut lane_indexes = [0i32; LANES];
+
+    unsafe {
+        let all_eights = _mm256_set1_epi32(LANES as i32);
+        let mut index_v = _mm256_setr_epi32(0, 1, 2, 3, 4, 5, 6, 7);
+        let mut min_dists_v = _mm256_set1_ps(*best_dist);
+        let mut min_indexes_v = _mm256_set1_epi32(-1);
 
-    let mut any_is_better = false;
-    for chunk in acc.as_chunks_unchecked::<8>().iter() {
-        let chunk_v = _mm256_loadu_ps(std::ptr::addr_of!(chunk[0]));
+        for chunk in chunks {
+            let chunk_v = _mm256_loadu_ps(chunk.as_ptr());
 
-        let is_better = _mm256_cmp_ps(chunk_v, min_dists_v, _CMP_LT_OQ);
+            // strictly less than, so that each lane keeps the first of any equal dists
+            let is_better = _mm256_cmp_ps::<_CMP_LT_OQ>(chunk_v, min_dists_v);
 
-        let these_better = _mm256_testz_si256(
-            std::mem::transmute(is_better),
-            std::mem::transmute(is_better),
-        );
+            min_dists_v = _mm256_blendv_ps(min_dists_v, chunk_v, is_better);
+            min_indexes_v = _mm256_castps_si256(_mm256_blendv_ps(
+                _mm256_castsi256_ps(min_indexes_v),
+                _mm256_castsi256_ps(index_v),
+                is_better,
+            ));
 
-        any_is_better |= these_better == 0;
+            index_v = _mm256_add_epi32(index_v, all_eights);
+        }
 
-        min_dists_v = _mm256_min_ps(min_dists_v, chunk_v);
+        _mm256_storeu_ps(lane_dists.as_mut_ptr(), min_dists_v);
+        _mm256_storeu_si256(lane_indexes.as_mut_ptr() as *mut __m256i, min_indexes_v);
+    }
 
-        min_dist_indexes_v = std::mem::transmute(_mm256_blendv_ps(
-            std::mem::transmute(min_dist_indexes_v),
-            std::mem::transmute(index_v),
-            is_better,
-        ));
+    // Reduce across lanes. Only lanes that found something better than `best_dist`
+    // have a non-negative index. Equal dists are resolved in favour of the lowest index.
+    let mut min_dist = *best_dist;
+    let mut min_idx = usize::MAX;
+    for (&dist, &idx) in lane_dists.iter().zip(lane_indexes.iter()) {
+        if idx >= 0 && (dist < min_dist || (dist == min_dist && (idx as usize) < min_idx)) {
+            min_dist = dist;
+            min_idx = idx as usize;
+        }
+    }
 
-        index_v = _mm256_add_epi32(index_v, all_eights);
+    let remainder_offset = dists.len() - remainder.len();
+    for (idx, &dist) in remainder.iter().enumerate() {
+        if dist < min_dist {
+            min_dist = dist;
+            min_idx = remainder_offset + idx;
+        }
     }
 
-    if !any_is_better {
-        return;
+    if min_idx != usize::MAX {
+        *best_dist = min_dist;
+        *best_item = items[min_idx];
     }
+}
+
+/// Calls `f` with the index of every one of `dists` that is within `radius`, in
+/// ascending order of index.
+///
+/// # Safety
+///
+/// The CPU must support AVX2.
+#[allow(unused_unsafe)]
+#[inline]
+pub(crate) unsafe fn for_each_dist_within_f32_avx2(
+    dists: &[f32],
+    radius: f32,
+    mut f: impl FnMut(usize),
+) {
+    let chunks = dists.chunks_exact(LANES);
+    let remainder = chunks.remainder();
+
+    unsafe {
+        let radius_v = _mm256_set1_ps(radius);
+
+        for (chunk_idx, chunk) in chunks.enumerate() {
+            let chunk_v = _mm256_loadu_ps(chunk.as_ptr());
+            let mut mask =
+                _mm256_movemask_ps(_mm256_cmp_ps::<_CMP_LE_OQ>(chunk_v, radius_v)) as u32;
+
+            // Most chunks visited by a query contain no results at all
+            while mask != 0 {
+                f(chunk_idx * LANES + mask.trailing_zeros() as usize);
+                mask &= mask - 1;
+            }
+        }
+    }
+
+    let remainder_offset = dists.len() - remainder.len();
+    for (idx, &dist) in remainder.iter().enumerate() {
+        if dist <= radius {
+            f(remainder_offset + idx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{for_each_dist_within_f32_avx2, update_nearest_dist_f32_avx2};
+    use crate::float_leaf_slice::fallback::update_nearest_dist_autovec;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn avx2_nearest_dist_matches_autovec() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(7);
+
+        for len in 1..70 {
+            // a coarse range, so that there are plenty of equal dists
+            let dists: Vec<f32> = (0..len).map(|_| rng.gen_range(0..20) as f32).collect();
+            let items: Vec<u32> = (0..len as u32).collect();
+
+            let mut expected_dist = 10f32;
+            let mut expected_item = u32::MAX;
+            update_nearest_dist_autovec(&dists, &items, &mut expected_dist, &mut expected_item);
+
+            let mut best_dist = 10f32;
+            let mut best_item = u32::MAX;
+            unsafe { update_nearest_dist_f32_avx2(&dists, &items, &mut best_dist, &mut best_item) };
+
+            assert_eq!(best_dist, expected_dist);
+            assert_eq!(best_item, expected_item);
+        }
+    }
+
+    #[test]
+    fn avx2_dists_within_matches_scalar() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(8);
+
+        for len in 1..70 {
+            let dists: Vec<f32> = (0..len).map(|_| rng.gen_range(0..20) as f32).collect();
 
-    let mut min_dist_indexes = [0i32; 8];
+            let expected: Vec<usize> = (0..len).filter(|&idx| dists[idx] <= 5f32).collect();
 
-    _mm256_store_si256(
-        std::mem::transmute(std::ptr::addr_of_mut!(min_dist_indexes[0])),
-        min_dist_indexes_v,
-    );
-    _mm256_storeu_ps(std::ptr::addr_of_mut!(min_dists[0]), min_dists_v);
+            let mut found = Vec::new();
+            unsafe { for_each_dist_within_f32_avx2(&dists, 5f32, |idx| found.push(idx)) };
 
-    for (i, dist) in min_dists.iter().enumerate() {
-        if *dist < *best_dist {
-            *best_dist = *dist;
-            *best_item = items[min_dist_indexes[i] as usize];
+            assert_eq!(found, expected);
         }
     }
 }
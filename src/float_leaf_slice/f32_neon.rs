@@ -0,0 +1,170 @@
+//! NEON leaf-scanning kernels for `f32`, processing four distances per instruction.
+//!
+//! Only compiled when the `simd` feature is enabled on `aarch64`, where NEON is always
+//! available. Results are identical to those of the portable kernels in
+//! [`fallback`](super::fallback), including tie-breaking.
+use core::arch::aarch64::{
+    vaddq_u32, vaddvq_u32, vandq_u32, vbslq_f32, vbslq_u32, vcleq_f32, vcltq_f32, vdupq_n_f32,
+    vdupq_n_u32, vld1q_f32, vld1q_u32, vst1q_f32, vst1q_u32,
+};
+
+use crate::traits::Content;
+
+const LANES: usize = 4;
+
+/// Finds the smallest of `dists`, and replaces `best_dist` / `best_item` with it if it
+/// is smaller than `best_dist`. If several dists are equally smallest, the first wins.
+///
+/// # Safety
+///
+/// `items` must be at least as long as `dists`.
+#[allow(unused_unsafe)]
+#[inline]
+pub(crate) unsafe fn update_nearest_dist_f32_neon<T: Content>(
+    dists: &[f32],
+    items: &[T],
+    best_dist: &mut f32,
+    best_item: &mut T,
+) {
+    let chunks = dists.chunks_exact(LANES);
+    let remainder = chunks.remainder();
+
+    let mut lane_dists = [0f32; LANES];
+    let mut lane_indexes = [0u32; LANES];
+
+    unsafe {
+        let initial_indexes = [0u32, 1, 2, 3];
+        let all_fours = vdupq_n_u32(LANES as u32);
+        let mut index_v = vld1q_u32(initial_indexes.as_ptr());
+        let mut min_dists_v = vdupq_n_f32(*best_dist);
+        let mut min_indexes_v = vdupq_n_u32(u32::MAX);
+
+        for chunk in chunks {
+            let chunk_v = vld1q_f32(chunk.as_ptr());
+
+            // strictly less than, so that each lane keeps the first of any equal dists
+            let is_better = vcltq_f32(chunk_v, min_dists_v);
+
+            min_dists_v = vbslq_f32(is_better, chunk_v, min_dists_v);
+            min_indexes_v = vbslq_u32(is_better, index_v, min_indexes_v);
+
+            index_v = vaddq_u32(index_v, all_fours);
+        }
+
+        vst1q_f32(lane_dists.as_mut_ptr(), min_dists_v);
+        vst1q_u32(lane_indexes.as_mut_ptr(), min_indexes_v);
+    }
+
+    // Reduce across lanes. Only lanes that found something better than `best_dist`
+    // have an index other than `u32::MAX`. Equal dists are resolved in favour of the
+    // lowest index.
+    let mut min_dist = *best_dist;
+    let mut min_idx = usize::MAX;
+    for (&dist, &idx) in lane_dists.iter().zip(lane_indexes.iter()) {
+        if idx != u32::MAX && (dist < min_dist || (dist == min_dist && (idx as usize) < min_idx)) {
+            min_dist = dist;
+            min_idx = idx as usize;
+        }
+    }
+
+    let remainder_offset = dists.len() - remainder.len();
+    for (idx, &dist) in remainder.iter().enumerate() {
+        if dist < min_dist {
+            min_dist = dist;
+            min_idx = remainder_offset + idx;
+        }
+    }
+
+    if min_idx != usize::MAX {
+        *best_dist = min_dist;
+        *best_item = items[min_idx];
+    }
+}
+
+/// Calls `f` with the index of every one of `dists` that is within `radius`, in
+/// ascending order of index.
+///
+/// # Safety
+///
+/// Always safe to call on `aarch64`. Marked `unsafe` for parity with the other kernels.
+#[allow(unused_unsafe)]
+#[inline]
+pub(crate) unsafe fn for_each_dist_within_f32_neon(
+    dists: &[f32],
+    radius: f32,
+    mut f: impl FnMut(usize),
+) {
+    let chunks = dists.chunks_exact(LANES);
+    let remainder = chunks.remainder();
+
+    unsafe {
+        let lane_bits = [1u32, 2, 4, 8];
+        let lane_bits_v = vld1q_u32(lane_bits.as_ptr());
+        let radius_v = vdupq_n_f32(radius);
+
+        for (chunk_idx, chunk) in chunks.enumerate() {
+            let chunk_v = vld1q_f32(chunk.as_ptr());
+
+            // collapse the per-lane comparison results into a bitmask
+            let mut mask = vaddvq_u32(vandq_u32(vcleq_f32(chunk_v, radius_v), lane_bits_v));
+
+            // Most chunks visited by a query contain no results at all
+            while mask != 0 {
+                f(chunk_idx * LANES + mask.trailing_zeros() as usize);
+                mask &= mask - 1;
+            }
+        }
+    }
+
+    let remainder_offset = dists.len() - remainder.len();
+    for (idx, &dist) in remainder.iter().enumerate() {
+        if dist <= radius {
+            f(remainder_offset + idx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{for_each_dist_within_f32_neon, update_nearest_dist_f32_neon};
+    use crate::float_leaf_slice::fallback::update_nearest_dist_autovec;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn neon_nearest_dist_matches_autovec() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(7);
+
+        for len in 1..70 {
+            // a coarse range, so that there are plenty of equal dists
+            let dists: Vec<f32> = (0..len).map(|_| rng.gen_range(0..20) as f32).collect();
+            let items: Vec<u32> = (0..len as u32).collect();
+
+            let mut expected_dist = 10f32;
+            let mut expected_item = u32::MAX;
+            update_nearest_dist_autovec(&dists, &items, &mut expected_dist, &mut expected_item);
+
+            let mut best_dist = 10f32;
+            let mut best_item = u32::MAX;
+            unsafe { update_nearest_dist_f32_neon(&dists, &items, &mut best_dist, &mut best_item) };
+
+            assert_eq!(best_dist, expected_dist);
+            assert_eq!(best_item, expected_item);
+        }
+    }
+
+    #[test]
+    fn neon_dists_within_matches_scalar() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(8);
+
+        for len in 1..70 {
+            let dists: Vec<f32> = (0..len).map(|_| rng.gen_range(0..20) as f32).collect();
+
+            let expected: Vec<usize> = (0..len).filter(|&idx| dists[idx] <= 5f32).collect();
+
+            let mut found = Vec::new();
+            unsafe { for_each_dist_within_f32_neon(&dists, 5f32, |idx| found.push(idx)) };
+
+            assert_eq!(found, expected);
+        }
+    }
+}
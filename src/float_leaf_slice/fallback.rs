@@ -1,11 +1,66 @@
+//! Portable leaf-scanning kernels, used wherever hand-written SIMD is not available.
+//!
+//! These are written as fixed-width, branch-free loops over the SoA distance buffers so that
+//! LLVM can auto-vectorize them for whichever target features are enabled at compile time.
 use az::Cast;
 use std::collections::BinaryHeap;
 
 use crate::float::result_collection::ResultCollection;
 use crate::{float::kdtree::Axis, traits::Content, BestNeighbour, NearestNeighbour};
 
+/// Number of independent lanes used by the kernels below. Each lane keeps its own
+/// running minimum, so there is no loop-carried dependency between lanes and LLVM is
+/// free to map them onto vector registers (e.g. 2x 256bit registers for `f64` on AVX2)
+const LANES: usize = 8;
+
+/// Returns the smallest of `dists`, or `init` if none are smaller.
+///
+/// Written as a branch-free, fixed-width loop followed by a pairwise reduction
+/// across lanes, so that both halves auto-vectorize.
 #[inline]
-pub(crate) fn update_nearest_dist_autovec<A: Axis, T: Content>(
+#[allow(clippy::needless_range_loop)]
+fn min_dist<A: Axis>(dists: &[A], init: A) -> A {
+    let mut lane_dists = [init; LANES];
+
+    let chunks = dists.chunks_exact(LANES);
+    let remainder = chunks.remainder();
+
+    for chunk in chunks {
+        for lane in 0..LANES {
+            lane_dists[lane] = if chunk[lane] < lane_dists[lane] {
+                chunk[lane]
+            } else {
+                lane_dists[lane]
+            };
+        }
+    }
+
+    let mut width = LANES / 2;
+    while width > 0 {
+        for lane in 0..width {
+            lane_dists[lane] = if lane_dists[lane + width] < lane_dists[lane] {
+                lane_dists[lane + width]
+            } else {
+                lane_dists[lane]
+            };
+        }
+        width /= 2;
+    }
+
+    let mut min_dist = lane_dists[0];
+    for &dist in remainder {
+        if dist < min_dist {
+            min_dist = dist;
+        }
+    }
+
+    min_dist
+}
+
+/// Finds the smallest of `dists`, and replaces `best_dist` / `best_item` with it if it
+/// is smaller than `best_dist`. If several dists are equally smallest, the first wins.
+#[inline]
+pub fn update_nearest_dist_autovec<A: Axis, T: Content>(
     dists: &[A],
     items: &[T],
     best_dist: &mut A,
@@ -13,23 +68,21 @@ pub(crate) fn update_nearest_dist_autovec<A: Axis, T: Content>(
 ) where
     usize: Cast<T>,
 {
-    // Autovectorizes with 256bit vectors on x86_64 where available
-    // 341 loops (1 item per loop, unrolled x 3) of 4-8 instructions per item
-    let (leaf_best_item, leaf_best_dist) = dists
-        .iter()
-        .enumerate()
-        .min_by(|(_, &a), (_, b)| a.partial_cmp(b).unwrap())
-        .unwrap();
-
-    // 6 instructions, 1 branch
-    if *leaf_best_dist < *best_dist {
-        *best_dist = *leaf_best_dist;
-        *best_item = items[leaf_best_item];
+    let min_dist = min_dist(dists, *best_dist);
+
+    // Only once we know that the leaf contains a new best do we go back
+    // and find out which item it belongs to.
+    if min_dist < *best_dist {
+        if let Some(idx) = dists.iter().position(|&dist| dist == min_dist) {
+            *best_dist = min_dist;
+            *best_item = items[idx];
+        }
     }
 }
 
+/// Adds every one of `dists` that is within `radius` to `results`
 #[inline]
-pub(crate) fn update_nearest_dists_within_autovec<A: Axis, T: Content, R>(
+pub fn update_nearest_dists_within_autovec<A: Axis, T: Content, R>(
     dists: &[A],
     items: &[T],
     radius: A,
@@ -38,7 +91,11 @@ pub(crate) fn update_nearest_dists_within_autovec<A: Axis, T: Content, R>(
     usize: Cast<T>,
     R: ResultCollection<A, T>,
 {
-    // TODO: Optimise with Godbolt
+    // Most chunks visited by a query contain no results at all
+    if min_dist(dists, A::infinity()) > radius {
+        return;
+    }
+
     dists
         .iter()
         .zip(items.iter())
@@ -48,8 +105,10 @@ pub(crate) fn update_nearest_dists_within_autovec<A: Axis, T: Content, R>(
         });
 }
 
+/// Adds every one of `dists` that is within `radius` to `results`, keeping
+/// only the `max_qty` lowest items
 #[inline]
-pub(crate) fn update_best_dists_within_autovec<A: Axis, T: Content>(
+pub fn update_best_dists_within_autovec<A: Axis, T: Content>(
     dists: &[A],
     items: &[T],
     radius: A,
@@ -58,22 +117,36 @@ pub(crate) fn update_best_dists_within_autovec<A: Axis, T: Content>(
 ) where
     usize: Cast<T>,
 {
-    // TODO: Optimise with Godbolt
+    // Most chunks visited by a query contain no results at all
+    if min_dist(dists, A::infinity()) > radius {
+        return;
+    }
+
     dists
         .iter()
         .zip(items.iter())
         .filter(|(&distance, _)| distance <= radius)
-        .for_each(|(&distance, &item)| {
-            if results.len() < max_qty {
-                results.push(BestNeighbour { distance, item });
-            } else {
-                let mut top = results.peek_mut().unwrap();
-                if item < top.item {
-                    top.item = item;
-                    top.distance = distance;
-                }
-            }
-        });
+        .for_each(|(&distance, &item)| push_best(results, max_qty, distance, item));
+}
+
+/// Adds `item` to `results` if there are fewer than `max_qty` of them,
+/// or if it is lower than the highest item in `results`
+#[inline]
+pub(crate) fn push_best<A: Axis, T: Content>(
+    results: &mut BinaryHeap<BestNeighbour<A, T>>,
+    max_qty: usize,
+    distance: A,
+    item: T,
+) {
+    if results.len() < max_qty {
+        results.push(BestNeighbour { distance, item });
+    } else {
+        let mut top = results.peek_mut().unwrap();
+        if item < top.item {
+            top.item = item;
+            top.distance = distance;
+        }
+    }
 }
 
 #[cfg(test)]
@@ -83,6 +156,7 @@ mod tests {
         update_nearest_dists_within_autovec,
     };
     use crate::{BestNeighbour, NearestNeighbour};
+    use rand::{Rng, SeedableRng};
     use std::collections::BinaryHeap;
 
     #[test]
@@ -113,6 +187,50 @@ mod tests {
         assert_eq!(best_item, 5u32);
     }
 
+    #[test]
+    fn test_get_best_from_dists_autovec_picks_first_of_equal_dists() {
+        let dists = [
+            5f32, 3f32, 9f32, 3f32, 7f32, 3f32, 8f32, 6f32, 3f32, 4f32, 3f32,
+        ];
+        let items = [0u32, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+
+        let mut best_dist = f32::INFINITY;
+        let mut best_item = 12345u32;
+
+        update_nearest_dist_autovec(&dists[..], &items[..], &mut best_dist, &mut best_item);
+
+        assert_eq!(best_dist, 3f32);
+        assert_eq!(best_item, 1u32);
+    }
+
+    #[test]
+    fn test_get_best_from_dists_autovec_matches_linear_scan() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(5);
+
+        for len in 1..70 {
+            let dists: Vec<f64> = (0..len).map(|_| rng.gen_range(0f64..100f64)).collect();
+            let items: Vec<u32> = (0..len as u32).collect();
+
+            let (expected_item, expected_dist) = dists.iter().enumerate().fold(
+                (usize::MAX, 50f64),
+                |(best_idx, best), (idx, &dist)| {
+                    if dist < best {
+                        (idx, dist)
+                    } else {
+                        (best_idx, best)
+                    }
+                },
+            );
+
+            let mut best_dist = 50f64;
+            let mut best_item = u32::MAX;
+            update_nearest_dist_autovec(&dists[..], &items[..], &mut best_dist, &mut best_item);
+
+            assert_eq!(best_dist, expected_dist);
+            assert_eq!(best_item, expected_item as u32);
+        }
+    }
+
     #[test]
     fn test_update_nearest_dists_within_autovec_leaves_nearest() {
         let dists = [10000f64, 20000f64, 20f64];
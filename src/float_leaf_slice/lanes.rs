@@ -0,0 +1,150 @@
+//! Explicit-lane leaf-scanning kernels that build on stable Rust, used when the
+//! `simd_stable` feature is enabled and no hand-written intrinsics apply.
+//!
+//! Rather than leaving LLVM to find the parallelism in scalar loops, as the kernels in
+//! [`fallback`](super::fallback) do, these work on blocks of [`LANES`] distances at a time,
+//! tracking a best distance and index per lane and building a bitmask of the lanes that
+//! are within a radius, in the same way as the AVX2 and NEON kernels. Each block maps onto
+//! vector compares, blends and a movemask on any target. Results are identical to those of
+//! the portable kernels, including tie-breaking.
+use crate::float::kdtree::Axis;
+use crate::traits::Content;
+
+/// Number of distances processed per block, e.g. one 256bit register of `f32` or two of
+/// `f64` on AVX2
+pub const LANES: usize = 8;
+
+/// Finds the smallest of `dists`, and replaces `best_dist` / `best_item` with it if it
+/// is smaller than `best_dist`. If several dists are equally smallest, the first wins.
+///
+/// `items` must be at least as long as `dists`.
+#[inline]
+#[allow(clippy::needless_range_loop)]
+pub fn update_nearest_dist_lanes<A: Axis, T: Content>(
+    dists: &[A],
+    items: &[T],
+    best_dist: &mut A,
+    best_item: &mut T,
+) {
+    let chunks = dists.chunks_exact(LANES);
+    let remainder = chunks.remainder();
+
+    let mut lane_dists = [*best_dist; LANES];
+    let mut lane_indexes = [u32::MAX; LANES];
+
+    for (chunk_idx, chunk) in chunks.enumerate() {
+        let base = (chunk_idx * LANES) as u32;
+        for lane in 0..LANES {
+            // strictly less than, so that each lane keeps the first of any equal dists
+            let is_better = chunk[lane] < lane_dists[lane];
+            lane_dists[lane] = if is_better {
+                chunk[lane]
+            } else {
+                lane_dists[lane]
+            };
+            lane_indexes[lane] = if is_better {
+                base + lane as u32
+            } else {
+                lane_indexes[lane]
+            };
+        }
+    }
+
+    // Reduce across lanes. Only lanes that found something better than `best_dist`
+    // have an index. Equal dists are resolved in favour of the lowest index.
+    let mut min_dist = *best_dist;
+    let mut min_idx = usize::MAX;
+    for (&dist, &idx) in lane_dists.iter().zip(lane_indexes.iter()) {
+        if idx != u32::MAX && (dist < min_dist || (dist == min_dist && (idx as usize) < min_idx)) {
+            min_dist = dist;
+            min_idx = idx as usize;
+        }
+    }
+
+    let remainder_offset = dists.len() - remainder.len();
+    for (idx, &dist) in remainder.iter().enumerate() {
+        if dist < min_dist {
+            min_dist = dist;
+            min_idx = remainder_offset + idx;
+        }
+    }
+
+    if min_idx != usize::MAX {
+        *best_dist = min_dist;
+        *best_item = items[min_idx];
+    }
+}
+
+/// Calls `f` with the index of every one of `dists` that is within `radius`, in
+/// ascending order of index.
+#[inline]
+#[allow(clippy::needless_range_loop)]
+pub fn for_each_dist_within_lanes<A: Axis>(dists: &[A], radius: A, mut f: impl FnMut(usize)) {
+    let chunks = dists.chunks_exact(LANES);
+    let remainder = chunks.remainder();
+
+    for (chunk_idx, chunk) in chunks.enumerate() {
+        let mut mask = 0u32;
+        for lane in 0..LANES {
+            mask |= u32::from(chunk[lane] <= radius) << lane;
+        }
+
+        // Most chunks visited by a query contain no results at all
+        while mask != 0 {
+            f(chunk_idx * LANES + mask.trailing_zeros() as usize);
+            mask &= mask - 1;
+        }
+    }
+
+    let remainder_offset = dists.len() - remainder.len();
+    for (idx, &dist) in remainder.iter().enumerate() {
+        if dist <= radius {
+            f(remainder_offset + idx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{for_each_dist_within_lanes, update_nearest_dist_lanes};
+    use crate::float_leaf_slice::fallback::update_nearest_dist_autovec;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn lanes_nearest_dist_matches_autovec() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(29);
+
+        for len in 1..70 {
+            // a coarse range, so that there are plenty of equal dists
+            let dists: Vec<f64> = (0..len).map(|_| rng.gen_range(0..20) as f64).collect();
+            let items: Vec<u32> = (0..len as u32).collect();
+
+            for initial_best in [f64::INFINITY, 5.0, 0.0] {
+                let mut expected = (initial_best, 12345u32);
+                update_nearest_dist_autovec(&dists, &items, &mut expected.0, &mut expected.1);
+
+                let mut actual = (initial_best, 12345u32);
+                update_nearest_dist_lanes(&dists, &items, &mut actual.0, &mut actual.1);
+
+                assert_eq!(actual, expected, "len {len}, initial best {initial_best}");
+            }
+        }
+    }
+
+    #[test]
+    fn lanes_dists_within_are_found_in_order() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(30);
+
+        for len in 0..70 {
+            let dists: Vec<f32> = (0..len).map(|_| rng.gen_range(0..20) as f32).collect();
+            let radius = 6f32;
+
+            let expected: Vec<usize> = (0..len).filter(|&idx| dists[idx] <= radius).collect();
+
+            let mut actual = Vec::new();
+            for_each_dist_within_lanes(&dists, radius, |idx| actual.push(idx));
+
+            assert_eq!(actual, expected);
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use crate::float::result_collection::ResultCollection;
 use az::Cast;
+use num_traits::Zero;
 use std::collections::BinaryHeap;
 use std::slice::ChunksExact;
 
@@ -11,7 +12,6 @@ const CHUNK_SIZE: usize = 32;
     any(target_arch = "x86", target_arch = "x86_64")
 ))]
 use super::f64_avx2::get_best_from_dists_f64_avx2;*/
-//use super::{f32_avx2::get_best_from_dists_f32_avx2};
 
 // #[cfg(all(
 //     feature = "simd",
@@ -20,12 +20,23 @@ use super::f64_avx2::get_best_from_dists_f64_avx2;*/
 // ))]
 // use super::f64_avx512::get_best_from_dists_f64_avx512;
 
+use super::fallback::push_best;
+#[cfg(not(feature = "simd_stable"))]
 use super::fallback::{
     update_best_dists_within_autovec, update_nearest_dist_autovec,
     update_nearest_dists_within_autovec,
 };
 
-use crate::traits::DistanceMetric;
+#[cfg(all(feature = "simd", target_arch = "x86_64", target_feature = "avx2"))]
+use super::f32_avx2::{for_each_dist_within_f32_avx2, update_nearest_dist_f32_avx2};
+
+#[cfg(all(feature = "simd", target_arch = "aarch64", target_feature = "neon"))]
+use super::f32_neon::{for_each_dist_within_f32_neon, update_nearest_dist_f32_neon};
+
+#[cfg(feature = "simd_stable")]
+use super::lanes::{for_each_dist_within_lanes, update_nearest_dist_lanes};
+
+use crate::traits::{accumulate_dist, DistanceMetric};
 use crate::{float::kdtree::Axis, traits::Content, BestNeighbour, NearestNeighbour};
 
 #[doc(hidden)]
@@ -43,38 +54,62 @@ where
     usize: Cast<T>,
 {
     #[allow(dead_code)]
+    /// Scans the leaf for an entry nearer to `query` than `best_dist`, using the same
+    /// vectorized kernels as the tree's own queries. If one is found, `best_dist` and
+    /// `best_item` are updated to its distance and item.
+    ///
+    /// Start with `best_dist` set to `A::infinity()` to find the nearest entry in the leaf,
+    /// or pass the results of scanning one leaf in when scanning the next.
     #[inline]
-    pub(crate) fn nearest_one<D>(&self, query: &[A; K], best_dist: &mut A, best_item: &mut T)
+    pub fn nearest_one<D>(&self, query: &[A; K], best_dist: &mut A, best_item: &mut T)
     where
         D: DistanceMetric<A, K>,
     {
-        // AVX512: 4 loops of 32 iterations, each 4x unrolled, 5 instructions per pre-unrolled iteration
-        let mut acc = [A::zero(); C];
-        (0..K).step_by(1).for_each(|dim| {
-            let qd = [query[dim]; C];
-
-            (0..C).step_by(1).for_each(|idx| {
-                acc[idx] += D::dist1(self.content_points[dim][idx], qd[idx]);
-            });
-        });
+        let acc = A::dists_for_chunk::<D, C>(self.content_points, query);
 
-        A::update_nearest_dist(acc, self.content_items, best_dist, best_item);
+        let mut best_dist_acc = best_dist.widen();
+        A::update_nearest_dist(acc, self.content_items, &mut best_dist_acc, best_item);
+        *best_dist = A::narrow(best_dist_acc);
     }
 }
 
-#[doc(hidden)]
+/// The points and items stored in one leaf of an immutable tree, as returned by
+/// `get_leaf_slice`, for implementing custom leaf-level scans.
+///
+/// Points are stored one axis at a time: `content_points[dim][idx]` is the coordinate on
+/// axis `dim` of the entry at `idx`, whose item is `content_items[idx]`. All `K` slices of
+/// `content_points` have the same length as `content_items`. Entries are in no particular
+/// order within a leaf.
 #[derive(Debug)]
-pub(crate) struct LeafSlice<'a, A: Axis, T: Content, const K: usize> {
+pub struct LeafSlice<'a, A: Axis, T: Content, const K: usize> {
+    /// The coordinates of the leaf's entries, one slice per axis
     pub content_points: [&'a [A]; K],
+    /// The items of the leaf's entries
     pub content_items: &'a [T],
 }
 
 impl<A: Axis, T: Content, const K: usize> LeafSlice<'_, A, T, K> {
-    #[allow(dead_code)]
+    /// Returns the number of entries in the leaf
     #[inline]
-    fn len(&self) -> usize {
+    pub fn len(&self) -> usize {
         self.content_items.len()
     }
+
+    /// Returns whether the leaf has no entries
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.content_items.is_empty()
+    }
+
+    /// Iterates over the `(item, point)` pairs of the leaf's entries
+    pub fn iter(&self) -> impl Iterator<Item = (T, [A; K])> + '_ {
+        self.content_items.iter().enumerate().map(|(idx, &item)| {
+            (
+                item,
+                core::array::from_fn(|dim| self.content_points[dim][idx]),
+            )
+        })
+    }
 }
 
 pub(crate) struct LeafFixedSliceIterator<'a, A: Axis, T: Content, const K: usize, const C: usize> {
@@ -113,11 +148,14 @@ impl<'a, A: Axis, T: Content, const K: usize, const C: usize>
     }
 }
 
-pub trait LeafSliceFloatChunk<T, const K: usize>
+pub trait LeafSliceFloatChunk<T, const K: usize>: LeafSliceFloat<T>
 where
     T: Content,
 {
-    fn dists_for_chunk<D, const C: usize>(chunk: [&[Self; C]; K], query: &[Self; K]) -> [Self; C]
+    fn dists_for_chunk<D, const C: usize>(
+        chunk: [&[Self; C]; K],
+        query: &[Self; K],
+    ) -> [Self::Acc; C]
     where
         D: DistanceMetric<Self, K>,
         Self: Sized;
@@ -127,16 +165,28 @@ pub trait LeafSliceFloat<T>
 where
     T: Content,
 {
+    /// The type that distances are accumulated and compared in whilst scanning a leaf.
+    ///
+    /// This is `Self` for `f32` and `f64`. For `f16` it is `f32`, so that summing the
+    /// per-axis distances does not round away the differences between nearby points.
+    type Acc: Axis;
+
+    /// Converts a value into the accumulator type
+    fn widen(self) -> Self::Acc;
+
+    /// Converts an accumulated distance back into the type stored in the tree
+    fn narrow(acc: Self::Acc) -> Self;
+
     fn update_nearest_dist<const C: usize>(
-        acc: [Self; C],
+        acc: [Self::Acc; C],
         items: &[T; C],
-        best_dist: &mut Self,
+        best_dist: &mut Self::Acc,
         best_item: &mut T,
     ) where
         Self: Sized;
 
     fn update_nearest_dists_within<R, const C: usize>(
-        acc: [Self; C],
+        acc: [Self::Acc; C],
         items: &[T; C],
         radius: Self,
         results: &mut R,
@@ -146,7 +196,7 @@ where
         Self: Axis + Sized;
 
     fn update_best_dists_within<const C: usize>(
-        acc: [Self; C],
+        acc: [Self::Acc; C],
         items: &[T; C],
         radius: Self,
         max_qty: usize,
@@ -178,7 +228,7 @@ where
     }
 
     #[inline]
-    fn as_full_chunks<const C: usize>(&self) -> LeafFixedSliceIterator<A, T, K, C> {
+    fn as_full_chunks<const C: usize>(&self) -> LeafFixedSliceIterator<'_, A, T, K, C> {
         let points_iterators = self.content_points.map(|i| i.chunks_exact(C));
         let items_iterator = self.content_items.chunks_exact(C);
 
@@ -188,35 +238,69 @@ where
         }
     }
 
+    /// Scans the leaf for an entry nearer to `query` than `best_dist`, using the same
+    /// vectorized kernels as the tree's own queries. If one is found, `best_dist` and
+    /// `best_item` are updated to its distance and item.
+    ///
+    /// Start with `best_dist` set to `A::infinity()` to find the nearest entry in the leaf,
+    /// or pass the results of scanning one leaf in when scanning the next.
     #[inline]
-    pub(crate) fn nearest_one<D>(&self, query: &[A; K], best_dist: &mut A, best_item: &mut T)
+    pub fn nearest_one<D>(&self, query: &[A; K], best_dist: &mut A, best_item: &mut T)
     where
         D: DistanceMetric<A, K>,
     {
+        // Track the best distance in the accumulator type for the whole leaf, so that
+        // candidates within the same leaf are never compared at reduced precision
+        let mut best_dist_acc = best_dist.widen();
+
         let chunk_iter = self.as_full_chunks::<CHUNK_SIZE>();
         let (remainder_points, remainder_items) = chunk_iter.remainder();
         for chunk in chunk_iter {
             let dists = A::dists_for_chunk::<D, CHUNK_SIZE>(chunk.0, query);
-            A::update_nearest_dist(dists, chunk.1, best_dist, best_item);
+            A::update_nearest_dist(dists, chunk.1, &mut best_dist_acc, best_item);
         }
 
         #[allow(clippy::needless_range_loop)]
         for idx in 0..remainder_items.len() {
-            let mut dist = A::zero();
+            let mut dist = A::Acc::zero();
             (0..K).step_by(1).for_each(|dim| {
-                dist += D::dist1(remainder_points[dim][idx], query[dim]);
+                dist = accumulate_dist(
+                    dist,
+                    D::dist1(remainder_points[dim][idx], query[dim]).widen(),
+                    D::MAX_OF_AXES,
+                    |a, b| a + b,
+                );
             });
 
             // TODO: make branchless
-            let dist_is_better = u8::from(dist < *best_dist);
+            let dist_is_better = u8::from(dist < best_dist_acc);
             // best_dist.cmovnz(&dist, dist_is_better);
             // best_item.cmovnz(&remainder_items[idx], dist_is_better);
 
             if dist_is_better == 1 {
-                *best_dist = dist;
+                best_dist_acc = dist;
                 *best_item = remainder_items[idx];
             }
         }
+
+        *best_dist = A::narrow(best_dist_acc);
+    }
+
+    /// Calls `visitor` with the item and distance of every entry in the leaf that is
+    /// within `radius` of `query`, in arbitrary order.
+    #[inline]
+    pub fn for_each_within<D, F>(&self, query: &[A; K], radius: A, mut visitor: F)
+    where
+        D: DistanceMetric<A, K>,
+        F: FnMut(T, A),
+    {
+        self.nearest_n_within::<D, _>(
+            query,
+            radius,
+            &mut crate::float::result_collection::VisitedResults {
+                visitor: &mut visitor,
+            },
+        );
     }
 
     #[inline]
@@ -233,19 +317,14 @@ where
             A::update_nearest_dists_within(dists, chunk.1, radius, results);
         }
 
-        #[allow(clippy::needless_range_loop)]
-        for idx in 0..remainder_items.len() {
-            let mut distance = A::zero();
-            (0..K).step_by(1).for_each(|dim| {
-                distance += D::dist1(remainder_points[dim][idx], query[dim]);
-            });
-
-            if distance < radius {
-                results.add(NearestNeighbour {
-                    distance,
-                    item: *unsafe { self.content_items.get_unchecked(idx) },
-                });
-            }
+        for (idx, &item) in remainder_items.iter().enumerate() {
+            add_if_within::<A, T, D, R, K>(
+                query,
+                |dim| remainder_points[dim][idx],
+                item,
+                radius,
+                results,
+            );
         }
     }
 
@@ -269,22 +348,20 @@ where
 
         #[allow(clippy::needless_range_loop)]
         for idx in 0..remainder_items.len() {
-            let mut distance = A::zero();
+            let mut distance = A::Acc::zero();
             (0..K).step_by(1).for_each(|dim| {
-                distance += D::dist1(remainder_points[dim][idx], query[dim]);
+                distance = accumulate_dist(
+                    distance,
+                    D::dist1(remainder_points[dim][idx], query[dim]).widen(),
+                    D::MAX_OF_AXES,
+                    |a, b| a + b,
+                );
             });
 
-            if distance < radius {
+            if distance < radius.widen() {
+                let distance = A::narrow(distance);
                 let item = *unsafe { remainder_items.get_unchecked(idx) };
-                if results.len() < max_qty {
-                    results.push(BestNeighbour { distance, item });
-                } else {
-                    let mut top = results.peek_mut().unwrap();
-                    if item < top.item {
-                        top.item = item;
-                        top.distance = distance;
-                    }
-                }
+                push_best(results, max_qty, distance, item);
             }
         }
     }
@@ -295,6 +372,18 @@ where
     T: Content,
     usize: Cast<T>,
 {
+    type Acc = f64;
+
+    #[inline]
+    fn widen(self) -> f64 {
+        self
+    }
+
+    #[inline]
+    fn narrow(acc: f64) -> f64 {
+        acc
+    }
+
     #[inline]
     fn update_nearest_dist<const C: usize>(
         acc: [f64; C],
@@ -302,7 +391,16 @@ where
         best_dist: &mut f64,
         best_item: &mut T,
     ) {
-        #[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64")))]
+        #[cfg(feature = "simd_stable")]
+        {
+            update_nearest_dist_lanes(&acc, items, best_dist, best_item)
+        }
+
+        #[cfg(all(
+            not(feature = "simd_stable"),
+            feature = "simd",
+            any(target_arch = "x86", target_arch = "x86_64")
+        ))]
         {
             /*if is_x86_feature_detected!("avx512f") {
                 #[cfg(target_feature = "avx512f")]
@@ -320,9 +418,12 @@ where
             // }
         }
 
-        #[cfg(any(
-            not(feature = "simd"),
-            not(any(target_arch = "x86", target_arch = "x86_64"))
+        #[cfg(all(
+            not(feature = "simd_stable"),
+            any(
+                not(feature = "simd"),
+                not(any(target_arch = "x86", target_arch = "x86_64"))
+            )
         ))]
         {
             update_nearest_dist_autovec(&acc, items, best_dist, best_item)
@@ -338,7 +439,20 @@ where
     ) where
         R: ResultCollection<f64, T>,
     {
-        update_nearest_dists_within_autovec(&acc, items, radius, results)
+        #[cfg(feature = "simd_stable")]
+        {
+            for_each_dist_within_lanes(&acc, radius, |idx| {
+                results.add(NearestNeighbour {
+                    distance: acc[idx],
+                    item: items[idx],
+                })
+            })
+        }
+
+        #[cfg(not(feature = "simd_stable"))]
+        {
+            update_nearest_dists_within_autovec(&acc, items, radius, results)
+        }
     }
 
     #[inline]
@@ -349,7 +463,17 @@ where
         max_qty: usize,
         results: &mut BinaryHeap<BestNeighbour<f64, T>>,
     ) {
-        update_best_dists_within_autovec(&acc, items, radius, max_qty, results)
+        #[cfg(feature = "simd_stable")]
+        {
+            for_each_dist_within_lanes(&acc, radius, |idx| {
+                push_best(results, max_qty, acc[idx], items[idx])
+            })
+        }
+
+        #[cfg(not(feature = "simd_stable"))]
+        {
+            update_best_dists_within_autovec(&acc, items, radius, max_qty, results)
+        }
     }
 }
 
@@ -359,7 +483,7 @@ where
     usize: Cast<T>,
 {
     #[inline]
-    fn dists_for_chunk<D, const C: usize>(chunk: [&[Self; C]; K], query: &[Self; K]) -> [Self; C]
+    fn dists_for_chunk<D, const C: usize>(chunk: [&[Self; C]; K], query: &[Self; K]) -> [f64; C]
     where
         D: DistanceMetric<Self, K>,
         Self: Sized,
@@ -370,7 +494,12 @@ where
             let qd = [query[dim]; C];
 
             (0..C).step_by(1).for_each(|idx| {
-                acc[idx] += D::dist1(chunk[dim][idx], qd[idx]);
+                acc[idx] = accumulate_dist(
+                    acc[idx],
+                    D::dist1(chunk[dim][idx], qd[idx]),
+                    D::MAX_OF_AXES,
+                    |a, b| a + b,
+                );
             });
         });
 
@@ -383,6 +512,18 @@ where
     T: Content,
     usize: Cast<T>,
 {
+    type Acc = f32;
+
+    #[inline]
+    fn widen(self) -> f32 {
+        self
+    }
+
+    #[inline]
+    fn narrow(acc: f32) -> f32 {
+        acc
+    }
+
     #[inline]
     fn update_nearest_dist<const C: usize>(
         acc: [f32; C],
@@ -390,26 +531,32 @@ where
         best_dist: &mut f32,
         best_item: &mut T,
     ) {
-        #[cfg(all(feature = "simd", any(target_arch = "x86", target_arch = "x86_64")))]
-        {
-            /* if is_x86_feature_detected!("avx512f") {
-                // TODO
-                unimplemented!()
-            } else */
-            /*if is_x86_feature_detected!("avx2") {
-                #[cfg(target_feature = "avx2")]
-                unsafe {
-                    get_best_from_dists_f32_avx2(&acc, items, best_dist, best_item)
-                }
-            } else {*/
-            update_nearest_dist_autovec(&acc, items, best_dist, best_item)
-            //}
+        #[cfg(all(feature = "simd", target_arch = "x86_64", target_feature = "avx2"))]
+        unsafe {
+            update_nearest_dist_f32_avx2(&acc, items, best_dist, best_item)
+        }
+
+        #[cfg(all(feature = "simd", target_arch = "aarch64", target_feature = "neon"))]
+        unsafe {
+            update_nearest_dist_f32_neon(&acc, items, best_dist, best_item)
         }
 
-        #[cfg(any(
-            not(feature = "simd"),
-            not(any(target_arch = "x86", target_arch = "x86_64"))
+        #[cfg(all(
+            feature = "simd_stable",
+            not(any(
+                all(feature = "simd", target_arch = "x86_64", target_feature = "avx2"),
+                all(feature = "simd", target_arch = "aarch64", target_feature = "neon")
+            ))
         ))]
+        {
+            update_nearest_dist_lanes(&acc, items, best_dist, best_item)
+        }
+
+        #[cfg(not(any(
+            all(feature = "simd", target_arch = "x86_64", target_feature = "avx2"),
+            all(feature = "simd", target_arch = "aarch64", target_feature = "neon"),
+            feature = "simd_stable"
+        )))]
         {
             update_nearest_dist_autovec(&acc, items, best_dist, best_item)
         }
@@ -424,7 +571,50 @@ where
     ) where
         R: ResultCollection<f32, T>,
     {
-        update_nearest_dists_within_autovec(&acc, items, radius, results)
+        #[cfg(all(feature = "simd", target_arch = "x86_64", target_feature = "avx2"))]
+        unsafe {
+            for_each_dist_within_f32_avx2(&acc, radius, |idx| {
+                results.add(NearestNeighbour {
+                    distance: acc[idx],
+                    item: items[idx],
+                })
+            })
+        }
+
+        #[cfg(all(feature = "simd", target_arch = "aarch64", target_feature = "neon"))]
+        unsafe {
+            for_each_dist_within_f32_neon(&acc, radius, |idx| {
+                results.add(NearestNeighbour {
+                    distance: acc[idx],
+                    item: items[idx],
+                })
+            })
+        }
+
+        #[cfg(all(
+            feature = "simd_stable",
+            not(any(
+                all(feature = "simd", target_arch = "x86_64", target_feature = "avx2"),
+                all(feature = "simd", target_arch = "aarch64", target_feature = "neon")
+            ))
+        ))]
+        {
+            for_each_dist_within_lanes(&acc, radius, |idx| {
+                results.add(NearestNeighbour {
+                    distance: acc[idx],
+                    item: items[idx],
+                })
+            })
+        }
+
+        #[cfg(not(any(
+            all(feature = "simd", target_arch = "x86_64", target_feature = "avx2"),
+            all(feature = "simd", target_arch = "aarch64", target_feature = "neon"),
+            feature = "simd_stable"
+        )))]
+        {
+            update_nearest_dists_within_autovec(&acc, items, radius, results)
+        }
     }
 
     #[inline]
@@ -435,7 +625,41 @@ where
         max_qty: usize,
         results: &mut BinaryHeap<BestNeighbour<f32, T>>,
     ) {
-        update_best_dists_within_autovec(&acc, items, radius, max_qty, results)
+        #[cfg(all(feature = "simd", target_arch = "x86_64", target_feature = "avx2"))]
+        unsafe {
+            for_each_dist_within_f32_avx2(&acc, radius, |idx| {
+                push_best(results, max_qty, acc[idx], items[idx])
+            })
+        }
+
+        #[cfg(all(feature = "simd", target_arch = "aarch64", target_feature = "neon"))]
+        unsafe {
+            for_each_dist_within_f32_neon(&acc, radius, |idx| {
+                push_best(results, max_qty, acc[idx], items[idx])
+            })
+        }
+
+        #[cfg(all(
+            feature = "simd_stable",
+            not(any(
+                all(feature = "simd", target_arch = "x86_64", target_feature = "avx2"),
+                all(feature = "simd", target_arch = "aarch64", target_feature = "neon")
+            ))
+        ))]
+        {
+            for_each_dist_within_lanes(&acc, radius, |idx| {
+                push_best(results, max_qty, acc[idx], items[idx])
+            })
+        }
+
+        #[cfg(not(any(
+            all(feature = "simd", target_arch = "x86_64", target_feature = "avx2"),
+            all(feature = "simd", target_arch = "aarch64", target_feature = "neon"),
+            feature = "simd_stable"
+        )))]
+        {
+            update_best_dists_within_autovec(&acc, items, radius, max_qty, results)
+        }
     }
 }
 
@@ -445,7 +669,7 @@ where
     usize: Cast<T>,
 {
     #[inline]
-    fn dists_for_chunk<D, const C: usize>(chunk: [&[Self; C]; K], query: &[Self; K]) -> [Self; C]
+    fn dists_for_chunk<D, const C: usize>(chunk: [&[Self; C]; K], query: &[Self; K]) -> [f32; C]
     where
         D: DistanceMetric<Self, K>,
         Self: Sized,
@@ -456,7 +680,12 @@ where
             let qd = [query[dim]; C];
 
             (0..C).step_by(1).for_each(|idx| {
-                acc[idx] += D::dist1(chunk[dim][idx], qd[idx]);
+                acc[idx] = accumulate_dist(
+                    acc[idx],
+                    D::dist1(chunk[dim][idx], qd[idx]),
+                    D::MAX_OF_AXES,
+                    |a, b| a + b,
+                );
             });
         });
 
@@ -464,9 +693,46 @@ where
     }
 }
 
+/// Adds `item` to `results` if the point whose coordinate on each axis is given by `point`
+/// is within `radius` of `query`.
+///
+/// This is how `LeafSlice::nearest_n_within` checks the entries left over after its full
+/// chunks, one at a time. It is also used by trees that don't keep their points in a
+/// `LeafSlice`, so that they apply exactly the same rule.
+#[inline(always)]
+pub(crate) fn add_if_within<A, T, D, R, const K: usize>(
+    query: &[A; K],
+    point: impl Fn(usize) -> A,
+    item: T,
+    radius: A,
+    results: &mut R,
+) where
+    A: Axis + LeafSliceFloat<T>,
+    T: Content,
+    D: DistanceMetric<A, K>,
+    R: ResultCollection<A, T>,
+{
+    let mut distance = A::Acc::zero();
+    (0..K).step_by(1).for_each(|dim| {
+        distance = accumulate_dist(
+            distance,
+            D::dist1(point(dim), query[dim]).widen(),
+            D::MAX_OF_AXES,
+            |a, b| a + b,
+        );
+    });
+
+    if distance < radius.widen() {
+        results.add(NearestNeighbour {
+            distance: A::narrow(distance),
+            item,
+        });
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::float_leaf_slice::leaf_slice::{LeafFixedSlice, LeafSliceFloat};
+    use crate::float_leaf_slice::leaf_slice::{LeafFixedSlice, LeafSlice, LeafSliceFloat};
     use crate::{BestNeighbour, NearestNeighbour, SquaredEuclidean};
     use std::collections::BinaryHeap;
 
@@ -493,6 +759,24 @@ mod test {
         assert_eq!(best_item, 1u32);
     }
 
+    #[test]
+    fn leaf_slice_nearest_n_within_labels_items_beyond_first_chunk() {
+        let xs: Vec<f64> = (0..40).map(|i| i as f64).collect();
+        let ys = vec![0f64; 40];
+        let items: Vec<u32> = (100..140).collect();
+
+        let slice = LeafSlice::new([&xs[..], &ys[..]], &items[..]);
+
+        let mut results: Vec<NearestNeighbour<f64, u32>> = Vec::new();
+        slice.nearest_n_within::<SquaredEuclidean, _>(&[0f64, 0f64], 10_000f64, &mut results);
+
+        assert_eq!(results.len(), 40);
+        for result in results {
+            let x = (result.item - 100) as f64;
+            assert_eq!(result.distance, x * x);
+        }
+    }
+
     #[test]
     fn test_f64_leafslicefloat_update_nearest_dists_within() {
         let dists = [10000f64, 20000f64, 20f64];
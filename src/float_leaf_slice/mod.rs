@@ -1,14 +1,19 @@
-pub(crate) mod fallback;
+#[doc(hidden)]
+pub mod fallback;
 pub mod leaf_slice;
 
-// TODO: fix f32 AVX2
+#[cfg(feature = "f16")]
+mod f16;
 
-// #[cfg(all(
-//     feature = "simd",
-//     target_feature = "avx2",
-//     any(target_arch = "x86", target_arch = "x86_64")
-// ))]
-// pub(crate) mod f32_avx2;
+#[cfg(feature = "simd_stable")]
+#[doc(hidden)]
+pub mod lanes;
+
+#[cfg(all(feature = "simd", target_arch = "x86_64", target_feature = "avx2"))]
+pub(crate) mod f32_avx2;
+
+#[cfg(all(feature = "simd", target_arch = "aarch64", target_feature = "neon"))]
+pub(crate) mod f32_neon;
 
 // TODO: fix f64 AVX2
 
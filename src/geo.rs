@@ -0,0 +1,257 @@
+//! Nearest neighbour queries over points on the Earth's surface, given as latitude and
+//! longitude in degrees, with distances in metres or kilometres.
+//!
+//! Requires the `geo` feature.
+use std::num::NonZero;
+
+use az::Cast;
+
+use crate::float::distance::SquaredEuclidean;
+use crate::immutable::float::kdtree::ImmutableKdTree;
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::Content;
+
+/// The mean radius of the Earth, in metres, as used by [`GeoKdTree`] to convert between
+/// angles and distances.
+pub const EARTH_RADIUS_METRES: f64 = 6_371_008.8;
+
+/// An immutable tree of points on the Earth's surface, queried by latitude and longitude.
+///
+/// Points are converted to positions on a unit sphere when the tree is built, and queries
+/// are answered with great-circle distances, treating the Earth as a sphere with a radius
+/// of [`EARTH_RADIUS_METRES`]. This is accurate to within about 0.5% anywhere on Earth,
+/// and avoids the problems that storing latitude and longitude directly has near the poles
+/// and the antimeridian.
+///
+/// Latitudes and longitudes are in degrees. As with
+/// [`ImmutableKdTree::new_from_slice`], each point's item is its index in the slice that
+/// the tree was built from.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::geo::GeoKdTree;
+///
+/// let cities: Vec<[f64; 2]> = vec![
+///     [51.5072, -0.1276],  // London
+///     [48.8566, 2.3522],   // Paris
+///     [52.5200, 13.4050],  // Berlin
+/// ];
+/// let tree: GeoKdTree = GeoKdTree::new_from_lat_lon(&cities);
+///
+/// // Oxford
+/// let nearest = tree.nearest_one(51.7520, -1.2577);
+/// assert_eq!(nearest.item, 0);
+/// assert!((nearest.distance - 82_600.0).abs() < 100.0);
+///
+/// assert!(tree.nearest_within_metres(51.7520, -1.2577, 50_000.0).is_none());
+/// assert_eq!(tree.within_km(51.7520, -1.2577, 500.0).len(), 2);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeoKdTree<T: Copy + Default = u64, const B: usize = 32> {
+    tree: ImmutableKdTree<f64, T, 3, B>,
+}
+
+impl<T: Content, const B: usize> GeoKdTree<T, B>
+where
+    usize: Cast<T>,
+{
+    /// Creates a `GeoKdTree` from points given as `[latitude, longitude]` in degrees.
+    pub fn new_from_lat_lon(points: &[[f64; 2]]) -> Self {
+        let positions: Vec<[f64; 3]> = points
+            .iter()
+            .map(|&[lat, lon]| lat_lon_to_unit_sphere(lat, lon))
+            .collect();
+
+        GeoKdTree {
+            tree: ImmutableKdTree::new_from_slice(&positions),
+        }
+    }
+
+    /// Returns the number of points stored in the tree
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.tree.size()
+    }
+
+    /// Returns the underlying tree, whose points are positions on a unit sphere and whose
+    /// distances are squared chord lengths, for queries that this wrapper doesn't provide.
+    #[inline]
+    pub fn tree(&self) -> &ImmutableKdTree<f64, T, 3, B> {
+        &self.tree
+    }
+
+    /// Finds the nearest point to the given latitude and longitude, with its distance in
+    /// metres.
+    pub fn nearest_one(&self, lat: f64, lon: f64) -> NearestNeighbour<f64, T> {
+        let nearest = self
+            .tree
+            .nearest_one::<SquaredEuclidean>(&lat_lon_to_unit_sphere(lat, lon));
+
+        to_metres(nearest)
+    }
+
+    /// Finds the nearest point that is less than `max_metres` from the given latitude and
+    /// longitude, with its distance in metres, or `None` if there are no points that close.
+    pub fn nearest_within_metres(
+        &self,
+        lat: f64,
+        lon: f64,
+        max_metres: f64,
+    ) -> Option<NearestNeighbour<f64, T>> {
+        self.tree
+            .nearest_one_within::<SquaredEuclidean>(
+                &lat_lon_to_unit_sphere(lat, lon),
+                metres_to_squared_chord(max_metres),
+            )
+            .map(to_metres)
+    }
+
+    /// Finds the nearest `max_qty` points to the given latitude and longitude, nearest
+    /// first, with their distances in metres.
+    pub fn nearest_n(
+        &self,
+        lat: f64,
+        lon: f64,
+        max_qty: NonZero<usize>,
+    ) -> Vec<NearestNeighbour<f64, T>> {
+        self.tree
+            .nearest_n::<SquaredEuclidean>(&lat_lon_to_unit_sphere(lat, lon), max_qty)
+            .into_iter()
+            .map(to_metres)
+            .collect()
+    }
+
+    /// Finds all points within `metres` of the given latitude and longitude, nearest first,
+    /// with their distances in metres.
+    pub fn within_metres(&self, lat: f64, lon: f64, metres: f64) -> Vec<NearestNeighbour<f64, T>> {
+        self.tree
+            .within::<SquaredEuclidean>(
+                &lat_lon_to_unit_sphere(lat, lon),
+                metres_to_squared_chord(metres),
+            )
+            .into_iter()
+            .map(to_metres)
+            .collect()
+    }
+
+    /// Finds all points within `km` kilometres of the given latitude and longitude, nearest
+    /// first, with their distances in kilometres.
+    pub fn within_km(&self, lat: f64, lon: f64, km: f64) -> Vec<NearestNeighbour<f64, T>> {
+        self.within_metres(lat, lon, km * 1000.0)
+            .into_iter()
+            .map(|neighbour| NearestNeighbour {
+                distance: neighbour.distance / 1000.0,
+                item: neighbour.item,
+            })
+            .collect()
+    }
+}
+
+/// Converts a latitude and longitude in degrees to a position on a unit sphere.
+fn lat_lon_to_unit_sphere(lat: f64, lon: f64) -> [f64; 3] {
+    let (lat_sin, lat_cos) = lat.to_radians().sin_cos();
+    let (lon_sin, lon_cos) = lon.to_radians().sin_cos();
+
+    [lat_cos * lon_cos, lat_cos * lon_sin, lat_sin]
+}
+
+/// Converts a great-circle distance in metres to the squared length of the chord between
+/// its ends on a unit sphere. Distances of more than half way around the Earth are
+/// treated as being exactly half way around.
+fn metres_to_squared_chord(metres: f64) -> f64 {
+    let angle = (metres / EARTH_RADIUS_METRES).min(std::f64::consts::PI);
+    let chord = 2.0 * (angle / 2.0).sin();
+
+    chord * chord
+}
+
+/// Converts the squared length of a chord on a unit sphere to the great-circle distance
+/// in metres between its ends.
+fn squared_chord_to_metres(squared_chord: f64) -> f64 {
+    let half_chord = (squared_chord.sqrt() / 2.0).min(1.0);
+
+    2.0 * half_chord.asin() * EARTH_RADIUS_METRES
+}
+
+fn to_metres<T>(neighbour: NearestNeighbour<f64, T>) -> NearestNeighbour<f64, T> {
+    NearestNeighbour {
+        distance: squared_chord_to_metres(neighbour.distance),
+        item: neighbour.item,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{metres_to_squared_chord, squared_chord_to_metres, GeoKdTree, EARTH_RADIUS_METRES};
+    use rand::{Rng, SeedableRng};
+    use std::num::NonZero;
+
+    fn haversine_metres([lat_a, lon_a]: [f64; 2], [lat_b, lon_b]: [f64; 2]) -> f64 {
+        let d_lat = (lat_b - lat_a).to_radians();
+        let d_lon = (lon_b - lon_a).to_radians();
+        let h = (d_lat / 2.0).sin().powi(2)
+            + lat_a.to_radians().cos() * lat_b.to_radians().cos() * (d_lon / 2.0).sin().powi(2);
+
+        2.0 * h.sqrt().min(1.0).asin() * EARTH_RADIUS_METRES
+    }
+
+    #[test]
+    fn distances_round_trip_through_chords() {
+        for metres in [0.0, 1.0, 1_000.0, 50_000.0, 5_000_000.0, 19_000_000.0] {
+            let round_tripped = squared_chord_to_metres(metres_to_squared_chord(metres));
+            assert!(
+                (round_tripped - metres).abs() < 1e-3,
+                "{metres} {round_tripped}"
+            );
+        }
+    }
+
+    #[test]
+    fn queries_match_haversine_brute_force() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(74);
+        let points: Vec<[f64; 2]> = (0..3_000)
+            .map(|_| [rng.gen_range(-90.0..90.0), rng.gen_range(-180.0..180.0)])
+            .collect();
+        let tree: GeoKdTree<u32, 32> = GeoKdTree::new_from_lat_lon(&points);
+        assert_eq!(tree.size(), points.len());
+
+        for _ in 0..100 {
+            let query = [rng.gen_range(-90.0..90.0), rng.gen_range(-180.0..180.0)];
+            let mut expected: Vec<(f64, u32)> = points
+                .iter()
+                .enumerate()
+                .map(|(idx, &point)| (haversine_metres(query, point), idx as u32))
+                .collect();
+            expected.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+            let nearest = tree.nearest_one(query[0], query[1]);
+            assert_eq!(nearest.item, expected[0].1);
+            assert!((nearest.distance - expected[0].0).abs() < 1.0);
+
+            let nearest_n = tree.nearest_n(query[0], query[1], NonZero::new(5).unwrap());
+            assert_eq!(
+                nearest_n.iter().map(|n| n.item).collect::<Vec<_>>(),
+                expected[..5].iter().map(|e| e.1).collect::<Vec<_>>()
+            );
+
+            let radius = 500_000.0;
+            let within = tree.within_metres(query[0], query[1], radius);
+            let expected_within = expected.iter().take_while(|e| e.0 <= radius).count();
+            assert_eq!(within.len(), expected_within);
+            assert_eq!(
+                tree.within_km(query[0], query[1], radius / 1000.0).len(),
+                within.len()
+            );
+
+            assert_eq!(
+                tree.nearest_within_metres(query[0], query[1], expected[0].0 + 1.0)
+                    .map(|n| n.item),
+                Some(expected[0].1)
+            );
+            assert!(tree
+                .nearest_within_metres(query[0], query[1], expected[0].0 - 1.0)
+                .is_none());
+        }
+    }
+}
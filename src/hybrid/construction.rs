@@ -1,4 +1,4 @@
-use crate::float_sss::kdtree::{Axis, KdTree, StemNode};
+use crate::float_sss::kdtree::{Axis, KdTree, LeafNode, SplitPolicy, StemNode};
 use crate::mirror_select_nth_unstable_by::mirror_select_nth_unstable_by;
 use crate::types::{Content, Index};
 use az::{Az, Cast};
@@ -39,6 +39,9 @@ where
         let mut parent_idx = 1;
         let mut val: A = A::zero();
 
+        // the bounds of the region of space that the leaf covers, on each axis
+        let mut cell = [(A::neg_infinity(), A::infinity()); K];
+
         while stem_idx < self.stems.capacity() {
             val = *unsafe { self.stems.get_unchecked(stem_idx) };
 
@@ -56,11 +59,17 @@ where
                     split_dim,
                     leaf_idx,
                     LeafParent::Stem(parent_idx, is_right_child),
+                    cell[split_dim],
                 );
             }
 
             parent_idx = stem_idx;
             is_right_child = *unsafe { query.get_unchecked(split_dim) } > val;
+            if is_right_child {
+                cell[split_dim].0 = val;
+            } else {
+                cell[split_dim].1 = val;
+            }
             stem_idx = (stem_idx << 1) + usize::from(is_right_child);
             split_dim = (split_dim + 1).rem(K);
         }
@@ -75,6 +84,11 @@ where
 
                 parent_idx = stem_idx;
                 is_right_child = *unsafe { query.get_unchecked(split_dim) } > val;
+                if is_right_child {
+                    cell[split_dim].0 = node.split_val;
+                } else {
+                    cell[split_dim].1 = node.split_val;
+                }
 
                 stem_idx = (*unsafe { node.children.get_unchecked(usize::from(is_right_child)) })
                     .az::<usize>();
@@ -87,6 +101,7 @@ where
                 split_dim,
                 leaf_idx,
                 LeafParent::DStem(parent_idx.az::<IDX>(), is_right_child),
+                cell[split_dim],
             );
         } else {
             let leaf_idx: IDX = (stem_idx - self.stems.capacity()).az::<IDX>();
@@ -96,6 +111,7 @@ where
                 split_dim,
                 leaf_idx,
                 LeafParent::Stem(parent_idx, is_right_child),
+                cell[split_dim],
             );
         }
     }
@@ -148,13 +164,14 @@ where
         split_dim: usize,
         leaf_idx: IDX,
         parent: LeafParent<IDX>,
+        cell: (A, A),
     ) {
         let node: &mut super::kdtree::LeafNode<A, T, K, B, IDX>;
 
         let node_size = unsafe { self.leaves.get_unchecked_mut(leaf_idx.az::<usize>()) }.size;
 
         if node_size == B.az::<IDX>() {
-            let (right_leaf_idx, split_val) = self.split(leaf_idx, split_dim, parent);
+            let (right_leaf_idx, split_val) = self.split(leaf_idx, split_dim, parent, cell);
             if query[split_dim] > split_val {
                 node = unsafe { self.leaves.get_unchecked_mut(right_leaf_idx.az::<usize>()) };
             } else {
@@ -244,33 +261,22 @@ where
         removed
     } */
 
-    fn split(&mut self, leaf_idx: IDX, split_dim: usize, parent: LeafParent<IDX>) -> (IDX, A) {
-        let mut split_val: A;
-        let pivot_idx: IDX;
-        {
+    fn split(
+        &mut self,
+        leaf_idx: IDX,
+        split_dim: usize,
+        parent: LeafParent<IDX>,
+        cell: (A, A),
+    ) -> (IDX, A) {
+        let (pivot_idx, mut split_val) = {
             let orig = unsafe { self.leaves.get_unchecked_mut(leaf_idx.az::<usize>()) };
-            pivot_idx = (B / 2).az::<IDX>();
-
-            // partially sort original leaf so that first half of content
-            // is sorted ascending
-            mirror_select_nth_unstable_by(
-                &mut orig.content_points,
-                &mut orig.content_items,
-                pivot_idx.az::<usize>(),
-                |a, b| unsafe {
-                    a.get_unchecked(split_dim)
-                        .partial_cmp(b.get_unchecked(split_dim))
-                        .expect("Leaf node sort failed.")
-                },
-            );
 
-            split_val = unsafe {
-                *orig
-                    .content_points
-                    .get_unchecked(pivot_idx.az::<usize>())
-                    .get_unchecked(split_dim)
-            };
-        }
+            match self.split_policy {
+                SplitPolicy::Median => Self::median_split(orig, split_dim),
+                SplitPolicy::Midpoint => Self::midpoint_split(orig, split_dim, None),
+                SplitPolicy::SlidingMidpoint => Self::midpoint_split(orig, split_dim, Some(cell)),
+            }
+        };
 
         // determine where to copy the upper half of points to
         let mut right_idx;
@@ -406,39 +412,138 @@ where
             }
         }
 
-        let [right, orig] = unsafe {
-            self.leaves
-                .get_many_unchecked_mut([right_idx.az::<usize>(), leaf_idx.az::<usize>()])
+        // the new right leaf is always distinct from the leaf being split, so
+        // split_at_mut can hand out a mutable reference to each of them
+        let (right, orig) = {
+            let right_idx = right_idx.az::<usize>();
+            let leaf_idx = leaf_idx.az::<usize>();
+            debug_assert_ne!(right_idx, leaf_idx);
+
+            if right_idx < leaf_idx {
+                let (lower, upper) = self.leaves.split_at_mut(leaf_idx);
+                (&mut lower[right_idx], &mut upper[0])
+            } else {
+                let (lower, upper) = self.leaves.split_at_mut(right_idx);
+                (&mut upper[0], &mut lower[leaf_idx])
+            }
         };
 
+        // the policies other than the median can leave any number of items on either
+        // side of the pivot, so the right leaf gets everything from the pivot onwards
+        let right_len = B - pivot_idx.az::<usize>();
         unsafe {
             right
                 .content_points
-                .get_unchecked_mut(..(pivot_idx.az::<usize>()))
+                .get_unchecked_mut(..right_len)
                 .copy_from_slice(
                     orig.content_points
                         .get_unchecked((pivot_idx.az::<usize>())..),
                 );
             right
                 .content_items
-                .get_unchecked_mut(..(pivot_idx.az::<usize>()))
+                .get_unchecked_mut(..right_len)
                 .copy_from_slice(
                     orig.content_items
                         .get_unchecked((pivot_idx.az::<usize>())..),
                 );
         }
 
-        right.size = (B.az::<IDX>()) - pivot_idx;
+        right.size = right_len.az::<IDX>();
         orig.size = pivot_idx;
 
         (right_idx, split_val)
     }
+
+    /// Splits a full leaf at the median of its values on `split_dim`, returning the
+    /// index of the first of its items that belong in the right leaf, and the split value.
+    fn median_split(orig: &mut LeafNode<A, T, K, B, IDX>, split_dim: usize) -> (IDX, A) {
+        let pivot_idx = (B / 2).az::<IDX>();
+
+        // partially sort original leaf so that first half of content
+        // is sorted ascending
+        mirror_select_nth_unstable_by(
+            &mut orig.content_points,
+            &mut orig.content_items,
+            pivot_idx.az::<usize>(),
+            |a, b| unsafe {
+                a.get_unchecked(split_dim)
+                    .partial_cmp(b.get_unchecked(split_dim))
+                    .expect("Leaf node sort failed.")
+            },
+        );
+
+        let split_val = unsafe {
+            *orig
+                .content_points
+                .get_unchecked(pivot_idx.az::<usize>())
+                .get_unchecked(split_dim)
+        };
+
+        (pivot_idx, split_val)
+    }
+
+    /// Splits a full leaf halfway between the bounds of its values on `split_dim`, or
+    /// halfway across `cell` where it is bounded, returning the index of the first of its
+    /// items that belong in the right leaf, and the split value.
+    ///
+    /// The split value is slid to the nearest of the leaf's values where needed, so that
+    /// both leaves get at least one item. Leaves whose values on `split_dim` are all the
+    /// same are split at the median instead.
+    fn midpoint_split(
+        orig: &mut LeafNode<A, T, K, B, IDX>,
+        split_dim: usize,
+        cell: Option<(A, A)>,
+    ) -> (IDX, A) {
+        let (min, max) = orig
+            .content_points
+            .iter()
+            .fold((A::infinity(), A::neg_infinity()), |(min, max), point| {
+                (min.min(point[split_dim]), max.max(point[split_dim]))
+            });
+        if min == max {
+            return Self::median_split(orig, split_dim);
+        }
+
+        let (lo, hi) = match cell {
+            Some((lo, hi)) => (
+                if lo.is_finite() { lo } else { min },
+                if hi.is_finite() { hi } else { max },
+            ),
+            None => (min, max),
+        };
+        let two = A::one() + A::one();
+        let mut split_val = lo / two + hi / two;
+
+        // items with values equal to the split value go in the left leaf, so it must be
+        // at least the lowest value, and below the highest
+        if split_val < min {
+            split_val = min;
+        } else if split_val >= max {
+            split_val = orig
+                .content_points
+                .iter()
+                .map(|point| point[split_dim])
+                .filter(|&val| val < max)
+                .fold(min, A::max);
+        }
+
+        let mut pivot_idx = 0;
+        for idx in 0..B {
+            if orig.content_points[idx][split_dim] <= split_val {
+                orig.content_points.swap(idx, pivot_idx);
+                orig.content_items.swap(idx, pivot_idx);
+                pivot_idx += 1;
+            }
+        }
+
+        (pivot_idx.az::<IDX>(), split_val)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
-        float_sss::kdtree::{FloatLSB, KdTree},
+        float_sss::kdtree::{FloatLSB, KdTree, SplitPolicy},
         types::Index,
     };
     use rand::Rng;
@@ -1185,6 +1290,26 @@ mod tests {
         assert_eq!(tree.size(), 1000);
     }
 
+    #[test]
+    fn can_add_sorted_points_with_each_split_policy() {
+        for split_policy in [
+            SplitPolicy::Median,
+            SplitPolicy::Midpoint,
+            SplitPolicy::SlidingMidpoint,
+        ] {
+            let mut tree: KdTree<Flt, u32, 2, 4, u32> =
+                KdTree::with_capacity_and_split_policy(1000, split_policy);
+
+            for i in 0..1000 {
+                let point = [n(i as Flt / 1000f32), n((i * 37 % 1000) as Flt / 1000f32)];
+
+                tree.add(&point, i);
+            }
+
+            assert_eq!(tree.size(), 1000);
+        }
+    }
+
     #[test]
     fn can_add_shitloads_of_random_points() {
         fn rand_data_2d() -> ([f64; 2], u32) {
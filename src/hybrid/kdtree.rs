@@ -101,6 +101,28 @@ pub struct KdTree<A: Copy + Default, T: Copy + Default, const K: usize, const B:
 
     pub(crate) unreserved_leaf_idx: usize,
     pub(crate) optimized_read_only: bool,
+    pub(crate) split_policy: SplitPolicy,
+}
+
+/// How a full leaf chooses the value on its splitting axis at which it is split in two.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SplitPolicy {
+    /// Split at the median of the leaf's values, so that each half gets half of them.
+    /// Works well for uniformly distributed data, but on sorted or lattice input the
+    /// split values creep along behind the data and the tree grows deep on one side.
+    #[default]
+    Median,
+    /// Split halfway between the lowest and highest of the leaf's values.
+    Midpoint,
+    /// Split halfway across the region of space that the leaf covers, sliding the split
+    /// value to the nearest of the leaf's values if this would leave either half empty.
+    /// Keeps the cells of the tree from becoming long and thin on clustered data.
+    SlidingMidpoint,
 }
 
 #[doc(hidden)]
@@ -214,6 +236,26 @@ where
     /// ```
     #[inline]
     pub fn with_capacity(capacity: usize) -> Self {
+        KdTree::with_capacity_and_split_policy(capacity, SplitPolicy::default())
+    }
+
+    /// Creates a new float KdTree, reserving capacity for a specific number of items
+    /// and splitting full leaves according to `split_policy`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::float::kdtree::{KdTree, SplitPolicy};
+    ///
+    /// let mut tree: KdTree<f64, u32, 3, 32, u32> =
+    ///     KdTree::with_capacity_and_split_policy(1_000, SplitPolicy::SlidingMidpoint);
+    ///
+    /// tree.add(&[1.0, 2.0, 5.0], 100);
+    ///
+    /// assert_eq!(tree.size(), 1);
+    /// ```
+    #[inline]
+    pub fn with_capacity_and_split_policy(capacity: usize, split_policy: SplitPolicy) -> Self {
         assert!(capacity <= <IDX as Index>::capacity_with_bucket_size(B));
 
         let leaf_capacity = DivCeil::div_ceil(capacity, B.az::<usize>()).next_power_of_two();
@@ -246,6 +288,7 @@ where
             leaves,
             unreserved_leaf_idx: leaf_capacity,
             optimized_read_only: false,
+            split_policy,
         };
 
         tree.leaves[0].size = IDX::zero();
@@ -313,6 +356,7 @@ where
             leaves,
             unreserved_leaf_idx: leaf_node_count,
             optimized_read_only: true,
+            split_policy: SplitPolicy::default(),
         };
 
         for (idx, point) in source.iter().enumerate() {
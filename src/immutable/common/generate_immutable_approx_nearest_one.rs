@@ -11,46 +11,10 @@ macro_rules! generate_immutable_approx_nearest_one {
                 D: DistanceMetric<A, K>,
                 usize: Cast<T>,
             {
-                #[cfg(feature = "modified_van_emde_boas")]
-                use $crate::modified_van_emde_boas::modified_van_emde_boas_get_child_idx_v2;
-
-                #[cfg(feature = "modified_van_emde_boas")]
-                let mut curr_idx: usize = 0;
-                #[cfg(not(feature = "modified_van_emde_boas"))]
-                let mut curr_idx: usize = 1;
-
-                let mut dim: usize = 0;
                 let mut best_item = T::zero();
                 let mut best_dist = A::max_value();
-                let mut level: usize = 0;
-                let mut leaf_idx: usize = 0;
-
-                while level as isize <= self.max_stem_level as isize {
-                    let val = *unsafe { self.stems.get_unchecked(curr_idx) };
-                    let is_right_child = *unsafe { query.get_unchecked(dim) } >= val;
-
-                    #[cfg(feature = "modified_van_emde_boas")]
-                    let next_idx = modified_van_emde_boas_get_child_idx_v2(curr_idx as u32, is_right_child, level as u32) as usize;
-                    #[cfg(not(feature = "modified_van_emde_boas"))]
-                    let next_idx = (curr_idx << 1) + usize::from(is_right_child);
 
-                    curr_idx = next_idx;
-
-                    let is_right_child = usize::from(is_right_child);
-                    leaf_idx = (leaf_idx << 1) + is_right_child;
-
-                    level += 1;
-                    dim = (dim + 1) % K;
-                }
-
-                let (start, end) = unsafe { *self.leaf_extents.get_unchecked(leaf_idx) };
-
-                let leaf_slice = $crate::float_leaf_slice::leaf_slice::LeafSlice::new(
-                    array_init::array_init(|i|
-                        &self.leaf_points[i][start as usize..end as usize]
-                    ),
-                    &self.leaf_items[start as usize..end as usize],
-                );
+                let leaf_slice = self.get_leaf_slice(self.leaf_for_point(query));
 
                 leaf_slice.nearest_one::<D>(
                     query,
@@ -64,5 +28,47 @@ macro_rules! generate_immutable_approx_nearest_one {
                 }
             }
         }
+
+        /// Returns the index of the leaf that `query` falls into, found by descending the
+        /// tree's stems without any backtracking. Suitable for passing to `get_leaf_slice`.
+        ///
+        /// The leaf is the one that would hold `query` if it were stored in the tree, except
+        /// that points lying exactly on a split value may be stored in the leaf on either
+        /// side of it.
+        #[inline]
+        pub fn leaf_for_point(&self, query: &[A; K]) -> usize {
+            #[cfg(feature = "modified_van_emde_boas")]
+            use $crate::modified_van_emde_boas::modified_van_emde_boas_get_child_idx_v2;
+
+            #[cfg(feature = "modified_van_emde_boas")]
+            let mut curr_idx: usize = 0;
+            #[cfg(not(feature = "modified_van_emde_boas"))]
+            let mut curr_idx: usize = 1;
+
+            let mut dim: usize = 0;
+            let mut level: usize = 0;
+            let mut leaf_idx: usize = 0;
+
+            while level as isize <= self.max_stem_level as isize {
+                let split_dim = self.split_dim_at(curr_idx, dim);
+                let val = *unsafe { self.stems.get_unchecked(curr_idx) };
+                let is_right_child = *unsafe { query.get_unchecked(split_dim) } >= val;
+
+                #[cfg(feature = "modified_van_emde_boas")]
+                let next_idx = modified_van_emde_boas_get_child_idx_v2(curr_idx as u32, is_right_child, level as u32) as usize;
+                #[cfg(not(feature = "modified_van_emde_boas"))]
+                let next_idx = (curr_idx << 1) + usize::from(is_right_child);
+
+                curr_idx = next_idx;
+
+                let is_right_child = usize::from(is_right_child);
+                leaf_idx = (leaf_idx << 1) + is_right_child;
+
+                level += 1;
+                dim = (dim + 1) % K;
+            }
+
+            leaf_idx
+        }
     };
 }
@@ -53,9 +53,46 @@ macro_rules! generate_immutable_best_n_within {
                     0,
                 );
 
+                #[cfg(feature = "verify_results")]
+                let best_items = {
+                    let best_items = best_items.into_vec();
+                    $crate::verify::verify_best_n_within(self, &$crate::verify::LeafMetric::<D, T>::new(), query, dist, max_qty.into(), &best_items);
+                    best_items
+                };
+
                 best_items.into_iter()
             }
 
+            /// Finds the "best" `max_qty` elements within `dist` of `query`, where "best" means
+            /// having the lowest score, as returned by `score` for the item and its distance.
+            ///
+            /// Generalizes `best_n_within`, which always prefers the lowest items, to any
+            /// ordering, such as by a property of each item that is stored outside the tree.
+            /// Results are sorted by score, lowest first.
+            #[inline]
+            pub fn best_n_within_by<D, S, F>(
+                &self,
+                query: &[A; K],
+                dist: A,
+                max_qty: NonZero<usize>,
+                score: F,
+            ) -> Vec<BestNeighbour<A, T>>
+            where
+                A: LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+                usize: Cast<T>,
+                D: DistanceMetric<A, K>,
+                S: PartialOrd,
+                F: Fn(T, A) -> S,
+            {
+                let mut best_items = $crate::best_neighbour::BestByScore::new(max_qty.get());
+
+                self.within_unsorted_visit::<D, _>(query, dist, |item, distance| {
+                    best_items.add(distance, item, score(item, distance))
+                });
+
+                best_items.into_sorted_vec()
+            }
+
             #[cfg(not(feature = "modified_van_emde_boas"))]
             #[allow(clippy::too_many_arguments)]
             fn best_n_within_recurse<D>(
@@ -80,6 +117,7 @@ macro_rules! generate_immutable_best_n_within {
                     return;
                 }
 
+                let split_dim = self.split_dim_at(stem_idx as usize, split_dim);
                 let val = *unsafe { self.stems.get_unchecked(stem_idx as usize) };
                 let is_right_child = usize::from(*unsafe { query.get_unchecked(split_dim as usize) } >= val);
 
@@ -110,7 +148,7 @@ macro_rules! generate_immutable_best_n_within {
                     closer_leaf_idx,
                 );
 
-                rd = Axis::rd_update(rd, D::dist1(new_off, old_off));
+                rd = $crate::traits::accumulate_dist(rd, D::dist1(new_off, old_off), D::MAX_OF_AXES, Axis::rd_update);
 
                 if rd <= radius {
                     off[split_dim] = new_off;
@@ -158,6 +196,7 @@ macro_rules! generate_immutable_best_n_within {
                     return;
                 }
 
+                let split_dim = self.split_dim_at(stem_idx as usize, split_dim);
                 let val = *unsafe { self.stems.get_unchecked(stem_idx as usize) };
                 let is_right_child = usize::from(*unsafe { query.get_unchecked(split_dim as usize) } >= val);
 
@@ -191,7 +230,7 @@ macro_rules! generate_immutable_best_n_within {
                     closer_leaf_idx,
                 );
 
-                rd = Axis::rd_update(rd, D::dist1(new_off, old_off));
+                rd = $crate::traits::accumulate_dist(rd, D::dist1(new_off, old_off), D::MAX_OF_AXES, Axis::rd_update);
 
                 if rd <= radius {
                     off[split_dim] = new_off;
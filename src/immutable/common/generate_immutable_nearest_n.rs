@@ -14,5 +14,48 @@ macro_rules! generate_immutable_nearest_n {
                 self.nearest_n_within::<D>(query, A::infinity(), max_qty, true)
             }
         }
+
+        /// Finds the nearest `max_qty` elements to `query`, sorted by distance, writing them
+        /// into `out`.
+        ///
+        /// Behaves the same as `nearest_n`, but clears and reuses `out` rather than returning
+        /// a new `Vec`, so a loop that makes many queries with the same buffer only allocates
+        /// when `out` needs to grow.
+        #[inline]
+        pub fn nearest_n_into<D>(&self, query: &[A; K], max_qty: NonZero<usize>, out: &mut Vec<NearestNeighbour<A, T>>)
+        where
+            A: LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+            D: DistanceMetric<A, K>,
+            usize: Cast<T>,
+        {
+            let results = $crate::common::nearest_n_results::ResultBuffer::new(core::mem::take(out), max_qty.get());
+            *out = self.nearest_n_within_stub::<D, _>(query, A::infinity(), results, true);
+
+            #[cfg(feature = "verify_results")]
+            $crate::verify::verify_nearest_n_within(self, &$crate::verify::LeafMetric::<D, T>::new(), query, None, max_qty.get(), out);
+        }
+
+        /// Finds the distance from `query` to its `k`-th nearest neighbour, where a `k` of
+        /// one gives the distance to the nearest.
+        ///
+        /// Only the distances to the nearest `k` neighbours found so far are kept while
+        /// searching, in a bounded max-heap, rather than the neighbours themselves, making this
+        /// suitable for estimators of local density that call it in tight loops.
+        /// Returns infinity if the tree holds fewer than `k` items.
+        #[inline]
+        pub fn kth_nearest_distance<D>(&self, query: &[A; K], k: NonZero<usize>) -> A
+        where
+            A: LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+            D: DistanceMetric<A, K>,
+            usize: Cast<T>,
+        {
+            self.nearest_n_within_collect::<D, _>(
+                query,
+                A::infinity(),
+                $crate::common::nearest_n_results::KthDistance::new(k.get()),
+            )
+            .kth_dist()
+            .unwrap_or(A::infinity())
+        }
     };
 }
@@ -11,50 +11,94 @@ macro_rules! generate_immutable_nearest_n_within {
             {
                 let max_items = max_items.into();
 
-                if sorted && max_items < usize::MAX {
+                let result = if max_items < usize::MAX {
                     if max_items <= MAX_VEC_RESULT_SIZE {
-                        self.nearest_n_within_stub::<D, SortedVec<NearestNeighbour<A, T>>>(query, dist, max_items, sorted)
+                        self.nearest_n_within_stub::<D, _>(query, dist, SortedVec::with_capacity(max_items), sorted)
                     } else {
-                        self.nearest_n_within_stub::<D, BinaryHeap<NearestNeighbour<A, T>>>(query, dist, max_items, sorted)
+                        self.nearest_n_within_stub::<D, _>(query, dist, BinaryHeap::with_capacity(max_items), sorted)
                     }
                 } else {
-                    self.nearest_n_within_stub::<D, Vec<NearestNeighbour<A,T>>>(query, dist, 0, sorted)
-                }
+                    self.nearest_n_within_stub::<D, _>(query, dist, Vec::new(), sorted)
+                };
+
+                #[cfg(feature = "verify_results")]
+                $crate::verify::verify_nearest_n_within(self, &$crate::verify::LeafMetric::<D, T>::new(), query, Some(dist), max_items, &result);
+
+                result
             }
 
-            fn nearest_n_within_stub<D: DistanceMetric<A, K>, H: ResultCollection<A, T>>(
-                &self, query: &[A; K], dist: A, res_capacity: usize, sorted: bool
-            ) -> Vec<NearestNeighbour<A, T>> {
-                let mut matching_items = H::new_with_capacity(res_capacity);
-                let mut off = [A::zero(); K];
+            /// Finds the nearest `max_qty` elements to `query` whose item satisfies `filter`,
+            /// sorted by distance.
+            ///
+            /// `filter` is applied to each item as the tree is searched, rather than to the
+            /// results afterwards, so items that it rejects never take up space in the results.
+            /// For example, the neighbours of a point that is itself stored in the tree can be
+            /// found without the point itself being returned, even if other items share its
+            /// coordinates, by passing `|item| item != own_item`.
+            #[inline]
+            pub fn nearest_n_filtered<D, F>(
+                &self,
+                query: &[A; K],
+                max_qty: NonZero<usize>,
+                filter: F,
+            ) -> Vec<NearestNeighbour<A, T>>
+            where
+                D: DistanceMetric<A, K>,
+                F: Fn(T) -> bool,
+            {
+                let max_qty = max_qty.get();
 
-                #[cfg(not(feature = "modified_van_emde_boas"))]
-                self.nearest_n_within_recurse::<D, H>(
-                    query,
-                    dist,
-                    1,
-                    0,
-                    &mut matching_items,
-                    &mut off,
-                    A::zero(),
-                    0,
-                    0,
-                );
+                if max_qty <= MAX_VEC_RESULT_SIZE {
+                    self.nearest_n_within_stub::<D, _>(
+                        query,
+                        A::infinity(),
+                        $crate::float::result_collection::FilteredResults {
+                            results: SortedVec::with_capacity(max_qty),
+                            filter: &filter,
+                        },
+                        true,
+                    )
+                } else {
+                    self.nearest_n_within_stub::<D, _>(
+                        query,
+                        A::infinity(),
+                        $crate::float::result_collection::FilteredResults {
+                            results: BinaryHeap::with_capacity(max_qty),
+                            filter: &filter,
+                        },
+                        true,
+                    )
+                }
+            }
 
-                #[cfg(feature = "modified_van_emde_boas")]
-                self.nearest_n_within_recurse::<D, H>(
+            /// Finds the nearest `N` elements to `query`, sorted by distance, without allocating.
+            ///
+            /// Behaves the same as `nearest_n`, but returns an array rather than a `Vec`, making
+            /// it suitable for small `N` in loops where allocation must be avoided. If the tree
+            /// holds fewer than `N` items, the remaining entries have a distance of
+            /// infinity and an item of zero.
+            #[inline]
+            pub fn nearest_n_const<D, const N: usize>(&self, query: &[A; K]) -> [NearestNeighbour<A, T>; N]
+            where
+                D: DistanceMetric<A, K>,
+            {
+                let result = self.nearest_n_within_collect::<D, _>(
                     query,
-                    dist,
-                    0,
-                    0,
-                    &mut matching_items,
-                    &mut off,
-                    A::zero(),
-                    0,
-                    0,
-                    0,
+                    A::infinity(),
+                    $crate::common::nearest_n_results::SortedArray::<A, T, N>::new(A::infinity()),
                 );
 
+                #[cfg(feature = "verify_results")]
+                $crate::verify::verify_nearest_n_within(self, &$crate::verify::LeafMetric::<D, T>::new(), query, None, N, result.as_slice());
+
+                result.into_array()
+            }
+
+            pub(crate) fn nearest_n_within_stub<D: DistanceMetric<A, K>, H: ResultCollection<A, T>>(
+                &self, query: &[A; K], dist: A, matching_items: H, sorted: bool
+            ) -> Vec<NearestNeighbour<A, T>> {
+                let matching_items = self.nearest_n_within_collect::<D, H>(query, dist, matching_items);
+
                 if sorted {
                     matching_items.into_sorted_vec()
                 } else {
@@ -62,172 +106,258 @@ macro_rules! generate_immutable_nearest_n_within {
                 }
             }
 
-            #[allow(clippy::too_many_arguments)]
-            #[cfg(not(feature = "modified_van_emde_boas"))]
-            fn nearest_n_within_recurse<D, R>(
+            #[inline]
+            fn search_leaf_for_nearest_n_within<D, R>(
                 &self,
                 query: &[A; K],
                 radius: A,
-                stem_idx: usize,
-                split_dim: usize,
-                matching_items: &mut R,
-                off: &mut [A; K],
-                rd: A,
-                mut level: usize,
-                mut leaf_idx: usize,
+                results: &mut R,
+                leaf_idx: usize,
             ) where
                 D: DistanceMetric<A, K>,
                 R: ResultCollection<A, T>,
             {
-                if level > self.max_stem_level as usize || self.stems.is_empty() {
-                    self.search_leaf_for_nearest_n_within::<D, R>(query, radius, matching_items, leaf_idx as usize);
-                    return;
-                }
+                let leaf_slice = self.get_leaf_slice(leaf_idx);
 
-                let val = *unsafe { self.stems.get_unchecked(stem_idx as usize) };
-                let is_right_child = usize::from(*unsafe { query.get_unchecked(split_dim as usize) } >= val);
+                leaf_slice.nearest_n_within::<D, R>(
+                    query,
+                    radius,
+                    results,
+                );
+            }
+        }
+
+        $crate::generate_immutable_nearest_n_within_traversal!();
+    };
+}
 
-                leaf_idx <<= 1;
-                let closer_leaf_idx = leaf_idx + is_right_child;
-                let further_leaf_idx = leaf_idx + (1 - is_right_child);
+/// Generates `nearest_n_within_collect`, and the Eytzinger and vEB traversals behind it, for
+/// any immutable tree that has `stems`, `max_stem_level` and `split_dim_at`.
+///
+/// Leaves are scanned by calling `self.search_leaf_for_nearest_n_within`, which each tree
+/// provides, so trees that store their leaves differently can share the same traversal.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! generate_immutable_nearest_n_within_traversal {
+    () => {
+        pub(crate) fn nearest_n_within_collect<
+            D: DistanceMetric<A, K>,
+            H: ResultCollection<A, T>,
+        >(
+            &self,
+            query: &[A; K],
+            dist: A,
+            mut matching_items: H,
+        ) -> H {
+            let mut off = [A::zero(); K];
 
-                let closer_node_idx = (stem_idx << 1) + is_right_child;
-                let further_node_idx = (stem_idx << 1) + 1 - is_right_child;
+            #[cfg(not(feature = "modified_van_emde_boas"))]
+            self.nearest_n_within_recurse::<D, H>(
+                query,
+                dist,
+                1,
+                0,
+                &mut matching_items,
+                &mut off,
+                A::zero(),
+                0,
+                0,
+            );
 
-                let mut rd = rd;
-                let old_off = off[split_dim];
-                let new_off = query[split_dim].saturating_dist(val);
+            #[cfg(feature = "modified_van_emde_boas")]
+            self.nearest_n_within_recurse::<D, H>(
+                query,
+                dist,
+                0,
+                0,
+                &mut matching_items,
+                &mut off,
+                A::zero(),
+                0,
+                0,
+                0,
+            );
 
-                level += 1;
-                let next_split_dim = (split_dim + 1).rem(K);
+            matching_items
+        }
 
-                self.nearest_n_within_recurse::<D, R>(
+        #[allow(clippy::too_many_arguments)]
+        #[cfg(not(feature = "modified_van_emde_boas"))]
+        pub(crate) fn nearest_n_within_recurse<D, R>(
+            &self,
+            query: &[A; K],
+            radius: A,
+            stem_idx: usize,
+            split_dim: usize,
+            matching_items: &mut R,
+            off: &mut [A; K],
+            rd: A,
+            mut level: usize,
+            mut leaf_idx: usize,
+        ) where
+            D: DistanceMetric<A, K>,
+            R: ResultCollection<A, T>,
+        {
+            if level > self.max_stem_level as usize || self.stems.is_empty() {
+                self.search_leaf_for_nearest_n_within::<D, R>(
                     query,
                     radius,
-                    closer_node_idx,
-                    next_split_dim,
                     matching_items,
-                    off,
-                    rd,
-                    level,
-                    closer_leaf_idx,
+                    leaf_idx as usize,
                 );
-
-                rd = Axis::rd_update(rd, D::dist1(new_off, old_off));
-
-                if rd <= radius && rd < matching_items.max_dist() {
-                    off[split_dim] = new_off;
-                    self.nearest_n_within_recurse::<D, R>(
-                        query,
-                        radius,
-                        further_node_idx,
-                        next_split_dim,
-                        matching_items,
-                        off,
-                        rd,
-                        level,
-                        further_leaf_idx,
-                    );
-                    off[split_dim] = old_off;
-                }
+                return;
             }
 
-            #[cfg(feature = "modified_van_emde_boas")]
-            #[allow(clippy::too_many_arguments)]
-            fn nearest_n_within_recurse<D, R>(
-                &self,
-                query: &[A; K],
-                radius: A,
-                stem_idx: u32,
-                split_dim: usize,
-                matching_items: &mut R,
-                off: &mut [A; K],
-                rd: A,
-                mut level: i32,
-                mut minor_level: u32,
-                mut leaf_idx: usize,
-            ) where
-                D: DistanceMetric<A, K>,
-                R: ResultCollection<A, T>,
-            {
-                use cmov::Cmov;
-                use $crate::modified_van_emde_boas::modified_van_emde_boas_get_child_idx_v2_branchless;
+            let split_dim = self.split_dim_at(stem_idx as usize, split_dim);
+            let val = *unsafe { self.stems.get_unchecked(stem_idx as usize) };
+            let is_right_child =
+                usize::from(*unsafe { query.get_unchecked(split_dim as usize) } >= val);
 
-                if level > self.max_stem_level || self.stems.is_empty() {
-                    self.search_leaf_for_nearest_n_within::<D, R>(query, radius, matching_items, leaf_idx as usize);
-                    return;
-                }
+            leaf_idx <<= 1;
+            let closer_leaf_idx = leaf_idx + is_right_child;
+            let further_leaf_idx = leaf_idx + (1 - is_right_child);
 
-                let val = *unsafe { self.stems.get_unchecked(stem_idx as usize) };
-                let is_right_child = usize::from(*unsafe { query.get_unchecked(split_dim as usize) } >= val);
+            let closer_node_idx = (stem_idx << 1) + is_right_child;
+            let further_node_idx = (stem_idx << 1) + 1 - is_right_child;
 
-                leaf_idx <<= 1;
-                let closer_leaf_idx = leaf_idx + is_right_child;
-                let further_leaf_idx = leaf_idx + (1 - is_right_child);
+            let mut rd = rd;
+            let old_off = off[split_dim];
+            let new_off = query[split_dim].saturating_dist(val);
 
-                let closer_node_idx = modified_van_emde_boas_get_child_idx_v2_branchless(stem_idx, is_right_child == 1, minor_level);
-                let further_node_idx = modified_van_emde_boas_get_child_idx_v2_branchless(stem_idx, is_right_child == 0, minor_level);
+            level += 1;
+            let next_split_dim = (split_dim + 1).rem(K);
 
-                let mut rd = rd;
-                let old_off = off[split_dim];
-                let new_off = query[split_dim].saturating_dist(val);
+            self.nearest_n_within_recurse::<D, R>(
+                query,
+                radius,
+                closer_node_idx,
+                next_split_dim,
+                matching_items,
+                off,
+                rd,
+                level,
+                closer_leaf_idx,
+            );
 
-                level += 1;
-                let next_split_dim = (split_dim + 1).rem(K);
-                minor_level += 1;
-                minor_level.cmovnz(&0, u8::from(minor_level == 3));
+            rd = $crate::traits::accumulate_dist(
+                rd,
+                D::dist1(new_off, old_off),
+                D::MAX_OF_AXES,
+                Axis::rd_update,
+            );
 
+            if rd <= radius && rd <= matching_items.max_dist() {
+                off[split_dim] = new_off;
                 self.nearest_n_within_recurse::<D, R>(
                     query,
                     radius,
-                    closer_node_idx,
+                    further_node_idx,
                     next_split_dim,
                     matching_items,
                     off,
                     rd,
                     level,
-                    minor_level,
-                    closer_leaf_idx,
+                    further_leaf_idx,
                 );
+                off[split_dim] = old_off;
+            }
+        }
 
-                rd = Axis::rd_update(rd, D::dist1(new_off, old_off));
+        #[cfg(feature = "modified_van_emde_boas")]
+        #[allow(clippy::too_many_arguments)]
+        pub(crate) fn nearest_n_within_recurse<D, R>(
+            &self,
+            query: &[A; K],
+            radius: A,
+            stem_idx: u32,
+            split_dim: usize,
+            matching_items: &mut R,
+            off: &mut [A; K],
+            rd: A,
+            mut level: i32,
+            mut minor_level: u32,
+            mut leaf_idx: usize,
+        ) where
+            D: DistanceMetric<A, K>,
+            R: ResultCollection<A, T>,
+        {
+            use cmov::Cmov;
+            use $crate::modified_van_emde_boas::modified_van_emde_boas_get_child_idx_v2_branchless;
 
-                if rd <= radius && rd < matching_items.max_dist() {
-                    off[split_dim] = new_off;
-                    self.nearest_n_within_recurse::<D, R>(
-                        query,
-                        radius,
-                        further_node_idx,
-                        next_split_dim,
-                        matching_items,
-                        off,
-                        rd,
-                        level,
-                        minor_level,
-                        further_leaf_idx,
-                    );
-                    off[split_dim] = old_off;
-                }
+            if level > self.max_stem_level || self.stems.is_empty() {
+                self.search_leaf_for_nearest_n_within::<D, R>(
+                    query,
+                    radius,
+                    matching_items,
+                    leaf_idx as usize,
+                );
+                return;
             }
 
-            #[inline]
-            fn search_leaf_for_nearest_n_within<D, R>(
-                &self,
-                query: &[A; K],
-                radius: A,
-                results: &mut R,
-                leaf_idx: usize,
-            ) where
-                D: DistanceMetric<A, K>,
-                R: ResultCollection<A, T>,
-            {
-                let leaf_slice = self.get_leaf_slice(leaf_idx);
+            let split_dim = self.split_dim_at(stem_idx as usize, split_dim);
+            let val = *unsafe { self.stems.get_unchecked(stem_idx as usize) };
+            let is_right_child =
+                usize::from(*unsafe { query.get_unchecked(split_dim as usize) } >= val);
 
-                leaf_slice.nearest_n_within::<D, R>(
+            leaf_idx <<= 1;
+            let closer_leaf_idx = leaf_idx + is_right_child;
+            let further_leaf_idx = leaf_idx + (1 - is_right_child);
+
+            let closer_node_idx = modified_van_emde_boas_get_child_idx_v2_branchless(
+                stem_idx,
+                is_right_child == 1,
+                minor_level,
+            );
+            let further_node_idx = modified_van_emde_boas_get_child_idx_v2_branchless(
+                stem_idx,
+                is_right_child == 0,
+                minor_level,
+            );
+
+            let mut rd = rd;
+            let old_off = off[split_dim];
+            let new_off = query[split_dim].saturating_dist(val);
+
+            level += 1;
+            let next_split_dim = (split_dim + 1).rem(K);
+            minor_level += 1;
+            minor_level.cmovnz(&0, u8::from(minor_level == 3));
+
+            self.nearest_n_within_recurse::<D, R>(
+                query,
+                radius,
+                closer_node_idx,
+                next_split_dim,
+                matching_items,
+                off,
+                rd,
+                level,
+                minor_level,
+                closer_leaf_idx,
+            );
+
+            rd = $crate::traits::accumulate_dist(
+                rd,
+                D::dist1(new_off, old_off),
+                D::MAX_OF_AXES,
+                Axis::rd_update,
+            );
+
+            if rd <= radius && rd <= matching_items.max_dist() {
+                off[split_dim] = new_off;
+                self.nearest_n_within_recurse::<D, R>(
                     query,
                     radius,
-                    results,
+                    further_node_idx,
+                    next_split_dim,
+                    matching_items,
+                    off,
+                    rd,
+                    level,
+                    minor_level,
+                    further_leaf_idx,
                 );
+                off[split_dim] = old_off;
             }
         }
     };
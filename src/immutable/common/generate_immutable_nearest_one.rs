@@ -9,207 +9,492 @@ macro_rules! generate_immutable_nearest_one {
                 where
                     D: DistanceMetric<A, K>,
             {
-                let mut off = [A::zero(); K];
-                let mut result = NearestNeighbour {
-                    distance: A::max_value(),
-                    item: T::zero(),
-                };
-
-                if self.stems.is_empty() {
-                    self.search_leaf_for_nearest_one::<D>(query, &mut result, 0);
-                    return result;
+                self.nearest_one_and_leaf_idx::<D>(query).0
+            }
+        }
+
+        /// Finds the nearest element to `query`, returning the coordinates that it was
+        /// stored at alongside its distance and item.
+        ///
+        /// Behaves the same as `nearest_one`, but saves having to maintain a separate
+        /// lookup from item to point.
+        #[inline]
+        pub fn nearest_one_point<D>(
+            &self,
+            query: &[A; K],
+        ) -> $crate::nearest_neighbour::NearestNeighbourWithPoint<A, T, K>
+        where
+            D: DistanceMetric<A, K>,
+        {
+            let (result, leaf_idx) = self.nearest_one_and_leaf_idx::<D>(query);
+
+            // The leaf kernels only track the item, so recover the point by
+            // re-scanning the leaf that the result came from.
+            let leaf_slice = self.get_leaf_slice(leaf_idx);
+            let mut point = [A::zero(); K];
+            let mut best_dist = A::infinity();
+            for (idx, item) in leaf_slice.content_items.iter().enumerate() {
+                if *item == result.item {
+                    let candidate: [A; K] =
+                        std::array::from_fn(|dim| leaf_slice.content_points[dim][idx]);
+                    let dist = D::dist(query, &candidate);
+                    if dist < best_dist {
+                        best_dist = dist;
+                        point = candidate;
+                    }
                 }
+            }
+
+            $crate::nearest_neighbour::NearestNeighbourWithPoint {
+                distance: result.distance,
+                item: result.item,
+                point,
+            }
+        }
+
+        /// Finds the nearest element to `query` that is less than `max_dist` from it,
+        /// or `None` if there are no elements that close.
+        ///
+        /// Faster than calling `nearest_one` and checking the distance of the result
+        /// afterwards, as `max_dist` is used to prune the search from the outset.
+        #[inline]
+        pub fn nearest_one_within<D>(
+            &self,
+            query: &[A; K],
+            max_dist: A,
+        ) -> Option<NearestNeighbour<A, T>>
+        where
+            D: DistanceMetric<A, K>,
+        {
+            let (result, _) = self.nearest_one_bounded::<D, _>(query, max_dist, &mut ());
+            let result = (result.distance < max_dist).then_some(result);
+
+            #[cfg(feature = "verify_results")]
+            $crate::verify::verify_nearest_n_within(
+                self,
+                &$crate::verify::LeafMetric::<D, T>::new(),
+                query,
+                Some(max_dist),
+                1,
+                result.as_slice(),
+            );
+
+            result
+        }
+
+        /// Finds the nearest element to `query` whose item satisfies `filter`, or `None`
+        /// if no items do.
+        ///
+        /// `filter` is applied to each item as the tree is searched. For example, the nearest
+        /// neighbour of a point that is itself stored in the tree can be found, even if other
+        /// items share its coordinates, by passing `|item| item != own_item`.
+        #[inline]
+        pub fn nearest_one_filtered<D, F>(
+            &self,
+            query: &[A; K],
+            filter: F,
+        ) -> Option<NearestNeighbour<A, T>>
+        where
+            D: DistanceMetric<A, K>,
+            F: Fn(T) -> bool,
+        {
+            self.nearest_n_filtered::<D, F>(query, std::num::NonZero::<usize>::MIN, filter)
+                .pop()
+        }
+
+        /// Finds the nearest element to `query`, along with counts of the stems visited,
+        /// leaves scanned and points compared whilst finding it.
+        ///
+        /// Returns the same result as `nearest_one`. Requires the `stats` feature.
+        #[cfg(feature = "stats")]
+        #[inline]
+        pub fn nearest_one_with_stats<D>(
+            &self,
+            query: &[A; K],
+        ) -> (NearestNeighbour<A, T>, $crate::stats::QueryStats)
+        where
+            D: DistanceMetric<A, K>,
+        {
+            let mut stats = $crate::stats::QueryStats::default();
+            let (result, _) = self.nearest_one_bounded::<D, _>(query, A::max_value(), &mut stats);
+
+            #[cfg(feature = "verify_results")]
+            $crate::verify::verify_nearest_one(
+                self,
+                &$crate::verify::LeafMetric::<D, T>::new(),
+                query,
+                &result,
+            );
+
+            (result, stats)
+        }
+
+        #[inline]
+        fn nearest_one_and_leaf_idx<D>(&self, query: &[A; K]) -> (NearestNeighbour<A, T>, usize)
+        where
+            D: DistanceMetric<A, K>,
+        {
+            let (result, nearest_leaf_idx) =
+                self.nearest_one_bounded::<D, _>(query, A::max_value(), &mut ());
 
-                #[cfg(not(feature = "modified_van_emde_boas"))]
-                let initial_stem_idx = 1;
-                #[cfg(feature = "modified_van_emde_boas")]
-                let initial_stem_idx = 0;
+            #[cfg(feature = "verify_results")]
+            $crate::verify::verify_nearest_one(
+                self,
+                &$crate::verify::LeafMetric::<D, T>::new(),
+                query,
+                &result,
+            );
 
-                #[cfg(not(feature = "modified_van_emde_boas"))]
-                self.nearest_one_recurse::<D>(
+            (result, nearest_leaf_idx)
+        }
+
+        /// Finds the nearest element to `query`, starting the search from the leaf that
+        /// `hint` points at, and then updating `hint` to point at the leaf that the result
+        /// came from.
+        ///
+        /// Returns the same result as `nearest_one`. When successive queries are close to
+        /// one another, such as when tracking a moving object, the leaf that held the
+        /// previous result usually holds a close neighbour of the next query too. Scanning
+        /// it first gives a tight bound on the distance from the outset, allowing more of
+        /// the tree to be skipped.
+        #[inline]
+        pub fn nearest_one_with_hint<D>(
+            &self,
+            query: &[A; K],
+            hint: &mut $crate::nearest_neighbour::NearestHint,
+        ) -> NearestNeighbour<A, T>
+        where
+            D: DistanceMetric<A, K>,
+        {
+            let mut result = NearestNeighbour {
+                distance: A::max_value(),
+                item: T::zero(),
+            };
+            let mut nearest_leaf_idx = 0;
+
+            // hints from another tree may not point at a leaf of this one
+            if hint.leaf_idx < self.leaf_count() {
+                self.search_leaf_for_nearest_one::<D, _>(
                     query,
-                    initial_stem_idx,
-                    0,
                     &mut result,
-                    &mut off,
-                    A::zero(),
+                    &mut nearest_leaf_idx,
+                    hint.leaf_idx,
+                    &mut (),
                 );
+            }
+
+            let (result, nearest_leaf_idx) =
+                self.nearest_one_from::<D, _>(query, result, nearest_leaf_idx, &mut ());
+
+            #[cfg(feature = "verify_results")]
+            $crate::verify::verify_nearest_one(
+                self,
+                &$crate::verify::LeafMetric::<D, T>::new(),
+                query,
+                &result,
+            );
+
+            hint.leaf_idx = nearest_leaf_idx;
+            result
+        }
+
+        /// Finds the nearest element to `query` that is less than `max_dist` from it,
+        /// along with the index of the leaf that it is in. If there isn't one, the
+        /// returned distance is `max_dist`.
+        #[inline]
+        fn nearest_one_bounded<D, S>(
+            &self,
+            query: &[A; K],
+            max_dist: A,
+            stats: &mut S,
+        ) -> (NearestNeighbour<A, T>, usize)
+        where
+            D: DistanceMetric<A, K>,
+            S: $crate::traits::QueryStatsRecorder,
+        {
+            let result = NearestNeighbour {
+                distance: max_dist,
+                item: T::zero(),
+            };
 
-                #[cfg(feature = "modified_van_emde_boas")]
-                self.nearest_one_recurse::<D>(
+            self.nearest_one_from::<D, S>(query, result, 0, stats)
+        }
+
+        /// Finds the nearest element to `query` that is nearer than `result`, which is
+        /// in the leaf at `nearest_leaf_idx`, returning `result` unchanged if there isn't
+        /// one.
+        #[inline]
+        fn nearest_one_from<D, S>(
+            &self,
+            query: &[A; K],
+            mut result: NearestNeighbour<A, T>,
+            mut nearest_leaf_idx: usize,
+            stats: &mut S,
+        ) -> (NearestNeighbour<A, T>, usize)
+        where
+            D: DistanceMetric<A, K>,
+            S: $crate::traits::QueryStatsRecorder,
+        {
+            let mut off = [A::zero(); K];
+
+            if self.stems.is_empty() {
+                self.search_leaf_for_nearest_one::<D, S>(
                     query,
-                    initial_stem_idx,
-                    0,
                     &mut result,
-                    &mut off,
-                    A::zero(),
-                    0,
-                    0,
+                    &mut nearest_leaf_idx,
                     0,
+                    stats,
                 );
 
-                result
+                return (result, nearest_leaf_idx);
             }
 
-            #[allow(clippy::too_many_arguments)]
+            #[cfg(not(feature = "modified_van_emde_boas"))]
+            let initial_stem_idx = 1;
             #[cfg(feature = "modified_van_emde_boas")]
-            #[inline]
-            fn nearest_one_recurse<D>(
-                &self,
-                query: &[A; K],
-                stem_idx: u32,
-                split_dim: u64,
-                nearest: &mut NearestNeighbour<A, T>,
-                off: &mut [A; K],
-                rd: A,
-                mut level: i32,
-                mut minor_level: u32,
-                mut leaf_idx: u32,
-            )
-                where
-                    D: DistanceMetric<A, K>,
-            {
-                use cmov::Cmov;
-                use $crate::modified_van_emde_boas::modified_van_emde_boas_get_child_idx_v2_branchless;
+            let initial_stem_idx = 0;
 
-                if level > self.max_stem_level {
-                    self.search_leaf_for_nearest_one::<D>(query, nearest, leaf_idx as usize);
-                    return;
-                }
+            #[cfg(not(feature = "modified_van_emde_boas"))]
+            self.nearest_one_recurse::<D, S>(
+                query,
+                initial_stem_idx,
+                0,
+                &mut result,
+                &mut nearest_leaf_idx,
+                &mut off,
+                A::zero(),
+                stats,
+            );
 
-                let val = *unsafe { self.stems.get_unchecked(stem_idx as usize) };
-                let is_right_child = u32::from(*unsafe { query.get_unchecked(split_dim as usize) } >= val);
+            #[cfg(feature = "modified_van_emde_boas")]
+            self.nearest_one_recurse::<D, S>(
+                query,
+                initial_stem_idx,
+                0,
+                &mut result,
+                &mut nearest_leaf_idx,
+                &mut off,
+                A::zero(),
+                0,
+                0,
+                0,
+                stats,
+            );
 
-                leaf_idx <<= 1;
-                let closer_leaf_idx = leaf_idx + is_right_child;
-                let farther_leaf_idx = leaf_idx + (1 - is_right_child);
+            (result, nearest_leaf_idx)
+        }
 
-                let closer_node_idx = modified_van_emde_boas_get_child_idx_v2_branchless(stem_idx, is_right_child == 1, minor_level);
-                let further_node_idx = modified_van_emde_boas_get_child_idx_v2_branchless(stem_idx, is_right_child == 0, minor_level);
+        #[allow(clippy::too_many_arguments)]
+        #[cfg(feature = "modified_van_emde_boas")]
+        #[inline]
+        fn nearest_one_recurse<D, S>(
+            &self,
+            query: &[A; K],
+            stem_idx: u32,
+            split_dim: u64,
+            nearest: &mut NearestNeighbour<A, T>,
+            nearest_leaf_idx: &mut usize,
+            off: &mut [A; K],
+            rd: A,
+            mut level: i32,
+            mut minor_level: u32,
+            mut leaf_idx: u32,
+            stats: &mut S,
+        ) where
+            D: DistanceMetric<A, K>,
+            S: $crate::traits::QueryStatsRecorder,
+        {
+            use cmov::Cmov;
+            use $crate::modified_van_emde_boas::modified_van_emde_boas_get_child_idx_v2_branchless;
 
-                let mut rd = rd;
-                let old_off = off[split_dim as usize];
-                let new_off = query[split_dim as usize].saturating_dist(val);
+            if level > self.max_stem_level {
+                self.search_leaf_for_nearest_one::<D, S>(
+                    query,
+                    nearest,
+                    nearest_leaf_idx,
+                    leaf_idx as usize,
+                    stats,
+                );
+                return;
+            }
+            stats.visit_stem();
 
-                level += 1;
-                minor_level += 1;
-                minor_level.cmovnz(&0, u8::from(minor_level == 3));
+            let split_dim = self.split_dim_at(stem_idx as usize, split_dim as usize) as u64;
+            let val = *unsafe { self.stems.get_unchecked(stem_idx as usize) };
+            let is_right_child =
+                u32::from(*unsafe { query.get_unchecked(split_dim as usize) } >= val);
 
-                let mut next_split_dim = split_dim + 1;
-                next_split_dim.cmovnz(&0, u8::from(next_split_dim == K as u64));
+            leaf_idx <<= 1;
+            let closer_leaf_idx = leaf_idx + is_right_child;
+            let farther_leaf_idx = leaf_idx + (1 - is_right_child);
 
-                self.nearest_one_recurse::<D>(
+            let closer_node_idx = modified_van_emde_boas_get_child_idx_v2_branchless(
+                stem_idx,
+                is_right_child == 1,
+                minor_level,
+            );
+            let further_node_idx = modified_van_emde_boas_get_child_idx_v2_branchless(
+                stem_idx,
+                is_right_child == 0,
+                minor_level,
+            );
+
+            let mut rd = rd;
+            let old_off = off[split_dim as usize];
+            let new_off = query[split_dim as usize].saturating_dist(val);
+
+            level += 1;
+            minor_level += 1;
+            minor_level.cmovnz(&0, u8::from(minor_level == 3));
+
+            let mut next_split_dim = split_dim + 1;
+            next_split_dim.cmovnz(&0, u8::from(next_split_dim == K as u64));
+
+            self.nearest_one_recurse::<D, S>(
+                query,
+                closer_node_idx,
+                next_split_dim,
+                nearest,
+                nearest_leaf_idx,
+                off,
+                rd,
+                level,
+                minor_level,
+                closer_leaf_idx,
+                stats,
+            );
+
+            rd = $crate::traits::accumulate_dist(
+                rd,
+                D::dist1(new_off, old_off),
+                D::MAX_OF_AXES,
+                Axis::rd_update,
+            );
+
+            if rd <= nearest.distance {
+                off[split_dim as usize] = new_off;
+                self.nearest_one_recurse::<D, S>(
                     query,
-                    closer_node_idx,
+                    further_node_idx,
                     next_split_dim,
                     nearest,
+                    nearest_leaf_idx,
                     off,
                     rd,
                     level,
                     minor_level,
-                    closer_leaf_idx,
+                    farther_leaf_idx,
+                    stats,
                 );
+                off[split_dim as usize] = old_off;
+            }
+        }
 
-                rd = Axis::rd_update(rd, D::dist1(new_off, old_off));
-
-                if rd <= nearest.distance {
-                    off[split_dim as usize] = new_off;
-                    self.nearest_one_recurse::<D>(
-                        query,
-                        further_node_idx,
-                        next_split_dim,
-                        nearest,
-                        off,
-                        rd,
-                        level,
-                        minor_level,
-                        farther_leaf_idx,
-                    );
-                    off[split_dim as usize] = old_off;
-                }
+        #[allow(clippy::too_many_arguments)]
+        #[cfg(not(feature = "modified_van_emde_boas"))]
+        #[inline]
+        fn nearest_one_recurse<D, S>(
+            &self,
+            query: &[A; K],
+            stem_idx: usize,
+            split_dim: u64,
+            nearest: &mut NearestNeighbour<A, T>,
+            nearest_leaf_idx: &mut usize,
+            off: &mut [A; K],
+            rd: A,
+            stats: &mut S,
+        ) where
+            D: DistanceMetric<A, K>,
+            S: $crate::traits::QueryStatsRecorder,
+        {
+            use cmov::Cmov;
+
+            if stem_idx >= self.stems.len() {
+                self.search_leaf_for_nearest_one::<D, S>(
+                    query,
+                    nearest,
+                    nearest_leaf_idx,
+                    stem_idx - self.stems.len(),
+                    stats,
+                );
+                return;
             }
+            stats.visit_stem();
 
-            #[allow(clippy::too_many_arguments)]
-            #[cfg(not(feature = "modified_van_emde_boas"))]
-            #[inline]
-            fn nearest_one_recurse<D>(
-                &self,
-                query: &[A; K],
-                stem_idx: usize,
-                split_dim: u64,
-                nearest: &mut NearestNeighbour<A, T>,
-                off: &mut [A; K],
-                rd: A,
-            )
-                where
-                    D: DistanceMetric<A, K>,
-            {
-                use cmov::Cmov;
+            let left_child_idx = stem_idx << 1;
 
-                if stem_idx >= self.stems.len() {
-                    self.search_leaf_for_nearest_one::<D>(query, nearest, stem_idx - self.stems.len());
-                    return;
-                }
+            // #[cfg(all(feature = "simd", any(target_arch = "x86_64", target_arch = "aarch64")))]
+            // self.prefetch_stems(left_child_idx);
 
-                let left_child_idx = stem_idx << 1;
+            let split_dim = self.split_dim_at(stem_idx as usize, split_dim as usize) as u64;
+            let val = *unsafe { self.stems.get_unchecked(stem_idx as usize) };
+            let is_right_child =
+                usize::from(*unsafe { query.get_unchecked(split_dim as usize) } >= val);
 
-                // #[cfg(all(feature = "simd", any(target_arch = "x86_64", target_arch = "aarch64")))]
-                // self.prefetch_stems(left_child_idx);
+            let closer_node_idx = left_child_idx + is_right_child;
+            let further_node_idx = left_child_idx + 1 - is_right_child;
 
-                let val = *unsafe { self.stems.get_unchecked(stem_idx as usize) };
-                let is_right_child = usize::from(*unsafe { query.get_unchecked(split_dim as usize) } >= val);
+            let mut rd = rd;
+            let old_off = off[split_dim as usize];
+            let new_off = query[split_dim as usize].saturating_dist(val);
 
-                let closer_node_idx = left_child_idx + is_right_child;
-                let further_node_idx = left_child_idx + 1 - is_right_child;
+            let mut next_split_dim = split_dim + 1;
+            next_split_dim.cmovnz(&0, u8::from(next_split_dim == K as u64));
 
-                let mut rd = rd;
-                let old_off = off[split_dim as usize];
-                let new_off = query[split_dim as usize].saturating_dist(val);
+            self.nearest_one_recurse::<D, S>(
+                query,
+                closer_node_idx,
+                next_split_dim,
+                nearest,
+                nearest_leaf_idx,
+                off,
+                rd,
+                stats,
+            );
 
-                let mut next_split_dim = split_dim + 1;
-                next_split_dim.cmovnz(&0, u8::from(next_split_dim == K as u64));
+            rd = $crate::traits::accumulate_dist(
+                rd,
+                D::dist1(new_off, old_off),
+                D::MAX_OF_AXES,
+                Axis::rd_update,
+            );
 
-                self.nearest_one_recurse::<D>(
+            if rd <= nearest.distance {
+                off[split_dim as usize] = new_off;
+                self.nearest_one_recurse::<D, S>(
                     query,
-                    closer_node_idx,
+                    further_node_idx,
                     next_split_dim,
                     nearest,
+                    nearest_leaf_idx,
                     off,
                     rd,
+                    stats,
                 );
-
-                rd = Axis::rd_update(rd, D::dist1(new_off, old_off));
-
-                if rd <= nearest.distance {
-                    off[split_dim as usize] = new_off;
-                    self.nearest_one_recurse::<D>(
-                        query,
-                        further_node_idx,
-                        next_split_dim,
-                        nearest,
-                        off,
-                        rd,
-                    );
-                    off[split_dim as usize] = old_off;
-                }
+                off[split_dim as usize] = old_off;
             }
+        }
 
-            #[inline]
-            fn search_leaf_for_nearest_one<D>(
-                &self,
-                query: &[A; K],
-                nearest: &mut NearestNeighbour<A, T>,
-                leaf_idx: usize,
-            ) where
-                D: DistanceMetric<A, K>,
-            {
-                let leaf_slice = self.get_leaf_slice(leaf_idx);
+        #[inline]
+        fn search_leaf_for_nearest_one<D, S>(
+            &self,
+            query: &[A; K],
+            nearest: &mut NearestNeighbour<A, T>,
+            nearest_leaf_idx: &mut usize,
+            leaf_idx: usize,
+            stats: &mut S,
+        ) where
+            D: DistanceMetric<A, K>,
+            S: $crate::traits::QueryStatsRecorder,
+        {
+            let leaf_slice = self.get_leaf_slice(leaf_idx);
+            stats.scan_leaf(leaf_slice.content_items.len());
+            let prev_dist = nearest.distance;
 
-                leaf_slice.nearest_one::<D>(
-                    query,
-                    &mut nearest.distance,
-                    &mut nearest.item
-                );
+            leaf_slice.nearest_one::<D>(query, &mut nearest.distance, &mut nearest.item);
+
+            if nearest.distance < prev_dist {
+                *nearest_leaf_idx = leaf_idx;
             }
         }
     };
@@ -0,0 +1,31 @@
+#[doc(hidden)]
+#[macro_export]
+macro_rules! generate_immutable_tree_stats {
+    () => {
+        /// Returns the number of stems between the root and the leaves.
+        ///
+        /// Every leaf of an immutable tree is at the same depth. Trees with a single leaf
+        /// have a depth of zero.
+        #[inline]
+        pub fn max_depth(&self) -> usize {
+            (self.max_stem_level + 1) as usize
+        }
+
+        /// Returns the mean number of items per leaf, as a fraction of the bucket size.
+        pub fn average_leaf_occupancy(&self) -> f64 {
+            self.generate_stats().average_leaf_occupancy
+        }
+
+        /// Returns a summary of how the tree's items are spread across its leaves, and of
+        /// how deep it is. See [`TreeStats`]($crate::tree_stats::TreeStats) for details.
+        pub fn generate_stats(&self) -> $crate::tree_stats::TreeStats {
+            $crate::tree_stats::TreeStats::from_leaf_sizes(
+                self.leaf_extents
+                    .iter()
+                    .map(|&(start, end)| (end - start) as usize),
+                self.max_depth(),
+                B,
+            )
+        }
+    };
+}
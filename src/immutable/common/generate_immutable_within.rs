@@ -13,5 +13,72 @@ macro_rules! generate_immutable_within {
                 self.nearest_n_within::<D>(query, dist, std::num::NonZero::new(usize::MAX).unwrap(), true)
             }
         }
+
+        /// Finds all elements within `dist` of `query`, sorted by distance, writing them
+        /// into `out`.
+        ///
+        /// Behaves the same as `within`, but clears and reuses `out` rather than returning
+        /// a new `Vec`, so a loop that makes many queries with the same buffer only allocates
+        /// when `out` needs to grow.
+        #[inline]
+        pub fn within_into<D>(&self, query: &[A; K], dist: A, out: &mut Vec<NearestNeighbour<A, T>>)
+        where
+            A: LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+            D: DistanceMetric<A, K>,
+            usize: Cast<T>,
+        {
+            let mut results = core::mem::take(out);
+            results.clear();
+            *out = self.nearest_n_within_stub::<D, _>(query, dist, results, false);
+            out.sort_unstable();
+        }
+
+        /// Finds all elements within `dist` of `query`, sorted by item rather than by
+        /// distance.
+        ///
+        /// Useful when the results are to be joined against data that is sorted by item,
+        /// as the matches are collected and then sorted by item directly, without being
+        /// sorted by distance first. Entries with equal items are in no particular order.
+        #[inline]
+        pub fn within_sorted_by_item<D>(&self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+        where
+            A: LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+            D: DistanceMetric<A, K>,
+            usize: Cast<T>,
+        {
+            let mut matching_items =
+                self.nearest_n_within_stub::<D, _>(query, dist, Vec::new(), false);
+            matching_items.sort_unstable_by(|a, b| a.item.cmp(&b.item));
+            matching_items
+        }
+
+        /// Finds all elements within `dist` of `query` whose item satisfies `filter`,
+        /// sorted by distance.
+        ///
+        /// `filter` is applied to each item as the tree is searched, rather than to the
+        /// results afterwards, so items that it rejects never take up space in the results.
+        #[inline]
+        pub fn within_filtered<D, F>(
+            &self,
+            query: &[A; K],
+            dist: A,
+            filter: F,
+        ) -> Vec<NearestNeighbour<A, T>>
+        where
+            A: LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+            D: DistanceMetric<A, K>,
+            F: Fn(T) -> bool,
+            usize: Cast<T>,
+        {
+            self.nearest_n_within_stub::<D, _>(
+                query,
+                dist,
+                $crate::float::result_collection::FilteredResults {
+                    results: Vec::new(),
+                    filter: &filter,
+                },
+                true,
+            )
+        }
     };
 }
@@ -13,5 +13,46 @@ macro_rules! generate_immutable_within_unsorted {
                 self.nearest_n_within::<D>(query, dist, std::num::NonZero::new(usize::MAX).unwrap(), false)
             }
         }
+
+        /// Calls `visitor` with the item and distance of every element within `dist` of
+        /// `query`, in arbitrary order.
+        ///
+        /// Behaves the same as `within_unsorted`, but without collecting the results into
+        /// a `Vec`, so that they can be aggregated in place (for example counted, summed,
+        /// or reduced to a maximum) without allocating.
+        #[inline]
+        pub fn within_unsorted_visit<D, F>(&self, query: &[A; K], dist: A, mut visitor: F)
+        where
+            A: LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+            D: DistanceMetric<A, K>,
+            F: FnMut(T, A),
+            usize: Cast<T>,
+        {
+            self.nearest_n_within_stub::<D, _>(
+                query,
+                dist,
+                $crate::float::result_collection::VisitedResults { visitor: &mut visitor },
+                false,
+            );
+        }
+
+        /// Returns `true` if there is any element within `dist` of `query`.
+        ///
+        /// Stops searching as soon as a match is found, so is much faster than checking
+        /// whether the results of `within_unsorted` are empty.
+        #[inline]
+        pub fn any_within<D>(&self, query: &[A; K], dist: A) -> bool
+        where
+            A: LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+            D: DistanceMetric<A, K>,
+            usize: Cast<T>,
+        {
+            self.nearest_n_within_collect::<D, _>(
+                query,
+                dist,
+                $crate::float::result_collection::AnyResult::default(),
+            )
+            .found
+        }
     };
 }
@@ -53,6 +53,7 @@ macro_rules! generate_immutable_within_unsorted_iter {
                 use $crate::modified_van_emde_boas::modified_van_emde_boas_get_child_idx_v2_branchless;
 
                 if level <= self.max_stem_level as usize {
+                    let split_dim = self.split_dim_at(stem_idx as usize, split_dim);
                     let val = *unsafe { self.stems.get_unchecked(stem_idx as usize) };
                     let is_right_child = usize::from(*unsafe { query.get_unchecked(split_dim as usize) } >= val);
 
@@ -84,7 +85,7 @@ macro_rules! generate_immutable_within_unsorted_iter {
                         closer_leaf_idx,
                     );
 
-                    rd = Axis::rd_update(rd, D::dist1(new_off, old_off));
+                    rd = $crate::traits::accumulate_dist(rd, D::dist1(new_off, old_off), D::MAX_OF_AXES, Axis::rd_update);
 
                     if rd <= radius {
                         off[split_dim] = new_off;
@@ -3,6 +3,7 @@ pub(crate) mod generate_immutable_best_n_within;
 pub(crate) mod generate_immutable_nearest_n;
 pub(crate) mod generate_immutable_nearest_n_within;
 pub(crate) mod generate_immutable_nearest_one;
+pub(crate) mod generate_immutable_tree_stats;
 pub(crate) mod generate_immutable_within;
 pub(crate) mod generate_immutable_within_unsorted;
 // pub(crate) mod generate_immutable_within_unsorted_iter;
@@ -0,0 +1,385 @@
+//! A linear scan over a handful of points, for datasets too small to benefit from a tree.
+//!
+//! Traversing the stems of a tree costs more than it saves when there are only a few
+//! hundred points: scanning every point is quicker. [`BruteForce`] does just that, and
+//! [`AutoIndex`] picks between it and an [`ImmutableKdTree`] according to the number of
+//! points, which suits applications that build many small indexes, such as one per cell
+//! of a coarse grid, whose sizes vary widely.
+
+use std::collections::BinaryHeap;
+use std::num::NonZero;
+
+use az::{Az, Cast};
+
+use crate::best_neighbour::BestNeighbour;
+use crate::float::kdtree::Axis;
+use crate::float_leaf_slice::fallback::push_best;
+use crate::float_leaf_slice::leaf_slice::{LeafSliceFloat, LeafSliceFloatChunk};
+use crate::immutable::float::kdtree::ImmutableKdTree;
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{Content, DistanceMetric, KdTreeQuery};
+
+/// The number of points below which [`AutoIndex::new_from_slice`] uses a [`BruteForce`]
+/// scan rather than an [`ImmutableKdTree`].
+pub const BRUTE_FORCE_MAX_SIZE: usize = 256;
+
+/// Answers the same queries as an [`ImmutableKdTree`] by comparing the query point
+/// against every stored point.
+///
+/// Faster than a tree for a few hundred points or fewer, and cheaper to build, as there is
+/// nothing to partition. Results are the same as those of an [`ImmutableKdTree`] built
+/// from the same points, other than the relative order of any items that are equidistant
+/// from the query point.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::immutable::float::brute_force::BruteForce;
+/// use kiddo::SquaredEuclidean;
+///
+/// let points = vec![[1.0, 2.0, 5.0], [2.0, 3.0, 6.0]];
+/// let index: BruteForce<f64, u32, 3> = BruteForce::new_from_slice(&points);
+///
+/// assert_eq!(index.nearest_one::<SquaredEuclidean>(&[2.0, 3.0, 5.9]).item, 1);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct BruteForce<A, T, const K: usize> {
+    points: Vec<[A; K]>,
+    items: Vec<T>,
+}
+
+impl<A: Axis, T: Content, const K: usize> BruteForce<A, T, K> {
+    /// Creates a `BruteForce` index of the points in `source`, with each item being the
+    /// index of its point within `source`.
+    pub fn new_from_slice(source: &[[A; K]]) -> Self
+    where
+        usize: Cast<T>,
+    {
+        BruteForce {
+            points: source.to_vec(),
+            items: (0..source.len()).map(|idx| idx.az::<T>()).collect(),
+        }
+    }
+
+    /// Returns the number of points in the index
+    pub fn size(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Iterates over the distance of each point from `query`, along with its item
+    fn distances<'a, D>(&'a self, query: &'a [A; K]) -> impl Iterator<Item = (A, T)> + 'a
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.points
+            .iter()
+            .zip(self.items.iter())
+            .map(move |(point, &item)| (D::dist(query, point), item))
+    }
+
+    /// Finds the nearest element to `query`, using the specified distance metric.
+    ///
+    /// Returns a distance of `A::max_value()` if the index is empty.
+    pub fn nearest_one<D>(&self, query: &[A; K]) -> NearestNeighbour<A, T>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut nearest = NearestNeighbour {
+            distance: A::max_value(),
+            item: T::zero(),
+        };
+        for (distance, item) in self.distances::<D>(query) {
+            if distance < nearest.distance {
+                nearest = NearestNeighbour { distance, item };
+            }
+        }
+
+        nearest
+    }
+
+    /// Finds the nearest `max_qty` elements to `query`, sorted by distance.
+    pub fn nearest_n<D>(
+        &self,
+        query: &[A; K],
+        max_qty: NonZero<usize>,
+    ) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut results: Vec<_> = self
+            .distances::<D>(query)
+            .map(|(distance, item)| NearestNeighbour { distance, item })
+            .collect();
+        results.sort();
+        results.truncate(max_qty.get());
+
+        results
+    }
+
+    /// Finds all elements within `dist` of `query`, sorted by distance.
+    pub fn within<D>(&self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut results: Vec<_> = self
+            .distances::<D>(query)
+            .filter(|&(distance, _)| distance <= dist)
+            .map(|(distance, item)| NearestNeighbour { distance, item })
+            .collect();
+        results.sort();
+
+        results
+    }
+
+    /// Finds the "best" `max_qty` elements within `dist` of `query`, in arbitrary order,
+    /// where "best" means having the lowest item.
+    pub fn best_n_within<D>(
+        &self,
+        query: &[A; K],
+        dist: A,
+        max_qty: NonZero<usize>,
+    ) -> impl Iterator<Item = BestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut best_items = BinaryHeap::new();
+        for (distance, item) in self.distances::<D>(query) {
+            if distance <= dist {
+                push_best(&mut best_items, max_qty.get(), distance, item);
+            }
+        }
+
+        best_items.into_iter()
+    }
+}
+
+/// Either a [`BruteForce`] scan or an [`ImmutableKdTree`], chosen according to the
+/// number of points when built with [`new_from_slice`](AutoIndex::new_from_slice).
+///
+/// Query it through the [`KdTreeQuery`] trait, which both kinds of index implement.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::immutable::float::brute_force::AutoIndex;
+/// use kiddo::traits::KdTreeQuery;
+/// use kiddo::SquaredEuclidean;
+///
+/// let few: Vec<[f64; 2]> = (0..10).map(|idx| [idx as f64, 0.0]).collect();
+/// let many: Vec<[f64; 2]> = (0..10_000).map(|idx| [idx as f64, 0.0]).collect();
+///
+/// let small: AutoIndex<f64, u32, 2, 32> = AutoIndex::new_from_slice(&few);
+/// let large: AutoIndex<f64, u32, 2, 32> = AutoIndex::new_from_slice(&many);
+///
+/// assert!(matches!(small, AutoIndex::BruteForce(_)));
+/// assert!(matches!(large, AutoIndex::Tree(_)));
+/// assert_eq!(small.nearest_one::<SquaredEuclidean>(&[3.2, 0.0]).item, 3);
+/// assert_eq!(large.nearest_one::<SquaredEuclidean>(&[3.2, 0.0]).item, 3);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum AutoIndex<A: Copy + Default, T: Copy + Default, const K: usize, const B: usize> {
+    /// A linear scan, for small numbers of points
+    BruteForce(BruteForce<A, T, K>),
+    /// A tree, for larger numbers of points
+    Tree(ImmutableKdTree<A, T, K, B>),
+}
+
+impl<A, T, const K: usize, const B: usize> AutoIndex<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    usize: Cast<T>,
+{
+    /// Creates an index of the points in `source`, with each item being the index of its
+    /// point within `source`: a [`BruteForce`] scan if there are fewer than
+    /// [`BRUTE_FORCE_MAX_SIZE`] points, or an [`ImmutableKdTree`] otherwise.
+    pub fn new_from_slice(source: &[[A; K]]) -> Self {
+        Self::new_from_slice_with_threshold(source, BRUTE_FORCE_MAX_SIZE)
+    }
+
+    /// Creates an index of the points in `source`, as
+    /// [`new_from_slice`](AutoIndex::new_from_slice) does, but using a [`BruteForce`] scan
+    /// only if there are fewer than `threshold` points.
+    ///
+    /// The best threshold depends on `K`, the distance metric and the machine, so this
+    /// is worth tuning with a benchmark of the intended queries.
+    pub fn new_from_slice_with_threshold(source: &[[A; K]], threshold: usize) -> Self {
+        if source.len() < threshold {
+            AutoIndex::BruteForce(BruteForce::new_from_slice(source))
+        } else {
+            AutoIndex::Tree(ImmutableKdTree::new_from_slice(source))
+        }
+    }
+
+    /// Returns the number of points in the index
+    pub fn size(&self) -> usize {
+        match self {
+            AutoIndex::BruteForce(index) => index.size(),
+            AutoIndex::Tree(tree) => tree.size(),
+        }
+    }
+}
+
+impl<A: Axis, T: Content, const K: usize> KdTreeQuery<A, T, K> for BruteForce<A, T, K> {
+    #[inline]
+    fn nearest_one<D>(&self, query: &[A; K]) -> NearestNeighbour<A, T>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.nearest_one::<D>(query)
+    }
+
+    #[inline]
+    fn nearest_n<D>(&self, query: &[A; K], max_qty: NonZero<usize>) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.nearest_n::<D>(query, max_qty)
+    }
+
+    #[inline]
+    fn within<D>(&self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.within::<D>(query, dist)
+    }
+
+    #[inline]
+    fn best_n_within<D>(
+        &self,
+        query: &[A; K],
+        dist: A,
+        max_qty: NonZero<usize>,
+    ) -> impl Iterator<Item = BestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.best_n_within::<D>(query, dist, max_qty)
+    }
+}
+
+impl<A, T, const K: usize, const B: usize> KdTreeQuery<A, T, K> for AutoIndex<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    usize: Cast<T>,
+{
+    #[inline]
+    fn nearest_one<D>(&self, query: &[A; K]) -> NearestNeighbour<A, T>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        match self {
+            AutoIndex::BruteForce(index) => index.nearest_one::<D>(query),
+            AutoIndex::Tree(tree) => tree.nearest_one::<D>(query),
+        }
+    }
+
+    #[inline]
+    fn nearest_n<D>(&self, query: &[A; K], max_qty: NonZero<usize>) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        match self {
+            AutoIndex::BruteForce(index) => index.nearest_n::<D>(query, max_qty),
+            AutoIndex::Tree(tree) => tree.nearest_n::<D>(query, max_qty),
+        }
+    }
+
+    #[inline]
+    fn within<D>(&self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        match self {
+            AutoIndex::BruteForce(index) => index.within::<D>(query, dist),
+            AutoIndex::Tree(tree) => tree.within::<D>(query, dist),
+        }
+    }
+
+    #[inline]
+    fn best_n_within<D>(
+        &self,
+        query: &[A; K],
+        dist: A,
+        max_qty: NonZero<usize>,
+    ) -> impl Iterator<Item = BestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let best: Vec<_> = match self {
+            AutoIndex::BruteForce(index) => {
+                index.best_n_within::<D>(query, dist, max_qty).collect()
+            }
+            AutoIndex::Tree(tree) => tree.best_n_within::<D>(query, dist, max_qty).collect(),
+        };
+
+        best.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AutoIndex, BruteForce};
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use crate::traits::KdTreeQuery;
+    use crate::{Manhattan, SquaredEuclidean};
+    use rand::{Rng, SeedableRng};
+    use std::num::NonZero;
+
+    #[test]
+    fn brute_force_gives_the_same_results_as_a_tree() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(53);
+        let points: Vec<[f64; 3]> = (0..200).map(|_| rng.gen()).collect();
+
+        let index: BruteForce<f64, u32, 3> = BruteForce::new_from_slice(&points);
+        let tree: ImmutableKdTree<f64, u32, 3, 8> = ImmutableKdTree::new_from_slice(&points);
+        let max_qty = NonZero::new(5).unwrap();
+
+        for _ in 0..100 {
+            let query: [f64; 3] = rng.gen();
+
+            assert_eq!(
+                index.nearest_one::<SquaredEuclidean>(&query),
+                tree.nearest_one::<SquaredEuclidean>(&query)
+            );
+            assert_eq!(
+                index.nearest_n::<Manhattan>(&query, max_qty),
+                tree.nearest_n::<Manhattan>(&query, max_qty)
+            );
+            assert_eq!(
+                index.within::<SquaredEuclidean>(&query, 0.05),
+                tree.within::<SquaredEuclidean>(&query, 0.05)
+            );
+
+            let mut best: Vec<_> = index
+                .best_n_within::<SquaredEuclidean>(&query, 0.1, max_qty)
+                .collect();
+            let mut expected: Vec<_> = tree
+                .best_n_within::<SquaredEuclidean>(&query, 0.1, max_qty)
+                .collect();
+            best.sort();
+            expected.sort();
+            assert_eq!(best, expected);
+        }
+    }
+
+    #[test]
+    fn auto_index_switches_to_a_tree_at_the_threshold() {
+        let points: Vec<[f64; 2]> = (0..100).map(|idx| [idx as f64, 0.0]).collect();
+
+        let below: AutoIndex<f64, u32, 2, 32> =
+            AutoIndex::new_from_slice_with_threshold(&points, 101);
+        let at: AutoIndex<f64, u32, 2, 32> = AutoIndex::new_from_slice_with_threshold(&points, 100);
+
+        assert!(matches!(below, AutoIndex::BruteForce(_)));
+        assert!(matches!(at, AutoIndex::Tree(_)));
+        assert_eq!(below.size(), 100);
+        assert_eq!(at.size(), 100);
+        assert_eq!(
+            below.within::<SquaredEuclidean>(&[50.2, 0.0], 4.0),
+            at.within::<SquaredEuclidean>(&[50.2, 0.0], 4.0)
+        );
+    }
+}
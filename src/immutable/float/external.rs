@@ -0,0 +1,319 @@
+//! An [`ImmutableKdTree`] that doesn't store the coordinates of its points, reading them
+//! from a slice supplied at query time instead.
+//!
+//! Where the points that a tree is built from are kept around anyway, and only the items
+//! of query results are needed, storing the coordinates in the tree's leaves as well as in
+//! the original slice doubles up on memory. Leaving them out roughly halves the size of the
+//! tree, and of the tree when serialized.
+
+use std::collections::BinaryHeap;
+use std::num::NonZero;
+use std::ops::Rem;
+
+use aligned_vec::AVec;
+use az::{Az, Cast};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use sorted_vec::SortedVec;
+
+use crate::float::kdtree::Axis;
+use crate::float::result_collection::{AnyResult, ResultCollection};
+use crate::float_leaf_slice::leaf_slice::{add_if_within, LeafSliceFloat, LeafSliceFloatChunk};
+use crate::generate_immutable_nearest_n_within_traversal;
+use crate::immutable::float::kdtree::{ImmutableKdTree, LeafExtent};
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{Content, DistanceMetric};
+
+const MAX_VEC_RESULT_SIZE: usize = 20;
+
+/// An [`ImmutableKdTree`] whose queries read the coordinates of its points from the slice
+/// that it was built from, rather than storing them itself.
+///
+/// As with [`ImmutableKdTree::new_from_slice`], the item of each point is its index within
+/// that slice. Every query must be passed the same slice, unmodified: passing a different
+/// one gives wrong results, and may panic if it is too short.
+///
+/// Scanning a leaf means looking up each of its points in the slice, rather than reading
+/// them from memory laid out for vectorized scans, so queries are slower than those of an
+/// [`ImmutableKdTree`].
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::immutable::float::external::ExternalImmutableKdTree;
+/// use kiddo::SquaredEuclidean;
+///
+/// let points: Vec<[f64; 2]> = vec![[1.0, 2.0], [3.0, 4.0], [5.0, 6.0]];
+/// let tree: ExternalImmutableKdTree<f64, u32, 2, 32> =
+///     ExternalImmutableKdTree::new_from_slice_external(&points);
+///
+/// let nearest = tree.nearest_one::<SquaredEuclidean>(&points, &[3.1, 4.1]);
+/// assert_eq!(nearest.item, 1);
+///
+/// assert_eq!(tree.within::<SquaredEuclidean>(&points, &[2.0, 3.0], 2.5).len(), 2);
+/// ```
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExternalImmutableKdTree<
+    A: Copy + Default,
+    T: Copy + Default,
+    const K: usize,
+    const B: usize,
+> {
+    stems: AVec<A>,
+    leaf_items: Vec<T>,
+    leaf_extents: Vec<(LeafExtent, LeafExtent)>,
+    max_stem_level: i32,
+    tombstone_count: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    split_dims: Vec<u8>,
+}
+
+impl<A, T, const K: usize, const B: usize> ExternalImmutableKdTree<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content + Cast<usize>,
+    usize: Cast<T>,
+{
+    /// Creates an `ExternalImmutableKdTree` from the points in `source`, which must then be
+    /// passed to each of its queries.
+    pub fn new_from_slice_external(source: &[[A; K]]) -> Self {
+        ImmutableKdTree::new_from_slice(source).into()
+    }
+
+    /// Returns the number of points in the tree
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.leaf_items.len() - self.tombstone_count
+    }
+
+    /// Finds the nearest point to `query`, reading the tree's points from `points`.
+    pub fn nearest_one<D>(&self, points: &[[A; K]], query: &[A; K]) -> NearestNeighbour<A, T>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.with_points(points)
+            .nearest_n_within_collect::<D, _>(query, A::infinity(), SortedVec::with_capacity(1))
+            .into_vec()
+            .pop()
+            .unwrap_or(NearestNeighbour {
+                distance: A::infinity(),
+                item: T::zero(),
+            })
+    }
+
+    /// Finds the nearest `max_qty` points to `query`, sorted by distance, reading the
+    /// tree's points from `points`.
+    pub fn nearest_n<D>(
+        &self,
+        points: &[[A; K]],
+        query: &[A; K],
+        max_qty: NonZero<usize>,
+    ) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let max_qty = max_qty.get();
+
+        if max_qty <= MAX_VEC_RESULT_SIZE {
+            self.with_points(points)
+                .nearest_n_within_collect::<D, _>(
+                    query,
+                    A::infinity(),
+                    SortedVec::with_capacity(max_qty),
+                )
+                .into_sorted_vec()
+        } else {
+            self.with_points(points)
+                .nearest_n_within_collect::<D, _>(
+                    query,
+                    A::infinity(),
+                    BinaryHeap::with_capacity(max_qty),
+                )
+                .into_sorted_vec()
+        }
+    }
+
+    /// Finds all points within `dist` of `query`, sorted by distance, reading the tree's
+    /// points from `points`.
+    pub fn within<D>(
+        &self,
+        points: &[[A; K]],
+        query: &[A; K],
+        dist: A,
+    ) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut results = self.within_unsorted::<D>(points, query, dist);
+        results.sort();
+        results
+    }
+
+    /// Finds all points within `dist` of `query`, in arbitrary order, reading the tree's
+    /// points from `points`.
+    pub fn within_unsorted<D>(
+        &self,
+        points: &[[A; K]],
+        query: &[A; K],
+        dist: A,
+    ) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.with_points(points)
+            .nearest_n_within_collect::<D, _>(query, dist, Vec::new())
+    }
+
+    /// Returns `true` if there is any point within `dist` of `query`, reading the tree's
+    /// points from `points`, stopping as soon as one is found.
+    pub fn any_within<D>(&self, points: &[[A; K]], query: &[A; K], dist: A) -> bool
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.with_points(points)
+            .nearest_n_within_collect::<D, _>(query, dist, AnyResult::default())
+            .found
+    }
+
+    /// Pairs the tree with the slice of points that a query reads coordinates from
+    #[inline]
+    fn with_points<'a>(&'a self, points: &'a [[A; K]]) -> WithPoints<'a, A, T, K> {
+        WithPoints {
+            stems: &self.stems,
+            max_stem_level: self.max_stem_level,
+            split_dims: &self.split_dims,
+            leaf_items: &self.leaf_items,
+            leaf_extents: &self.leaf_extents,
+            points,
+        }
+    }
+}
+
+/// An [`ExternalImmutableKdTree`] borrowed together with the slice of points passed to a
+/// query, so that the query can use the same traversal as an [`ImmutableKdTree`], only
+/// scanning leaves differently.
+struct WithPoints<'a, A, T, const K: usize> {
+    stems: &'a [A],
+    max_stem_level: i32,
+    split_dims: &'a [u8],
+    leaf_items: &'a [T],
+    leaf_extents: &'a [(LeafExtent, LeafExtent)],
+    points: &'a [[A; K]],
+}
+
+impl<A, T, const K: usize> WithPoints<'_, A, T, K>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content + Cast<usize>,
+    usize: Cast<T>,
+{
+    generate_immutable_nearest_n_within_traversal!();
+
+    /// Returns the dimension that the stem at `stem_idx` splits on, as recorded by the
+    /// tree that this one was created from
+    #[inline(always)]
+    fn split_dim_at(&self, stem_idx: usize, round_robin_dim: usize) -> usize {
+        if self.split_dims.is_empty() {
+            round_robin_dim
+        } else {
+            self.split_dims[stem_idx] as usize
+        }
+    }
+
+    #[inline]
+    fn search_leaf_for_nearest_n_within<D, R>(
+        &self,
+        query: &[A; K],
+        radius: A,
+        results: &mut R,
+        leaf_idx: usize,
+    ) where
+        D: DistanceMetric<A, K>,
+        R: ResultCollection<A, T>,
+    {
+        let (start, end) = self.leaf_extents[leaf_idx];
+
+        for &item in &self.leaf_items[start as usize..end as usize] {
+            let point = &self.points[item.az::<usize>()];
+            add_if_within::<A, T, D, R, K>(query, |dim| point[dim], item, radius, results);
+        }
+    }
+}
+
+impl<A, T, const K: usize, const B: usize> From<ImmutableKdTree<A, T, K, B>>
+    for ExternalImmutableKdTree<A, T, K, B>
+where
+    A: Axis,
+    T: Content,
+{
+    /// Drops the coordinates stored in the leaves of `tree`, whose items must be the
+    /// indices of its points within the slice that will be passed to each query.
+    /// [Deleted](ImmutableKdTree::delete) entries remain excluded from query results.
+    fn from(tree: ImmutableKdTree<A, T, K, B>) -> Self {
+        let ImmutableKdTree {
+            stems,
+            leaf_items,
+            leaf_extents,
+            max_stem_level,
+            tombstone_count,
+            split_dims,
+            ..
+        } = tree;
+
+        ExternalImmutableKdTree {
+            stems,
+            leaf_items,
+            leaf_extents,
+            max_stem_level,
+            tombstone_count,
+            split_dims,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExternalImmutableKdTree;
+    use crate::float::distance::SquaredEuclidean;
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use rand::{Rng, SeedableRng};
+    use std::num::NonZero;
+
+    #[test]
+    fn queries_match_the_immutable_tree() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(82);
+        let points: Vec<[f32; 3]> = (0..5_000).map(|_| rng.gen()).collect();
+
+        let tree: ImmutableKdTree<f32, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+        let external: ExternalImmutableKdTree<f32, u32, 3, 32> =
+            ExternalImmutableKdTree::new_from_slice_external(&points);
+        assert_eq!(external.size(), points.len());
+
+        for _ in 0..100 {
+            let query = rng.gen::<[f32; 3]>();
+
+            assert_eq!(
+                external.nearest_one::<SquaredEuclidean>(&points, &query),
+                tree.nearest_one::<SquaredEuclidean>(&query)
+            );
+            assert_eq!(
+                external.nearest_n::<SquaredEuclidean>(&points, &query, NonZero::new(30).unwrap()),
+                tree.nearest_n::<SquaredEuclidean>(&query, NonZero::new(30).unwrap())
+            );
+
+            let mut within = external.within::<SquaredEuclidean>(&points, &query, 0.02);
+            let mut expected = tree.within::<SquaredEuclidean>(&query, 0.02);
+            within.sort_by_key(|nn| nn.item);
+            expected.sort_by_key(|nn| nn.item);
+            assert_eq!(within, expected);
+
+            assert_eq!(
+                external.any_within::<SquaredEuclidean>(&points, &query, 0.0005),
+                !tree
+                    .within_unsorted::<SquaredEuclidean>(&query, 0.0005)
+                    .is_empty()
+            );
+        }
+    }
+}
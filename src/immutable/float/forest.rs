@@ -0,0 +1,364 @@
+//! A [`Forest`] of [`ImmutableKdTree`] shards that are queried as if they were one tree.
+//!
+//! Data that arrives in batches, or that is too large to build into a single tree in one
+//! go, can be built into one tree per batch instead. A [`Forest`] queries every shard and
+//! merges their results, so that callers see one set of neighbours. Each result records
+//! the shard that it came from, as items are only unique within their own shard.
+//!
+//! With the `rayon` feature, `par_nearest_n` and `par_within` query the shards in
+//! parallel.
+
+use std::cmp::Ordering;
+use std::num::NonZero;
+
+use az::Cast;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::float::kdtree::Axis;
+use crate::float_leaf_slice::leaf_slice::{LeafSliceFloat, LeafSliceFloatChunk};
+use crate::immutable::float::kdtree::ImmutableKdTree;
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{Content, DistanceMetric};
+
+/// A query result from a [`Forest`]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ForestNeighbour<A, T> {
+    /// the index of the shard that the result came from, within [`Forest::shards`]
+    pub shard: usize,
+    /// the distance of the found item from the query point according to the supplied distance metric
+    pub distance: A,
+    /// the stored item that was found, as returned by the shard
+    pub item: T,
+}
+
+impl<A, T> ForestNeighbour<A, T> {
+    fn from_shard(shard: usize, neighbour: NearestNeighbour<A, T>) -> Self {
+        ForestNeighbour {
+            shard,
+            distance: neighbour.distance,
+            item: neighbour.item,
+        }
+    }
+}
+
+/// Several [`ImmutableKdTree`] shards, queried together.
+///
+/// Queries are made against every shard, and the results merged. Results that are the
+/// same distance from the query are ordered by shard.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::num::NonZero;
+/// use kiddo::immutable::float::forest::Forest;
+/// use kiddo::{ImmutableKdTree, SquaredEuclidean};
+///
+/// let first: ImmutableKdTree<f64, 2> = ImmutableKdTree::new_from_slice(&[[1.0, 2.0], [5.0, 6.0]]);
+/// let second: ImmutableKdTree<f64, 2> = ImmutableKdTree::new_from_slice(&[[3.0, 4.0]]);
+///
+/// let forest = Forest::new(vec![first, second]);
+/// assert_eq!(forest.size(), 3);
+///
+/// let nearest = forest.nearest_n::<SquaredEuclidean>(&[3.1, 4.1], NonZero::new(2).unwrap());
+///
+/// assert_eq!((nearest[0].shard, nearest[0].item), (1, 0));
+/// assert_eq!((nearest[1].shard, nearest[1].item), (0, 1));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Forest<A: Copy + Default, T: Copy + Default, const K: usize, const B: usize> {
+    shards: Vec<ImmutableKdTree<A, T, K, B>>,
+}
+
+impl<A: Copy + Default, T: Copy + Default, const K: usize, const B: usize> Default
+    for Forest<A, T, K, B>
+{
+    fn default() -> Self {
+        Forest { shards: Vec::new() }
+    }
+}
+
+impl<A: Copy + Default, T: Copy + Default, const K: usize, const B: usize>
+    From<Vec<ImmutableKdTree<A, T, K, B>>> for Forest<A, T, K, B>
+{
+    fn from(shards: Vec<ImmutableKdTree<A, T, K, B>>) -> Self {
+        Forest { shards }
+    }
+}
+
+impl<A, T, const K: usize, const B: usize> Forest<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    usize: Cast<T>,
+{
+    /// Creates a `Forest` from `shards`, which are numbered in the order given.
+    pub fn new(shards: Vec<ImmutableKdTree<A, T, K, B>>) -> Self {
+        shards.into()
+    }
+
+    /// Adds a shard to the forest, returning the index that its results will carry.
+    pub fn push(&mut self, shard: ImmutableKdTree<A, T, K, B>) -> usize {
+        self.shards.push(shard);
+        self.shards.len() - 1
+    }
+
+    /// Returns the shards of the forest, in order.
+    pub fn shards(&self) -> &[ImmutableKdTree<A, T, K, B>] {
+        &self.shards
+    }
+
+    /// Returns the total number of elements stored across all of the shards
+    pub fn size(&self) -> usize {
+        self.shards.iter().map(|shard| shard.size()).sum()
+    }
+
+    /// Finds the nearest element to `query` in any shard, using the specified distance
+    /// metric.
+    ///
+    /// If the forest is empty, the result has an infinite distance.
+    pub fn nearest_one<D>(&self, query: &[A; K]) -> ForestNeighbour<A, T>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut best = ForestNeighbour {
+            shard: 0,
+            distance: A::infinity(),
+            item: T::zero(),
+        };
+
+        for (idx, shard) in self.shards.iter().enumerate() {
+            let result = shard.nearest_one::<D>(query);
+            if result.distance < best.distance {
+                best = ForestNeighbour::from_shard(idx, result);
+            }
+        }
+
+        best
+    }
+
+    /// Finds the nearest `max_qty` elements to `query` across all shards, sorted by
+    /// distance, using the specified distance metric.
+    ///
+    /// Once `max_qty` results have been found, later shards are only searched within the
+    /// distance of the furthest of them.
+    pub fn nearest_n<D>(
+        &self,
+        query: &[A; K],
+        max_qty: NonZero<usize>,
+    ) -> Vec<ForestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut results: Vec<ForestNeighbour<A, T>> = Vec::with_capacity(max_qty.get());
+
+        for (idx, shard) in self.shards.iter().enumerate() {
+            let shard_results = if results.len() < max_qty.get() {
+                shard.nearest_n::<D>(query, max_qty)
+            } else {
+                let max_dist = results[results.len() - 1].distance;
+                shard.nearest_n_within::<D>(query, max_dist, max_qty, true)
+            };
+
+            results.extend(
+                shard_results
+                    .into_iter()
+                    .map(|neighbour| ForestNeighbour::from_shard(idx, neighbour)),
+            );
+            sort_by_distance(&mut results);
+            results.truncate(max_qty.get());
+        }
+
+        results
+    }
+
+    /// Finds all elements within `dist` of `query` across all shards, sorted by distance,
+    /// using the specified distance metric.
+    pub fn within<D>(&self, query: &[A; K], dist: A) -> Vec<ForestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut results = self.within_unsorted::<D>(query, dist);
+        sort_by_distance(&mut results);
+
+        results
+    }
+
+    /// Finds all elements within `dist` of `query` across all shards, in arbitrary order,
+    /// using the specified distance metric.
+    pub fn within_unsorted<D>(&self, query: &[A; K], dist: A) -> Vec<ForestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.shards
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, shard)| {
+                shard
+                    .within_unsorted::<D>(query, dist)
+                    .into_iter()
+                    .map(move |neighbour| ForestNeighbour::from_shard(idx, neighbour))
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<A, T, const K: usize, const B: usize> Forest<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    usize: Cast<T>,
+{
+    /// Finds the nearest `max_qty` elements to `query` across all shards, sorted by
+    /// distance, with the shards searched in parallel on the current rayon thread pool.
+    ///
+    /// Returns the same results as [`nearest_n`](Forest::nearest_n), but as every shard is
+    /// searched at once, none of them can be cut short by the results of the others.
+    /// Requires the `rayon` feature.
+    pub fn par_nearest_n<D>(
+        &self,
+        query: &[A; K],
+        max_qty: NonZero<usize>,
+    ) -> Vec<ForestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut results = self.par_map_shards(|shard| shard.nearest_n::<D>(query, max_qty));
+        results.truncate(max_qty.get());
+
+        results
+    }
+
+    /// Finds all elements within `dist` of `query` across all shards, sorted by distance,
+    /// with the shards searched in parallel on the current rayon thread pool.
+    ///
+    /// Requires the `rayon` feature.
+    pub fn par_within<D>(&self, query: &[A; K], dist: A) -> Vec<ForestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.par_map_shards(|shard| shard.within_unsorted::<D>(query, dist))
+    }
+
+    fn par_map_shards<F>(&self, query_fn: F) -> Vec<ForestNeighbour<A, T>>
+    where
+        F: Fn(&ImmutableKdTree<A, T, K, B>) -> Vec<NearestNeighbour<A, T>> + Send + Sync,
+    {
+        let per_shard: Vec<Vec<NearestNeighbour<A, T>>> =
+            self.shards.par_iter().map(query_fn).collect();
+
+        let mut results: Vec<ForestNeighbour<A, T>> = per_shard
+            .into_iter()
+            .enumerate()
+            .flat_map(|(idx, shard_results)| {
+                shard_results
+                    .into_iter()
+                    .map(move |neighbour| ForestNeighbour::from_shard(idx, neighbour))
+            })
+            .collect();
+        sort_by_distance(&mut results);
+
+        results
+    }
+}
+
+/// Sorts `results` by distance. The sort is stable, so results at equal distances stay in
+/// shard order as long as they were added in shard order.
+fn sort_by_distance<A: Axis, T>(results: &mut [ForestNeighbour<A, T>]) {
+    results.sort_by(|a, b| {
+        a.distance
+            .partial_cmp(&b.distance)
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Forest;
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use crate::SquaredEuclidean;
+    use rand::{Rng, SeedableRng};
+    use std::num::NonZero;
+
+    #[test]
+    fn forest_queries_match_a_single_tree() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(91);
+        let points: Vec<[f64; 3]> = (0..10_000).map(|_| rng.gen()).collect();
+        let chunks: Vec<&[[f64; 3]]> = points.chunks(3_000).collect();
+
+        let tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+        let forest: Forest<f64, u32, 3, 32> = Forest::new(
+            chunks
+                .iter()
+                .map(|chunk| ImmutableKdTree::new_from_slice(chunk))
+                .collect(),
+        );
+        assert_eq!(forest.shards().len(), 4);
+        assert_eq!(forest.size(), points.len());
+
+        // maps a forest result back to its index within `points`
+        let global_idx = |shard: usize, item: u32| shard * 3_000 + item as usize;
+
+        for _ in 0..100 {
+            let query: [f64; 3] = rng.gen();
+
+            let expected = tree.nearest_one::<SquaredEuclidean>(&query);
+            let nearest = forest.nearest_one::<SquaredEuclidean>(&query);
+            assert_eq!(nearest.distance, expected.distance);
+            assert_eq!(
+                global_idx(nearest.shard, nearest.item),
+                expected.item as usize
+            );
+
+            let expected = tree.nearest_n::<SquaredEuclidean>(&query, NonZero::new(7).unwrap());
+            let nearest = forest.nearest_n::<SquaredEuclidean>(&query, NonZero::new(7).unwrap());
+            assert_eq!(
+                nearest
+                    .iter()
+                    .map(|nn| (nn.distance, global_idx(nn.shard, nn.item)))
+                    .collect::<Vec<_>>(),
+                expected
+                    .iter()
+                    .map(|nn| (nn.distance, nn.item as usize))
+                    .collect::<Vec<_>>()
+            );
+            #[cfg(feature = "rayon")]
+            assert_eq!(
+                forest.par_nearest_n::<SquaredEuclidean>(&query, NonZero::new(7).unwrap()),
+                nearest
+            );
+
+            let expected = tree.within::<SquaredEuclidean>(&query, 0.01);
+            let within = forest.within::<SquaredEuclidean>(&query, 0.01);
+            assert_eq!(
+                within
+                    .iter()
+                    .map(|nn| (nn.distance, global_idx(nn.shard, nn.item)))
+                    .collect::<Vec<_>>(),
+                expected
+                    .iter()
+                    .map(|nn| (nn.distance, nn.item as usize))
+                    .collect::<Vec<_>>()
+            );
+            #[cfg(feature = "rayon")]
+            assert_eq!(forest.par_within::<SquaredEuclidean>(&query, 0.01), within);
+        }
+    }
+
+    #[test]
+    fn empty_forest_finds_nothing() {
+        let forest: Forest<f64, u32, 2, 32> = Forest::default();
+        let query = [0.5, 0.5];
+
+        assert_eq!(forest.size(), 0);
+        assert_eq!(
+            forest.nearest_one::<SquaredEuclidean>(&query).distance,
+            f64::INFINITY
+        );
+        assert!(forest
+            .nearest_n::<SquaredEuclidean>(&query, NonZero::new(3).unwrap())
+            .is_empty());
+        assert!(forest.within::<SquaredEuclidean>(&query, 1.0).is_empty());
+    }
+}
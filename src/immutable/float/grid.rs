@@ -0,0 +1,447 @@
+//! A uniform grid over the leaves of an [`ImmutableKdTree`], for accelerating small-radius
+//! queries on low-dimensional data.
+//!
+//! For two- or three-dimensional data that is fairly uniformly dense, looking up the leaves
+//! that a small query ball overlaps in a coarse grid can be quicker than finding them by
+//! traversing the stems of the tree. [`GriddedImmutableKdTree`] wraps an [`ImmutableKdTree`]
+//! to do just that, falling back to a normal tree traversal for queries whose radius is too
+//! large for the grid to help.
+
+use az::Cast;
+
+use crate::float::kdtree::Axis;
+use crate::float_leaf_slice::leaf_slice::{LeafSliceFloat, LeafSliceFloatChunk};
+use crate::immutable::float::kdtree::ImmutableKdTree;
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{Content, DistanceMetric};
+
+/// An [`ImmutableKdTree`] with a coarse uniform grid built over its leaves.
+///
+/// Each grid cell records the leaves whose bounding boxes overlap it. `within` queries
+/// that overlap no more than [`max_grid_cells`](GriddedImmutableKdTree::with_max_grid_cells)
+/// cells get their candidate leaves from the grid, skipping traversal of the tree's stems
+/// entirely. Larger queries are passed through to the tree. Either way, the results are
+/// the same as querying the tree directly, other than the relative order of any items
+/// that are equidistant from the query point.
+///
+/// Only worthwhile for low values of `K` (2 or 3), as the number of grid cells that a
+/// query overlaps grows exponentially with `K`.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::immutable::float::grid::GriddedImmutableKdTree;
+/// use kiddo::{ImmutableKdTree, SquaredEuclidean};
+///
+/// let points: Vec<[f64; 2]> = (0..10_000)
+///     .map(|i| [(i % 100) as f64, (i / 100) as f64])
+///     .collect();
+/// let tree: ImmutableKdTree<f64, 2> = ImmutableKdTree::new_from_slice(&points);
+/// let gridded = GriddedImmutableKdTree::new(tree);
+///
+/// let within = gridded.within::<SquaredEuclidean>(&[50.2, 50.3], 1.5);
+///
+/// assert_eq!(within.len(), 4);
+/// assert_eq!(within, gridded.tree().within::<SquaredEuclidean>(&[50.2, 50.3], 1.5));
+/// ```
+#[derive(Debug)]
+pub struct GriddedImmutableKdTree<
+    A: Copy + Default,
+    T: Copy + Default,
+    const K: usize,
+    const B: usize,
+> {
+    tree: ImmutableKdTree<A, T, K, B>,
+    cells_per_axis: usize,
+    origin: [A; K],
+    cell_width: [A; K],
+    cell_offsets: Vec<u32>,
+    cell_leaves: Vec<u32>,
+    max_grid_cells: usize,
+}
+
+impl<A, T, const K: usize, const B: usize> GriddedImmutableKdTree<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    usize: Cast<T>,
+{
+    /// Builds a grid over the leaves of `tree`, sized so that there is around one leaf per cell.
+    pub fn new(tree: ImmutableKdTree<A, T, K, B>) -> Self {
+        let leaf_count = tree.leaf_count().max(1);
+
+        // round down so as not to exceed one cell per leaf on average
+        let mut cells_per_axis = (leaf_count as f64).powf(1.0 / K as f64).floor() as usize;
+        cells_per_axis = cells_per_axis.max(1);
+
+        Self::with_cells_per_axis(tree, cells_per_axis)
+    }
+
+    /// Builds a grid over the leaves of `tree`, with `cells_per_axis` cells along each axis.
+    ///
+    /// Panics if `cells_per_axis` is zero or if the total number of cells does not fit in a `u32`.
+    pub fn with_cells_per_axis(tree: ImmutableKdTree<A, T, K, B>, cells_per_axis: usize) -> Self {
+        assert!(cells_per_axis > 0, "cells_per_axis must be at least 1");
+        let cell_count = (0..K).try_fold(1usize, |acc, _| acc.checked_mul(cells_per_axis));
+        let cell_count = cell_count
+            .filter(|&c| c < u32::MAX as usize)
+            .expect("too many grid cells");
+
+        let leaf_bounds: Vec<Option<([A; K], [A; K])>> = (0..tree.leaf_count())
+            .map(|leaf_idx| leaf_bounds(&tree, leaf_idx))
+            .collect();
+
+        let mut origin = [A::infinity(); K];
+        let mut extent = [A::neg_infinity(); K];
+        for (min, max) in leaf_bounds.iter().flatten() {
+            for dim in 0..K {
+                origin[dim] = origin[dim].min(min[dim]);
+                extent[dim] = extent[dim].max(max[dim]);
+            }
+        }
+
+        let cells = A::from(cells_per_axis).unwrap();
+        let cell_width: [A; K] = std::array::from_fn(|dim| {
+            let width = (extent[dim] - origin[dim]) / cells;
+            if width > A::zero() && width.is_finite() {
+                width
+            } else {
+                A::one()
+            }
+        });
+        if origin[0].is_infinite() {
+            origin = [A::zero(); K];
+        }
+
+        let mut gridded = Self {
+            tree,
+            cells_per_axis,
+            origin,
+            cell_width,
+            cell_offsets: vec![0; cell_count + 1],
+            cell_leaves: Vec::new(),
+            max_grid_cells: 4usize.saturating_pow(K as u32),
+        };
+
+        // build the cell -> leaves lookup in compressed sparse row form, first counting
+        // how many leaves overlap each cell and then filling them in
+        let leaf_cell_ranges: Vec<Option<[(usize, usize); K]>> = leaf_bounds
+            .iter()
+            .map(|bounds| {
+                bounds.map(|(min, max)| {
+                    std::array::from_fn(|dim| {
+                        (
+                            gridded.cell_coord(dim, min[dim]),
+                            gridded.cell_coord(dim, max[dim]),
+                        )
+                    })
+                })
+            })
+            .collect();
+
+        for ranges in leaf_cell_ranges.iter().flatten() {
+            for_each_cell(cells_per_axis, ranges, |cell| {
+                gridded.cell_offsets[cell + 1] += 1
+            });
+        }
+        for cell in 0..cell_count {
+            gridded.cell_offsets[cell + 1] += gridded.cell_offsets[cell];
+        }
+
+        let mut cell_leaves = vec![0u32; gridded.cell_offsets[cell_count] as usize];
+        let mut cursors = gridded.cell_offsets.clone();
+        for (leaf_idx, ranges) in leaf_cell_ranges.iter().enumerate() {
+            if let Some(ranges) = ranges {
+                for_each_cell(cells_per_axis, ranges, |cell| {
+                    cell_leaves[cursors[cell] as usize] = leaf_idx as u32;
+                    cursors[cell] += 1;
+                });
+            }
+        }
+        gridded.cell_leaves = cell_leaves;
+
+        gridded
+    }
+
+    /// Sets the largest number of grid cells that a query can overlap for the grid to be
+    /// used. Queries overlapping more cells than this traverse the tree instead.
+    ///
+    /// Defaults to `4^K`.
+    pub fn with_max_grid_cells(mut self, max_grid_cells: usize) -> Self {
+        self.max_grid_cells = max_grid_cells;
+        self
+    }
+
+    /// Returns a reference to the wrapped tree
+    pub fn tree(&self) -> &ImmutableKdTree<A, T, K, B> {
+        &self.tree
+    }
+
+    /// Unwraps the tree, discarding the grid
+    pub fn into_inner(self) -> ImmutableKdTree<A, T, K, B> {
+        self.tree
+    }
+
+    /// Returns the number of grid cells along each axis
+    pub fn cells_per_axis(&self) -> usize {
+        self.cells_per_axis
+    }
+
+    /// Finds all elements within `dist` of `query`, using the specified distance metric
+    /// function. Results are returned in arbitrary order.
+    ///
+    /// Uses the grid to find the leaves to search if the query overlaps few enough
+    /// cells, or traverses the tree otherwise.
+    pub fn within_unsorted<D>(&self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let Some(ranges) = self.query_cell_ranges::<D>(query, dist) else {
+            return self.tree.within_unsorted::<D>(query, dist);
+        };
+
+        let mut leaves: Vec<u32> = Vec::new();
+        for_each_cell(self.cells_per_axis, &ranges, |cell| {
+            let start = self.cell_offsets[cell] as usize;
+            let end = self.cell_offsets[cell + 1] as usize;
+            leaves.extend_from_slice(&self.cell_leaves[start..end]);
+        });
+        leaves.sort_unstable();
+        leaves.dedup();
+
+        let mut results = Vec::new();
+        for leaf_idx in leaves {
+            self.tree
+                .get_leaf_slice(leaf_idx as usize)
+                .nearest_n_within::<D, Vec<NearestNeighbour<A, T>>>(query, dist, &mut results);
+        }
+
+        #[cfg(feature = "verify_results")]
+        crate::verify::verify_nearest_n_within(
+            &self.tree,
+            &crate::verify::LeafMetric::<D, T>::new(),
+            query,
+            Some(dist),
+            usize::MAX,
+            &results,
+        );
+
+        results
+    }
+
+    /// Finds all elements within `dist` of `query`, using the specified distance metric
+    /// function. Results are returned sorted nearest-first.
+    ///
+    /// Uses the grid to find the leaves to search if the query overlaps few enough
+    /// cells, or traverses the tree otherwise.
+    pub fn within<D>(&self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut results = self.within_unsorted::<D>(query, dist);
+        results.sort();
+        results
+    }
+
+    /// Returns the range of cells, on each axis, that could contain points within
+    /// `dist` of `query`, or `None` if that would be more than `max_grid_cells` cells.
+    fn query_cell_ranges<D>(&self, query: &[A; K], dist: A) -> Option<[(usize, usize); K]>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut ranges = [(0, 0); K];
+        let mut cell_count = 1usize;
+
+        for dim in 0..K {
+            // no point can be within range of a query if it is out of range along any
+            // one axis, so a cell only needs searching if its nearest edge is in range
+            // The edges are widened slightly so that rounding can't cause a cell to be
+            // skipped that `cell_coord` would have placed a matching point in.
+            let slack = self.cell_width[dim] / A::from(256).unwrap();
+            let in_range = |cell: usize| {
+                let lo = self.origin[dim] + self.cell_width[dim] * A::from(cell).unwrap() - slack;
+                let hi = lo + self.cell_width[dim] + slack + slack;
+                D::dist1(query[dim], query[dim].max(lo).min(hi)) <= dist
+            };
+
+            let query_cell = self.cell_coord(dim, query[dim]);
+            if !in_range(query_cell) {
+                return Some([(1, 0); K]);
+            }
+
+            let mut lo = query_cell;
+            while lo > 0 && in_range(lo - 1) {
+                lo -= 1;
+            }
+            let mut hi = query_cell;
+            while hi + 1 < self.cells_per_axis && in_range(hi + 1) {
+                hi += 1;
+            }
+
+            cell_count = cell_count.saturating_mul(hi - lo + 1);
+            if cell_count > self.max_grid_cells {
+                return None;
+            }
+            ranges[dim] = (lo, hi);
+        }
+
+        Some(ranges)
+    }
+
+    fn cell_coord(&self, dim: usize, val: A) -> usize {
+        ((val - self.origin[dim]) / self.cell_width[dim])
+            .floor()
+            .to_usize()
+            .unwrap_or(0)
+            .min(self.cells_per_axis - 1)
+    }
+}
+
+/// Calls `f` with the index of every cell within the inclusive per-axis `ranges`
+fn for_each_cell<const K: usize>(
+    cells_per_axis: usize,
+    ranges: &[(usize, usize); K],
+    mut f: impl FnMut(usize),
+) {
+    if ranges.iter().any(|&(lo, hi)| lo > hi) {
+        return;
+    }
+
+    let mut coords: [usize; K] = ranges.map(|(lo, _)| lo);
+    loop {
+        let cell = coords
+            .iter()
+            .rev()
+            .fold(0, |acc, &c| acc * cells_per_axis + c);
+        f(cell);
+
+        let mut dim = 0;
+        loop {
+            if dim == K {
+                return;
+            }
+            if coords[dim] < ranges[dim].1 {
+                coords[dim] += 1;
+                break;
+            }
+            coords[dim] = ranges[dim].0;
+            dim += 1;
+        }
+    }
+}
+
+fn leaf_bounds<A, T, const K: usize, const B: usize>(
+    tree: &ImmutableKdTree<A, T, K, B>,
+    leaf_idx: usize,
+) -> Option<([A; K], [A; K])>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    usize: Cast<T>,
+{
+    let (start, end) = tree.leaf_extents[leaf_idx];
+    if start == end {
+        return None;
+    }
+
+    let range = start as usize..end as usize;
+    Some((
+        std::array::from_fn(|dim| {
+            tree.leaf_points[dim][range.clone()]
+                .iter()
+                .copied()
+                .fold(A::infinity(), A::min)
+        }),
+        std::array::from_fn(|dim| {
+            tree.leaf_points[dim][range.clone()]
+                .iter()
+                .copied()
+                .fold(A::neg_infinity(), A::max)
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GriddedImmutableKdTree;
+    use crate::float::distance::{Chebyshev, Manhattan, SquaredEuclidean};
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn grid_queries_match_tree_queries() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(11);
+        let points: Vec<[f64; 2]> = (0..20_000).map(|_| rng.gen::<[f64; 2]>()).collect();
+        let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+        let gridded = GriddedImmutableKdTree::new(tree);
+        assert!(gridded.cells_per_axis() > 1);
+
+        for _ in 0..200 {
+            // include queries outside of the bounds of the points
+            let query = [rng.gen_range(-0.1..1.1), rng.gen_range(-0.1..1.1)];
+
+            for radius in [0.0001, 0.001, 0.01, 0.5] {
+                assert_eq!(
+                    gridded.within::<SquaredEuclidean>(&query, radius),
+                    gridded.tree().within::<SquaredEuclidean>(&query, radius)
+                );
+            }
+            assert_eq!(
+                gridded.within::<Manhattan>(&query, 0.02),
+                gridded.tree().within::<Manhattan>(&query, 0.02)
+            );
+            assert_eq!(
+                gridded.within::<Chebyshev>(&query, 0.02),
+                gridded.tree().within::<Chebyshev>(&query, 0.02)
+            );
+        }
+    }
+
+    #[test]
+    fn grid_queries_match_tree_queries_3d() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(12);
+        let points: Vec<[f32; 3]> = (0..20_000).map(|_| rng.gen::<[f32; 3]>()).collect();
+        let tree: ImmutableKdTree<f32, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+        let gridded = GriddedImmutableKdTree::with_cells_per_axis(tree, 16);
+
+        for _ in 0..200 {
+            let query = rng.gen::<[f32; 3]>();
+            assert_eq!(
+                gridded.within::<SquaredEuclidean>(&query, 0.002),
+                gridded.tree().within::<SquaredEuclidean>(&query, 0.002)
+            );
+        }
+    }
+
+    #[test]
+    fn handles_degenerate_trees() {
+        let empty: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&[]);
+        let gridded = GriddedImmutableKdTree::new(empty);
+        assert!(gridded
+            .within::<SquaredEuclidean>(&[0.0, 0.0], 1.0)
+            .is_empty());
+
+        // all points on a line, so that the grid has no width on one axis
+        let points: Vec<[f64; 2]> = (0..1000).map(|i| [i as f64, 5.0]).collect();
+        let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+        let gridded = GriddedImmutableKdTree::new(tree);
+
+        // equidistant items can come back in either order, so compare as sorted pairs
+        let sorted_pairs = |results: Vec<crate::nearest_neighbour::NearestNeighbour<f64, u32>>| {
+            let mut pairs: Vec<(f64, u32)> = results
+                .into_iter()
+                .map(|nn| (nn.distance, nn.item))
+                .collect();
+            pairs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            pairs
+        };
+        assert_eq!(
+            sorted_pairs(gridded.within::<SquaredEuclidean>(&[500.0, 5.0], 4.5)),
+            sorted_pairs(
+                gridded
+                    .tree()
+                    .within::<SquaredEuclidean>(&[500.0, 5.0], 4.5)
+            )
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Ordering of points along a Hilbert curve, used to improve the memory locality of
+//! `ImmutableKdTree` construction.
+
+use crate::float::kdtree::Axis;
+
+const MAX_BITS_PER_AXIS: usize = 16;
+
+/// Returns the indices of the points in `source`, in the order in which they lie along
+/// a Hilbert curve that covers their bounding box, or `None` if `K` is too large for
+/// a curve with a useful resolution to be indexed with a `u64`.
+///
+/// The curve only needs to be fine enough to separate points into groups that are
+/// about the size of a leaf, so it has around as many cells as there are points, with
+/// each axis divided into at most `2^16` cells.
+pub(crate) fn hilbert_order<A: Axis, const K: usize>(source: &[[A; K]]) -> Option<Vec<usize>> {
+    let max_bits = (u64::BITS as usize / K.max(1)).min(MAX_BITS_PER_AXIS);
+    if max_bits < 2 {
+        return None;
+    }
+    let bits_for_len = (source.len().max(1).ilog2() as usize).div_ceil(K.max(1)) + 1;
+    let bits = bits_for_len.clamp(2, max_bits) as u32;
+
+    let mut min = [f64::INFINITY; K];
+    let mut max = [f64::NEG_INFINITY; K];
+    for point in source {
+        for dim in 0..K {
+            let val = point[dim].to_f64().unwrap_or(0.0);
+            min[dim] = min[dim].min(val);
+            max[dim] = max[dim].max(val);
+        }
+    }
+
+    let cells = ((1u64 << bits) - 1) as f64;
+    let scale: [f64; K] = std::array::from_fn(|dim| {
+        if max[dim] > min[dim] {
+            cells / (max[dim] - min[dim])
+        } else {
+            0.0
+        }
+    });
+
+    let mut keyed: Vec<(u64, usize)> = source
+        .iter()
+        .enumerate()
+        .map(|(idx, point)| {
+            let mut cell = [0u32; K];
+            for dim in 0..K {
+                let val = point[dim].to_f64().unwrap_or(0.0);
+                // NaNs saturate to zero
+                cell[dim] = ((val - min[dim]) * scale[dim]) as u32;
+            }
+            (hilbert_index(cell, bits), idx)
+        })
+        .collect();
+
+    keyed.sort_unstable_by_key(|&(key, _)| key);
+
+    Some(keyed.into_iter().map(|(_, idx)| idx).collect())
+}
+
+/// The distance along a `bits`-deep Hilbert curve of the cell at `cell`, using
+/// Skilling's algorithm ("Programming the Hilbert curve", AIP Conf. Proc. 707, 2004).
+fn hilbert_index<const K: usize>(mut cell: [u32; K], bits: u32) -> u64 {
+    let top = 1u32 << (bits - 1);
+
+    // inverse undo
+    let mut q = top;
+    while q > 1 {
+        let p = q - 1;
+        for dim in 0..K {
+            // branchless form of: if the bit is set, invert the low bits of the first
+            // axis, otherwise exchange the low bits of the first axis and this one.
+            // The bits of each point are effectively random, so branches mispredict
+            let is_set = 0u32.wrapping_sub(u32::from(cell[dim] & q != 0));
+            let t = (cell[0] ^ cell[dim]) & p & !is_set;
+            cell[0] ^= (p & is_set) | t;
+            cell[dim] ^= t;
+        }
+        q >>= 1;
+    }
+
+    // gray encode
+    for dim in 1..K {
+        cell[dim] ^= cell[dim - 1];
+    }
+    let mut t = 0;
+    let mut q = top;
+    while q > 1 {
+        t ^= (q - 1) & 0u32.wrapping_sub(u32::from(cell[K - 1] & q != 0));
+        q >>= 1;
+    }
+    for val in &mut cell {
+        *val ^= t;
+    }
+
+    // interleave the transposed bits, most significant first
+    let mut index = 0u64;
+    for bit in (0..bits).rev() {
+        for val in cell {
+            index = (index << 1) | ((val >> bit) & 1) as u64;
+        }
+    }
+
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hilbert_index, hilbert_order};
+
+    #[test]
+    fn consecutive_hilbert_indices_are_adjacent_cells() {
+        const BITS: u32 = 4;
+        let side = 1u32 << BITS;
+
+        let mut cells: Vec<(u64, [u32; 2])> = (0..side)
+            .flat_map(|x| (0..side).map(move |y| [x, y]))
+            .map(|cell| (hilbert_index(cell, BITS), cell))
+            .collect();
+        cells.sort();
+
+        // every index is used exactly once
+        assert!(cells
+            .iter()
+            .enumerate()
+            .all(|(idx, (key, _))| *key == idx as u64));
+
+        for pair in cells.windows(2) {
+            let [(_, a), (_, b)] = pair else {
+                unreachable!()
+            };
+            assert_eq!(a[0].abs_diff(b[0]) + a[1].abs_diff(b[1]), 1);
+        }
+    }
+
+    #[test]
+    fn hilbert_order_is_a_permutation() {
+        let points: Vec<[f64; 3]> = (0..1_000).map(|_| rand::random()).collect();
+
+        let mut order = hilbert_order(&points).unwrap();
+        order.sort();
+
+        assert_eq!(order, (0..1_000).collect::<Vec<_>>());
+        assert!(hilbert_order::<f64, 40>(&[[0.0; 40]]).is_none());
+    }
+}
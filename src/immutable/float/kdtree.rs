@@ -11,22 +11,51 @@
 //! As with the vanilla tree, [`f64`] or [`f32`] are supported currently for co-ordinate
 //! values, or [`f16`](https://docs.rs/half/latest/half/struct.f16.html) if the `f16` feature is enabled
 
+use crate::best_neighbour::BestNeighbour;
+use crate::build_progress::{BuildMonitor, CancellationToken};
+use crate::construction_error::{check_finite, ConstructionError};
 pub use crate::float::kdtree::Axis;
 use crate::float_leaf_slice::leaf_slice::{LeafSlice, LeafSliceFloat, LeafSliceFloatChunk};
+use crate::generate_immutable_tree_stats;
+use crate::immutable::float::hilbert::hilbert_order;
+use crate::iter::{IterableTreeData, TreeItems, TreeIter};
 #[cfg(feature = "modified_van_emde_boas")]
 use crate::modified_van_emde_boas::modified_van_emde_boas_get_child_idx_v2_branchless;
-use crate::traits::Content;
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::rounding::Rounding;
+use crate::split_policy::SplitPolicy;
+use crate::traits::{Content, DistanceMetric, KdTreeQuery};
 use aligned_vec::{avec, AVec, ConstAlign, CACHELINE_ALIGN};
 use array_init::array_init;
-use az::{Az, Cast};
+use az::{Az, Cast, CheckedCast};
 use cmov::Cmov;
 use ordered_float::OrderedFloat;
 #[cfg(feature = "rkyv")]
 use rkyv::vec::ArchivedVec;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "allocator_api")]
+use std::alloc::Allocator;
 use std::cmp::PartialEq;
 use std::fmt::Debug;
+use std::num::NonZero;
+
+/// The type used to store the start and end of the items of each leaf of an
+/// [`ImmutableKdTree`], which limits the number of items that the tree can hold.
+///
+/// This is `u32` unless the `u64_leaf_extents` feature is enabled, in which case it is
+/// `u64` and trees can hold more than `u32::MAX` items, at the cost of another eight
+/// bytes per leaf.
+#[cfg(not(feature = "u64_leaf_extents"))]
+pub type LeafExtent = u32;
+
+/// The type used to store the start and end of the items of each leaf of an
+/// [`ImmutableKdTree`], which limits the number of items that the tree can hold.
+///
+/// This is `u64` as the `u64_leaf_extents` feature is enabled, so trees can hold more
+/// than `u32::MAX` items.
+#[cfg(feature = "u64_leaf_extents")]
+pub type LeafExtent = u64;
 
 /// Immutable floating point k-d tree
 ///
@@ -45,6 +74,7 @@ use std::fmt::Debug;
 ///
 /// A convenient type alias exists for ImmutableKdTree with some sensible defaults set: [`kiddo::ImmutableKdTree`](`crate::ImmutableKdTree`).
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "ImmutableKdTreeSerde<A, T, K>"))]
 #[derive(Clone, Debug, PartialEq)]
 pub struct ImmutableKdTree<A: Copy + Default, T: Copy + Default, const K: usize, const B: usize> {
     pub(crate) stems: AVec<A>,
@@ -59,8 +89,74 @@ pub struct ImmutableKdTree<A: Copy + Default, T: Copy + Default, const K: usize,
     )]
     pub(crate) leaf_points: [Vec<A>; K],
     pub(crate) leaf_items: Vec<T>,
-    pub(crate) leaf_extents: Vec<(u32, u32)>,
+    pub(crate) leaf_extents: Vec<(LeafExtent, LeafExtent)>,
     pub(crate) max_stem_level: i32,
+
+    /// The number of entries that have been deleted, and now sit after the end of
+    /// their leaf's extent. Not serialized, as it can be worked out from the extents
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) tombstone_count: usize,
+
+    /// The dimension that each stem splits on, indexed in the same way as `stems`. Empty
+    /// for trees whose stems cycle through the dimensions in order, one per level.
+    /// Serializing a tree that has them fails, as the serde layout can't hold them
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            skip_serializing_if = "Vec::is_empty",
+            serialize_with = "reject_split_dims"
+        )
+    )]
+    pub(crate) split_dims: Vec<u8>,
+}
+
+/// Fails to serialize the split dimensions of a tree. Only called for trees that have them,
+/// which need [`Versioned`](crate::versioned::Versioned), so that the layout stays the same
+/// for every other tree
+#[cfg(feature = "serde")]
+fn reject_split_dims<S: serde::Serializer>(_: &[u8], _: S) -> Result<S::Ok, S::Error> {
+    Err(serde::ser::Error::custom(
+        "trees built with a SplitPolicy other than RoundRobin can only be serialized \
+         with serde by wrapping them in kiddo::versioned::Versioned",
+    ))
+}
+
+/// The fields of an [`ImmutableKdTree`] that are serialized with serde, which are the same
+/// as in earlier versions of Kiddo, so that trees that they serialized can still be read
+/// with non-self-describing formats such as bincode
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "A: Deserialize<'de>, T: Deserialize<'de> + Copy + Default"))]
+struct ImmutableKdTreeSerde<A: Copy + Default, T: Copy + Default, const K: usize> {
+    stems: AVec<A>,
+    #[serde(with = "crate::custom_serde::array_of_vecs")]
+    leaf_points: [Vec<A>; K],
+    leaf_items: Vec<T>,
+    leaf_extents: Vec<(LeafExtent, LeafExtent)>,
+    max_stem_level: i32,
+}
+
+#[cfg(feature = "serde")]
+impl<A: Copy + Default, T: Copy + Default, const K: usize, const B: usize>
+    From<ImmutableKdTreeSerde<A, T, K>> for ImmutableKdTree<A, T, K, B>
+{
+    fn from(repr: ImmutableKdTreeSerde<A, T, K>) -> Self {
+        let live_count: usize = repr
+            .leaf_extents
+            .iter()
+            .map(|&(start, end)| (end - start) as usize)
+            .sum();
+
+        ImmutableKdTree {
+            tombstone_count: repr.leaf_items.len().saturating_sub(live_count),
+            stems: repr.stems,
+            leaf_points: repr.leaf_points,
+            leaf_items: repr.leaf_items,
+            leaf_extents: repr.leaf_extents,
+            max_stem_level: repr.max_stem_level,
+            split_dims: Vec::new(),
+        }
+    }
 }
 
 /// rkyv-Archivable / Serializable version of an [`ImmutableKdTree`].
@@ -74,8 +170,9 @@ pub struct ImmutableKdTreeRK<A: Copy + Default, T: Copy + Default, const K: usiz
     pub(crate) stems: Vec<A>,
     pub(crate) leaf_points: [Vec<A>; K],
     pub(crate) leaf_items: Vec<T>,
-    pub(crate) leaf_extents: Vec<(u32, u32)>,
+    pub(crate) leaf_extents: Vec<(LeafExtent, LeafExtent)>,
     pub(crate) max_stem_level: i32,
+    pub(crate) split_dims: Vec<u8>,
 }
 
 #[cfg(feature = "rkyv")]
@@ -106,13 +203,18 @@ where
     ///
     /// assert_eq!(tree.size(), 1);
     /// ```
-    fn from(orig: ImmutableKdTree<A, T, K, B>) -> Self {
+    fn from(mut orig: ImmutableKdTree<A, T, K, B>) -> Self {
+        // archived trees have no record of deleted items, so leave them out altogether
+        orig.compact();
+
         let ImmutableKdTree {
             stems,
             leaf_points,
             leaf_items,
             leaf_extents,
             max_stem_level,
+            split_dims,
+            ..
         } = orig;
 
         let (ptr, _, length, capacity) = stems.into_raw_parts();
@@ -124,16 +226,22 @@ where
             leaf_items,
             leaf_extents,
             max_stem_level,
+            split_dims,
         }
     }
 }
 
 /// rkyv zero-copy deserializable version of an `ImmutableKdTree`.
 ///
-/// Convert an `ImmutableKdTreeRK` into this in order to perform queries.
+/// Convert an `ImmutableKdTreeRK` into this in order to perform queries with the stems
+/// aligned to cache lines, as they are in an [`ImmutableKdTree`].
 /// Required because the AlignedVec used for storing stem node values cannot
 /// be zero-copy deserialized. You need to first zero-copy-deserialize into an
 /// `ImmutableKdTreeRK` and then convert that into one of these, re-aligning the stems.
+///
+/// Re-aligning the stems involves copying them. For trees that are only queried a handful
+/// of times after being loaded, it can be quicker to query the `ArchivedImmutableKdTreeRK`
+/// directly instead.
 #[cfg(feature = "rkyv")]
 #[derive(Debug, PartialEq)]
 pub struct AlignedArchivedImmutableKdTree<
@@ -146,8 +254,9 @@ pub struct AlignedArchivedImmutableKdTree<
     pub(crate) stems: AVec<A, ConstAlign<CACHELINE_ALIGN>>,
     pub(crate) leaf_points: &'a [ArchivedVec<A>; K],
     pub(crate) leaf_items: &'a ArchivedVec<T>,
-    pub(crate) leaf_extents: &'a ArchivedVec<(u32, u32)>,
+    pub(crate) leaf_extents: &'a ArchivedVec<(LeafExtent, LeafExtent)>,
     pub(crate) max_stem_level: i32,
+    pub(crate) split_dims: &'a ArchivedVec<u8>,
 }
 
 #[cfg(feature = "rkyv")]
@@ -168,6 +277,7 @@ impl<
             leaf_extents: &value.leaf_extents,
             leaf_items: &value.leaf_items,
             max_stem_level: value.max_stem_level,
+            split_dims: &value.split_dims,
         }
     }
 
@@ -196,10 +306,47 @@ where
         self.leaf_items.len()
     }
 
-    /// Returns a LeafSlice for a given leaf index
+    /// Returns the level of the deepest stem node in the tree, with the root being level `0`.
+    ///
+    /// Trees with a single leaf have no stems, and return `-1`.
+    #[inline]
+    pub fn max_stem_level(&self) -> i32 {
+        self.max_stem_level
+    }
+
+    /// Returns the length of the stem array, including any unused padding entries
+    #[inline]
+    pub fn stem_count(&self) -> usize {
+        self.stems.len()
+    }
+
+    /// Returns the number of leaves in the tree
+    #[inline]
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_extents.len()
+    }
+
+    /// Returns the number of items in the largest leaf of the tree
+    #[inline]
+    pub fn max_leaf_size(&self) -> usize {
+        self.leaf_extents
+            .iter()
+            .map(|&(start, end)| (end - start) as usize)
+            .max()
+            .unwrap_or(0)
+    }
+
+    generate_immutable_tree_stats!();
+
+    /// Returns a [`LeafSlice`] over the points and items of the leaf at `leaf_idx`.
+    /// See [`ImmutableKdTree::get_leaf_slice`] for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `leaf_idx` is not less than [`leaf_count`](Self::leaf_count).
     #[inline]
-    pub(crate) fn get_leaf_slice(&self, leaf_idx: usize) -> LeafSlice<A, T, K> {
-        let (start, end) = unsafe { *self.leaf_extents.get_unchecked(leaf_idx) };
+    pub fn get_leaf_slice(&self, leaf_idx: usize) -> LeafSlice<'_, A, T, K> {
+        let (start, end) = self.leaf_extents[leaf_idx];
 
         // Artificially extend size to be at least chunk length for faster processing
         // TODO: why does this slow things down?
@@ -212,6 +359,346 @@ where
     }
 }
 
+#[cfg(feature = "rkyv")]
+impl<A, T, const K: usize, const B: usize> AlignedArchivedImmutableKdTree<'_, A, T, K, B>
+where
+    A: Axis + rkyv::Archive<Archived = A>,
+    T: Content + rkyv::Archive<Archived = T>,
+{
+    /// Returns the dimension that the stem at `stem_idx` splits on, which is
+    /// `round_robin_dim` unless the tree was built with a [`SplitPolicy`] that chooses
+    /// the dimension of each stem.
+    #[inline(always)]
+    pub(crate) fn split_dim_at(&self, stem_idx: usize, round_robin_dim: usize) -> usize {
+        if self.split_dims.is_empty() {
+            round_robin_dim
+        } else {
+            self.split_dims[stem_idx] as usize
+        }
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<A, T, const K: usize, const B: usize> IterableTreeData<A, T, K>
+    for AlignedArchivedImmutableKdTree<'_, A, T, K, B>
+where
+    A: Axis + rkyv::Archive<Archived = A>,
+    T: Content + rkyv::Archive<Archived = T>,
+{
+    fn get_leaf_data(&self, idx: usize, out: &mut Vec<(T, [A; K])>) -> Option<usize> {
+        let (start, end) = *self.leaf_extents.get(idx)?;
+        out.extend((start as usize..end as usize).map(|i| {
+            (
+                self.leaf_items[i],
+                array_init(|dim| self.leaf_points[dim][i]),
+            )
+        }));
+        Some((end - start) as usize)
+    }
+}
+
+/// Queries can be performed directly on an `ArchivedImmutableKdTreeRK`, without first
+/// converting it into an [`AlignedArchivedImmutableKdTree`].
+///
+/// Conversion copies the stems into a new cache-line aligned allocation, which takes time
+/// and memory proportional to the number of leaves in the tree, but makes every query after
+/// that slightly faster. Querying the `ArchivedImmutableKdTreeRK` directly avoids the copy,
+/// at the cost of the stems only having the natural alignment of `A` within the archive.
+/// Prefer this if a tree is loaded and only queried a handful of times before being
+/// discarded, and prefer converting to an [`AlignedArchivedImmutableKdTree`] otherwise.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::fs::File;
+/// use memmap::MmapOptions;
+///
+/// use kiddo::immutable::float::kdtree::ImmutableKdTreeRK;
+/// use kiddo::SquaredEuclidean;
+///
+/// let mmap = unsafe { MmapOptions::new().map(&File::open("./examples/immutable-doctest-tree.rkyv").unwrap()).unwrap() };
+/// let tree = unsafe { rkyv::archived_root::<ImmutableKdTreeRK<f64, u32, 3, 256>>(&mmap) };
+///
+/// let nearest = tree.nearest_one::<SquaredEuclidean>(&[1.0, 2.0, 5.1]);
+/// assert_eq!(nearest.item, 0);
+/// ```
+#[cfg(feature = "rkyv")]
+impl<A, T, const K: usize, const B: usize> ArchivedImmutableKdTreeRK<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K> + rkyv::Archive<Archived = A>,
+    T: Content + rkyv::Archive<Archived = T>,
+    usize: Cast<T>,
+{
+    /// Returns the current number of elements stored in the tree
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.leaf_items.len()
+    }
+
+    /// Returns the level of the deepest stem node in the tree, with the root being level `0`.
+    ///
+    /// Trees with a single leaf have no stems, and return `-1`.
+    #[inline]
+    pub fn max_stem_level(&self) -> i32 {
+        self.max_stem_level
+    }
+
+    /// Returns the length of the stem array, including any unused padding entries
+    #[inline]
+    pub fn stem_count(&self) -> usize {
+        self.stems.len()
+    }
+
+    /// Returns the number of leaves in the tree
+    #[inline]
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_extents.len()
+    }
+
+    /// Returns the number of items in the largest leaf of the tree
+    #[inline]
+    pub fn max_leaf_size(&self) -> usize {
+        self.leaf_extents
+            .iter()
+            .map(|&(start, end)| (end - start) as usize)
+            .max()
+            .unwrap_or(0)
+    }
+
+    generate_immutable_tree_stats!();
+
+    /// Returns a [`LeafSlice`] over the points and items of the leaf at `leaf_idx`.
+    /// See [`ImmutableKdTree::get_leaf_slice`] for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `leaf_idx` is not less than [`leaf_count`](Self::leaf_count).
+    #[inline]
+    pub fn get_leaf_slice(&self, leaf_idx: usize) -> LeafSlice<'_, A, T, K> {
+        let (start, end) = self.leaf_extents[leaf_idx];
+
+        LeafSlice::new(
+            array_init::array_init(|i| &self.leaf_points[i][start as usize..end as usize]),
+            &self.leaf_items[start as usize..end as usize],
+        )
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<A, T, const K: usize, const B: usize> ArchivedImmutableKdTreeRK<A, T, K, B>
+where
+    A: Axis + rkyv::Archive<Archived = A>,
+    T: Content + rkyv::Archive<Archived = T>,
+{
+    /// Returns the dimension that the stem at `stem_idx` splits on, which is
+    /// `round_robin_dim` unless the tree was built with a [`SplitPolicy`] that chooses
+    /// the dimension of each stem.
+    #[inline(always)]
+    pub(crate) fn split_dim_at(&self, stem_idx: usize, round_robin_dim: usize) -> usize {
+        if self.split_dims.is_empty() {
+            round_robin_dim
+        } else {
+            self.split_dims[stem_idx] as usize
+        }
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl<A, T, const K: usize, const B: usize> IterableTreeData<A, T, K>
+    for ArchivedImmutableKdTreeRK<A, T, K, B>
+where
+    A: Axis + rkyv::Archive<Archived = A>,
+    T: Content + rkyv::Archive<Archived = T>,
+{
+    fn get_leaf_data(&self, idx: usize, out: &mut Vec<(T, [A; K])>) -> Option<usize> {
+        let (start, end) = *self.leaf_extents.get(idx)?;
+        out.extend((start as usize..end as usize).map(|i| {
+            (
+                self.leaf_items[i],
+                array_init(|dim| self.leaf_points[dim][i]),
+            )
+        }));
+        Some((end - start) as usize)
+    }
+}
+
+/// A version of an [`ImmutableKdTree`] whose stems and leaves are stored in memory from
+/// a caller-supplied allocator.
+///
+/// Useful for placing large trees in a specific memory pool, such as the local memory of
+/// a NUMA node or an arena. Requires the `allocator_api` feature, which needs nightly Rust.
+///
+/// The stems are not cache-line aligned, as they are in an [`ImmutableKdTree`], unless
+/// the supplied allocator aligns them itself.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::alloc::System;
+/// use kiddo::immutable::float::kdtree::ImmutableKdTreeIn;
+/// use kiddo::SquaredEuclidean;
+///
+/// let points: Vec<[f64; 3]> = vec!([1.0, 2.0, 5.0], [2.0, 3.0, 6.0]);
+/// let tree: ImmutableKdTreeIn<f64, u32, 3, 32, System> =
+///     ImmutableKdTreeIn::new_from_slice_in(&points, System);
+///
+/// assert_eq!(tree.size(), 2);
+/// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[1.0, 2.0, 5.1]).item, 0);
+/// ```
+#[cfg(feature = "allocator_api")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImmutableKdTreeIn<
+    A: Copy + Default,
+    T: Copy + Default,
+    const K: usize,
+    const B: usize,
+    Alloc: Allocator + Clone,
+> {
+    pub(crate) stems: Vec<A, Alloc>,
+    pub(crate) leaf_points: [Vec<A, Alloc>; K],
+    pub(crate) leaf_items: Vec<T, Alloc>,
+    pub(crate) leaf_extents: Vec<(LeafExtent, LeafExtent), Alloc>,
+    pub(crate) max_stem_level: i32,
+    pub(crate) split_dims: Vec<u8, Alloc>,
+}
+
+#[cfg(feature = "allocator_api")]
+impl<A, T, const K: usize, const B: usize, Alloc> ImmutableKdTreeIn<A, T, K, B, Alloc>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    Alloc: Allocator + Clone,
+    usize: Cast<T>,
+{
+    /// Creates an `ImmutableKdTreeIn`, balanced and optimized, populated with items from
+    /// `source`, storing its stems and leaves in memory from `alloc`.
+    ///
+    /// The tree is built in the same way as by [`ImmutableKdTree::new_from_slice`], using
+    /// the global allocator for its working memory, and then copied into `alloc`.
+    pub fn new_from_slice_in(source: &[[A; K]], alloc: Alloc) -> Self {
+        Self::copy_in(&ImmutableKdTree::new_from_slice(source), alloc)
+    }
+
+    /// Creates an `ImmutableKdTreeIn` holding a copy of `tree`, with its stems and leaves
+    /// stored in memory from `alloc`.
+    pub fn copy_in(tree: &ImmutableKdTree<A, T, K, B>, alloc: Alloc) -> Self {
+        if tree.tombstone_count() > 0 {
+            let mut compacted = tree.clone();
+            compacted.compact();
+            return Self::copy_in(&compacted, alloc);
+        }
+
+        fn copy_slice_in<X: Copy, Alloc: Allocator>(slice: &[X], alloc: Alloc) -> Vec<X, Alloc> {
+            let mut vec = Vec::with_capacity_in(slice.len(), alloc);
+            vec.extend_from_slice(slice);
+            vec
+        }
+
+        ImmutableKdTreeIn {
+            stems: copy_slice_in(&tree.stems, alloc.clone()),
+            leaf_points: array_init(|dim| copy_slice_in(&tree.leaf_points[dim], alloc.clone())),
+            leaf_items: copy_slice_in(&tree.leaf_items, alloc.clone()),
+            leaf_extents: copy_slice_in(&tree.leaf_extents, alloc.clone()),
+            max_stem_level: tree.max_stem_level,
+            split_dims: copy_slice_in(&tree.split_dims, alloc),
+        }
+    }
+
+    /// Returns the current number of elements stored in the tree
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.leaf_items.len()
+    }
+
+    /// Returns the level of the deepest stem node in the tree, with the root being level `0`.
+    ///
+    /// Trees with a single leaf have no stems, and return `-1`.
+    #[inline]
+    pub fn max_stem_level(&self) -> i32 {
+        self.max_stem_level
+    }
+
+    /// Returns the length of the stem array, including any unused padding entries
+    #[inline]
+    pub fn stem_count(&self) -> usize {
+        self.stems.len()
+    }
+
+    /// Returns the number of leaves in the tree
+    #[inline]
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_extents.len()
+    }
+
+    /// Returns the number of items in the largest leaf of the tree
+    #[inline]
+    pub fn max_leaf_size(&self) -> usize {
+        self.leaf_extents
+            .iter()
+            .map(|&(start, end)| (end - start) as usize)
+            .max()
+            .unwrap_or(0)
+    }
+
+    generate_immutable_tree_stats!();
+
+    /// Returns a [`LeafSlice`] over the points and items of the leaf at `leaf_idx`.
+    /// See [`ImmutableKdTree::get_leaf_slice`] for details.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `leaf_idx` is not less than [`leaf_count`](Self::leaf_count).
+    #[inline]
+    pub fn get_leaf_slice(&self, leaf_idx: usize) -> LeafSlice<'_, A, T, K> {
+        let (start, end) = self.leaf_extents[leaf_idx];
+
+        LeafSlice::new(
+            array_init::array_init(|i| &self.leaf_points[i][start as usize..end as usize]),
+            &self.leaf_items[start as usize..end as usize],
+        )
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+impl<A, T, const K: usize, const B: usize, Alloc> ImmutableKdTreeIn<A, T, K, B, Alloc>
+where
+    A: Axis,
+    T: Content,
+    Alloc: Allocator + Clone,
+{
+    /// Returns the dimension that the stem at `stem_idx` splits on, which is
+    /// `round_robin_dim` unless the tree was built with a [`SplitPolicy`] that chooses
+    /// the dimension of each stem.
+    #[inline(always)]
+    pub(crate) fn split_dim_at(&self, stem_idx: usize, round_robin_dim: usize) -> usize {
+        if self.split_dims.is_empty() {
+            round_robin_dim
+        } else {
+            self.split_dims[stem_idx] as usize
+        }
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+impl<A, T, const K: usize, const B: usize, Alloc> IterableTreeData<A, T, K>
+    for ImmutableKdTreeIn<A, T, K, B, Alloc>
+where
+    A: Axis,
+    T: Content,
+    Alloc: Allocator + Clone,
+{
+    fn get_leaf_data(&self, idx: usize, out: &mut Vec<(T, [A; K])>) -> Option<usize> {
+        let (start, end) = *self.leaf_extents.get(idx)?;
+        out.extend((start as usize..end as usize).map(|i| {
+            (
+                self.leaf_items[i],
+                array_init(|dim| self.leaf_points[dim][i]),
+            )
+        }));
+        Some((end - start) as usize)
+    }
+}
+
 impl<A: Axis, T: Content, const K: usize, const B: usize> From<&[[A; K]]>
     for ImmutableKdTree<A, T, K, B>
 where
@@ -240,6 +727,36 @@ where
     }
 }
 
+/// Random access to the coordinates of the points that a tree is being built from,
+/// whether they are held as one array per point or as one column per dimension
+pub(crate) trait PointSource<A> {
+    fn coord(&self, idx: usize, dim: usize) -> A;
+}
+
+impl<A: Copy, const K: usize> PointSource<A> for [[A; K]] {
+    #[inline(always)]
+    fn coord(&self, idx: usize, dim: usize) -> A {
+        self[idx][dim]
+    }
+}
+
+impl<A: Copy, C: AsRef<[A]>, const K: usize> PointSource<A> for [C; K] {
+    #[inline(always)]
+    fn coord(&self, idx: usize, dim: usize) -> A {
+        self[dim].as_ref()[idx]
+    }
+}
+
+/// The stems and leaf extents of a tree under construction, along with the indices of its
+/// points in the order in which they are to be stored in the leaves
+struct Partitioned<A> {
+    stems: AVec<A, ConstAlign<{ CACHELINE_ALIGN }>>,
+    split_dims: Vec<u8>,
+    sort_index: Vec<usize>,
+    leaf_extents: Vec<(LeafExtent, LeafExtent)>,
+    max_stem_level: i32,
+}
+
 // prevent clippy complaining that the feature unreliable_select_nth_unstable
 // is not defined (I don't want to explicitly define it as if I do then
 // passing --all-features in CI will enable it, which I don't want to do
@@ -271,46 +788,523 @@ where
     where
         usize: Cast<T>,
     {
-        let item_count = source.len();
-        let leaf_node_count = item_count.div_ceil(B);
+        Self::new_from_slice_with_items(source, &|idx| idx.az::<T>(), SplitPolicy::RoundRobin)
+    }
 
-        #[cfg(not(feature = "modified_van_emde_boas"))]
-        let stem_node_count = if leaf_node_count < 2 {
-            0
-        } else {
-            leaf_node_count.next_power_of_two()
-        };
+    /// Creates an `ImmutableKdTree`, balanced and optimized, populated with items from
+    /// `source`, choosing the dimension that each stem splits on according to `policy`.
+    ///
+    /// With [`SplitPolicy::MaxSpread`], each stem splits on the dimension along which its
+    /// points are most spread out, rather than the dimensions being taken in turn. This
+    /// suits anisotropic data, such as geographic points covering a narrow range of
+    /// latitudes, whose queries would otherwise visit more leaves than they need to. Each
+    /// stem then also records its dimension, adding a byte per stem to the size of the tree.
+    /// Such trees can be serialized with `rkyv`, in the [`portable`](crate::portable) format
+    /// or, with serde, by wrapping them in `kiddo::versioned::Versioned`, but not by
+    /// serializing them with serde directly.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `policy` is [`SplitPolicy::MaxSpread`] and `K` is more than 256.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    /// use kiddo::{SplitPolicy, SquaredEuclidean};
+    ///
+    /// let points: Vec<[f64; 2]> = (0..1000).map(|idx| [idx as f64, (idx % 7) as f64 * 0.001]).collect();
+    /// let tree: ImmutableKdTree<f64, u32, 2, 32> =
+    ///     ImmutableKdTree::new_from_slice_with_policy(&points, SplitPolicy::MaxSpread);
+    ///
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[500.1, 0.0]).item, 500);
+    /// ```
+    pub fn new_from_slice_with_policy(source: &[[A; K]], policy: SplitPolicy) -> Self
+    where
+        usize: Cast<T>,
+    {
+        assert!(
+            policy == SplitPolicy::RoundRobin || K <= u8::MAX as usize + 1,
+            "split dimensions are stored as u8, so trees with more than 256 dimensions must use SplitPolicy::RoundRobin"
+        );
 
-        #[cfg(feature = "modified_van_emde_boas")]
-        let stem_node_count = if leaf_node_count < 2 {
-            0
-        } else {
-            leaf_node_count.next_power_of_two() - 1
-        };
+        Self::new_from_slice_with_items(source, &|idx| idx.az::<T>(), policy)
+    }
 
-        let max_stem_level: i32 = leaf_node_count.next_power_of_two().ilog2() as i32 - 1;
+    /// Creates an `ImmutableKdTree`, balanced and optimized, populated with items from
+    /// `source`, or returns an error instead of panicking or building an unusable tree if
+    /// that isn't possible.
+    ///
+    /// Fails if any point has a NaN coordinate, or if there are too many points for their
+    /// indices to fit in `T` or for the tree's [`LeafExtent`] type to address.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    /// use kiddo::ConstructionError;
+    ///
+    /// let points: Vec<[f64; 2]> = vec!([1.0, 2.0], [f64::NAN, 4.0]);
+    /// let result = ImmutableKdTree::<f64, u32, 2, 32>::try_new_from_slice(&points);
+    ///
+    /// assert_eq!(result.unwrap_err(), ConstructionError::NanCoordinate { index: 1 });
+    /// ```
+    pub fn try_new_from_slice(source: &[[A; K]]) -> Result<Self, ConstructionError>
+    where
+        usize: CheckedCast<T>,
+    {
+        if let Some(index) = source
+            .iter()
+            .position(|point| point.iter().any(|val| val.is_nan()))
+        {
+            return Err(ConstructionError::NanCoordinate { index });
+        }
 
-        // TODO: It would be nice to be able to determine the exact required length up-front.
-        //  Instead, we just trim the stems afterwards by traversing right-child non-inf nodes
-        //  till we hit max level to get the max used stem
-        #[cfg(feature = "modified_van_emde_boas")]
-        let stem_node_count = stem_node_count * 5;
+        // leaf extents must be able to address every item, and each item is its point's index
+        let fits = LeafExtent::try_from(source.len()).is_ok()
+            && source
+                .len()
+                .checked_sub(1)
+                .is_none_or(|max_idx| max_idx.checked_cast().is_some());
+        if !fits {
+            return Err(ConstructionError::TooManyItems { len: source.len() });
+        }
 
-        let mut stems = avec![A::infinity(); stem_node_count];
-        let mut leaf_points: [Vec<A>; K] = array_init(|_| Vec::with_capacity(item_count));
-        let mut leaf_items: Vec<T> = Vec::with_capacity(item_count);
-        let mut leaf_extents: Vec<(u32, u32)> = Vec::with_capacity(item_count.div_ceil(B));
+        Ok(Self::new_from_slice(source))
+    }
+
+    /// Creates an `ImmutableKdTree`, balanced and optimized, populated with items from
+    /// `source`, after checking that every coordinate of every point is finite.
+    ///
+    /// NaN coordinates cannot be ordered, and infinite ones are used to mark unused stems,
+    /// so points with either can be misplaced.
+    /// [`new_from_slice`](ImmutableKdTree::new_from_slice) only checks for NaNs, and only in
+    /// debug builds. Otherwise fails in the same cases as
+    /// [`try_new_from_slice`](ImmutableKdTree::try_new_from_slice).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    /// use kiddo::ConstructionError;
+    ///
+    /// let points: Vec<[f64; 2]> = vec!([1.0, 2.0], [3.0, f64::INFINITY]);
+    /// let result = ImmutableKdTree::<f64, u32, 2, 32>::new_from_slice_checked(&points);
+    ///
+    /// assert_eq!(result.unwrap_err(), ConstructionError::InfiniteCoordinate { index: 1 });
+    /// ```
+    pub fn new_from_slice_checked(source: &[[A; K]]) -> Result<Self, ConstructionError>
+    where
+        usize: CheckedCast<T>,
+    {
+        for (index, point) in source.iter().enumerate() {
+            check_finite(index, point)?;
+        }
+
+        Self::try_new_from_slice(source)
+    }
+
+    /// Creates an `ImmutableKdTree`, balanced and optimized, populated with items from
+    /// `source`, calling `progress` as the points are placed in their leaves, and giving up
+    /// if `cancel` is cancelled.
+    ///
+    /// Building a tree of hundreds of millions of points can take minutes. `progress` is
+    /// passed the number of points placed so far and the total number of points each time
+    /// a leaf is filled, and so is called once for every `B` points or so: throttle any
+    /// expensive reporting inside it. Calling [`cancel`](CancellationToken::cancel) on
+    /// `cancel`, or on a clone of it, from another thread stops the build shortly
+    /// afterwards, returning [`ConstructionError::Cancelled`].
+    ///
+    /// Otherwise the tree is exactly the same as that built by
+    /// [`new_from_slice`](ImmutableKdTree::new_from_slice).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    /// use kiddo::{CancellationToken, ConstructionError};
+    ///
+    /// let points: Vec<[f64; 2]> = (0..1000).map(|idx| [idx as f64, (idx * 37 % 1000) as f64]).collect();
+    /// let mut placed = 0;
+    /// let tree = ImmutableKdTree::<f64, u32, 2, 32>::new_from_slice_with_progress(
+    ///     &points,
+    ///     |done, _total| placed = done,
+    ///     &CancellationToken::new(),
+    /// ).unwrap();
+    ///
+    /// assert_eq!(tree.size(), 1000);
+    /// assert_eq!(placed, 1000);
+    ///
+    /// let cancel = CancellationToken::new();
+    /// let result = ImmutableKdTree::<f64, u32, 2, 32>::new_from_slice_with_progress(
+    ///     &points,
+    ///     |_done, _total| cancel.cancel(),
+    ///     &cancel,
+    /// );
+    ///
+    /// assert_eq!(result.unwrap_err(), ConstructionError::Cancelled);
+    /// ```
+    pub fn new_from_slice_with_progress<F>(
+        source: &[[A; K]],
+        mut progress: F,
+        cancel: &CancellationToken,
+    ) -> Result<Self, ConstructionError>
+    where
+        F: FnMut(usize, usize),
+        usize: Cast<T>,
+    {
+        let mut monitor = BuildMonitor::new(source.len(), &mut progress, cancel);
+
+        Self::new_from_slice_monitored(
+            source,
+            &|idx| idx.az::<T>(),
+            SplitPolicy::RoundRobin,
+            &mut monitor,
+        )
+    }
+
+    /// Creates an `ImmutableKdTree`, balanced and optimized, populated with items from
+    /// `source`, by first copying the points into the order in which they lie along a
+    /// Hilbert curve.
+    ///
+    /// Points that are close to each other in space then mostly end up close to each other
+    /// in memory too, which makes partitioning them much more cache-friendly. Ordering the
+    /// points has a cost of its own though, as does the memory needed for a second copy of
+    /// `source`, so whether this is faster overall than
+    /// [`new_from_slice`](ImmutableKdTree::new_from_slice) depends on the machine and the
+    /// data. It is only worth trying for trees with many millions of points.
+    ///
+    /// As with [`new_from_slice`](ImmutableKdTree::new_from_slice), each item is the index
+    /// of its point within `source`, so the tree can be used in exactly the same way.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    /// use kiddo::SquaredEuclidean;
+    ///
+    /// let points: Vec<[f64; 3]> = vec!([1.0, 2.0, 3.0], [4.0, 5.0, 6.0], [7.0, 8.0, 9.0]);
+    /// let tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice_hilbert(&points);
+    ///
+    /// assert_eq!(tree.size(), 3);
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[4.0, 5.0, 6.1]).item, 1);
+    /// ```
+    pub fn new_from_slice_hilbert(source: &[[A; K]]) -> Self
+    where
+        usize: Cast<T>,
+    {
+        let Some(order) = hilbert_order(source) else {
+            return Self::new_from_slice(source);
+        };
+
+        let sorted: Vec<[A; K]> = order.iter().map(|&idx| source[idx]).collect();
+
+        Self::new_from_slice_with_items(
+            &sorted,
+            &|idx| order[idx].az::<T>(),
+            SplitPolicy::RoundRobin,
+        )
+    }
+
+    /// Creates an `ImmutableKdTree` that contains every item of each of `trees`.
+    ///
+    /// Items keep the values that they had in the tree they came from, so if the trees
+    /// were each built with [`new_from_slice`](ImmutableKdTree::new_from_slice), the
+    /// same item will appear once for each tree. The points are copied straight out of
+    /// each tree's leaves, with no need to keep the slices that the trees were built from.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    /// use kiddo::SquaredEuclidean;
+    ///
+    /// let shard_a: ImmutableKdTree<f64, u32, 2, 32> =
+    ///     ImmutableKdTree::new_from_slice(&[[1.0, 2.0], [3.0, 4.0]]);
+    /// let shard_b: ImmutableKdTree<f64, u32, 2, 32> =
+    ///     ImmutableKdTree::new_from_slice(&[[5.0, 6.0]]);
+    ///
+    /// let tree = ImmutableKdTree::from_trees(&[&shard_a, &shard_b]);
+    ///
+    /// assert_eq!(tree.size(), 3);
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[5.0, 6.1]).item, 0);
+    /// ```
+    pub fn from_trees(trees: &[&Self]) -> Self {
+        let item_count = trees.iter().map(|tree| tree.size()).sum();
+        let mut points: Vec<[A; K]> = Vec::with_capacity(item_count);
+        let mut items: Vec<T> = Vec::with_capacity(item_count);
+
+        for tree in trees {
+            for (item, point) in tree.iter() {
+                items.push(item);
+                points.push(point);
+            }
+        }
+
+        Self::new_from_slice_with_items(&points, &|idx| items[idx], SplitPolicy::RoundRobin)
+    }
+
+    /// Creates an `ImmutableKdTree`, balanced and optimized, populated with the points
+    /// yielded by `iter`, without needing them to be collected into a slice first.
+    ///
+    /// The points are copied straight into the column-per-dimension layout that the tree
+    /// stores them in, and then reordered in place one dimension at a time, so building
+    /// from a streaming source such as a file reader needs little more memory than the
+    /// finished tree, rather than a full extra copy of the points.
+    ///
+    /// `len_hint` is used to reserve space up front, and should be the number of points
+    /// that `iter` will yield, if known. As with
+    /// [`new_from_slice`](ImmutableKdTree::new_from_slice), each item is the index of
+    /// its point in the order that `iter` yields them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    /// use kiddo::SquaredEuclidean;
+    ///
+    /// let points = (0..100).map(|idx| [idx as f64, (idx * 37 % 100) as f64]);
+    /// let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_iter(100, points);
+    ///
+    /// assert_eq!(tree.size(), 100);
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[5.0, 85.1]).item, 5);
+    /// ```
+    pub fn new_from_iter<I>(len_hint: usize, iter: I) -> Self
+    where
+        I: IntoIterator<Item = [A; K]>,
+    {
+        let mut columns: [Vec<A>; K] = array_init(|_| Vec::with_capacity(len_hint));
+        let mut item_count = 0;
+        for point in iter {
+            for (column, val) in columns.iter_mut().zip(point) {
+                column.push(val);
+            }
+            item_count += 1;
+        }
+
+        debug_assert!(
+            !columns.iter().flatten().any(|val| val.is_nan()),
+            "points must not have NaN coordinates"
+        );
+
+        let Partitioned {
+            stems,
+            split_dims,
+            sort_index,
+            leaf_extents,
+            max_stem_level,
+        } = Self::partition(
+            &columns,
+            item_count,
+            SplitPolicy::RoundRobin,
+            &mut BuildMonitor::default(),
+        );
+
+        // only one column needs to be held twice at any one time
+        let mut leaf_points = columns;
+        for column in &mut leaf_points {
+            *column = sort_index.iter().map(|&idx| column[idx]).collect();
+        }
+        let leaf_items = sort_index.iter().map(|&idx| idx.az::<T>()).collect();
+
+        Self {
+            stems,
+            leaf_points,
+            leaf_items,
+            leaf_extents,
+            max_stem_level,
+            tombstone_count: 0,
+            split_dims,
+        }
+    }
+
+    /// Creates an `ImmutableKdTree`, balanced and optimized, populated with points whose
+    /// coordinates are given by one slice per dimension.
+    ///
+    /// Useful when the points are already held in columnar form, such as in Arrow arrays,
+    /// as the coordinates are copied straight from the columns into the tree's leaves,
+    /// without first having to be assembled into a slice of `[A; K]`. As with
+    /// [`new_from_slice`](ImmutableKdTree::new_from_slice), each item is the index of its
+    /// point within the columns.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the columns are not all the same length.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    /// use kiddo::SquaredEuclidean;
+    ///
+    /// let xs = vec![1.0, 3.0, 5.0];
+    /// let ys = vec![2.0, 4.0, 6.0];
+    /// let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_columns(&[&xs, &ys]);
+    ///
+    /// assert_eq!(tree.size(), 3);
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[3.1, 4.1]).item, 1);
+    /// ```
+    pub fn new_from_columns(columns: &[&[A]; K]) -> Self {
+        let item_count = columns.first().map_or(0, |column| column.len());
+        assert!(
+            columns.iter().all(|column| column.len() == item_count),
+            "every column must have the same length"
+        );
+
+        Self::new_from_point_source(columns, item_count)
+    }
+
+    /// Creates an `ImmutableKdTree` from the `item_count` points of `source`, with each
+    /// item being the index of its point.
+    pub(crate) fn new_from_point_source<S: PointSource<A> + ?Sized>(
+        source: &S,
+        item_count: usize,
+    ) -> Self {
+        debug_assert!(
+            !(0..item_count).any(|idx| (0..K).any(|dim| source.coord(idx, dim).is_nan())),
+            "points must not have NaN coordinates"
+        );
+
+        let Partitioned {
+            stems,
+            split_dims,
+            sort_index,
+            leaf_extents,
+            max_stem_level,
+        } = Self::partition(
+            source,
+            item_count,
+            SplitPolicy::RoundRobin,
+            &mut BuildMonitor::default(),
+        );
+
+        let leaf_points = array_init(|dim| {
+            sort_index
+                .iter()
+                .map(|&idx| source.coord(idx, dim))
+                .collect()
+        });
+        let leaf_items = sort_index.iter().map(|&idx| idx.az::<T>()).collect();
+
+        Self {
+            stems,
+            leaf_points,
+            leaf_items,
+            leaf_extents,
+            max_stem_level,
+            tombstone_count: 0,
+            split_dims,
+        }
+    }
+
+    /// Creates an `ImmutableKdTree` from `source`, with the item for each point
+    /// being given by `item_for` when passed the point's index within `source`.
+    pub(crate) fn new_from_slice_with_items(
+        source: &[[A; K]],
+        item_for: &impl Fn(usize) -> T,
+        policy: SplitPolicy,
+    ) -> Self {
+        let Ok(tree) =
+            Self::new_from_slice_monitored(source, item_for, policy, &mut BuildMonitor::default())
+        else {
+            unreachable!("construction can only be cancelled through a CancellationToken")
+        };
+
+        tree
+    }
+
+    /// As [`new_from_slice_with_items`](ImmutableKdTree::new_from_slice_with_items), but
+    /// reporting progress to, and stopping early if cancelled through, `monitor`.
+    fn new_from_slice_monitored(
+        source: &[[A; K]],
+        item_for: &impl Fn(usize) -> T,
+        policy: SplitPolicy,
+        monitor: &mut BuildMonitor<'_>,
+    ) -> Result<Self, ConstructionError> {
+        debug_assert!(
+            !source.iter().flatten().any(|val| val.is_nan()),
+            "points must not have NaN coordinates"
+        );
+
+        let Partitioned {
+            stems,
+            split_dims,
+            sort_index,
+            leaf_extents,
+            max_stem_level,
+        } = Self::partition(source, source.len(), policy, monitor);
+
+        if monitor.is_cancelled() {
+            return Err(ConstructionError::Cancelled);
+        }
+
+        let leaf_points =
+            array_init(|dim| sort_index.iter().map(|&idx| source[idx][dim]).collect());
+        let leaf_items = sort_index.iter().map(|&idx| item_for(idx)).collect();
+
+        Ok(Self {
+            stems,
+            leaf_points,
+            leaf_items,
+            leaf_extents,
+            max_stem_level,
+            tombstone_count: 0,
+            split_dims,
+        })
+    }
+
+    /// Works out the stems and leaves of a tree holding the `item_count` points of
+    /// `source`, returning along with them the indices of the points in the order in
+    /// which they are to be stored in the leaves.
+    fn partition<S: PointSource<A> + ?Sized>(
+        source: &S,
+        item_count: usize,
+        policy: SplitPolicy,
+        monitor: &mut BuildMonitor<'_>,
+    ) -> Partitioned<A> {
+        assert!(
+            LeafExtent::try_from(item_count).is_ok(),
+            "too many items for the leaf extent type. Enable the u64_leaf_extents feature"
+        );
+        let leaf_node_count = item_count.div_ceil(B);
+
+        #[cfg(not(feature = "modified_van_emde_boas"))]
+        let stem_node_count = if leaf_node_count < 2 {
+            0
+        } else {
+            leaf_node_count.next_power_of_two()
+        };
+
+        #[cfg(feature = "modified_van_emde_boas")]
+        let stem_node_count = if leaf_node_count < 2 {
+            0
+        } else {
+            leaf_node_count.next_power_of_two() - 1
+        };
+
+        let max_stem_level: i32 = leaf_node_count.next_power_of_two().ilog2() as i32 - 1;
+
+        // TODO: It would be nice to be able to determine the exact required length up-front.
+        //  Instead, we just trim the stems afterwards by traversing right-child non-inf nodes
+        //  till we hit max level to get the max used stem
+        #[cfg(feature = "modified_van_emde_boas")]
+        let stem_node_count = stem_node_count * 5;
+
+        let mut stems = avec![A::infinity(); stem_node_count];
+        let mut split_dims = if policy == SplitPolicy::RoundRobin {
+            Vec::new()
+        } else {
+            vec![0u8; stem_node_count]
+        };
+        let mut leaf_extents: Vec<(LeafExtent, LeafExtent)> =
+            Vec::with_capacity(item_count.div_ceil(B));
 
         let mut sort_index = Vec::from_iter(0..item_count);
 
         if stem_node_count == 0 {
             // Write leaf and terminate recursion
-            leaf_extents.push((0u32, sort_index.len() as u32));
-
-            (0..sort_index.len()).for_each(|i| {
-                (0..K).for_each(|dim| leaf_points[dim].push(source[sort_index[i]][dim]));
-                leaf_items.push(sort_index[i].az::<T>())
-            });
+            leaf_extents.push((0, sort_index.len() as LeafExtent));
+            monitor.items_placed(item_count);
         } else {
             #[cfg(not(feature = "modified_van_emde_boas"))]
             let initial_stem_idx = 1;
@@ -326,10 +1320,11 @@ where
                 0,
                 0,
                 max_stem_level,
-                leaf_node_count * B,
-                &mut leaf_points,
-                &mut leaf_items,
+                leaf_node_count.next_power_of_two() * B,
                 &mut leaf_extents,
+                policy,
+                &mut split_dims,
+                monitor,
             );
 
             // trim unneeded stems
@@ -354,212 +1349,1391 @@ where
                     }
                 }
                 stems.truncate(stem_idx + 1);
+                split_dims.truncate(stems.len());
+            }
+        }
+
+        Partitioned {
+            stems,
+            split_dims,
+            sort_index,
+            leaf_extents,
+            max_stem_level,
+        }
+    }
+
+    /// Partitions `sort_index` into the leaves below `stem_index`, writing the stems'
+    /// split values and, since leaves are visited in order, the extent of each leaf.
+    #[allow(clippy::too_many_arguments)]
+    fn populate_recursive<S: PointSource<A> + ?Sized>(
+        stems: &mut AVec<A, ConstAlign<{ CACHELINE_ALIGN }>>,
+        dim: usize,
+        source: &S,
+        sort_index: &mut [usize],
+        stem_index: usize,
+        mut level: i32,
+        mut minor_level: u64,
+        max_stem_level: i32,
+        capacity: usize,
+        leaf_extents: &mut Vec<(LeafExtent, LeafExtent)>,
+        policy: SplitPolicy,
+        split_dims: &mut [u8],
+        monitor: &mut BuildMonitor<'_>,
+    ) {
+        if monitor.is_cancelled() {
+            return;
+        }
+
+        let chunk_length = sort_index.len();
+
+        if level > max_stem_level {
+            // Write leaf and terminate recursion
+            let start = leaf_extents.last().map_or(0, |&(_, end)| end);
+            let end = start + chunk_length as LeafExtent;
+            leaf_extents.push((start, end));
+            monitor.items_placed(end as usize);
+
+            return;
+        }
+
+        let dim = match policy {
+            SplitPolicy::RoundRobin => dim,
+            _ => {
+                let dim = Self::choose_split_dim(source, sort_index, policy);
+                split_dims[stem_index] = dim as u8;
+                dim
+            }
+        };
+
+        let levels_below = max_stem_level - level;
+        let left_capacity = (2usize.pow(levels_below as u32) * B).min(capacity);
+        let right_capacity = capacity.saturating_sub(left_capacity);
+
+        let mut pivot = Self::calc_pivot(chunk_length, stem_index, right_capacity);
+
+        // only bother with this if we are putting at least one item in the right hand child
+        if pivot < chunk_length {
+            pivot = Self::update_pivot(
+                source,
+                sort_index,
+                dim,
+                pivot,
+                left_capacity,
+                right_capacity,
+            );
+
+            // if we end up with a pivot of 0, something has gone wrong,
+            // unless we only had a slice of len 1 anyway
+            debug_assert!(pivot > 0 || chunk_length == 1);
+            debug_assert!(
+                stems[stem_index].is_infinite(),
+                "Wrote to stem #{:?} for a second time",
+                stem_index
+            );
+
+            // the pivot may have been moved past a run of equal values that
+            // extends to the end of the chunk, leaving the right child empty
+            if pivot < chunk_length {
+                stems[stem_index] = source.coord(sort_index[pivot], dim);
+            }
+        }
+
+        #[cfg(feature = "modified_van_emde_boas")]
+        let left_child_idx = modified_van_emde_boas_get_child_idx_v2_branchless(
+            stem_index as u32,
+            false,
+            minor_level as u32,
+        ) as usize;
+        #[cfg(feature = "modified_van_emde_boas")]
+        let right_child_idx = modified_van_emde_boas_get_child_idx_v2_branchless(
+            stem_index as u32,
+            true,
+            minor_level as u32,
+        ) as usize;
+
+        #[cfg(not(feature = "modified_van_emde_boas"))]
+        let left_child_idx = stem_index << 1;
+        #[cfg(not(feature = "modified_van_emde_boas"))]
+        let right_child_idx = (stem_index << 1) + 1;
+
+        let (lower_sort_index, upper_sort_index) = sort_index.split_at_mut(pivot);
+
+        level += 1;
+        minor_level += 1;
+        minor_level.cmovnz(&0, u8::from(minor_level == 3));
+
+        let next_dim = (dim + 1) % K;
+
+        Self::populate_recursive(
+            stems,
+            next_dim,
+            source,
+            lower_sort_index,
+            left_child_idx,
+            level,
+            minor_level,
+            max_stem_level,
+            left_capacity,
+            leaf_extents,
+            policy,
+            split_dims,
+            monitor,
+        );
+
+        Self::populate_recursive(
+            stems,
+            next_dim,
+            source,
+            upper_sort_index,
+            right_child_idx,
+            level,
+            minor_level,
+            max_stem_level,
+            right_capacity,
+            leaf_extents,
+            policy,
+            split_dims,
+            monitor,
+        );
+    }
+
+    #[cfg(not(feature = "unreliable_select_nth_unstable"))]
+    #[inline]
+    fn update_pivot<S: PointSource<A> + ?Sized>(
+        source: &S,
+        sort_index: &mut [usize],
+        dim: usize,
+        mut pivot: usize,
+        left_capacity: usize,
+        right_capacity: usize,
+    ) -> usize {
+        // TODO: this block might be faster by using a quickselect with a fat partition?
+        //       we could then run that quickselect and subtract (fat partition length - 1)
+        //       from the pivot, avoiding the need for the while loop.
+
+        // ensure the item whose index = pivot is in its correctly sorted position, and any
+        // items that are equal to it are adjacent, according to our assumptions about the
+        // behaviour of `select_nth_unstable_by` (See examples/check_select_nth_unstable.rs)
+        Self::select_nth(source, sort_index, dim, pivot);
+
+        if pivot == 0 {
+            return pivot;
+        }
+
+        let requested_pivot = pivot;
+        let pivot_val = source.coord(sort_index[pivot], dim);
+
+        // if the pivot straddles two values that are equal, keep nudging it left until they aren't
+        while source.coord(sort_index[pivot], dim) == source.coord(sort_index[pivot - 1], dim)
+            && pivot > 1
+        {
+            pivot -= 1;
+        }
+
+        let chunk_length = sort_index.len();
+        if chunk_length - pivot <= right_capacity {
+            return pivot;
+        }
+
+        // Nudging left has pushed more items into the right subtree than its leaves can hold.
+        // Try nudging right instead, past every item equal to the split value. Everything
+        // from the requested pivot onwards is no smaller than it, so start by gathering
+        // the equal items together at the front of that range.
+        let upper = &mut sort_index[requested_pivot..];
+        let mut equal_count = 0;
+        for idx in 0..upper.len() {
+            if source.coord(upper[idx], dim) == pivot_val {
+                upper.swap(idx, equal_count);
+                equal_count += 1;
+            }
+        }
+
+        let right_pivot = requested_pivot + equal_count;
+        if right_pivot <= left_capacity {
+            // The split value needs to be the smallest of the items going right
+            if right_pivot < chunk_length {
+                Self::select_nth(source, &mut sort_index[right_pivot..], dim, 0);
+            }
+
+            return right_pivot;
+        }
+
+        // Neither fits, so spill the run of equal values across both children, splitting
+        // it at the requested pivot. Queries only rely on items in the left child being no
+        // greater than the split value, and those in the right child being no smaller.
+        requested_pivot
+    }
+
+    /// Moves the item that belongs at position `nth` of `sort_index`, when ordered by
+    /// coordinate `dim`, into that position, with no larger items before it and no
+    /// smaller items after it.
+    ///
+    /// With the `deterministic_construction` feature, the whole of `sort_index` is sorted
+    /// instead, breaking ties by index, so that the order in which points are stored does
+    /// not depend on the implementation of `select_nth_unstable` in the standard library.
+    #[inline]
+    fn select_nth<S: PointSource<A> + ?Sized>(
+        source: &S,
+        sort_index: &mut [usize],
+        dim: usize,
+        nth: usize,
+    ) {
+        #[cfg(not(feature = "deterministic_construction"))]
+        sort_index.select_nth_unstable_by_key(nth, |&i| OrderedFloat(source.coord(i, dim)));
+
+        #[cfg(feature = "deterministic_construction")]
+        {
+            let _ = nth;
+            sort_index.sort_unstable_by_key(|&i| (OrderedFloat(source.coord(i, dim)), i));
+        }
+    }
+
+    /// Returns the current number of elements stored in the tree
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    ///
+    /// let points: Vec<[f64; 3]> = vec!([1.0f64, 2.0f64, 3.0f64]);
+    /// let tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+    ///
+    /// assert_eq!(tree.size(), 1);
+    /// ```
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.leaf_items.len() - self.tombstone_count
+    }
+
+    /// Returns the theoretical max capacity of this tree
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.size()
+    }
+
+    /// Iterate over all `(index, point)` tuples in arbitrary order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    ///
+    /// let points: Vec<[f64; 3]> = vec!([1.0f64, 2.0f64, 3.0f64], [4.0f64, 5.0f64, 6.0f64]);
+    /// let tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+    ///
+    /// let mut pairs: Vec<_> = tree.iter().collect();
+    /// pairs.sort_by_key(|&(item, _)| item);
+    /// assert_eq!(pairs, vec![(0, [1.0, 2.0, 3.0]), (1, [4.0, 5.0, 6.0])]);
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = (T, [A; K])> + '_ {
+        TreeIter::new(self, B)
+    }
+
+    /// Returns the level of the deepest stem node in the tree, with the root being level `0`.
+    ///
+    /// Trees with a single leaf have no stems, and return `-1`.
+    /// Useful for sizing the traversal stack of an external query implementation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    ///
+    /// let points: Vec<[f64; 2]> = (0..1000).map(|i| [i as f64, ((i * 7919) % 1000) as f64]).collect();
+    /// let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+    ///
+    /// assert_eq!(tree.leaf_count(), 32);
+    /// assert_eq!(tree.max_stem_level(), 4);
+    /// assert!(tree.max_leaf_size() <= 32);
+    /// assert!(tree.stem_count() >= 31);
+    /// ```
+    #[inline]
+    pub fn max_stem_level(&self) -> i32 {
+        self.max_stem_level
+    }
+
+    /// Returns the length of the stem array, including any unused padding entries
+    #[inline]
+    pub fn stem_count(&self) -> usize {
+        self.stems.len()
+    }
+
+    /// Returns the number of leaves in the tree
+    #[inline]
+    pub fn leaf_count(&self) -> usize {
+        self.leaf_extents.len()
+    }
+
+    /// Returns the number of items in the largest leaf of the tree
+    #[inline]
+    pub fn max_leaf_size(&self) -> usize {
+        self.leaf_extents
+            .iter()
+            .map(|&(start, end)| (end - start) as usize)
+            .max()
+            .unwrap_or(0)
+    }
+
+    generate_immutable_tree_stats!();
+
+    /// Returns the point that `item` is stored at, or `None` if it isn't in the tree.
+    ///
+    /// If `item` is stored more than once, any one of its points may be returned. Every
+    /// item is scanned, so this takes time proportional to the size of the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    ///
+    /// let points: Vec<[f64; 3]> = vec!([1.0, 2.0, 3.0], [4.0, 5.0, 6.0]);
+    /// let tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+    ///
+    /// assert_eq!(tree.get(1), Some([4.0, 5.0, 6.0]));
+    /// assert!(!tree.contains_item(2));
+    /// ```
+    pub fn get(&self, item: T) -> Option<[A; K]> {
+        self.iter()
+            .find(|&(stored, _)| stored == item)
+            .map(|(_, point)| point)
+    }
+
+    /// Returns whether `item` is stored in the tree. See [`get`](ImmutableKdTree::get)
+    /// for details.
+    pub fn contains_item(&self, item: T) -> bool {
+        self.get(item).is_some()
+    }
+
+    /// Deletes every entry for `item` from the tree, returning the number of entries
+    /// deleted.
+    ///
+    /// Deleted entries are skipped by all queries, but the tree's layout is left as it
+    /// is: each one is moved to the end of its leaf, and the leaf shrunk to exclude it.
+    /// Their space is only reclaimed by [`compact`](ImmutableKdTree::compact), which is
+    /// worth calling once [`tombstone_fraction`](ImmutableKdTree::tombstone_fraction)
+    /// grows large enough for the gaps in the leaves to slow queries down. Every item is
+    /// scanned, so this takes time proportional to the size of the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    /// use kiddo::SquaredEuclidean;
+    ///
+    /// let points: Vec<[f64; 2]> = vec!([1.0, 2.0], [3.0, 4.0], [5.0, 6.0]);
+    /// let mut tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+    ///
+    /// assert_eq!(tree.delete(1), 1);
+    ///
+    /// assert_eq!(tree.size(), 2);
+    /// assert_eq!(tree.tombstone_count(), 1);
+    /// assert_ne!(tree.nearest_one::<SquaredEuclidean>(&[3.0, 4.0]).item, 1);
+    ///
+    /// tree.compact();
+    /// assert_eq!(tree.size(), 2);
+    /// assert_eq!(tree.tombstone_count(), 0);
+    /// ```
+    pub fn delete(&mut self, item: T) -> usize {
+        let mut deleted = 0;
+
+        for leaf_idx in 0..self.leaf_extents.len() {
+            let (start, mut end) = self.leaf_extents[leaf_idx];
+            let mut idx = start;
+
+            while idx < end {
+                if self.leaf_items[idx as usize] == item {
+                    end -= 1;
+                    self.leaf_items.swap(idx as usize, end as usize);
+                    self.leaf_points
+                        .iter_mut()
+                        .for_each(|points| points.swap(idx as usize, end as usize));
+                    deleted += 1;
+                } else {
+                    idx += 1;
+                }
+            }
+
+            self.leaf_extents[leaf_idx].1 = end;
+        }
+
+        self.tombstone_count += deleted;
+        deleted
+    }
+
+    /// Returns the number of entries that have been deleted with
+    /// [`delete`](ImmutableKdTree::delete) since the tree was built or last compacted
+    #[inline]
+    pub fn tombstone_count(&self) -> usize {
+        self.tombstone_count
+    }
+
+    /// Returns the fraction of the tree's stored entries that have been deleted, from
+    /// `0.0` for a tree with no deletions to `1.0` for one where everything was deleted
+    pub fn tombstone_fraction(&self) -> f64 {
+        if self.leaf_items.is_empty() {
+            0.0
+        } else {
+            self.tombstone_count as f64 / self.leaf_items.len() as f64
+        }
+    }
+
+    /// Rebuilds the tree without its deleted entries, releasing the space that they
+    /// took up. Does nothing if nothing has been deleted.
+    pub fn compact(&mut self) {
+        if self.tombstone_count == 0 {
+            return;
+        }
+
+        let policy = if self.split_dims.is_empty() {
+            SplitPolicy::RoundRobin
+        } else {
+            SplitPolicy::MaxSpread
+        };
+
+        let (items, points): (Vec<T>, Vec<[A; K]>) = self.iter().unzip();
+        *self = Self::new_from_slice_with_items(&points, &|idx| items[idx], policy);
+    }
+
+    /// Returns the dimension along which the points of `sort_index` have the greatest range
+    fn choose_split_dim<S: PointSource<A> + ?Sized>(
+        source: &S,
+        sort_index: &[usize],
+        policy: SplitPolicy,
+    ) -> usize {
+        debug_assert_eq!(policy, SplitPolicy::MaxSpread);
+
+        let mut mins = [A::infinity(); K];
+        let mut maxes = [A::neg_infinity(); K];
+        for &idx in sort_index {
+            for dim in 0..K {
+                let val = source.coord(idx, dim);
+                mins[dim] = mins[dim].min(val);
+                maxes[dim] = maxes[dim].max(val);
+            }
+        }
+
+        (0..K)
+            .max_by_key(|&dim| OrderedFloat(maxes[dim] - mins[dim]))
+            .unwrap_or(0)
+    }
+
+    /// Splits the chunk in half, unless that would give the right subtree more items
+    /// than its leaves can hold.
+    fn calc_pivot(chunk_length: usize, _stem_index: usize, right_capacity: usize) -> usize {
+        (chunk_length >> 1).max(chunk_length.saturating_sub(right_capacity))
+    }
+
+    /// Returns a [`LeafSlice`] over the points and items of the leaf at `leaf_idx`, for
+    /// implementing custom scans over the tree's leaves.
+    ///
+    /// Leaves are numbered from `0` to [`leaf_count`](ImmutableKdTree::leaf_count) `- 1`,
+    /// in the order of their positions along the tree, and
+    /// [`leaf_for_point`](ImmutableKdTree::leaf_for_point) finds the leaf that a query
+    /// descends to. Entries that have been [deleted](ImmutableKdTree::delete) are not
+    /// included.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `leaf_idx` is not less than [`leaf_count`](ImmutableKdTree::leaf_count).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    ///
+    /// let points: Vec<[f64; 2]> = (0..1000).map(|i| [i as f64, (i * 37 % 1000) as f64]).collect();
+    /// let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+    ///
+    /// let leaf = tree.get_leaf_slice(tree.leaf_for_point(&[10.0, 370.0]));
+    /// assert!(leaf.iter().any(|(item, point)| item == 10 && point == [10.0, 370.0]));
+    ///
+    /// let total: usize = (0..tree.leaf_count()).map(|idx| tree.get_leaf_slice(idx).len()).sum();
+    /// assert_eq!(total, tree.size());
+    /// ```
+    #[inline]
+    pub fn get_leaf_slice(&self, leaf_idx: usize) -> LeafSlice<'_, A, T, K> {
+        let (start, end) = self.leaf_extents[leaf_idx];
+
+        // Artificially extend size to be at least chunk length for faster processing
+        // TODO: why does this slow things down?
+        // let end = end.max(start + 32).min(self.leaf_items.len() as u32);
+
+        LeafSlice::new(
+            array_init::array_init(|i| &self.leaf_points[i][start as usize..end as usize]),
+            &self.leaf_items[start as usize..end as usize],
+        )
+    }
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize> ImmutableKdTree<A, T, K, B> {
+    /// Returns the dimension that the stem at `stem_idx` splits on, which is
+    /// `round_robin_dim` unless the tree was built with a [`SplitPolicy`] that chooses
+    /// the dimension of each stem.
+    #[inline(always)]
+    pub(crate) fn split_dim_at(&self, stem_idx: usize, round_robin_dim: usize) -> usize {
+        if self.split_dims.is_empty() {
+            round_robin_dim
+        } else {
+            self.split_dims[stem_idx] as usize
+        }
+    }
+}
+
+impl<T: Content, const K: usize, const B: usize> ImmutableKdTree<f64, T, K, B> {
+    /// Converts the tree into one that stores its co-ordinates as `f32`s, halving the
+    /// memory that they take up, with each co-ordinate rounded as directed by `rounding`.
+    ///
+    /// The structure of the tree is kept as it is rather than being rebuilt from the
+    /// converted points, which is much quicker and means that items stay in the same
+    /// leaves. Rounding preserves the order of the co-ordinates, so queries of the
+    /// converted tree give the same results as a search of every converted point would.
+    ///
+    /// Fails with [`ConstructionError::CoordinateOutOfRange`] if any finite co-ordinate
+    /// would become infinite.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    /// use kiddo::{Rounding, SquaredEuclidean};
+    ///
+    /// let points: Vec<[f64; 2]> = (0..1000).map(|idx| [idx as f64 * 0.1, (idx % 7) as f64]).collect();
+    /// let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+    ///
+    /// let small: ImmutableKdTree<f32, u32, 2, 32> = tree.to_f32(Rounding::Nearest).unwrap();
+    ///
+    /// assert_eq!(small.size(), 1000);
+    /// assert_eq!(small.nearest_one::<SquaredEuclidean>(&[50.02, 3.0]).item, 500);
+    /// ```
+    pub fn to_f32(
+        &self,
+        rounding: Rounding,
+    ) -> Result<ImmutableKdTree<f32, T, K, B>, ConstructionError> {
+        let convert = |vals: &[f64]| -> Result<Vec<f32>, ConstructionError> {
+            vals.iter()
+                .map(|&val| rounding.f64_to_f32(val))
+                .collect::<Option<_>>()
+                .ok_or(ConstructionError::CoordinateOutOfRange)
+        };
+
+        let stems = convert(&self.stems)?;
+        let mut leaf_points: [Vec<f32>; K] = array_init(|_| Vec::new());
+        for (converted, column) in leaf_points.iter_mut().zip(self.leaf_points.iter()) {
+            *converted = convert(column)?;
+        }
+
+        Ok(ImmutableKdTree {
+            stems: AVec::from_slice(CACHELINE_ALIGN, &stems),
+            leaf_points,
+            leaf_items: self.leaf_items.clone(),
+            leaf_extents: self.leaf_extents.clone(),
+            max_stem_level: self.max_stem_level,
+            tombstone_count: self.tombstone_count,
+            split_dims: self.split_dims.clone(),
+        })
+    }
+}
+
+impl<T: Content, const K: usize, const B: usize> ImmutableKdTree<f32, T, K, B> {
+    /// Converts the tree into one that stores its co-ordinates as `f64`s, keeping the
+    /// structure of the tree as it is.
+    ///
+    /// Every `f32` can be represented exactly as an `f64`, so the converted tree holds
+    /// exactly the same points as this one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    ///
+    /// let points: Vec<[f32; 2]> = vec![[1.0, 2.0], [3.0, 4.0]];
+    /// let tree: ImmutableKdTree<f32, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+    ///
+    /// let precise: ImmutableKdTree<f64, u32, 2, 32> = tree.to_f64();
+    ///
+    /// assert_eq!(precise.size(), 2);
+    /// ```
+    pub fn to_f64(&self) -> ImmutableKdTree<f64, T, K, B> {
+        let stems: Vec<f64> = self.stems.iter().map(|&val| val as f64).collect();
+
+        ImmutableKdTree {
+            stems: AVec::from_slice(CACHELINE_ALIGN, &stems),
+            leaf_points: array_init(|dim| {
+                self.leaf_points[dim]
+                    .iter()
+                    .map(|&val| val as f64)
+                    .collect()
+            }),
+            leaf_items: self.leaf_items.clone(),
+            leaf_extents: self.leaf_extents.clone(),
+            max_stem_level: self.max_stem_level,
+            tombstone_count: self.tombstone_count,
+            split_dims: self.split_dims.clone(),
+        }
+    }
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize> IterableTreeData<A, T, K>
+    for ImmutableKdTree<A, T, K, B>
+{
+    fn get_leaf_data(&self, idx: usize, out: &mut Vec<(T, [A; K])>) -> Option<usize> {
+        let (start, end) = *self.leaf_extents.get(idx)?;
+        out.extend((start as usize..end as usize).map(|i| {
+            (
+                self.leaf_items[i],
+                array_init(|dim| self.leaf_points[dim][i]),
+            )
+        }));
+        Some((end - start) as usize)
+    }
+}
+
+impl<'a, A: Axis, T: Content, const K: usize, const B: usize> IntoIterator
+    for &'a ImmutableKdTree<A, T, K, B>
+{
+    type Item = (T, [A; K]);
+    type IntoIter = TreeItems<'a, A, T, K>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        TreeItems::new(TreeIter::new(self, B))
+    }
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize> FromIterator<[A; K]>
+    for ImmutableKdTree<A, T, K, B>
+where
+    A: LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    usize: Cast<T>,
+{
+    /// Creates an `ImmutableKdTree` from an iterator of points, with each point's item
+    /// being its position in the iterator, as with
+    /// [`new_from_slice`](ImmutableKdTree::new_from_slice).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    ///
+    /// let tree: ImmutableKdTree<f64, u32, 2, 32> =
+    ///     (0..100).map(|i| [i as f64, (i % 7) as f64]).collect();
+    ///
+    /// assert_eq!(tree.size(), 100);
+    /// ```
+    fn from_iter<I: IntoIterator<Item = [A; K]>>(iter: I) -> Self {
+        let points: Vec<[A; K]> = iter.into_iter().collect();
+        Self::new_from_slice(&points)
+    }
+}
+
+/// Implements the methods of [`KdTreeQuery`] by calling the inherent methods of the same
+/// names, which every immutable tree type has.
+macro_rules! impl_kd_tree_query_methods {
+    () => {
+        #[inline]
+        fn nearest_one<D>(&self, query: &[A; K]) -> NearestNeighbour<A, T>
+        where
+            D: DistanceMetric<A, K>,
+        {
+            self.nearest_one::<D>(query)
+        }
+
+        #[inline]
+        fn nearest_n<D>(
+            &self,
+            query: &[A; K],
+            max_qty: NonZero<usize>,
+        ) -> Vec<NearestNeighbour<A, T>>
+        where
+            D: DistanceMetric<A, K>,
+        {
+            self.nearest_n::<D>(query, max_qty)
+        }
+
+        #[inline]
+        fn within<D>(&self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+        where
+            D: DistanceMetric<A, K>,
+        {
+            self.within::<D>(query, dist)
+        }
+
+        #[inline]
+        fn best_n_within<D>(
+            &self,
+            query: &[A; K],
+            dist: A,
+            max_qty: NonZero<usize>,
+        ) -> impl Iterator<Item = BestNeighbour<A, T>>
+        where
+            D: DistanceMetric<A, K>,
+        {
+            self.best_n_within::<D>(query, dist, max_qty)
+        }
+    };
+}
+
+impl<A, T, const K: usize, const B: usize> KdTreeQuery<A, T, K> for ImmutableKdTree<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    usize: Cast<T>,
+{
+    impl_kd_tree_query_methods!();
+}
+
+#[cfg(feature = "rkyv")]
+impl<A, T, const K: usize, const B: usize> KdTreeQuery<A, T, K>
+    for AlignedArchivedImmutableKdTree<'_, A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K> + rkyv::Archive<Archived = A>,
+    T: Content + rkyv::Archive<Archived = T>,
+    usize: Cast<T>,
+{
+    impl_kd_tree_query_methods!();
+}
+
+#[cfg(feature = "rkyv")]
+impl<A, T, const K: usize, const B: usize> KdTreeQuery<A, T, K>
+    for ArchivedImmutableKdTreeRK<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K> + rkyv::Archive<Archived = A>,
+    T: Content + rkyv::Archive<Archived = T>,
+    usize: Cast<T>,
+{
+    impl_kd_tree_query_methods!();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::construction_error::ConstructionError;
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use crate::split_policy::SplitPolicy;
+    use crate::SquaredEuclidean;
+    use ordered_float::OrderedFloat;
+    use rand::{Rng, SeedableRng};
+    use std::num::NonZero;
+
+    #[test]
+    fn can_construct_an_empty_tree() {
+        let tree = ImmutableKdTree::<f64, u32, 3, 32>::new_from_slice(&[]);
+        let _result = tree.nearest_one::<SquaredEuclidean>(&[0.; 3]);
+    }
+
+    #[cfg(feature = "deterministic_construction")]
+    #[test]
+    fn construction_is_deterministic_with_many_equal_coordinates() {
+        let points: Vec<[f64; 2]> = (0..40)
+            .map(|idx| [(idx % 3) as f64, (idx * 7 % 5) as f64])
+            .collect();
+        let tree: ImmutableKdTree<f64, u32, 2, 4> = ImmutableKdTree::new_from_slice(&points);
+
+        let items: Vec<u32> = tree.iter().map(|(item, _)| item).collect();
+        assert_eq!(
+            items,
+            vec![
+                0, 30, 15, 33, 18, 3, 6, 9, 36, 21, 27, 12, 39, 24, 25, 10, 28, 13, 35, 20, 5, 38,
+                23, 8, 4, 31, 16, 1, 22, 7, 34, 19, 29, 14, 26, 11, 37, 32, 17, 2
+            ]
+        );
+    }
+
+    #[cfg(feature = "allocator_api")]
+    #[test]
+    fn can_store_a_tree_in_a_custom_allocator() {
+        use crate::immutable::float::kdtree::ImmutableKdTreeIn;
+        use std::alloc::{AllocError, Allocator, Layout, System};
+        use std::ptr::NonNull;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        #[derive(Clone)]
+        struct CountingAllocator(Arc<AtomicUsize>);
+
+        unsafe impl Allocator for CountingAllocator {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                self.0.fetch_add(layout.size(), Ordering::Relaxed);
+                System.allocate(layout)
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                self.0.fetch_sub(layout.size(), Ordering::Relaxed);
+                System.deallocate(ptr, layout)
             }
         }
 
-        Self {
-            stems,
-            leaf_points,
-            leaf_items,
-            leaf_extents,
-            max_stem_level,
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(73);
+        let points: Vec<[f64; 3]> = (0..5_000).map(|_| rng.gen()).collect();
+        let allocated = Arc::new(AtomicUsize::new(0));
+
+        let tree = ImmutableKdTree::<f64, u32, 3, 32>::new_from_slice(&points);
+        let tree_in: ImmutableKdTreeIn<f64, u32, 3, 32, _> =
+            ImmutableKdTreeIn::new_from_slice_in(&points, CountingAllocator(allocated.clone()));
+
+        assert!(allocated.load(Ordering::Relaxed) >= points.len() * (3 * 8 + 4));
+        assert_eq!(tree_in.size(), tree.size());
+        assert_eq!(tree_in.generate_stats(), tree.generate_stats());
+
+        for _ in 0..100 {
+            let query: [f64; 3] = rng.gen();
+            assert_eq!(
+                tree_in.nearest_one::<SquaredEuclidean>(&query),
+                tree.nearest_one::<SquaredEuclidean>(&query)
+            );
+            assert_eq!(
+                tree_in.within::<SquaredEuclidean>(&query, 0.01),
+                tree.within::<SquaredEuclidean>(&query, 0.01)
+            );
         }
+
+        drop(tree_in);
+        assert_eq!(allocated.load(Ordering::Relaxed), 0);
     }
 
-    #[allow(clippy::too_many_arguments)]
-    fn populate_recursive(
-        stems: &mut AVec<A, ConstAlign<{ CACHELINE_ALIGN }>>,
-        dim: usize,
-        source: &[[A; K]],
-        sort_index: &mut [usize],
-        stem_index: usize,
-        mut level: i32,
-        mut minor_level: u64,
-        max_stem_level: i32,
-        capacity: usize,
-        leaf_points: &mut [Vec<A>; K],
-        leaf_items: &mut Vec<T>,
-        leaf_extents: &mut Vec<(u32, u32)>,
-    ) {
-        let chunk_length = sort_index.len();
+    #[test]
+    fn generates_stats_for_a_balanced_tree() {
+        let points: Vec<[f64; 2]> = (0..1_000)
+            .map(|i| [i as f64, (i * 37 % 1_000) as f64])
+            .collect();
+        let tree = ImmutableKdTree::<f64, u32, 2, 32>::new_from_slice(&points);
+
+        let stats = tree.generate_stats();
+        assert_eq!(stats.size, 1_000);
+        assert_eq!(stats.leaf_count, tree.leaf_count());
+        assert_eq!(stats.max_leaf_size, tree.max_leaf_size());
+        assert_eq!(stats.max_depth, (tree.max_stem_level() + 1) as usize);
+        assert_eq!(tree.max_depth(), stats.max_depth);
+        assert!(tree.average_leaf_occupancy() > 0.5);
+
+        let single_leaf = ImmutableKdTree::<f64, u32, 2, 32>::new_from_slice(&points[..10]);
+        assert_eq!(single_leaf.max_depth(), 0);
+    }
 
-        if level > max_stem_level {
-            // Write leaf and terminate recursion
-            leaf_extents.push((
-                leaf_items.len() as u32,
-                (leaf_items.len() + chunk_length) as u32,
-            ));
+    #[test]
+    fn reports_progress_and_can_be_cancelled() {
+        use crate::build_progress::CancellationToken;
 
-            (0..chunk_length).for_each(|i| {
-                (0..K).for_each(|dim| leaf_points[dim].push(source[sort_index[i]][dim]));
-                leaf_items.push(sort_index[i].az::<T>())
-            });
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(41);
+        let points: Vec<[f64; 3]> = (0..10_000).map(|_| rng.gen()).collect();
 
-            return;
-        }
+        let mut reports = Vec::new();
+        let tree = ImmutableKdTree::<f64, u32, 3, 32>::new_from_slice_with_progress(
+            &points,
+            |done, total| reports.push((done, total)),
+            &CancellationToken::new(),
+        )
+        .unwrap();
+
+        assert_eq!(tree, ImmutableKdTree::new_from_slice(&points));
+        assert_eq!(reports.len(), tree.leaf_count());
+        assert!(reports.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(reports.iter().all(|&(_, total)| total == points.len()));
+        assert_eq!(reports.last(), Some(&(points.len(), points.len())));
+
+        let cancel = CancellationToken::new();
+        let mut calls = 0;
+        let result = ImmutableKdTree::<f64, u32, 3, 32>::new_from_slice_with_progress(
+            &points,
+            |_, _| {
+                calls += 1;
+                cancel.cancel();
+            },
+            &cancel,
+        );
 
-        let levels_below = max_stem_level - level;
-        let left_capacity = (2usize.pow(levels_below as u32) * B).min(capacity);
-        let right_capacity = capacity.saturating_sub(left_capacity);
+        assert_eq!(result.unwrap_err(), ConstructionError::Cancelled);
+        assert_eq!(calls, 1);
+    }
 
-        let mut pivot = Self::calc_pivot(chunk_length, stem_index, right_capacity);
+    #[test]
+    fn converts_between_f64_and_f32_without_rebuilding() {
+        use crate::rounding::Rounding;
+        use crate::traits::DistanceMetric;
+
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(43);
+        let points: Vec<[f64; 3]> = (0..5_000).map(|_| rng.gen()).collect();
+        let tree = ImmutableKdTree::<f64, u32, 3, 32>::new_from_slice(&points);
+
+        for rounding in [Rounding::Nearest, Rounding::Down, Rounding::Up] {
+            let small = tree.to_f32(rounding).unwrap();
+            let converted: Vec<[f32; 3]> = points
+                .iter()
+                .map(|p| p.map(|val| rounding.f64_to_f32(val).unwrap()))
+                .collect();
+
+            assert_eq!(small.size(), points.len());
+            assert_eq!(small.leaf_count(), tree.leaf_count());
+
+            for _ in 0..100 {
+                let query: [f32; 3] = rng.gen();
+                let expected = converted
+                    .iter()
+                    .map(|p| OrderedFloat(SquaredEuclidean::dist(p, &query)))
+                    .min()
+                    .unwrap();
+
+                assert_eq!(
+                    small.nearest_one::<SquaredEuclidean>(&query).distance,
+                    expected.0
+                );
+            }
 
-        // only bother with this if we are putting at least one item in the right hand child
-        if pivot < chunk_length {
-            pivot = Self::update_pivot(source, sort_index, dim, pivot);
+            assert_eq!(small.to_f64().to_f32(rounding).unwrap(), small);
+        }
 
-            // if we end up with a pivot of 0, something has gone wrong,
-            // unless we only had a slice of len 1 anyway
-            debug_assert!(pivot > 0 || chunk_length == 1);
-            debug_assert!(
-                stems[stem_index].is_infinite(),
-                "Wrote to stem #{:?} for a second time",
-                stem_index
-            );
+        let huge = ImmutableKdTree::<f64, u32, 3, 32>::new_from_slice(&[[1e300, 0.0, 0.0]]);
+        assert_eq!(
+            huge.to_f32(Rounding::Nearest).unwrap_err(),
+            ConstructionError::CoordinateOutOfRange
+        );
+    }
+
+    #[test]
+    fn can_collect_and_iterate_by_reference() {
+        let points: Vec<[f64; 2]> = (0..1_000)
+            .map(|i| [i as f64, (i * 37 % 1_000) as f64])
+            .collect();
+        let tree: ImmutableKdTree<f64, u32, 2, 32> = points.iter().copied().collect();
+
+        assert_eq!(tree.size(), points.len());
+        let mut count = 0;
+        for (item, point) in &tree {
+            assert_eq!(point, points[item as usize]);
+            count += 1;
+        }
+        assert_eq!(count, points.len());
+    }
 
-            stems[stem_index] = source[sort_index[pivot]][dim];
+    #[test]
+    fn can_get_the_point_of_an_item() {
+        let points: Vec<[f64; 2]> = (0..1_000)
+            .map(|i| [i as f64, (i * 37 % 1_000) as f64])
+            .collect();
+        let tree = ImmutableKdTree::<f64, u32, 2, 32>::new_from_slice(&points);
+
+        for (idx, point) in points.iter().enumerate().step_by(7) {
+            assert_eq!(tree.get(idx as u32), Some(*point));
+            assert!(tree.contains_item(idx as u32));
         }
+        assert_eq!(tree.get(1_000), None);
+        assert!(!tree.contains_item(1_000));
+    }
 
-        #[cfg(feature = "modified_van_emde_boas")]
-        let left_child_idx = modified_van_emde_boas_get_child_idx_v2_branchless(
-            stem_index as u32,
-            false,
-            minor_level as u32,
-        ) as usize;
-        #[cfg(feature = "modified_van_emde_boas")]
-        let right_child_idx = modified_van_emde_boas_get_child_idx_v2_branchless(
-            stem_index as u32,
-            true,
-            minor_level as u32,
-        ) as usize;
+    #[test]
+    fn queries_skip_deleted_items_until_compacted() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(75);
+        let points: Vec<[f64; 2]> = (0..2_000)
+            .map(|_| [rng.gen_range(0.0..100.0), rng.gen_range(0.0..100.0)])
+            .collect();
+        let mut tree = ImmutableKdTree::<f64, u32, 2, 32>::new_from_slice(&points);
+
+        let deleted: Vec<u32> = (0..2_000).filter(|i| i % 3 == 0).collect();
+        for &item in &deleted {
+            assert_eq!(tree.delete(item), 1);
+        }
+        assert_eq!(tree.delete(0), 0);
+        assert_eq!(tree.size(), 2_000 - deleted.len());
+        assert_eq!(tree.tombstone_count(), deleted.len());
+        assert!((tree.tombstone_fraction() - deleted.len() as f64 / 2_000.0).abs() < 1e-9);
+        assert!(!tree.contains_item(3));
+        assert_eq!(tree.get(4), Some(points[4]));
+        assert_eq!(tree.iter().count(), tree.size());
+
+        let live: Vec<(u32, [f64; 2])> = (0..2_000u32)
+            .filter(|i| i % 3 != 0)
+            .map(|i| (i, points[i as usize]))
+            .collect();
+        let rebuilt = ImmutableKdTree::<f64, u32, 2, 32>::new_from_slice(
+            &live.iter().map(|&(_, point)| point).collect::<Vec<_>>(),
+        );
 
-        #[cfg(not(feature = "modified_van_emde_boas"))]
-        let left_child_idx = stem_index << 1;
-        #[cfg(not(feature = "modified_van_emde_boas"))]
-        let right_child_idx = (stem_index << 1) + 1;
+        for _ in 0..100 {
+            let query = [rng.gen_range(0.0..100.0), rng.gen_range(0.0..100.0)];
+            let mut expected: Vec<(OrderedFloat<f64>, u32)> = live
+                .iter()
+                .map(|&(item, [x, y])| {
+                    (
+                        OrderedFloat((x - query[0]).powi(2) + (y - query[1]).powi(2)),
+                        item,
+                    )
+                })
+                .collect();
+            expected.sort();
+
+            assert_eq!(
+                tree.nearest_one::<SquaredEuclidean>(&query).item,
+                expected[0].1
+            );
+            let nearest_n = tree.nearest_n::<SquaredEuclidean>(&query, NonZero::new(5).unwrap());
+            assert_eq!(
+                nearest_n.iter().map(|nn| nn.item).collect::<Vec<_>>(),
+                expected[..5].iter().map(|e| e.1).collect::<Vec<_>>()
+            );
+            assert_eq!(
+                tree.within::<SquaredEuclidean>(&query, 25.0).len(),
+                rebuilt.within::<SquaredEuclidean>(&query, 25.0).len()
+            );
+        }
 
-        let (lower_sort_index, upper_sort_index) = sort_index.split_at_mut(pivot);
+        tree.compact();
+        assert_eq!(tree.tombstone_count(), 0);
+        assert_eq!(tree.size(), live.len());
+        assert_eq!(tree.leaf_items.len(), live.len());
+        let mut items: Vec<u32> = tree.iter().map(|(item, _)| item).collect();
+        items.sort();
+        assert_eq!(
+            items,
+            live.iter().map(|&(item, _)| item).collect::<Vec<_>>()
+        );
+    }
 
-        level += 1;
-        minor_level += 1;
-        minor_level.cmovnz(&0, u8::from(minor_level == 3));
+    #[test]
+    fn custom_leaf_scans_match_the_built_in_queries() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(79);
+        let points: Vec<[f64; 3]> = (0..5_000).map(|_| rng.gen()).collect();
+        let tree = ImmutableKdTree::<f64, u32, 3, 32>::new_from_slice(&points);
+
+        for _ in 0..100 {
+            let query = rng.gen::<[f64; 3]>();
+
+            let leaf = tree.get_leaf_slice(tree.leaf_for_point(&query));
+            let mut best_dist = f64::INFINITY;
+            let mut best_item = 0;
+            leaf.nearest_one::<SquaredEuclidean>(&query, &mut best_dist, &mut best_item);
+            let approx = tree.approx_nearest_one::<SquaredEuclidean>(&query);
+            assert_eq!((best_dist, best_item), (approx.distance, approx.item));
+
+            let mut within_count = 0;
+            for leaf_idx in 0..tree.leaf_count() {
+                tree.get_leaf_slice(leaf_idx)
+                    .for_each_within::<SquaredEuclidean, _>(&query, 0.01, |_, _| within_count += 1);
+            }
+            assert_eq!(
+                within_count,
+                tree.within_unsorted::<SquaredEuclidean>(&query, 0.01).len()
+            );
+        }
+    }
 
-        let next_dim = (dim + 1) % K;
+    #[test]
+    fn from_trees_contains_the_items_of_every_tree() {
+        let points: Vec<[f64; 2]> = (0..1_000)
+            .map(|i| [i as f64, (i * 37 % 1_000) as f64])
+            .collect();
+        let shard_a = ImmutableKdTree::<f64, u32, 2, 32>::new_from_slice(&points[..300]);
+        let shard_b = ImmutableKdTree::<f64, u32, 2, 32>::new_from_slice(&points[300..]);
+
+        let tree = ImmutableKdTree::from_trees(&[&shard_a, &shard_b]);
+        assert_eq!(tree.size(), 1_000);
+
+        for (idx, point) in points.iter().enumerate().step_by(7) {
+            let expected = if idx < 300 { idx } else { idx - 300 };
+            assert_eq!(
+                tree.nearest_one::<SquaredEuclidean>(point).item,
+                expected as u32
+            );
+        }
+    }
 
-        Self::populate_recursive(
-            stems,
-            next_dim,
-            source,
-            lower_sort_index,
-            left_child_idx,
-            level,
-            minor_level,
-            max_stem_level,
-            left_capacity,
-            leaf_points,
-            leaf_items,
-            leaf_extents,
+    #[test]
+    fn try_new_from_slice_rejects_unusable_input() {
+        let mut points: Vec<[f64; 2]> = (0..300)
+            .map(|i| [i as f64, (i * 37 % 300) as f64])
+            .collect();
+
+        let tree = ImmutableKdTree::<f64, u32, 2, 32>::try_new_from_slice(&points).unwrap();
+        assert_eq!(tree.size(), 300);
+
+        // items are indices, and 300 points can't each have a distinct u8 index
+        assert_eq!(
+            ImmutableKdTree::<f64, u8, 2, 32>::try_new_from_slice(&points).unwrap_err(),
+            ConstructionError::TooManyItems { len: 300 }
         );
+        assert!(ImmutableKdTree::<f64, u8, 2, 32>::try_new_from_slice(&points[..256]).is_ok());
 
-        Self::populate_recursive(
-            stems,
-            next_dim,
-            source,
-            upper_sort_index,
-            right_child_idx,
-            level,
-            minor_level,
-            max_stem_level,
-            right_capacity,
-            leaf_points,
-            leaf_items,
-            leaf_extents,
+        points[200][0] = f64::NEG_INFINITY;
+        assert!(ImmutableKdTree::<f64, u32, 2, 32>::try_new_from_slice(&points).is_ok());
+        assert_eq!(
+            ImmutableKdTree::<f64, u32, 2, 32>::new_from_slice_checked(&points).unwrap_err(),
+            ConstructionError::InfiniteCoordinate { index: 200 }
+        );
+
+        points[123][1] = f64::NAN;
+        assert_eq!(
+            ImmutableKdTree::<f64, u32, 2, 32>::try_new_from_slice(&points).unwrap_err(),
+            ConstructionError::NanCoordinate { index: 123 }
+        );
+        assert_eq!(
+            ImmutableKdTree::<f64, u32, 2, 32>::new_from_slice_checked(&points).unwrap_err(),
+            ConstructionError::NanCoordinate { index: 123 }
         );
     }
 
-    #[cfg(not(feature = "unreliable_select_nth_unstable"))]
-    #[inline]
-    fn update_pivot(
-        source: &[[A; K]],
-        sort_index: &mut [usize],
-        dim: usize,
-        mut pivot: usize,
-    ) -> usize {
-        // TODO: this block might be faster by using a quickselect with a fat partition?
-        //       we could then run that quickselect and subtract (fat partition length - 1)
-        //       from the pivot, avoiding the need for the while loop.
+    #[test]
+    fn hilbert_construction_gives_the_same_results() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(51);
+        let points: Vec<[f64; 3]> = (0..20_000).map(|_| rng.gen()).collect();
+
+        let tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+        let hilbert: ImmutableKdTree<f64, u32, 3, 32> =
+            ImmutableKdTree::new_from_slice_hilbert(&points);
+
+        assert_eq!(hilbert.size(), tree.size());
+        assert_eq!(hilbert.leaf_count(), tree.leaf_count());
+
+        for _ in 0..1_000 {
+            let query: [f64; 3] = rng.gen();
+            assert_eq!(
+                hilbert.nearest_n::<SquaredEuclidean>(&query, NonZero::new(5).unwrap()),
+                tree.nearest_n::<SquaredEuclidean>(&query, NonZero::new(5).unwrap())
+            );
+        }
 
-        // ensure the item whose index = pivot is in its correctly sorted position, and any
-        // items that are equal to it are adjacent, according to our assumptions about the
-        // behaviour of `select_nth_unstable_by` (See examples/check_select_nth_unstable.rs)
-        sort_index.select_nth_unstable_by_key(pivot, |&i| OrderedFloat(source[i][dim]));
+        let empty = ImmutableKdTree::<f64, u32, 3, 32>::new_from_slice_hilbert(&[]);
+        assert_eq!(empty.size(), 0);
+    }
 
-        if pivot == 0 {
-            return pivot;
+    #[test]
+    fn max_spread_construction_gives_the_same_results() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(94);
+        let points: Vec<[f64; 3]> = (0..20_000)
+            .map(|_| {
+                [
+                    rng.gen_range(0.0..100.0),
+                    rng.gen_range(0.0..1.0),
+                    rng.gen_range(0.0..0.01),
+                ]
+            })
+            .collect();
+
+        let tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+        let mut max_spread: ImmutableKdTree<f64, u32, 3, 32> =
+            ImmutableKdTree::new_from_slice_with_policy(&points, SplitPolicy::MaxSpread);
+
+        assert_eq!(max_spread.size(), tree.size());
+        assert_eq!(max_spread.split_dims.len(), max_spread.stems.len());
+        let splits_on = |dim: u8| max_spread.split_dims.iter().filter(|&&d| d == dim).count();
+        // the narrowest axis is never split on, unlike in a round-robin tree
+        assert!(splits_on(1) > 0);
+        assert_eq!(splits_on(2), 0);
+
+        for _ in 0..1_000 {
+            let query = [
+                rng.gen_range(0.0..100.0),
+                rng.gen_range(0.0..1.0),
+                rng.gen_range(0.0..0.01),
+            ];
+            assert_eq!(
+                max_spread.nearest_one::<SquaredEuclidean>(&query),
+                tree.nearest_one::<SquaredEuclidean>(&query)
+            );
+            assert_eq!(
+                max_spread.nearest_n::<SquaredEuclidean>(&query, NonZero::new(5).unwrap()),
+                tree.nearest_n::<SquaredEuclidean>(&query, NonZero::new(5).unwrap())
+            );
+            assert_eq!(
+                max_spread.within::<SquaredEuclidean>(&query, 1.0),
+                tree.within::<SquaredEuclidean>(&query, 1.0)
+            );
         }
 
-        // if the pivot straddles two values that are equal, keep nudging it left until they aren't
-        while source[sort_index[pivot]][dim] == source[sort_index[pivot - 1]][dim] && pivot > 1 {
-            pivot -= 1;
+        for (idx, point) in points.iter().enumerate().step_by(97) {
+            let leaf = max_spread.get_leaf_slice(max_spread.leaf_for_point(point));
+            assert!(leaf.content_items.contains(&(idx as u32)));
         }
 
-        pivot
+        let (item, _) = max_spread.iter().next().unwrap();
+        max_spread.delete(item);
+        max_spread.compact();
+        assert!(!max_spread.split_dims.is_empty());
     }
 
-    /// Returns the current number of elements stored in the tree
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
-    ///
-    /// let points: Vec<[f64; 3]> = vec!([1.0f64, 2.0f64, 3.0f64]);
-    /// let tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
-    ///
-    /// assert_eq!(tree.size(), 1);
-    /// ```
-    #[inline]
-    pub fn size(&self) -> usize {
-        self.leaf_items.len()
+    #[test]
+    fn iterator_construction_builds_the_same_tree() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(85);
+
+        for size in [0, 1, 31, 1_000, 20_001] {
+            let points: Vec<[f64; 3]> = (0..size).map(|_| rng.gen()).collect();
+
+            let tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+            let from_iter: ImmutableKdTree<f64, u32, 3, 32> =
+                ImmutableKdTree::new_from_iter(0, points.iter().copied());
+
+            assert_eq!(from_iter, tree);
+        }
     }
 
-    /// Returns the theoretical max capacity of this tree
-    #[inline]
-    pub fn capacity(&self) -> usize {
-        self.size()
+    #[test]
+    fn column_construction_builds_the_same_tree() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(86);
+
+        for size in [0, 1, 31, 1_000, 20_001] {
+            let points: Vec<[f64; 3]> = (0..size).map(|_| rng.gen()).collect();
+            let columns: [Vec<f64>; 3] =
+                std::array::from_fn(|dim| points.iter().map(|point| point[dim]).collect());
+
+            let tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+            let from_columns: ImmutableKdTree<f64, u32, 3, 32> =
+                ImmutableKdTree::new_from_columns(&[&columns[0], &columns[1], &columns[2]]);
+
+            assert_eq!(from_columns, tree);
+        }
     }
 
-    fn calc_pivot(chunk_length: usize, _stem_index: usize, _right_capacity: usize) -> usize {
-        chunk_length >> 1
+    #[test]
+    #[should_panic(expected = "every column must have the same length")]
+    fn column_construction_panics_on_mismatched_columns() {
+        let _tree: ImmutableKdTree<f64, u32, 2, 32> =
+            ImmutableKdTree::new_from_columns(&[&[1.0, 2.0], &[3.0]]);
     }
 
-    /// Returns a LeafSlice for a given leaf index
-    #[inline]
-    pub(crate) fn get_leaf_slice(&self, leaf_idx: usize) -> LeafSlice<A, T, K> {
-        let (start, end) = unsafe { *self.leaf_extents.get_unchecked(leaf_idx) };
+    #[test]
+    fn shape_getters_describe_tree() {
+        let empty = ImmutableKdTree::<f64, u32, 3, 32>::new_from_slice(&[]);
+        assert_eq!(empty.max_stem_level(), -1);
+        assert_eq!(empty.stem_count(), 0);
+        assert_eq!(empty.leaf_count(), 1);
+        assert_eq!(empty.max_leaf_size(), 0);
+
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(3);
+        let points: Vec<[f64; 3]> = (0..10_000).map(|_| rng.gen::<[f64; 3]>()).collect();
+        let tree = ImmutableKdTree::<f64, u32, 3, 32>::new_from_slice(&points);
+
+        let leaf_count = tree.leaf_count();
+        assert_eq!(leaf_count, tree.leaf_extents.len());
+        assert_eq!(
+            tree.max_stem_level(),
+            leaf_count.next_power_of_two().ilog2() as i32 - 1
+        );
+        assert_eq!(tree.stem_count(), tree.stems.len());
+        assert!(tree.max_leaf_size() > 0);
+        assert!(tree.max_leaf_size() <= 32);
+    }
 
-        // Artificially extend size to be at least chunk length for faster processing
-        // TODO: why does this slow things down?
-        // let end = end.max(start + 32).min(self.leaf_items.len() as u32);
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn can_query_archived_tree_without_realigning() {
+        use crate::immutable::float::kdtree::{AlignedArchivedImmutableKdTree, ImmutableKdTreeRK};
+
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(4);
+        let points: Vec<[f64; 3]> = (0..10_000).map(|_| rng.gen::<[f64; 3]>()).collect();
+        let tree = ImmutableKdTree::<f64, u32, 3, 32>::new_from_slice(&points);
+
+        let tree_rk: ImmutableKdTreeRK<f64, u32, 3, 32> = tree.clone().into();
+        let bytes = rkyv::to_bytes::<_, 256>(&tree_rk).unwrap();
+        let archived = unsafe { rkyv::archived_root::<ImmutableKdTreeRK<f64, u32, 3, 32>>(&bytes) };
+        let aligned = AlignedArchivedImmutableKdTree::<f64, u32, 3, 32>::from_bytes(&bytes);
+
+        assert_eq!(archived.size(), tree.size());
+        assert_eq!(archived.leaf_count(), tree.leaf_count());
+        assert_eq!(archived.stem_count(), tree.stem_count());
+        assert_eq!(archived.max_stem_level(), tree.max_stem_level());
+        assert_eq!(archived.max_leaf_size(), tree.max_leaf_size());
+
+        for _ in 0..100 {
+            let query = rng.gen::<[f64; 3]>();
+            let expected = tree.nearest_one::<SquaredEuclidean>(&query);
+            assert_eq!(archived.nearest_one::<SquaredEuclidean>(&query), expected);
+            assert_eq!(aligned.nearest_one::<SquaredEuclidean>(&query), expected);
+
+            let max_qty = std::num::NonZero::new(5).unwrap();
+            assert_eq!(
+                archived.nearest_n::<SquaredEuclidean>(&query, max_qty),
+                tree.nearest_n::<SquaredEuclidean>(&query, max_qty)
+            );
+            assert_eq!(
+                archived.within::<SquaredEuclidean>(&query, 0.01),
+                tree.within::<SquaredEuclidean>(&query, 0.01)
+            );
+        }
+    }
 
-        LeafSlice::new(
-            array_init::array_init(|i| &self.leaf_points[i][start as usize..end as usize]),
-            &self.leaf_items[start as usize..end as usize],
-        )
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn can_archive_a_tree_built_with_max_spread() {
+        use crate::immutable::float::kdtree::{AlignedArchivedImmutableKdTree, ImmutableKdTreeRK};
+
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(95);
+        let points: Vec<[f64; 3]> = (0..10_000)
+            .map(|_| [rng.gen_range(0.0..100.0), rng.gen_range(0.0..1.0), 0.0])
+            .collect();
+        let tree: ImmutableKdTree<f64, u32, 3, 32> =
+            ImmutableKdTree::new_from_slice_with_policy(&points, SplitPolicy::MaxSpread);
+
+        let tree_rk: ImmutableKdTreeRK<f64, u32, 3, 32> = tree.clone().into();
+        let bytes = rkyv::to_bytes::<_, 256>(&tree_rk).unwrap();
+        let archived = unsafe { rkyv::archived_root::<ImmutableKdTreeRK<f64, u32, 3, 32>>(&bytes) };
+        let aligned = AlignedArchivedImmutableKdTree::<f64, u32, 3, 32>::from_bytes(&bytes);
+
+        assert_eq!(&archived.split_dims[..], &tree.split_dims[..]);
+        for _ in 0..100 {
+            let query = [rng.gen_range(0.0..100.0), rng.gen_range(0.0..1.0), 0.0];
+            let expected = tree.nearest_one::<SquaredEuclidean>(&query);
+            assert_eq!(archived.nearest_one::<SquaredEuclidean>(&query), expected);
+            assert_eq!(aligned.nearest_one::<SquaredEuclidean>(&query), expected);
+            assert_eq!(
+                archived.within::<SquaredEuclidean>(&query, 0.5),
+                tree.within::<SquaredEuclidean>(&query, 0.5)
+            );
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::immutable::float::kdtree::ImmutableKdTree;
-    use crate::SquaredEuclidean;
-    use ordered_float::OrderedFloat;
-    use rand::{Rng, SeedableRng};
+    #[cfg(feature = "serde")]
+    #[test]
+    fn can_deserialize_trees_serialized_with_the_original_layout() {
+        use crate::immutable::float::kdtree::LeafExtent;
+        use aligned_vec::AVec;
+        use serde::Serialize;
+
+        /// The fields of an `ImmutableKdTree`, as they were serialized before deleted
+        /// entries or split dimensions could be recorded
+        #[derive(Serialize)]
+        struct OriginalLayout {
+            stems: AVec<f64>,
+            #[serde(with = "crate::custom_serde::array_of_vecs")]
+            leaf_points: [Vec<f64>; 3],
+            leaf_items: Vec<u32>,
+            leaf_extents: Vec<(LeafExtent, LeafExtent)>,
+            max_stem_level: i32,
+        }
 
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(96);
+        let points: Vec<[f64; 3]> = (0..1_000).map(|_| rng.gen()).collect();
+        let tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+
+        let original = OriginalLayout {
+            stems: tree.stems.clone(),
+            leaf_points: tree.leaf_points.clone(),
+            leaf_items: tree.leaf_items.clone(),
+            leaf_extents: tree.leaf_extents.clone(),
+            max_stem_level: tree.max_stem_level,
+        };
+        let bytes = bincode::serialize(&original).unwrap();
+
+        let loaded: ImmutableKdTree<f64, u32, 3, 32> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(loaded, tree);
+        assert_eq!(bincode::serialize(&tree).unwrap(), bytes);
+
+        // the layout can't hold split dimensions, so trees that have them are rejected
+        // rather than losing them
+        let max_spread: ImmutableKdTree<f64, u32, 3, 32> =
+            ImmutableKdTree::new_from_slice_with_policy(&points, SplitPolicy::MaxSpread);
+        assert!(bincode::serialize(&max_spread).is_err());
+    }
+
+    #[cfg(feature = "serde")]
     #[test]
-    fn can_construct_an_empty_tree() {
-        let tree = ImmutableKdTree::<f64, u32, 3, 32>::new_from_slice(&[]);
-        let _result = tree.nearest_one::<SquaredEuclidean>(&[0.; 3]);
+    fn deleted_items_stay_deleted_when_serialized() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(97);
+        let points: Vec<[f64; 3]> = (0..1_000).map(|_| rng.gen()).collect();
+        let mut tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+        tree.delete(10);
+        tree.delete(20);
+
+        let loaded: ImmutableKdTree<f64, u32, 3, 32> =
+            bincode::deserialize(&bincode::serialize(&tree).unwrap()).unwrap();
+        assert_eq!(loaded, tree);
+        assert_eq!(loaded.tombstone_count(), 2);
+        assert_eq!(loaded.size(), 998);
     }
 
     #[test]
@@ -840,6 +3014,85 @@ mod tests {
             ImmutableKdTree::new_from_slice(&content_to_add);
     }
 
+    #[test]
+    fn leaves_do_not_overflow_when_split_values_are_duplicated() {
+        use crate::NearestNeighbour;
+        use std::collections::HashSet;
+
+        // the bad example seeds from above, with the first dimension squashed down to a
+        // handful of distinct values, so that most candidate split points land on a run
+        let bad_examples = [
+            (18, 894771),
+            (33, 100045),
+            (155, 480),
+            (26, 455191),
+            (21, 131851),
+            (32, 455191),
+            (56, 450533),
+            (18, 992063),
+            (19, 894771),
+            (20, 894771),
+            (36, 375096),
+            (10000, 257281),
+        ];
+
+        for (tree_size, seed) in bad_examples {
+            let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(seed);
+            let content_to_add: Vec<[f32; 4]> = (0..tree_size)
+                .map(|_| {
+                    let mut point = rng.gen::<[f32; 4]>();
+                    point[0] = (point[0] * 3.0).floor();
+                    point
+                })
+                .collect();
+
+            let tree: ImmutableKdTree<f32, usize, 4, 4> =
+                ImmutableKdTree::new_from_slice(&content_to_add);
+
+            assert!(
+                tree.max_leaf_size() <= 4,
+                "seed {seed}: leaf of {} items",
+                tree.max_leaf_size()
+            );
+
+            let results: Vec<NearestNeighbour<f32, usize>> =
+                tree.within_unsorted::<SquaredEuclidean>(&[0.0; 4], f32::MAX);
+            let items: HashSet<usize> = results.iter().map(|nn| nn.item).collect();
+            assert_eq!(items.len(), tree_size, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn leaves_spill_when_one_dimension_is_constant() {
+        use crate::traits::DistanceMetric;
+        use std::collections::HashSet;
+
+        let content_to_add: Vec<[f64; 2]> = (0..1000).map(|i| [i as f64, 5.0]).collect();
+
+        let tree: ImmutableKdTree<f64, u32, 2, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add);
+
+        assert!(tree.max_leaf_size() <= 32);
+
+        for query in [[0.0, 0.0], [500.5, 5.0], [999.0, 10.0]] {
+            let results = tree.within::<SquaredEuclidean>(&query, 400.0);
+            let items: HashSet<u32> = results.iter().map(|nn| nn.item).collect();
+            assert_eq!(items.len(), results.len());
+
+            for nn in results {
+                assert_eq!(
+                    nn.distance,
+                    SquaredEuclidean::dist(&query, &content_to_add[nn.item as usize])
+                );
+            }
+        }
+
+        // identical points can never be split, but construction should still terminate
+        let identical = vec![[1.0f64, 2.0]; 1000];
+        let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&identical);
+        assert_eq!(tree.size(), 1000);
+    }
+
     #[test]
     fn can_construct_optimized_tree_many_dupes() {
         let tree_size = 8;
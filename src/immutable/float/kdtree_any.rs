@@ -0,0 +1,310 @@
+//! An [`ImmutableKdTree`] whose item type is chosen at construction, to be as small as the
+//! number of points allows.
+//!
+//! The items of a tree built with [`new_from_slice`](ImmutableKdTree::new_from_slice) are
+//! the indices of its points, and take up as much space in the leaves as a coordinate does.
+//! Storing them as `u64`s when there are fewer than 65,536 points wastes three quarters of
+//! that space. [`ImmutableKdTreeAny`] picks `u16`, `u32` or `u64` items according to the
+//! number of points, and answers queries with `u64` items whichever it picked.
+
+use std::num::NonZero;
+
+use az::CheckedCast;
+
+use crate::best_neighbour::BestNeighbour;
+use crate::float::kdtree::Axis;
+use crate::float_leaf_slice::leaf_slice::{LeafSliceFloat, LeafSliceFloatChunk};
+use crate::immutable::float::kdtree::ImmutableKdTree;
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{Content, DistanceMetric, KdTreeQuery};
+
+/// An [`ImmutableKdTree`] with `u16`, `u32` or `u64` items, whichever is the smallest that
+/// can hold the index of every point.
+///
+/// Query it through the [`KdTreeQuery`] trait, which returns `u64` items whichever type
+/// the tree stores them as.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+/// use kiddo::immutable::float::kdtree_any::ImmutableKdTreeAny;
+/// use kiddo::traits::KdTreeQuery;
+/// use kiddo::SquaredEuclidean;
+///
+/// let points: Vec<[f64; 2]> = (0..1000).map(|idx| [idx as f64, 0.0]).collect();
+/// let tree: ImmutableKdTreeAny<f64, 2, 32> = ImmutableKdTree::new_auto(&points);
+///
+/// assert!(matches!(tree, ImmutableKdTreeAny::U16(_)));
+/// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[3.2, 0.0]).item, 3u64);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImmutableKdTreeAny<A: Copy + Default, const K: usize, const B: usize> {
+    /// A tree of at most 65,536 points
+    U16(ImmutableKdTree<A, u16, K, B>),
+    /// A tree of at most 2<sup>32</sup> points
+    U32(ImmutableKdTree<A, u32, K, B>),
+    /// A tree of more points
+    U64(ImmutableKdTree<A, u64, K, B>),
+}
+
+impl<A, const K: usize, const B: usize> ImmutableKdTree<A, u64, K, B>
+where
+    A: Axis
+        + LeafSliceFloat<u16>
+        + LeafSliceFloatChunk<u16, K>
+        + LeafSliceFloat<u32>
+        + LeafSliceFloatChunk<u32, K>
+        + LeafSliceFloat<u64>
+        + LeafSliceFloatChunk<u64, K>,
+{
+    /// Creates a tree of the points in `source`, with each item being the index of its
+    /// point within `source`, stored in the smallest of `u16`, `u32` and `u64` that can
+    /// hold them all.
+    ///
+    /// See [`ImmutableKdTreeAny`] for an example.
+    pub fn new_auto(source: &[[A; K]]) -> ImmutableKdTreeAny<A, K, B> {
+        ImmutableKdTreeAny::new_from_slice(source)
+    }
+}
+
+impl<A, const K: usize, const B: usize> ImmutableKdTreeAny<A, K, B>
+where
+    A: Axis
+        + LeafSliceFloat<u16>
+        + LeafSliceFloatChunk<u16, K>
+        + LeafSliceFloat<u32>
+        + LeafSliceFloatChunk<u32, K>
+        + LeafSliceFloat<u64>
+        + LeafSliceFloatChunk<u64, K>,
+{
+    /// Creates a tree of the points in `source`, with each item being the index of its
+    /// point within `source`, stored in the smallest of `u16`, `u32` and `u64` that can
+    /// hold them all.
+    pub fn new_from_slice(source: &[[A; K]]) -> Self {
+        let max_item = source.len().saturating_sub(1) as u64;
+
+        if max_item <= u16::MAX as u64 {
+            ImmutableKdTreeAny::U16(ImmutableKdTree::new_from_slice(source))
+        } else if max_item <= u32::MAX as u64 {
+            ImmutableKdTreeAny::U32(ImmutableKdTree::new_from_slice(source))
+        } else {
+            ImmutableKdTreeAny::U64(ImmutableKdTree::new_from_slice(source))
+        }
+    }
+
+    /// Returns the number of points in the tree
+    pub fn size(&self) -> usize {
+        match self {
+            ImmutableKdTreeAny::U16(tree) => tree.size(),
+            ImmutableKdTreeAny::U32(tree) => tree.size(),
+            ImmutableKdTreeAny::U64(tree) => tree.size(),
+        }
+    }
+
+    /// Converts the tree into one with `u64` items, whichever type it stores them as
+    pub fn into_u64_items(self) -> ImmutableKdTree<A, u64, K, B> {
+        match self {
+            ImmutableKdTreeAny::U16(tree) => tree.try_convert_items().unwrap(),
+            ImmutableKdTreeAny::U32(tree) => tree.try_convert_items().unwrap(),
+            ImmutableKdTreeAny::U64(tree) => tree,
+        }
+    }
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize> ImmutableKdTree<A, T, K, B> {
+    /// Converts the tree into one whose items are of type `U`, keeping the structure of
+    /// the tree as it is, or returns `None` if any item does not fit in a `U`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    ///
+    /// let points: Vec<[f64; 2]> = (0..1000).map(|idx| [idx as f64, 0.0]).collect();
+    /// let tree: ImmutableKdTree<f64, u64, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+    ///
+    /// let small: Option<ImmutableKdTree<f64, u16, 2, 32>> = tree.try_convert_items();
+    /// let tiny: Option<ImmutableKdTree<f64, u8, 2, 32>> = tree.try_convert_items();
+    ///
+    /// assert_eq!(small.unwrap().size(), 1000);
+    /// assert!(tiny.is_none());
+    /// ```
+    pub fn try_convert_items<U: Content>(&self) -> Option<ImmutableKdTree<A, U, K, B>>
+    where
+        T: CheckedCast<U>,
+    {
+        Some(ImmutableKdTree {
+            stems: self.stems.clone(),
+            leaf_points: self.leaf_points.clone(),
+            leaf_items: self
+                .leaf_items
+                .iter()
+                .map(|&item| item.checked_cast())
+                .collect::<Option<_>>()?,
+            leaf_extents: self.leaf_extents.clone(),
+            max_stem_level: self.max_stem_level,
+            tombstone_count: self.tombstone_count,
+            split_dims: self.split_dims.clone(),
+        })
+    }
+}
+
+fn widen_nearest<A, T: Into<u64>>(nearest: NearestNeighbour<A, T>) -> NearestNeighbour<A, u64> {
+    NearestNeighbour {
+        distance: nearest.distance,
+        item: nearest.item.into(),
+    }
+}
+
+fn widen_best<A, T: Into<u64>>(best: BestNeighbour<A, T>) -> BestNeighbour<A, u64> {
+    BestNeighbour {
+        distance: best.distance,
+        item: best.item.into(),
+    }
+}
+
+impl<A, const K: usize, const B: usize> KdTreeQuery<A, u64, K> for ImmutableKdTreeAny<A, K, B>
+where
+    A: Axis
+        + LeafSliceFloat<u16>
+        + LeafSliceFloatChunk<u16, K>
+        + LeafSliceFloat<u32>
+        + LeafSliceFloatChunk<u32, K>
+        + LeafSliceFloat<u64>
+        + LeafSliceFloatChunk<u64, K>,
+{
+    #[inline]
+    fn nearest_one<D>(&self, query: &[A; K]) -> NearestNeighbour<A, u64>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        match self {
+            ImmutableKdTreeAny::U16(tree) => widen_nearest(tree.nearest_one::<D>(query)),
+            ImmutableKdTreeAny::U32(tree) => widen_nearest(tree.nearest_one::<D>(query)),
+            ImmutableKdTreeAny::U64(tree) => tree.nearest_one::<D>(query),
+        }
+    }
+
+    #[inline]
+    fn nearest_n<D>(&self, query: &[A; K], max_qty: NonZero<usize>) -> Vec<NearestNeighbour<A, u64>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        match self {
+            ImmutableKdTreeAny::U16(tree) => tree
+                .nearest_n::<D>(query, max_qty)
+                .into_iter()
+                .map(widen_nearest)
+                .collect(),
+            ImmutableKdTreeAny::U32(tree) => tree
+                .nearest_n::<D>(query, max_qty)
+                .into_iter()
+                .map(widen_nearest)
+                .collect(),
+            ImmutableKdTreeAny::U64(tree) => tree.nearest_n::<D>(query, max_qty),
+        }
+    }
+
+    #[inline]
+    fn within<D>(&self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, u64>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        match self {
+            ImmutableKdTreeAny::U16(tree) => tree
+                .within::<D>(query, dist)
+                .into_iter()
+                .map(widen_nearest)
+                .collect(),
+            ImmutableKdTreeAny::U32(tree) => tree
+                .within::<D>(query, dist)
+                .into_iter()
+                .map(widen_nearest)
+                .collect(),
+            ImmutableKdTreeAny::U64(tree) => tree.within::<D>(query, dist),
+        }
+    }
+
+    #[inline]
+    fn best_n_within<D>(
+        &self,
+        query: &[A; K],
+        dist: A,
+        max_qty: NonZero<usize>,
+    ) -> impl Iterator<Item = BestNeighbour<A, u64>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let best: Vec<_> = match self {
+            ImmutableKdTreeAny::U16(tree) => tree
+                .best_n_within::<D>(query, dist, max_qty)
+                .map(widen_best)
+                .collect(),
+            ImmutableKdTreeAny::U32(tree) => tree
+                .best_n_within::<D>(query, dist, max_qty)
+                .map(widen_best)
+                .collect(),
+            ImmutableKdTreeAny::U64(tree) => {
+                tree.best_n_within::<D>(query, dist, max_qty).collect()
+            }
+        };
+
+        best.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ImmutableKdTreeAny;
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use crate::traits::KdTreeQuery;
+    use crate::SquaredEuclidean;
+    use rand::{Rng, SeedableRng};
+    use std::num::NonZero;
+
+    #[test]
+    fn picks_the_smallest_item_type_that_fits() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(23);
+
+        let at_limit: Vec<[f32; 2]> = (0..65_536).map(|_| rng.gen()).collect();
+        let tree: ImmutableKdTreeAny<f32, 2, 32> = ImmutableKdTree::new_auto(&at_limit);
+        assert!(matches!(tree, ImmutableKdTreeAny::U16(_)));
+
+        let over_limit: Vec<[f32; 2]> = (0..65_537).map(|_| rng.gen()).collect();
+        let tree: ImmutableKdTreeAny<f32, 2, 32> = ImmutableKdTree::new_auto(&over_limit);
+        assert!(matches!(tree, ImmutableKdTreeAny::U32(_)));
+        assert_eq!(tree.size(), 65_537);
+
+        let wide: ImmutableKdTree<f32, u64, 2, 32> = ImmutableKdTree::new_from_slice(&over_limit);
+        for _ in 0..100 {
+            let query = rng.gen::<[f32; 2]>();
+            let max_qty = NonZero::new(5).unwrap();
+
+            assert_eq!(
+                tree.nearest_one::<SquaredEuclidean>(&query),
+                wide.nearest_one::<SquaredEuclidean>(&query)
+            );
+            assert_eq!(
+                tree.nearest_n::<SquaredEuclidean>(&query, max_qty),
+                wide.nearest_n::<SquaredEuclidean>(&query, max_qty)
+            );
+            assert_eq!(
+                tree.within::<SquaredEuclidean>(&query, 0.001),
+                wide.within::<SquaredEuclidean>(&query, 0.001)
+            );
+        }
+
+        assert_eq!(tree.into_u64_items(), wide);
+    }
+
+    #[test]
+    fn converts_items_only_when_they_fit() {
+        let points: Vec<[f64; 2]> = (0..300).map(|idx| [idx as f64, 0.0]).collect();
+        let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+
+        let narrow: ImmutableKdTree<f64, u16, 2, 32> = tree.try_convert_items().unwrap();
+        assert_eq!(narrow.try_convert_items::<u32>(), Some(tree.clone()));
+        assert_eq!(tree.try_convert_items::<u8>(), None);
+    }
+}
@@ -21,6 +21,13 @@
 //! in the tree must be floats ([`f64`] or [`f32`],
 //! or [`f16`](https://docs.rs/half/latest/half/struct.f16.html) if the `f16` feature is enabled).
 
+pub mod brute_force;
+pub mod external;
+pub mod forest;
+pub mod grid;
+pub(crate) mod hilbert;
 pub mod kdtree;
+pub mod kdtree_any;
 #[doc(hidden)]
 pub mod query;
+pub mod radius;
@@ -67,6 +67,49 @@ impl<
     );
 }
 
+#[cfg(feature = "rkyv")]
+use crate::immutable::float::kdtree::ArchivedImmutableKdTreeRK;
+#[cfg(feature = "rkyv")]
+impl<
+        A: Axis + rkyv::Archive<Archived = A>,
+        T: Content + rkyv::Archive<Archived = T>,
+        const K: usize,
+        const B: usize,
+    > ArchivedImmutableKdTreeRK<A, T, K, B>
+{
+    generate_immutable_approx_float_nearest_one!(
+        "use std::fs::File;
+    use memmap::MmapOptions;
+
+    use kiddo::immutable::float::kdtree::ImmutableKdTreeRK;
+
+    let mmap = unsafe { MmapOptions::new().map(&File::open(\"./examples/immutable-doctest-tree.rkyv\").unwrap()).unwrap() };
+    let tree = unsafe { rkyv::archived_root::<ImmutableKdTreeRK<f64, u32, 3, 256>>(&mmap) };"
+    );
+}
+
+#[cfg(feature = "allocator_api")]
+use crate::immutable::float::kdtree::ImmutableKdTreeIn;
+#[cfg(feature = "allocator_api")]
+impl<A, T, const K: usize, const B: usize, Alloc> ImmutableKdTreeIn<A, T, K, B, Alloc>
+where
+    A: Axis,
+    T: Content,
+    Alloc: std::alloc::Allocator + Clone,
+{
+    generate_immutable_approx_float_nearest_one!(
+        "use std::alloc::System;
+    use kiddo::immutable::float::kdtree::ImmutableKdTreeIn;
+
+    let content: Vec<[f64; 3]> = vec!(
+            [1.0, 2.0, 5.0],
+            [2.0, 3.0, 6.0]
+        );
+
+        let tree: ImmutableKdTreeIn<f64, u64, 3, 32, System> = ImmutableKdTreeIn::new_from_slice_in(&content, System);"
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use crate::float::distance::Manhattan;
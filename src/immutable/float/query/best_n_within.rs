@@ -79,6 +79,49 @@ where
     );
 }
 
+#[cfg(feature = "rkyv")]
+use crate::immutable::float::kdtree::ArchivedImmutableKdTreeRK;
+#[cfg(feature = "rkyv")]
+impl<A, T, const K: usize, const B: usize> ArchivedImmutableKdTreeRK<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K> + rkyv::Archive<Archived = A>,
+    T: Content + rkyv::Archive<Archived = T>,
+    usize: Cast<T>,
+{
+    generate_immutable_float_best_n_within!(
+        "use std::fs::File;
+    use memmap::MmapOptions;
+
+    use kiddo::immutable::float::kdtree::ImmutableKdTreeRK;
+
+    let mmap = unsafe { MmapOptions::new().map(&File::open(\"./examples/immutable-doctest-tree.rkyv\").unwrap()).unwrap() };
+    let tree = unsafe { rkyv::archived_root::<ImmutableKdTreeRK<f64, u32, 3, 256>>(&mmap) };"
+    );
+}
+
+#[cfg(feature = "allocator_api")]
+use crate::immutable::float::kdtree::ImmutableKdTreeIn;
+#[cfg(feature = "allocator_api")]
+impl<A, T, const K: usize, const B: usize, Alloc> ImmutableKdTreeIn<A, T, K, B, Alloc>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    Alloc: std::alloc::Allocator + Clone,
+    usize: Cast<T>,
+{
+    generate_immutable_float_best_n_within!(
+        "use std::alloc::System;
+    use kiddo::immutable::float::kdtree::ImmutableKdTreeIn;
+
+    let content: Vec<[f64; 3]> = vec!(
+            [1.0, 2.0, 5.0],
+            [2.0, 3.0, 6.0]
+        );
+
+        let tree: ImmutableKdTreeIn<f64, u64, 3, 32, System> = ImmutableKdTreeIn::new_from_slice_in(&content, System);"
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use crate::best_neighbour::BestNeighbour;
@@ -206,6 +249,38 @@ mod tests {
         }
     }
 
+    #[test]
+    fn best_n_within_by_keeps_the_lowest_scores() {
+        let mut rng = rand::thread_rng();
+        let points: Vec<[AX; 2]> = (0..5_000).map(|_| rng.gen()).collect();
+        let magnitudes: Vec<f64> = (0..points.len()).map(|_| rng.gen()).collect();
+
+        let tree: ImmutableKdTree<AX, i32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+        let max_qty = NonZero::new(5).unwrap();
+
+        for _ in 0..50 {
+            let query: [AX; 2] = rng.gen();
+            let radius = 0.01;
+            let score = |item: i32, _distance: f64| magnitudes[item as usize];
+
+            let mut expected: Vec<_> = points
+                .iter()
+                .enumerate()
+                .map(|(idx, point)| (SquaredEuclidean::dist(&query, point), idx as i32))
+                .filter(|&(distance, _)| distance < radius)
+                .collect();
+            expected.sort_by(|a, b| score(a.1, a.0).partial_cmp(&score(b.1, b.0)).unwrap());
+            expected.truncate(max_qty.get());
+
+            let result: Vec<_> = tree
+                .best_n_within_by::<SquaredEuclidean, _, _>(&query, radius, max_qty, score)
+                .into_iter()
+                .map(|n| (n.distance, n.item))
+                .collect();
+            assert_eq!(result, expected);
+        }
+    }
+
     fn linear_search(
         content: &[[f64; 2]],
         query: &[f64; 2],
@@ -3,6 +3,10 @@ pub mod best_n_within;
 pub mod nearest_n;
 pub mod nearest_n_within;
 pub mod nearest_one;
+#[cfg(feature = "rayon")]
+pub mod par_batch;
+#[cfg(feature = "rayon")]
+pub mod par_within;
 pub mod within;
 pub mod within_unsorted;
 
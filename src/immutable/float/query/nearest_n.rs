@@ -72,6 +72,50 @@ impl<
     );
 }
 
+#[cfg(feature = "rkyv")]
+use crate::immutable::float::kdtree::ArchivedImmutableKdTreeRK;
+#[cfg(feature = "rkyv")]
+impl<
+        A: Axis + rkyv::Archive<Archived = A>,
+        T: Content + rkyv::Archive<Archived = T>,
+        const K: usize,
+        const B: usize,
+    > ArchivedImmutableKdTreeRK<A, T, K, B>
+{
+    generate_immutable_float_nearest_n!(
+        "use std::fs::File;
+    use memmap::MmapOptions;
+
+    use kiddo::immutable::float::kdtree::ImmutableKdTreeRK;
+
+    let mmap = unsafe { MmapOptions::new().map(&File::open(\"./examples/immutable-doctest-tree.rkyv\").unwrap()).unwrap() };
+    let tree = unsafe { rkyv::archived_root::<ImmutableKdTreeRK<f64, u32, 3, 256>>(&mmap) };"
+    );
+}
+
+#[cfg(feature = "allocator_api")]
+use crate::immutable::float::kdtree::ImmutableKdTreeIn;
+#[cfg(feature = "allocator_api")]
+impl<A, T, const K: usize, const B: usize, Alloc> ImmutableKdTreeIn<A, T, K, B, Alloc>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    Alloc: std::alloc::Allocator + Clone,
+    usize: Cast<T>,
+{
+    generate_immutable_float_nearest_n!(
+        "use std::alloc::System;
+    use kiddo::immutable::float::kdtree::ImmutableKdTreeIn;
+
+    let content: Vec<[f64; 3]> = vec!(
+            [1.0, 2.0, 5.0],
+            [2.0, 3.0, 6.0]
+        );
+
+        let tree: ImmutableKdTreeIn<f64, u64, 3, 32, System> = ImmutableKdTreeIn::new_from_slice_in(&content, System);"
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use crate::float::distance::SquaredEuclidean;
@@ -271,6 +315,168 @@ mod tests {
         }
     }
 
+    #[test]
+    fn can_query_into_a_reused_buffer() {
+        let content_to_add: Vec<[f64; 4]> =
+            (0..5_000).map(|_| rand::random::<[f64; 4]>()).collect();
+        let tree: ImmutableKdTree<f64, u32, 4, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add);
+
+        // start with a buffer larger than any query needs, holding stale results
+        let mut out = tree.within::<SquaredEuclidean>(&[0.5; 4], 0.5);
+        let dists = |results: &[crate::nearest_neighbour::NearestNeighbour<f64, u32>]| {
+            results.iter().map(|n| n.distance).collect::<Vec<_>>()
+        };
+
+        for qty in [1, 10, 100] {
+            let query = rand::random::<[f64; 4]>();
+            let qty = NonZero::new(qty).unwrap();
+
+            tree.nearest_n_into::<SquaredEuclidean>(&query, qty, &mut out);
+            assert_eq!(
+                dists(&out),
+                dists(&tree.nearest_n::<SquaredEuclidean>(&query, qty))
+            );
+
+            tree.within_into::<SquaredEuclidean>(&query, 0.05, &mut out);
+            assert_eq!(
+                dists(&out),
+                dists(&tree.within::<SquaredEuclidean>(&query, 0.05))
+            );
+        }
+    }
+
+    #[test]
+    fn can_query_nearest_n_const() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+
+        let content_to_add: Vec<[f32; 4]> =
+            (0..TREE_SIZE).map(|_| rand::random::<[f32; 4]>()).collect();
+
+        let tree: ImmutableKdTree<f32, u32, 4, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add);
+
+        for _ in 0..NUM_QUERIES {
+            let query_point = rand::random::<[f32; 4]>();
+            let expected: Vec<(f32, u32)> = linear_search(&content_to_add, 10, &query_point);
+
+            let result: Vec<_> = tree
+                .nearest_n_const::<SquaredEuclidean, 10>(&query_point)
+                .into_iter()
+                .map(|n| (n.distance, n.item))
+                .collect();
+
+            assert_eq!(result, expected);
+        }
+
+        // fewer items than N
+        let small: ImmutableKdTree<f32, u32, 4, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add[..2]);
+        let result = small.nearest_n_const::<SquaredEuclidean, 3>(&[0.0; 4]);
+        assert!(result[0].distance <= result[1].distance);
+        assert_eq!(result[2].distance, f32::INFINITY);
+    }
+
+    #[test]
+    fn can_query_kth_nearest_distance() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+
+        let content_to_add: Vec<[f64; 4]> =
+            (0..TREE_SIZE).map(|_| rand::random::<[f64; 4]>()).collect();
+
+        let tree: ImmutableKdTree<f64, u32, 4, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add);
+
+        for _ in 0..NUM_QUERIES {
+            let query_point = rand::random::<[f64; 4]>();
+            let expected: Vec<(f64, u32)> = linear_search(&content_to_add, 20, &query_point);
+
+            for k in [1, 5, 20] {
+                assert_eq!(
+                    tree.kth_nearest_distance::<SquaredEuclidean>(
+                        &query_point,
+                        NonZero::new(k).unwrap()
+                    ),
+                    expected[k - 1].0
+                );
+            }
+        }
+
+        // fewer items than k
+        let small: ImmutableKdTree<f64, u32, 4, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add[..3]);
+        assert_eq!(
+            small.kth_nearest_distance::<SquaredEuclidean>(&[0.0; 4], NonZero::new(4).unwrap()),
+            f64::INFINITY
+        );
+    }
+
+    #[test]
+    fn can_query_nearest_n_items_filtered() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+
+        let content_to_add: Vec<[f32; 4]> =
+            (0..TREE_SIZE).map(|_| rand::random::<[f32; 4]>()).collect();
+
+        let tree: ImmutableKdTree<f32, u32, 4, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add);
+
+        let query_points: Vec<[f32; 4]> = (0..NUM_QUERIES)
+            .map(|_| rand::random::<[f32; 4]>())
+            .collect();
+
+        // one quantity that is collected into a SortedVec, and one into a BinaryHeap
+        for qty in [10, 30] {
+            let max_qty = NonZero::new(qty).unwrap();
+
+            for query_point in &query_points {
+                let mut expected: Vec<(f32, u32)> = content_to_add
+                    .iter()
+                    .enumerate()
+                    .filter(|&(idx, _)| idx % 3 == 0)
+                    .map(|(idx, p)| (SquaredEuclidean::dist(query_point, p), idx as u32))
+                    .collect();
+                expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                expected.truncate(qty);
+
+                let result: Vec<_> = tree
+                    .nearest_n_filtered::<SquaredEuclidean, _>(query_point, max_qty, |item| {
+                        item % 3 == 0
+                    })
+                    .into_iter()
+                    .map(|n| (n.distance, n.item))
+                    .collect();
+
+                let result_dists: Vec<_> = result.iter().map(|(d, _)| *d).collect();
+                let expected_dists: Vec<_> = expected.iter().map(|(d, _)| *d).collect();
+
+                assert_eq!(result_dists, expected_dists);
+                assert!(result.iter().all(|(_, item)| item % 3 == 0));
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_one_filtered_can_exclude_a_duplicate_of_the_query() {
+        let content_to_add = [[0.5f32, 0.5], [0.5, 0.5], [0.6, 0.5]];
+        let tree: ImmutableKdTree<f32, u32, 2, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add);
+
+        let result = tree
+            .nearest_one_filtered::<SquaredEuclidean, _>(&[0.5, 0.5], |item| item != 0)
+            .unwrap();
+        assert_eq!(result.item, 1);
+        assert_eq!(result.distance, 0.0);
+
+        assert_eq!(
+            tree.nearest_one_filtered::<SquaredEuclidean, _>(&[0.5, 0.5], |_| false),
+            None
+        );
+    }
+
     fn linear_search<A: Axis, R, const K: usize>(
         content: &[[A; K]],
         qty: usize,
@@ -22,6 +22,10 @@ macro_rules! generate_immutable_float_nearest_n_within {
             "Finds up to n elements within `dist` of `query`, using the specified
 distance metric function.
 
+If `sorted` is `true`, the results are sorted by distance. Otherwise they are in
+arbitrary order, which saves sorting them when only the set of neighbours is needed.
+They are the same `max_items` nearest elements either way.
+
 # Examples
 
 ```rust
@@ -81,6 +85,54 @@ where
     );
 }
 
+#[cfg(feature = "rkyv")]
+use crate::immutable::float::kdtree::ArchivedImmutableKdTreeRK;
+#[cfg(feature = "rkyv")]
+impl<
+        A: Axis + rkyv::Archive<Archived = A>,
+        T: Content + rkyv::Archive<Archived = T>,
+        const K: usize,
+        const B: usize,
+    > ArchivedImmutableKdTreeRK<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    usize: Cast<T>,
+{
+    generate_immutable_float_nearest_n_within!(
+        "use std::fs::File;
+    use memmap::MmapOptions;
+
+    use kiddo::immutable::float::kdtree::ImmutableKdTreeRK;
+
+    let mmap = unsafe { MmapOptions::new().map(&File::open(\"./examples/immutable-doctest-tree.rkyv\").unwrap()).unwrap() };
+    let tree = unsafe { rkyv::archived_root::<ImmutableKdTreeRK<f64, u32, 3, 256>>(&mmap) };"
+    );
+}
+
+#[cfg(feature = "allocator_api")]
+use crate::immutable::float::kdtree::ImmutableKdTreeIn;
+#[cfg(feature = "allocator_api")]
+impl<A, T, const K: usize, const B: usize, Alloc> ImmutableKdTreeIn<A, T, K, B, Alloc>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    Alloc: std::alloc::Allocator + Clone,
+    usize: Cast<T>,
+{
+    generate_immutable_float_nearest_n_within!(
+        "use std::alloc::System;
+    use kiddo::immutable::float::kdtree::ImmutableKdTreeIn;
+
+    let content: Vec<[f64; 3]> = vec!(
+            [1.0, 2.0, 5.0],
+            [2.0, 3.0, 6.0]
+        );
+
+        let tree: ImmutableKdTreeIn<f64, u64, 3, 32, System> = ImmutableKdTreeIn::new_from_slice_in(&content, System);"
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use crate::float::distance::SquaredEuclidean;
@@ -197,6 +249,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn can_query_items_within_radius_unsorted() {
+        let content_to_add: Vec<[f32; 4]> =
+            (0..10_000).map(|_| rand::random::<[f32; 4]>()).collect();
+
+        let tree: ImmutableKdTree<AX, u32, 4, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add);
+
+        let mut rng = rand::thread_rng();
+        for max_qty in [3, 50] {
+            let max_qty = NonZero::new(max_qty).unwrap();
+
+            for _i in 0..100 {
+                let query_point: [f32; 4] = rng.gen();
+                let radius = 0.2;
+
+                let expected = linear_search(&content_to_add, &query_point, radius)
+                    .into_iter()
+                    .take(max_qty.into())
+                    .collect::<Vec<_>>();
+
+                let mut result: Vec<_> = tree
+                    .nearest_n_within::<SquaredEuclidean>(&query_point, radius, max_qty, false)
+                    .into_iter()
+                    .map(|n| (n.distance, n.item))
+                    .collect();
+                stabilize_sort(&mut result);
+
+                assert_eq!(result, expected);
+            }
+        }
+    }
+
     fn linear_search<A: Axis, const K: usize>(
         content: &[[A; K]],
         query_point: &[A; K],
@@ -71,9 +71,52 @@ where
     );
 }
 
+#[cfg(feature = "rkyv")]
+use crate::immutable::float::kdtree::ArchivedImmutableKdTreeRK;
+#[cfg(feature = "rkyv")]
+impl<A, T, const K: usize, const B: usize> ArchivedImmutableKdTreeRK<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K> + rkyv::Archive<Archived = A>,
+    T: Content + rkyv::Archive<Archived = T>,
+    usize: Cast<T>,
+{
+    generate_immutable_float_nearest_one!(
+        "use std::fs::File;
+    use memmap::MmapOptions;
+
+    use kiddo::immutable::float::kdtree::ImmutableKdTreeRK;
+
+    let mmap = unsafe { MmapOptions::new().map(&File::open(\"./examples/immutable-doctest-tree.rkyv\").unwrap()).unwrap() };
+    let tree = unsafe { rkyv::archived_root::<ImmutableKdTreeRK<f64, u32, 3, 256>>(&mmap) };"
+    );
+}
+
+#[cfg(feature = "allocator_api")]
+use crate::immutable::float::kdtree::ImmutableKdTreeIn;
+#[cfg(feature = "allocator_api")]
+impl<A, T, const K: usize, const B: usize, Alloc> ImmutableKdTreeIn<A, T, K, B, Alloc>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    Alloc: std::alloc::Allocator + Clone,
+    usize: Cast<T>,
+{
+    generate_immutable_float_nearest_one!(
+        "use std::alloc::System;
+    use kiddo::immutable::float::kdtree::ImmutableKdTreeIn;
+
+    let content: Vec<[f64; 3]> = vec!(
+            [1.0, 2.0, 5.0],
+            [2.0, 3.0, 6.0]
+        );
+
+        let tree: ImmutableKdTreeIn<f64, u64, 3, 32, System> = ImmutableKdTreeIn::new_from_slice_in(&content, System);"
+    );
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::float::distance::SquaredEuclidean;
+    use crate::float::distance::{Chebyshev, Cosine, Minkowski, SquaredEuclidean};
     use crate::float::kdtree::Axis;
     use crate::immutable::float::kdtree::ImmutableKdTree;
     use crate::nearest_neighbour::NearestNeighbour;
@@ -246,6 +289,164 @@ mod tests {
         }
     }
 
+    #[test]
+    fn can_query_nearest_one_point() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(7);
+
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 200;
+
+        let content_to_add: Vec<[f64; 3]> = (0..TREE_SIZE).map(|_| rng.gen::<[f64; 3]>()).collect();
+
+        let tree: ImmutableKdTree<f64, u32, 3, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add);
+
+        for _ in 0..NUM_QUERIES {
+            let query_point = rng.gen::<[f64; 3]>();
+            let expected = linear_search(&content_to_add, &query_point);
+
+            let result = tree.nearest_one_point::<SquaredEuclidean>(&query_point);
+
+            assert_eq!(result.distance, expected.distance);
+            assert_eq!(result.item as usize, expected.item);
+            assert_eq!(result.point, content_to_add[result.item as usize]);
+        }
+    }
+
+    #[test]
+    fn can_query_nearest_one_item_with_chebyshev_and_minkowski() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(5);
+
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 200;
+
+        // K = 5 so that the leaf scan exercises both the chunked and remainder paths
+        let content_to_add: Vec<[f64; 5]> = (0..TREE_SIZE).map(|_| rng.gen::<[f64; 5]>()).collect();
+
+        let tree: ImmutableKdTree<f64, u32, 5, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add);
+
+        for _ in 0..NUM_QUERIES {
+            let query_point = rng.gen::<[f64; 5]>();
+
+            let expected = content_to_add
+                .iter()
+                .map(|p| Chebyshev::dist(&query_point, p))
+                .fold(f64::INFINITY, f64::min);
+            let result = tree.nearest_one::<Chebyshev>(&query_point);
+            assert_eq!(result.distance, expected);
+
+            let expected = content_to_add
+                .iter()
+                .map(|p| Minkowski::<3>::dist(&query_point, p))
+                .fold(f64::INFINITY, f64::min);
+            let result = tree.nearest_one::<Minkowski<3>>(&query_point);
+            assert_eq!(result.distance, expected);
+        }
+    }
+
+    #[test]
+    fn can_query_nearest_one_by_cosine_distance() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(88);
+
+        let content_to_add: Vec<[f64; 8]> = (0..10_000)
+            .map(|_| Cosine::normalize(&rng.gen::<[f64; 8]>().map(|val| val * 2.0 - 1.0)))
+            .collect();
+
+        let tree: ImmutableKdTree<f64, u32, 8, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add);
+
+        for _ in 0..200 {
+            let query_point = Cosine::normalize(&rng.gen::<[f64; 8]>().map(|val| val * 2.0 - 1.0));
+
+            let (expected_item, max_similarity) = content_to_add
+                .iter()
+                .enumerate()
+                .map(|(idx, p)| {
+                    (
+                        idx,
+                        p.iter().zip(&query_point).map(|(a, b)| a * b).sum::<f64>(),
+                    )
+                })
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap();
+
+            let result = tree.nearest_one::<Cosine>(&query_point);
+            assert_eq!(result.item as usize, expected_item);
+            assert!((result.distance - (1.0 - max_similarity)).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn can_query_nearest_one_within() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(9);
+
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 200;
+
+        let content_to_add: Vec<[f64; 4]> = (0..TREE_SIZE).map(|_| rng.gen::<[f64; 4]>()).collect();
+
+        let tree: ImmutableKdTree<f64, u32, 4, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add);
+
+        for _ in 0..NUM_QUERIES {
+            let query_point = rng.gen::<[f64; 4]>();
+            let expected = linear_search(&content_to_add, &query_point);
+
+            for max_dist in [0.001, 0.01, 0.1] {
+                let result = tree.nearest_one_within::<SquaredEuclidean>(&query_point, max_dist);
+
+                if expected.distance < max_dist {
+                    let result = result.unwrap();
+                    assert_eq!(result.distance, expected.distance);
+                    assert_eq!(result.item as usize, expected.item);
+                } else {
+                    assert_eq!(result, None);
+                }
+            }
+        }
+
+        // a tree with no stems
+        let small: ImmutableKdTree<f64, u32, 4, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add[..10]);
+        assert_eq!(
+            small.nearest_one_within::<SquaredEuclidean>(&[2.0; 4], 1.0),
+            None
+        );
+        assert!(small
+            .nearest_one_within::<SquaredEuclidean>(&[0.5; 4], 4.0)
+            .is_some());
+    }
+
+    #[test]
+    fn can_query_nearest_one_with_hint() {
+        use crate::nearest_neighbour::NearestHint;
+
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(78);
+
+        let content_to_add: Vec<[f64; 3]> = (0..20_000).map(|_| rng.gen::<[f64; 3]>()).collect();
+        let tree: ImmutableKdTree<f64, usize, 3, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add);
+
+        // a random walk, so that successive queries are close together
+        let mut query_point = [0.5f64; 3];
+        let mut hint = NearestHint::default();
+        for _ in 0..1_000 {
+            query_point = core::array::from_fn(|dim| {
+                (query_point[dim] + rng.gen_range(-0.01..0.01)).clamp(0.0, 1.0)
+            });
+
+            let result = tree.nearest_one_with_hint::<SquaredEuclidean>(&query_point, &mut hint);
+            assert_eq!(result, linear_search(&content_to_add, &query_point));
+        }
+
+        // hints from a larger tree must not be trusted
+        let small_tree: ImmutableKdTree<f64, usize, 3, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add[..100]);
+        let result = small_tree.nearest_one_with_hint::<SquaredEuclidean>(&query_point, &mut hint);
+        assert_eq!(result, linear_search(&content_to_add[..100], &query_point));
+    }
+
     fn linear_search<A: Axis, const K: usize>(
         content: &[[A; K]],
         query_point: &[A; K],
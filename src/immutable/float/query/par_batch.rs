@@ -0,0 +1,171 @@
+use az::Cast;
+use rayon::prelude::*;
+use std::num::NonZero;
+
+use crate::float::kdtree::Axis;
+use crate::float_leaf_slice::leaf_slice::{LeafSliceFloat, LeafSliceFloatChunk};
+use crate::immutable::float::hilbert::hilbert_order;
+use crate::immutable::float::kdtree::ImmutableKdTree;
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{Content, DistanceMetric};
+
+/// Batches smaller than this are queried in the order that they are given in, as the
+/// cost of reordering them outweighs the gain
+const MIN_BATCH_TO_REORDER: usize = 1024;
+
+impl<A: Axis, T: Content, const K: usize, const B: usize> ImmutableKdTree<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    usize: Cast<T>,
+{
+    /// Finds the nearest element to each of `queries`, using the specified distance metric
+    /// function, with the queries spread across the threads of the current rayon thread pool.
+    ///
+    /// Returns one result per query, in the same order as `queries`. Large batches are
+    /// queried in the order in which they lie along a Hilbert curve, so that queries that
+    /// run one after the other on the same thread mostly visit the same parts of the tree.
+    /// Requires the `rayon` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    ///     use kiddo::ImmutableKdTree;
+    ///     use kiddo::SquaredEuclidean;
+    ///
+    ///     let content: Vec<[f64; 3]> = vec!([1.0, 2.0, 5.0], [2.0, 3.0, 6.0]);
+    ///     let tree: ImmutableKdTree<f64, 3> = ImmutableKdTree::new_from_slice(&content);
+    ///
+    ///     let nearest = tree.par_nearest_one_batch::<SquaredEuclidean>(&[[1.0, 2.0, 5.1], [2.0, 3.0, 5.9]]);
+    ///
+    ///     assert_eq!(nearest.iter().map(|n| n.item).collect::<Vec<_>>(), vec![0, 1]);
+    /// ```
+    pub fn par_nearest_one_batch<D>(&self, queries: &[[A; K]]) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        par_map_queries(queries, |query| self.nearest_one::<D>(query))
+    }
+
+    /// Finds the nearest `max_qty` elements to each of `queries`, sorted by distance, with
+    /// the queries spread across the threads of the current rayon thread pool.
+    ///
+    /// Returns one `Vec` of results per query, in the same order as `queries`. See
+    /// [`par_nearest_one_batch`](ImmutableKdTree::par_nearest_one_batch) for details.
+    /// Requires the `rayon` feature.
+    pub fn par_nearest_n_batch<D>(
+        &self,
+        queries: &[[A; K]],
+        max_qty: NonZero<usize>,
+    ) -> Vec<Vec<NearestNeighbour<A, T>>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        par_map_queries(queries, |query| self.nearest_n::<D>(query, max_qty))
+    }
+
+    /// Finds all elements within `dist` of each of `queries`, sorted by distance, with
+    /// the queries spread across the threads of the current rayon thread pool.
+    ///
+    /// Returns one `Vec` of results per query, in the same order as `queries`. See
+    /// [`par_nearest_one_batch`](ImmutableKdTree::par_nearest_one_batch) for details.
+    /// Requires the `rayon` feature.
+    pub fn par_within_batch<D>(
+        &self,
+        queries: &[[A; K]],
+        dist: A,
+    ) -> Vec<Vec<NearestNeighbour<A, T>>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        par_map_queries(queries, |query| self.within::<D>(query, dist))
+    }
+
+    /// Finds all elements within `dist` of each of `queries`, in arbitrary order, with
+    /// the queries spread across the threads of the current rayon thread pool.
+    ///
+    /// Returns one `Vec` of results per query, in the same order as `queries`. See
+    /// [`par_nearest_one_batch`](ImmutableKdTree::par_nearest_one_batch) for details.
+    /// Requires the `rayon` feature.
+    pub fn par_within_unsorted_batch<D>(
+        &self,
+        queries: &[[A; K]],
+        dist: A,
+    ) -> Vec<Vec<NearestNeighbour<A, T>>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        par_map_queries(queries, |query| self.within_unsorted::<D>(query, dist))
+    }
+}
+
+/// Calls `query_fn` on each of `queries` in parallel, returning the results in the same
+/// order as `queries`, but running large batches in Hilbert curve order
+fn par_map_queries<A: Axis, const K: usize, R: Send>(
+    queries: &[[A; K]],
+    query_fn: impl Fn(&[A; K]) -> R + Sync + Send,
+) -> Vec<R> {
+    let order = (queries.len() >= MIN_BATCH_TO_REORDER)
+        .then(|| hilbert_order(queries))
+        .flatten();
+
+    let Some(order) = order else {
+        return queries.par_iter().map(query_fn).collect();
+    };
+
+    let results: Vec<R> = order
+        .par_iter()
+        .map(|&idx| query_fn(&queries[idx]))
+        .collect();
+
+    // put the results back into the order of the queries
+    let mut unordered: Vec<Option<R>> = (0..queries.len()).map(|_| None).collect();
+    for (idx, result) in order.into_iter().zip(results) {
+        unordered[idx] = Some(result);
+    }
+    unordered.into_iter().map(Option::unwrap).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::float::distance::SquaredEuclidean;
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use rand::{Rng, SeedableRng};
+    use std::num::NonZero;
+
+    #[test]
+    fn batch_queries_match_single_queries() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(54);
+        let points: Vec<[f64; 3]> = (0..10_000).map(|_| rng.gen()).collect();
+        let tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+
+        // one batch small enough to run in the order given, and one large enough to be reordered
+        for batch_size in [100, 5_000] {
+            let queries: Vec<[f64; 3]> = (0..batch_size).map(|_| rng.gen()).collect();
+
+            let nearest_one = tree.par_nearest_one_batch::<SquaredEuclidean>(&queries);
+            let nearest_n =
+                tree.par_nearest_n_batch::<SquaredEuclidean>(&queries, NonZero::new(5).unwrap());
+            let within = tree.par_within_batch::<SquaredEuclidean>(&queries, 0.001);
+            let within_unsorted =
+                tree.par_within_unsorted_batch::<SquaredEuclidean>(&queries, 0.001);
+
+            assert_eq!(nearest_one.len(), batch_size);
+            for (idx, query) in queries.iter().enumerate() {
+                assert_eq!(
+                    nearest_one[idx],
+                    tree.nearest_one::<SquaredEuclidean>(query)
+                );
+                assert_eq!(
+                    nearest_n[idx],
+                    tree.nearest_n::<SquaredEuclidean>(query, NonZero::new(5).unwrap())
+                );
+                assert_eq!(within[idx], tree.within::<SquaredEuclidean>(query, 0.001));
+                assert_eq!(
+                    within_unsorted[idx].len(),
+                    tree.within_unsorted::<SquaredEuclidean>(query, 0.001).len()
+                );
+            }
+        }
+    }
+}
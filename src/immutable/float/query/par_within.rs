@@ -0,0 +1,300 @@
+use az::Cast;
+use rayon::prelude::*;
+use std::ops::Rem;
+
+use crate::float::kdtree::Axis;
+use crate::float_leaf_slice::leaf_slice::{LeafSliceFloat, LeafSliceFloatChunk};
+use crate::immutable::float::kdtree::ImmutableKdTree;
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{Content, DistanceMetric};
+
+/// The state of the traversal at the root of a subtree that is searched on its own task
+struct Subtree<A, const K: usize> {
+    stem_idx: usize,
+    split_dim: usize,
+    off: [A; K],
+    rd: A,
+    level: usize,
+    #[cfg(feature = "modified_van_emde_boas")]
+    minor_level: u32,
+    leaf_idx: usize,
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize> ImmutableKdTree<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    usize: Cast<T>,
+{
+    /// Finds all elements within `dist` of `query`, sorted by distance, splitting the
+    /// traversal of a single query across the threads of the current rayon thread pool.
+    ///
+    /// See [`par_within_unsorted`](ImmutableKdTree::par_within_unsorted) for details. Only
+    /// worthwhile for very large radii. Requires the `rayon` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    ///     use kiddo::ImmutableKdTree;
+    ///     use kiddo::SquaredEuclidean;
+    ///
+    ///     let content: Vec<[f64; 3]> = vec!([1.0, 2.0, 5.0], [2.0, 3.0, 6.0]);
+    ///     let tree: ImmutableKdTree<f64, 3> = ImmutableKdTree::new_from_slice(&content);
+    ///
+    ///     let within = tree.par_within::<SquaredEuclidean>(&[1.0, 2.0, 5.0], 10f64);
+    ///
+    ///     assert_eq!(within.iter().map(|n| n.item).collect::<Vec<_>>(), vec![0, 1]);
+    /// ```
+    pub fn par_within<D>(&self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut matching_items = self.par_within_unsorted::<D>(query, dist);
+        matching_items.par_sort_unstable();
+        matching_items
+    }
+
+    /// Finds all elements within `dist` of `query`, in arbitrary order, splitting the
+    /// traversal of a single query across the threads of the current rayon thread pool.
+    ///
+    /// The first few stem levels are traversed up front, and each subtree below them that
+    /// intersects the query ball is then searched on its own rayon task, with the results
+    /// concatenated. Only worthwhile for very large radii that match a sizable fraction of
+    /// the tree; for typical queries, [`within_unsorted`](ImmutableKdTree::within_unsorted)
+    /// will be faster. Requires the `rayon` feature.
+    pub fn par_within_unsorted<D>(&self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        // split until there are a few tasks per thread, so that work-stealing
+        // can even out subtrees that contain differing numbers of matches
+        let parallel_levels = (rayon::current_num_threads() * 4)
+            .next_power_of_two()
+            .trailing_zeros() as usize;
+
+        let mut subtrees = Vec::new();
+        self.collect_subtrees::<D>(
+            query,
+            dist,
+            Subtree {
+                #[cfg(not(feature = "modified_van_emde_boas"))]
+                stem_idx: 1,
+                #[cfg(feature = "modified_van_emde_boas")]
+                stem_idx: 0,
+                split_dim: 0,
+                off: [A::zero(); K],
+                rd: A::zero(),
+                level: 0,
+                #[cfg(feature = "modified_van_emde_boas")]
+                minor_level: 0,
+                leaf_idx: 0,
+            },
+            parallel_levels,
+            &mut subtrees,
+        );
+
+        let matching_items: Vec<NearestNeighbour<A, T>> = subtrees
+            .into_par_iter()
+            .flat_map_iter(|mut subtree| {
+                let mut matching_items = Vec::new();
+                self.search_subtree::<D>(query, dist, &mut subtree, &mut matching_items);
+                matching_items
+            })
+            .collect();
+
+        #[cfg(feature = "verify_results")]
+        crate::verify::verify_nearest_n_within(
+            self,
+            &crate::verify::LeafMetric::<D, T>::new(),
+            query,
+            Some(dist),
+            usize::MAX,
+            &matching_items,
+        );
+
+        matching_items
+    }
+
+    /// Descends `parallel_levels` stem levels from `subtree`, pushing each subtree below
+    /// them that could hold points within `radius` of `query` onto `subtrees`.
+    fn collect_subtrees<D>(
+        &self,
+        query: &[A; K],
+        radius: A,
+        subtree: Subtree<A, K>,
+        parallel_levels: usize,
+        subtrees: &mut Vec<Subtree<A, K>>,
+    ) where
+        D: DistanceMetric<A, K>,
+    {
+        if parallel_levels == 0
+            || subtree.level > self.max_stem_level as usize
+            || self.stems.is_empty()
+        {
+            subtrees.push(subtree);
+            return;
+        }
+
+        let split_dim = self.split_dim_at(subtree.stem_idx, subtree.split_dim);
+        let val = self.stems[subtree.stem_idx];
+        let is_right_child = query[split_dim] >= val;
+
+        #[cfg(not(feature = "modified_van_emde_boas"))]
+        let (closer_node_idx, further_node_idx) = {
+            let is_right_child = usize::from(is_right_child);
+            (
+                (subtree.stem_idx << 1) + is_right_child,
+                (subtree.stem_idx << 1) + 1 - is_right_child,
+            )
+        };
+        #[cfg(feature = "modified_van_emde_boas")]
+        let (closer_node_idx, further_node_idx) = {
+            use crate::modified_van_emde_boas::modified_van_emde_boas_get_child_idx_v2_branchless;
+            (
+                modified_van_emde_boas_get_child_idx_v2_branchless(
+                    subtree.stem_idx as u32,
+                    is_right_child,
+                    subtree.minor_level,
+                ) as usize,
+                modified_van_emde_boas_get_child_idx_v2_branchless(
+                    subtree.stem_idx as u32,
+                    !is_right_child,
+                    subtree.minor_level,
+                ) as usize,
+            )
+        };
+        #[cfg(feature = "modified_van_emde_boas")]
+        let minor_level = (subtree.minor_level + 1).rem(3);
+
+        let leaf_idx = subtree.leaf_idx << 1;
+        let old_off = subtree.off[split_dim];
+        let new_off = query[split_dim].saturating_dist(val);
+        let further_rd = crate::traits::accumulate_dist(
+            subtree.rd,
+            D::dist1(new_off, old_off),
+            D::MAX_OF_AXES,
+            Axis::rd_update,
+        );
+
+        let child = |stem_idx, leaf_idx, off, rd| Subtree {
+            stem_idx,
+            split_dim: (split_dim + 1).rem(K),
+            off,
+            rd,
+            level: subtree.level + 1,
+            #[cfg(feature = "modified_van_emde_boas")]
+            minor_level,
+            leaf_idx,
+        };
+
+        self.collect_subtrees::<D>(
+            query,
+            radius,
+            child(
+                closer_node_idx,
+                leaf_idx + usize::from(is_right_child),
+                subtree.off,
+                subtree.rd,
+            ),
+            parallel_levels - 1,
+            subtrees,
+        );
+
+        if further_rd <= radius {
+            let mut off = subtree.off;
+            off[split_dim] = new_off;
+            self.collect_subtrees::<D>(
+                query,
+                radius,
+                child(
+                    further_node_idx,
+                    leaf_idx + usize::from(!is_right_child),
+                    off,
+                    further_rd,
+                ),
+                parallel_levels - 1,
+                subtrees,
+            );
+        }
+    }
+
+    fn search_subtree<D>(
+        &self,
+        query: &[A; K],
+        radius: A,
+        subtree: &mut Subtree<A, K>,
+        matching_items: &mut Vec<NearestNeighbour<A, T>>,
+    ) where
+        D: DistanceMetric<A, K>,
+    {
+        #[cfg(not(feature = "modified_van_emde_boas"))]
+        self.nearest_n_within_recurse::<D, _>(
+            query,
+            radius,
+            subtree.stem_idx,
+            subtree.split_dim,
+            matching_items,
+            &mut subtree.off,
+            subtree.rd,
+            subtree.level,
+            subtree.leaf_idx,
+        );
+
+        #[cfg(feature = "modified_van_emde_boas")]
+        self.nearest_n_within_recurse::<D, _>(
+            query,
+            radius,
+            subtree.stem_idx as u32,
+            subtree.split_dim,
+            matching_items,
+            &mut subtree.off,
+            subtree.rd,
+            subtree.level as i32,
+            subtree.minor_level,
+            subtree.leaf_idx,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use crate::SquaredEuclidean;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn par_within_matches_within() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(92);
+        let points: Vec<[f64; 3]> = (0..50_000).map(|_| rng.gen()).collect();
+        let tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+
+        for radius in [0.0001, 0.01, 0.3, 3.0] {
+            for _ in 0..10 {
+                let query: [f64; 3] = rng.gen();
+
+                let expected = tree.within::<SquaredEuclidean>(&query, radius);
+                assert_eq!(
+                    tree.par_within::<SquaredEuclidean>(&query, radius),
+                    expected
+                );
+
+                let mut unsorted = tree.par_within_unsorted::<SquaredEuclidean>(&query, radius);
+                unsorted.sort();
+                assert_eq!(unsorted, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn par_within_on_a_single_leaf_tree() {
+        let points = [[1.0, 2.0], [3.0, 4.0], [5.0, 6.0]];
+        let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+
+        let within = tree.par_within::<SquaredEuclidean>(&[3.0, 4.5], 20.0);
+
+        assert_eq!(
+            within.iter().map(|nn| nn.item).collect::<Vec<_>>(),
+            vec![1, 2, 0]
+        );
+    }
+}
@@ -69,6 +69,50 @@ impl<
     );
 }
 
+#[cfg(feature = "rkyv")]
+use crate::immutable::float::kdtree::ArchivedImmutableKdTreeRK;
+#[cfg(feature = "rkyv")]
+impl<
+        A: Axis + rkyv::Archive<Archived = A>,
+        T: Content + rkyv::Archive<Archived = T>,
+        const K: usize,
+        const B: usize,
+    > ArchivedImmutableKdTreeRK<A, T, K, B>
+{
+    generate_immutable_float_within!(
+        "use std::fs::File;
+    use memmap::MmapOptions;
+
+    use kiddo::immutable::float::kdtree::ImmutableKdTreeRK;
+
+    let mmap = unsafe { MmapOptions::new().map(&File::open(\"./examples/immutable-doctest-tree.rkyv\").unwrap()).unwrap() };
+    let tree = unsafe { rkyv::archived_root::<ImmutableKdTreeRK<f64, u32, 3, 256>>(&mmap) };"
+    );
+}
+
+#[cfg(feature = "allocator_api")]
+use crate::immutable::float::kdtree::ImmutableKdTreeIn;
+#[cfg(feature = "allocator_api")]
+impl<A, T, const K: usize, const B: usize, Alloc> ImmutableKdTreeIn<A, T, K, B, Alloc>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    Alloc: std::alloc::Allocator + Clone,
+    usize: Cast<T>,
+{
+    generate_immutable_float_within!(
+        "use std::alloc::System;
+    use kiddo::immutable::float::kdtree::ImmutableKdTreeIn;
+
+    let content: Vec<[f64; 3]> = vec!(
+            [1.0, 2.0, 5.0],
+            [2.0, 3.0, 6.0]
+        );
+
+        let tree: ImmutableKdTreeIn<f64, u64, 3, 32, System> = ImmutableKdTreeIn::new_from_slice_in(&content, System);"
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use crate::float::distance::Manhattan;
@@ -172,6 +216,63 @@ mod tests {
         }
     }
 
+    #[test]
+    fn can_query_items_within_radius_with_filter() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+        const RADIUS: f32 = 0.2;
+
+        let content_to_add: Vec<[f32; 4]> =
+            (0..TREE_SIZE).map(|_| rand::random::<[f32; 4]>()).collect();
+
+        let tree: ImmutableKdTree<AX, u32, 4, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add);
+
+        for _ in 0..NUM_QUERIES {
+            let query_point = rand::random::<[f32; 4]>();
+
+            let mut expected = linear_search(&content_to_add, &query_point, RADIUS);
+            expected.retain(|(_, item)| item % 3 == 0);
+
+            let mut result: Vec<_> = tree
+                .within_filtered::<Manhattan, _>(&query_point, RADIUS, |item| item % 3 == 0)
+                .into_iter()
+                .map(|n| (n.distance, n.item))
+                .collect();
+            stabilize_sort(&mut result);
+
+            assert_eq!(result, expected);
+        }
+    }
+
+    #[test]
+    fn can_query_items_within_radius_sorted_by_item() {
+        const TREE_SIZE: usize = 10_000;
+        const NUM_QUERIES: usize = 100;
+        const RADIUS: f32 = 0.2;
+
+        let content_to_add: Vec<[f32; 4]> =
+            (0..TREE_SIZE).map(|_| rand::random::<[f32; 4]>()).collect();
+
+        let tree: ImmutableKdTree<AX, u32, 4, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add);
+
+        for _ in 0..NUM_QUERIES {
+            let query_point = rand::random::<[f32; 4]>();
+
+            let mut expected = linear_search(&content_to_add, &query_point, RADIUS);
+            expected.sort_by_key(|&(_, item)| item);
+
+            let result: Vec<_> = tree
+                .within_sorted_by_item::<Manhattan>(&query_point, RADIUS)
+                .into_iter()
+                .map(|n| (n.distance, n.item))
+                .collect();
+
+            assert_eq!(result, expected);
+        }
+    }
+
     fn linear_search<A: Axis, const K: usize>(
         content: &[[A; K]],
         query_point: &[A; K],
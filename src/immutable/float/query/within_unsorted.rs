@@ -69,6 +69,50 @@ impl<
     );
 }
 
+#[cfg(feature = "rkyv")]
+use crate::immutable::float::kdtree::ArchivedImmutableKdTreeRK;
+#[cfg(feature = "rkyv")]
+impl<
+        A: Axis + rkyv::Archive<Archived = A>,
+        T: Content + rkyv::Archive<Archived = T>,
+        const K: usize,
+        const B: usize,
+    > ArchivedImmutableKdTreeRK<A, T, K, B>
+{
+    generate_immutable_float_within_unsorted!(
+        "use std::fs::File;
+    use memmap::MmapOptions;
+
+    use kiddo::immutable::float::kdtree::ImmutableKdTreeRK;
+
+    let mmap = unsafe { MmapOptions::new().map(&File::open(\"./examples/immutable-doctest-tree.rkyv\").unwrap()).unwrap() };
+    let tree = unsafe { rkyv::archived_root::<ImmutableKdTreeRK<f64, u32, 3, 256>>(&mmap) };"
+    );
+}
+
+#[cfg(feature = "allocator_api")]
+use crate::immutable::float::kdtree::ImmutableKdTreeIn;
+#[cfg(feature = "allocator_api")]
+impl<A, T, const K: usize, const B: usize, Alloc> ImmutableKdTreeIn<A, T, K, B, Alloc>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    Alloc: std::alloc::Allocator + Clone,
+    usize: Cast<T>,
+{
+    generate_immutable_float_within_unsorted!(
+        "use std::alloc::System;
+    use kiddo::immutable::float::kdtree::ImmutableKdTreeIn;
+
+    let content: Vec<[f64; 3]> = vec!(
+            [1.0, 2.0, 5.0],
+            [2.0, 3.0, 6.0]
+        );
+
+        let tree: ImmutableKdTreeIn<f64, u64, 3, 32, System> = ImmutableKdTreeIn::new_from_slice_in(&content, System);"
+    );
+}
+
 #[cfg(test)]
 mod tests {
     use crate::float::distance::SquaredEuclidean;
@@ -170,6 +214,56 @@ mod tests {
         }
     }
 
+    #[test]
+    fn within_unsorted_visit_visits_the_same_items_as_within_unsorted() {
+        const TREE_SIZE: usize = 10_000;
+        const RADIUS: f32 = 0.2;
+
+        let content_to_add: Vec<[f32; 4]> =
+            (0..TREE_SIZE).map(|_| rand::random::<[f32; 4]>()).collect();
+
+        let tree: ImmutableKdTree<f32, u32, 4, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add);
+
+        for _ in 0..100 {
+            let query_point = rand::random::<[f32; 4]>();
+            let expected = linear_search(&content_to_add, &query_point, RADIUS);
+
+            let mut visited = vec![];
+            tree.within_unsorted_visit::<SquaredEuclidean, _>(
+                &query_point,
+                RADIUS,
+                |item, dist| visited.push((dist, item)),
+            );
+
+            stabilize_sort(&mut visited);
+            assert_eq!(visited, expected);
+        }
+    }
+
+    #[test]
+    fn any_within_matches_whether_within_unsorted_is_empty() {
+        const TREE_SIZE: usize = 10_000;
+
+        let content_to_add: Vec<[f32; 4]> =
+            (0..TREE_SIZE).map(|_| rand::random::<[f32; 4]>()).collect();
+
+        let tree: ImmutableKdTree<f32, u32, 4, 32> =
+            ImmutableKdTree::new_from_slice(&content_to_add);
+
+        for radius in [0.001, 0.01, 0.2] {
+            for _ in 0..100 {
+                let query_point = rand::random::<[f32; 4]>();
+                let expected = !linear_search(&content_to_add, &query_point, radius).is_empty();
+
+                assert_eq!(
+                    tree.any_within::<SquaredEuclidean>(&query_point, radius),
+                    expected
+                );
+            }
+        }
+    }
+
     fn linear_search<A: Axis, const K: usize>(
         content: &[[A; K]],
         query_point: &[A; K],
@@ -0,0 +1,196 @@
+//! An [`ImmutableKdTree`] of spheres rather than points, for finding the stored spheres
+//! that contain a query point.
+//!
+//! Each stored point has a radius of its own, making it a sphere (or a circle, in two
+//! dimensions) of influence around the point. This is what collision broad-phases and
+//! influence maps need, and is awkward to emulate with a plain point tree as the radius
+//! to query with depends on which point is being tested.
+
+use az::{Az, Cast};
+
+use crate::float::kdtree::Axis;
+use crate::float_leaf_slice::leaf_slice::{LeafSliceFloat, LeafSliceFloatChunk};
+use crate::immutable::float::kdtree::ImmutableKdTree;
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{Content, DistanceMetric};
+
+/// An [`ImmutableKdTree`] in which every point has its own radius.
+///
+/// Queries find the stored points that are closer to the query point than their own
+/// radius. They search the tree for points within the largest radius of any point, and
+/// then discard those that are out of range of their own radius, so a few points with
+/// very large radii will slow down every query.
+///
+/// Radii are in the same units as the distances of the distance metric used to query,
+/// just as the `dist` of [`within`](ImmutableKdTree::within) is. For
+/// [`SquaredEuclidean`](crate::SquaredEuclidean) that means that they are squared.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::immutable::float::radius::RadiusImmutableKdTree;
+/// use kiddo::SquaredEuclidean;
+///
+/// let points: Vec<[f64; 2]> = vec![[0.0, 0.0], [10.0, 0.0], [5.0, 5.0]];
+/// // squared, as they are used with SquaredEuclidean
+/// let radii = vec![2.0 * 2.0, 6.0 * 6.0, 1.0];
+/// let tree: RadiusImmutableKdTree<f64, u32, 2, 32> =
+///     RadiusImmutableKdTree::new_from_slice(&points, &radii);
+///
+/// let mut hits: Vec<u32> = tree
+///     .intersecting::<SquaredEuclidean>(&[1.5, 0.0])
+///     .iter()
+///     .map(|hit| hit.item)
+///     .collect();
+/// hits.sort();
+///
+/// assert_eq!(hits, vec![0]);
+/// assert!(tree.intersects_any::<SquaredEuclidean>(&[6.0, 0.0]));
+/// assert!(!tree.intersects_any::<SquaredEuclidean>(&[0.0, 5.0]));
+/// ```
+#[derive(Debug)]
+pub struct RadiusImmutableKdTree<
+    A: Copy + Default,
+    T: Copy + Default,
+    const K: usize,
+    const B: usize,
+> {
+    tree: ImmutableKdTree<A, T, K, B>,
+    radii: Vec<A>,
+    max_radius: A,
+}
+
+impl<A, T, const K: usize, const B: usize> RadiusImmutableKdTree<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content + Cast<usize>,
+    usize: Cast<T>,
+{
+    /// Creates a `RadiusImmutableKdTree` from the points in `source` and the radius of
+    /// each of them in `radii`. As with [`ImmutableKdTree::new_from_slice`], the item of
+    /// each point is its index within `source`.
+    ///
+    /// Panics if `source` and `radii` are of different lengths, or if any radius is
+    /// negative or NaN.
+    pub fn new_from_slice(source: &[[A; K]], radii: &[A]) -> Self {
+        assert_eq!(
+            source.len(),
+            radii.len(),
+            "there must be one radius for each point"
+        );
+        assert!(
+            radii.iter().all(|&radius| radius >= A::zero()),
+            "radii must not be negative or NaN"
+        );
+
+        Self {
+            tree: ImmutableKdTree::new_from_slice(source),
+            radii: radii.to_vec(),
+            max_radius: radii.iter().copied().fold(A::zero(), A::max),
+        }
+    }
+
+    /// Returns a reference to the wrapped tree
+    pub fn tree(&self) -> &ImmutableKdTree<A, T, K, B> {
+        &self.tree
+    }
+
+    /// Returns the radius of the point with item `item`
+    pub fn radius(&self, item: T) -> A {
+        self.radii[item.az::<usize>()]
+    }
+
+    /// Returns the largest radius of any point
+    pub fn max_radius(&self) -> A {
+        self.max_radius
+    }
+
+    /// Finds every stored point that `query` is within the radius of, using the specified
+    /// distance metric function. Results are returned in arbitrary order.
+    pub fn intersecting<D>(&self, query: &[A; K]) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        let mut results = self.tree.within_unsorted::<D>(query, self.max_radius);
+        results.retain(|neighbour| neighbour.distance < self.radius(neighbour.item));
+        results
+    }
+
+    /// Returns whether `query` is within the radius of any stored point, using the
+    /// specified distance metric function.
+    pub fn intersects_any<D>(&self, query: &[A; K]) -> bool
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.tree
+            .within_unsorted::<D>(query, self.max_radius)
+            .iter()
+            .any(|neighbour| neighbour.distance < self.radius(neighbour.item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RadiusImmutableKdTree;
+    use crate::float::distance::{Manhattan, SquaredEuclidean};
+    use crate::traits::DistanceMetric;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn finds_every_sphere_containing_the_query() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(52);
+        let points: Vec<[f64; 3]> = (0..5_000).map(|_| rng.gen()).collect();
+        // mostly small radii, with the occasional large one
+        let radii: Vec<f64> = (0..5_000)
+            .map(|idx| {
+                let radius: f64 = rng.gen_range(0.0..0.05);
+                if idx % 500 == 0 {
+                    radius * 10.0
+                } else {
+                    radius
+                }
+            })
+            .collect();
+
+        let tree: RadiusImmutableKdTree<f64, u32, 3, 32> =
+            RadiusImmutableKdTree::new_from_slice(&points, &radii);
+
+        for _ in 0..200 {
+            let query: [f64; 3] = rng.gen();
+
+            let mut expected: Vec<u32> = (0..points.len())
+                .filter(|&idx| SquaredEuclidean::dist(&query, &points[idx]) < radii[idx])
+                .map(|idx| idx as u32)
+                .collect();
+
+            let mut result: Vec<u32> = tree
+                .intersecting::<SquaredEuclidean>(&query)
+                .iter()
+                .map(|hit| hit.item)
+                .collect();
+            result.sort();
+            expected.sort();
+
+            assert_eq!(result, expected);
+            assert_eq!(
+                tree.intersects_any::<SquaredEuclidean>(&query),
+                !expected.is_empty()
+            );
+
+            let manhattan_count = (0..points.len())
+                .filter(|&idx| Manhattan::dist(&query, &points[idx]) < radii[idx])
+                .count();
+            assert_eq!(
+                tree.intersecting::<Manhattan>(&query).len(),
+                manhattan_count
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "there must be one radius for each point")]
+    fn requires_a_radius_for_every_point() {
+        let _: RadiusImmutableKdTree<f64, u32, 2, 32> =
+            RadiusImmutableKdTree::new_from_slice(&[[0.0, 0.0], [1.0, 1.0]], &[1.0]);
+    }
+}
@@ -23,3 +23,5 @@
 #[doc(hidden)]
 pub(crate) mod common;
 pub mod float;
+
+pub use crate::float_leaf_slice::leaf_slice::LeafSlice;
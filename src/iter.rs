@@ -1,4 +1,7 @@
+//! Iterator object returned by iterating over a reference to a tree
 use crate::traits::Content;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
 
 pub(crate) trait IterableTreeData<A: Copy + Default, T: Content, const K: usize> {
     fn get_leaf_data(&self, idx: usize, out: &mut Vec<(T, [A; K])>) -> Option<usize>;
@@ -43,3 +46,21 @@ impl<A: Copy + Default, T: Content, const K: usize, X: IterableTreeData<A, T, K>
         self.leaf_data.pop()
     }
 }
+
+/// Iterator object returned by iterating over a reference to a tree, yielding
+/// `(item, point)` tuples in arbitrary order
+pub struct TreeItems<'a, A, T, const K: usize>(Box<dyn Iterator<Item = (T, [A; K])> + 'a>);
+
+impl<'a, A, T, const K: usize> TreeItems<'a, A, T, K> {
+    pub(crate) fn new(iter: impl Iterator<Item = (T, [A; K])> + 'a) -> Self {
+        TreeItems(Box::new(iter))
+    }
+}
+
+impl<A, T, const K: usize> Iterator for TreeItems<'_, A, T, K> {
+    type Item = (T, [A; K]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
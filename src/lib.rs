@@ -1,4 +1,3 @@
-#![cfg_attr(feature = "simd", feature(slice_as_chunks))]
 #![warn(rustdoc::missing_crate_level_docs)]
 #![deny(rustdoc::invalid_codeblock_attributes)]
 #![warn(missing_docs)]
@@ -6,6 +5,8 @@
 #![warn(rustdoc::private_intra_doc_links)]
 #![doc(html_root_url = "https://docs.rs/kiddo/5.0.3")]
 #![doc(issue_tracker_base_url = "https://github.com/sdd/kiddo/issues/")]
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
 
 //! # Kiddo
 //!
@@ -15,19 +16,48 @@
 //!
 //! Kiddo provides:
 //! - A standard floating-point k-d tree, exposed as [`kiddo::KdTree`](`crate::KdTree`), for when you may need to add or remove
-//!     points to the tree after the initial construction / deserialization
+//!   points to the tree after the initial construction / deserialization
 //! - An [`ImmutableKdTree`](`immutable::float::kdtree::ImmutableKdTree`) with performance space and advantages over the standard
 //!   k-d tree, for situations where the tree does not need to be modified after creation
+//! - A [`DynKdTree`](`float::dyn_kdtree::DynKdTree`), for when the number of dimensions is only known at runtime
+//! - A [`kdtree_rt::KdTree`](`float::kdtree_rt::KdTree`), for when the bucket size is only known at runtime
 //! - **integer / fixed point support** via the [`fixed`](https://docs.rs/fixed/latest/fixed/) crate;
 //! - **`f16` support** via the [`half`](https://docs.rs/half/latest/half/) crate;
 //! - **instant zero-copy deserialization** and serialization via [`Rkyv`](https://docs.rs/rkyv/latest/rkyv/) ([`Serde`](https://docs.rs/serde/latest/serde/) still available).
+//! - a [**portable binary format**](`portable`) for [`ImmutableKdTree`](`immutable::float::kdtree::ImmutableKdTree`)s,
+//!   that can be written on one platform and read on any other, whatever its endianness or pointer width,
+//!   optionally with its leaf coordinates bit-packed to make it smaller.
+//! - an [`ExternalImmutableKdTree`](`immutable::float::external::ExternalImmutableKdTree`), which reads the
+//!   co-ordinates of its points from the caller's slice at query time, for about half the size.
+//! - a [`Forest`](`immutable::float::forest::Forest`) of [`ImmutableKdTree`](`immutable::float::kdtree::ImmutableKdTree`) shards,
+//!   queried as one tree, optionally in parallel.
+//! - a choice of [`SplitPolicy`] when building an [`ImmutableKdTree`](`immutable::float::kdtree::ImmutableKdTree`),
+//!   so that anisotropic data can be split on the dimension with the widest spread at each stem.
+//! - an [`AutoIndex`](`immutable::float::brute_force::AutoIndex`), which answers queries over a few hundred
+//!   points or fewer with a [linear scan](`immutable::float::brute_force::BruteForce`), and over more with a tree.
+//! - a [`TransformedTree`](`transformed::TransformedTree`), which queries a tree built in one frame of reference,
+//!   such as a sensor's, with points in another, through an [`AffineTransform`](`transformed::AffineTransform`).
+//! - conversion of an [`ImmutableKdTree`](`immutable::float::kdtree::ImmutableKdTree`) between `f64` and `f32`
+//!   [co-ordinates](`immutable::float::kdtree::ImmutableKdTree::to_f32`) without rebuilding it, with a choice of [`Rounding`].
+//! - batch queries that read their query points straight out of [flat, interleaved buffers](`flat`),
+//!   such as vertex buffers, given an offset and a stride.
+//! - an [`ImmutableKdTreeAny`](`immutable::float::kdtree_any::ImmutableKdTreeAny`), which stores its items as
+//!   the smallest of `u16`, `u32` or `u64` that the number of points allows.
 
 //!
 //! Kiddo is ideal for super-fast spatial / geospatial lookups and nearest-neighbour / KNN
 //! queries for low-ish numbers of dimensions, where you want to ask questions such as:
 //!  - Find the [nearest_n](`float::kdtree::KdTree::nearest_n`) item(s) to a query point, ordered by distance;
 //!  - Find all items [within](`float::kdtree::KdTree::within`) a specified radius of a query point;
+//!  - Find all items [within an annulus](`float::kdtree::KdTree::within_annulus`), at least a minimum and at most a maximum distance from a query point;
+//!  - Find all items [within a polygon](`float::kdtree::KdTree::within_polygon`), for 2-D trees, such as when geofencing;
+//!  - Find the item [nearest to a line segment](`float::kdtree::KdTree::nearest_to_segment`), or all items [within a distance of one](`float::kdtree::KdTree::within_of_segment`), such as for corridor searches;
+//!  - Find the [nearest `n` items](`float::kdtree::KdTree::nearest_n_in_slab`) whose coordinate on one axis lies within a range, such as a window of time;
+//!  - Find all pairs of items, one from each of two trees, that are [within](`float::kdtree::KdTree::join_within`) a specified distance of each other;
+//!  - Find [groups of items](`float::kdtree::KdTree::find_duplicates`) that are within a tolerance of each other, such as when deduplicating a dataset;
 //!  - Find the ["best" n item(s) within](`float::kdtree::KdTree::best_n_within`) a specified distance of a query point, for some definition of "best"
+//!    (also available on [`ImmutableKdTree`](`immutable::float::kdtree::ImmutableKdTree::best_n_within`) and the
+//!    [fixed-point `KdTree`](`fixed::kdtree::KdTree::best_n_within`))
 //!
 //! ## Installation
 //!
@@ -73,42 +103,138 @@
 //! See the [examples documentation](https://github.com/sdd/kiddo/tree/master/examples) for some more in-depth examples.
 //! ## Optional Features
 
-//! The Kiddo crate exposes the following features. Any labelled as **(NIGHTLY)** are not available on `stable` Rust as they require some unstable features. You'll need to build with `nightly` in order to user them.
-//! * **serde** - serialization / deserialization via [`Serde`](https://docs.rs/serde/latest/serde/)
+//! The Kiddo crate exposes the following features. All of them except `allocator_api` are available on `stable` Rust.
+//! Leaf scans do not need any feature to be vectorized: by default they use portable kernels that are
+//! written to be auto-vectorized by LLVM on any target, using whichever target features are enabled at
+//! compile time (e.g. `RUSTFLAGS="-C target-cpu=native"` to use AVX2 where available).
+//! * **serde** - serialization / deserialization via [`Serde`](https://docs.rs/serde/latest/serde/).
+//!   Wrap a tree in `versioned::Versioned` for a representation that will remain readable by future versions.
 //! * **rkyv** - zero-copy serialization / deserialization via [`Rkyv`](https://docs.rs/rkyv/latest/rkyv/)
-//! * `simd` **(NIGHTLY)** - enables some hand written SIMD and pre-fetch intrinsics code within [`ImmutableKdTree`](`immutable::float::kdtree::ImmutableKdTree`) that may improve performance (currently only on nearest_one with `f64`)
-//! * `f16` - enables usage of `f16` from the `half` crate for float trees.
+//!   `save_rkyv` and `load_rkyv_mmap` write a tree to a file and memory-map it back again.
+//! * `allocator_api` - requires nightly Rust. Adds `ImmutableKdTreeIn`,
+//!   which stores the stems and leaves of an immutable tree in memory from an allocator that you supply,
+//!   e.g. to place a large tree on a particular NUMA node or in an arena.
+//!   Because of this, `--all-features` only builds on nightly, and `ImmutableKdTreeIn` is left out of the docs on docs.rs.
+//! * `simd` - enables some hand written SIMD and pre-fetch intrinsics code within [`ImmutableKdTree`](`immutable::float::kdtree::ImmutableKdTree`) that may improve performance
+//!   (currently the leaf scans of `nearest_one`, `nearest_n`, `within` and `best_n_within` with `f32`, using AVX2 on x86_64
+//!   or NEON on aarch64. AVX2 must be enabled at compile time, e.g. with `RUSTFLAGS="-C target-cpu=native"`).
+//!   Requires Rust 1.88 or later.
+//! * `simd_stable` - uses explicit-lane leaf scan kernels for `f32` and `f64` within [`ImmutableKdTree`](`immutable::float::kdtree::ImmutableKdTree`),
+//!   written in plain stable Rust so that they vectorize on any target. Where `simd` selects a hand written
+//!   intrinsics kernel, that takes precedence.
+//! * `f16` - enables usage of `f16` from the `half` crate for float trees. `ImmutableKdTree<f16, ...>`
+//!   accumulates distances in `f32` whilst scanning leaves, so memory use is halved without the frequent ties
+//!   and misrankings that summing in `f16` causes.
+//! * `verify_results` - debugging aid. A sample of queries have their results checked against a brute-force
+//!   search over every point in the tree. Any mismatch is logged, along with the query, and the query panics.
+//!   The sampling interval defaults to every 16th query and can be changed with the `KIDDO_VERIFY_SAMPLE_INTERVAL`
+//!   environment variable. Very slow - not intended for use in production.
+//! * `rayon` - adds `par_within` and `par_within_unsorted` to the mutable [`KdTree`](`float::kdtree::KdTree`)s and to
+//!   [`ImmutableKdTree`](`immutable::float::kdtree::ImmutableKdTree`),
+//!   which split the traversal of a single query across threads. Only worthwhile for very large radius queries.
+//!   Also adds `par_nearest_one_batch`, `par_nearest_n_batch`, `par_within_batch` and `par_within_unsorted_batch`
+//!   to [`ImmutableKdTree`](`immutable::float::kdtree::ImmutableKdTree`), which spread a batch of queries across threads.
+//! * `cluster` - adds the `cluster` module, providing DBSCAN clustering over an
+//!   [`ImmutableKdTree`](`immutable::float::kdtree::ImmutableKdTree`), and a histogram of
+//!   k-th nearest neighbour distances for choosing its `eps`.
+//! * `geo` - adds the `geo` module, providing `GeoKdTree`, an immutable tree of points given as latitude and
+//!   longitude, with queries such as `nearest_within_metres` and `within_km` that take and return
+//!   great-circle distances.
+//! * `capi` - adds the `capi` module, providing `extern "C"` functions for building and running
+//!   nearest neighbour queries on trees of `f32` or `f64` points with a number of dimensions chosen
+//!   at runtime, for use from other languages.
+//! * `ndarray` - adds `from_ndarray` to [`ImmutableKdTree`](`immutable::float::kdtree::ImmutableKdTree`) and the
+//!   mutable [`KdTree`](`float::kdtree::KdTree`), building a tree from the rows of an `ndarray` array in any
+//!   memory layout, along with `nearest_one_ndarray` and `nearest_n_ndarray`, which answer a batch of queries
+//!   given as rows and return their results as arrays.
+//! * `stats` - adds `nearest_one_with_stats`, which also returns counts of the stems visited,
+//!   leaves scanned and points compared by the query. Useful when choosing a bucket size.
+//! * `debug_viz` - adds `to_dot` to the mutable [`KdTree`](`float::kdtree::KdTree`), which renders the
+//!   structure of the tree for [Graphviz](https://graphviz.org/). `debug_nodes`, which iterates over
+//!   the tree's stems and leaves, is available without it.
+//! * `u64_leaf_extents` - stores the extents of the leaves of
+//!   [`ImmutableKdTree`](`immutable::float::kdtree::ImmutableKdTree`)s as `u64`s rather than `u32`s,
+//!   allowing trees of more than `u32::MAX` items at the cost of eight more bytes per leaf.
+//! * `deterministic_construction` - makes the layout of an
+//!   [`ImmutableKdTree`](`immutable::float::kdtree::ImmutableKdTree`) depend only on its input, so that
+//!   trees built by different Rust versions or platforms are byte-identical when serialized. Slower to build.
+//! * `std` - enabled by default. Without it the crate is `no_std`, needing only `alloc`, for use on
+//!   embedded targets. Only the fixed-point [`fixed::kdtree::KdTree`] is available in that case: the float
+//!   trees and the other features (aside from `stats`) all enable `std`.
 
 #[macro_use]
 extern crate doc_comment;
+extern crate alloc;
 extern crate core;
 
 #[doc(hidden)]
 pub mod best_neighbour;
 #[doc(hidden)]
+#[cfg(feature = "std")]
+pub mod build_progress;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "cluster")]
+pub mod cluster;
+#[doc(hidden)]
 pub(crate) mod common;
+#[doc(hidden)]
+pub mod construction_error;
 #[cfg(feature = "serde")]
 #[doc(hidden)]
 mod custom_serde;
+#[doc(hidden)]
+pub mod duplicate_policy;
 pub mod fixed;
+#[cfg(feature = "std")]
+pub mod flat;
+#[cfg(feature = "std")]
 pub mod float;
+#[cfg(feature = "geo")]
+pub mod geo;
+#[cfg(feature = "std")]
 pub mod immutable;
 mod mirror_select_nth_unstable_by;
+#[cfg(feature = "ndarray")]
+pub mod ndarray;
 #[doc(hidden)]
 pub mod nearest_neighbour;
+#[cfg(feature = "rkyv")]
+pub mod persistence;
+#[cfg(feature = "std")]
+pub mod portable;
+#[cfg(feature = "std")]
+pub mod query_builder;
+#[doc(hidden)]
+pub mod rounding;
+#[doc(hidden)]
+pub mod split_policy;
+#[cfg(feature = "stats")]
+pub mod stats;
 #[doc(hidden)]
 #[cfg(feature = "test_utils")]
 pub mod test_utils;
 pub mod traits;
+#[cfg(feature = "std")]
+pub mod transformed;
+pub mod tree_stats;
+#[cfg(feature = "std")]
+pub mod tune;
+#[cfg(feature = "serde")]
+pub mod versioned;
 
-mod iter;
+#[doc(hidden)]
+pub mod iter;
+#[cfg(feature = "verify_results")]
+mod verify;
 
 #[doc(hidden)]
-#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
 pub mod within_unsorted_iter;
 
 #[doc(hidden)]
+#[cfg(feature = "std")]
 pub mod float_leaf_slice;
+#[cfg(feature = "std")]
 mod modified_van_emde_boas;
 
 /// A floating-point k-d tree with default parameters.
@@ -118,6 +244,7 @@ mod modified_van_emde_boas;
 ///
 /// To manually specify more advanced parameters, use [`KdTree`](`float::kdtree::KdTree`) directly.
 /// To store positions using integer or fixed-point types, use [`fixed::kdtree::KdTree`].
+#[cfg(feature = "std")]
 pub type KdTree<A, const K: usize> = float::kdtree::KdTree<A, u64, K, 32, u32>;
 
 /// An immutable floating-point k-d tree with default parameters.
@@ -127,13 +254,23 @@ pub type KdTree<A, const K: usize> = float::kdtree::KdTree<A, u64, K, 32, u32>;
 ///
 /// To manually specify more advanced parameters, use [`ImmutableKdTree`](`immutable::float::kdtree::ImmutableKdTree`) directly.
 /// To store positions using integer or fixed-point types, use [`fixed::kdtree::KdTree`].
+#[cfg(feature = "std")]
 pub type ImmutableKdTree<A, const K: usize> =
     immutable::float::kdtree::ImmutableKdTree<A, u64, K, 32>;
 
 pub use best_neighbour::BestNeighbour;
+#[cfg(feature = "std")]
+pub use build_progress::CancellationToken;
+pub use construction_error::ConstructionError;
+pub use duplicate_policy::DuplicatePolicy;
+#[cfg(feature = "std")]
 pub use float::distance::Manhattan;
+#[cfg(feature = "std")]
 pub use float::distance::SquaredEuclidean;
+pub use iter::TreeItems;
+pub use nearest_neighbour::NearestHint;
 pub use nearest_neighbour::NearestNeighbour;
-
-#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+pub use nearest_neighbour::NearestNeighbourWithPoint;
+pub use rounding::Rounding;
+pub use split_policy::SplitPolicy;
 pub use within_unsorted_iter::WithinUnsortedIter;
@@ -0,0 +1,309 @@
+//! Construction from, and batch queries returning, [`ndarray`](https://docs.rs/ndarray)
+//! arrays.
+//!
+//! Points are given as two-dimensional arrays with one row per point and one column per
+//! dimension, in any memory layout. Batch queries take their query points in the same
+//! form, and return their results as arrays with one row per query.
+//!
+//! Requires the `ndarray` feature.
+use std::num::NonZero;
+
+use ::ndarray::{Array1, Array2, ArrayView2};
+use az::{Az, Cast};
+
+use crate::float::kdtree::{Axis, KdTree};
+use crate::float_leaf_slice::leaf_slice::{LeafSliceFloat, LeafSliceFloatChunk};
+use crate::immutable::float::kdtree::{ImmutableKdTree, PointSource};
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{Content, DistanceMetric, Index};
+
+impl<A: Copy> PointSource<A> for ArrayView2<'_, A> {
+    #[inline(always)]
+    fn coord(&self, idx: usize, dim: usize) -> A {
+        self[[idx, dim]]
+    }
+}
+
+impl<A, T, const K: usize, const B: usize> ImmutableKdTree<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    usize: Cast<T>,
+{
+    /// Creates an `ImmutableKdTree`, balanced and optimized, populated with the rows of
+    /// `points`, each of which is a point.
+    ///
+    /// The coordinates are read straight out of `points` whatever its memory layout, so
+    /// no intermediate copy is made. As with
+    /// [`new_from_slice`](ImmutableKdTree::new_from_slice), each item is the index of its
+    /// point's row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `points` does not have `K` columns.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    /// use kiddo::SquaredEuclidean;
+    /// use ndarray::array;
+    ///
+    /// let points = array![[1.0, 2.0], [3.0, 4.0], [5.0, 6.0]];
+    /// let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::from_ndarray(points.view());
+    ///
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[3.1, 4.1]).item, 1);
+    /// ```
+    pub fn from_ndarray(points: ArrayView2<A>) -> Self {
+        check_columns::<K>(&points);
+
+        Self::new_from_point_source(&points, points.nrows())
+    }
+
+    /// Finds the nearest point to each row of `queries`, returning the distances and
+    /// items of the results as arrays with one element per query.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `queries` does not have `K` columns.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    /// use kiddo::SquaredEuclidean;
+    /// use ndarray::array;
+    ///
+    /// let points = array![[1.0, 2.0], [3.0, 4.0], [5.0, 6.0]];
+    /// let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::from_ndarray(points.view());
+    ///
+    /// let queries = array![[5.1, 6.1], [0.9, 1.9]];
+    /// let (_distances, items) = tree.nearest_one_ndarray::<SquaredEuclidean>(queries.view());
+    ///
+    /// assert_eq!(items, array![2, 0]);
+    /// ```
+    pub fn nearest_one_ndarray<D>(&self, queries: ArrayView2<A>) -> (Array1<A>, Array1<T>)
+    where
+        D: DistanceMetric<A, K>,
+    {
+        nearest_one_rows(queries, |query| self.nearest_one::<D>(query))
+    }
+
+    /// Finds the nearest `max_qty` points to each row of `queries`, returning the
+    /// distances and items of the results as arrays with one row per query, sorted by
+    /// distance.
+    ///
+    /// If the tree holds fewer than `max_qty` points, the rest of each row is filled with
+    /// infinite distances and zero items.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `queries` does not have `K` columns.
+    pub fn nearest_n_ndarray<D>(
+        &self,
+        queries: ArrayView2<A>,
+        max_qty: NonZero<usize>,
+    ) -> (Array2<A>, Array2<T>)
+    where
+        D: DistanceMetric<A, K>,
+    {
+        nearest_n_rows(queries, max_qty.get(), |query| {
+            self.nearest_n::<D>(query, max_qty)
+        })
+    }
+}
+
+impl<A: Axis, T: Content, const K: usize, const B: usize, IDX: Index<T = IDX>>
+    KdTree<A, T, K, B, IDX>
+where
+    usize: Cast<IDX>,
+    usize: Cast<T>,
+{
+    /// Creates a `KdTree` populated with the rows of `points`, each of which is a point,
+    /// with each item being the index of its point's row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `points` does not have `K` columns.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{KdTree, SquaredEuclidean};
+    /// use ndarray::array;
+    ///
+    /// let points = array![[1.0, 2.0], [3.0, 4.0], [5.0, 6.0]];
+    /// let tree: KdTree<f64, 2> = KdTree::from_ndarray(points.view());
+    ///
+    /// assert_eq!(tree.nearest_one::<SquaredEuclidean>(&[3.1, 4.1]).item, 1);
+    /// ```
+    pub fn from_ndarray(points: ArrayView2<A>) -> Self {
+        check_columns::<K>(&points);
+
+        let mut tree = Self::with_capacity(points.nrows());
+        for (idx, row) in points.rows().into_iter().enumerate() {
+            tree.add(&std::array::from_fn(|dim| row[dim]), idx.az::<T>());
+        }
+
+        tree
+    }
+
+    /// Finds the nearest point to each row of `queries`, returning the distances and
+    /// items of the results as arrays with one element per query.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `queries` does not have `K` columns.
+    pub fn nearest_one_ndarray<D>(&self, queries: ArrayView2<A>) -> (Array1<A>, Array1<T>)
+    where
+        D: DistanceMetric<A, K>,
+    {
+        nearest_one_rows(queries, |query| self.nearest_one::<D>(query))
+    }
+
+    /// Finds the nearest `max_qty` points to each row of `queries`, returning the
+    /// distances and items of the results as arrays with one row per query, sorted by
+    /// distance.
+    ///
+    /// If the tree holds fewer than `max_qty` points, the rest of each row is filled with
+    /// infinite distances and zero items.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `queries` does not have `K` columns.
+    pub fn nearest_n_ndarray<D>(
+        &self,
+        queries: ArrayView2<A>,
+        max_qty: NonZero<usize>,
+    ) -> (Array2<A>, Array2<T>)
+    where
+        D: DistanceMetric<A, K>,
+    {
+        nearest_n_rows(queries, max_qty.get(), |query| {
+            self.nearest_n::<D>(query, max_qty.get())
+        })
+    }
+}
+
+fn check_columns<const K: usize>(points: &ArrayView2<impl Sized>) {
+    assert_eq!(
+        points.ncols(),
+        K,
+        "expected points with {K} columns, but got {}",
+        points.ncols()
+    );
+}
+
+fn nearest_one_rows<A: Axis, T: Content, const K: usize>(
+    queries: ArrayView2<A>,
+    query_fn: impl Fn(&[A; K]) -> NearestNeighbour<A, T>,
+) -> (Array1<A>, Array1<T>) {
+    check_columns::<K>(&queries);
+
+    let (distances, items) = queries
+        .rows()
+        .into_iter()
+        .map(|row| {
+            let result = query_fn(&std::array::from_fn(|dim| row[dim]));
+            (result.distance, result.item)
+        })
+        .unzip();
+
+    (Array1::from_vec(distances), Array1::from_vec(items))
+}
+
+fn nearest_n_rows<A: Axis, T: Content, const K: usize>(
+    queries: ArrayView2<A>,
+    max_qty: usize,
+    query_fn: impl Fn(&[A; K]) -> Vec<NearestNeighbour<A, T>>,
+) -> (Array2<A>, Array2<T>) {
+    check_columns::<K>(&queries);
+
+    let mut distances = Array2::from_elem((queries.nrows(), max_qty), A::infinity());
+    let mut items = Array2::from_elem((queries.nrows(), max_qty), T::zero());
+
+    for (row_idx, row) in queries.rows().into_iter().enumerate() {
+        let results = query_fn(&std::array::from_fn(|dim| row[dim]));
+        for (col_idx, result) in results.into_iter().enumerate() {
+            distances[[row_idx, col_idx]] = result.distance;
+            items[[row_idx, col_idx]] = result.item;
+        }
+    }
+
+    (distances, items)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::float::kdtree::KdTree;
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use crate::SquaredEuclidean;
+    use ::ndarray::{Array2, ShapeBuilder};
+    use rand::{Rng, SeedableRng};
+    use std::num::NonZero;
+
+    #[test]
+    fn ndarray_construction_and_queries_match_slices() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(87);
+        let points: Vec<[f64; 3]> = (0..2_000).map(|_| rng.gen()).collect();
+        let queries: Vec<[f64; 3]> = (0..50).map(|_| rng.gen()).collect();
+
+        // column-major, to check that the layout of the array doesn't matter
+        let mut array = Array2::zeros((points.len(), 3).f());
+        for (idx, point) in points.iter().enumerate() {
+            for dim in 0..3 {
+                array[[idx, dim]] = point[dim];
+            }
+        }
+        let query_array = Array2::from_shape_vec((queries.len(), 3), queries.concat()).unwrap();
+
+        let tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+        let from_array: ImmutableKdTree<f64, u32, 3, 32> =
+            ImmutableKdTree::from_ndarray(array.view());
+        assert_eq!(from_array, tree);
+
+        let mutable: KdTree<f64, u32, 3, 32, u32> = KdTree::from_ndarray(array.view());
+        assert_eq!(mutable.size() as usize, points.len());
+
+        let (distances, items) = tree.nearest_one_ndarray::<SquaredEuclidean>(query_array.view());
+        let (n_distances, n_items) = mutable
+            .nearest_n_ndarray::<SquaredEuclidean>(query_array.view(), NonZero::new(5).unwrap());
+
+        for (idx, query) in queries.iter().enumerate() {
+            let expected = tree.nearest_one::<SquaredEuclidean>(query);
+            assert_eq!(
+                (distances[idx], items[idx]),
+                (expected.distance, expected.item)
+            );
+
+            let expected = mutable.nearest_n::<SquaredEuclidean>(query, 5);
+            for (col, nn) in expected.iter().enumerate() {
+                assert_eq!(
+                    (n_distances[[idx, col]], n_items[[idx, col]]),
+                    (nn.distance, nn.item)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_n_ndarray_pads_short_results() {
+        let points = ::ndarray::array![[1.0, 2.0], [3.0, 4.0]];
+        let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::from_ndarray(points.view());
+
+        let (distances, items) = tree.nearest_n_ndarray::<SquaredEuclidean>(
+            ::ndarray::array![[1.0, 2.0]].view(),
+            NonZero::new(3).unwrap(),
+        );
+
+        assert_eq!(distances.row(0).to_vec(), vec![0.0, 8.0, f64::INFINITY]);
+        assert_eq!(items.row(0).to_vec(), vec![0, 1, 0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected points with 3 columns, but got 2")]
+    fn from_ndarray_panics_on_wrong_column_count() {
+        let points = ::ndarray::array![[1.0, 2.0]];
+        let _tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::from_ndarray(points.view());
+    }
+}
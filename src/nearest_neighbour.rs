@@ -1,6 +1,6 @@
 //! A result item returned by a query
 use crate::traits::Content;
-use std::cmp::Ordering;
+use core::cmp::Ordering;
 
 /// Represents an entry in the results of a nearest neighbour query, with `distance` being the distance of this
 /// particular item from the query point, and `item` being the stored item index that was found
@@ -43,6 +43,72 @@ impl<A, T: Content> From<NearestNeighbour<A, T>> for (A, T) {
     }
 }
 
+/// Represents the result of a nearest neighbour query that also returns the coordinates
+/// of the point that was found, as returned by methods such as `nearest_one_point`.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::{KdTree, NearestNeighbour, NearestNeighbourWithPoint, SquaredEuclidean};
+///
+/// let mut tree: KdTree<f64, 2> = KdTree::new();
+/// tree.add(&[1.0, 2.0], 5);
+/// tree.add(&[8.0, 9.0], 6);
+///
+/// let nearest = tree.nearest_one_point::<SquaredEuclidean>(&[1.0, 1.0]);
+/// assert_eq!(nearest, NearestNeighbourWithPoint { distance: 1.0, item: 5, point: [1.0, 2.0] });
+///
+/// let nearest: NearestNeighbour<f64, u64> = nearest.into();
+/// assert_eq!(nearest, NearestNeighbour { distance: 1.0, item: 5 });
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NearestNeighbourWithPoint<A, T, const K: usize> {
+    /// the distance of the found item from the query point according to the supplied distance metric
+    pub distance: A,
+    /// the stored index of an item that was found in the query
+    pub item: T,
+    /// the coordinates that the found item was stored at
+    pub point: [A; K],
+}
+
+impl<A, T, const K: usize> From<NearestNeighbourWithPoint<A, T, K>> for NearestNeighbour<A, T> {
+    fn from(elem: NearestNeighbourWithPoint<A, T, K>) -> Self {
+        NearestNeighbour {
+            distance: elem.distance,
+            item: elem.item,
+        }
+    }
+}
+
+/// Remembers which leaf of an `ImmutableKdTree` the result of a previous query came from,
+/// so that a following query close to it can start its search there. Used with
+/// `nearest_one_with_hint`.
+///
+/// A default hint points at the first leaf, and so is no better than no hint at all. A
+/// hint that came from a different tree still gives correct results, just without any
+/// speed-up.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+/// use kiddo::{NearestHint, SquaredEuclidean};
+///
+/// let points: Vec<[f64; 2]> = (0..1000).map(|i| [i as f64, (i * 37 % 1000) as f64]).collect();
+/// let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+///
+/// let mut hint = NearestHint::default();
+/// for step in 0..100 {
+///     let query = [500.0 + step as f64 * 0.1, 500.0];
+///     let nearest = tree.nearest_one_with_hint::<SquaredEuclidean>(&query, &mut hint);
+///     assert_eq!(nearest, tree.nearest_one::<SquaredEuclidean>(&query));
+/// }
+/// ```
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct NearestHint {
+    pub(crate) leaf_idx: usize,
+}
+
 #[cfg(test)]
 mod tests {
     use crate::nearest_neighbour::NearestNeighbour;
@@ -0,0 +1,265 @@
+//! Saving trees to files with [`rkyv`], and loading them back by memory-mapping those files.
+//!
+//! Loading a tree this way is near-instant regardless of its size, as nothing is read from
+//! disk until a query touches it. These helpers take care of the serializer and scratch space
+//! setup, and of the alignment that the archived trees rely upon.
+//!
+//! Requires the `rkyv` feature.
+use std::fmt::Debug;
+use std::fs::File;
+use std::io;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::path::Path;
+
+use az::Cast;
+use memmap::{Mmap, MmapOptions};
+use rkyv::ser::serializers::AllocSerializer;
+
+use crate::float::kdtree::{Axis, KdTree};
+use crate::float_leaf_slice::leaf_slice::{LeafSliceFloat, LeafSliceFloatChunk};
+use crate::immutable::float::kdtree::{
+    AlignedArchivedImmutableKdTree, ImmutableKdTree, ImmutableKdTreeRK,
+};
+use crate::traits::{Content, Index};
+
+/// The scratch space that is allocated on the stack before the serializer falls back
+/// to allocating on the heap
+const SCRATCH_SPACE: usize = 4096;
+
+/// A tree of type `R` that has been loaded by memory-mapping a file written by `save_rkyv`.
+///
+/// Dereferences to the archived form of `R`, which can be queried directly. The file
+/// remains mapped for as long as this is kept alive.
+pub struct MmapArchive<R> {
+    mmap: Mmap,
+    _marker: PhantomData<R>,
+}
+
+impl<R: rkyv::Archive> MmapArchive<R> {
+    /// Memory-maps the file at `path`, treating its contents as an archived `R`.
+    ///
+    /// # Safety
+    ///
+    /// The file must have been written by `save_rkyv` from an `R` with exactly the same
+    /// type parameters, and must not be modified whilst it is mapped. Neither of these can
+    /// be checked, and a file that does not meet them results in undefined behaviour.
+    unsafe fn map(path: &Path) -> io::Result<Self> {
+        let mmap = MmapOptions::new().map(&File::open(path)?)?;
+
+        Ok(MmapArchive {
+            mmap,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<R: rkyv::Archive> Deref for MmapArchive<R> {
+    type Target = R::Archived;
+
+    fn deref(&self) -> &Self::Target {
+        // upheld by the caller of `map`
+        unsafe { rkyv::archived_root::<R>(&self.mmap) }
+    }
+}
+
+impl<R> Debug for MmapArchive<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MmapArchive")
+            .field("len", &self.mmap.len())
+            .finish()
+    }
+}
+
+impl<A, T, const K: usize, const B: usize> MmapArchive<ImmutableKdTreeRK<A, T, K, B>>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K> + rkyv::Archive<Archived = A>,
+    T: Content + rkyv::Archive<Archived = T>,
+    usize: Cast<T>,
+{
+    /// Returns an [`AlignedArchivedImmutableKdTree`] that borrows from the mapped file.
+    ///
+    /// This copies the stems so that they are aligned to cache lines, which makes queries
+    /// slightly faster. For trees that are only queried a handful of times, query this
+    /// `MmapArchive` directly instead.
+    pub fn aligned(&self) -> AlignedArchivedImmutableKdTree<'_, A, T, K, B> {
+        AlignedArchivedImmutableKdTree::from_bytes(&self.mmap)
+    }
+}
+
+fn save<R>(value: &R, path: &Path) -> io::Result<()>
+where
+    R: rkyv::Serialize<AllocSerializer<SCRATCH_SPACE>>,
+{
+    let bytes = rkyv::to_bytes::<_, SCRATCH_SPACE>(value)
+        .map_err(|err| io::Error::other(format!("could not serialize tree: {err:?}")))?;
+
+    std::fs::write(path, &bytes)
+}
+
+impl<A, T, const K: usize, const B: usize> ImmutableKdTree<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K> + rkyv::Archive<Archived = A>,
+    T: Content + rkyv::Archive<Archived = T>,
+    usize: Cast<T>,
+    ImmutableKdTreeRK<A, T, K, B>: rkyv::Serialize<AllocSerializer<SCRATCH_SPACE>>,
+{
+    /// Serializes the tree with [`rkyv`] and writes it to the file at `path`, replacing
+    /// the file if it already exists.
+    ///
+    /// The tree is copied into an [`ImmutableKdTreeRK`] in order to be serialized, so
+    /// this temporarily needs enough memory for two more copies of the tree.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{ImmutableKdTree, SquaredEuclidean};
+    ///
+    /// let points: Vec<[f64; 3]> = vec![[1.0, 2.0, 5.0], [2.0, 3.0, 6.0]];
+    /// let tree: ImmutableKdTree<f64, 3> = ImmutableKdTree::new_from_slice(&points);
+    ///
+    /// let path = std::env::temp_dir().join("kiddo-doctest-immutable-save.rkyv");
+    /// tree.save_rkyv(&path).unwrap();
+    ///
+    /// let loaded = unsafe { ImmutableKdTree::<f64, 3>::load_rkyv_mmap(&path) }.unwrap();
+    /// assert_eq!(loaded.nearest_one::<SquaredEuclidean>(&[2.0, 3.0, 6.1]).item, 1);
+    /// # drop(loaded);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn save_rkyv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let rk_tree: ImmutableKdTreeRK<A, T, K, B> = self.clone().into();
+
+        save(&rk_tree, path.as_ref())
+    }
+
+    /// Memory-maps a tree from a file written by [`save_rkyv`](ImmutableKdTree::save_rkyv).
+    ///
+    /// The returned [`MmapArchive`] can be queried directly, or converted into an
+    /// [`AlignedArchivedImmutableKdTree`] with [`MmapArchive::aligned`].
+    ///
+    /// # Safety
+    ///
+    /// The file must have been written by `save_rkyv` from a tree with exactly the same
+    /// type parameters, and must not be modified whilst it is mapped. The contents of the
+    /// file are not validated, and loading a file that does not meet these requirements
+    /// results in undefined behaviour.
+    pub unsafe fn load_rkyv_mmap(
+        path: impl AsRef<Path>,
+    ) -> io::Result<MmapArchive<ImmutableKdTreeRK<A, T, K, B>>> {
+        MmapArchive::map(path.as_ref())
+    }
+}
+
+impl<A, T, const K: usize, const B: usize, IDX> KdTree<A, T, K, B, IDX>
+where
+    A: Axis + rkyv::Archive<Archived = A>,
+    T: Content + rkyv::Archive<Archived = T>,
+    IDX: Index<T = IDX> + rkyv::Archive<Archived = IDX>,
+    usize: Cast<IDX>,
+    Self: rkyv::Serialize<AllocSerializer<SCRATCH_SPACE>>,
+{
+    /// Serializes the tree with [`rkyv`] and writes it to the file at `path`, replacing
+    /// the file if it already exists.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::{KdTree, SquaredEuclidean};
+    ///
+    /// let mut tree: KdTree<f64, 3> = KdTree::new();
+    /// tree.add(&[1.0, 2.0, 5.0], 100);
+    /// tree.add(&[2.0, 3.0, 6.0], 101);
+    ///
+    /// let path = std::env::temp_dir().join("kiddo-doctest-save.rkyv");
+    /// tree.save_rkyv(&path).unwrap();
+    ///
+    /// let loaded = unsafe { KdTree::<f64, 3>::load_rkyv_mmap(&path) }.unwrap();
+    /// assert_eq!(loaded.nearest_one::<SquaredEuclidean>(&[2.0, 3.0, 6.1]).item, 101);
+    /// # drop(loaded);
+    /// # std::fs::remove_file(&path).unwrap();
+    /// ```
+    pub fn save_rkyv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        save(self, path.as_ref())
+    }
+
+    /// Memory-maps a tree from a file written by [`save_rkyv`](KdTree::save_rkyv).
+    ///
+    /// The returned [`MmapArchive`] dereferences to an
+    /// [`ArchivedKdTree`](crate::float::kdtree::ArchivedKdTree), which can be queried
+    /// in the same way as a [`KdTree`].
+    ///
+    /// # Safety
+    ///
+    /// The file must have been written by `save_rkyv` from a tree with exactly the same
+    /// type parameters, and must not be modified whilst it is mapped. The contents of the
+    /// file are not validated, and loading a file that does not meet these requirements
+    /// results in undefined behaviour.
+    pub unsafe fn load_rkyv_mmap(path: impl AsRef<Path>) -> io::Result<MmapArchive<Self>> {
+        MmapArchive::map(path.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::float::kdtree::KdTree;
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use crate::SquaredEuclidean;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn can_save_and_mmap_trees() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(6);
+        let points: Vec<[f32; 3]> = (0..5_000).map(|_| rng.gen()).collect();
+
+        let mutable: KdTree<f32, u32, 3, 32, u32> = KdTree::from(&points);
+        let immutable: ImmutableKdTree<f32, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+
+        let dir = std::env::temp_dir();
+        let mutable_path = dir.join(format!("kiddo-test-{}-mutable.rkyv", std::process::id()));
+        let immutable_path = dir.join(format!("kiddo-test-{}-immutable.rkyv", std::process::id()));
+
+        mutable.save_rkyv(&mutable_path).unwrap();
+        immutable.save_rkyv(&immutable_path).unwrap();
+
+        let loaded_mutable =
+            unsafe { KdTree::<f32, u32, 3, 32, u32>::load_rkyv_mmap(&mutable_path) }.unwrap();
+        let loaded_immutable =
+            unsafe { ImmutableKdTree::<f32, u32, 3, 32>::load_rkyv_mmap(&immutable_path) }.unwrap();
+        let aligned = loaded_immutable.aligned();
+
+        assert_eq!(loaded_mutable.size(), 5_000);
+        assert_eq!(loaded_immutable.size(), 5_000);
+
+        for _ in 0..100 {
+            let query: [f32; 3] = rng.gen();
+
+            let expected = mutable.nearest_n::<SquaredEuclidean>(&query, 5);
+            assert_eq!(
+                loaded_mutable.nearest_n::<SquaredEuclidean>(&query, 5),
+                expected
+            );
+
+            let expected = immutable.nearest_one::<SquaredEuclidean>(&query);
+            assert_eq!(
+                loaded_immutable.nearest_one::<SquaredEuclidean>(&query),
+                expected
+            );
+            assert_eq!(aligned.nearest_one::<SquaredEuclidean>(&query), expected);
+        }
+
+        drop(aligned);
+        drop(loaded_mutable);
+        drop(loaded_immutable);
+        std::fs::remove_file(&mutable_path).unwrap();
+        std::fs::remove_file(&immutable_path).unwrap();
+    }
+
+    #[test]
+    fn loading_a_missing_file_is_an_error() {
+        let path = std::env::temp_dir().join("kiddo-test-this-file-does-not-exist.rkyv");
+
+        let result = unsafe { ImmutableKdTree::<f32, u32, 3, 32>::load_rkyv_mmap(&path) };
+
+        assert_eq!(result.unwrap_err().kind(), std::io::ErrorKind::NotFound);
+    }
+}
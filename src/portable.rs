@@ -0,0 +1,781 @@
+//! A portable binary format for [`ImmutableKdTree`], readable on any platform.
+//!
+//! Archives written with `rkyv` use the native endianness and alignment of the machine that
+//! wrote them, so a tree built on an x86_64 server can't be loaded on a big-endian or
+//! 32-bit device. The format written by
+//! [`to_portable_bytes`](ImmutableKdTree::to_portable_bytes) avoids this: every value is
+//! stored little-endian, with no padding, and the same tree always produces the same bytes
+//! whatever platform it was built on. Reading it back with
+//! [`from_portable_bytes`](ImmutableKdTree::from_portable_bytes) involves a copy, so it is
+//! slower to load than a memory-mapped `rkyv` archive.
+//!
+//! Where size on disk matters more than load time,
+//! [`to_compressed_portable_bytes`](ImmutableKdTree::to_compressed_portable_bytes) writes
+//! the same format with the coordinates of the leaf entries bit-packed, which
+//! [`from_portable_bytes`](ImmutableKdTree::from_portable_bytes) also reads.
+//!
+//! # Layout
+//!
+//! The file starts with a 56 byte header:
+//!
+//! | Offset | Size | Contents                                                       |
+//! |--------|------|----------------------------------------------------------------|
+//! | 0      | 8    | The magic bytes `KIDDOIKD`                                     |
+//! | 8      | 2    | The format version, `1`, `2` if compressed, or `3` if flags are set (`u16`) |
+//! | 10     | 1    | The [type tag](PortableValue::TYPE_TAG) of the axis type `A`   |
+//! | 11     | 1    | The [type tag](PortableValue::TYPE_TAG) of the item type `T`   |
+//! | 12     | 1    | The stem ordering: `0` for Eytzinger, `1` for the layout used when the `modified_van_emde_boas` feature is enabled |
+//! | 13     | 1    | The leaf coordinate encoding: `0` for raw, `1` for packed. Always `0` in version `1` |
+//! | 14     | 1    | Flags. Bit `0` is set if the split dimensions of the stems follow them. Always `0` before version `3` |
+//! | 15     | 1    | Reserved, written as zero                                      |
+//! | 16     | 4    | `K` (`u32`)                                                    |
+//! | 20     | 4    | `B` (`u32`)                                                    |
+//! | 24     | 4    | The level of the deepest stem (`i32`)                          |
+//! | 28     | 4    | Reserved, written as zero                                      |
+//! | 32     | 8    | The number of stems, `S` (`u64`)                               |
+//! | 40     | 8    | The number of entries in the leaves, `N` (`u64`)               |
+//! | 48     | 8    | The number of leaves, `L` (`u64`)                              |
+//!
+//! It is followed by:
+//! * the `S` stem split values, each an `A`
+//! * if bit `0` of the flags is set, the `S` dimensions that the stems split on, each a
+//!   `u8`. This is only the case for trees built with a [`SplitPolicy`](crate::SplitPolicy)
+//!   other than `RoundRobin`, whose stems don't cycle through the dimensions in order
+//! * the `N` coordinates of each leaf entry on the first axis, each an `A`, then the `N`
+//!   coordinates on the second axis, and so on for all `K` axes. If they are packed, the
+//!   coordinates on each axis are split into blocks of `B` entries, each of which is
+//!   stored as:
+//!   * the smallest coordinate in the block, as an `A`
+//!   * the number of bits, `W`, that each coordinate is packed into (`u8`)
+//!   * the number of low bits, `Z`, that are zero in every difference (`u8`)
+//!   * for each coordinate, the difference between it and the smallest coordinate, shifted
+//!     right by `Z` bits and stored in `W` bits, packed into bytes starting with the least
+//!     significant bit. The differences are taken between the bits of the coordinates,
+//!     mapped to integers that sort in the same order as the coordinates, so nothing is
+//!     lost. Points in the same leaf lie close together, and coordinates with less
+//!     precision than `A` end in zero bits, so the differences usually need far fewer
+//!     bits than the coordinates
+//! * the `N` leaf entry items, each a `T`
+//! * the `L` leaf extents, each a pair of `u64`s giving the index of the first entry in
+//!   the leaf and one past the index of its last entry
+//!
+//! Entries that have been [deleted](ImmutableKdTree::delete) sit outside of every leaf's
+//! extent, and remain deleted when the tree is read back.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use kiddo::immutable::float::kdtree::ImmutableKdTree;
+//! use kiddo::SquaredEuclidean;
+//!
+//! let points: Vec<[f64; 3]> = vec![[1.0, 2.0, 5.0], [2.0, 3.0, 6.0]];
+//! let tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+//!
+//! let bytes = tree.to_portable_bytes();
+//!
+//! let loaded: ImmutableKdTree<f64, u32, 3, 32> =
+//!     ImmutableKdTree::from_portable_bytes(&bytes).unwrap();
+//!
+//! assert_eq!(loaded.nearest_one::<SquaredEuclidean>(&[1.0, 2.0, 5.1]).item, 0);
+//! ```
+use std::fmt;
+
+use aligned_vec::{AVec, CACHELINE_ALIGN};
+
+use crate::float::kdtree::Axis;
+use crate::immutable::float::kdtree::{ImmutableKdTree, LeafExtent};
+use crate::traits::Content;
+
+const MAGIC: &[u8; 8] = b"KIDDOIKD";
+const FORMAT_VERSION: u16 = 1;
+/// The version that added the leaf coordinate encoding to the header
+const ENCODED_FORMAT_VERSION: u16 = 2;
+/// The version that added the flags to the header
+const FLAGS_FORMAT_VERSION: u16 = 3;
+const HEADER_LEN: usize = 56;
+
+/// Set if the split dimensions of the stems follow them
+const FLAG_SPLIT_DIMS: u8 = 1;
+
+const LEAF_ENCODING_RAW: u8 = 0;
+const LEAF_ENCODING_PACKED: u8 = 1;
+
+#[cfg(not(feature = "modified_van_emde_boas"))]
+const STEM_ORDERING: u8 = 0;
+#[cfg(feature = "modified_van_emde_boas")]
+const STEM_ORDERING: u8 = 1;
+
+/// The reasons that bytes could not be read as a tree by
+/// [`ImmutableKdTree::from_portable_bytes`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PortableFormatError {
+    /// The bytes do not start with the magic bytes of the portable format
+    NotPortableFormat,
+    /// The bytes were written with a version of the format that this version of Kiddo
+    /// can't read
+    UnsupportedVersion {
+        /// The version of the format that the bytes were written with
+        version: u16,
+    },
+    /// The tree was written with a different axis or item type
+    TypeMismatch,
+    /// The tree was written with a different `K` or `B`
+    DimensionMismatch {
+        /// The `K` that the tree was written with
+        k: u32,
+        /// The `B` that the tree was written with
+        b: u32,
+    },
+    /// The tree was written by a build of Kiddo with the `modified_van_emde_boas`
+    /// feature set differently, and so has its stems in a different order
+    StemOrderingMismatch,
+    /// The bytes end before the tree does
+    Truncated,
+    /// The bytes describe a tree that is inconsistent, or that can't be represented
+    /// on this platform
+    Invalid {
+        /// What was wrong with the tree
+        reason: &'static str,
+    },
+}
+
+impl fmt::Display for PortableFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PortableFormatError::NotPortableFormat => {
+                write!(f, "not a tree in Kiddo's portable format")
+            }
+            PortableFormatError::UnsupportedVersion { version } => {
+                write!(
+                    f,
+                    "version {version} of the portable format is not supported"
+                )
+            }
+            PortableFormatError::TypeMismatch => {
+                write!(f, "tree was written with a different axis or item type")
+            }
+            PortableFormatError::DimensionMismatch { k, b } => {
+                write!(f, "tree was written with K = {k} and B = {b}")
+            }
+            PortableFormatError::StemOrderingMismatch => write!(
+                f,
+                "tree was written with a different stem ordering. \
+                 Check that the modified_van_emde_boas feature matches"
+            ),
+            PortableFormatError::Truncated => write!(f, "bytes end part way through the tree"),
+            PortableFormatError::Invalid { reason } => write!(f, "invalid tree: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for PortableFormatError {}
+
+/// A value that can be stored in the portable format, as the axis type or item type of
+/// a tree.
+pub trait PortableValue: Copy {
+    /// Identifies the type in the header, so that a tree can't be read back with a
+    /// different type to the one that it was written with
+    const TYPE_TAG: u8;
+
+    /// The number of bytes that each value takes up
+    const WIDTH: usize;
+
+    /// Appends the little-endian bytes of the value to `out`
+    fn write_le(self, out: &mut Vec<u8>);
+
+    /// Reads a value from exactly [`WIDTH`](PortableValue::WIDTH) little-endian bytes,
+    /// returning `None` if it can't be represented on this platform
+    fn read_le(bytes: &[u8]) -> Option<Self>;
+}
+
+macro_rules! impl_portable_value {
+    ($($ty:ty => $tag:expr),* $(,)?) => {
+        $(
+            impl PortableValue for $ty {
+                const TYPE_TAG: u8 = $tag;
+                const WIDTH: usize = core::mem::size_of::<$ty>();
+
+                fn write_le(self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn read_le(bytes: &[u8]) -> Option<Self> {
+                    Some(<$ty>::from_le_bytes(bytes.try_into().ok()?))
+                }
+            }
+        )*
+    };
+}
+
+impl_portable_value!(
+    u8 => 1,
+    u16 => 2,
+    u32 => 3,
+    u64 => 4,
+    u128 => 5,
+    f32 => 17,
+    f64 => 18,
+);
+
+#[cfg(feature = "f16")]
+impl_portable_value!(half::f16 => 16);
+
+/// `usize` items are stored as `u64`s, so that trees can be moved between platforms
+/// with different pointer widths. Reading fails if an item doesn't fit.
+impl PortableValue for usize {
+    const TYPE_TAG: u8 = u64::TYPE_TAG;
+    const WIDTH: usize = u64::WIDTH;
+
+    fn write_le(self, out: &mut Vec<u8>) {
+        (self as u64).write_le(out);
+    }
+
+    fn read_le(bytes: &[u8]) -> Option<Self> {
+        usize::try_from(u64::read_le(bytes)?).ok()
+    }
+}
+
+impl<A, T, const K: usize, const B: usize> ImmutableKdTree<A, T, K, B>
+where
+    A: Axis + PortableValue,
+    T: Content + PortableValue,
+{
+    /// Writes the tree in the portable format, which can be read back on any platform.
+    /// See the [`portable`](crate::portable) module docs for details.
+    pub fn to_portable_bytes(&self) -> Vec<u8> {
+        self.write_portable_bytes(LEAF_ENCODING_RAW)
+    }
+
+    /// Writes the tree in the portable format, as [`to_portable_bytes`](ImmutableKdTree::to_portable_bytes)
+    /// does, but with the coordinates of the leaf entries bit-packed to make it smaller.
+    ///
+    /// How much smaller depends upon the data. Coordinates that have less precision than
+    /// `A`, such as `f64`s converted from `f32`s or rounded to a grid, pack well. See the
+    /// [`portable`](crate::portable) module docs for details.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::immutable::float::kdtree::ImmutableKdTree;
+    ///
+    /// let points: Vec<[f64; 2]> = (0..1000).map(|i| [(i % 40) as f64, (i / 40) as f64]).collect();
+    /// let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+    ///
+    /// let bytes = tree.to_compressed_portable_bytes();
+    /// assert!(bytes.len() < tree.to_portable_bytes().len() / 2);
+    ///
+    /// let loaded: ImmutableKdTree<f64, u32, 2, 32> =
+    ///     ImmutableKdTree::from_portable_bytes(&bytes).unwrap();
+    /// assert_eq!(loaded, tree);
+    /// ```
+    pub fn to_compressed_portable_bytes(&self) -> Vec<u8> {
+        self.write_portable_bytes(LEAF_ENCODING_PACKED)
+    }
+
+    fn write_portable_bytes(&self, leaf_encoding: u8) -> Vec<u8> {
+        let flags = if self.split_dims.is_empty() {
+            0
+        } else {
+            FLAG_SPLIT_DIMS
+        };
+
+        let item_count = self.leaf_items.len();
+        let mut bytes = Vec::with_capacity(
+            HEADER_LEN
+                + (self.stems.len() + K * item_count) * A::WIDTH
+                + self.split_dims.len()
+                + item_count * T::WIDTH
+                + self.leaf_extents.len() * 16,
+        );
+
+        // write the oldest version that can hold the tree, so that older versions of
+        // Kiddo can still read it
+        bytes.extend_from_slice(MAGIC);
+        if flags != 0 {
+            FLAGS_FORMAT_VERSION.write_le(&mut bytes);
+        } else if leaf_encoding != LEAF_ENCODING_RAW {
+            ENCODED_FORMAT_VERSION.write_le(&mut bytes);
+        } else {
+            FORMAT_VERSION.write_le(&mut bytes);
+        }
+        bytes.extend_from_slice(&[
+            A::TYPE_TAG,
+            T::TYPE_TAG,
+            STEM_ORDERING,
+            leaf_encoding,
+            flags,
+            0,
+        ]);
+        (K as u32).write_le(&mut bytes);
+        (B as u32).write_le(&mut bytes);
+        (self.max_stem_level as u32).write_le(&mut bytes);
+        0u32.write_le(&mut bytes);
+        (self.stems.len() as u64).write_le(&mut bytes);
+        (item_count as u64).write_le(&mut bytes);
+        (self.leaf_extents.len() as u64).write_le(&mut bytes);
+
+        self.stems.iter().for_each(|&val| val.write_le(&mut bytes));
+        bytes.extend_from_slice(&self.split_dims);
+        if leaf_encoding == LEAF_ENCODING_RAW {
+            self.leaf_points
+                .iter()
+                .flatten()
+                .for_each(|&val| val.write_le(&mut bytes));
+        } else {
+            self.leaf_points
+                .iter()
+                .for_each(|column| write_packed::<A, B>(column, &mut bytes));
+        }
+        self.leaf_items
+            .iter()
+            .for_each(|&item| item.write_le(&mut bytes));
+        // a no-op cast when the u64_leaf_extents feature is enabled
+        #[allow(clippy::unnecessary_cast)]
+        self.leaf_extents.iter().for_each(|&(start, end)| {
+            (start as u64).write_le(&mut bytes);
+            (end as u64).write_le(&mut bytes);
+        });
+
+        bytes
+    }
+
+    /// Reads a tree that was written by [`to_portable_bytes`](ImmutableKdTree::to_portable_bytes),
+    /// on this or any other platform.
+    ///
+    /// The tree must have been written with the same `A`, `T`, `K` and `B`, and by a
+    /// build with the `modified_van_emde_boas` feature set the same way.
+    pub fn from_portable_bytes(bytes: &[u8]) -> Result<Self, PortableFormatError> {
+        let mut reader = Reader { bytes };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(PortableFormatError::NotPortableFormat);
+        }
+        let version: u16 = reader.value()?;
+        if !(FORMAT_VERSION..=FLAGS_FORMAT_VERSION).contains(&version) {
+            return Err(PortableFormatError::UnsupportedVersion { version });
+        }
+
+        let tags = reader.take(6)?;
+        let (axis_tag, item_tag, stem_ordering) = (tags[0], tags[1], tags[2]);
+        let leaf_encoding = if version == FORMAT_VERSION {
+            LEAF_ENCODING_RAW
+        } else {
+            tags[3]
+        };
+        let flags = if version >= FLAGS_FORMAT_VERSION {
+            tags[4]
+        } else {
+            0
+        };
+        if axis_tag != A::TYPE_TAG || item_tag != T::TYPE_TAG {
+            return Err(PortableFormatError::TypeMismatch);
+        }
+
+        let k: u32 = reader.value()?;
+        let b: u32 = reader.value()?;
+        if k as usize != K || b as usize != B {
+            return Err(PortableFormatError::DimensionMismatch { k, b });
+        }
+        if stem_ordering != STEM_ORDERING {
+            return Err(PortableFormatError::StemOrderingMismatch);
+        }
+        if leaf_encoding != LEAF_ENCODING_RAW && leaf_encoding != LEAF_ENCODING_PACKED {
+            return Err(PortableFormatError::Invalid {
+                reason: "unknown leaf coordinate encoding",
+            });
+        }
+        if flags & !FLAG_SPLIT_DIMS != 0 {
+            return Err(PortableFormatError::Invalid {
+                reason: "unknown flags",
+            });
+        }
+
+        let max_stem_level = reader.value::<u32>()? as i32;
+        reader.take(4)?;
+        let stem_count = reader.count()?;
+        let item_count = reader.count()?;
+        let leaf_count = reader.count()?;
+
+        let stems: Vec<A> = reader.values(stem_count)?;
+        let split_dims = if flags & FLAG_SPLIT_DIMS != 0 {
+            let split_dims = reader.take(stem_count)?.to_vec();
+            if split_dims.iter().any(|&dim| dim as usize >= K) {
+                return Err(PortableFormatError::Invalid {
+                    reason: "stem splits on a dimension that does not exist",
+                });
+            }
+            split_dims
+        } else {
+            Vec::new()
+        };
+        let mut leaf_points = Vec::with_capacity(K);
+        for _ in 0..K {
+            leaf_points.push(if leaf_encoding == LEAF_ENCODING_RAW {
+                reader.values::<A>(item_count)?
+            } else {
+                reader.packed_values::<A, B>(item_count)?
+            });
+        }
+        let leaf_items: Vec<T> = reader.values(item_count)?;
+        let leaf_extents = reader
+            .values::<u64>(
+                leaf_count
+                    .checked_mul(2)
+                    .ok_or(PortableFormatError::Truncated)?,
+            )?
+            .chunks_exact(2)
+            .map(|extent| {
+                let start = LeafExtent::try_from(extent[0]).ok()?;
+                let end = LeafExtent::try_from(extent[1]).ok()?;
+                (start <= end && end as usize <= item_count).then_some((start, end))
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or(PortableFormatError::Invalid {
+                reason: "leaf extent is out of bounds",
+            })?;
+
+        if !reader.bytes.is_empty() {
+            return Err(PortableFormatError::Invalid {
+                reason: "unexpected bytes after the end of the tree",
+            });
+        }
+
+        let live_count: usize = leaf_extents
+            .iter()
+            .map(|&(start, end)| (end - start) as usize)
+            .sum();
+        let mut leaf_points = leaf_points.into_iter();
+
+        Ok(ImmutableKdTree {
+            stems: AVec::from_slice(CACHELINE_ALIGN, &stems),
+            leaf_points: array_init::array_init(|_| leaf_points.next().unwrap()),
+            leaf_items,
+            leaf_extents,
+            max_stem_level,
+            tombstone_count: item_count.saturating_sub(live_count),
+            split_dims,
+        })
+    }
+}
+
+/// Reads values from the front of a byte slice, advancing past them
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], PortableFormatError> {
+        if self.bytes.len() < len {
+            return Err(PortableFormatError::Truncated);
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn value<V: PortableValue>(&mut self) -> Result<V, PortableFormatError> {
+        V::read_le(self.take(V::WIDTH)?).ok_or(PortableFormatError::Invalid {
+            reason: "value can't be represented on this platform",
+        })
+    }
+
+    /// Reads a `u64` count, checking that it fits in a `usize`
+    fn count(&mut self) -> Result<usize, PortableFormatError> {
+        usize::try_from(self.value::<u64>()?).map_err(|_| PortableFormatError::Truncated)
+    }
+
+    fn values<V: PortableValue>(&mut self, count: usize) -> Result<Vec<V>, PortableFormatError> {
+        // check the length before allocating, so that a corrupt count can't cause a
+        // huge allocation
+        let len = count
+            .checked_mul(V::WIDTH)
+            .ok_or(PortableFormatError::Truncated)?;
+        self.take(len)?
+            .chunks_exact(V::WIDTH)
+            .map(|bytes| {
+                V::read_le(bytes).ok_or(PortableFormatError::Invalid {
+                    reason: "value can't be represented on this platform",
+                })
+            })
+            .collect()
+    }
+
+    /// Reads `count` values written by [`write_packed`]
+    fn packed_values<V: PortableValue, const B: usize>(
+        &mut self,
+        count: usize,
+    ) -> Result<Vec<V>, PortableFormatError> {
+        let invalid = PortableFormatError::Invalid {
+            reason: "packed coordinates are corrupt",
+        };
+
+        // every block takes up some bytes, whatever its width, so capping the initial
+        // allocation by the bytes left stops a corrupt count from causing a huge one
+        let mut values = Vec::with_capacity(count.min(self.bytes.len()));
+        let mut remaining = count;
+        while remaining > 0 {
+            let len = remaining.min(B.max(1));
+            remaining -= len;
+
+            let base = to_ordered_bits::<V>(self.take(V::WIDTH)?);
+            let width = self.take(1)?[0] as usize;
+            let shift = self.take(1)?[0] as u32;
+            if width > V::WIDTH * 8 || shift >= u64::BITS {
+                return Err(invalid);
+            }
+            let packed = self.take((len * width).div_ceil(8))?;
+
+            let mut acc: u128 = 0;
+            let mut acc_bits = 0;
+            let mut packed = packed.iter();
+            for _ in 0..len {
+                while acc_bits < width {
+                    acc |= (*packed.next().ok_or(invalid)? as u128) << acc_bits;
+                    acc_bits += 8;
+                }
+                let delta = (acc & ((1u128 << width) - 1)) as u64;
+                acc >>= width;
+                acc_bits -= width;
+
+                let shifted = delta << shift;
+                if shifted >> shift != delta {
+                    return Err(invalid);
+                }
+                let ordered = base.checked_add(shifted).ok_or(invalid)?;
+                values.push(
+                    from_ordered_bits::<V>(ordered)
+                        .and_then(|bytes| V::read_le(&bytes[..V::WIDTH]))
+                        .ok_or(invalid)?,
+                );
+            }
+        }
+
+        Ok(values)
+    }
+}
+
+/// Writes `column` in blocks of `B` values, each packed into as few bits as the range
+/// of the values in the block allows. See the [`portable`](crate::portable) module docs.
+fn write_packed<V: PortableValue, const B: usize>(column: &[V], out: &mut Vec<u8>) {
+    let mut raw = Vec::with_capacity(V::WIDTH * B.max(1));
+
+    for block in column.chunks(B.max(1)) {
+        raw.clear();
+        block.iter().for_each(|&val| val.write_le(&mut raw));
+        let ordered: Vec<u64> = raw
+            .chunks_exact(V::WIDTH)
+            .map(to_ordered_bits::<V>)
+            .collect();
+
+        let base = *ordered.iter().min().unwrap();
+        let shift = ordered
+            .iter()
+            .fold(0, |zero_bits, &val| zero_bits | (val - base))
+            .trailing_zeros()
+            % u64::BITS;
+        let max_delta = (ordered.iter().max().unwrap() - base) >> shift;
+        let width = (u64::BITS - max_delta.leading_zeros()) as usize;
+
+        from_ordered_bits::<V>(base)
+            .map(|bytes| out.extend_from_slice(&bytes[..V::WIDTH]))
+            .unwrap();
+        out.push(width as u8);
+        out.push(shift as u8);
+
+        let mut acc: u128 = 0;
+        let mut acc_bits = 0;
+        for &val in &ordered {
+            acc |= (((val - base) >> shift) as u128) << acc_bits;
+            acc_bits += width;
+            while acc_bits >= 8 {
+                out.push(acc as u8);
+                acc >>= 8;
+                acc_bits -= 8;
+            }
+        }
+        if acc_bits > 0 {
+            out.push(acc as u8);
+        }
+    }
+}
+
+/// Maps the little-endian bytes of a float to an integer that sorts in the same order
+/// as the float does
+fn to_ordered_bits<V: PortableValue>(bytes: &[u8]) -> u64 {
+    let mut buf = [0u8; 8];
+    buf[..bytes.len()].copy_from_slice(bytes);
+    let bits = u64::from_le_bytes(buf);
+
+    let sign = 1u64 << (V::WIDTH * 8 - 1);
+    if bits & sign != 0 {
+        !bits & width_mask::<V>()
+    } else {
+        bits | sign
+    }
+}
+
+/// Reverses [`to_ordered_bits`], returning `None` if `ordered` has more bits than `V`
+fn from_ordered_bits<V: PortableValue>(ordered: u64) -> Option<[u8; 8]> {
+    if ordered & !width_mask::<V>() != 0 {
+        return None;
+    }
+
+    let sign = 1u64 << (V::WIDTH * 8 - 1);
+    let bits = if ordered & sign != 0 {
+        ordered & !sign
+    } else {
+        !ordered & width_mask::<V>()
+    };
+
+    Some(bits.to_le_bytes())
+}
+
+fn width_mask<V: PortableValue>() -> u64 {
+    u64::MAX >> (64 - V::WIDTH * 8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PortableFormatError;
+    use crate::float::distance::SquaredEuclidean;
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use crate::split_policy::SplitPolicy;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn immutable_kdtree_round_trips() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(77);
+
+        let points: Vec<[f32; 3]> = (0..2_000).map(|_| rng.gen()).collect();
+        let mut tree: ImmutableKdTree<f32, u64, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+        tree.delete(10);
+
+        let bytes = tree.to_portable_bytes();
+        let loaded: ImmutableKdTree<f32, u64, 3, 32> =
+            ImmutableKdTree::from_portable_bytes(&bytes).unwrap();
+
+        assert_eq!(loaded, tree);
+        assert_eq!(loaded.size(), 1_999);
+        for _ in 0..20 {
+            let query = rng.gen::<[f32; 3]>();
+            assert_eq!(
+                loaded.nearest_one::<SquaredEuclidean>(&query),
+                tree.nearest_one::<SquaredEuclidean>(&query)
+            );
+        }
+    }
+
+    #[test]
+    fn compressed_trees_round_trip() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(78);
+
+        // a mix of signs, and coordinates with less precision than an f64, which pack well
+        let points: Vec<[f64; 3]> = (0..2_000)
+            .map(|_| rng.gen::<[f32; 3]>().map(|val| (val as f64) * 2.0 - 1.0))
+            .collect();
+        let mut tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+        tree.delete(10);
+
+        let bytes = tree.to_compressed_portable_bytes();
+        assert_eq!(&bytes[8..10], &[2, 0]);
+        assert_eq!(bytes[13], 1);
+        assert!(bytes.len() < tree.to_portable_bytes().len() * 3 / 4);
+
+        let loaded: ImmutableKdTree<f64, u32, 3, 32> =
+            ImmutableKdTree::from_portable_bytes(&bytes).unwrap();
+        assert_eq!(loaded, tree);
+
+        // including values at the extremes of the axis type
+        let extremes: Vec<[f32; 2]> = vec![
+            [f32::MIN, f32::MAX],
+            [-0.0, 0.0],
+            [f32::MIN_POSITIVE, -f32::MIN_POSITIVE],
+        ];
+        let tree: ImmutableKdTree<f32, u32, 2, 32> = ImmutableKdTree::new_from_slice(&extremes);
+        let loaded: ImmutableKdTree<f32, u32, 2, 32> =
+            ImmutableKdTree::from_portable_bytes(&tree.to_compressed_portable_bytes()).unwrap();
+        assert_eq!(loaded, tree);
+
+        let truncated = &bytes[..bytes.len() - 200];
+        assert_eq!(
+            ImmutableKdTree::<f64, u32, 3, 32>::from_portable_bytes(truncated),
+            Err(PortableFormatError::Truncated)
+        );
+    }
+
+    #[test]
+    fn trees_built_with_max_spread_round_trip() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(79);
+
+        let points: Vec<[f64; 3]> = (0..2_000)
+            .map(|_| [rng.gen_range(0.0..100.0), rng.gen_range(0.0..1.0), 0.0])
+            .collect();
+        let tree: ImmutableKdTree<f64, u32, 3, 32> =
+            ImmutableKdTree::new_from_slice_with_policy(&points, SplitPolicy::MaxSpread);
+
+        for bytes in [
+            tree.to_portable_bytes(),
+            tree.to_compressed_portable_bytes(),
+        ] {
+            assert_eq!(&bytes[8..10], &[3, 0]);
+            assert_eq!(bytes[14], 1);
+
+            let loaded: ImmutableKdTree<f64, u32, 3, 32> =
+                ImmutableKdTree::from_portable_bytes(&bytes).unwrap();
+            assert_eq!(loaded, tree);
+        }
+
+        let mut bytes = tree.to_portable_bytes();
+        let first_split_dim = 56 + tree.stems.len() * 8;
+        bytes[first_split_dim] = 3;
+        assert!(matches!(
+            ImmutableKdTree::<f64, u32, 3, 32>::from_portable_bytes(&bytes),
+            Err(PortableFormatError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn header_is_little_endian() {
+        let points: Vec<[f64; 2]> = vec![[1.0, 2.0], [3.0, 4.0]];
+        let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+        let bytes = tree.to_portable_bytes();
+
+        assert_eq!(&bytes[..8], b"KIDDOIKD");
+        assert_eq!(&bytes[8..10], &[1, 0]);
+        assert_eq!(&bytes[16..24], &[2, 0, 0, 0, 32, 0, 0, 0]);
+        assert_eq!(&bytes[40..48], &[2, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(bytes.len(), 56 + 4 * 8 + 2 * 4 + 16);
+    }
+
+    #[test]
+    fn rejects_mismatched_or_damaged_trees() {
+        let points: Vec<[f64; 2]> = (0..100).map(|i| [i as f64, (i * 7 % 100) as f64]).collect();
+        let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+        let bytes = tree.to_portable_bytes();
+
+        assert_eq!(
+            ImmutableKdTree::<f64, u32, 2, 16>::from_portable_bytes(&bytes),
+            Err(PortableFormatError::DimensionMismatch { k: 2, b: 32 })
+        );
+        assert_eq!(
+            ImmutableKdTree::<f32, u32, 2, 32>::from_portable_bytes(&bytes),
+            Err(PortableFormatError::TypeMismatch)
+        );
+        assert_eq!(
+            ImmutableKdTree::<f64, u32, 2, 32>::from_portable_bytes(&bytes[..bytes.len() - 1]),
+            Err(PortableFormatError::Truncated)
+        );
+        assert_eq!(
+            ImmutableKdTree::<f64, u32, 2, 32>::from_portable_bytes(&bytes[1..]),
+            Err(PortableFormatError::NotPortableFormat)
+        );
+
+        let mut huge_count = bytes.clone();
+        huge_count[40..48].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            ImmutableKdTree::<f64, u32, 2, 32>::from_portable_bytes(&huge_count),
+            Err(PortableFormatError::Truncated)
+        );
+    }
+}
@@ -0,0 +1,563 @@
+//! A single entry point for the nearest-neighbour and radius queries of the float trees.
+//!
+//! Rather than picking the right one of `nearest_n`, `nearest_n_within`, `within` and
+//! `within_unsorted`, start a query with `tree.query(&point)`, describe the results that
+//! you want, and call [`run`](`QueryBuilder::run`):
+//!
+//! ```rust
+//! use kiddo::{ImmutableKdTree, Manhattan};
+//!
+//! let points = vec![[1.0f64, 2.0, 5.0], [2.0, 3.0, 6.0], [10.0, 10.0, 10.0]];
+//! let tree: ImmutableKdTree<f64, 3> = ImmutableKdTree::new_from_slice(&points);
+//!
+//! let nearest = tree
+//!     .query(&[1.0, 2.0, 5.1])
+//!     .metric::<Manhattan>()
+//!     .k(2)
+//!     .max_dist(5.0)
+//!     .run();
+//!
+//! assert_eq!(nearest.len(), 2);
+//! assert_eq!(nearest[0].item, 0);
+//! ```
+//!
+//! The builder dispatches to the same query methods that can be called directly, so there
+//! is no cost to using it.
+use az::Cast;
+use std::marker::PhantomData;
+use std::num::NonZero;
+
+use crate::float::distance::SquaredEuclidean;
+use crate::float::kdtree::{Axis, KdTree};
+use crate::float::result_collection::{sort_breaking_ties_by_item, TieBrokenResults};
+use crate::float_leaf_slice::leaf_slice::{LeafSliceFloat, LeafSliceFloatChunk};
+use crate::immutable::float::kdtree::ImmutableKdTree;
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{Content, DistanceMetric, Index, Native, QueryMetric, StaticMetric};
+
+/// Trees that can be queried with a [`QueryBuilder`].
+pub trait Queryable<A: Axis, T: Content, const K: usize> {
+    /// Finds up to `max_qty` elements (or all of them, if `None`) within `max_dist` of
+    /// `query`, sorted by distance if `sorted` is true. If `break_ties_by_item` is true,
+    /// elements at the same distance are chosen and sorted in order of their items.
+    fn run_query<D>(
+        &self,
+        query: &[A; K],
+        max_qty: Option<NonZero<usize>>,
+        max_dist: A,
+        sorted: bool,
+        break_ties_by_item: bool,
+    ) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>;
+}
+
+/// Describes a query against a tree. Created by calling `query` on a
+/// [`KdTree`](`crate::float::kdtree::KdTree`) or an
+/// [`ImmutableKdTree`](`crate::immutable::float::kdtree::ImmutableKdTree`).
+///
+/// By default, every element is returned, sorted by [`SquaredEuclidean`] distance.
+#[derive(Debug)]
+#[must_use = "queries do nothing until `run` is called"]
+pub struct QueryBuilder<'a, Q, A, T, const K: usize, D = Native<SquaredEuclidean>> {
+    tree: &'a Q,
+    query: &'a [A; K],
+    max_qty: Option<usize>,
+    max_dist: A,
+    sorted: bool,
+    break_ties_by_item: bool,
+    _phantom: PhantomData<(T, D)>,
+}
+
+impl<'a, Q, A, T, const K: usize> QueryBuilder<'a, Q, A, T, K>
+where
+    Q: Queryable<A, T, K>,
+    A: Axis,
+    T: Content,
+{
+    pub(crate) fn new(tree: &'a Q, query: &'a [A; K]) -> Self {
+        QueryBuilder {
+            tree,
+            query,
+            max_qty: None,
+            max_dist: A::infinity(),
+            sorted: true,
+            break_ties_by_item: false,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<'a, Q, A, T, const K: usize, D> QueryBuilder<'a, Q, A, T, K, D>
+where
+    Q: Queryable<A, T, K>,
+    A: Axis,
+    T: Content,
+    D: QueryMetric<A, K>,
+{
+    /// Measures distances using `D2` rather than [`SquaredEuclidean`].
+    pub fn metric<D2: DistanceMetric<A, K>>(self) -> QueryBuilder<'a, Q, A, T, K, Native<D2>> {
+        self.query_metric::<Native<D2>>()
+    }
+
+    /// Measures distances using the [`QueryMetric`] `M` rather than [`SquaredEuclidean`],
+    /// such as [`Euclidean`](`crate::float::distance::Euclidean`), which reports distances
+    /// in different units to those of the metric that it searches with.
+    pub fn query_metric<M: QueryMetric<A, K>>(self) -> QueryBuilder<'a, Q, A, T, K, M> {
+        QueryBuilder {
+            tree: self.tree,
+            query: self.query,
+            max_qty: self.max_qty,
+            max_dist: self.max_dist,
+            sorted: self.sorted,
+            break_ties_by_item: self.break_ties_by_item,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Returns at most the `qty` nearest elements.
+    pub fn k(mut self, qty: usize) -> Self {
+        self.max_qty = Some(qty);
+        self
+    }
+
+    /// Only returns elements that are less than `dist` from the query point.
+    /// `dist` is in the units of the metric, e.g. squared distance for [`SquaredEuclidean`],
+    /// or unsquared distance for [`Euclidean`](`crate::float::distance::Euclidean`).
+    pub fn max_dist(mut self, dist: A) -> Self {
+        self.max_dist = dist;
+        self
+    }
+
+    /// Whether the results should be sorted by distance. Defaults to `true`.
+    /// Queries that return many results are faster when unsorted.
+    pub fn sorted(mut self, sorted: bool) -> Self {
+        self.sorted = sorted;
+        self
+    }
+
+    /// Makes the results deterministic when several elements are at exactly the same
+    /// distance from the query point, by preferring the elements with the lowest items.
+    ///
+    /// Without this, which of a set of tied elements are returned when `k` cuts through
+    /// them, and the order in which tied elements are returned, depend on the shape of
+    /// the tree and can differ between tree types and between versions of this crate.
+    /// With it, results are always sorted by distance and then by item, which is useful
+    /// for comparing results against stored expected outputs. It makes queries slightly
+    /// slower.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, 2> = KdTree::new();
+    /// tree.add(&[1.0, 0.0], 30);
+    /// tree.add(&[0.0, 1.0], 10);
+    /// tree.add(&[-1.0, 0.0], 20);
+    ///
+    /// let nearest = tree.query(&[0.0, 0.0]).k(2).break_ties_by_item().run();
+    ///
+    /// assert_eq!(nearest.iter().map(|n| n.item).collect::<Vec<_>>(), vec![10, 20]);
+    /// ```
+    pub fn break_ties_by_item(mut self) -> Self {
+        self.break_ties_by_item = true;
+        self
+    }
+
+    /// Performs the query.
+    pub fn run(self) -> Vec<NearestNeighbour<A, T>> {
+        let max_qty = match self.max_qty {
+            Some(qty) => match NonZero::new(qty) {
+                Some(qty) => Some(qty),
+                None => return Vec::new(),
+            },
+            None => None,
+        };
+
+        let mut results = self.tree.run_query::<D::Search>(
+            self.query,
+            max_qty,
+            D::to_search_units(self.max_dist),
+            self.sorted,
+            self.break_ties_by_item,
+        );
+        for result in results.iter_mut() {
+            result.distance = D::from_search_units(result.distance);
+        }
+
+        results
+    }
+}
+
+impl<A, T, const K: usize, const B: usize, IDX> Queryable<A, T, K> for KdTree<A, T, K, B, IDX>
+where
+    A: Axis,
+    T: Content,
+    IDX: Index<T = IDX>,
+    usize: Cast<IDX>,
+{
+    fn run_query<D>(
+        &self,
+        query: &[A; K],
+        max_qty: Option<NonZero<usize>>,
+        max_dist: A,
+        sorted: bool,
+        break_ties_by_item: bool,
+    ) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        match max_qty {
+            Some(max_qty) if break_ties_by_item => self.nearest_n_within_stub(
+                &StaticMetric::<D>::new(),
+                query,
+                max_dist,
+                TieBrokenResults::new(max_qty.get()),
+                true,
+            ),
+            None if break_ties_by_item => {
+                let mut results = self.within::<D>(query, max_dist);
+                sort_breaking_ties_by_item(&mut results);
+                results
+            }
+            Some(max_qty) if max_dist == A::infinity() && sorted => {
+                self.nearest_n::<D>(query, max_qty.get())
+            }
+            Some(max_qty) => self.nearest_n_within::<D>(query, max_dist, max_qty, sorted),
+            None if sorted => self.within::<D>(query, max_dist),
+            None => self.within_unsorted::<D>(query, max_dist),
+        }
+    }
+}
+
+impl<A, T, const K: usize, const B: usize, IDX> KdTree<A, T, K, B, IDX>
+where
+    A: Axis,
+    T: Content,
+    IDX: Index<T = IDX>,
+    usize: Cast<IDX>,
+{
+    /// Starts building a query for the elements nearest to `query`.
+    /// See [`QueryBuilder`] for the available options.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::KdTree;
+    ///
+    /// let mut tree: KdTree<f64, 3> = KdTree::new();
+    /// tree.add(&[1.0, 2.0, 5.0], 100);
+    /// tree.add(&[2.0, 3.0, 6.0], 101);
+    ///
+    /// let within = tree.query(&[1.0, 2.0, 5.1]).max_dist(1.0).sorted(false).run();
+    ///
+    /// assert_eq!(within.len(), 1);
+    /// assert_eq!(within[0].item, 100);
+    /// ```
+    #[inline]
+    pub fn query<'a>(&'a self, query: &'a [A; K]) -> QueryBuilder<'a, Self, A, T, K> {
+        QueryBuilder::new(self, query)
+    }
+}
+
+impl<A, T, const K: usize, const B: usize> Queryable<A, T, K> for ImmutableKdTree<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    usize: Cast<T>,
+{
+    fn run_query<D>(
+        &self,
+        query: &[A; K],
+        max_qty: Option<NonZero<usize>>,
+        max_dist: A,
+        sorted: bool,
+        break_ties_by_item: bool,
+    ) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        match max_qty {
+            Some(max_qty) if break_ties_by_item => self.nearest_n_within_stub::<D, _>(
+                query,
+                max_dist,
+                TieBrokenResults::new(max_qty.get()),
+                true,
+            ),
+            None if break_ties_by_item => {
+                let mut results = self.within::<D>(query, max_dist);
+                sort_breaking_ties_by_item(&mut results);
+                results
+            }
+            Some(max_qty) => self.nearest_n_within::<D>(query, max_dist, max_qty, sorted),
+            None if sorted => self.within::<D>(query, max_dist),
+            None => self.within_unsorted::<D>(query, max_dist),
+        }
+    }
+}
+
+impl<A, T, const K: usize, const B: usize> ImmutableKdTree<A, T, K, B>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    usize: Cast<T>,
+{
+    /// Starts building a query for the elements nearest to `query`.
+    /// See [`QueryBuilder`] for the available options.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use kiddo::ImmutableKdTree;
+    ///
+    /// let points = vec![[1.0f64, 2.0, 5.0], [2.0, 3.0, 6.0]];
+    /// let tree: ImmutableKdTree<f64, 3> = ImmutableKdTree::new_from_slice(&points);
+    ///
+    /// let nearest = tree.query(&[1.0, 2.0, 5.1]).k(1).run();
+    ///
+    /// assert_eq!(nearest.len(), 1);
+    /// assert_eq!(nearest[0].item, 0);
+    /// ```
+    #[inline]
+    pub fn query<'a>(&'a self, query: &'a [A; K]) -> QueryBuilder<'a, Self, A, T, K> {
+        QueryBuilder::new(self, query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::float::distance::{Euclidean, Manhattan, SquaredEuclidean};
+    use crate::float::kdtree::KdTree;
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use crate::nearest_neighbour::NearestNeighbour;
+    use crate::traits::DistanceMetric;
+    use rand::{Rng, SeedableRng};
+    use std::num::NonZero;
+
+    fn sorted_by_item(mut results: Vec<NearestNeighbour<f64, u32>>) -> Vec<(f64, u32)> {
+        results.sort_by_key(|n| n.item);
+        results.into_iter().map(|n| (n.distance, n.item)).collect()
+    }
+
+    #[test]
+    fn breaking_ties_by_item_gives_the_same_results_from_every_tree() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(61);
+
+        // points on a lattice are at many identical distances from each other, and are
+        // shuffled so that items don't follow the order of the points
+        let mut points: Vec<[f64; 2]> = (0..400)
+            .map(|idx| [(idx % 20) as f64, (idx / 20) as f64])
+            .collect();
+        for idx in (1..points.len()).rev() {
+            points.swap(idx, rng.gen_range(0..=idx));
+        }
+
+        let immutable: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+        let mut mutable: KdTree<f64, u32, 2, 32, u32> = KdTree::new();
+        points
+            .iter()
+            .enumerate()
+            .for_each(|(idx, point)| mutable.add(point, idx as u32));
+
+        for query in [[0.0, 0.0], [7.0, 12.0], [9.5, 9.5], [19.0, 3.5]] {
+            let mut expected: Vec<NearestNeighbour<f64, u32>> = points
+                .iter()
+                .enumerate()
+                .map(|(idx, point)| NearestNeighbour {
+                    distance: SquaredEuclidean::dist(&query, point),
+                    item: idx as u32,
+                })
+                .collect();
+            expected.sort_by(|a, b| a.cmp(b).then(a.item.cmp(&b.item)));
+
+            for k in [1, 3, 6, 11, 30] {
+                let expected = &expected[..k];
+
+                assert_eq!(
+                    mutable.query(&query).k(k).break_ties_by_item().run(),
+                    expected
+                );
+                assert_eq!(
+                    immutable.query(&query).k(k).break_ties_by_item().run(),
+                    expected
+                );
+            }
+
+            let expected: Vec<_> = expected.into_iter().filter(|n| n.distance < 10.5).collect();
+            assert_eq!(
+                mutable
+                    .query(&query)
+                    .max_dist(10.5)
+                    .break_ties_by_item()
+                    .run(),
+                expected
+            );
+            assert_eq!(
+                immutable
+                    .query(&query)
+                    .max_dist(10.5)
+                    .break_ties_by_item()
+                    .run(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn builder_matches_direct_queries() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(5);
+
+        let points: Vec<[f64; 3]> = (0..2_000).map(|_| rng.gen()).collect();
+
+        let immutable: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+        let mut mutable: KdTree<f64, u32, 3, 32, u32> = KdTree::new();
+        points
+            .iter()
+            .enumerate()
+            .for_each(|(idx, point)| mutable.add(point, idx as u32));
+
+        let max_qty = NonZero::new(10).unwrap();
+
+        for _ in 0..50 {
+            let query: [f64; 3] = rng.gen();
+
+            for tree_results in [
+                [
+                    mutable.query(&query).k(10).run(),
+                    mutable.nearest_n::<SquaredEuclidean>(&query, 10),
+                ],
+                [
+                    immutable.query(&query).k(10).run(),
+                    immutable.nearest_n::<SquaredEuclidean>(&query, max_qty),
+                ],
+                [
+                    mutable.query(&query).max_dist(0.01).run(),
+                    mutable.within::<SquaredEuclidean>(&query, 0.01),
+                ],
+                [
+                    immutable.query(&query).max_dist(0.01).run(),
+                    immutable.within::<SquaredEuclidean>(&query, 0.01),
+                ],
+                [
+                    mutable
+                        .query(&query)
+                        .metric::<Manhattan>()
+                        .k(10)
+                        .max_dist(0.2)
+                        .run(),
+                    mutable.nearest_n_within::<Manhattan>(&query, 0.2, max_qty, true),
+                ],
+                [
+                    immutable
+                        .query(&query)
+                        .metric::<Manhattan>()
+                        .k(10)
+                        .max_dist(0.2)
+                        .run(),
+                    immutable.nearest_n_within::<Manhattan>(&query, 0.2, max_qty, true),
+                ],
+            ] {
+                let [built, direct] = tree_results;
+                assert_eq!(built, direct);
+            }
+
+            // unsorted results can come back in any order
+            for tree_results in [
+                [
+                    mutable.query(&query).max_dist(0.01).sorted(false).run(),
+                    mutable.within_unsorted::<SquaredEuclidean>(&query, 0.01),
+                ],
+                [
+                    immutable.query(&query).max_dist(0.01).sorted(false).run(),
+                    immutable.within_unsorted::<SquaredEuclidean>(&query, 0.01),
+                ],
+                [
+                    mutable
+                        .query(&query)
+                        .k(5)
+                        .max_dist(0.01)
+                        .sorted(false)
+                        .run(),
+                    mutable
+                        .within::<SquaredEuclidean>(&query, 0.01)
+                        .into_iter()
+                        .take(5)
+                        .collect(),
+                ],
+                [
+                    immutable
+                        .query(&query)
+                        .k(5)
+                        .max_dist(0.01)
+                        .sorted(false)
+                        .run(),
+                    immutable
+                        .within::<SquaredEuclidean>(&query, 0.01)
+                        .into_iter()
+                        .take(5)
+                        .collect(),
+                ],
+            ] {
+                let [built, direct] = tree_results;
+                assert_eq!(sorted_by_item(built), sorted_by_item(direct));
+            }
+        }
+    }
+
+    #[test]
+    fn builder_defaults_to_every_element_sorted() {
+        let points: Vec<[f64; 2]> = vec![[0.0, 0.0], [3.0, 0.0], [1.0, 0.0], [2.0, 0.0]];
+        let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+
+        let results = tree.query(&[0.0, 0.0]).run();
+        let items: Vec<_> = results.iter().map(|n| n.item).collect();
+        assert_eq!(items, vec![0, 2, 3, 1]);
+        assert_eq!(
+            results[3].distance,
+            SquaredEuclidean::dist(&[0.0, 0.0], &[3.0, 0.0])
+        );
+
+        assert!(tree.query(&[0.0, 0.0]).k(0).run().is_empty());
+    }
+
+    #[test]
+    fn euclidean_reports_unsquared_distances() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(93);
+        let points: Vec<[f64; 3]> = (0..2_000).map(|_| rng.gen()).collect();
+        let tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+
+        for _ in 0..50 {
+            let query: [f64; 3] = rng.gen();
+
+            let expected: Vec<_> = tree
+                .within::<SquaredEuclidean>(&query, 0.1 * 0.1)
+                .into_iter()
+                .map(|n| NearestNeighbour {
+                    distance: n.distance.sqrt(),
+                    item: n.item,
+                })
+                .collect();
+            let within = tree
+                .query(&query)
+                .query_metric::<Euclidean>()
+                .max_dist(0.1)
+                .run();
+            assert_eq!(within, expected);
+
+            let nearest = tree.query(&query).query_metric::<Euclidean>().k(3).run();
+            for result in nearest {
+                let point = points[result.item as usize];
+                let dist = SquaredEuclidean::dist(&query, &point).sqrt();
+                assert_eq!(result.distance, dist);
+            }
+        }
+
+        let none = tree
+            .query(&[0.5, 0.5, 0.5])
+            .query_metric::<Euclidean>()
+            .max_dist(-1.0)
+            .run();
+        assert!(none.is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+//! How co-ordinates are rounded when a tree is converted to a less precise float type
+
+/// Decides how each co-ordinate that can't be represented exactly is rounded when
+/// converting a tree to a less precise float type, such as with
+/// [`ImmutableKdTree::to_f32`](`crate::immutable::float::kdtree::ImmutableKdTree::to_f32`).
+///
+/// Every mode preserves the order of the co-ordinates, so the converted tree can keep the
+/// structure of the original without being rebuilt.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Rounding {
+    /// Round to the nearest representable value, with ties going to the one with an even
+    /// least significant bit, as `as` casts do
+    #[default]
+    Nearest,
+    /// Round towards negative infinity, so that no co-ordinate increases
+    Down,
+    /// Round towards positive infinity, so that no co-ordinate decreases
+    Up,
+}
+
+#[cfg(feature = "std")]
+impl Rounding {
+    /// Converts `val` to an `f32`, rounding as directed. Returns `None` if `val` is finite
+    /// but would become infinite.
+    pub(crate) fn f64_to_f32(self, val: f64) -> Option<f32> {
+        let mut rounded = val as f32;
+
+        match self {
+            Rounding::Nearest => {}
+            Rounding::Down if (rounded as f64) > val => rounded = next_down(rounded),
+            Rounding::Up if (rounded as f64) < val => rounded = next_up(rounded),
+            Rounding::Down | Rounding::Up => {}
+        }
+
+        (rounded.is_finite() || !val.is_finite()).then_some(rounded)
+    }
+}
+
+/// The greatest `f32` that is less than `val`, which must not be NaN or negative infinity
+#[cfg(feature = "std")]
+fn next_down(val: f32) -> f32 {
+    if val == 0.0 {
+        -f32::from_bits(1)
+    } else if val > 0.0 {
+        f32::from_bits(val.to_bits() - 1)
+    } else {
+        f32::from_bits(val.to_bits() + 1)
+    }
+}
+
+/// The least `f32` that is greater than `val`, which must not be NaN or positive infinity
+#[cfg(feature = "std")]
+fn next_up(val: f32) -> f32 {
+    if val == 0.0 {
+        f32::from_bits(1)
+    } else if val > 0.0 {
+        f32::from_bits(val.to_bits() + 1)
+    } else {
+        f32::from_bits(val.to_bits() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rounding;
+
+    #[test]
+    fn rounds_in_the_requested_direction() {
+        let val = 0.1f64;
+        let nearest = Rounding::Nearest.f64_to_f32(val).unwrap();
+        let down = Rounding::Down.f64_to_f32(val).unwrap();
+        let up = Rounding::Up.f64_to_f32(val).unwrap();
+
+        assert_eq!(nearest, 0.1f32);
+        assert!((down as f64) <= val && val <= (up as f64));
+        assert!(down < up);
+
+        let negative = Rounding::Down.f64_to_f32(-val).unwrap();
+        assert!((negative as f64) <= -val);
+
+        // values that are exactly representable are left as they are
+        assert_eq!(Rounding::Down.f64_to_f32(0.5), Some(0.5));
+        assert_eq!(Rounding::Up.f64_to_f32(-0.5), Some(-0.5));
+    }
+
+    #[test]
+    fn rejects_values_that_overflow() {
+        assert_eq!(Rounding::Nearest.f64_to_f32(1e300), None);
+        assert_eq!(Rounding::Up.f64_to_f32(1e300), None);
+        assert_eq!(Rounding::Down.f64_to_f32(1e300), Some(f32::MAX));
+        assert_eq!(
+            Rounding::Nearest.f64_to_f32(f64::INFINITY),
+            Some(f32::INFINITY)
+        );
+    }
+}
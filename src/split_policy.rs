@@ -0,0 +1,17 @@
+//! How the dimension that each stem of an immutable tree splits on is chosen
+
+/// Decides which dimension each stem node of an
+/// [`ImmutableKdTree`](`crate::immutable::float::kdtree::ImmutableKdTree`) splits its
+/// points on, when it is built with
+/// [`new_from_slice_with_policy`](`crate::immutable::float::kdtree::ImmutableKdTree::new_from_slice_with_policy`).
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum SplitPolicy {
+    /// Cycle through the dimensions in order, one per level of the tree, as `new_from_slice`
+    /// does. Cheapest to build and to query, and best for points spread evenly on every axis
+    #[default]
+    RoundRobin,
+    /// Split each stem on the dimension along which its points have the greatest range.
+    /// Suits anisotropic data, such as geographic points that cover a narrow range of
+    /// latitudes
+    MaxSpread,
+}
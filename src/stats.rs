@@ -0,0 +1,100 @@
+//! Counts of the work done by individual queries.
+//!
+//! Requires the `stats` feature.
+use crate::traits::QueryStatsRecorder;
+
+/// The work done by a single query, as returned by the `*_with_stats` query methods.
+///
+/// Comparing these counts across different bucket sizes, or against the size of the
+/// tree, shows how effectively a query is being pruned.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::{KdTree, SquaredEuclidean};
+///
+/// let mut tree: KdTree<f64, 2> = KdTree::new();
+/// for i in 0..1000 {
+///     tree.add(&[(i * 37 % 1000) as f64, i as f64], i);
+/// }
+///
+/// let (nearest, stats) = tree.nearest_one_with_stats::<SquaredEuclidean>(&[37.0, 1.0]);
+///
+/// assert_eq!(nearest.item, 1);
+/// assert!(stats.leaves_scanned >= 1);
+/// assert!(stats.points_compared < 1000);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueryStats {
+    /// The number of stem nodes that the query descended through
+    pub stems_visited: usize,
+
+    /// The number of leaves whose contents were scanned
+    pub leaves_scanned: usize,
+
+    /// The number of points whose distance from the query was calculated
+    pub points_compared: usize,
+}
+
+impl QueryStatsRecorder for QueryStats {
+    #[inline]
+    fn visit_stem(&mut self) {
+        self.stems_visited += 1;
+    }
+
+    #[inline]
+    fn scan_leaf(&mut self, points: usize) {
+        self.leaves_scanned += 1;
+        self.points_compared += points;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ImmutableKdTree, KdTree, SquaredEuclidean};
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn counts_the_work_done_by_nearest_one() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(5);
+        let points: Vec<[f64; 3]> = (0..10_000).map(|_| rng.gen()).collect();
+
+        let mutable: KdTree<f64, 3> = KdTree::from(&points);
+        let immutable: ImmutableKdTree<f64, 3> = ImmutableKdTree::new_from_slice(&points);
+
+        for _ in 0..100 {
+            let query: [f64; 3] = rng.gen();
+
+            let (nearest, stats) = mutable.nearest_one_with_stats::<SquaredEuclidean>(&query);
+            assert_eq!(nearest, mutable.nearest_one::<SquaredEuclidean>(&query));
+            assert!(stats.stems_visited > 0);
+            assert!(stats.leaves_scanned > 0);
+            assert!(stats.points_compared <= stats.leaves_scanned * 32);
+            assert!(stats.points_compared < points.len() / 10);
+
+            let (nearest, stats) = immutable.nearest_one_with_stats::<SquaredEuclidean>(&query);
+            assert_eq!(nearest, immutable.nearest_one::<SquaredEuclidean>(&query));
+            assert!(stats.stems_visited > 0);
+            assert!(stats.leaves_scanned > 0);
+            assert!(stats.points_compared <= stats.leaves_scanned * 32);
+            assert!(stats.points_compared < points.len() / 10);
+        }
+    }
+
+    #[test]
+    fn trees_with_a_single_leaf_scan_it_and_nothing_else() {
+        let points = [[1.0f64, 2.0], [3.0, 4.0], [5.0, 6.0]];
+
+        let mutable: KdTree<f64, 2> = KdTree::from(&points.to_vec());
+        let (_, stats) = mutable.nearest_one_with_stats::<SquaredEuclidean>(&[0.0, 0.0]);
+        assert_eq!(stats.stems_visited, 0);
+        assert_eq!(stats.leaves_scanned, 1);
+        assert_eq!(stats.points_compared, 3);
+
+        let immutable: ImmutableKdTree<f64, 2> = ImmutableKdTree::new_from_slice(&points);
+        let (_, stats) = immutable.nearest_one_with_stats::<SquaredEuclidean>(&[0.0, 0.0]);
+        assert_eq!(stats.stems_visited, 0);
+        assert_eq!(stats.leaves_scanned, 1);
+        assert_eq!(stats.points_compared, 3);
+    }
+}
@@ -1,8 +1,14 @@
 //! Definitions and implementations for some traits that are common between the [`float`](crate::float), [`immutable`](crate::immutable) and [`fixed`](crate::fixed)  modules
+use alloc::vec::Vec;
 use az::Cast;
+use core::fmt::Debug;
+use core::marker::PhantomData;
+use core::num::NonZero;
 use divrem::DivCeil;
 use num_traits::{One, PrimInt, Unsigned, Zero};
-use std::fmt::Debug;
+
+use crate::best_neighbour::BestNeighbour;
+use crate::nearest_neighbour::NearestNeighbour;
 
 /// Content trait.
 ///
@@ -14,7 +20,7 @@ use std::fmt::Debug;
 /// a Vec, and try switching tqo a smaller type and benchmarking to see if you get better
 /// performance.
 pub trait Content:
-    Zero + One + PartialEq + Default + Clone + Copy + Ord + Debug + std::ops::SubAssign + Sync + Send
+    Zero + One + PartialEq + Default + Clone + Copy + Ord + Debug + core::ops::SubAssign + Sync + Send
 {
 }
 impl<
@@ -26,7 +32,7 @@ impl<
             + Copy
             + Ord
             + Debug
-            + std::ops::SubAssign
+            + core::ops::SubAssign
             + Sync
             + Send,
     > Content for T
@@ -121,12 +127,205 @@ pub trait DistanceMetric<A, const K: usize> {
     /// to extend the min acceptable distance for a node when recursing
     /// back up the tree)
     fn dist1(a: A, b: A) -> A;
+
+    /// Set to `true` for metrics, such as Chebyshev, where the distance between two
+    /// points is the largest of the per-axis distances returned by [`dist1`](DistanceMetric::dist1)
+    /// rather than their sum.
+    const MAX_OF_AXES: bool = false;
+}
+
+/// A metric that can be chosen with
+/// [`QueryBuilder::query_metric`](`crate::query_builder::QueryBuilder::query_metric`),
+/// which searches the tree with a [`DistanceMetric`] but reports distances in other units.
+///
+/// For example, [`Euclidean`](`crate::float::distance::Euclidean`) searches with the cheaper
+/// [`SquaredEuclidean`](`crate::float::distance::SquaredEuclidean`), and takes the square
+/// root of the distances that it reports. [`Native`] reports the distances of any
+/// [`DistanceMetric`] unchanged.
+pub trait QueryMetric<A, const K: usize> {
+    /// The metric that the tree is searched with
+    type Search: DistanceMetric<A, K>;
+
+    /// Converts a distance in the units reported by this metric into the units of
+    /// [`Search`](QueryMetric::Search). Must preserve the order of distances.
+    fn to_search_units(dist: A) -> A;
+
+    /// Converts a distance in the units of [`Search`](QueryMetric::Search) into the units
+    /// reported by this metric. Must preserve the order of distances.
+    fn from_search_units(dist: A) -> A;
+}
+
+/// A [`QueryMetric`] that searches with `D` and reports distances in its units.
+pub struct Native<D>(PhantomData<fn() -> D>);
+
+impl<A, const K: usize, D: DistanceMetric<A, K>> QueryMetric<A, K> for Native<D> {
+    type Search = D;
+
+    #[inline(always)]
+    fn to_search_units(dist: A) -> A {
+        dist
+    }
+
+    #[inline(always)]
+    fn from_search_units(dist: A) -> A {
+        dist
+    }
+}
+
+/// Trait that needs to be implemented by distance metrics that carry
+/// runtime state, such as per-axis weights, for use within the
+/// `*_with_metric` query methods.
+///
+/// Unlike [`DistanceMetric`], the methods take `&self`, and [`dist1`](StatefulDistanceMetric::dist1)
+/// is told which axis the distance is being measured along.
+pub trait StatefulDistanceMetric<A, const K: usize> {
+    /// returns the distance between two K-d points, as measured
+    /// by this distance metric
+    fn dist(&self, a: &[A; K], b: &[A; K]) -> A;
+
+    /// returns the distance between two points along axis `dim`,
+    /// as measured by this distance metric.
+    ///
+    /// (used by the NN query implementations to extend the min acceptable
+    /// distance for a node when recursing back up the tree. It must never
+    /// return more than the contribution of axis `dim` to [`dist`](StatefulDistanceMetric::dist),
+    /// otherwise parts of the tree containing valid results could be pruned)
+    fn dist1(&self, a: A, b: A, dim: usize) -> A;
+
+    /// Set to `true` for metrics, such as Chebyshev, where the distance between two
+    /// points is the largest of the per-axis distances returned by [`dist1`](StatefulDistanceMetric::dist1)
+    /// rather than their sum.
+    const MAX_OF_AXES: bool = false;
+}
+
+/// Adapts a stateless [`DistanceMetric`] so that it can be used wherever a
+/// [`StatefulDistanceMetric`] is expected. Holds no `D`, so is always `Send` and `Sync`.
+pub(crate) struct StaticMetric<D>(PhantomData<fn() -> D>);
+
+impl<D> StaticMetric<D> {
+    #[inline]
+    pub(crate) const fn new() -> Self {
+        StaticMetric(PhantomData)
+    }
+}
+
+impl<A, const K: usize, D: DistanceMetric<A, K>> StatefulDistanceMetric<A, K> for StaticMetric<D> {
+    #[inline]
+    fn dist(&self, a: &[A; K], b: &[A; K]) -> A {
+        D::dist(a, b)
+    }
+
+    #[inline]
+    fn dist1(&self, a: A, b: A, _dim: usize) -> A {
+        D::dist1(a, b)
+    }
+
+    const MAX_OF_AXES: bool = D::MAX_OF_AXES;
+}
+
+/// The queries that are common to [`KdTree`](crate::float::kdtree::KdTree),
+/// [`ImmutableKdTree`](crate::immutable::float::kdtree::ImmutableKdTree) and the
+/// archived forms of `ImmutableKdTree`, so that code that queries a tree can be written
+/// once and used with a tree that was built in memory or one that was loaded with `rkyv`.
+///
+/// The methods behave the same as the inherent methods of the same names.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::num::NonZero;
+/// use kiddo::{ImmutableKdTree, KdTree, SquaredEuclidean};
+/// use kiddo::traits::KdTreeQuery;
+///
+/// fn nearest_item<Q: KdTreeQuery<f64, u64, 3>>(tree: &Q, query: &[f64; 3]) -> u64 {
+///     tree.nearest_one::<SquaredEuclidean>(query).item
+/// }
+///
+/// let points = vec![[1.0, 2.0, 5.0], [2.0, 3.0, 6.0]];
+///
+/// let immutable: ImmutableKdTree<f64, 3> = ImmutableKdTree::new_from_slice(&points);
+/// let mut mutable: KdTree<f64, 3> = KdTree::new();
+/// mutable.add(&points[0], 0);
+/// mutable.add(&points[1], 1);
+///
+/// assert_eq!(nearest_item(&immutable, &[2.0, 3.0, 5.9]), 1);
+/// assert_eq!(nearest_item(&mutable, &[2.0, 3.0, 5.9]), 1);
+/// ```
+pub trait KdTreeQuery<A, T, const K: usize> {
+    /// Finds the nearest element to `query`, using the specified distance metric.
+    fn nearest_one<D>(&self, query: &[A; K]) -> NearestNeighbour<A, T>
+    where
+        D: DistanceMetric<A, K>;
+
+    /// Finds the nearest `max_qty` elements to `query`, sorted by distance.
+    fn nearest_n<D>(&self, query: &[A; K], max_qty: NonZero<usize>) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>;
+
+    /// Finds all elements within `dist` of `query`, sorted by distance.
+    fn within<D>(&self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>;
+
+    /// Finds the "best" `max_qty` elements within `dist` of `query`, in arbitrary order.
+    fn best_n_within<D>(
+        &self,
+        query: &[A; K],
+        dist: A,
+        max_qty: NonZero<usize>,
+    ) -> impl Iterator<Item = BestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>;
+}
+
+/// Combines a running distance with `delta`, the distance along one further axis:
+/// by taking the larger of the two for metrics where [`DistanceMetric::MAX_OF_AXES`]
+/// is set, or with `add` otherwise.
+#[inline(always)]
+pub(crate) fn accumulate_dist<A: PartialOrd>(
+    acc: A,
+    delta: A,
+    max_of_axes: bool,
+    add: impl FnOnce(A, A) -> A,
+) -> A {
+    if max_of_axes {
+        if delta > acc {
+            delta
+        } else {
+            acc
+        }
+    } else {
+        add(acc, delta)
+    }
+}
+
+/// Receives counts of the work done by a query as it traverses a tree.
+///
+/// Queries that are not being instrumented record into `()`, which discards them.
+pub(crate) trait QueryStatsRecorder {
+    /// Called once for each stem node that the query descends through
+    fn visit_stem(&mut self);
+
+    /// Called once for each leaf that is scanned, with the number of points in it
+    fn scan_leaf(&mut self, points: usize);
+}
+
+impl QueryStatsRecorder for () {
+    #[inline(always)]
+    fn visit_stem(&mut self) {}
+
+    #[inline(always)]
+    fn scan_leaf(&mut self, _points: usize) {}
 }
 
 #[cfg(test)]
 mod tests {
 
-    use crate::traits::Index;
+    use crate::float::distance::SquaredEuclidean;
+    use crate::float::kdtree::KdTree;
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use crate::traits::{Index, KdTreeQuery};
+    use std::num::NonZero;
 
     #[test]
     fn test_u16() {
@@ -158,4 +357,45 @@ mod tests {
             (u32::MAX - u32::MAX.overflowing_shr(1).0).saturating_mul(bucket_size);
         assert_eq!(capacity_with_bucket_size, u32::MAX);
     }
+
+    fn query_all<Q: KdTreeQuery<f64, u32, 2>>(tree: &Q, query: &[f64; 2]) -> Vec<(f64, u32)> {
+        let max_qty = NonZero::new(5).unwrap();
+
+        let mut results = vec![tree.nearest_one::<SquaredEuclidean>(query)];
+        results.extend(tree.nearest_n::<SquaredEuclidean>(query, max_qty));
+        results.extend(tree.within::<SquaredEuclidean>(query, 20.0));
+
+        let mut best: Vec<_> = tree
+            .best_n_within::<SquaredEuclidean>(query, 20.0, max_qty)
+            .map(|n| (n.distance, n.item))
+            .collect();
+        best.sort_by_key(|&(_, item)| item);
+
+        results
+            .into_iter()
+            .map(|n| (n.distance, n.item))
+            .chain(best)
+            .collect()
+    }
+
+    #[test]
+    fn kd_tree_query_gives_the_same_results_from_every_tree() {
+        let points: Vec<[f64; 2]> = (0..200)
+            .map(|idx| {
+                let idx = idx as f64;
+                [(idx * 0.618).fract() * 23.0, (idx * 0.414).fract() * 29.0]
+            })
+            .collect();
+
+        let immutable: ImmutableKdTree<f64, u32, 2, 8> = ImmutableKdTree::new_from_slice(&points);
+        let mut mutable: KdTree<f64, u32, 2, 8, u32> = KdTree::new();
+        points
+            .iter()
+            .enumerate()
+            .for_each(|(idx, point)| mutable.add(point, idx as u32));
+
+        for query in [[0.0, 0.0], [11.3, 14.2], [22.0, 3.7]] {
+            assert_eq!(query_all(&mutable, &query), query_all(&immutable, &query));
+        }
+    }
 }
@@ -0,0 +1,359 @@
+//! Querying a tree in a different frame of reference to the one its points were stored in.
+//!
+//! A tree built from points in one frame, such as a sensor's, can be queried with points in
+//! another, such as the world frame, by wrapping it in a [`TransformedTree`] along with the
+//! [`AffineTransform`] that maps the second frame into the first. Queries are transformed
+//! on the way in, so the tree never needs to be rebuilt when the transform changes.
+
+use std::num::NonZero;
+
+use az::Cast;
+
+use crate::best_neighbour::BestNeighbour;
+use crate::float::kdtree::{Axis, KdTree};
+use crate::float_leaf_slice::leaf_slice::{LeafSliceFloat, LeafSliceFloatChunk};
+use crate::immutable::float::kdtree::ImmutableKdTree;
+use crate::nearest_neighbour::{NearestNeighbour, NearestNeighbourWithPoint};
+use crate::traits::{Content, DistanceMetric, Index, KdTreeQuery};
+
+/// An affine transform of `K`-dimensional points: a linear map, such as a rotation or a
+/// scaling, followed by a translation.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::transformed::AffineTransform;
+///
+/// // a quarter turn anticlockwise, then a shift of 10 along x
+/// let transform = AffineTransform::new([[0.0, -1.0], [1.0, 0.0]], [10.0, 0.0]);
+///
+/// assert_eq!(transform.apply(&[1.0, 0.0]), [10.0, 1.0]);
+/// assert_eq!(transform.inverse().unwrap().apply(&[10.0, 1.0]), [1.0, 0.0]);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AffineTransform<A, const K: usize> {
+    matrix: [[A; K]; K],
+    translation: [A; K],
+}
+
+impl<A: Axis, const K: usize> AffineTransform<A, K> {
+    /// Creates a transform that multiplies a point by `matrix`, given as an array of rows,
+    /// and then adds `translation` to it.
+    pub fn new(matrix: [[A; K]; K], translation: [A; K]) -> Self {
+        AffineTransform {
+            matrix,
+            translation,
+        }
+    }
+
+    /// Creates a transform that leaves every point where it is
+    pub fn identity() -> Self {
+        Self::new(Self::identity_matrix(), [A::zero(); K])
+    }
+
+    /// Creates a transform that adds `translation` to every point
+    pub fn translation(translation: [A; K]) -> Self {
+        Self::new(Self::identity_matrix(), translation)
+    }
+
+    fn identity_matrix() -> [[A; K]; K] {
+        std::array::from_fn(|row| {
+            std::array::from_fn(|col| if row == col { A::one() } else { A::zero() })
+        })
+    }
+
+    /// Returns the result of applying the transform to `point`
+    #[inline]
+    pub fn apply(&self, point: &[A; K]) -> [A; K] {
+        std::array::from_fn(|row| {
+            self.matrix[row]
+                .iter()
+                .zip(point.iter())
+                .fold(self.translation[row], |acc, (&m, &p)| acc + m * p)
+        })
+    }
+
+    /// Returns the transform that undoes this one, or `None` if its matrix is singular,
+    /// as is the case for a projection onto fewer dimensions.
+    pub fn inverse(&self) -> Option<Self> {
+        // Gauss-Jordan elimination, with partial pivoting, of [matrix | identity]
+        let mut left = self.matrix;
+        let mut right = Self::identity_matrix();
+
+        for col in 0..K {
+            let pivot_row = (col..K).max_by(|&a, &b| {
+                left[a][col]
+                    .abs()
+                    .partial_cmp(&left[b][col].abs())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })?;
+            let pivot = left[pivot_row][col];
+            if pivot == A::zero() || !pivot.is_finite() {
+                return None;
+            }
+            left.swap(col, pivot_row);
+            right.swap(col, pivot_row);
+
+            for val in left[col].iter_mut().chain(right[col].iter_mut()) {
+                *val = *val / pivot;
+            }
+
+            for row in 0..K {
+                let factor = left[row][col];
+                if row == col || factor == A::zero() {
+                    continue;
+                }
+                for idx in 0..K {
+                    left[row][idx] = left[row][idx] - factor * left[col][idx];
+                    right[row][idx] = right[row][idx] - factor * right[col][idx];
+                }
+            }
+        }
+
+        // the inverse of x -> Mx + t is y -> M⁻¹y - M⁻¹t
+        let inverse = Self::new(right, [A::zero(); K]);
+        let shift = inverse.apply(&self.translation);
+
+        Some(Self::new(right, shift.map(|val| -val)))
+    }
+}
+
+/// A tree that is queried with points in a different frame of reference to the one that
+/// its points were stored in.
+///
+/// Every query point is passed through `to_tree`, the transform from the frame of the
+/// queries into the frame of the tree, before the wrapped tree is queried. Distances, both
+/// those passed in and those returned, are measured in the frame of the tree. They are the
+/// same in both frames when the transform only rotates and translates, but are scaled
+/// along with the points otherwise.
+///
+/// Wraps any tree that implements [`KdTreeQuery`], and implements it too. Points returned
+/// by [`nearest_one_point`](TransformedTree::nearest_one_point) are passed back through the
+/// inverse of the transform, into the frame of the queries.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::transformed::{AffineTransform, TransformedTree};
+/// use kiddo::traits::KdTreeQuery;
+/// use kiddo::{ImmutableKdTree, SquaredEuclidean};
+///
+/// // points seen by a sensor that sits at (100, 50) in the world
+/// let sensor_points = vec![[1.0, 0.0], [0.0, 2.0]];
+/// let tree: ImmutableKdTree<f64, 2> = ImmutableKdTree::new_from_slice(&sensor_points);
+///
+/// let world_to_sensor = AffineTransform::translation([-100.0, -50.0]);
+/// let transformed = TransformedTree::new(tree, world_to_sensor);
+///
+/// let nearest = transformed.nearest_one_point::<SquaredEuclidean>(&[100.1, 52.0]);
+///
+/// assert_eq!(nearest.item, 1);
+/// assert_eq!(nearest.point, [100.0, 52.0]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransformedTree<Q, A, const K: usize> {
+    tree: Q,
+    to_tree: AffineTransform<A, K>,
+    from_tree: AffineTransform<A, K>,
+}
+
+impl<Q, A: Axis, const K: usize> TransformedTree<Q, A, K> {
+    /// Wraps `tree`, whose points are in a frame that `to_tree` maps query points into.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `to_tree` cannot be inverted.
+    pub fn new(tree: Q, to_tree: AffineTransform<A, K>) -> Self {
+        let from_tree = to_tree.inverse().expect("the transform must be invertible");
+
+        TransformedTree {
+            tree,
+            to_tree,
+            from_tree,
+        }
+    }
+
+    /// Returns the wrapped tree
+    pub fn tree(&self) -> &Q {
+        &self.tree
+    }
+
+    /// Returns the transform from the frame of the queries into the frame of the tree
+    pub fn transform(&self) -> &AffineTransform<A, K> {
+        &self.to_tree
+    }
+
+    /// Replaces the transform from the frame of the queries into the frame of the tree,
+    /// such as when the sensor that the tree's points came from has moved.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `to_tree` cannot be inverted.
+    pub fn set_transform(&mut self, to_tree: AffineTransform<A, K>) {
+        self.from_tree = to_tree.inverse().expect("the transform must be invertible");
+        self.to_tree = to_tree;
+    }
+
+    /// Unwraps the tree
+    pub fn into_inner(self) -> Q {
+        self.tree
+    }
+
+    /// Maps a point in the frame of the queries into the frame of the tree
+    #[inline]
+    pub fn to_tree_frame(&self, point: &[A; K]) -> [A; K] {
+        self.to_tree.apply(point)
+    }
+
+    /// Maps a point in the frame of the tree into the frame of the queries
+    #[inline]
+    pub fn from_tree_frame(&self, point: &[A; K]) -> [A; K] {
+        self.from_tree.apply(point)
+    }
+
+    fn with_point_from_tree<T>(
+        &self,
+        mut nearest: NearestNeighbourWithPoint<A, T, K>,
+    ) -> NearestNeighbourWithPoint<A, T, K> {
+        nearest.point = self.from_tree_frame(&nearest.point);
+        nearest
+    }
+}
+
+impl<A, T, const K: usize, const B: usize> TransformedTree<ImmutableKdTree<A, T, K, B>, A, K>
+where
+    A: Axis + LeafSliceFloat<T> + LeafSliceFloatChunk<T, K>,
+    T: Content,
+    usize: Cast<T>,
+{
+    /// Finds the nearest element to `query`, returning the coordinates that it was stored
+    /// at, mapped into the frame of the queries, alongside its distance and item.
+    pub fn nearest_one_point<D>(&self, query: &[A; K]) -> NearestNeighbourWithPoint<A, T, K>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.with_point_from_tree(self.tree.nearest_one_point::<D>(&self.to_tree_frame(query)))
+    }
+}
+
+impl<A, T, const K: usize, const B: usize, IDX> TransformedTree<KdTree<A, T, K, B, IDX>, A, K>
+where
+    A: Axis,
+    T: Content,
+    IDX: Index<T = IDX>,
+    usize: Cast<IDX>,
+{
+    /// Finds the nearest element to `query`, returning the coordinates that it was stored
+    /// at, mapped into the frame of the queries, alongside its distance and item.
+    pub fn nearest_one_point<D>(&self, query: &[A; K]) -> NearestNeighbourWithPoint<A, T, K>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.with_point_from_tree(self.tree.nearest_one_point::<D>(&self.to_tree_frame(query)))
+    }
+}
+
+impl<Q, A, T, const K: usize> KdTreeQuery<A, T, K> for TransformedTree<Q, A, K>
+where
+    Q: KdTreeQuery<A, T, K>,
+    A: Axis,
+{
+    #[inline]
+    fn nearest_one<D>(&self, query: &[A; K]) -> NearestNeighbour<A, T>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.tree.nearest_one::<D>(&self.to_tree_frame(query))
+    }
+
+    #[inline]
+    fn nearest_n<D>(&self, query: &[A; K], max_qty: NonZero<usize>) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.tree
+            .nearest_n::<D>(&self.to_tree_frame(query), max_qty)
+    }
+
+    #[inline]
+    fn within<D>(&self, query: &[A; K], dist: A) -> Vec<NearestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        self.tree.within::<D>(&self.to_tree_frame(query), dist)
+    }
+
+    #[inline]
+    fn best_n_within<D>(
+        &self,
+        query: &[A; K],
+        dist: A,
+        max_qty: NonZero<usize>,
+    ) -> impl Iterator<Item = BestNeighbour<A, T>>
+    where
+        D: DistanceMetric<A, K>,
+    {
+        // the results can't borrow the transformed query, which is dropped on returning
+        let best: Vec<_> = self
+            .tree
+            .best_n_within::<D>(&self.to_tree_frame(query), dist, max_qty)
+            .collect();
+
+        best.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AffineTransform, TransformedTree};
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use crate::traits::KdTreeQuery;
+    use crate::SquaredEuclidean;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn inverse_undoes_the_transform() {
+        let transform: AffineTransform<f64, 3> = AffineTransform::new(
+            [[2.0, 1.0, 0.0], [0.0, 3.0, -1.0], [1.0, 0.0, 1.0]],
+            [5.0, -2.0, 0.5],
+        );
+        let inverse = transform.inverse().unwrap();
+
+        let point = [0.3, -7.0, 12.5];
+        let round_trip = inverse.apply(&transform.apply(&point));
+        for dim in 0..3 {
+            assert!((round_trip[dim] - point[dim]).abs() < 1e-12);
+        }
+
+        let singular = AffineTransform::new([[1.0, 2.0], [2.0, 4.0]], [0.0, 0.0]);
+        assert!(singular.inverse().is_none());
+    }
+
+    #[test]
+    fn queries_in_the_world_frame_match_queries_in_the_tree_frame() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(61);
+        let points: Vec<[f64; 2]> = (0..2_000).map(|_| rng.gen()).collect();
+        let tree: ImmutableKdTree<f64, u32, 2, 32> = ImmutableKdTree::new_from_slice(&points);
+
+        // a rotation by 30 degrees and a translation, which preserve distances
+        let (sin, cos) = 30f64.to_radians().sin_cos();
+        let to_tree = AffineTransform::new([[cos, -sin], [sin, cos]], [0.25, -0.5]);
+        let from_tree = to_tree.inverse().unwrap();
+        let transformed = TransformedTree::new(tree.clone(), to_tree);
+
+        for _ in 0..100 {
+            let tree_query: [f64; 2] = rng.gen();
+            let world_query = from_tree.apply(&tree_query);
+
+            assert_eq!(
+                transformed.within::<SquaredEuclidean>(&world_query, 0.01),
+                tree.within::<SquaredEuclidean>(&transformed.to_tree_frame(&world_query), 0.01)
+            );
+
+            let nearest = transformed.nearest_one_point::<SquaredEuclidean>(&world_query);
+            let expected = from_tree.apply(&points[nearest.item as usize]);
+            assert!((nearest.point[0] - expected[0]).abs() < 1e-12);
+            assert!((nearest.point[1] - expected[1]).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! Summary statistics describing the shape of a tree, as returned by the
+//! `generate_stats` method of each tree type.
+
+/// Describes how a tree's items are spread across its leaves, and how deep it is.
+///
+/// Useful for capacity planning, and for tests that check that a tree stays
+/// reasonably balanced as items are added and removed.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::KdTree;
+///
+/// let mut tree: KdTree<f64, 2> = KdTree::new();
+/// for i in 0..1000 {
+///     tree.add(&[(i * 37 % 1000) as f64, i as f64], i);
+/// }
+///
+/// let stats = tree.generate_stats();
+///
+/// assert_eq!(stats.size, 1000);
+/// assert_eq!(stats.leaf_count, tree.leaf_count());
+/// assert!(stats.max_leaf_size <= 32);
+/// assert!(stats.average_leaf_occupancy > 0.25 && stats.average_leaf_occupancy <= 1.0);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TreeStats {
+    /// The number of items stored in the tree
+    pub size: usize,
+
+    /// The number of leaves in the tree, including any that are empty
+    pub leaf_count: usize,
+
+    /// The number of leaves that hold no items
+    pub empty_leaf_count: usize,
+
+    /// The number of items in the emptiest leaf of the tree
+    pub min_leaf_size: usize,
+
+    /// The number of items in the fullest leaf of the tree
+    pub max_leaf_size: usize,
+
+    /// The number of stems between the root and the deepest leaf. Trees with a single
+    /// leaf have a depth of zero
+    pub max_depth: usize,
+
+    /// The mean number of items per leaf, as a fraction of the bucket size
+    pub average_leaf_occupancy: f64,
+}
+
+impl TreeStats {
+    pub(crate) fn from_leaf_sizes(
+        leaf_sizes: impl Iterator<Item = usize>,
+        max_depth: usize,
+        bucket_size: usize,
+    ) -> Self {
+        let mut stats = TreeStats {
+            min_leaf_size: usize::MAX,
+            max_depth,
+            ..Default::default()
+        };
+
+        for leaf_size in leaf_sizes {
+            stats.size += leaf_size;
+            stats.leaf_count += 1;
+            if leaf_size == 0 {
+                stats.empty_leaf_count += 1;
+            }
+            stats.min_leaf_size = stats.min_leaf_size.min(leaf_size);
+            stats.max_leaf_size = stats.max_leaf_size.max(leaf_size);
+        }
+
+        if stats.leaf_count == 0 {
+            stats.min_leaf_size = 0;
+        } else {
+            stats.average_leaf_occupancy =
+                stats.size as f64 / (stats.leaf_count * bucket_size) as f64;
+        }
+
+        stats
+    }
+}
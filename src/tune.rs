@@ -0,0 +1,158 @@
+//! Helps to choose a bucket size by timing queries against trees built with several.
+//!
+//! The best bucket size depends on the number of dimensions, the distribution of the
+//! points and queries, and the machine, so it is best found by measurement.
+//! [`recommend_bucket_size`] builds an [`ImmutableKdTree`] from a sample of the points for
+//! each of [`CANDIDATE_BUCKET_SIZES`], times nearest neighbour queries on each, and
+//! recommends the fastest.
+//!
+//! The sample should be representative of the real data, and large enough for the trees
+//! built from it to have many leaves: a few hundred thousand points is usually plenty.
+//! Build with optimizations enabled, or the timings will be meaningless.
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use crate::float::distance::SquaredEuclidean;
+use crate::float::kdtree::Axis;
+use crate::float_leaf_slice::leaf_slice::{LeafSliceFloat, LeafSliceFloatChunk};
+use crate::immutable::float::kdtree::ImmutableKdTree;
+
+/// The bucket sizes that [`recommend_bucket_size`] compares
+pub const CANDIDATE_BUCKET_SIZES: [usize; 6] = [8, 16, 32, 64, 128, 256];
+
+/// The number of times that each candidate's queries are timed, keeping the fastest run,
+/// to reduce the effect of noise from elsewhere on the machine
+const TIMING_RUNS: usize = 3;
+
+/// The result of [`recommend_bucket_size`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct BucketSizeRecommendation {
+    /// The candidate bucket size whose queries were fastest
+    pub bucket_size: usize,
+
+    /// Each candidate bucket size, along with the time that it took to run all of the
+    /// sample queries, in the order of [`CANDIDATE_BUCKET_SIZES`]
+    pub timings: Vec<(usize, Duration)>,
+}
+
+/// Recommends a bucket size for an [`ImmutableKdTree`] holding points like those in
+/// `sample_points`, by timing [`nearest_one`](ImmutableKdTree::nearest_one) queries for
+/// `sample_queries` against trees built with each of [`CANDIDATE_BUCKET_SIZES`].
+///
+/// Queries use [`SquaredEuclidean`] distances. The recommendation is usually also a good
+/// choice for other metrics and query types, and for the mutable
+/// [`KdTree`](`crate::float::kdtree::KdTree`).
+///
+/// # Panics
+///
+/// Panics if `sample_queries` is empty.
+///
+/// # Examples
+///
+/// ```rust
+/// use kiddo::tune::{recommend_bucket_size, CANDIDATE_BUCKET_SIZES};
+///
+/// let points: Vec<[f64; 3]> = (0..10_000).map(|_| rand::random()).collect();
+/// let queries: Vec<[f64; 3]> = (0..1_000).map(|_| rand::random()).collect();
+///
+/// let recommendation = recommend_bucket_size::<f64, 3>(&points, &queries);
+///
+/// assert!(CANDIDATE_BUCKET_SIZES.contains(&recommendation.bucket_size));
+/// ```
+pub fn recommend_bucket_size<A, const K: usize>(
+    sample_points: &[[A; K]],
+    sample_queries: &[[A; K]],
+) -> BucketSizeRecommendation
+where
+    A: Axis + LeafSliceFloat<u32> + LeafSliceFloatChunk<u32, K>,
+{
+    assert!(
+        !sample_queries.is_empty(),
+        "at least one sample query is needed to time"
+    );
+
+    let timings = vec![
+        (8, time_queries::<A, K, 8>(sample_points, sample_queries)),
+        (16, time_queries::<A, K, 16>(sample_points, sample_queries)),
+        (32, time_queries::<A, K, 32>(sample_points, sample_queries)),
+        (64, time_queries::<A, K, 64>(sample_points, sample_queries)),
+        (
+            128,
+            time_queries::<A, K, 128>(sample_points, sample_queries),
+        ),
+        (
+            256,
+            time_queries::<A, K, 256>(sample_points, sample_queries),
+        ),
+    ];
+    debug_assert!(timings
+        .iter()
+        .map(|&(bucket_size, _)| bucket_size)
+        .eq(CANDIDATE_BUCKET_SIZES));
+
+    let bucket_size = timings
+        .iter()
+        .min_by_key(|&&(_, duration)| duration)
+        .map(|&(bucket_size, _)| bucket_size)
+        .unwrap();
+
+    BucketSizeRecommendation {
+        bucket_size,
+        timings,
+    }
+}
+
+fn time_queries<A, const K: usize, const B: usize>(
+    sample_points: &[[A; K]],
+    sample_queries: &[[A; K]],
+) -> Duration
+where
+    A: Axis + LeafSliceFloat<u32> + LeafSliceFloatChunk<u32, K>,
+{
+    let tree: ImmutableKdTree<A, u32, K, B> = ImmutableKdTree::new_from_slice(sample_points);
+
+    (0..TIMING_RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            for query in sample_queries {
+                black_box(tree.nearest_one::<SquaredEuclidean>(black_box(query)));
+            }
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{recommend_bucket_size, CANDIDATE_BUCKET_SIZES};
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn recommends_one_of_the_candidates() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(90);
+        let points: Vec<[f32; 2]> = (0..5_000).map(|_| rng.gen()).collect();
+        let queries: Vec<[f32; 2]> = (0..100).map(|_| rng.gen()).collect();
+
+        let recommendation = recommend_bucket_size::<f32, 2>(&points, &queries);
+
+        assert!(CANDIDATE_BUCKET_SIZES.contains(&recommendation.bucket_size));
+        assert_eq!(recommendation.timings.len(), CANDIDATE_BUCKET_SIZES.len());
+
+        let fastest = recommendation
+            .timings
+            .iter()
+            .map(|&(_, duration)| duration)
+            .min()
+            .unwrap();
+        assert!(recommendation
+            .timings
+            .contains(&(recommendation.bucket_size, fastest)));
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one sample query is needed to time")]
+    fn panics_without_queries() {
+        recommend_bucket_size::<f32, 2>(&[[0.0, 0.0]], &[]);
+    }
+}
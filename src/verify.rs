@@ -0,0 +1,458 @@
+//! Brute-force verification of query results.
+//!
+//! Only compiled when the `verify_results` feature is enabled. A sampled subset of query calls
+//! (one in every [`DEFAULT_SAMPLE_INTERVAL`] by default, configurable at runtime via the
+//! `KIDDO_VERIFY_SAMPLE_INTERVAL` environment variable) re-run the query as a linear scan over
+//! every point in the tree and compare the outcome with what the tree returned.
+//!
+//! Any discrepancy is logged at `error` level along with everything needed to reproduce it
+//! (the query, its parameters, the distance metric, both result sets and, for trees of up to
+//! [`MAX_LOGGED_POINTS`] items, the full contents of the tree), before panicking.
+//!
+//! Comparisons are made on distances rather than items so that ties between equidistant
+//! points do not cause false positives. Points lying exactly on the radius of a radius-bounded
+//! query may legitimately be either included or excluded.
+use crate::best_neighbour::BestNeighbour;
+use crate::float_leaf_slice::leaf_slice::LeafSliceFloat;
+use crate::iter::{IterableTreeData, TreeIter};
+use crate::nearest_neighbour::NearestNeighbour;
+use crate::traits::{accumulate_dist, Content, DistanceMetric, StatefulDistanceMetric};
+use num_traits::Zero;
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::OnceLock;
+
+/// Verify one in every `DEFAULT_SAMPLE_INTERVAL` queries unless overridden
+/// by the `KIDDO_VERIFY_SAMPLE_INTERVAL` environment variable.
+pub(crate) const DEFAULT_SAMPLE_INTERVAL: usize = 16;
+
+/// Trees larger than this have their contents logged at `debug` rather than `error` level
+pub(crate) const MAX_LOGGED_POINTS: usize = 1_000;
+
+static QUERY_COUNTER: AtomicUsize = AtomicUsize::new(0);
+static SAMPLE_INTERVAL: OnceLock<usize> = OnceLock::new();
+
+fn sample_interval() -> usize {
+    *SAMPLE_INTERVAL.get_or_init(|| {
+        std::env::var("KIDDO_VERIFY_SAMPLE_INTERVAL")
+            .ok()
+            .and_then(|val| val.parse::<usize>().ok())
+            .filter(|&val| val > 0)
+            .unwrap_or(DEFAULT_SAMPLE_INTERVAL)
+    })
+}
+
+#[inline]
+fn should_verify() -> bool {
+    QUERY_COUNTER
+        .fetch_add(1, AtomicOrdering::Relaxed)
+        .is_multiple_of(sample_interval())
+}
+
+/// Measures distances in the same way as the leaves of the immutable float trees, summing the
+/// per-axis distances in [`LeafSliceFloat::Acc`] and only narrowing the total.
+///
+/// `f16` leaves accumulate in `f32`, so checking their results against distances summed in
+/// `f16` would report discrepancies that are only rounding differences.
+pub(crate) struct LeafMetric<D, T>(PhantomData<fn() -> (D, T)>);
+
+impl<D, T> LeafMetric<D, T> {
+    pub(crate) const fn new() -> Self {
+        LeafMetric(PhantomData)
+    }
+}
+
+impl<A, T, const K: usize, D> StatefulDistanceMetric<A, K> for LeafMetric<D, T>
+where
+    A: LeafSliceFloat<T> + Copy,
+    T: Content,
+    D: DistanceMetric<A, K>,
+{
+    fn dist(&self, a: &[A; K], b: &[A; K]) -> A {
+        let acc = (0..K).fold(<A as LeafSliceFloat<T>>::Acc::zero(), |acc, dim| {
+            accumulate_dist(
+                acc,
+                D::dist1(b[dim], a[dim]).widen(),
+                D::MAX_OF_AXES,
+                |a, b| a + b,
+            )
+        });
+
+        A::narrow(acc)
+    }
+
+    fn dist1(&self, a: A, b: A, _dim: usize) -> A {
+        D::dist1(a, b)
+    }
+
+    const MAX_OF_AXES: bool = D::MAX_OF_AXES;
+}
+
+fn collect_points<A, T, const K: usize, X>(tree: &X) -> Vec<(T, [A; K])>
+where
+    A: Copy + Default,
+    T: Content,
+    X: IterableTreeData<A, T, K>,
+{
+    TreeIter::new(tree, 0).collect()
+}
+
+fn cmp_dist<A: PartialOrd>(a: &A, b: &A) -> Ordering {
+    a.partial_cmp(b).unwrap_or(Ordering::Equal)
+}
+
+fn report<A: Debug, T: Debug, const K: usize>(
+    query_type: &str,
+    metric: &str,
+    query: &[A; K],
+    params: String,
+    discrepancy: String,
+    points: &[(T, [A; K])],
+) -> ! {
+    log::error!(
+        "kiddo verify_results: {} discrepancy. metric: {}, query: {:?}, {}. {}",
+        query_type,
+        metric,
+        query,
+        params,
+        discrepancy
+    );
+    if points.len() <= MAX_LOGGED_POINTS {
+        log::error!(
+            "kiddo verify_results: tree contents (item, point): {:?}",
+            points
+        );
+    } else {
+        log::debug!(
+            "kiddo verify_results: tree contents (item, point): {:?}",
+            points
+        );
+    }
+
+    panic!(
+        "kiddo verify_results: {} result disagrees with brute force: {}",
+        query_type, discrepancy
+    );
+}
+
+/// Checks a `nearest_one` result against a linear scan over `points`
+pub(crate) fn check_nearest_one<A, T, const K: usize, M>(
+    metric: &M,
+    points: &[(T, [A; K])],
+    query: &[A; K],
+    result: &NearestNeighbour<A, T>,
+) -> Result<(), String>
+where
+    A: Copy + PartialOrd + Debug,
+    T: Content,
+    M: StatefulDistanceMetric<A, K>,
+{
+    let expected = points
+        .iter()
+        .map(|(item, point)| (metric.dist(query, point), *item))
+        .filter(|(dist, _)| dist.partial_cmp(dist).is_some())
+        .min_by(|a, b| cmp_dist(&a.0, &b.0));
+
+    match expected {
+        Some((distance, item)) if distance != result.distance => Err(format!(
+            "expected nearest {:?} (item {:?}), got {:?} (item {:?})",
+            distance, item, result.distance, result.item
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Checks a result set that should consist of the `max_qty` nearest points that are
+/// within `radius` of `query` (a radius of `None` meaning unbounded).
+pub(crate) fn check_nearest_n_within<A, T, const K: usize, M>(
+    metric: &M,
+    points: &[(T, [A; K])],
+    query: &[A; K],
+    radius: Option<A>,
+    max_qty: usize,
+    result: &[NearestNeighbour<A, T>],
+) -> Result<(), String>
+where
+    A: Copy + PartialOrd + Debug,
+    T: Content,
+    M: StatefulDistanceMetric<A, K>,
+{
+    let mut expected_dists: Vec<A> = points
+        .iter()
+        .map(|(_, point)| metric.dist(query, point))
+        .filter(|dist| radius.is_none_or(|radius| *dist <= radius))
+        .collect();
+    expected_dists.sort_by(cmp_dist);
+
+    let strictly_within = match radius {
+        Some(radius) => expected_dists.iter().filter(|&&dist| dist < radius).count(),
+        None => expected_dists.len(),
+    };
+    let min_len = strictly_within.min(max_qty);
+    let max_len = expected_dists.len().min(max_qty);
+
+    if result.len() < min_len || result.len() > max_len {
+        return Err(format!(
+            "expected between {} and {} results, got {}",
+            min_len,
+            max_len,
+            result.len()
+        ));
+    }
+
+    let mut result_dists: Vec<A> = result.iter().map(|nn| nn.distance).collect();
+    result_dists.sort_by(cmp_dist);
+    expected_dists.truncate(result_dists.len());
+
+    if result_dists != expected_dists {
+        return Err(format!(
+            "expected distances {:?}, got {:?}",
+            expected_dists, result_dists
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks a `best_n_within` result: the `max_qty` lowest items within `radius` of `query`.
+pub(crate) fn check_best_n_within<A, T, const K: usize, M>(
+    metric: &M,
+    points: &[(T, [A; K])],
+    query: &[A; K],
+    radius: A,
+    max_qty: usize,
+    result: &[BestNeighbour<A, T>],
+) -> Result<(), String>
+where
+    A: Copy + PartialOrd + Debug,
+    T: Content,
+    M: StatefulDistanceMetric<A, K>,
+{
+    let mut strict: Vec<T> = vec![];
+    let mut loose: Vec<T> = vec![];
+    for (item, point) in points {
+        let dist = metric.dist(query, point);
+        if dist < radius {
+            strict.push(*item);
+        }
+        if dist <= radius {
+            loose.push(*item);
+        }
+    }
+    strict.sort();
+    loose.sort();
+
+    let mut result_items: Vec<T> = result.iter().map(|bn| bn.item).collect();
+    result_items.sort();
+
+    let matches = |candidates: &[T]| {
+        let expected = &candidates[..candidates.len().min(max_qty)];
+        expected == &result_items[..]
+    };
+
+    if matches(&strict) || matches(&loose) {
+        Ok(())
+    } else {
+        let expected = &strict[..strict.len().min(max_qty)];
+        Err(format!(
+            "expected items {:?}, got {:?}",
+            expected, result_items
+        ))
+    }
+}
+
+/// Verifies a sample of `nearest_one` queries
+pub(crate) fn verify_nearest_one<A, T, const K: usize, M, X>(
+    tree: &X,
+    metric: &M,
+    query: &[A; K],
+    result: &NearestNeighbour<A, T>,
+) where
+    A: Copy + Default + PartialOrd + Debug,
+    T: Content,
+    M: StatefulDistanceMetric<A, K>,
+    X: IterableTreeData<A, T, K>,
+{
+    if !should_verify() {
+        return;
+    }
+
+    let points = collect_points(tree);
+    if let Err(discrepancy) = check_nearest_one(metric, &points, query, result) {
+        report(
+            "nearest_one",
+            std::any::type_name::<M>(),
+            query,
+            String::new(),
+            discrepancy,
+            &points,
+        );
+    }
+}
+
+/// Verifies a sample of `nearest_n`, `nearest_n_within`, `within` and `within_unsorted` queries
+pub(crate) fn verify_nearest_n_within<A, T, const K: usize, M, X>(
+    tree: &X,
+    metric: &M,
+    query: &[A; K],
+    radius: Option<A>,
+    max_qty: usize,
+    result: &[NearestNeighbour<A, T>],
+) where
+    A: Copy + Default + PartialOrd + Debug,
+    T: Content,
+    M: StatefulDistanceMetric<A, K>,
+    X: IterableTreeData<A, T, K>,
+{
+    if !should_verify() {
+        return;
+    }
+
+    let points = collect_points(tree);
+    if let Err(discrepancy) =
+        check_nearest_n_within(metric, &points, query, radius, max_qty, result)
+    {
+        report(
+            "nearest_n_within",
+            std::any::type_name::<M>(),
+            query,
+            format!("radius: {:?}, max_qty: {}", radius, max_qty),
+            discrepancy,
+            &points,
+        );
+    }
+}
+
+/// Verifies a sample of `best_n_within` queries
+pub(crate) fn verify_best_n_within<A, T, const K: usize, M, X>(
+    tree: &X,
+    metric: &M,
+    query: &[A; K],
+    radius: A,
+    max_qty: usize,
+    result: &[BestNeighbour<A, T>],
+) where
+    A: Copy + Default + PartialOrd + Debug,
+    T: Content,
+    M: StatefulDistanceMetric<A, K>,
+    X: IterableTreeData<A, T, K>,
+{
+    if !should_verify() {
+        return;
+    }
+
+    let points = collect_points(tree);
+    if let Err(discrepancy) = check_best_n_within(metric, &points, query, radius, max_qty, result) {
+        report(
+            "best_n_within",
+            std::any::type_name::<M>(),
+            query,
+            format!("radius: {:?}, max_qty: {}", radius, max_qty),
+            discrepancy,
+            &points,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_best_n_within, check_nearest_n_within, check_nearest_one};
+    use crate::traits::StaticMetric;
+    use crate::{BestNeighbour, NearestNeighbour, SquaredEuclidean};
+
+    const METRIC: StaticMetric<SquaredEuclidean> = StaticMetric::new();
+
+    const POINTS: [(u32, [f64; 2]); 4] = [
+        (0, [0.0, 0.0]),
+        (1, [1.0, 0.0]),
+        (2, [0.0, 2.0]),
+        (3, [3.0, 0.0]),
+    ];
+
+    #[test]
+    fn check_nearest_one_accepts_correct_result() {
+        let result = NearestNeighbour {
+            distance: 0.0,
+            item: 1,
+        };
+        assert!(check_nearest_one(&METRIC, &POINTS, &[1.0, 0.0], &result).is_ok());
+    }
+
+    #[test]
+    fn check_nearest_one_rejects_incorrect_result() {
+        let result = NearestNeighbour {
+            distance: 4.0,
+            item: 2,
+        };
+        assert!(check_nearest_one(&METRIC, &POINTS, &[0.1, 0.0], &result).is_err());
+    }
+
+    #[test]
+    fn check_nearest_n_within_accepts_either_side_of_radius_boundary() {
+        let query = [0.0, 0.0];
+        let inclusive = vec![
+            NearestNeighbour {
+                distance: 0.0,
+                item: 0,
+            },
+            NearestNeighbour {
+                distance: 1.0,
+                item: 1,
+            },
+        ];
+        let exclusive = &inclusive[..1];
+
+        assert!(check_nearest_n_within(
+            &METRIC,
+            &POINTS,
+            &query,
+            Some(1.0),
+            usize::MAX,
+            &inclusive
+        )
+        .is_ok());
+        assert!(
+            check_nearest_n_within(&METRIC, &POINTS, &query, Some(1.0), usize::MAX, exclusive)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn check_nearest_n_within_rejects_missing_result() {
+        let result = vec![NearestNeighbour {
+            distance: 0.0,
+            item: 0,
+        }];
+
+        assert!(check_nearest_n_within(&METRIC, &POINTS, &[0.0, 0.0], None, 2, &result).is_err());
+    }
+
+    #[test]
+    fn check_best_n_within_requires_lowest_items() {
+        let good = vec![
+            BestNeighbour {
+                distance: 1.0,
+                item: 1,
+            },
+            BestNeighbour {
+                distance: 0.0,
+                item: 0,
+            },
+        ];
+        let bad = vec![
+            BestNeighbour {
+                distance: 4.0,
+                item: 2,
+            },
+            BestNeighbour {
+                distance: 0.0,
+                item: 0,
+            },
+        ];
+
+        assert!(check_best_n_within(&METRIC, &POINTS, &[0.0, 0.0], 5.0, 2, &good).is_ok());
+        assert!(check_best_n_within(&METRIC, &POINTS, &[0.0, 0.0], 5.0, 2, &bad).is_err());
+    }
+}
@@ -0,0 +1,532 @@
+//! A stable, versioned serde representation of [`KdTree`] and [`ImmutableKdTree`].
+//!
+//! Deriving `Serialize` / `Deserialize` on the trees themselves (as the `serde` feature does)
+//! ties the serialized form to their internal layout, so files written by one version of
+//! Kiddo may not be readable by the next. Wrapping a tree in [`Versioned`] instead writes it
+//! inside an envelope that records which version of the representation was used, along with
+//! the `K` and `B` that it was built with. Future versions of Kiddo will continue to be able
+//! to read every version of the representation, converting it to their own layout if need be.
+//!
+//! The envelope is an externally tagged enum (`{"V1": {...}}` in JSON, or a leading variant
+//! index in bincode), rather than a `"version"` field, so that it can be used with
+//! non-self-describing formats such as bincode.
+//!
+//! [`ImmutableKdTree`]s are written as `V1` unless they were built with a
+//! [`SplitPolicy`](crate::SplitPolicy) other than `RoundRobin`. Those are written as `V2`,
+//! which adds the dimension that each stem splits on.
+//!
+//! Requires the `serde` feature.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use kiddo::immutable::float::kdtree::ImmutableKdTree;
+//! use kiddo::versioned::Versioned;
+//! use kiddo::SquaredEuclidean;
+//!
+//! let points: Vec<[f64; 3]> = vec![[1.0, 2.0, 5.0], [2.0, 3.0, 6.0]];
+//! let tree: ImmutableKdTree<f64, u32, 3, 32> = ImmutableKdTree::new_from_slice(&points);
+//!
+//! let bytes = bincode::serialize(&Versioned(&tree)).unwrap();
+//!
+//! let Versioned(loaded): Versioned<ImmutableKdTree<f64, u32, 3, 32>> =
+//!     bincode::deserialize(&bytes).unwrap();
+//!
+//! assert_eq!(loaded.nearest_one::<SquaredEuclidean>(&[1.0, 2.0, 5.1]).item, 0);
+//! ```
+use std::borrow::Cow;
+
+use aligned_vec::{AVec, CACHELINE_ALIGN};
+use az::{Az, Cast};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::float::kdtree::{Axis, KdTree, LeafNode, StemNode};
+use crate::immutable::float::kdtree::{ImmutableKdTree, LeafExtent};
+use crate::traits::{is_stem_index, Content, Index};
+
+/// Wraps a tree (or a reference to one) so that it is serialized, or deserialized,
+/// using the stable versioned representation. See the [module docs](self) for details.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Versioned<T>(pub T);
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "A: Serialize, T: Serialize",
+    deserialize = "A: Deserialize<'de> + Clone, T: Deserialize<'de> + Clone"
+))]
+enum KdTreeEnvelope<'a, A: Clone, T: Clone> {
+    V1(KdTreeV1<'a, A, T>),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "A: Serialize, T: Serialize",
+    deserialize = "A: Deserialize<'de> + Clone, T: Deserialize<'de> + Clone"
+))]
+struct KdTreeV1<'a, A: Clone, T: Clone> {
+    k: u32,
+    b: u32,
+    size: T,
+    root: NodeRefV1,
+    stems: Vec<StemV1<A>>,
+    leaves: Vec<LeafV1<'a, A, T>>,
+    deferred_points: Cow<'a, [A]>,
+    deferred_items: Cow<'a, [T]>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+enum NodeRefV1 {
+    Stem(u64),
+    Leaf(u64),
+}
+
+#[derive(Serialize, Deserialize)]
+struct StemV1<A> {
+    split_val: A,
+    left: NodeRefV1,
+    right: NodeRefV1,
+}
+
+/// The points of a leaf, flattened to `K` values per point
+#[derive(Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "A: Serialize, T: Serialize",
+    deserialize = "A: Deserialize<'de> + Clone, T: Deserialize<'de> + Clone"
+))]
+struct LeafV1<'a, A: Clone, T: Clone> {
+    points: Vec<A>,
+    items: Cow<'a, [T]>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "A: Serialize, T: Serialize",
+    deserialize = "A: Deserialize<'de> + Clone, T: Deserialize<'de> + Clone"
+))]
+enum ImmutableKdTreeEnvelope<'a, A: Clone, T: Clone> {
+    V1(ImmutableKdTreeV1<'a, A, T>),
+    V2(ImmutableKdTreeV2<'a, A, T>),
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "A: Serialize, T: Serialize",
+    deserialize = "A: Deserialize<'de> + Clone, T: Deserialize<'de> + Clone"
+))]
+struct ImmutableKdTreeV1<'a, A: Clone, T: Clone> {
+    k: u32,
+    b: u32,
+    stem_ordering: StemOrderingV1,
+    max_stem_level: i32,
+    stems: Cow<'a, [A]>,
+    leaf_points: Vec<Cow<'a, [A]>>,
+    leaf_items: Cow<'a, [T]>,
+    leaf_extents: Cow<'a, [(u32, u32)]>,
+}
+
+/// A V1 tree, along with the dimension that each of its stems splits on
+#[derive(Serialize, Deserialize)]
+#[serde(bound(
+    serialize = "A: Serialize, T: Serialize",
+    deserialize = "A: Deserialize<'de> + Clone, T: Deserialize<'de> + Clone"
+))]
+struct ImmutableKdTreeV2<'a, A: Clone, T: Clone> {
+    tree: ImmutableKdTreeV1<'a, A, T>,
+    split_dims: Cow<'a, [u8]>,
+}
+
+/// The order in which the stems of an `ImmutableKdTree` are laid out, which depends
+/// upon whether the `modified_van_emde_boas` feature is enabled
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+enum StemOrderingV1 {
+    Eytzinger,
+    ModifiedVanEmdeBoas,
+}
+
+impl StemOrderingV1 {
+    #[cfg(not(feature = "modified_van_emde_boas"))]
+    const CURRENT: StemOrderingV1 = StemOrderingV1::Eytzinger;
+    #[cfg(feature = "modified_van_emde_boas")]
+    const CURRENT: StemOrderingV1 = StemOrderingV1::ModifiedVanEmdeBoas;
+}
+
+fn check_dimensions<E: Error>(
+    k: u32,
+    b: u32,
+    expected_k: usize,
+    expected_b: usize,
+) -> Result<(), E> {
+    if k as usize != expected_k || b as usize != expected_b {
+        return Err(E::custom(format!(
+            "tree was serialized with K = {k} and B = {b}, but K = {expected_k} and B = {expected_b} were expected"
+        )));
+    }
+
+    Ok(())
+}
+
+impl<A, T, const K: usize, const B: usize, IDX> Serialize for Versioned<&KdTree<A, T, K, B, IDX>>
+where
+    A: Axis + Serialize,
+    T: Content + Serialize,
+    IDX: Index<T = IDX>,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let tree = self.0;
+
+        let node_ref = |idx: IDX| {
+            if is_stem_index(idx) {
+                NodeRefV1::Stem(idx.az::<usize>() as u64)
+            } else {
+                NodeRefV1::Leaf((idx - IDX::leaf_offset()).az::<usize>() as u64)
+            }
+        };
+
+        KdTreeEnvelope::V1(KdTreeV1 {
+            k: K as u32,
+            b: B as u32,
+            size: tree.size,
+            root: node_ref(tree.root_index),
+            stems: tree
+                .stems
+                .iter()
+                .map(|stem| StemV1 {
+                    split_val: stem.split_val,
+                    left: node_ref(stem.left),
+                    right: node_ref(stem.right),
+                })
+                .collect(),
+            leaves: tree
+                .leaves
+                .iter()
+                .map(|leaf| {
+                    let size = leaf.size.az::<usize>();
+                    LeafV1 {
+                        points: leaf.content_points[..size]
+                            .iter()
+                            .flatten()
+                            .copied()
+                            .collect(),
+                        items: Cow::Borrowed(&leaf.content_items[..size]),
+                    }
+                })
+                .collect(),
+            deferred_points: Cow::Borrowed(&tree.deferred_points),
+            deferred_items: Cow::Borrowed(&tree.deferred_items),
+        })
+        .serialize(serializer)
+    }
+}
+
+impl<'de, A, T, const K: usize, const B: usize, IDX> Deserialize<'de>
+    for Versioned<KdTree<A, T, K, B, IDX>>
+where
+    A: Axis + Deserialize<'de>,
+    T: Content + Deserialize<'de>,
+    IDX: Index<T = IDX>,
+    usize: Cast<IDX>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let KdTreeEnvelope::V1(repr) = KdTreeEnvelope::<A, T>::deserialize(deserializer)?;
+        check_dimensions(repr.k, repr.b, K, B)?;
+
+        let stem_count = repr.stems.len();
+        let leaf_count = repr.leaves.len();
+        if leaf_count == 0 || leaf_count > IDX::capacity_with_bucket_size(1) {
+            return Err(Error::custom(format!("invalid leaf count {leaf_count}")));
+        }
+        if stem_count > IDX::leaf_offset().az::<usize>() {
+            return Err(Error::custom(format!("invalid stem count {stem_count}")));
+        }
+
+        let node_idx = |node_ref: NodeRefV1| -> Result<IDX, D::Error> {
+            match node_ref {
+                NodeRefV1::Stem(idx) if (idx as usize) < stem_count => {
+                    Ok((idx as usize).az::<IDX>())
+                }
+                NodeRefV1::Leaf(idx) if (idx as usize) < leaf_count => {
+                    Ok(IDX::leaf_offset() + (idx as usize).az::<IDX>())
+                }
+                _ => Err(Error::custom("stem refers to a node that does not exist")),
+            }
+        };
+
+        let stems = repr
+            .stems
+            .into_iter()
+            .map(|stem| {
+                Ok(StemNode {
+                    left: node_idx(stem.left)?,
+                    right: node_idx(stem.right)?,
+                    split_val: stem.split_val,
+                })
+            })
+            .collect::<Result<Vec<_>, D::Error>>()?;
+
+        let leaves = repr
+            .leaves
+            .into_iter()
+            .map(|leaf_repr| {
+                let size = leaf_repr.items.len();
+                if size > B || leaf_repr.points.len() != size * K {
+                    return Err(Error::custom("leaf has an invalid number of points"));
+                }
+
+                let mut leaf = LeafNode::new();
+                leaf.content_items[..size].copy_from_slice(&leaf_repr.items);
+                leaf.content_points[..size]
+                    .iter_mut()
+                    .zip(leaf_repr.points.chunks_exact(K))
+                    .for_each(|(point, values)| point.copy_from_slice(values));
+                leaf.size = size.az::<IDX>();
+
+                Ok(leaf)
+            })
+            .collect::<Result<Vec<_>, D::Error>>()?;
+
+        if repr.deferred_points.len() != repr.deferred_items.len() * K {
+            return Err(Error::custom("invalid number of deferred points"));
+        }
+
+        Ok(Versioned(KdTree {
+            leaves,
+            stems,
+            root_index: node_idx(repr.root)?,
+            size: repr.size,
+            deferred_points: repr.deferred_points.into_owned(),
+            deferred_items: repr.deferred_items.into_owned(),
+        }))
+    }
+}
+
+impl<A, T, const K: usize, const B: usize> Serialize for Versioned<&ImmutableKdTree<A, T, K, B>>
+where
+    A: Axis + Serialize,
+    T: Content + Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let tree = self.0;
+
+        let repr = ImmutableKdTreeV1 {
+            k: K as u32,
+            b: B as u32,
+            stem_ordering: StemOrderingV1::CURRENT,
+            max_stem_level: tree.max_stem_level,
+            stems: Cow::Borrowed(tree.stems.as_slice()),
+            leaf_points: tree
+                .leaf_points
+                .iter()
+                .map(|points| Cow::Borrowed(points.as_slice()))
+                .collect(),
+            leaf_items: Cow::Borrowed(&tree.leaf_items),
+            leaf_extents: extents_to_v1(&tree.leaf_extents).ok_or_else(|| {
+                <S::Error as serde::ser::Error>::custom(
+                    "leaf extents do not fit in the u32s of the V1 format",
+                )
+            })?,
+        };
+
+        // only use V2 when it is needed, so that older versions of Kiddo can still read
+        // round-robin trees
+        if tree.split_dims.is_empty() {
+            ImmutableKdTreeEnvelope::V1(repr)
+        } else {
+            ImmutableKdTreeEnvelope::V2(ImmutableKdTreeV2 {
+                tree: repr,
+                split_dims: Cow::Borrowed(&tree.split_dims),
+            })
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, A, T, const K: usize, const B: usize> Deserialize<'de>
+    for Versioned<ImmutableKdTree<A, T, K, B>>
+where
+    A: Axis + Deserialize<'de>,
+    T: Content + Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (repr, split_dims) = match ImmutableKdTreeEnvelope::<A, T>::deserialize(deserializer)? {
+            ImmutableKdTreeEnvelope::V1(repr) => (repr, Vec::new()),
+            ImmutableKdTreeEnvelope::V2(ImmutableKdTreeV2 { tree, split_dims }) => {
+                (tree, split_dims.into_owned())
+            }
+        };
+        check_dimensions(repr.k, repr.b, K, B)?;
+
+        if repr.stem_ordering != StemOrderingV1::CURRENT {
+            return Err(Error::custom(
+                "tree was serialized with a different stem ordering. \
+                 Check that the modified_van_emde_boas feature matches",
+            ));
+        }
+
+        let size = repr.leaf_items.len();
+        if repr.leaf_points.len() != K || repr.leaf_points.iter().any(|points| points.len() != size)
+        {
+            return Err(Error::custom("leaf points do not match leaf items"));
+        }
+        if repr
+            .leaf_extents
+            .iter()
+            .any(|&(start, end)| start > end || end as usize > size)
+        {
+            return Err(Error::custom("leaf extent is out of bounds"));
+        }
+        if !split_dims.is_empty()
+            && (split_dims.len() != repr.stems.len()
+                || split_dims.iter().any(|&dim| dim as usize >= K))
+        {
+            return Err(Error::custom("split dimensions do not match the stems"));
+        }
+
+        let live_count: usize = repr
+            .leaf_extents
+            .iter()
+            .map(|&(start, end)| (end - start) as usize)
+            .sum();
+        let mut leaf_points = repr.leaf_points.into_iter().map(Cow::into_owned);
+
+        Ok(Versioned(ImmutableKdTree {
+            stems: AVec::from_slice(CACHELINE_ALIGN, &repr.stems),
+            leaf_points: array_init::array_init(|_| leaf_points.next().unwrap()),
+            leaf_items: repr.leaf_items.into_owned(),
+            leaf_extents: repr
+                .leaf_extents
+                .iter()
+                .map(|&(start, end)| (start as LeafExtent, end as LeafExtent))
+                .collect(),
+            max_stem_level: repr.max_stem_level,
+            tombstone_count: size.saturating_sub(live_count),
+            split_dims,
+        }))
+    }
+}
+
+/// The V1 format stores leaf extents as `u32`s, whatever the [`LeafExtent`] type is
+#[cfg(not(feature = "u64_leaf_extents"))]
+fn extents_to_v1(extents: &[(LeafExtent, LeafExtent)]) -> Option<Cow<'_, [(u32, u32)]>> {
+    Some(Cow::Borrowed(extents))
+}
+
+/// The V1 format stores leaf extents as `u32`s, whatever the [`LeafExtent`] type is
+#[cfg(feature = "u64_leaf_extents")]
+fn extents_to_v1(extents: &[(LeafExtent, LeafExtent)]) -> Option<Cow<'_, [(u32, u32)]>> {
+    extents
+        .iter()
+        .map(|&(start, end)| Some((u32::try_from(start).ok()?, u32::try_from(end).ok()?)))
+        .collect::<Option<Vec<_>>>()
+        .map(Cow::Owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Versioned;
+    use crate::float::distance::SquaredEuclidean;
+    use crate::float::kdtree::KdTree;
+    use crate::immutable::float::kdtree::ImmutableKdTree;
+    use crate::split_policy::SplitPolicy;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn kdtree_round_trips() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(11);
+
+        let mut tree: KdTree<f32, u32, 3, 8, u16> = KdTree::new();
+        (0..500u32).for_each(|idx| tree.add(&rng.gen::<[f32; 3]>(), idx));
+        tree.add_deferred((500..510u32).map(|idx| (rng.gen::<[f32; 3]>(), idx)));
+
+        let bytes = bincode::serialize(&Versioned(&tree)).unwrap();
+        let Versioned(from_bincode): Versioned<KdTree<f32, u32, 3, 8, u16>> =
+            bincode::deserialize(&bytes).unwrap();
+
+        let json = serde_json::to_string(&Versioned(&tree)).unwrap();
+        let Versioned(from_json): Versioned<KdTree<f32, u32, 3, 8, u16>> =
+            serde_json::from_str(&json).unwrap();
+
+        let mut expected: Vec<_> = tree.iter().collect();
+        expected.sort_by_key(|&(item, _)| item);
+
+        for mut loaded in [from_bincode, from_json] {
+            assert_eq!(loaded.size(), tree.size());
+            let mut actual: Vec<_> = loaded.iter().collect();
+            actual.sort_by_key(|&(item, _)| item);
+            assert_eq!(actual, expected);
+
+            for _ in 0..20 {
+                let query = rng.gen::<[f32; 3]>();
+                assert_eq!(
+                    loaded.nearest_n::<SquaredEuclidean>(&query, 5),
+                    tree.nearest_n::<SquaredEuclidean>(&query, 5)
+                );
+            }
+
+            loaded.finalize_splits();
+            assert_eq!(loaded.size(), 510);
+        }
+    }
+
+    #[test]
+    fn immutable_kdtree_round_trips() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(12);
+
+        let points: Vec<[f64; 4]> = (0..2_000).map(|_| rng.gen()).collect();
+        let tree: ImmutableKdTree<f64, u32, 4, 32> = ImmutableKdTree::new_from_slice(&points);
+
+        let bytes = bincode::serialize(&Versioned(&tree)).unwrap();
+        let Versioned(loaded): Versioned<ImmutableKdTree<f64, u32, 4, 32>> =
+            bincode::deserialize(&bytes).unwrap();
+
+        assert_eq!(loaded, tree);
+    }
+
+    #[test]
+    fn immutable_kdtree_built_with_max_spread_round_trips_as_v2() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(13);
+
+        let points: Vec<[f64; 3]> = (0..2_000)
+            .map(|_| [rng.gen_range(0.0..100.0), rng.gen_range(0.0..1.0), 0.0])
+            .collect();
+        let tree: ImmutableKdTree<f64, u32, 3, 32> =
+            ImmutableKdTree::new_from_slice_with_policy(&points, SplitPolicy::MaxSpread);
+
+        let bytes = bincode::serialize(&Versioned(&tree)).unwrap();
+        // the variant index of V2
+        assert_eq!(&bytes[..4], &[1, 0, 0, 0]);
+        let Versioned(loaded): Versioned<ImmutableKdTree<f64, u32, 3, 32>> =
+            bincode::deserialize(&bytes).unwrap();
+        assert_eq!(loaded, tree);
+
+        let round_robin: ImmutableKdTree<f64, u32, 3, 32> =
+            ImmutableKdTree::new_from_slice(&points);
+        let bytes = bincode::serialize(&Versioned(&round_robin)).unwrap();
+        assert_eq!(&bytes[..4], &[0, 0, 0, 0]);
+    }
+
+    #[cfg(feature = "u64_leaf_extents")]
+    #[test]
+    fn rejects_leaf_extents_too_large_for_the_v1_format() {
+        let points: Vec<[f64; 4]> = vec![[0.0; 4], [1.0; 4]];
+        let mut tree: ImmutableKdTree<f64, u32, 4, 32> = ImmutableKdTree::new_from_slice(&points);
+        tree.leaf_extents[0].1 = u64::from(u32::MAX) + 1;
+
+        assert!(bincode::serialize(&Versioned(&tree)).is_err());
+    }
+
+    #[test]
+    fn rejects_trees_with_different_dimensions() {
+        let points: Vec<[f64; 4]> = vec![[0.0; 4], [1.0; 4]];
+        let tree: ImmutableKdTree<f64, u32, 4, 32> = ImmutableKdTree::new_from_slice(&points);
+        let bytes = bincode::serialize(&Versioned(&tree)).unwrap();
+
+        let result: Result<Versioned<ImmutableKdTree<f64, u32, 3, 32>>, _> =
+            bincode::deserialize(&bytes);
+        assert!(result.unwrap_err().to_string().contains("K = 4 and B = 32"));
+
+        let result: Result<Versioned<ImmutableKdTree<f64, u32, 4, 64>>, _> =
+            bincode::deserialize(&bytes);
+        assert!(result.is_err());
+    }
+}
@@ -1,13 +1,14 @@
 //! Iterator object returned by within_unsorted_iter
+use alloc::boxed::Box;
+
 use crate::nearest_neighbour::NearestNeighbour;
-use generator::Generator;
 
 /// Iterator object returned by within_unsorted_iter
-pub struct WithinUnsortedIter<'a, A, T>(Generator<'a, (), NearestNeighbour<A, T>>);
+pub struct WithinUnsortedIter<'a, A, T>(Box<dyn Iterator<Item = NearestNeighbour<A, T>> + 'a>);
 
 impl<'a, A, T> WithinUnsortedIter<'a, A, T> {
-    pub(crate) fn new(gen: Generator<'a, (), NearestNeighbour<A, T>>) -> Self {
-        WithinUnsortedIter(gen)
+    pub(crate) fn new(iter: impl Iterator<Item = NearestNeighbour<A, T>> + 'a) -> Self {
+        WithinUnsortedIter(Box::new(iter))
     }
 }
 